@@ -0,0 +1,143 @@
+#![no_std]
+use soroban_sdk::{
+    contract, contracterror, contractimpl, contracttype, token, xdr::ToXdr, Address, BytesN, Env,
+    Symbol,
+};
+
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum FactoryError {
+    AlreadyInitialized = 1,
+    NotInitialized = 2,
+    InvalidAmount = 3,
+    InvalidSafetyDeposit = 4,
+    InvalidTimelock = 5,
+    InvalidFactory = 6,
+    EscrowAlreadyExists = 7,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub enum DataKey {
+    EscrowWasmHash,
+    Escrow(BytesN<32>),
+}
+
+/// Swap parameters the escrow address commits to; mirrors the escrow contract's type
+#[derive(Clone, Debug, PartialEq)]
+#[contracttype]
+pub struct Immutables {
+    pub factory: Address,
+    pub sender: Address,
+    pub receiver: Address,
+    pub token_address: Address,
+    pub amount: i128,
+    pub hashlock: BytesN<32>,
+    pub timelock: u64,
+    pub safety_deposit: i128,
+}
+
+#[contract]
+pub struct EscrowFactory;
+
+#[contractimpl]
+impl EscrowFactory {
+    /// Sets the escrow Wasm hash used for every deployment
+    pub fn initialize(env: Env, escrow_wasm_hash: BytesN<32>) -> Result<(), FactoryError> {
+        if env.storage().instance().has(&DataKey::EscrowWasmHash) {
+            return Err(FactoryError::AlreadyInitialized);
+        }
+
+        env.storage()
+            .instance()
+            .set(&DataKey::EscrowWasmHash, &escrow_wasm_hash);
+
+        Ok(())
+    }
+
+    /// Deploys a dedicated escrow for the swap and funds it from the sender
+    pub fn create_escrow(env: Env, immutables: Immutables) -> Result<Address, FactoryError> {
+        // Authorization check
+        immutables.sender.require_auth();
+
+        // Input validation
+        if immutables.amount <= 0 {
+            return Err(FactoryError::InvalidAmount);
+        }
+
+        if immutables.safety_deposit < 0 {
+            return Err(FactoryError::InvalidSafetyDeposit);
+        }
+
+        if immutables.timelock <= env.ledger().timestamp() {
+            return Err(FactoryError::InvalidTimelock);
+        }
+
+        // Escrows validate their immutables against this factory's address
+        if immutables.factory != env.current_contract_address() {
+            return Err(FactoryError::InvalidFactory);
+        }
+
+        let escrow_wasm_hash: BytesN<32> = env
+            .storage()
+            .instance()
+            .get(&DataKey::EscrowWasmHash)
+            .ok_or(FactoryError::NotInitialized)?;
+
+        // Check if escrow already exists
+        let salt = compute_salt(&env, &immutables);
+        if env
+            .storage()
+            .persistent()
+            .has(&DataKey::Escrow(salt.clone()))
+        {
+            return Err(FactoryError::EscrowAlreadyExists);
+        }
+
+        // Deploy the escrow at its deterministic address
+        let escrow_address = env
+            .deployer()
+            .with_current_contract(salt.clone())
+            .deploy(escrow_wasm_hash);
+        env.storage()
+            .persistent()
+            .set(&DataKey::Escrow(salt), &escrow_address);
+
+        // Fund the escrow with the amount and safety deposit
+        let token_client = token::Client::new(&env, &immutables.token_address);
+        token_client.transfer(
+            &immutables.sender,
+            &escrow_address,
+            &(immutables.amount + immutables.safety_deposit),
+        );
+
+        env.events().publish(
+            (Symbol::new(&env, "EscrowCreated"), escrow_address.clone()),
+            immutables,
+        );
+
+        Ok(escrow_address)
+    }
+
+    /// Predicts the escrow address for a swap without deploying it
+    pub fn escrow_address(env: Env, immutables: Immutables) -> Address {
+        env.deployer()
+            .with_current_contract(compute_salt(&env, &immutables))
+            .deployed_address()
+    }
+
+    /// Checks if the escrow for a swap was deployed by this factory
+    pub fn escrow_exists(env: Env, immutables: Immutables) -> bool {
+        env.storage()
+            .persistent()
+            .has(&DataKey::Escrow(compute_salt(&env, &immutables)))
+    }
+}
+
+/// Computes the deployment salt committing to the immutables
+fn compute_salt(env: &Env, immutables: &Immutables) -> BytesN<32> {
+    env.crypto().sha256(&immutables.clone().to_xdr(env))
+}
+
+mod test;
@@ -0,0 +1,138 @@
+#![cfg(test)]
+
+use super::*;
+use soroban_sdk::{
+    testutils::{Address as _, Ledger},
+    token, Address, Bytes, BytesN, Env, FromVal, IntoVal, Val,
+};
+use stellar_htlc_escrow::{EscrowContract, EscrowContractClient, EscrowStatus};
+
+const AMOUNT: i128 = 1_000_000_000; // 100 XLM (7 decimals)
+const SAFETY_DEPOSIT: i128 = 100_000_000; // 10 XLM
+const TIMELOCK_SECS: u64 = 3_600; // 1 hour
+
+fn setup() -> (Env, Immutables, BytesN<32>, EscrowFactoryClient<'static>) {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let factory_address = env.register_contract(None, EscrowFactory);
+    let client = EscrowFactoryClient::new(&env, &factory_address);
+
+    // Native test contracts are deployed from the empty Wasm
+    let escrow_wasm_hash = env.deployer().upload_contract_wasm(Bytes::new(&env));
+    client.initialize(&escrow_wasm_hash);
+
+    let sender = Address::generate(&env);
+    let token_address = env.register_stellar_asset_contract(sender.clone());
+    token::StellarAssetClient::new(&env, &token_address).mint(&sender, &(AMOUNT + SAFETY_DEPOSIT));
+
+    let preimage = BytesN::from_array(&env, &[42u8; 32]);
+    let immutables = Immutables {
+        factory: factory_address,
+        sender,
+        receiver: Address::generate(&env),
+        token_address,
+        amount: AMOUNT,
+        hashlock: env.crypto().sha256(&preimage.clone().into()),
+        timelock: env.ledger().timestamp() + TIMELOCK_SECS,
+        safety_deposit: SAFETY_DEPOSIT,
+    };
+
+    (env, immutables, preimage, client)
+}
+
+/// Attaches the native escrow implementation to a deployed escrow address
+fn escrow_client(env: &Env, escrow_address: &Address) -> EscrowContractClient<'static> {
+    env.register_contract(Some(escrow_address), EscrowContract);
+    EscrowContractClient::new(env, escrow_address)
+}
+
+fn escrow_immutables(env: &Env, immutables: &Immutables) -> stellar_htlc_escrow::Immutables {
+    let val: Val = immutables.into_val(env);
+    stellar_htlc_escrow::Immutables::from_val(env, &val)
+}
+
+#[test]
+fn create_escrow_at_predicted_address() {
+    let (env, immutables, _, client) = setup();
+
+    let predicted = client.escrow_address(&immutables);
+    let escrow_address = client.create_escrow(&immutables);
+    assert_eq!(escrow_address, predicted);
+    assert!(client.escrow_exists(&immutables));
+
+    // Funds are isolated in the per-swap escrow
+    let token_client = token::Client::new(&env, &immutables.token_address);
+    assert_eq!(
+        token_client.balance(&escrow_address),
+        AMOUNT + SAFETY_DEPOSIT
+    );
+    assert_eq!(token_client.balance(&client.address), 0);
+}
+
+#[test]
+fn escrow_withdraw_after_factory_deploy() {
+    let (env, immutables, preimage, client) = setup();
+
+    let escrow_address = client.create_escrow(&immutables);
+    let escrow = escrow_client(&env, &escrow_address);
+    escrow.withdraw(&escrow_immutables(&env, &immutables), &preimage);
+    assert_eq!(escrow.get_status(), EscrowStatus::Withdrawn);
+
+    let token_client = token::Client::new(&env, &immutables.token_address);
+    assert_eq!(token_client.balance(&immutables.receiver), AMOUNT);
+    assert_eq!(token_client.balance(&immutables.sender), SAFETY_DEPOSIT);
+}
+
+#[test]
+fn escrow_refund_after_factory_deploy() {
+    let (env, immutables, _, client) = setup();
+
+    let escrow_address = client.create_escrow(&immutables);
+    let escrow = escrow_client(&env, &escrow_address);
+    env.ledger().with_mut(|l| l.timestamp = immutables.timelock);
+    escrow.refund(&escrow_immutables(&env, &immutables));
+
+    let token_client = token::Client::new(&env, &immutables.token_address);
+    assert_eq!(
+        token_client.balance(&immutables.sender),
+        AMOUNT + SAFETY_DEPOSIT
+    );
+}
+
+#[test]
+fn duplicate_escrow() {
+    let (env, immutables, _, client) = setup();
+    token::StellarAssetClient::new(&env, &immutables.token_address)
+        .mint(&immutables.sender, &(AMOUNT + SAFETY_DEPOSIT));
+
+    client.create_escrow(&immutables);
+    let result = client.try_create_escrow(&immutables);
+    assert_eq!(result, Err(Ok(FactoryError::EscrowAlreadyExists)));
+}
+
+#[test]
+fn create_escrow_wrong_factory() {
+    let (env, mut immutables, _, client) = setup();
+
+    immutables.factory = Address::generate(&env);
+    let result = client.try_create_escrow(&immutables);
+    assert_eq!(result, Err(Ok(FactoryError::InvalidFactory)));
+}
+
+#[test]
+fn create_escrow_amount_zero() {
+    let (_, mut immutables, _, client) = setup();
+
+    immutables.amount = 0;
+    let result = client.try_create_escrow(&immutables);
+    assert_eq!(result, Err(Ok(FactoryError::InvalidAmount)));
+}
+
+#[test]
+fn initialize_twice() {
+    let (env, _, _, client) = setup();
+
+    let result = client.try_initialize(&BytesN::from_array(&env, &[0u8; 32]));
+    assert_eq!(result, Err(Ok(FactoryError::AlreadyInitialized)));
+}
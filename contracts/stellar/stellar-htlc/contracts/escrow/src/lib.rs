@@ -0,0 +1,172 @@
+#![no_std]
+use soroban_sdk::{
+    contract, contracterror, contractimpl, contracttype, token, xdr::ToXdr, Address, Bytes, BytesN,
+    Env, Symbol,
+};
+
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum EscrowError {
+    InvalidImmutables = 1,
+    AlreadyWithdrawn = 2,
+    AlreadyRefunded = 3,
+    TimelockExpired = 4,
+    TimelockNotExpired = 5,
+    InvalidPreimage = 6,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub enum DataKey {
+    Status,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[contracttype]
+pub enum EscrowStatus {
+    Active,
+    Withdrawn,
+    Refunded,
+}
+
+/// Swap parameters the escrow address commits to
+#[derive(Clone, Debug, PartialEq)]
+#[contracttype]
+pub struct Immutables {
+    pub factory: Address,
+    pub sender: Address,
+    pub receiver: Address,
+    pub token_address: Address,
+    pub amount: i128,
+    pub hashlock: BytesN<32>,
+    pub timelock: u64,
+    pub safety_deposit: i128,
+}
+
+#[contract]
+pub struct EscrowContract;
+
+#[contractimpl]
+impl EscrowContract {
+    /// Withdraws funds to the receiver by revealing the preimage
+    pub fn withdraw(
+        env: Env,
+        immutables: Immutables,
+        preimage: BytesN<32>,
+    ) -> Result<(), EscrowError> {
+        Self::validate_immutables(&env, &immutables)?;
+
+        // Authorization check - only receiver can withdraw
+        immutables.receiver.require_auth();
+
+        // Status check
+        Self::ensure_active(&env)?;
+
+        // Timelock check - must withdraw before expiry
+        if env.ledger().timestamp() >= immutables.timelock {
+            return Err(EscrowError::TimelockExpired);
+        }
+
+        // Validate preimage against hashlock
+        let preimage_bytes: Bytes = preimage.clone().into();
+        if env.crypto().sha256(&preimage_bytes) != immutables.hashlock {
+            return Err(EscrowError::InvalidPreimage);
+        }
+
+        // Record the final state before moving funds
+        env.storage()
+            .instance()
+            .set(&DataKey::Status, &EscrowStatus::Withdrawn);
+
+        // Transfer tokens to receiver and return safety deposit to sender
+        let token_client = token::Client::new(&env, &immutables.token_address);
+        token_client.transfer(
+            &env.current_contract_address(),
+            &immutables.receiver,
+            &immutables.amount,
+        );
+        if immutables.safety_deposit > 0 {
+            token_client.transfer(
+                &env.current_contract_address(),
+                &immutables.sender,
+                &immutables.safety_deposit,
+            );
+        }
+
+        env.events()
+            .publish((Symbol::new(&env, "EscrowWithdraw"),), preimage);
+
+        Ok(())
+    }
+
+    /// Refunds funds to the sender after timelock expiry
+    pub fn refund(env: Env, immutables: Immutables) -> Result<(), EscrowError> {
+        Self::validate_immutables(&env, &immutables)?;
+
+        // Authorization check - only sender can refund
+        immutables.sender.require_auth();
+
+        // Status check
+        Self::ensure_active(&env)?;
+
+        // Timelock check - can only refund after expiry
+        if env.ledger().timestamp() < immutables.timelock {
+            return Err(EscrowError::TimelockNotExpired);
+        }
+
+        // Record the final state before moving funds
+        env.storage()
+            .instance()
+            .set(&DataKey::Status, &EscrowStatus::Refunded);
+
+        // Transfer tokens and safety deposit back to sender
+        let token_client = token::Client::new(&env, &immutables.token_address);
+        token_client.transfer(
+            &env.current_contract_address(),
+            &immutables.sender,
+            &(immutables.amount + immutables.safety_deposit),
+        );
+
+        env.events()
+            .publish((Symbol::new(&env, "EscrowRefund"),), immutables.hashlock);
+
+        Ok(())
+    }
+
+    /// Gets escrow status
+    pub fn get_status(env: Env) -> EscrowStatus {
+        env.storage()
+            .instance()
+            .get(&DataKey::Status)
+            .unwrap_or(EscrowStatus::Active)
+    }
+
+    // Private helper functions
+    fn ensure_active(env: &Env) -> Result<(), EscrowError> {
+        match Self::get_status(env.clone()) {
+            EscrowStatus::Active => Ok(()),
+            EscrowStatus::Withdrawn => Err(EscrowError::AlreadyWithdrawn),
+            EscrowStatus::Refunded => Err(EscrowError::AlreadyRefunded),
+        }
+    }
+
+    /// Checks the immutables hash to this escrow's deterministic address
+    fn validate_immutables(env: &Env, immutables: &Immutables) -> Result<(), EscrowError> {
+        let expected_address = env
+            .deployer()
+            .with_address(immutables.factory.clone(), compute_salt(env, immutables))
+            .deployed_address();
+        if expected_address != env.current_contract_address() {
+            return Err(EscrowError::InvalidImmutables);
+        }
+        Ok(())
+    }
+}
+
+/// Computes the deployment salt the factory derives from the immutables
+pub fn compute_salt(env: &Env, immutables: &Immutables) -> BytesN<32> {
+    env.crypto().sha256(&immutables.clone().to_xdr(env))
+}
+
+mod test;
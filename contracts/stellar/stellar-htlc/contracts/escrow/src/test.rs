@@ -0,0 +1,107 @@
+#![cfg(test)]
+
+use super::*;
+use soroban_sdk::{
+    testutils::{Address as _, Ledger},
+    token, Address, BytesN, Env,
+};
+
+const AMOUNT: i128 = 1_000_000_000; // 100 XLM (7 decimals)
+const SAFETY_DEPOSIT: i128 = 100_000_000; // 10 XLM
+const TIMELOCK_SECS: u64 = 3_600; // 1 hour
+
+/// Registers the escrow at the address a factory would deploy it to and funds it
+fn setup() -> (Env, Immutables, BytesN<32>, EscrowContractClient<'static>) {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let sender = Address::generate(&env);
+    let token_address = env.register_stellar_asset_contract(sender.clone());
+    let preimage = BytesN::from_array(&env, &[42u8; 32]);
+
+    let immutables = Immutables {
+        factory: Address::generate(&env),
+        sender,
+        receiver: Address::generate(&env),
+        token_address: token_address.clone(),
+        amount: AMOUNT,
+        hashlock: env.crypto().sha256(&preimage.clone().into()),
+        timelock: env.ledger().timestamp() + TIMELOCK_SECS,
+        safety_deposit: SAFETY_DEPOSIT,
+    };
+
+    let escrow_address = env
+        .deployer()
+        .with_address(immutables.factory.clone(), compute_salt(&env, &immutables))
+        .deployed_address();
+    env.register_contract(Some(&escrow_address), EscrowContract);
+    token::StellarAssetClient::new(&env, &token_address)
+        .mint(&escrow_address, &(AMOUNT + SAFETY_DEPOSIT));
+
+    let client = EscrowContractClient::new(&env, &escrow_address);
+    (env, immutables, preimage, client)
+}
+
+#[test]
+fn withdraw_success() {
+    let (env, immutables, preimage, client) = setup();
+
+    client.withdraw(&immutables, &preimage);
+    assert_eq!(client.get_status(), EscrowStatus::Withdrawn);
+
+    let token_client = token::Client::new(&env, &immutables.token_address);
+    assert_eq!(token_client.balance(&immutables.receiver), AMOUNT);
+    assert_eq!(token_client.balance(&immutables.sender), SAFETY_DEPOSIT);
+    assert_eq!(token_client.balance(&client.address), 0);
+}
+
+#[test]
+fn refund_success() {
+    let (env, immutables, _, client) = setup();
+
+    env.ledger().with_mut(|l| l.timestamp = immutables.timelock);
+    client.refund(&immutables);
+    assert_eq!(client.get_status(), EscrowStatus::Refunded);
+
+    let token_client = token::Client::new(&env, &immutables.token_address);
+    assert_eq!(
+        token_client.balance(&immutables.sender),
+        AMOUNT + SAFETY_DEPOSIT
+    );
+}
+
+#[test]
+fn mismatched_immutables() {
+    let (_, immutables, preimage, client) = setup();
+
+    let mut tampered = immutables.clone();
+    tampered.amount += 1;
+    let result = client.try_withdraw(&tampered, &preimage);
+    assert_eq!(result, Err(Ok(EscrowError::InvalidImmutables)));
+}
+
+#[test]
+fn wrong_preimage() {
+    let (env, immutables, _, client) = setup();
+
+    let bad_preimage = BytesN::from_array(&env, &[1u8; 32]);
+    let result = client.try_withdraw(&immutables, &bad_preimage);
+    assert_eq!(result, Err(Ok(EscrowError::InvalidPreimage)));
+}
+
+#[test]
+fn refund_before_timelock() {
+    let (_, immutables, _, client) = setup();
+
+    let result = client.try_refund(&immutables);
+    assert_eq!(result, Err(Ok(EscrowError::TimelockNotExpired)));
+}
+
+#[test]
+fn double_withdraw() {
+    let (_, immutables, preimage, client) = setup();
+
+    client.withdraw(&immutables, &preimage);
+    let result = client.try_withdraw(&immutables, &preimage);
+    assert_eq!(result, Err(Ok(EscrowError::AlreadyWithdrawn)));
+}
@@ -0,0 +1,45 @@
+//! Derives contract IDs from two arbitrary sets of immutables: the packing is fixed-width,
+//! so IDs may only collide when the immutables are identical.
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use soroban_sdk::{testutils::Address as _, Address, BytesN};
+use stellar_htlc::testutils::*;
+
+#[derive(Arbitrary, Debug, PartialEq)]
+struct Immutables {
+    sender: u8,
+    receiver: u8,
+    amount: i128,
+    hashlock: [u8; 32],
+    timelock: u64,
+    timestamp: u64,
+}
+
+/// Distinct parties the fuzzer picks senders and receivers from
+const PARTIES: usize = 4;
+
+fuzz_target!(|input: (Immutables, Immutables)| {
+    let (env, _, _, _, client) = setup_in(new_env_without_snapshot());
+    let parties: Vec<Address> = (0..PARTIES).map(|_| Address::generate(&env)).collect();
+    let party = |index: u8| parties[index as usize % PARTIES].clone();
+
+    let contract_id = |immutables: &Immutables| {
+        client.compute_contract_id(
+            &party(immutables.sender),
+            &party(immutables.receiver),
+            &immutables.amount,
+            &BytesN::from_array(&env, &immutables.hashlock),
+            &immutables.timelock,
+            &immutables.timestamp,
+        )
+    };
+    let (a, b) = input;
+    let same_parties = a.sender as usize % PARTIES == b.sender as usize % PARTIES
+        && a.receiver as usize % PARTIES == b.receiver as usize % PARTIES;
+    let same_immutables = same_parties
+        && (a.amount, a.hashlock, a.timelock, a.timestamp)
+            == (b.amount, b.hashlock, b.timelock, b.timestamp);
+    assert_eq!(contract_id(&a) == contract_id(&b), same_immutables, "{a:?} / {b:?}");
+});
@@ -0,0 +1,82 @@
+//! Fills a partial HTLC with an arbitrary secret, index, proof and amount: every call
+//! either fails with a typed error or carries a proof that a reference verifier accepts.
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use soroban_sdk::{Bytes, BytesN, Env, Vec};
+use stellar_htlc::testutils::*;
+use stellar_htlc::HashAlgo;
+
+/// Longest proof tried, enough for a tree of 65 536 secrets
+const MAX_PROOF_LEN: usize = 16;
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    keccak: bool,
+    root: [u8; 32],
+    parts: u8,
+    secret: [u8; 32],
+    index: u32,
+    proof: std::vec::Vec<[u8; 32]>,
+    fill_amount: i128,
+}
+
+fn hash(env: &Env, hash_algo: HashAlgo, data: &[u8]) -> [u8; 32] {
+    let data = Bytes::from_slice(env, data);
+    match hash_algo {
+        HashAlgo::Sha256 => env.crypto().sha256(&data).to_array(),
+        HashAlgo::Keccak256 => env.crypto().keccak256(&data).to_array(),
+    }
+}
+
+/// Root the proof leads to from the leaf `H(index || H(secret))`, hashing sorted pairs
+fn reference_root(env: &Env, hash_algo: HashAlgo, input: &Input) -> [u8; 32] {
+    let mut leaf = (input.index as u64).to_be_bytes().to_vec();
+    leaf.extend_from_slice(&hash(env, hash_algo, &input.secret));
+    let mut computed = hash(env, hash_algo, &leaf);
+    for node in input.proof.iter().take(MAX_PROOF_LEN) {
+        let (first, second) = if computed <= *node {
+            (computed, *node)
+        } else {
+            (*node, computed)
+        };
+        computed = hash(env, hash_algo, &[first, second].concat());
+    }
+    computed
+}
+
+fuzz_target!(|input: Input| {
+    let (env, sender, receiver, token_address, client) = setup_in(new_env_without_snapshot());
+    let hash_algo = if input.keccak {
+        HashAlgo::Keccak256
+    } else {
+        HashAlgo::Sha256
+    };
+    let root = BytesN::from_array(&env, &input.root);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &root);
+    params.hash_algo = hash_algo;
+    params.parts = input.parts as u32;
+    let contract_id = match client.try_create_htlc_with_params(&params) {
+        Ok(Ok(contract_id)) => contract_id,
+        Err(Ok(_)) => return,
+        Ok(Err(_)) | Err(Err(_)) => panic!("creation failed without a typed error"),
+    };
+
+    let mut proof = Vec::new(&env);
+    for node in input.proof.iter().take(MAX_PROOF_LEN) {
+        proof.push_back(BytesN::from_array(&env, node));
+    }
+    let result = client.try_withdraw_partial(
+        &contract_id,
+        &BytesN::from_array(&env, &input.secret),
+        &input.index,
+        &proof,
+        &input.fill_amount,
+    );
+    match result {
+        Ok(Ok(())) => assert_eq!(reference_root(&env, hash_algo, &input), input.root),
+        Err(Ok(_)) => {}
+        Ok(Err(_)) | Err(Err(_)) => panic!("fill failed without a typed error"),
+    }
+});
@@ -0,0 +1,60 @@
+//! Withdraws with arbitrary preimages, against both a known secret and an arbitrary
+//! hashlock: only the secret opens its HTLC and every other attempt fails with
+//! `InvalidPreimage`.
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use soroban_sdk::BytesN;
+use stellar_htlc::testutils::*;
+use stellar_htlc::{HTLCError, HTLCStatus, HashAlgo};
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    keccak: bool,
+    hashlock: [u8; 32],
+    preimage: [u8; 32],
+}
+
+fuzz_target!(|input: Input| {
+    let (env, sender, receiver, token_address, client) = setup_in(new_env_without_snapshot());
+    let (hash_algo, secret_hashlock) = {
+        let secret = BytesN::from_array(&env, &SECRET);
+        if input.keccak {
+            (HashAlgo::Keccak256, env.crypto().keccak256(&secret.into()))
+        } else {
+            (HashAlgo::Sha256, env.crypto().sha256(&secret.into()))
+        }
+    };
+    let preimage = BytesN::from_array(&env, &input.preimage);
+
+    // Either the known secret's hashlock or one the fuzzer picked
+    let hashlock = if input.hashlock[0] & 1 == 0 {
+        secret_hashlock.clone()
+    } else {
+        BytesN::from_array(&env, &input.hashlock)
+    };
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.hash_algo = hash_algo;
+    let contract_id = match client.try_create_htlc_with_params(&params) {
+        Ok(Ok(contract_id)) => contract_id,
+        // Degenerate hashlocks are rejected up front
+        Err(Ok(HTLCError::ZeroHashlock | HTLCError::EmptyPreimageHashlock)) => return,
+        other => panic!("unexpected creation result {other:?}"),
+    };
+
+    let opens = hashlock == secret_hashlock && input.preimage == SECRET;
+    match client.try_withdraw(&contract_id, &preimage) {
+        Ok(Ok(())) => assert!(opens, "withdrawn with a wrong preimage"),
+        Err(Ok(HTLCError::InvalidPreimage)) => assert!(!opens, "secret rejected"),
+        other => panic!("unexpected withdrawal result {other:?}"),
+    }
+    let expected = if opens {
+        HTLCStatus::Withdrawn
+    } else {
+        HTLCStatus::Active
+    };
+    assert_eq!(client.get_status(&contract_id), expected);
+});
+
+const SECRET: [u8; 32] = [42; 32];
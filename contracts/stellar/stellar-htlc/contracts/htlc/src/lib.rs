@@ -1,288 +1,5011 @@
 #![no_std]
 use soroban_sdk::{
-    contract, contractimpl, contracttype, token, xdr::ToXdr, Address, Bytes, BytesN, Env, Symbol,
+    contract, contractclient, contracterror, contractimpl, contracttype,
+    events::Topics,
+    token,
+    xdr::{FromXdr, ToXdr},
+    Address, Bytes, BytesN, Env, IntoVal, Map, Symbol, TryFromVal, Val, Vec,
 };
 
+/// Errors of every contract function. The enum is not exported as is, since a spec error
+/// enum holds at most 50 cases; `error_spec` publishes it in two parts instead.
+#[contracterror(export = false)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum HTLCError {
+    InvalidAmount = 1,
+    InvalidSafetyDeposit = 2,
+    InvalidTimelock = 3,
+    ContractAlreadyExists = 4,
+    ContractNotFound = 5,
+    ReentrancyDetected = 6,
+    AlreadyWithdrawn = 7,
+    AlreadyRefunded = 8,
+    TimelockExpired = 9,
+    TimelockNotExpired = 10,
+    InvalidPreimage = 11,
+    InvalidPartsCount = 12,
+    InvalidFillAmount = 13,
+    InvalidFillIndex = 14,
+    InvalidMerkleProof = 15,
+    PartialFillRequired = 16,
+    PartialFillNotAllowed = 17,
+    InvalidPublicWithdrawal = 18,
+    PublicWithdrawalNotStarted = 19,
+    InvalidPublicCancellation = 20,
+    PublicCancellationNotStarted = 21,
+    AlreadyInitialized = 22,
+    NotInitialized = 23,
+    ContractPaused = 24,
+    ResolverNotWhitelisted = 25,
+    InvalidFee = 26,
+    InvalidTtlExtension = 27,
+    ContractNotCompleted = 28,
+    CleanupTooEarly = 29,
+    InsufficientRescuableBalance = 30,
+    InvalidAuction = 31,
+    InvalidOrderSigner = 32,
+    OrderExpired = 33,
+    OrderAlreadyUsed = 34,
+    SignatureExpired = 35,
+    InvalidSigner = 36,
+    InvalidTimelockBounds = 37,
+    AmountOverflow = 38,
+    AmountAboveCap = 39,
+    InvalidBasket = 40,
+    InvalidSwap = 41,
+    NoPendingAdmin = 42,
+    AdminTransferNotReady = 43,
+    HashlockInUse = 44,
+    TakerExclusive = 45,
+    TokenNotAllowed = 46,
+    SenderIsReceiver = 47,
+    ZeroHashlock = 48,
+    EmptyPreimageHashlock = 49,
+    InvalidThreshold = 50,
+    MemoTooLong = 51,
+    RateLimited = 52,
+    InvalidRateLimit = 53,
+    FreezeDisabled = 54,
+    AlreadyFrozen = 55,
+    EscrowFrozen = 56,
+    ExtensionTooLong = 57,
+    InvalidTokenContract = 58,
+    InvalidVestingSchedule = 59,
+    NotVesting = 60,
+    NothingVested = 61,
+    FreezeTooLate = 62,
+    InvalidEscrowParams = 63,
+    WithdrawalNotStarted = 64,
+}
+
+/// Spec entries describing `HTLCError` to clients: codes 1 to 50 as `HTLCError` and the
+/// rest as `HTLCErrorExt`. Keep in sync with `HTLCError`.
+#[allow(dead_code)]
+mod error_spec {
+    use soroban_sdk::contracterror;
+
+    #[contracterror]
+    #[derive(Copy, Clone, Debug)]
+    #[repr(u32)]
+    pub enum HTLCError {
+        InvalidAmount = 1,
+        InvalidSafetyDeposit = 2,
+        InvalidTimelock = 3,
+        ContractAlreadyExists = 4,
+        ContractNotFound = 5,
+        ReentrancyDetected = 6,
+        AlreadyWithdrawn = 7,
+        AlreadyRefunded = 8,
+        TimelockExpired = 9,
+        TimelockNotExpired = 10,
+        InvalidPreimage = 11,
+        InvalidPartsCount = 12,
+        InvalidFillAmount = 13,
+        InvalidFillIndex = 14,
+        InvalidMerkleProof = 15,
+        PartialFillRequired = 16,
+        PartialFillNotAllowed = 17,
+        InvalidPublicWithdrawal = 18,
+        PublicWithdrawalNotStarted = 19,
+        InvalidPublicCancellation = 20,
+        PublicCancellationNotStarted = 21,
+        AlreadyInitialized = 22,
+        NotInitialized = 23,
+        ContractPaused = 24,
+        ResolverNotWhitelisted = 25,
+        InvalidFee = 26,
+        InvalidTtlExtension = 27,
+        ContractNotCompleted = 28,
+        CleanupTooEarly = 29,
+        InsufficientRescuableBalance = 30,
+        InvalidAuction = 31,
+        InvalidOrderSigner = 32,
+        OrderExpired = 33,
+        OrderAlreadyUsed = 34,
+        SignatureExpired = 35,
+        InvalidSigner = 36,
+        InvalidTimelockBounds = 37,
+        AmountOverflow = 38,
+        AmountAboveCap = 39,
+        InvalidBasket = 40,
+        InvalidSwap = 41,
+        NoPendingAdmin = 42,
+        AdminTransferNotReady = 43,
+        HashlockInUse = 44,
+        TakerExclusive = 45,
+        TokenNotAllowed = 46,
+        SenderIsReceiver = 47,
+        ZeroHashlock = 48,
+        EmptyPreimageHashlock = 49,
+        InvalidThreshold = 50,
+    }
+
+    #[contracterror]
+    #[derive(Copy, Clone, Debug)]
+    #[repr(u32)]
+    pub enum HTLCErrorExt {
+        MemoTooLong = 51,
+        RateLimited = 52,
+        InvalidRateLimit = 53,
+        FreezeDisabled = 54,
+        AlreadyFrozen = 55,
+        EscrowFrozen = 56,
+        ExtensionTooLong = 57,
+        InvalidTokenContract = 58,
+        InvalidVestingSchedule = 59,
+        NotVesting = 60,
+        NothingVested = 61,
+        FreezeTooLate = 62,
+        InvalidEscrowParams = 63,
+        WithdrawalNotStarted = 64,
+    }
+}
+
+/// Contract interface version, bumped with every upgrade that changes behavior
+const CONTRACT_VERSION: u32 = 1;
+
+/// Version of the event data layout, bumped whenever an event's payload changes shape
+const EVENT_SCHEMA_VERSION: u32 = 5;
+
+/// Layout version of stored HTLC entries, bumped whenever `StoredHTLC` changes shape;
+/// entries in the first release's layout (`BaselineHTLC`) under the legacy key count as
+/// version 0, version 1 entries lack `created_ledger`, version 2 entries lack the referral
+/// fields and version 3 entries lack `vesting`
+const HTLC_STORAGE_VERSION: u32 = 4;
+
+/// Upper bound on the protocol fee and on an HTLC's referral fee, in basis points (5%)
+const MAX_FEE_BPS: u32 = 500;
+
+const BPS_DENOMINATOR: i128 = 10_000;
+
+const DAY_IN_LEDGERS: u32 = 17_280;
+
+/// Seconds a completed HTLC stays readable before it may be purged (7 days)
+const CLEANUP_GRACE_PERIOD: u64 = 7 * 24 * 60 * 60;
+
+/// Ledgers an HTLC entry is kept alive for after every write unless configured otherwise
+const DEFAULT_TTL_EXTENSION: u32 = 30 * DAY_IN_LEDGERS;
+
+/// Seconds a proposed admin must wait before accepting the role (2 days)
+const ADMIN_TRANSFER_DELAY: u64 = 2 * 24 * 60 * 60;
+
+/// Maximum number of extra token legs in a multi-asset HTLC
+const MAX_BASKET_LEGS: u32 = 8;
+
+/// Maximum size of an HTLC's `extension`, in bytes
+const MAX_EXTENSION_LEN: u32 = 256;
+
+/// Maximum size of an HTLC's `memo`, in bytes
+const MAX_MEMO_LEN: u32 = 64;
+
+/// Maximum number of hashlocks in a k-of-n HTLC
+const MAX_THRESHOLD_HASHLOCKS: u32 = 16;
+
+/// Maximum number of tranches a vesting HTLC releases its amount in
+const MAX_TRANCHES: u32 = 64;
+
+/// Seconds before the timelock by which an admin freeze must end, so the receiver can
+/// still withdraw (10 minutes)
+const FREEZE_WITHDRAWAL_GRACE: u64 = 10 * 60;
+
+/// Ledgers a withdrawal commitment must age before it can be revealed
+const REVEAL_DELAY_LEDGERS: u32 = 2;
+
+/// Maximum number of contract IDs returned by one page of an index view
+const MAX_PAGE_SIZE: u32 = 100;
+
+/// XDR encoding of `Asset::Native`, used to derive the native XLM asset contract address
+const NATIVE_ASSET_XDR: [u8; 4] = [0, 0, 0, 0];
+
+/// XDR prefix of an `ScVal::Address` holding an ed25519 account, followed by the key
+const ACCOUNT_ADDRESS_XDR_PREFIX: [u8; 12] = [0, 0, 0, 18, 0, 0, 0, 0, 0, 0, 0, 0];
+
+/// How the escrowed amount and safety deposit reach the contract on creation
+#[derive(Clone, Copy)]
+enum Funding {
+    /// The sender authorized the creation and transfers the funds directly
+    Transfer,
+    /// The contract spends an allowance the sender granted it beforehand
+    Allowance,
+    /// The amount is already held by the contract, released by a withdrawal in the same call
+    Held,
+}
+
+/// Where an HTLC's state is kept
+#[derive(Clone, Copy, PartialEq)]
+enum Record {
+    /// The full record under `DataKey::HTLCEntry`, or `DataKey::HTLCData` before compaction
+    Full,
+    /// Only the mutable state under `DataKey::Escrow`, the rest restored from the immutables
+    Escrow,
+}
+
 #[derive(Clone)]
 #[contracttype]
 pub enum DataKey {
+    /// HTLC written by the first release, see `BaselineHTLC`, or a full `HTLCData` whose
+    /// timelock is too far out to compact; read until next rewritten
     HTLCData(BytesN<32>),
+    HTLCEntry(BytesN<32>),
+    /// Release progress of a revealed vesting HTLC with tranches still locked
+    Vesting(BytesN<32>),
+    Config,
+    Resolver(Address),
+    SenderHtlcs(Address),
+    ReceiverHtlcs(Address),
+    Locked(Address),
+    UsedOrder(BytesN<32>),
+    /// Signed order hash a maker invalidated before it was filled
+    CancelledOrder(Address, BytesN<32>),
+    Guard(BytesN<32>),
+    PendingAdmin,
+    CreationCount(Address),
+    EventSequence,
+    Stats,
+    Volume(Address),
+    HashlockHtlcs(BytesN<32>),
+    OrderState(BytesN<32>),
+    WithdrawCommit(BytesN<32>, Address),
+    AllowedToken(Address),
+    /// Protocol fee in force when an HTLC was created; absent if there was none
+    Fee(BytesN<32>),
+    /// Mutable state of an escrow created with `create_escrow`
+    Escrow(BytesN<32>),
+    /// Making amount of a Fusion+ order filled so far
+    OrderFilledAmount(BytesN<32>),
+    /// Start of a Fusion+ fill's `SrcWithdrawal` stage; absent if it opens on creation
+    WithdrawalStart(BytesN<32>),
+}
+
+/// Deployment parameters, set in `initialize` and changed through the admin setters
+#[derive(Clone, Debug, PartialEq)]
+#[contracttype]
+pub struct Config {
+    /// Holds no powers when `permissionless` is set
+    pub admin: Address,
+    /// Admin-free deployment: every admin function fails with `InvalidSigner`, forever
+    pub permissionless: bool,
+    /// Blocks creation of new HTLCs while set
+    pub paused: bool,
+    /// Protocol fee fixed into new HTLCs and taken on their withdrawal, in basis points;
+    /// 0 disables it
+    pub fee_bps: u32,
+    /// Receives protocol fees; the admin until set
+    pub fee_collector: Address,
+    pub min_timelock_duration: u64,
+    pub max_timelock_duration: u64,
+    /// Largest amount a single HTLC may lock
+    pub max_amount: i128,
+    /// Smallest safety deposit accepted on creation
+    pub min_safety_deposit: i128,
+    /// Ledgers HTLC entries are extended by on every write
+    pub ttl_extension: u32,
+    /// Restricts escrowed tokens to the admin-managed allowlist
+    pub enforce_allowlist: bool,
+    /// Policy contract consulted through `validate_htlc` before every creation; this
+    /// contract's own address if none
+    pub validator: Address,
+    /// Lets the admin instead of the sender call `rescue_htlc`
+    pub admin_rescue: bool,
+    /// Smallest amount a single HTLC may lock, keeping dust escrows out of storage
+    pub min_amount: i128,
+    /// Longest an admin freeze may block withdrawal of an HTLC, in seconds; 0 disables freezing
+    pub max_freeze_duration: u64,
+    /// HTLCs one sender may create per `rate_limit_window`; 0 disables the limit
+    pub max_creations_per_window: u32,
+    /// Length of the rolling creation window, in ledgers
+    pub rate_limit_window: u32,
+    /// Bonding registry deciding resolver eligibility in exclusive periods instead of the
+    /// admin-managed whitelist; this contract's own address if none
+    pub resolver_registry: Address,
+    /// Safety deposit required per HTLC, in basis points of the amount; 0 disables the policy
+    pub deposit_policy_bps: u32,
+    /// Lower bound of the policy deposit
+    pub deposit_policy_min: i128,
+    /// Upper bound of the policy deposit
+    pub deposit_policy_max: i128,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[contracttype]
+pub enum HTLCStatus {
+    Active,
+    Withdrawn,
+    Refunded,
+}
+
+/// Protocol fee taken from every successful withdrawal
+#[derive(Clone, Debug, PartialEq)]
+#[contracttype]
+pub struct FeeConfig {
+    pub fee_bps: u32,
+    pub collector: Address,
+}
+
+/// Safety deposit sizing policy: `bps` of the HTLC amount, clamped to `[min, max]`
+#[derive(Clone, Debug, PartialEq)]
+#[contracttype]
+pub struct DepositPolicy {
+    pub bps: u32,
+    pub min: i128,
+    pub max: i128,
+}
+
+/// Running protocol counters, updated on every HTLC creation and completion
+#[derive(Clone, Debug, Default, PartialEq)]
+#[contracttype]
+pub struct Stats {
+    pub total_created: u64,
+    pub total_withdrawn: u64,
+    pub total_refunded: u64,
+    /// HTLCs neither withdrawn nor refunded yet
+    pub active: u64,
+}
+
+/// Admin handover awaiting acceptance by the proposed address
+#[derive(Clone, Debug, PartialEq)]
+#[contracttype]
+pub struct PendingAdmin {
+    pub new_admin: Address,
+    /// Earliest ledger timestamp at which `accept_admin` succeeds
+    pub valid_after: u64,
+}
+
+/// Allowed range of `timelock - now` at creation, in seconds
+#[derive(Clone, Debug, PartialEq)]
+#[contracttype]
+pub struct TimelockBounds {
+    pub min_timelock_duration: u64,
+    pub max_timelock_duration: u64,
+}
+
+/// Contract balance of a token split into escrowed obligations and the rescuable excess
+#[derive(Clone, Debug, PartialEq)]
+#[contracttype]
+pub struct TokenAccounting {
+    /// Amounts and safety deposits owed to active HTLCs
+    pub locked: i128,
+    /// Actual token balance held by the contract
+    pub balance: i128,
+}
+
+/// Hash function used to validate secrets against the hashlock
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[contracttype]
+pub enum HashAlgo {
+    Sha256,
+    Keccak256,
+}
+
+/// Describes the EVM side of a cross-chain swap
+#[derive(Clone, Debug, PartialEq)]
+#[contracttype]
+pub struct CrossChainMetadata {
+    /// EVM chain id; 0 marks the metadata as unset
+    pub evm_chain_id: u64,
+    /// Escrow or order hash on the EVM chain
+    pub evm_order_hash: BytesN<32>,
+    /// Maker's 20-byte EVM address
+    pub maker: BytesN<20>,
+}
+
+/// Release schedule of a vesting HTLC: once the preimage is revealed, the amount unlocks
+/// in `tranches` equal parts, one every `interval` seconds starting at the reveal
+#[derive(Clone, Debug, PartialEq)]
+#[contracttype]
+pub struct VestingSchedule {
+    /// Number of tranches; 0 releases the whole amount on withdrawal
+    pub tranches: u32,
+    pub interval: u64,
+}
+
+impl VestingSchedule {
+    /// Schedule of HTLCs paying out in full on withdrawal
+    pub fn none() -> Self {
+        VestingSchedule {
+            tranches: 0,
+            interval: 0,
+        }
+    }
+}
+
+/// Release progress of a vesting HTLC, stored from the reveal until its last tranche is paid
+#[derive(Clone, Debug, PartialEq)]
+#[contracttype]
+pub struct VestingProgress {
+    /// Receives every tranche, e.g. the `withdraw_to` destination
+    pub recipient: Address,
+    /// Timestamp the preimage was revealed at, unlocking the first tranche
+    pub started_at: u64,
+    /// Number of tranches paid out so far
+    pub released: u32,
+}
+
+impl CrossChainMetadata {
+    /// Metadata for HTLCs not linked to an EVM leg
+    pub fn none(env: &Env) -> Self {
+        CrossChainMetadata {
+            evm_chain_id: 0,
+            evm_order_hash: BytesN::from_array(env, &[0; 32]),
+            maker: BytesN::from_array(env, &[0; 20]),
+        }
+    }
+}
+
+/// Stage of a cross-chain swap, numbered like the 1inch `TimelocksLib.Stage` enum
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[contracttype]
+pub enum TimelockStage {
+    SrcWithdrawal = 0,
+    SrcPublicWithdrawal = 1,
+    SrcCancellation = 2,
+    SrcPublicCancellation = 3,
+    DstWithdrawal = 4,
+    DstPublicWithdrawal = 5,
+    DstCancellation = 6,
+}
+
+/// Deployment timestamp and per-stage offsets of a swap, convertible to the packed
+/// `uint256` of the 1inch Solidity `TimelocksLib`.
+///
+/// The packed value is big-endian: `deployed_at` fills the top 32 bits and the offset of
+/// stage `i` sits at bits `32 * i .. 32 * i + 32`, all in seconds.
+#[derive(Clone, Debug, PartialEq)]
+#[contracttype]
+pub struct Timelocks {
+    pub deployed_at: u32,
+    pub src_withdrawal: u32,
+    pub src_public_withdrawal: u32,
+    pub src_cancellation: u32,
+    pub src_public_cancellation: u32,
+    pub dst_withdrawal: u32,
+    pub dst_public_withdrawal: u32,
+    pub dst_cancellation: u32,
+}
+
+impl Timelocks {
+    /// Encodes the timelocks as the 32-byte big-endian `uint256` used on the EVM side
+    pub fn pack(&self, env: &Env) -> BytesN<32> {
+        let mut packed = [0u8; 32];
+        packed[..4].copy_from_slice(&self.deployed_at.to_be_bytes());
+        for stage in Self::stages() {
+            let start = Self::stage_byte_offset(stage);
+            packed[start..start + 4].copy_from_slice(&self.offset(stage).to_be_bytes());
+        }
+        BytesN::from_array(env, &packed)
+    }
+
+    /// Decodes a packed `uint256` produced by `pack` or by `TimelocksLib`
+    pub fn unpack(packed: &BytesN<32>) -> Self {
+        let packed = packed.to_array();
+        let word = |start: usize| {
+            u32::from_be_bytes([
+                packed[start],
+                packed[start + 1],
+                packed[start + 2],
+                packed[start + 3],
+            ])
+        };
+        let offset = |stage: TimelockStage| word(Self::stage_byte_offset(stage));
+        Timelocks {
+            deployed_at: word(0),
+            src_withdrawal: offset(TimelockStage::SrcWithdrawal),
+            src_public_withdrawal: offset(TimelockStage::SrcPublicWithdrawal),
+            src_cancellation: offset(TimelockStage::SrcCancellation),
+            src_public_cancellation: offset(TimelockStage::SrcPublicCancellation),
+            dst_withdrawal: offset(TimelockStage::DstWithdrawal),
+            dst_public_withdrawal: offset(TimelockStage::DstPublicWithdrawal),
+            dst_cancellation: offset(TimelockStage::DstCancellation),
+        }
+    }
+
+    /// Offset of `stage` from the deployment, in seconds
+    pub fn offset(&self, stage: TimelockStage) -> u32 {
+        match stage {
+            TimelockStage::SrcWithdrawal => self.src_withdrawal,
+            TimelockStage::SrcPublicWithdrawal => self.src_public_withdrawal,
+            TimelockStage::SrcCancellation => self.src_cancellation,
+            TimelockStage::SrcPublicCancellation => self.src_public_cancellation,
+            TimelockStage::DstWithdrawal => self.dst_withdrawal,
+            TimelockStage::DstPublicWithdrawal => self.dst_public_withdrawal,
+            TimelockStage::DstCancellation => self.dst_cancellation,
+        }
+    }
+
+    /// Absolute timestamp at which `stage` starts, like `TimelocksLib.get`
+    pub fn get(&self, stage: TimelockStage) -> u64 {
+        self.deployed_at as u64 + self.offset(stage) as u64
+    }
+
+    /// Checks if `stage` has started at the current ledger time
+    pub fn reached(&self, env: &Env, stage: TimelockStage) -> bool {
+        env.ledger().timestamp() >= self.get(stage)
+    }
+
+    fn stages() -> [TimelockStage; 7] {
+        [
+            TimelockStage::SrcWithdrawal,
+            TimelockStage::SrcPublicWithdrawal,
+            TimelockStage::SrcCancellation,
+            TimelockStage::SrcPublicCancellation,
+            TimelockStage::DstWithdrawal,
+            TimelockStage::DstPublicWithdrawal,
+            TimelockStage::DstCancellation,
+        ]
+    }
+
+    /// Position of a stage's offset in the big-endian packed value
+    fn stage_byte_offset(stage: TimelockStage) -> usize {
+        28 - 4 * stage as usize
+    }
+}
+
+/// Extra token leg of a multi-asset HTLC
+#[derive(Clone, Debug, PartialEq)]
+#[contracttype]
+pub struct BasketLeg {
+    pub token: Address,
+    pub amount: i128,
+}
+
+/// Full parameter set accepted by `create_htlc_with_params`
+#[derive(Clone, Debug, PartialEq)]
+#[contracttype]
+pub struct CreateParams {
+    pub sender: Address,
+    pub receiver: Address,
+    pub amount: i128,
+    pub token_address: Address,
+    /// Hashlock, or the Merkle root of secrets when `parts > 0`
+    pub hashlock: BytesN<32>,
+    /// Hash function the hashlock was computed with
+    pub hash_algo: HashAlgo,
+    pub timelock: u64,
+    pub safety_deposit: i128,
+    /// Asset the safety deposit is paid in, e.g. native XLM while swapping a token
+    pub safety_deposit_token: Address,
+    /// Number of parts for partial fills; 0 for single-secret HTLCs
+    pub parts: u32,
+    /// Timestamp after which anyone holding the preimage may withdraw; 0 disables
+    pub public_withdrawal: u64,
+    /// Timestamp after which anyone may refund the sender; 0 disables
+    pub public_cancellation: u64,
+    /// External order hash used as the contract ID; all zeroes derives the ID instead
+    pub order_hash: BytesN<32>,
+    /// Restricts withdrawal during the exclusive period to whitelisted resolvers
+    pub resolvers_only: bool,
+    /// Links to the EVM leg of the swap; `CrossChainMetadata::none` when unused
+    pub metadata: CrossChainMetadata,
+    /// Further token legs locked and released together with `amount`; empty for single-asset swaps
+    pub basket: Vec<BasketLeg>,
+    /// Address posting the safety deposit, e.g. the resolver; usually `sender`
+    pub safety_deposit_payer: Address,
+    /// Mixed into the generated contract ID so same-shaped HTLCs can coexist; zeroes if unused
+    pub salt: BytesN<32>,
+    /// Accepts a hashlock already guarding another active HTLC, e.g. the legs of a swap
+    pub allow_reuse: bool,
+    /// Only address allowed to withdraw during the exclusive window, e.g. the auction winner; `receiver` if none
+    pub taker: Address,
+    /// Hashlocks of a k-of-n HTLC; `hashlock` must then be `H(hashlocks[0] || .. || hashlocks[n-1])`
+    pub hashlocks: Vec<BytesN<32>>,
+    /// Number of `hashlocks` preimages required to withdraw; 0 disables threshold mode
+    pub threshold: u32,
+    /// Contract whose `on_htlc_withdrawn` is invoked after every withdrawal; `sender` if none
+    pub callback: Address,
+    /// Passed through to `callback` unchanged, e.g. encoded instructions for the integrating protocol
+    pub callback_data: Bytes,
+    /// Auxiliary data committed to by the contract ID, e.g. referral codes; at most `MAX_EXTENSION_LEN` bytes
+    pub extension: Bytes,
+    /// Seconds after creation from which `rescue_htlc` may close out the HTLC; must end after the timelock, 0 disables
+    pub rescue_delay: u64,
+    /// Receives the amount and basket legs on refund, e.g. a recovery account; `sender` if none
+    pub refund_to: Address,
+    /// Public withdrawals must go through `commit_withdraw` and `reveal_withdraw`; requires `public_withdrawal`
+    pub commit_reveal: bool,
+    /// Free-form reference echoed in every lifecycle event, e.g. a wallet's order ID; at most `MAX_MEMO_LEN` bytes
+    pub memo: Bytes,
+    /// Integrator paid `referral_bps` of every withdrawal, e.g. the wallet that routed the swap; `sender` if none
+    pub referral: Address,
+    /// Share of each withdrawn amount paid to `referral`, in basis points; 0 disables, at most `MAX_FEE_BPS`
+    pub referral_bps: u32,
+    /// Fills in the required deposit from the deployment's deposit policy when `safety_deposit` is 0
+    pub use_deposit_policy: bool,
+    /// Releases the amount in tranches after the reveal; `VestingSchedule::none` to pay out at once
+    pub vesting: VestingSchedule,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[contracttype]
+pub struct HTLCData {
+    pub contract_id: BytesN<32>,
+    pub sender: Address,
+    pub receiver: Address,
+    pub amount: i128,
+    pub token_address: Address,
+    pub hashlock: BytesN<32>,
+    pub hash_algo: HashAlgo,
+    pub timelock: u64,
+    pub timestamp: u64,
+    /// Ledger sequence the HTLC was created in, 0 for HTLCs stored before it was recorded
+    pub created_ledger: u32,
+    pub safety_deposit: i128,
+    pub safety_deposit_token: Address,
+    pub status: HTLCStatus,
+    /// Number of parts the amount is split into; 0 for single-secret HTLCs
+    pub parts: u32,
+    pub filled_amount: i128,
+    /// Lowest secret index still usable for the next partial fill
+    pub next_fill_index: u32,
+    /// Timestamp after which anyone holding the preimage may withdraw; 0 disables
+    pub public_withdrawal: u64,
+    /// Timestamp after which anyone may refund the sender; 0 disables
+    pub public_cancellation: u64,
+    /// External order hash the HTLC is keyed by; all zeroes when the ID was derived
+    pub order_hash: BytesN<32>,
+    /// Restricts withdrawal during the exclusive period to whitelisted resolvers
+    pub resolvers_only: bool,
+    /// Timestamp the HTLC was withdrawn or refunded at; 0 while active
+    pub completed_at: u64,
+    /// Revealed preimage, or the latest revealed secret for partial fills; all zeroes
+    /// until revealed
+    pub preimage: BytesN<32>,
+    /// Links to the EVM leg of the swap; `evm_chain_id` is 0 when unused
+    pub metadata: CrossChainMetadata,
+    /// Further token legs locked and released together with `amount`
+    pub basket: Vec<BasketLeg>,
+    /// Address that posted the safety deposit; forfeits it once the exclusive deadline passes
+    pub safety_deposit_payer: Address,
+    /// Caller-supplied salt mixed into the contract ID, zeroes if unused
+    pub salt: BytesN<32>,
+    /// Only address allowed to withdraw during the exclusive window; equals `receiver` if none
+    pub taker: Address,
+    /// Hashlocks of a k-of-n HTLC, empty for single-secret HTLCs
+    pub hashlocks: Vec<BytesN<32>>,
+    /// Number of `hashlocks` preimages required to withdraw; 0 for non-threshold HTLCs
+    pub threshold: u32,
+    /// Contract notified through `on_htlc_withdrawn` after every withdrawal; equals `sender` if none
+    pub callback: Address,
+    /// Passed through to `callback` unchanged
+    pub callback_data: Bytes,
+    /// Auxiliary data committed to by the contract ID; empty if unused
+    pub extension: Bytes,
+    /// Seconds after creation from which `rescue_htlc` may close out the HTLC; 0 disables
+    pub rescue_delay: u64,
+    /// End of the admin freeze blocking withdrawals; 0 if the HTLC was never frozen
+    pub frozen_until: u64,
+    /// Receives the amount and basket legs on refund
+    pub refund_to: Address,
+    /// Public withdrawals must go through `commit_withdraw` and `reveal_withdraw`
+    pub commit_reveal: bool,
+    /// Free-form reference echoed in every lifecycle event
+    pub memo: Bytes,
+    /// Integrator paid `referral_bps` of every withdrawal; equals `sender` if none
+    pub referral: Address,
+    /// Share of each withdrawn amount paid to `referral`, in basis points; 0 if none
+    pub referral_bps: u32,
+    /// Tranches the amount is released in after the reveal; `tranches` is 0 if paid out at once
+    pub vesting: VestingSchedule,
+}
+
+/// Compact on-ledger form of `HTLCStatus`, stored as a plain integer
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u32)]
+#[contracttype(export = false)]
+enum StoredStatus {
+    Active = 0,
+    Withdrawn = 1,
+    Refunded = 2,
+}
+
+/// Ledger entry of an HTLC. Unlike `HTLCData` it leaves out the contract ID, which is
+/// already the key, encodes the status as an integer and keeps the timelock and completion
+/// time as `u32` offsets from `timestamp`.
+#[derive(Clone)]
+#[contracttype(export = false)]
+struct StoredHTLC {
+    /// Layout the entry was written in, see `HTLC_STORAGE_VERSION`
+    version: u32,
+    sender: Address,
+    receiver: Address,
+    amount: i128,
+    token_address: Address,
+    hashlock: BytesN<32>,
+    hash_algo: HashAlgo,
+    /// Seconds from `timestamp` to the timelock
+    timelock_offset: u32,
+    timestamp: u64,
+    created_ledger: u32,
+    safety_deposit: i128,
+    safety_deposit_token: Address,
+    status: StoredStatus,
+    parts: u32,
+    filled_amount: i128,
+    next_fill_index: u32,
+    public_withdrawal: u64,
+    public_cancellation: u64,
+    order_hash: BytesN<32>,
+    resolvers_only: bool,
+    /// Seconds from `timestamp` to completion; 0 while active
+    completed_offset: u32,
+    preimage: BytesN<32>,
+    metadata: CrossChainMetadata,
+    basket: Vec<BasketLeg>,
+    safety_deposit_payer: Address,
+    salt: BytesN<32>,
+    taker: Address,
+    hashlocks: Vec<BytesN<32>>,
+    threshold: u32,
+    callback: Address,
+    callback_data: Bytes,
+    extension: Bytes,
+    rescue_delay: u64,
+    frozen_until: u64,
+    refund_to: Address,
+    commit_reveal: bool,
+    memo: Bytes,
+    referral: Address,
+    referral_bps: u32,
+    vesting: VestingSchedule,
+}
+
+impl StoredHTLC {
+    /// Packs an HTLC, or `None` when its timelock is too far past creation for a u32
+    /// offset (only possible for entries created before the bound was enforced)
+    fn pack(htlc_data: &HTLCData) -> Option<Self> {
+        let timelock_offset = u32::try_from(htlc_data.timelock - htlc_data.timestamp).ok()?;
+        Some(StoredHTLC {
+            version: HTLC_STORAGE_VERSION,
+            sender: htlc_data.sender.clone(),
+            receiver: htlc_data.receiver.clone(),
+            amount: htlc_data.amount,
+            token_address: htlc_data.token_address.clone(),
+            hashlock: htlc_data.hashlock.clone(),
+            hash_algo: htlc_data.hash_algo,
+            timelock_offset,
+            timestamp: htlc_data.timestamp,
+            created_ledger: htlc_data.created_ledger,
+            safety_deposit: htlc_data.safety_deposit,
+            safety_deposit_token: htlc_data.safety_deposit_token.clone(),
+            status: match htlc_data.status {
+                HTLCStatus::Active => StoredStatus::Active,
+                HTLCStatus::Withdrawn => StoredStatus::Withdrawn,
+                HTLCStatus::Refunded => StoredStatus::Refunded,
+            },
+            parts: htlc_data.parts,
+            filled_amount: htlc_data.filled_amount,
+            next_fill_index: htlc_data.next_fill_index,
+            public_withdrawal: htlc_data.public_withdrawal,
+            public_cancellation: htlc_data.public_cancellation,
+            order_hash: htlc_data.order_hash.clone(),
+            resolvers_only: htlc_data.resolvers_only,
+            completed_offset: u32::try_from(
+                htlc_data.completed_at.saturating_sub(htlc_data.timestamp),
+            )
+            .unwrap_or(u32::MAX),
+            preimage: htlc_data.preimage.clone(),
+            metadata: htlc_data.metadata.clone(),
+            basket: htlc_data.basket.clone(),
+            safety_deposit_payer: htlc_data.safety_deposit_payer.clone(),
+            salt: htlc_data.salt.clone(),
+            taker: htlc_data.taker.clone(),
+            hashlocks: htlc_data.hashlocks.clone(),
+            threshold: htlc_data.threshold,
+            callback: htlc_data.callback.clone(),
+            callback_data: htlc_data.callback_data.clone(),
+            extension: htlc_data.extension.clone(),
+            rescue_delay: htlc_data.rescue_delay,
+            frozen_until: htlc_data.frozen_until,
+            refund_to: htlc_data.refund_to.clone(),
+            commit_reveal: htlc_data.commit_reveal,
+            memo: htlc_data.memo.clone(),
+            referral: htlc_data.referral.clone(),
+            referral_bps: htlc_data.referral_bps,
+            vesting: htlc_data.vesting.clone(),
+        })
+    }
+
+    fn unpack(self, contract_id: &BytesN<32>) -> HTLCData {
+        HTLCData {
+            contract_id: contract_id.clone(),
+            sender: self.sender,
+            receiver: self.receiver,
+            amount: self.amount,
+            token_address: self.token_address,
+            hashlock: self.hashlock,
+            hash_algo: self.hash_algo,
+            timelock: self.timestamp + self.timelock_offset as u64,
+            timestamp: self.timestamp,
+            created_ledger: self.created_ledger,
+            safety_deposit: self.safety_deposit,
+            safety_deposit_token: self.safety_deposit_token,
+            status: match self.status {
+                StoredStatus::Active => HTLCStatus::Active,
+                StoredStatus::Withdrawn => HTLCStatus::Withdrawn,
+                StoredStatus::Refunded => HTLCStatus::Refunded,
+            },
+            parts: self.parts,
+            filled_amount: self.filled_amount,
+            next_fill_index: self.next_fill_index,
+            public_withdrawal: self.public_withdrawal,
+            public_cancellation: self.public_cancellation,
+            order_hash: self.order_hash,
+            resolvers_only: self.resolvers_only,
+            completed_at: match self.status {
+                StoredStatus::Active => 0,
+                _ => self.timestamp + self.completed_offset as u64,
+            },
+            preimage: self.preimage,
+            metadata: self.metadata,
+            basket: self.basket,
+            safety_deposit_payer: self.safety_deposit_payer,
+            salt: self.salt,
+            taker: self.taker,
+            hashlocks: self.hashlocks,
+            threshold: self.threshold,
+            callback: self.callback,
+            callback_data: self.callback_data,
+            extension: self.extension,
+            rescue_delay: self.rescue_delay,
+            frozen_until: self.frozen_until,
+            refund_to: self.refund_to,
+            commit_reveal: self.commit_reveal,
+            memo: self.memo,
+            referral: self.referral,
+            referral_bps: self.referral_bps,
+            vesting: self.vesting,
+        }
+    }
+}
+
+/// Ledger entry of an escrow created with `create_escrow`. Only what changes or may differ
+/// from the request after creation is stored; the immutables are committed to by the
+/// contract ID and supplied with every call instead.
+#[derive(Clone)]
+#[contracttype(export = false)]
+struct StoredEscrow {
+    /// Amount that arrived, which a fee-on-transfer token makes differ from the request
+    amount: i128,
+    hash_algo: HashAlgo,
+    created_ledger: u32,
+    safety_deposit: i128,
+    status: StoredStatus,
+    /// Seconds from creation to completion; 0 while active
+    completed_offset: u32,
+    preimage: BytesN<32>,
+}
+
+impl StoredEscrow {
+    fn pack(htlc_data: &HTLCData) -> Self {
+        StoredEscrow {
+            amount: htlc_data.amount,
+            hash_algo: htlc_data.hash_algo,
+            created_ledger: htlc_data.created_ledger,
+            safety_deposit: htlc_data.safety_deposit,
+            status: match htlc_data.status {
+                HTLCStatus::Active => StoredStatus::Active,
+                HTLCStatus::Withdrawn => StoredStatus::Withdrawn,
+                HTLCStatus::Refunded => StoredStatus::Refunded,
+            },
+            completed_offset: u32::try_from(
+                htlc_data.completed_at.saturating_sub(htlc_data.timestamp),
+            )
+            .unwrap_or(u32::MAX),
+            preimage: htlc_data.preimage.clone(),
+        }
+    }
+
+    /// The full HTLC, every feature `create_escrow` rejects taking its neutral value
+    fn unpack(self, env: &Env, contract_id: &BytesN<32>, immutables: &HTLCImmutables) -> HTLCData {
+        let sender = &immutables.sender;
+        HTLCData {
+            contract_id: contract_id.clone(),
+            sender: sender.clone(),
+            receiver: immutables.receiver.clone(),
+            amount: self.amount,
+            token_address: immutables.token_address.clone(),
+            hashlock: immutables.hashlock.clone(),
+            hash_algo: self.hash_algo,
+            timelock: immutables.timelock,
+            timestamp: immutables.timestamp,
+            created_ledger: self.created_ledger,
+            safety_deposit: self.safety_deposit,
+            safety_deposit_token: immutables.token_address.clone(),
+            status: match self.status {
+                StoredStatus::Active => HTLCStatus::Active,
+                StoredStatus::Withdrawn => HTLCStatus::Withdrawn,
+                StoredStatus::Refunded => HTLCStatus::Refunded,
+            },
+            parts: 0,
+            filled_amount: 0,
+            next_fill_index: 0,
+            public_withdrawal: 0,
+            public_cancellation: 0,
+            order_hash: BytesN::from_array(env, &[0; 32]),
+            resolvers_only: false,
+            completed_at: match self.status {
+                StoredStatus::Active => 0,
+                _ => immutables.timestamp + self.completed_offset as u64,
+            },
+            preimage: self.preimage,
+            metadata: CrossChainMetadata::none(env),
+            basket: Vec::new(env),
+            safety_deposit_payer: sender.clone(),
+            salt: immutables.salt.clone(),
+            taker: immutables.receiver.clone(),
+            hashlocks: Vec::new(env),
+            threshold: 0,
+            callback: sender.clone(),
+            callback_data: Bytes::new(env),
+            extension: immutables.extension.clone(),
+            rescue_delay: 0,
+            frozen_until: 0,
+            refund_to: sender.clone(),
+            commit_reveal: false,
+            memo: Bytes::new(env),
+            referral: sender.clone(),
+            referral_bps: 0,
+            vesting: VestingSchedule::none(),
+        }
+    }
+}
+
+/// HTLC as the first release stored it under `DataKey::HTLCData`
+#[derive(Clone)]
+#[contracttype(export = false)]
+struct BaselineHTLC {
+    contract_id: BytesN<32>,
+    sender: Address,
+    receiver: Address,
+    amount: i128,
+    token_address: Address,
+    hashlock: BytesN<32>,
+    timelock: u64,
+    timestamp: u64,
+    safety_deposit: i128,
+    status: HTLCStatus,
+    /// Set while paying out and cleared before the same call returned
+    locked: bool,
+}
+
+impl BaselineHTLC {
+    /// The HTLC in the current layout. The first release had none of the later features,
+    /// so it is the plain escrow `create_escrow` makes, hashed with SHA-256 and without a
+    /// salt or extension. It recorded neither the preimage nor the completion time, which
+    /// becomes the creation time. An entry still `locked` was stored mid-payout and is
+    /// rejected, as the first release rejected it.
+    fn upgrade(self, env: &Env) -> Result<HTLCData, HTLCError> {
+        if self.locked {
+            return Err(HTLCError::ReentrancyDetected);
+        }
+        let state = StoredEscrow {
+            amount: self.amount,
+            hash_algo: HashAlgo::Sha256,
+            created_ledger: 0,
+            safety_deposit: self.safety_deposit,
+            status: match self.status {
+                HTLCStatus::Active => StoredStatus::Active,
+                HTLCStatus::Withdrawn => StoredStatus::Withdrawn,
+                HTLCStatus::Refunded => StoredStatus::Refunded,
+            },
+            completed_offset: 0,
+            preimage: BytesN::from_array(env, &[0; 32]),
+        };
+        let immutables = HTLCImmutables {
+            sender: self.sender,
+            receiver: self.receiver,
+            token_address: self.token_address,
+            amount: self.amount,
+            hashlock: self.hashlock,
+            timelock: self.timelock,
+            timestamp: self.timestamp,
+            salt: BytesN::from_array(env, &[0; 32]),
+            extension: Bytes::new(env),
+        };
+        Ok(state.unpack(env, &self.contract_id, &immutables))
+    }
+}
+
+/// Lifecycle of the escrow opened for an order, recorded on every state change and kept
+/// after the HTLC itself is cleaned up
+#[derive(Clone, Debug, PartialEq)]
+#[contracttype]
+pub struct OrderState {
+    pub contract_id: BytesN<32>,
+    /// Ledger sequence the escrow was created in
+    pub created_ledger: u32,
+    pub amount: i128,
+    /// Amount released to the receiver so far
+    pub filled_amount: i128,
+    /// Revealed preimage, or the latest secret for partial fills; all zeroes until revealed
+    pub preimage: BytesN<32>,
+    pub status: HTLCStatus,
+    /// Timestamp the escrow was withdrawn or refunded at; 0 while active
+    pub completed_at: u64,
+}
+
+/// Fusion+-style order whose fill amount decays linearly from `start_amount` to
+/// `end_amount` between `start_time` and `end_time`
+#[derive(Clone, Debug, PartialEq)]
+#[contracttype]
+pub struct AuctionOrder {
+    /// HTLC parameters; `amount` is replaced by the current auction amount
+    pub params: CreateParams,
+    pub start_amount: i128,
+    pub end_amount: i128,
+    pub start_time: u64,
+    pub end_time: u64,
+}
+
+/// Order a maker signs off-chain so a resolver can open the HTLC on their behalf
+#[derive(Clone, Debug, PartialEq)]
+#[contracttype]
+pub struct SignedOrder {
+    /// Maker's ed25519 public key; `params.sender` must be the matching account
+    pub maker: BytesN<32>,
+    pub params: CreateParams,
+    /// Distinguishes otherwise identical orders from the same maker
+    pub nonce: u64,
+    /// Last timestamp the order may be submitted at
+    pub deadline: u64,
+}
+
+/// Fusion+ order in the shape of the 1inch Limit Order Protocol order: the maker sells
+/// `making_amount` of `maker_asset` for `taking_amount` of `taker_asset` on the
+/// destination chain, and an allowed resolver fills it by opening the source escrow
+#[derive(Clone, Debug, PartialEq)]
+#[contracttype]
+pub struct FusionOrder {
+    /// Maker's ed25519 public key; the escrow is funded from the matching account
+    pub maker: BytesN<32>,
+    /// Distinguishes otherwise identical orders and salts the escrow's contract ID
+    pub salt: BytesN<32>,
+    pub maker_asset: Address,
+    /// Destination-chain asset the maker receives
+    pub taker_asset: BytesN<32>,
+    pub making_amount: i128,
+    pub taking_amount: i128,
+    pub hashlock: BytesN<32>,
+    pub hash_algo: HashAlgo,
+    /// Escrow stage offsets; `deployed_at` is ignored and set to the fill time
+    pub timelocks: Timelocks,
+    /// Native XLM the filling resolver posts as safety deposit
+    pub safety_deposit: i128,
+    /// Resolvers allowed to fill the order; empty lets any resolver fill
+    pub allowed_resolvers: Vec<Address>,
+    /// Last timestamp the order may be filled at
+    pub expiry: u64,
+    pub metadata: CrossChainMetadata,
+}
+
+/// Intra-Stellar atomic swap set up as two mirrored HTLCs sharing one hashlock.
+///
+/// The initiator holds the secret, so their leg must outlive the counterparty's: the
+/// initiator claims the counterparty leg first, revealing the secret in time for the
+/// counterparty to claim the initiator leg.
+#[derive(Clone, Debug, PartialEq)]
+#[contracttype]
+pub struct SwapParams {
+    pub initiator: Address,
+    pub counterparty: Address,
+    pub hashlock: BytesN<32>,
+    /// Token and amount the initiator locks for the counterparty
+    pub initiator_token: Address,
+    pub initiator_amount: i128,
+    pub initiator_timelock: u64,
+    /// Token and amount the counterparty locks for the initiator
+    pub counterparty_token: Address,
+    pub counterparty_amount: i128,
+    /// Must expire before `initiator_timelock`
+    pub counterparty_timelock: u64,
+}
+
+/// Parameters a derived contract ID commits to, in the style of the 1inch escrow
+/// immutables. `amount` is the amount requested on creation.
+#[derive(Clone, Debug, PartialEq)]
+#[contracttype]
+pub struct HTLCImmutables {
+    pub sender: Address,
+    pub receiver: Address,
+    pub token_address: Address,
+    pub amount: i128,
+    pub hashlock: BytesN<32>,
+    pub timelock: u64,
+    /// Ledger timestamp of the creation
+    pub timestamp: u64,
+    pub salt: BytesN<32>,
+    pub extension: Bytes,
+}
+
+/// Data of the `HTLCNew` event: every immutable parameter of the created HTLC
+#[derive(Clone, Debug, PartialEq)]
+#[contracttype]
+pub struct HTLCNewEvent {
+    pub sender: Address,
+    pub receiver: Address,
+    pub amount: i128,
+    pub token_address: Address,
+    pub hashlock: BytesN<32>,
+    pub hash_algo: HashAlgo,
+    pub timelock: u64,
+    pub timestamp: u64,
+    pub created_ledger: u32,
+    pub safety_deposit: i128,
+    pub safety_deposit_token: Address,
+    pub parts: u32,
+    pub public_withdrawal: u64,
+    pub public_cancellation: u64,
+    pub order_hash: BytesN<32>,
+    pub resolvers_only: bool,
+    pub metadata: CrossChainMetadata,
+    pub basket: Vec<BasketLeg>,
+    pub safety_deposit_payer: Address,
+    pub salt: BytesN<32>,
+    pub taker: Address,
+    pub hashlocks: Vec<BytesN<32>>,
+    pub threshold: u32,
+    pub callback: Address,
+    pub callback_data: Bytes,
+    pub extension: Bytes,
+    pub rescue_delay: u64,
+    pub refund_to: Address,
+    pub commit_reveal: bool,
+    pub memo: Bytes,
+    pub referral: Address,
+    pub referral_bps: u32,
+    pub vesting: VestingSchedule,
+}
+
+/// Interface of a `CreateParams.callback` contract, invoked once an HTLC paid out
+#[contractclient(name = "WithdrawCallbackClient")]
+pub trait WithdrawCallback {
+    /// Called after `amount` of `token`, net of the protocol fee, reached `recipient`.
+    ///
+    /// Runs after the HTLC is settled; a failing callback is reported through a
+    /// `CallbackFailed` event and never reverts the withdrawal.
+    fn on_htlc_withdrawn(
+        env: Env,
+        contract_id: BytesN<32>,
+        recipient: Address,
+        token: Address,
+        amount: i128,
+        callback_data: Bytes,
+    );
 }
 
-#[derive(Clone, Debug, PartialEq)]
-#[contracttype]
-pub enum HTLCStatus {
-    Active,
-    Withdrawn,
-    Refunded,
-}
+/// Interface of the `Config.validator` policy contract, e.g. sanctions screening or
+/// asset rules
+#[contractclient(name = "CreationValidatorClient")]
+pub trait CreationValidator {
+    /// Called with the full parameter set before any funds are pulled; rejects the
+    /// creation by failing, which reverts it with the validator's error
+    fn validate_htlc(env: Env, params: CreateParams);
+}
+
+/// Interface of the `Config.resolver_registry` bonding contract
+#[contractclient(name = "ResolverRegistryClient")]
+pub trait ResolverRegistry {
+    /// Checks if a resolver's stake meets the registry's minimum bond
+    fn is_bonded(env: Env, resolver: Address) -> bool;
+}
+
+#[contract]
+pub struct HTLCContract;
+
+#[contractimpl]
+impl HTLCContract {
+    /// Sets the admin allowed to pause and unpause HTLC creation, or, with `permissionless`,
+    /// permanently gives up every admin power so users can verify the deployment is admin-free
+    ///
+    /// Only the account that deployed this contract with `salt` may initialize it and
+    /// become its admin; anyone else fails with `InvalidSigner`, so an instance left
+    /// uninitialized cannot be claimed by the first caller.
+    pub fn initialize(
+        env: Env,
+        admin: Address,
+        permissionless: bool,
+        salt: BytesN<32>,
+    ) -> Result<(), HTLCError> {
+        if env.storage().instance().has(&DataKey::Config) {
+            return Err(HTLCError::AlreadyInitialized);
+        }
+        let deployed = env
+            .deployer()
+            .with_address(admin.clone(), salt)
+            .deployed_address();
+        if deployed != env.current_contract_address() {
+            return Err(HTLCError::InvalidSigner);
+        }
+
+        admin.require_auth();
+        Self::save_config(
+            &env,
+            &Config {
+                admin: admin.clone(),
+                permissionless,
+                paused: false,
+                fee_bps: 0,
+                fee_collector: admin.clone(),
+                min_timelock_duration: 0,
+                max_timelock_duration: u64::MAX,
+                max_amount: i128::MAX,
+                min_safety_deposit: 0,
+                ttl_extension: DEFAULT_TTL_EXTENSION,
+                enforce_allowlist: false,
+                validator: env.current_contract_address(),
+                admin_rescue: false,
+                min_amount: 0,
+                max_freeze_duration: 0,
+                max_creations_per_window: 0,
+                rate_limit_window: 0,
+                resolver_registry: env.current_contract_address(),
+                deposit_policy_bps: 0,
+                deposit_policy_min: 0,
+                deposit_policy_max: 0,
+            },
+        );
+
+        Self::publish_event(
+            &env,
+            (Symbol::new(&env, "Initialized"),),
+            (admin, permissionless),
+        );
+
+        Ok(())
+    }
+
+    /// Blocks creation of new HTLCs; withdrawals and refunds are never paused
+    pub fn pause(env: Env) -> Result<(), HTLCError> {
+        let mut config = Self::require_admin(&env)?;
+        config.paused = true;
+        Self::save_config(&env, &config);
+
+        Self::publish_event(&env, (Symbol::new(&env, "Paused"),), config.admin);
+
+        Ok(())
+    }
+
+    /// Proposes a new admin, who may accept the role once the transfer delay has passed
+    ///
+    /// A later proposal replaces the pending one and restarts the delay.
+    pub fn propose_admin(env: Env, new_admin: Address) -> Result<(), HTLCError> {
+        Self::require_admin(&env)?;
+
+        let pending = PendingAdmin {
+            new_admin,
+            valid_after: env.ledger().timestamp() + ADMIN_TRANSFER_DELAY,
+        };
+        env.storage()
+            .instance()
+            .set(&DataKey::PendingAdmin, &pending);
+
+        Self::publish_event(
+            &env,
+            (Symbol::new(&env, "AdminProposed"), pending.new_admin),
+            pending.valid_after,
+        );
+
+        Ok(())
+    }
+
+    /// Completes a proposed admin transfer; callable by the proposed admin after the delay
+    pub fn accept_admin(env: Env) -> Result<(), HTLCError> {
+        let mut config = Self::get_config(env.clone())?;
+        let pending = Self::get_pending_admin(env.clone()).ok_or(HTLCError::NoPendingAdmin)?;
+        pending.new_admin.require_auth();
+
+        if env.ledger().timestamp() < pending.valid_after {
+            return Err(HTLCError::AdminTransferNotReady);
+        }
+
+        let previous_admin = config.admin;
+        config.admin = pending.new_admin.clone();
+        Self::save_config(&env, &config);
+        env.storage().instance().remove(&DataKey::PendingAdmin);
+
+        Self::publish_event(
+            &env,
+            (Symbol::new(&env, "AdminTransferred"),),
+            (previous_admin, pending.new_admin),
+        );
+
+        Ok(())
+    }
+
+    /// Resumes creation of new HTLCs
+    pub fn unpause(env: Env) -> Result<(), HTLCError> {
+        let mut config = Self::require_admin(&env)?;
+        config.paused = false;
+        Self::save_config(&env, &config);
+
+        Self::publish_event(&env, (Symbol::new(&env, "Unpaused"),), config.admin);
+
+        Ok(())
+    }
+
+    /// Adds a token contract to the allowlist
+    pub fn add_allowed_token(env: Env, token: Address) -> Result<(), HTLCError> {
+        Self::require_admin(&env)?;
+        env.storage()
+            .instance()
+            .set(&DataKey::AllowedToken(token.clone()), &true);
+
+        Self::publish_event(&env, (Symbol::new(&env, "TokenAllowed"),), token);
+
+        Ok(())
+    }
+
+    /// Removes a token contract from the allowlist; active HTLCs in it are unaffected
+    pub fn remove_allowed_token(env: Env, token: Address) -> Result<(), HTLCError> {
+        Self::require_admin(&env)?;
+        env.storage()
+            .instance()
+            .remove(&DataKey::AllowedToken(token.clone()));
+
+        Self::publish_event(&env, (Symbol::new(&env, "TokenDisallowed"),), token);
+
+        Ok(())
+    }
+
+    /// Turns enforcement of the token allowlist on new HTLCs on or off
+    pub fn set_enforce_allowlist(env: Env, enforce: bool) -> Result<(), HTLCError> {
+        let mut config = Self::require_admin(&env)?;
+        config.enforce_allowlist = enforce;
+        Self::save_config(&env, &config);
+
+        Self::publish_event(&env, (Symbol::new(&env, "AllowlistEnforced"),), enforce);
+
+        Ok(())
+    }
+
+    /// Sets the policy contract every new HTLC must pass
+    pub fn set_validator(env: Env, validator: Address) -> Result<(), HTLCError> {
+        let mut config = Self::require_admin(&env)?;
+        config.validator = validator.clone();
+        Self::save_config(&env, &config);
+
+        Self::publish_event(&env, (Symbol::new(&env, "ValidatorSet"),), validator);
+
+        Ok(())
+    }
+
+    /// Stops consulting a policy contract on creation
+    pub fn remove_validator(env: Env) -> Result<(), HTLCError> {
+        let mut config = Self::require_admin(&env)?;
+        config.validator = env.current_contract_address();
+        Self::save_config(&env, &config);
+
+        Self::publish_event(&env, (Symbol::new(&env, "ValidatorRemoved"),), ());
+
+        Ok(())
+    }
+
+    /// Sets the bonding registry that decides which resolvers may fill in exclusive periods,
+    /// replacing the admin-managed whitelist
+    pub fn set_resolver_registry(env: Env, registry: Address) -> Result<(), HTLCError> {
+        let mut config = Self::require_admin(&env)?;
+        config.resolver_registry = registry.clone();
+        Self::save_config(&env, &config);
+
+        Self::publish_event(&env, (Symbol::new(&env, "ResolverRegistrySet"),), registry);
+
+        Ok(())
+    }
+
+    /// Falls back to the admin-managed resolver whitelist
+    pub fn remove_resolver_registry(env: Env) -> Result<(), HTLCError> {
+        let mut config = Self::require_admin(&env)?;
+        config.resolver_registry = env.current_contract_address();
+        Self::save_config(&env, &config);
+
+        Self::publish_event(&env, (Symbol::new(&env, "ResolverRegistryRemoved"),), ());
+
+        Ok(())
+    }
+
+    /// Adds a resolver to the whitelist
+    pub fn add_resolver(env: Env, resolver: Address) -> Result<(), HTLCError> {
+        Self::require_admin(&env)?;
+        env.storage()
+            .instance()
+            .set(&DataKey::Resolver(resolver.clone()), &true);
+
+        Self::publish_event(&env, (Symbol::new(&env, "ResolverAdded"),), resolver);
+
+        Ok(())
+    }
+
+    /// Removes a resolver from the whitelist
+    pub fn remove_resolver(env: Env, resolver: Address) -> Result<(), HTLCError> {
+        Self::require_admin(&env)?;
+        env.storage()
+            .instance()
+            .remove(&DataKey::Resolver(resolver.clone()));
+
+        Self::publish_event(&env, (Symbol::new(&env, "ResolverRemoved"),), resolver);
+
+        Ok(())
+    }
+
+    /// Sets the protocol fee taken on withdrawal of HTLCs created from now on; a zero fee
+    /// disables it. Existing HTLCs keep the fee and collector they were created with.
+    pub fn set_protocol_fee(env: Env, fee_bps: u32, collector: Address) -> Result<(), HTLCError> {
+        let mut config = Self::require_admin(&env)?;
+        if fee_bps > MAX_FEE_BPS {
+            return Err(HTLCError::InvalidFee);
+        }
+
+        config.fee_bps = fee_bps;
+        config.fee_collector = collector.clone();
+        Self::save_config(&env, &config);
+
+        let fee_config = FeeConfig { fee_bps, collector };
+        Self::publish_event(&env, (Symbol::new(&env, "ProtocolFeeSet"),), fee_config);
+
+        Ok(())
+    }
+
+    /// Sets the range of timelock durations accepted for new HTLCs
+    pub fn set_timelock_bounds(
+        env: Env,
+        min_timelock_duration: u64,
+        max_timelock_duration: u64,
+    ) -> Result<(), HTLCError> {
+        let mut config = Self::require_admin(&env)?;
+        if min_timelock_duration > max_timelock_duration {
+            return Err(HTLCError::InvalidTimelockBounds);
+        }
+
+        config.min_timelock_duration = min_timelock_duration;
+        config.max_timelock_duration = max_timelock_duration;
+        Self::save_config(&env, &config);
+
+        let bounds = TimelockBounds {
+            min_timelock_duration,
+            max_timelock_duration,
+        };
+        Self::publish_event(&env, (Symbol::new(&env, "TimelockBoundsSet"),), bounds);
+
+        Ok(())
+    }
+
+    /// Sets the largest amount a single HTLC may lock
+    pub fn set_max_amount(env: Env, max_amount: i128) -> Result<(), HTLCError> {
+        let mut config = Self::require_admin(&env)?;
+        if max_amount <= 0 {
+            return Err(HTLCError::InvalidAmount);
+        }
+
+        config.max_amount = max_amount;
+        Self::save_config(&env, &config);
+
+        Self::publish_event(&env, (Symbol::new(&env, "MaxAmountSet"),), max_amount);
+
+        Ok(())
+    }
+
+    /// Sets the smallest amount a new HTLC may lock
+    pub fn set_min_amount(env: Env, min_amount: i128) -> Result<(), HTLCError> {
+        let mut config = Self::require_admin(&env)?;
+        if min_amount < 0 || min_amount > config.max_amount {
+            return Err(HTLCError::InvalidAmount);
+        }
+
+        config.min_amount = min_amount;
+        Self::save_config(&env, &config);
+
+        Self::publish_event(&env, (Symbol::new(&env, "MinAmountSet"),), min_amount);
+
+        Ok(())
+    }
+
+    /// Limits every sender to `max_creations` new HTLCs per rolling window of
+    /// `window_ledgers`; 0 creations lifts the limit
+    pub fn set_rate_limit(
+        env: Env,
+        max_creations: u32,
+        window_ledgers: u32,
+    ) -> Result<(), HTLCError> {
+        let mut config = Self::require_admin(&env)?;
+        if max_creations > 0 && window_ledgers == 0 {
+            return Err(HTLCError::InvalidRateLimit);
+        }
+
+        config.max_creations_per_window = max_creations;
+        config.rate_limit_window = window_ledgers;
+        Self::save_config(&env, &config);
+
+        Self::publish_event(
+            &env,
+            (Symbol::new(&env, "RateLimitSet"),),
+            (max_creations, window_ledgers),
+        );
+
+        Ok(())
+    }
+
+    /// Sets the smallest safety deposit accepted for new HTLCs
+    pub fn set_min_safety_deposit(env: Env, min_safety_deposit: i128) -> Result<(), HTLCError> {
+        let mut config = Self::require_admin(&env)?;
+        if min_safety_deposit < 0 {
+            return Err(HTLCError::InvalidSafetyDeposit);
+        }
+
+        config.min_safety_deposit = min_safety_deposit;
+        Self::save_config(&env, &config);
+
+        Self::publish_event(
+            &env,
+            (Symbol::new(&env, "MinSafetyDepositSet"),),
+            min_safety_deposit,
+        );
+
+        Ok(())
+    }
+
+    /// Sets the policy sizing the safety deposit required for new HTLCs from their amount;
+    /// a zero `bps` disables it
+    pub fn set_deposit_policy(env: Env, bps: u32, min: i128, max: i128) -> Result<(), HTLCError> {
+        let mut config = Self::require_admin(&env)?;
+        if bps as i128 > BPS_DENOMINATOR || min < 0 || max < min {
+            return Err(HTLCError::InvalidSafetyDeposit);
+        }
+
+        config.deposit_policy_bps = bps;
+        config.deposit_policy_min = min;
+        config.deposit_policy_max = max;
+        Self::save_config(&env, &config);
+
+        Self::publish_event(
+            &env,
+            (Symbol::new(&env, "DepositPolicySet"),),
+            DepositPolicy { bps, min, max },
+        );
+
+        Ok(())
+    }
+
+    /// Sets the number of ledgers HTLC entries are extended by on every write
+    pub fn set_ttl_extension(env: Env, ledgers: u32) -> Result<(), HTLCError> {
+        let mut config = Self::require_admin(&env)?;
+        if ledgers < DAY_IN_LEDGERS || ledgers > env.storage().max_ttl() {
+            return Err(HTLCError::InvalidTtlExtension);
+        }
+
+        config.ttl_extension = ledgers;
+        Self::save_config(&env, &config);
+
+        Self::publish_event(&env, (Symbol::new(&env, "TtlExtensionSet"),), ledgers);
+
+        Ok(())
+    }
+
+    /// Moves tokens sent to the contract outside any HTLC; balances owed to active HTLCs
+    /// can never be rescued
+    pub fn rescue(env: Env, token: Address, amount: i128, to: Address) -> Result<(), HTLCError> {
+        Self::require_admin(&env)?;
+        if amount <= 0 {
+            return Err(HTLCError::InvalidAmount);
+        }
+
+        let accounting = Self::get_token_accounting(env.clone(), token.clone());
+        if amount > accounting.balance - accounting.locked {
+            return Err(HTLCError::InsufficientRescuableBalance);
+        }
+
+        token::Client::new(&env, &token).transfer(&env.current_contract_address(), &to, &amount);
+
+        Self::publish_event(&env, (Symbol::new(&env, "Rescued"), token), (to, amount));
+
+        Ok(())
+    }
+
+    /// Chooses whether the admin or each HTLC's sender may call `rescue_htlc`
+    pub fn set_admin_rescue(env: Env, enabled: bool) -> Result<(), HTLCError> {
+        let mut config = Self::require_admin(&env)?;
+        config.admin_rescue = enabled;
+        Self::save_config(&env, &config);
+
+        Self::publish_event(&env, (Symbol::new(&env, "AdminRescueSet"),), enabled);
+
+        Ok(())
+    }
+
+    /// Sets how long `freeze` may block withdrawals; 0 disables freezing
+    pub fn set_max_freeze_duration(env: Env, seconds: u64) -> Result<(), HTLCError> {
+        let mut config = Self::require_admin(&env)?;
+        config.max_freeze_duration = seconds;
+        Self::save_config(&env, &config);
+
+        Self::publish_event(&env, (Symbol::new(&env, "MaxFreezeDurationSet"),), seconds);
+
+        Ok(())
+    }
+
+    /// Blocks withdrawal of an active HTLC for `Config.max_freeze_duration`, e.g. while a
+    /// reported preimage theft is investigated.
+    ///
+    /// The freeze ends at the latest `FREEZE_WITHDRAWAL_GRACE` before the timelock and each
+    /// HTLC can be frozen only once, so the admin can delay but never confiscate a swap.
+    /// Fails with `FreezeDisabled` while freezing is disabled, with `AlreadyFrozen` if the
+    /// HTLC was frozen before and with `FreezeTooLate` inside the grace window.
+    pub fn freeze(env: Env, contract_id: BytesN<32>) -> Result<(), HTLCError> {
+        let config = Self::require_admin(&env)?;
+        let mut htlc_data = Self::get_htlc_data(&env, &contract_id)?;
+
+        // Status check
+        Self::ensure_active(&htlc_data)?;
+
+        if config.max_freeze_duration == 0 {
+            return Err(HTLCError::FreezeDisabled);
+        }
+        if htlc_data.frozen_until != 0 {
+            return Err(HTLCError::AlreadyFrozen);
+        }
+
+        // The receiver keeps a withdrawal window between the freeze and the refund
+        let now = env.ledger().timestamp();
+        let latest_end = htlc_data.timelock.saturating_sub(FREEZE_WITHDRAWAL_GRACE);
+        if now >= latest_end {
+            return Err(HTLCError::FreezeTooLate);
+        }
+        htlc_data.frozen_until = now
+            .saturating_add(config.max_freeze_duration)
+            .min(latest_end);
+        Self::save_htlc_data(&env, &contract_id, &htlc_data);
+
+        Self::publish_lifecycle_event(
+            &env,
+            (Symbol::new(&env, "HTLCFrozen"), contract_id),
+            &htlc_data,
+            htlc_data.frozen_until,
+        );
+
+        Ok(())
+    }
+
+    /// Lifts a freeze early; the HTLC still cannot be frozen again
+    pub fn unfreeze(env: Env, contract_id: BytesN<32>) -> Result<(), HTLCError> {
+        Self::require_admin(&env)?;
+        let mut htlc_data = Self::get_htlc_data(&env, &contract_id)?;
+
+        let now = env.ledger().timestamp();
+        if htlc_data.frozen_until > now {
+            htlc_data.frozen_until = now;
+            Self::save_htlc_data(&env, &contract_id, &htlc_data);
+        }
+
+        Self::publish_lifecycle_event(
+            &env,
+            (Symbol::new(&env, "HTLCUnfrozen"), contract_id),
+            &htlc_data,
+            (),
+        );
+
+        Ok(())
+    }
+
+    /// Replaces the contract code while keeping all stored HTLCs
+    pub fn upgrade(env: Env, new_wasm_hash: BytesN<32>) -> Result<(), HTLCError> {
+        Self::require_admin(&env)?;
+        env.deployer()
+            .update_current_contract_wasm(new_wasm_hash.clone());
+
+        Self::publish_event(&env, (Symbol::new(&env, "Upgraded"),), new_wasm_hash);
+
+        Ok(())
+    }
+
+    /// Creates a new HTLC
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_htlc(
+        env: Env,
+        sender: Address,
+        receiver: Address,
+        amount: i128,
+        token_address: Address,
+        hashlock: BytesN<32>,
+        timelock: u64,
+        safety_deposit: i128,
+    ) -> Result<BytesN<32>, HTLCError> {
+        Self::create(
+            &env,
+            CreateParams {
+                sender: sender.clone(),
+                receiver: receiver.clone(),
+                amount,
+                token_address: token_address.clone(),
+                hashlock,
+                hash_algo: HashAlgo::Sha256,
+                timelock,
+                safety_deposit,
+                safety_deposit_token: token_address,
+                parts: 0,
+                public_withdrawal: 0,
+                public_cancellation: 0,
+                order_hash: BytesN::from_array(&env, &[0; 32]),
+                resolvers_only: false,
+                metadata: CrossChainMetadata::none(&env),
+                basket: Vec::new(&env),
+                safety_deposit_payer: sender.clone(),
+                salt: BytesN::from_array(&env, &[0; 32]),
+                allow_reuse: false,
+                taker: receiver.clone(),
+                hashlocks: Vec::new(&env),
+                threshold: 0,
+                callback: sender.clone(),
+                callback_data: Bytes::new(&env),
+                extension: Bytes::new(&env),
+                rescue_delay: 0,
+                refund_to: sender.clone(),
+                commit_reveal: false,
+                memo: Bytes::new(&env),
+                referral: sender,
+                referral_bps: 0,
+                use_deposit_policy: false,
+                vesting: VestingSchedule::none(),
+            },
+        )
+    }
+
+    /// Creates a new HTLC from the full parameter set
+    pub fn create_htlc_with_params(
+        env: Env,
+        params: CreateParams,
+    ) -> Result<BytesN<32>, HTLCError> {
+        Self::create(&env, params)
+    }
+
+    /// Creates a new HTLC funded through an allowance the sender granted this contract,
+    /// so contract senders need not authorize a nested token transfer
+    pub fn create_htlc_from_allowance(
+        env: Env,
+        params: CreateParams,
+    ) -> Result<BytesN<32>, HTLCError> {
+        params.sender.require_auth();
+        if params.safety_deposit_payer != params.sender {
+            params.safety_deposit_payer.require_auth();
+        }
+        Self::create_authorized(&env, params, Funding::Allowance)
+    }
+
+    /// Creates a plain single-secret escrow that stores only its mutable state, identified
+    /// by `HTLCImmutables` like the 1inch escrows. It is withdrawn and refunded through
+    /// `withdraw_with_immutables` and `refund_with_immutables`, and read with `get_escrow`.
+    ///
+    /// Fails with `InvalidEscrowParams` unless every parameter outside the immutables is
+    /// at its neutral value: no order hash, partial fills, threshold, public windows,
+    /// basket, callback, rescue, memo, referral or vesting, and a safety deposit in the
+    /// swapped token paid by the sender.
+    pub fn create_escrow(env: Env, params: CreateParams) -> Result<BytesN<32>, HTLCError> {
+        params.sender.require_auth();
+        if params.safety_deposit_payer != params.sender {
+            params.safety_deposit_payer.require_auth();
+        }
+        Self::create_entry(&env, params, Funding::Transfer, Record::Escrow)
+    }
+
+    /// Creates a new HTLC for an auction order, locking the amount the auction curve
+    /// requires at the current ledger timestamp
+    pub fn create_htlc_from_order(env: Env, order: AuctionOrder) -> Result<BytesN<32>, HTLCError> {
+        let mut params = order.params.clone();
+        params.amount = Self::get_current_fill_amount(env.clone(), order)?;
+        Self::create(&env, params)
+    }
+
+    /// Creates a new HTLC for an order the maker signed with their ed25519 key.
+    ///
+    /// Anyone may submit the order. Funds are pulled through an allowance the maker
+    /// granted this contract, so the maker never submits a Stellar transaction for the
+    /// swap itself. Panics if the signature does not verify against `get_order_digest`.
+    pub fn create_htlc_from_signed_order(
+        env: Env,
+        order: SignedOrder,
+        signature: BytesN<64>,
+    ) -> Result<BytesN<32>, HTLCError> {
+        if env.ledger().timestamp() > order.deadline {
+            return Err(HTLCError::OrderExpired);
+        }
+
+        if order.params.sender != Self::account_address(&env, &order.maker) {
+            return Err(HTLCError::InvalidOrderSigner);
+        }
+
+        // Each signed order opens at most one HTLC
+        let digest = Self::get_order_digest(env.clone(), order.clone());
+        let used_key = DataKey::UsedOrder(digest.clone());
+        if env.storage().persistent().has(&used_key)
+            || Self::is_order_cancelled(env.clone(), order.params.sender.clone(), digest.clone())
+        {
+            return Err(HTLCError::OrderAlreadyUsed);
+        }
+
+        env.crypto()
+            .ed25519_verify(&order.maker, &digest.into(), &signature);
+
+        // A resolver posting the safety deposit authorizes its own transfer
+        if order.params.safety_deposit_payer != order.params.sender {
+            order.params.safety_deposit_payer.require_auth();
+        }
+
+        env.storage().persistent().set(&used_key, &true);
+        let extend_to = Self::get_ttl_extension(env.clone());
+        env.storage()
+            .persistent()
+            .extend_ttl(&used_key, extend_to - DAY_IN_LEDGERS, extend_to);
+
+        Self::create_authorized(&env, order.params, Funding::Allowance)
+    }
+
+    /// Fills part or all of a maker-signed Fusion+ order by opening a source escrow,
+    /// returning its contract ID: the order hash for the first fill and
+    /// `get_fill_id(order_hash, filled)` for each later one.
+    ///
+    /// The resolver fills `fill_amount` of what is left of the making amount in one escrow
+    /// it is the receiver of, posting the safety deposit in native XLM. Funds are pulled
+    /// through an allowance the maker granted this contract. The resolver may withdraw from
+    /// `SrcWithdrawal` until `SrcCancellation`. Panics if the signature does not verify
+    /// against `get_fusion_order_hash`.
+    pub fn fill_order(
+        env: Env,
+        resolver: Address,
+        order: FusionOrder,
+        signature: BytesN<64>,
+        fill_amount: i128,
+        extension: Bytes,
+    ) -> Result<BytesN<32>, HTLCError> {
+        if env.ledger().timestamp() > order.expiry {
+            return Err(HTLCError::OrderExpired);
+        }
+
+        if !order.allowed_resolvers.is_empty() && !order.allowed_resolvers.contains(&resolver) {
+            return Err(HTLCError::ResolverNotWhitelisted);
+        }
+
+        if order.making_amount <= 0 || fill_amount <= 0 || fill_amount > order.making_amount {
+            return Err(HTLCError::InvalidFillAmount);
+        }
+
+        // Fills draw on the making amount until it is used up, and stop once the maker
+        // cancelled the order. Orders filled before fills were tracked count as used up.
+        let maker = Self::account_address(&env, &order.maker);
+        let order_hash = Self::get_fusion_order_hash(env.clone(), order.clone());
+        let filled = Self::get_order_filled_amount(env.clone(), order_hash.clone());
+        if filled == order.making_amount
+            || env
+                .storage()
+                .persistent()
+                .has(&DataKey::UsedOrder(order_hash.clone()))
+            || Self::is_order_cancelled(env.clone(), maker.clone(), order_hash.clone())
+        {
+            return Err(HTLCError::OrderAlreadyUsed);
+        }
+        if fill_amount > order.making_amount - filled {
+            return Err(HTLCError::InvalidFillAmount);
+        }
+
+        env.crypto()
+            .ed25519_verify(&order.maker, &order_hash.clone().into(), &signature);
+        resolver.require_auth();
+
+        // The maker is owed the taking amount of the filled share, rounded in its favour
+        let taking_amount = order
+            .taking_amount
+            .checked_mul(fill_amount)
+            .ok_or(HTLCError::AmountOverflow)?
+            .checked_add(order.making_amount - 1)
+            .ok_or(HTLCError::AmountOverflow)?
+            / order.making_amount;
+
+        let filled_key = DataKey::OrderFilledAmount(order_hash.clone());
+        env.storage()
+            .persistent()
+            .set(&filled_key, &(filled + fill_amount));
+        Self::extend_ttl(&env, &filled_key);
+
+        let now = env.ledger().timestamp();
+        let stage_start = |stage: TimelockStage| match order.timelocks.offset(stage) {
+            0 => 0,
+            offset => now + offset as u64,
+        };
+        let mut params = Self::swap_leg(
+            &env,
+            &maker,
+            &resolver,
+            &order.maker_asset,
+            fill_amount,
+            &order.hashlock,
+            now + order.timelocks.offset(TimelockStage::SrcCancellation) as u64,
+        );
+        params.hash_algo = order.hash_algo;
+        params.safety_deposit = order.safety_deposit;
+        params.safety_deposit_token = Self::native_token_address(&env);
+        params.safety_deposit_payer = resolver.clone();
+        params.public_withdrawal = stage_start(TimelockStage::SrcPublicWithdrawal);
+        params.public_cancellation = stage_start(TimelockStage::SrcPublicCancellation);
+        params.order_hash = Self::get_fill_id(env.clone(), order_hash.clone(), filled);
+        // Every fill of an order is locked with the order's one hashlock
+        params.allow_reuse = filled > 0;
+        params.metadata = order.metadata;
+        params.salt = order.salt;
+        params.extension = extension;
+        let contract_id = Self::create_authorized(&env, params, Funding::Allowance)?;
+        let withdrawal_start = stage_start(TimelockStage::SrcWithdrawal);
+        if withdrawal_start > 0 {
+            let key = DataKey::WithdrawalStart(contract_id.clone());
+            env.storage().persistent().set(&key, &withdrawal_start);
+            Self::extend_ttl(&env, &key);
+        }
+
+        Self::publish_event(
+            &env,
+            (Symbol::new(&env, "OrderFilled"), order_hash),
+            (resolver, fill_amount, taking_amount),
+        );
+
+        Ok(contract_id)
+    }
+
+    /// Gets how much of a Fusion+ order's making amount `fill_order` has locked so far
+    pub fn get_order_filled_amount(env: Env, order_hash: BytesN<32>) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::OrderFilledAmount(order_hash))
+            .unwrap_or(0)
+    }
+
+    /// Gets the contract ID of the Fusion+ fill made once `filled` of the order was locked:
+    /// the order hash for the first fill, otherwise `SHA-256(order_hash || filled)` with
+    /// `filled` as a 16-byte big-endian integer
+    pub fn get_fill_id(env: Env, order_hash: BytesN<32>, filled: i128) -> BytesN<32> {
+        if filled == 0 {
+            return order_hash;
+        }
+        let mut preimage = Bytes::from(order_hash);
+        preimage.extend_from_array(&filled.to_be_bytes());
+        env.crypto().sha256(&preimage)
+    }
+
+    /// Invalidates an unfilled signed order, or the rest of a partially filled Fusion+
+    /// order, identified by its `get_fusion_order_hash` or `get_order_digest`; requires
+    /// the maker's authorization. Later fills fail with `OrderAlreadyUsed`.
+    pub fn cancel_order(env: Env, maker: Address, order_hash: BytesN<32>) {
+        maker.require_auth();
+
+        let key = DataKey::CancelledOrder(maker.clone(), order_hash.clone());
+        env.storage().persistent().set(&key, &true);
+        let extend_to = Self::get_ttl_extension(env.clone());
+        env.storage()
+            .persistent()
+            .extend_ttl(&key, extend_to - DAY_IN_LEDGERS, extend_to);
+
+        Self::publish_event(
+            &env,
+            (Symbol::new(&env, "OrderCancelled"), order_hash),
+            maker,
+        );
+    }
+
+    /// Checks if `maker` cancelled the signed order with this hash
+    pub fn is_order_cancelled(env: Env, maker: Address, order_hash: BytesN<32>) -> bool {
+        env.storage()
+            .persistent()
+            .has(&DataKey::CancelledOrder(maker, order_hash))
+    }
+
+    /// Creates both legs of an intra-Stellar swap atomically, returning the contract IDs
+    /// of the initiator's and the counterparty's HTLCs
+    pub fn create_swap(env: Env, swap: SwapParams) -> Result<(BytesN<32>, BytesN<32>), HTLCError> {
+        if swap.initiator == swap.counterparty {
+            return Err(HTLCError::InvalidSwap);
+        }
+
+        // Staggered timelocks keep the counterparty able to claim after the secret is out
+        if swap.counterparty_timelock >= swap.initiator_timelock {
+            return Err(HTLCError::InvalidTimelock);
+        }
+
+        // Authorization check - both parties fund a leg
+        swap.initiator.require_auth();
+        swap.counterparty.require_auth();
+
+        let initiator_id = Self::create_authorized(
+            &env,
+            Self::swap_leg(
+                &env,
+                &swap.initiator,
+                &swap.counterparty,
+                &swap.initiator_token,
+                swap.initiator_amount,
+                &swap.hashlock,
+                swap.initiator_timelock,
+            ),
+            Funding::Transfer,
+        )?;
+
+        // The mirrored leg deliberately shares the initiator leg's hashlock
+        let mut counterparty_leg = Self::swap_leg(
+            &env,
+            &swap.counterparty,
+            &swap.initiator,
+            &swap.counterparty_token,
+            swap.counterparty_amount,
+            &swap.hashlock,
+            swap.counterparty_timelock,
+        );
+        counterparty_leg.allow_reuse = true;
+        let counterparty_id = Self::create_authorized(&env, counterparty_leg, Funding::Transfer)?;
+
+        Self::publish_event(
+            &env,
+            (Symbol::new(&env, "SwapCreated"),),
+            (initiator_id.clone(), counterparty_id.clone()),
+        );
+
+        Ok((initiator_id, counterparty_id))
+    }
+
+    /// Creates several HTLCs atomically; any failing item reverts the whole batch
+    pub fn create_htlc_batch(
+        env: Env,
+        params: Vec<CreateParams>,
+    ) -> Result<Vec<BytesN<32>>, HTLCError> {
+        // Authorization check - once per distinct sender or safety deposit payer
+        let mut authorized: Vec<Address> = Vec::new(&env);
+        for item in params.iter() {
+            for address in [item.sender, item.safety_deposit_payer] {
+                if !authorized.contains(&address) {
+                    address.require_auth();
+                    authorized.push_back(address);
+                }
+            }
+        }
+
+        let mut contract_ids = Vec::new(&env);
+        for item in params.iter() {
+            contract_ids.push_back(Self::create_authorized(&env, item, Funding::Transfer)?);
+        }
+        Ok(contract_ids)
+    }
+
+    /// Creates a new partially-fillable HTLC whose secrets are committed in a Merkle root.
+    ///
+    /// The amount is split into `parts` equal parts unlocked by `parts + 1` secrets; the
+    /// secret at index `parts` is reserved for the fill that completes the order.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_htlc_partial(
+        env: Env,
+        sender: Address,
+        receiver: Address,
+        amount: i128,
+        token_address: Address,
+        merkle_root: BytesN<32>,
+        parts: u32,
+        timelock: u64,
+        safety_deposit: i128,
+    ) -> Result<BytesN<32>, HTLCError> {
+        if parts == 0 {
+            return Err(HTLCError::InvalidPartsCount);
+        }
+
+        Self::create(
+            &env,
+            CreateParams {
+                sender: sender.clone(),
+                receiver: receiver.clone(),
+                amount,
+                token_address: token_address.clone(),
+                hashlock: merkle_root,
+                hash_algo: HashAlgo::Sha256,
+                timelock,
+                safety_deposit,
+                safety_deposit_token: token_address,
+                parts,
+                public_withdrawal: 0,
+                public_cancellation: 0,
+                order_hash: BytesN::from_array(&env, &[0; 32]),
+                resolvers_only: false,
+                metadata: CrossChainMetadata::none(&env),
+                basket: Vec::new(&env),
+                safety_deposit_payer: sender.clone(),
+                salt: BytesN::from_array(&env, &[0; 32]),
+                allow_reuse: false,
+                taker: receiver.clone(),
+                hashlocks: Vec::new(&env),
+                threshold: 0,
+                callback: sender.clone(),
+                callback_data: Bytes::new(&env),
+                extension: Bytes::new(&env),
+                rescue_delay: 0,
+                refund_to: sender.clone(),
+                commit_reveal: false,
+                memo: Bytes::new(&env),
+                referral: sender,
+                referral_bps: 0,
+                use_deposit_policy: false,
+                vesting: VestingSchedule::none(),
+            },
+        )
+    }
+
+    /// Creates a new HTLC locking native XLM through the native asset contract
+    pub fn create_htlc_native(
+        env: Env,
+        sender: Address,
+        receiver: Address,
+        amount: i128,
+        hashlock: BytesN<32>,
+        timelock: u64,
+        safety_deposit: i128,
+    ) -> Result<BytesN<32>, HTLCError> {
+        let token_address = Self::native_token_address(&env);
+        Self::create_htlc(
+            env,
+            sender,
+            receiver,
+            amount,
+            token_address,
+            hashlock,
+            timelock,
+            safety_deposit,
+        )
+    }
+
+    /// Withdraws funds by revealing the preimage
+    pub fn withdraw(
+        env: Env,
+        contract_id: BytesN<32>,
+        preimage: BytesN<32>,
+    ) -> Result<(), HTLCError> {
+        let htlc_data = Self::get_htlc_data(&env, &contract_id)?;
+        Self::withdraw_htlc(&env, &contract_id, htlc_data, Record::Full, preimage)
+    }
+
+    /// Withdraws funds to `destination` by revealing the preimage, authorized by the receiver
+    pub fn withdraw_to(
+        env: Env,
+        contract_id: BytesN<32>,
+        preimage: BytesN<32>,
+        destination: Address,
+    ) -> Result<(), HTLCError> {
+        let htlc_data = Self::get_htlc_data(&env, &contract_id)?;
+
+        // Reentrancy protection
+        Self::ensure_unlocked(&env, &contract_id)?;
+
+        // Authorization check - only receiver can withdraw
+        htlc_data.receiver.require_auth();
+
+        // Status check
+        Self::ensure_active(&htlc_data)?;
+        Self::ensure_taker_window_closed(&env, &htlc_data)?;
+
+        // Safety deposit goes back to its payer unless slashed
+        let deposit_recipient = Self::deposit_recipient(&env, &htlc_data, &htlc_data.receiver);
+        Self::execute_withdraw(
+            &env,
+            &contract_id,
+            htlc_data,
+            preimage,
+            &destination,
+            &deposit_recipient,
+        )
+    }
+
+    /// Claims an HTLC and locks what the receiver is paid into a new HTLC in the same call,
+    /// returning the new contract ID. Multi-hop routes never leave the intermediary holding
+    /// free funds. `new_params.sender` must be the receiver and `new_params.token_address`
+    /// the claimed token, and `new_params.amount` is replaced by the amount paid out.
+    pub fn withdraw_and_relock(
+        env: Env,
+        contract_id: BytesN<32>,
+        preimage: BytesN<32>,
+        new_params: CreateParams,
+    ) -> Result<BytesN<32>, HTLCError> {
+        let htlc_data = Self::get_htlc_data(&env, &contract_id)?;
+
+        // Reentrancy protection
+        Self::ensure_unlocked(&env, &contract_id)?;
+
+        // Authorization check - the receiver funds the new HTLC, as does its deposit payer
+        htlc_data.receiver.require_auth();
+        if new_params.safety_deposit_payer != htlc_data.receiver {
+            new_params.safety_deposit_payer.require_auth();
+        }
+
+        // Status check
+        Self::ensure_active(&htlc_data)?;
+        Self::ensure_taker_window_closed(&env, &htlc_data)?;
+
+        // The new HTLC must lock exactly the claimed asset, taken from the receiver
+        if new_params.sender != htlc_data.receiver
+            || new_params.token_address != htlc_data.token_address
+        {
+            return Err(HTLCError::InvalidSwap);
+        }
+        if !htlc_data.basket.is_empty() || !new_params.basket.is_empty() {
+            return Err(HTLCError::InvalidBasket);
+        }
+        if htlc_data.vesting.tranches > 0 {
+            return Err(HTLCError::InvalidSwap);
+        }
+
+        // The claimed amount stays in the contract and funds the new HTLC
+        let deposit_recipient = Self::deposit_recipient(&env, &htlc_data, &htlc_data.receiver);
+        let paid = Self::claim(
+            &env,
+            &contract_id,
+            htlc_data,
+            Record::Full,
+            preimage,
+            &env.current_contract_address(),
+            &deposit_recipient,
+        )?;
+        let new_contract_id = Self::create_authorized(
+            &env,
+            CreateParams {
+                amount: paid,
+                ..new_params
+            },
+            Funding::Held,
+        )?;
+
+        Self::publish_event(
+            &env,
+            (Symbol::new(&env, "HTLCRelocked"), contract_id),
+            (new_contract_id.clone(), paid),
+        );
+
+        Ok(new_contract_id)
+    }
+
+    /// Pays out the tranches of a revealed vesting HTLC unlocked since the last claim to the
+    /// recipient fixed at withdrawal, returning the amount paid. Anyone may call it.
+    ///
+    /// Fails with `NotVesting` for HTLCs without a schedule and with `NothingVested` until
+    /// the next tranche unlocks.
+    pub fn claim_tranche(env: Env, contract_id: BytesN<32>) -> Result<i128, HTLCError> {
+        let htlc_data = Self::get_htlc_data(&env, &contract_id)?;
+        if htlc_data.vesting.tranches == 0 {
+            return Err(HTLCError::NotVesting);
+        }
+
+        let mut progress = match htlc_data.status {
+            HTLCStatus::Active => return Err(HTLCError::ContractNotCompleted),
+            HTLCStatus::Refunded => return Err(HTLCError::AlreadyRefunded),
+            HTLCStatus::Withdrawn => Self::get_vesting_progress(env.clone(), contract_id)
+                .ok_or(HTLCError::AlreadyWithdrawn)?,
+        };
+
+        match Self::release_tranches(&env, &htlc_data, &mut progress) {
+            0 => Err(HTLCError::NothingVested),
+            paid => Ok(paid),
+        }
+    }
+
+    /// Withdraws funds to the receiver on the strength of the receiver's ed25519 signature
+    /// over `get_withdraw_digest`, so any third party can submit and pay the fee.
+    ///
+    /// The receiver must be a Stellar account. Panics if the signature does not verify.
+    pub fn withdraw_with_sig(
+        env: Env,
+        contract_id: BytesN<32>,
+        preimage: BytesN<32>,
+        receiver_sig: BytesN<64>,
+        expiry: u64,
+    ) -> Result<(), HTLCError> {
+        let htlc_data = Self::get_htlc_data(&env, &contract_id)?;
+
+        // Reentrancy protection
+        Self::ensure_unlocked(&env, &contract_id)?;
+
+        if env.ledger().timestamp() > expiry {
+            return Err(HTLCError::SignatureExpired);
+        }
+
+        // Authorization check - the receiver's signature stands in for require_auth
+        let receiver_key =
+            Self::account_public_key(&env, &htlc_data.receiver).ok_or(HTLCError::InvalidSigner)?;
+        let digest =
+            Self::get_withdraw_digest(env.clone(), contract_id.clone(), preimage.clone(), expiry);
+        env.crypto()
+            .ed25519_verify(&receiver_key, &digest.into(), &receiver_sig);
+
+        // Status check
+        Self::ensure_active(&htlc_data)?;
+        Self::ensure_taker_window_closed(&env, &htlc_data)?;
+
+        // Funds go to receiver, safety deposit goes back to its payer unless slashed
+        let destination = htlc_data.receiver.clone();
+        let deposit_recipient = Self::deposit_recipient(&env, &htlc_data, &htlc_data.receiver);
+        Self::execute_withdraw(
+            &env,
+            &contract_id,
+            htlc_data,
+            preimage,
+            &destination,
+            &deposit_recipient,
+        )
+    }
+
+    /// Withdraws several HTLCs by revealing their preimages; any failing item reverts the
+    /// whole batch. Each pair is `(contract_id, preimage)`.
+    pub fn withdraw_batch(
+        env: Env,
+        withdrawals: Vec<(BytesN<32>, BytesN<32>)>,
+    ) -> Result<(), HTLCError> {
+        // Authorization check - receivers or takers in their exclusive window, once per address
+        let mut withdrawers: Vec<Address> = Vec::new(&env);
+        for (contract_id, _) in withdrawals.iter() {
+            let htlc_data = Self::get_htlc_data(&env, &contract_id)?;
+            let withdrawer = Self::withdrawer(&env, &htlc_data);
+            if !withdrawers.contains(&withdrawer) {
+                withdrawer.require_auth();
+                withdrawers.push_back(withdrawer);
+            }
+        }
+
+        let mut contract_ids = Vec::new(&env);
+        for (contract_id, preimage) in withdrawals.iter() {
+            let htlc_data = Self::get_htlc_data(&env, &contract_id)?;
+
+            // Reentrancy protection
+            Self::ensure_unlocked(&env, &contract_id)?;
+
+            // Status check
+            Self::ensure_active(&htlc_data)?;
+
+            // Funds go to receiver, safety deposit goes back to its payer unless slashed
+            let destination = htlc_data.receiver.clone();
+            let deposit_recipient = Self::deposit_recipient(&env, &htlc_data, &htlc_data.receiver);
+            Self::execute_withdraw(
+                &env,
+                &contract_id,
+                htlc_data,
+                preimage,
+                &destination,
+                &deposit_recipient,
+            )?;
+            contract_ids.push_back(contract_id);
+        }
+
+        Self::publish_event(
+            &env,
+            (Symbol::new(&env, "HTLCBatchWithdraw"),),
+            contract_ids,
+        );
+
+        Ok(())
+    }
+
+    /// Withdraws funds to the receiver on behalf of anyone holding the preimage once the
+    /// exclusive withdrawal period has ended. The caller earns the safety deposit.
+    pub fn public_withdraw(
+        env: Env,
+        caller: Address,
+        contract_id: BytesN<32>,
+        preimage: BytesN<32>,
+    ) -> Result<(), HTLCError> {
+        let htlc_data = Self::get_htlc_data(&env, &contract_id)?;
+
+        // Reentrancy protection
+        Self::ensure_unlocked(&env, &contract_id)?;
+
+        // Authorization check - caller claims the safety deposit
+        caller.require_auth();
+
+        // Status check
+        Self::ensure_active(&htlc_data)?;
+
+        // Public withdrawal must be enabled and its period started
+        if htlc_data.public_withdrawal == 0
+            || env.ledger().timestamp() < htlc_data.public_withdrawal
+        {
+            return Err(HTLCError::PublicWithdrawalNotStarted);
+        }
+
+        // Commit-reveal HTLCs never take the preimage in a single step
+        if htlc_data.commit_reveal {
+            return Err(HTLCError::InvalidPublicWithdrawal);
+        }
+
+        let destination = htlc_data.receiver.clone();
+        Self::execute_withdraw(
+            &env,
+            &contract_id,
+            htlc_data,
+            preimage,
+            &destination,
+            &caller,
+        )
+    }
+
+    /// First step of a commit-reveal public withdrawal: records `commitment`, computed with
+    /// `get_withdraw_commitment(preimage, caller)`, without exposing the preimage. A new
+    /// commitment by the same caller replaces the previous one.
+    pub fn commit_withdraw(
+        env: Env,
+        caller: Address,
+        contract_id: BytesN<32>,
+        commitment: BytesN<32>,
+    ) -> Result<(), HTLCError> {
+        let htlc_data = Self::get_htlc_data(&env, &contract_id)?;
+
+        // Authorization check - the committer later claims the safety deposit
+        caller.require_auth();
+
+        // Status check
+        Self::ensure_active(&htlc_data)?;
+
+        if !htlc_data.commit_reveal {
+            return Err(HTLCError::InvalidPublicWithdrawal);
+        }
+
+        // Timelock check - must commit before expiry
+        if env.ledger().timestamp() >= htlc_data.timelock {
+            return Err(HTLCError::TimelockExpired);
+        }
+
+        let key = DataKey::WithdrawCommit(contract_id.clone(), caller.clone());
+        env.storage()
+            .temporary()
+            .set(&key, &(commitment.clone(), env.ledger().sequence()));
+        env.storage()
+            .temporary()
+            .extend_ttl(&key, DAY_IN_LEDGERS, DAY_IN_LEDGERS);
+
+        Self::publish_event(
+            &env,
+            (Symbol::new(&env, "WithdrawCommitted"), contract_id),
+            (caller, commitment),
+        );
+
+        Ok(())
+    }
+
+    /// Second step of a commit-reveal public withdrawal: once the public withdrawal period
+    /// has started and the caller's commitment is at least `REVEAL_DELAY_LEDGERS` old,
+    /// withdraws to the receiver and pays the caller the safety deposit.
+    ///
+    /// A third party copying the preimage from a pending reveal cannot claim in time, as its
+    /// own commitment would still be too young. Commitments expire after a day of ledgers.
+    pub fn reveal_withdraw(
+        env: Env,
+        caller: Address,
+        contract_id: BytesN<32>,
+        preimage: BytesN<32>,
+    ) -> Result<(), HTLCError> {
+        let htlc_data = Self::get_htlc_data(&env, &contract_id)?;
+
+        // Reentrancy protection
+        Self::ensure_unlocked(&env, &contract_id)?;
+
+        // Authorization check - caller claims the safety deposit
+        caller.require_auth();
+
+        // Status check
+        Self::ensure_active(&htlc_data)?;
+
+        if !htlc_data.commit_reveal {
+            return Err(HTLCError::InvalidPublicWithdrawal);
+        }
+
+        // Public withdrawal period must have started
+        if env.ledger().timestamp() < htlc_data.public_withdrawal {
+            return Err(HTLCError::PublicWithdrawalNotStarted);
+        }
+
+        // The commitment must bind this preimage to the caller and have aged
+        let key = DataKey::WithdrawCommit(contract_id.clone(), caller.clone());
+        let (commitment, committed_at) = env
+            .storage()
+            .temporary()
+            .get::<_, (BytesN<32>, u32)>(&key)
+            .ok_or(HTLCError::InvalidPreimage)?;
+        if commitment
+            != Self::get_withdraw_commitment(env.clone(), preimage.clone(), caller.clone())
+        {
+            return Err(HTLCError::InvalidPreimage);
+        }
+        if env.ledger().sequence() < committed_at.saturating_add(REVEAL_DELAY_LEDGERS) {
+            return Err(HTLCError::PublicWithdrawalNotStarted);
+        }
+        env.storage().temporary().remove(&key);
+
+        let destination = htlc_data.receiver.clone();
+        Self::execute_withdraw(
+            &env,
+            &contract_id,
+            htlc_data,
+            preimage,
+            &destination,
+            &caller,
+        )
+    }
+
+    /// Computes the commitment `commit_withdraw` expects: `SHA-256(preimage || XDR(claimer))`
+    pub fn get_withdraw_commitment(env: Env, preimage: BytesN<32>, claimer: Address) -> BytesN<32> {
+        let mut data = Bytes::from_array(&env, &preimage.to_array());
+        data.append(&claimer.to_xdr(&env));
+        env.crypto().sha256(&data)
+    }
+
+    /// Withdraws a k-of-n HTLC by revealing at least `threshold` of the preimages of its
+    /// `hashlocks`, in any order. The preimages are published in the
+    /// `HTLCThresholdWithdraw` event rather than stored.
+    pub fn withdraw_threshold(
+        env: Env,
+        contract_id: BytesN<32>,
+        preimages: Vec<BytesN<32>>,
+    ) -> Result<(), HTLCError> {
+        let mut htlc_data = Self::get_htlc_data(&env, &contract_id)?;
+
+        // Reentrancy protection
+        Self::ensure_unlocked(&env, &contract_id)?;
+
+        // Authorization check - the taker during its exclusive window, otherwise the receiver
+        Self::withdrawer(&env, &htlc_data).require_auth();
+
+        // Status check
+        Self::ensure_active(&htlc_data)?;
+
+        if htlc_data.threshold == 0 {
+            return Err(HTLCError::InvalidThreshold);
+        }
+
+        Self::ensure_not_frozen(&env, &htlc_data)?;
+
+        // Timelock check - must withdraw before expiry
+        if env.ledger().timestamp() >= htlc_data.timelock {
+            return Err(HTLCError::TimelockExpired);
+        }
+
+        Self::ensure_resolver_allowed(&env, &htlc_data)?;
+
+        // Each preimage must open a distinct hashlock of the set
+        let mut opened: Vec<u32> = Vec::new(&env);
+        for preimage in preimages.iter() {
+            let leaf = Self::hash(&env, htlc_data.hash_algo, &preimage.into());
+            let position = htlc_data
+                .hashlocks
+                .first_index_of(&leaf)
+                .ok_or(HTLCError::InvalidPreimage)?;
+            if opened.contains(position) {
+                return Err(HTLCError::InvalidPreimage);
+            }
+            opened.push_back(position);
+        }
+        if opened.len() < htlc_data.threshold {
+            return Err(HTLCError::InvalidPreimage);
+        }
+
+        // Funds go to receiver, safety deposit goes back to its payer unless slashed
+        let destination = htlc_data.receiver.clone();
+        let deposit_recipient = Self::deposit_recipient(&env, &htlc_data, &htlc_data.receiver);
+        Self::settle_withdraw(
+            &env,
+            &contract_id,
+            &mut htlc_data,
+            Record::Full,
+            BytesN::from_array(&env, &[0; 32]),
+            &destination,
+            &deposit_recipient,
+        );
+
+        Self::publish_lifecycle_event(
+            &env,
+            (Symbol::new(&env, "HTLCThresholdWithdraw"), contract_id),
+            &htlc_data,
+            preimages,
+        );
+
+        Ok(())
+    }
+
+    /// Withdraws one fill of a partially-fillable HTLC by revealing the secret for `index`.
+    ///
+    /// The index must match the part reached by the cumulative filled amount, and `proof`
+    /// must link `H(index || H(secret))` to the stored Merkle root, where `H` is the
+    /// HTLC's hash algorithm.
+    pub fn withdraw_partial(
+        env: Env,
+        contract_id: BytesN<32>,
+        secret: BytesN<32>,
+        index: u32,
+        proof: Vec<BytesN<32>>,
+        fill_amount: i128,
+    ) -> Result<(), HTLCError> {
+        let mut htlc_data = Self::get_htlc_data(&env, &contract_id)?;
+
+        // Reentrancy protection
+        Self::ensure_unlocked(&env, &contract_id)?;
+
+        // Authorization check - the taker during its exclusive window, otherwise the receiver
+        Self::withdrawer(&env, &htlc_data).require_auth();
+
+        // Status check
+        Self::ensure_active(&htlc_data)?;
+
+        if htlc_data.parts == 0 {
+            return Err(HTLCError::PartialFillNotAllowed);
+        }
+
+        Self::ensure_not_frozen(&env, &htlc_data)?;
+
+        Self::ensure_resolver_allowed(&env, &htlc_data)?;
+
+        // Timelock check - must withdraw before expiry
+        let current_timestamp = env.ledger().timestamp();
+        if current_timestamp >= htlc_data.timelock {
+            return Err(HTLCError::TimelockExpired);
+        }
+
+        // Fill amount must fit in the unfilled remainder
+        let remaining_amount = htlc_data.amount - htlc_data.filled_amount;
+        if fill_amount <= 0 || fill_amount > remaining_amount {
+            return Err(HTLCError::InvalidFillAmount);
+        }
+
+        // Secret index must match the part reached by this fill and not reuse a spent secret
+        let expected_index = Self::fill_index(&htlc_data, fill_amount);
+        if index != expected_index || index < htlc_data.next_fill_index {
+            return Err(HTLCError::InvalidFillIndex);
+        }
+
+        // Validate secret against the Merkle root
+        let secret_bytes: Bytes = secret.clone().into();
+        let secret_hash = Self::hash(&env, htlc_data.hash_algo, &secret_bytes);
+        let leaf = Self::merkle_leaf(&env, htlc_data.hash_algo, index, &secret_hash);
+        if !Self::verify_merkle_proof(
+            &env,
+            htlc_data.hash_algo,
+            &leaf,
+            &proof,
+            &htlc_data.hashlock,
+        ) {
+            return Err(HTLCError::InvalidMerkleProof);
+        }
+
+        // The fill is recorded before any transfer, as in `settle_withdraw`, so a re-entrant
+        // call already sees its secret index spent and no separate guard write is needed
+        htlc_data.filled_amount += fill_amount;
+        htlc_data.next_fill_index = index + 1;
+        htlc_data.preimage = secret.clone();
+        let completed = htlc_data.filled_amount == htlc_data.amount;
+        if completed {
+            htlc_data.status = HTLCStatus::Withdrawn;
+            htlc_data.completed_at = current_timestamp;
+        }
+        Self::save_htlc_data(&env, &contract_id, &htlc_data);
+        if completed {
+            Self::record_completion(&env, &contract_id, &htlc_data);
+        }
+
+        // Transfer the filled part to receiver, less the protocol and referral fees
+        let paid = Self::transfer_with_fee(
+            &env,
+            &htlc_data,
+            &htlc_data.token_address,
+            &htlc_data.receiver,
+            fill_amount,
+        );
+
+        // Completing the order returns the safety deposit to sender
+        if completed {
+            let deposit_recipient = Self::deposit_recipient(&env, &htlc_data, &htlc_data.receiver);
+            Self::pay_safety_deposit(
+                &env,
+                &contract_id,
+                &htlc_data,
+                &deposit_recipient,
+                htlc_data.safety_deposit,
+            );
+        }
+
+        Self::publish_lifecycle_event(
+            &env,
+            (
+                Symbol::new(&env, "HTLCPartialWithdraw"),
+                contract_id.clone(),
+            ),
+            &htlc_data,
+            (secret, index, fill_amount, htlc_data.filled_amount),
+        );
+
+        let receiver = htlc_data.receiver.clone();
+        Self::notify_withdrawn(&env, &contract_id, &htlc_data, &receiver, paid);
+
+        Ok(())
+    }
+
+    /// Refunds funds after timelock expiry
+    pub fn refund(env: Env, contract_id: BytesN<32>) -> Result<(), HTLCError> {
+        let htlc_data = Self::get_htlc_data(&env, &contract_id)?;
+        Self::refund_htlc(&env, &contract_id, htlc_data, Record::Full)
+    }
+
+    /// Withdraws the HTLC the immutables hash to, so a relayer-supplied parameter set is
+    /// checked against the contract ID before anything moves
+    pub fn withdraw_with_immutables(
+        env: Env,
+        immutables: HTLCImmutables,
+        preimage: BytesN<32>,
+    ) -> Result<(), HTLCError> {
+        let (contract_id, htlc_data, record) = Self::load_by_immutables(&env, &immutables)?;
+        Self::withdraw_htlc(&env, &contract_id, htlc_data, record, preimage)
+    }
+
+    /// Refunds the HTLC the immutables hash to after timelock expiry
+    pub fn refund_with_immutables(env: Env, immutables: HTLCImmutables) -> Result<(), HTLCError> {
+        let (contract_id, htlc_data, record) = Self::load_by_immutables(&env, &immutables)?;
+        Self::refund_htlc(&env, &contract_id, htlc_data, record)
+    }
+
+    /// Closes out an HTLC stuck past its `rescue_delay`, returning what it still holds to
+    /// the depositors: the amount and basket legs to `refund_to`, the safety deposit to its
+    /// payer.
+    ///
+    /// Each transfer is attempted on its own so one misbehaving token cannot trap the
+    /// others; whatever fails to move is released to the admin `rescue` and its token
+    /// reported in the `HTLCRescued` event. Callable by the sender, or by the admin when
+    /// `Config.admin_rescue` is set.
+    pub fn rescue_htlc(env: Env, contract_id: BytesN<32>) -> Result<(), HTLCError> {
+        let mut htlc_data = Self::get_htlc_data(&env, &contract_id)?;
+
+        // Reentrancy protection
+        Self::ensure_unlocked(&env, &contract_id)?;
+
+        // Authorization check - the sender, or the admin when configured
+        let config = Self::get_config(env.clone()).ok();
+        match config {
+            Some(config) if config.admin_rescue => config.admin.require_auth(),
+            _ => htlc_data.sender.require_auth(),
+        }
+
+        // Status check
+        Self::ensure_active(&htlc_data)?;
+
+        if htlc_data.rescue_delay == 0
+            || env.ledger().timestamp() < htlc_data.timestamp + htlc_data.rescue_delay
+        {
+            return Err(HTLCError::TimelockNotExpired);
+        }
+
+        // Set reentrancy lock
+        Self::lock(&env, &contract_id);
+
+        let mut stuck_tokens = Vec::new(&env);
+        let mut payouts = Vec::from_array(
+            &env,
+            [(
+                htlc_data.token_address.clone(),
+                htlc_data.refund_to.clone(),
+                htlc_data.amount - htlc_data.filled_amount,
+            )],
+        );
+        for leg in htlc_data.basket.iter() {
+            payouts.push_back((leg.token, htlc_data.refund_to.clone(), leg.amount));
+        }
+        payouts.push_back((
+            htlc_data.safety_deposit_token.clone(),
+            htlc_data.safety_deposit_payer.clone(),
+            htlc_data.safety_deposit,
+        ));
+        for (token, recipient, amount) in payouts.iter() {
+            if amount <= 0 {
+                continue;
+            }
+            let transferred = token::Client::new(&env, &token).try_transfer(
+                &env.current_contract_address(),
+                &recipient,
+                &amount,
+            );
+            if !matches!(transferred, Ok(Ok(()))) && !stuck_tokens.contains(&token) {
+                stuck_tokens.push_back(token.clone());
+            }
+            Self::adjust_locked(&env, &token, -amount);
+        }
+
+        htlc_data.status = HTLCStatus::Refunded;
+        htlc_data.completed_at = env.ledger().timestamp();
+        Self::save_htlc_data(&env, &contract_id, &htlc_data);
+        Self::record_completion(&env, &contract_id, &htlc_data);
+        Self::unlock(&env, &contract_id);
+
+        Self::publish_lifecycle_event(
+            &env,
+            (Symbol::new(&env, "HTLCRescued"), contract_id),
+            &htlc_data,
+            stuck_tokens,
+        );
+
+        Ok(())
+    }
+
+    /// Cancels an HTLC before its timelock when sender and receiver both agree, refunding
+    /// the sender immediately
+    pub fn cancel_by_agreement(env: Env, contract_id: BytesN<32>) -> Result<(), HTLCError> {
+        let mut htlc_data = Self::get_htlc_data(&env, &contract_id)?;
+
+        // Reentrancy protection
+        Self::ensure_unlocked(&env, &contract_id)?;
+
+        // Authorization check - both parties must consent
+        htlc_data.sender.require_auth();
+        if htlc_data.receiver != htlc_data.sender {
+            htlc_data.receiver.require_auth();
+        }
+
+        // Status check
+        Self::ensure_active(&htlc_data)?;
+
+        // Safety deposit goes back to its payer
+        let deposit_recipient = htlc_data.safety_deposit_payer.clone();
+        Self::execute_refund(
+            &env,
+            &contract_id,
+            &mut htlc_data,
+            Record::Full,
+            &deposit_recipient,
+        );
+
+        Self::publish_lifecycle_event(
+            &env,
+            (Symbol::new(&env, "HTLCCancelled"), contract_id.clone()),
+            &htlc_data,
+            contract_id,
+        );
+
+        Ok(())
+    }
+
+    /// Refunds the sender on behalf of anyone once the public cancellation period has
+    /// started. The caller earns the safety deposit for cleaning up the escrow.
+    pub fn public_refund(
+        env: Env,
+        caller: Address,
+        contract_id: BytesN<32>,
+    ) -> Result<(), HTLCError> {
+        let mut htlc_data = Self::get_htlc_data(&env, &contract_id)?;
+
+        // Reentrancy protection
+        Self::ensure_unlocked(&env, &contract_id)?;
+
+        // Authorization check - caller claims the safety deposit
+        caller.require_auth();
+
+        // Status check
+        Self::ensure_active(&htlc_data)?;
+
+        // Public cancellation must be enabled and its period started
+        if htlc_data.public_cancellation == 0
+            || env.ledger().timestamp() < htlc_data.public_cancellation
+        {
+            return Err(HTLCError::PublicCancellationNotStarted);
+        }
+
+        Self::execute_refund(&env, &contract_id, &mut htlc_data, Record::Full, &caller);
+
+        // Emit HTLCRefund event - 1inch Fusion+ compatible
+        Self::publish_lifecycle_event(
+            &env,
+            (Symbol::new(&env, "HTLCRefund"), contract_id.clone()),
+            &htlc_data,
+            contract_id,
+        );
+
+        Ok(())
+    }
+
+    /// Moves the timelock of an active HTLC later when sender and receiver both agree, e.g.
+    /// when congestion on the counterparty chain delays relaying the secret
+    pub fn extend_timelock(
+        env: Env,
+        contract_id: BytesN<32>,
+        new_timelock: u64,
+    ) -> Result<(), HTLCError> {
+        let mut htlc_data = Self::get_htlc_data(&env, &contract_id)?;
+
+        // Authorization check - both parties must consent
+        htlc_data.sender.require_auth();
+        if htlc_data.receiver != htlc_data.sender {
+            htlc_data.receiver.require_auth();
+        }
+
+        // Status check
+        Self::ensure_active(&htlc_data)?;
+
+        // The new timelock must be later, in the future and within the configured maximum
+        let old_timelock = htlc_data.timelock;
+        let current_timestamp = env.ledger().timestamp();
+        let max_duration = Self::get_timelock_bounds(env.clone()).max_timelock_duration;
+        if new_timelock <= old_timelock
+            || new_timelock <= current_timestamp
+            || new_timelock - current_timestamp > max_duration
+            || new_timelock - htlc_data.timestamp > u32::MAX as u64
+        {
+            return Err(HTLCError::InvalidTimelock);
+        }
+
+        // Public cancellation still cannot open before the timelock
+        if htlc_data.public_cancellation != 0 && htlc_data.public_cancellation < new_timelock {
+            return Err(HTLCError::InvalidPublicCancellation);
+        }
+
+        // Nor may the rescue window
+        if htlc_data.rescue_delay != 0
+            && htlc_data.timestamp.saturating_add(htlc_data.rescue_delay) <= new_timelock
+        {
+            return Err(HTLCError::InvalidTimelock);
+        }
+
+        htlc_data.timelock = new_timelock;
+        Self::save_htlc_data(&env, &contract_id, &htlc_data);
+
+        Self::publish_lifecycle_event(
+            &env,
+            (Symbol::new(&env, "HTLCTimelockExtended"), contract_id),
+            &htlc_data,
+            (old_timelock, new_timelock),
+        );
+
+        Ok(())
+    }
+
+    /// Gets HTLC data by contract ID
+    pub fn get_htlc(env: Env, contract_id: BytesN<32>) -> Result<HTLCData, HTLCError> {
+        Self::get_htlc_data(&env, &contract_id)
+    }
+
+    /// Gets the HTLC the immutables hash to, including escrows from `create_escrow`
+    pub fn get_escrow(env: Env, immutables: HTLCImmutables) -> Result<HTLCData, HTLCError> {
+        Self::load_by_immutables(&env, &immutables).map(|(_, htlc_data, _)| htlc_data)
+    }
+
+    /// Gets HTLC data by contract ID, or `None` if no such HTLC exists
+    pub fn get_htlc_opt(env: Env, contract_id: BytesN<32>) -> Option<HTLCData> {
+        Self::get_htlc_data(&env, &contract_id).ok()
+    }
+
+    /// Checks if contract exists
+    pub fn contract_exists(env: Env, contract_id: BytesN<32>) -> bool {
+        env.storage()
+            .persistent()
+            .has(&DataKey::HTLCEntry(contract_id.clone()))
+            || Self::is_legacy_htlc(&env, &contract_id)
+            || env
+                .storage()
+                .persistent()
+                .has(&DataKey::Escrow(contract_id.clone()))
+    }
+
+    /// Checks several contract IDs at once, returning one flag per ID in input order
+    pub fn contracts_exist(env: Env, contract_ids: Vec<BytesN<32>>) -> Vec<bool> {
+        let mut exists = Vec::new(&env);
+        for contract_id in contract_ids.iter() {
+            exists.push_back(Self::contract_exists(env.clone(), contract_id));
+        }
+        exists
+    }
+
+    /// Gets contract status
+    pub fn get_status(env: Env, contract_id: BytesN<32>) -> Result<HTLCStatus, HTLCError> {
+        let htlc_data = Self::get_htlc_data(&env, &contract_id)?;
+        Ok(htlc_data.status)
+    }
+
+    /// Extends the storage TTL of an HTLC; callable by anyone keeping a long escrow alive
+    pub fn extend_htlc_ttl(env: Env, contract_id: BytesN<32>) -> Result<(), HTLCError> {
+        let escrow_key = DataKey::Escrow(contract_id.clone());
+        let entry_key = if env.storage().persistent().has(&escrow_key) {
+            escrow_key
+        } else {
+            // Entries in an old layout are migrated, which extends them too
+            if Self::get_storage_version(env.clone(), contract_id.clone())? != HTLC_STORAGE_VERSION
+            {
+                Self::migrate(env, contract_id)?;
+                return Ok(());
+            }
+            DataKey::HTLCEntry(contract_id.clone())
+        };
+
+        // Tranches still locked after the reveal must outlive the entry's own extension,
+        // and so must the fee they are charged
+        let vesting_key = DataKey::Vesting(contract_id.clone());
+        if env.storage().persistent().has(&vesting_key) {
+            Self::extend_ttl(&env, &vesting_key);
+        }
+        let fee_key = DataKey::Fee(contract_id.clone());
+        if env.storage().persistent().has(&fee_key) {
+            Self::extend_ttl(&env, &fee_key);
+        }
+        let withdrawal_start_key = DataKey::WithdrawalStart(contract_id.clone());
+        if env.storage().persistent().has(&withdrawal_start_key) {
+            Self::extend_ttl(&env, &withdrawal_start_key);
+        }
+        Self::extend_ttl(&env, &entry_key);
+        Ok(())
+    }
+
+    /// Rewrites an HTLC stored in an older layout in the current one; callable by anyone
+    /// and a no-op for entries that are already current. Returns the layout version the
+    /// entry is stored in afterwards. That stays 0 for a first-release entry whose timelock
+    /// is too far past creation for the compact layout, which is instead rewritten as a
+    /// full `HTLCData` under the legacy key
+    pub fn migrate(env: Env, contract_id: BytesN<32>) -> Result<u32, HTLCError> {
+        let from_version = Self::get_storage_version(env.clone(), contract_id.clone())?;
+        if from_version == HTLC_STORAGE_VERSION {
+            return Ok(from_version);
+        }
+
+        let htlc_data = Self::get_htlc_data(&env, &contract_id)?;
+        Self::save_htlc_data(&env, &contract_id, &htlc_data);
+        let to_version = Self::get_storage_version(env.clone(), contract_id.clone())?;
+        if to_version != from_version {
+            Self::publish_event(
+                &env,
+                (Symbol::new(&env, "HTLCMigrated"), contract_id),
+                (from_version, to_version),
+            );
+        }
+        Ok(to_version)
+    }
+
+    /// Gets the layout version an HTLC is stored in, 0 for an entry under the legacy key
+    /// written by the first release
+    pub fn get_storage_version(env: Env, contract_id: BytesN<32>) -> Result<u32, HTLCError> {
+        match Self::get_stored_htlc(&env, &contract_id) {
+            Some(stored) => Ok(stored.version),
+            None if Self::is_legacy_htlc(&env, &contract_id) => Ok(0),
+            None => Err(HTLCError::ContractNotFound),
+        }
+    }
+
+    /// Removes a withdrawn or refunded HTLC once the cleanup grace period has passed;
+    /// callable by anyone
+    pub fn cleanup(env: Env, contract_id: BytesN<32>) -> Result<(), HTLCError> {
+        let htlc_data = Self::get_htlc_data(&env, &contract_id)?;
+
+        // A vesting HTLC is only complete once its last tranche has been paid
+        if htlc_data.status == HTLCStatus::Active
+            || Self::get_vesting_progress(env.clone(), contract_id.clone()).is_some()
+        {
+            return Err(HTLCError::ContractNotCompleted);
+        }
+
+        if env.ledger().timestamp() < htlc_data.completed_at + CLEANUP_GRACE_PERIOD {
+            return Err(HTLCError::CleanupTooEarly);
+        }
+
+        if Self::is_legacy_htlc(&env, &contract_id) {
+            env.storage()
+                .persistent()
+                .remove(&DataKey::HTLCData(contract_id.clone()));
+        } else {
+            env.storage()
+                .persistent()
+                .remove(&DataKey::HTLCEntry(contract_id.clone()));
+        }
+        env.storage()
+            .persistent()
+            .remove(&DataKey::Fee(contract_id.clone()));
+        env.storage()
+            .persistent()
+            .remove(&DataKey::WithdrawalStart(contract_id.clone()));
+        Self::index_remove(
+            &env,
+            &DataKey::SenderHtlcs(htlc_data.sender.clone()),
+            &contract_id,
+        );
+        Self::index_remove(
+            &env,
+            &DataKey::ReceiverHtlcs(htlc_data.receiver.clone()),
+            &contract_id,
+        );
+
+        Self::publish_lifecycle_event(
+            &env,
+            (Symbol::new(&env, "HTLCCleanup"), contract_id),
+            &htlc_data,
+            (),
+        );
+
+        Ok(())
+    }
+
+    /// Gets a page of the contract IDs created by `sender`, oldest first
+    pub fn get_htlcs_by_sender(
+        env: Env,
+        sender: Address,
+        offset: u32,
+        limit: u32,
+    ) -> Vec<BytesN<32>> {
+        Self::index_page(&env, &DataKey::SenderHtlcs(sender), offset, limit)
+    }
+
+    /// Gets a page of the contract IDs `receiver` may claim, oldest first. With
+    /// `active_only` the page is taken over Active HTLCs only.
+    pub fn get_htlcs_by_receiver(
+        env: Env,
+        receiver: Address,
+        offset: u32,
+        limit: u32,
+        active_only: bool,
+    ) -> Vec<BytesN<32>> {
+        let key = DataKey::ReceiverHtlcs(receiver);
+        if !active_only {
+            return Self::index_page(&env, &key, offset, limit);
+        }
+
+        let limit = limit.min(MAX_PAGE_SIZE);
+        let mut skipped = 0;
+        let mut page = Vec::new(&env);
+        for contract_id in Self::index_get(&env, &key).iter() {
+            if page.len() == limit {
+                break;
+            }
+
+            let is_active = Self::get_htlc_data(&env, &contract_id)
+                .map(|htlc_data| htlc_data.status == HTLCStatus::Active)
+                .unwrap_or(false);
+            if !is_active {
+                continue;
+            }
+
+            if skipped < offset {
+                skipped += 1;
+            } else {
+                page.push_back(contract_id);
+            }
+        }
+        page
+    }
+
+    /// Gets the seconds left until the timelock expires; 0 once expired
+    pub fn get_remaining_time(env: Env, contract_id: BytesN<32>) -> Result<u64, HTLCError> {
+        let htlc_data = Self::get_htlc_data(&env, &contract_id)?;
+        Ok(htlc_data.timelock.saturating_sub(env.ledger().timestamp()))
+    }
+
+    /// Checks if the timelock has expired, i.e. the HTLC can no longer be withdrawn
+    pub fn is_expired(env: Env, contract_id: BytesN<32>) -> Result<bool, HTLCError> {
+        let htlc_data = Self::get_htlc_data(&env, &contract_id)?;
+        Ok(env.ledger().timestamp() >= htlc_data.timelock)
+    }
+
+    /// Gets the revealed preimage so the counterparty chain can be claimed even if the
+    /// withdrawal event was missed
+    pub fn get_preimage(env: Env, contract_id: BytesN<32>) -> Option<BytesN<32>> {
+        let htlc_data = Self::get_htlc_data(&env, &contract_id).ok()?;
+        if htlc_data.preimage.to_array() == [0; 32] {
+            return None;
+        }
+        Some(htlc_data.preimage)
+    }
+
+    /// Gets the amount an auction order requires at the current ledger timestamp
+    pub fn get_current_fill_amount(env: Env, order: AuctionOrder) -> Result<i128, HTLCError> {
+        if order.end_amount <= 0
+            || order.start_amount < order.end_amount
+            || order.start_time >= order.end_time
+        {
+            return Err(HTLCError::InvalidAuction);
+        }
+
+        let now = env.ledger().timestamp();
+        if now <= order.start_time {
+            return Ok(order.start_amount);
+        }
+        if now >= order.end_time {
+            return Ok(order.end_amount);
+        }
+
+        let elapsed = (now - order.start_time) as i128;
+        let duration = (order.end_time - order.start_time) as i128;
+        Ok(order.start_amount - (order.start_amount - order.end_amount) * elapsed / duration)
+    }
+
+    /// Predicts the ID `create_htlc` assigns when called at ledger `timestamp`, for HTLCs
+    /// without an order hash, salt or extension
+    pub fn compute_contract_id(
+        env: Env,
+        sender: Address,
+        receiver: Address,
+        amount: i128,
+        hashlock: BytesN<32>,
+        timelock: u64,
+        timestamp: u64,
+    ) -> BytesN<32> {
+        Self::generate_contract_id(
+            &env,
+            &sender,
+            &receiver,
+            amount,
+            &hashlock,
+            timelock,
+            timestamp,
+            &BytesN::from_array(&env, &[0; 32]),
+            &Bytes::new(&env),
+        )
+    }
+
+    /// Gets the digest a maker signs to authorize a `SignedOrder`: the SHA-256 of this
+    /// contract's address and the order, so a signature cannot be replayed on another
+    /// deployment or network
+    pub fn get_order_digest(env: Env, order: SignedOrder) -> BytesN<32> {
+        let mut packed_data = env.current_contract_address().to_xdr(&env);
+        packed_data.append(&order.to_xdr(&env));
+        env.crypto().sha256(&packed_data)
+    }
+
+    /// Gets the hash a maker signs for `fill_order`: the SHA-256 of this contract's address
+    /// and the order, so an order cannot be filled on another deployment
+    pub fn get_fusion_order_hash(env: Env, order: FusionOrder) -> BytesN<32> {
+        let mut packed_data = env.current_contract_address().to_xdr(&env);
+        packed_data.append(&order.to_xdr(&env));
+        env.crypto().sha256(&packed_data)
+    }
+
+    /// Gets the digest a receiver signs to authorize `withdraw_with_sig`: the SHA-256 of
+    /// the contract ID, the SHA-256 of the preimage and the big-endian expiry
+    pub fn get_withdraw_digest(
+        env: Env,
+        contract_id: BytesN<32>,
+        preimage: BytesN<32>,
+        expiry: u64,
+    ) -> BytesN<32> {
+        let preimage_hash = env.crypto().sha256(&preimage.into());
+        let mut packed_data = Bytes::new(&env);
+        packed_data.extend_from_slice(&contract_id.to_array());
+        packed_data.extend_from_slice(&preimage_hash.to_array());
+        packed_data.extend_from_slice(&expiry.to_be_bytes());
+        env.crypto().sha256(&packed_data)
+    }
+
+    /// Gets the lifecycle of the latest escrow opened for an order, looked up by the HTLC's
+    /// `order_hash` or, for derived IDs, its `metadata.evm_order_hash`
+    pub fn get_order_state(env: Env, order_hash: BytesN<32>) -> Option<OrderState> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::OrderState(order_hash))
+    }
+
+    /// Gets the HTLC created for an external order hash
+    pub fn escrow_by_order_hash(env: Env, order_hash: BytesN<32>) -> Result<HTLCData, HTLCError> {
+        if !Self::is_order_hash_set(&order_hash) {
+            return Err(HTLCError::ContractNotFound);
+        }
+
+        let htlc_data = Self::get_htlc_data(&env, &order_hash)?;
+        if htlc_data.order_hash != order_hash {
+            return Err(HTLCError::ContractNotFound);
+        }
+        Ok(htlc_data)
+    }
+
+    /// Encodes timelocks as the packed `uint256` of the 1inch `TimelocksLib`
+    pub fn pack_timelocks(env: Env, timelocks: Timelocks) -> BytesN<32> {
+        timelocks.pack(&env)
+    }
+
+    /// Decodes a packed 1inch `TimelocksLib` value
+    pub fn unpack_timelocks(_env: Env, packed: BytesN<32>) -> Timelocks {
+        Timelocks::unpack(&packed)
+    }
+
+    /// Gets the absolute start of `stage` in a packed 1inch `TimelocksLib` value
+    pub fn get_timelock_stage(_env: Env, packed: BytesN<32>, stage: TimelockStage) -> u64 {
+        Timelocks::unpack(&packed).get(stage)
+    }
+
+    /// Gets the native XLM asset contract address for the current network
+    pub fn native_token(env: Env) -> Address {
+        Self::native_token_address(&env)
+    }
+
+    /// Gets the contract interface version
+    pub fn version(_env: Env) -> u32 {
+        CONTRACT_VERSION
+    }
+
+    /// Gets the deployment configuration
+    pub fn get_config(env: Env) -> Result<Config, HTLCError> {
+        env.storage()
+            .instance()
+            .get(&DataKey::Config)
+            .ok_or(HTLCError::NotInitialized)
+    }
+
+    /// Gets the contract admin
+    pub fn get_admin(env: Env) -> Result<Address, HTLCError> {
+        Ok(Self::get_config(env)?.admin)
+    }
+
+    /// Gets the sequence number of the most recently emitted event; 0 before the first one
+    pub fn get_event_sequence(env: Env) -> u64 {
+        env.storage()
+            .instance()
+            .get(&DataKey::EventSequence)
+            .unwrap_or(0)
+    }
+
+    /// Gets the active HTLCs guarded by a hashlock
+    pub fn get_active_htlcs_by_hashlock(env: Env, hashlock: BytesN<32>) -> Vec<BytesN<32>> {
+        Self::index_get(&env, &DataKey::HashlockHtlcs(hashlock))
+    }
+
+    /// Gets the running creation and completion counters
+    pub fn get_stats(env: Env) -> Stats {
+        env.storage()
+            .instance()
+            .get(&DataKey::Stats)
+            .unwrap_or_default()
+    }
+
+    /// Gets the cumulative amount of a token ever locked in HTLCs, basket legs included
+    pub fn get_volume(env: Env, token: Address) -> i128 {
+        env.storage()
+            .instance()
+            .get(&DataKey::Volume(token))
+            .unwrap_or(0)
+    }
+
+    /// Gets the admin transfer awaiting acceptance, if any
+    pub fn get_pending_admin(env: Env) -> Option<PendingAdmin> {
+        env.storage().instance().get(&DataKey::PendingAdmin)
+    }
+
+    /// Gets the policy contract consulted on creation, if any
+    pub fn get_validator(env: Env) -> Option<Address> {
+        Self::get_config(env.clone())
+            .ok()
+            .map(|config| config.validator)
+            .filter(|validator| *validator != env.current_contract_address())
+    }
+
+    /// Gets the bonding registry deciding resolver eligibility, if any
+    pub fn get_resolver_registry(env: Env) -> Option<Address> {
+        Self::get_config(env.clone())
+            .ok()
+            .map(|config| config.resolver_registry)
+            .filter(|registry| *registry != env.current_contract_address())
+    }
+
+    /// Checks if a token contract is on the allowlist
+    pub fn is_token_allowed(env: Env, token: Address) -> bool {
+        env.storage().instance().has(&DataKey::AllowedToken(token))
+    }
+
+    /// Checks if an address is a whitelisted resolver
+    pub fn is_resolver(env: Env, resolver: Address) -> bool {
+        env.storage().instance().has(&DataKey::Resolver(resolver))
+    }
+
+    /// Gets the locked and actual contract balances of a token
+    pub fn get_token_accounting(env: Env, token: Address) -> TokenAccounting {
+        TokenAccounting {
+            locked: Self::locked_balance(&env, &token),
+            balance: token::Client::new(&env, &token).balance(&env.current_contract_address()),
+        }
+    }
+
+    /// Gets the protocol fee configuration, if a non-zero fee is set
+    pub fn get_protocol_fee(env: Env) -> Option<FeeConfig> {
+        Self::get_config(env).ok().and_then(|config| {
+            (config.fee_bps > 0).then_some(FeeConfig {
+                fee_bps: config.fee_bps,
+                collector: config.fee_collector,
+            })
+        })
+    }
+
+    /// Gets the safety deposit sizing policy, if enabled
+    pub fn get_deposit_policy(env: Env) -> Option<DepositPolicy> {
+        Self::get_config(env).ok().and_then(|config| {
+            (config.deposit_policy_bps > 0).then_some(DepositPolicy {
+                bps: config.deposit_policy_bps,
+                min: config.deposit_policy_min,
+                max: config.deposit_policy_max,
+            })
+        })
+    }
+
+    /// Gets the smallest safety deposit accepted for an HTLC locking `amount`: the deposit
+    /// policy's share of it, or the configured minimum if higher
+    pub fn get_required_safety_deposit(env: Env, amount: i128) -> i128 {
+        let minimum = Self::get_min_safety_deposit(env.clone());
+        match Self::get_deposit_policy(env) {
+            Some(policy) => {
+                let sized = amount.saturating_mul(policy.bps as i128) / BPS_DENOMINATOR;
+                minimum.max(sized.clamp(policy.min, policy.max))
+            }
+            None => minimum,
+        }
+    }
+
+    /// Gets the protocol fee an HTLC was created with, which its withdrawals are charged
+    /// whatever the admin has set since
+    pub fn get_htlc_fee(env: Env, contract_id: BytesN<32>) -> Option<FeeConfig> {
+        env.storage().persistent().get(&DataKey::Fee(contract_id))
+    }
+
+    /// Gets the release progress of a revealed vesting HTLC while tranches remain locked
+    pub fn get_vesting_progress(env: Env, contract_id: BytesN<32>) -> Option<VestingProgress> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Vesting(contract_id))
+    }
+
+    /// Gets the number of ledgers HTLC entries are extended by
+    pub fn get_ttl_extension(env: Env) -> u32 {
+        Self::get_config(env).map_or(DEFAULT_TTL_EXTENSION, |config| config.ttl_extension)
+    }
+
+    /// Gets the range of timelock durations accepted for new HTLCs; unbounded by default
+    pub fn get_timelock_bounds(env: Env) -> TimelockBounds {
+        match Self::get_config(env) {
+            Ok(config) => TimelockBounds {
+                min_timelock_duration: config.min_timelock_duration,
+                max_timelock_duration: config.max_timelock_duration,
+            },
+            Err(_) => TimelockBounds {
+                min_timelock_duration: 0,
+                max_timelock_duration: u64::MAX,
+            },
+        }
+    }
+
+    /// Gets the largest amount a single HTLC may lock; uncapped by default
+    pub fn get_max_amount(env: Env) -> i128 {
+        Self::get_config(env).map_or(i128::MAX, |config| config.max_amount)
+    }
+
+    /// Gets the smallest amount a new HTLC may lock; 0 by default
+    pub fn get_min_amount(env: Env) -> i128 {
+        Self::get_config(env).map_or(0, |config| config.min_amount)
+    }
+
+    /// Gets how many HTLCs `sender` created in the current rate-limit window
+    pub fn get_creation_count(env: Env, sender: Address) -> u32 {
+        Self::creation_window(&env, &sender).map_or(0, |(_, count)| count)
+    }
+
+    /// Gets the smallest safety deposit accepted for new HTLCs; 0 by default
+    pub fn get_min_safety_deposit(env: Env) -> i128 {
+        Self::get_config(env).map_or(0, |config| config.min_safety_deposit)
+    }
+
+    /// Checks if creation of new HTLCs is paused
+    pub fn is_paused(env: Env) -> bool {
+        Self::get_config(env).is_ok_and(|config| config.paused)
+    }
+
+    // Private helper functions
+
+    /// Requires authorization from the stored admin; permissionless deployments have none
+    fn require_admin(env: &Env) -> Result<Config, HTLCError> {
+        let config = Self::get_config(env.clone())?;
+        if config.permissionless {
+            return Err(HTLCError::InvalidSigner);
+        }
+        config.admin.require_auth();
+        Ok(config)
+    }
+
+    fn save_config(env: &Env, config: &Config) {
+        env.storage().instance().set(&DataKey::Config, config);
+    }
+
+    /// Publishes an event with its data wrapped as `(EVENT_SCHEMA_VERSION, sequence, data)`
+    ///
+    /// The sequence increases by one per event, so indexers can detect gaps.
+    fn publish_event<T, D>(env: &Env, topics: T, data: D)
+    where
+        T: Topics,
+        D: IntoVal<Env, Val>,
+    {
+        let sequence = Self::get_event_sequence(env.clone()) + 1;
+        env.storage()
+            .instance()
+            .set(&DataKey::EventSequence, &sequence);
+        env.events()
+            .publish(topics, (EVENT_SCHEMA_VERSION, sequence, data.into_val(env)));
+    }
+
+    /// Publishes an HTLC lifecycle event with its payload paired with the HTLC's memo and
+    /// creation ledger, as `(data, memo, created_ledger)`
+    fn publish_lifecycle_event<T, D>(env: &Env, topics: T, htlc_data: &HTLCData, data: D)
+    where
+        T: Topics,
+        D: IntoVal<Env, Val>,
+    {
+        Self::publish_event(
+            env,
+            topics,
+            (
+                data.into_val(env),
+                htlc_data.memo.clone(),
+                htlc_data.created_ledger,
+            ),
+        );
+    }
+
+    /// Requires sender authorization and creates a new HTLC
+    fn create(env: &Env, params: CreateParams) -> Result<BytesN<32>, HTLCError> {
+        params.sender.require_auth();
+        if params.safety_deposit_payer != params.sender {
+            params.safety_deposit_payer.require_auth();
+        }
+        Self::create_authorized(env, params, Funding::Transfer)
+    }
+
+    /// Validates inputs, pulls funds from sender and stores a new HTLC
+    fn create_authorized(
+        env: &Env,
+        params: CreateParams,
+        funding: Funding,
+    ) -> Result<BytesN<32>, HTLCError> {
+        Self::create_entry(env, params, funding, Record::Full)
+    }
+
+    /// Same as `create_authorized`, storing the HTLC as `record`
+    fn create_entry(
+        env: &Env,
+        params: CreateParams,
+        funding: Funding,
+        record: Record,
+    ) -> Result<BytesN<32>, HTLCError> {
+        // Integrator policy sees the parameters exactly as submitted
+        let validation =
+            Self::get_validator(env.clone()).map(|validator| (validator, params.clone()));
+
+        let CreateParams {
+            sender,
+            receiver,
+            amount,
+            token_address,
+            hashlock,
+            hash_algo,
+            timelock,
+            safety_deposit,
+            safety_deposit_token,
+            parts,
+            public_withdrawal,
+            public_cancellation,
+            order_hash,
+            resolvers_only,
+            metadata,
+            basket,
+            safety_deposit_payer,
+            salt,
+            allow_reuse,
+            taker,
+            hashlocks,
+            threshold,
+            callback,
+            callback_data,
+            extension,
+            rescue_delay,
+            refund_to,
+            commit_reveal,
+            memo,
+            referral,
+            referral_bps,
+            use_deposit_policy,
+            vesting,
+        } = params;
+
+        if Self::is_paused(env.clone()) {
+            return Err(HTLCError::ContractPaused);
+        }
+
+        // Input validation
+        if amount <= 0 {
+            return Err(HTLCError::InvalidAmount);
+        }
+
+        let required_deposit = Self::get_required_safety_deposit(env.clone(), amount);
+        let safety_deposit = if use_deposit_policy && safety_deposit == 0 {
+            required_deposit
+        } else {
+            safety_deposit
+        };
+        if safety_deposit < 0 || safety_deposit < required_deposit {
+            return Err(HTLCError::InvalidSafetyDeposit);
+        }
+
+        if amount > Self::get_max_amount(env.clone()) {
+            return Err(HTLCError::AmountAboveCap);
+        }
+
+        // Dust escrows only bloat storage and resolver order feeds
+        if amount < Self::get_min_amount(env.clone()) {
+            return Err(HTLCError::InvalidAmount);
+        }
+        Self::enforce_rate_limit(env, &sender)?;
+
+        // Configurations that only ever come from integration bugs and lock funds uselessly
+        if sender == receiver {
+            return Err(HTLCError::SenderIsReceiver);
+        }
+
+        if hashlock.to_array() == [0; 32] {
+            return Err(HTLCError::ZeroHashlock);
+        }
+
+        if hashlock == Self::hash(env, hash_algo, &Bytes::new(env)) {
+            return Err(HTLCError::EmptyPreimageHashlock);
+        }
+
+        // A k-of-n HTLC commits to its distinct hashlocks through the main hashlock
+        if threshold > 0 || !hashlocks.is_empty() {
+            if threshold == 0
+                || threshold > hashlocks.len()
+                || hashlocks.len() > MAX_THRESHOLD_HASHLOCKS
+                || parts > 0
+            {
+                return Err(HTLCError::InvalidThreshold);
+            }
+
+            let mut packed = Bytes::new(env);
+            for (position, leaf) in hashlocks.iter().enumerate() {
+                if hashlocks.first_index_of(&leaf) != Some(position as u32) {
+                    return Err(HTLCError::InvalidThreshold);
+                }
+                packed.extend_from_slice(&leaf.to_array());
+            }
+            if Self::hash(env, hash_algo, &packed) != hashlock {
+                return Err(HTLCError::InvalidThreshold);
+            }
+        }
+
+        if referral_bps > MAX_FEE_BPS {
+            return Err(HTLCError::InvalidFee);
+        }
+
+        // Tranches are paid from the reveal on and can't be combined with per-part or
+        // per-leg payouts
+        if vesting.tranches > 0 {
+            if vesting.tranches > MAX_TRANCHES
+                || vesting.tranches as i128 > amount
+                || vesting.interval == 0
+                || parts > 0
+            {
+                return Err(HTLCError::InvalidVestingSchedule);
+            }
+            if !basket.is_empty() {
+                return Err(HTLCError::InvalidBasket);
+            }
+        }
+
+        // Extension data is stored with the HTLC, so its size is capped
+        if extension.len() > MAX_EXTENSION_LEN {
+            return Err(HTLCError::ExtensionTooLong);
+        }
+        if memo.len() > MAX_MEMO_LEN {
+            return Err(HTLCError::MemoTooLong);
+        }
+
+        // The sender's total obligation must be representable before any funds move
+        let total = amount
+            .checked_add(safety_deposit)
+            .ok_or(HTLCError::AmountOverflow)?;
+
+        let current_timestamp = env.ledger().timestamp();
+        if timelock <= current_timestamp {
+            return Err(HTLCError::InvalidTimelock);
+        }
+
+        // Reject un-claimably short and capital-locking long timelocks; the
+        // stored entry keeps the timelock as a u32 offset from creation
+        let bounds = Self::get_timelock_bounds(env.clone());
+        let duration = timelock - current_timestamp;
+        if duration < bounds.min_timelock_duration
+            || duration > bounds.max_timelock_duration
+            || duration > u32::MAX as u64
+        {
+            return Err(HTLCError::InvalidTimelock);
+        }
+
+        // Basket legs are all-or-nothing, so they cannot be combined with partial fills
+        if !basket.is_empty() && (parts > 0 || basket.len() > MAX_BASKET_LEGS) {
+            return Err(HTLCError::InvalidBasket);
+        }
+        for leg in basket.iter() {
+            if leg.amount <= 0 || leg.token == token_address {
+                return Err(HTLCError::InvalidBasket);
+            }
+        }
+
+        // Only vetted token contracts may be escrowed while the allowlist is enforced
+        if Self::get_config(env.clone()).is_ok_and(|config| config.enforce_allowlist) {
+            Self::ensure_token_allowed(env, &token_address)?;
+            if safety_deposit > 0 {
+                Self::ensure_token_allowed(env, &safety_deposit_token)?;
+            }
+            for leg in basket.iter() {
+                Self::ensure_token_allowed(env, &leg.token)?;
+            }
+        }
+
+        // A non-token address would otherwise trap with an opaque host error mid-transfer
+        Self::ensure_token_contract(env, &token_address)?;
+        if safety_deposit > 0 && safety_deposit_token != token_address {
+            Self::ensure_token_contract(env, &safety_deposit_token)?;
+        }
+        for leg in basket.iter() {
+            Self::ensure_token_contract(env, &leg.token)?;
+        }
+
+        // Public withdrawal, when enabled, must open before the timelock expires
+        if public_withdrawal != 0
+            && (public_withdrawal < current_timestamp || public_withdrawal >= timelock)
+        {
+            return Err(HTLCError::InvalidPublicWithdrawal);
+        }
+
+        // Commit-reveal only guards the public withdrawal path
+        if commit_reveal && public_withdrawal == 0 {
+            return Err(HTLCError::InvalidPublicWithdrawal);
+        }
+
+        // Public cancellation, when enabled, cannot open before the timelock
+        if public_cancellation != 0 && public_cancellation < timelock {
+            return Err(HTLCError::InvalidPublicCancellation);
+        }
+
+        // Rescue is a last resort for stuck funds and must never cut the receiver's window short
+        if rescue_delay != 0 && current_timestamp.saturating_add(rescue_delay) <= timelock {
+            return Err(HTLCError::InvalidTimelock);
+        }
+
+        // Key by the external order hash when given, otherwise generate contract ID
+        let contract_id = if Self::is_order_hash_set(&order_hash) {
+            order_hash.clone()
+        } else {
+            Self::generate_contract_id(
+                env,
+                &sender,
+                &receiver,
+                amount,
+                &hashlock,
+                timelock,
+                current_timestamp,
+                &salt,
+                &extension,
+            )
+        };
+
+        // Check if contract already exists
+        if Self::contract_exists(env.clone(), contract_id.clone()) {
+            return Err(HTLCError::ContractAlreadyExists);
+        }
+
+        // One revealed secret would unlock every active HTLC sharing its hashlock
+        let hashlock_key = DataKey::HashlockHtlcs(hashlock.clone());
+        if !allow_reuse && !Self::index_get(env, &hashlock_key).is_empty() {
+            return Err(HTLCError::HashlockInUse);
+        }
+
+        // Consult the policy contract last, once the HTLC is known to be valid
+        if let Some((validator, params)) = validation {
+            CreationValidatorClient::new(env, &validator).validate_htlc(&params);
+        }
+
+        // Transfer tokens from sender and safety deposit from its payer to contract, in a
+        // single transfer when both use the same token and come from the sender. Fee-on-transfer
+        // and rebasing tokens can deliver less than requested, so the HTLC records and later
+        // pays out what actually arrived.
+        let requested_amount = amount;
+        let (amount, safety_deposit) = if safety_deposit_token == token_address
+            && safety_deposit_payer == sender
+            && !matches!(funding, Funding::Held)
+        {
+            let received = Self::pull_funds(env, &funding, &token_address, &sender, total);
+            // Any shortfall is charged to the swap amount; the deposit stays whole
+            (received - safety_deposit, safety_deposit)
+        } else {
+            let received_amount = Self::pull_funds(env, &funding, &token_address, &sender, amount);
+            let received_deposit = if safety_deposit > 0 {
+                // A separate payer authorized the creation itself and always transfers, as
+                // does a sender relocking held funds
+                let deposit_funding =
+                    if safety_deposit_payer == sender && !matches!(funding, Funding::Held) {
+                        funding
+                    } else {
+                        Funding::Transfer
+                    };
+                Self::pull_funds(
+                    env,
+                    &deposit_funding,
+                    &safety_deposit_token,
+                    &safety_deposit_payer,
+                    safety_deposit,
+                )
+            } else {
+                0
+            };
+            (received_amount, received_deposit)
+        };
+        if amount <= 0 {
+            return Err(HTLCError::InvalidAmount);
+        }
+        Self::adjust_locked(env, &token_address, amount);
+        if safety_deposit > 0 {
+            Self::adjust_locked(env, &safety_deposit_token, safety_deposit);
+            Self::publish_event(
+                env,
+                (Symbol::new(env, "SafetyDepositPaid"), contract_id.clone()),
+                (
+                    safety_deposit_payer.clone(),
+                    env.current_contract_address(),
+                    safety_deposit,
+                ),
+            );
+        }
+
+        let mut received_basket = Vec::new(env);
+        for leg in basket.iter() {
+            let received = Self::pull_funds(env, &funding, &leg.token, &sender, leg.amount);
+            if received <= 0 {
+                return Err(HTLCError::InvalidBasket);
+            }
+            Self::adjust_locked(env, &leg.token, received);
+            Self::add_volume(env, &leg.token, received);
+            received_basket.push_back(BasketLeg {
+                token: leg.token,
+                amount: received,
+            });
+        }
+        let basket = received_basket;
+        Self::add_volume(env, &token_address, amount);
+        Self::update_stats(env, |stats| {
+            stats.total_created += 1;
+            stats.active += 1;
+        });
+
+        // Create HTLC data
+        let htlc_data = HTLCData {
+            contract_id: contract_id.clone(),
+            sender: sender.clone(),
+            receiver: receiver.clone(),
+            amount,
+            token_address: token_address.clone(),
+            hashlock: hashlock.clone(),
+            hash_algo,
+            timelock,
+            timestamp: current_timestamp,
+            created_ledger: env.ledger().sequence(),
+            safety_deposit,
+            safety_deposit_token,
+            status: HTLCStatus::Active,
+            parts,
+            filled_amount: 0,
+            next_fill_index: 0,
+            public_withdrawal,
+            public_cancellation,
+            order_hash,
+            resolvers_only,
+            completed_at: 0,
+            preimage: BytesN::from_array(env, &[0; 32]),
+            metadata: metadata.clone(),
+            basket: basket.clone(),
+            safety_deposit_payer: safety_deposit_payer.clone(),
+            salt: salt.clone(),
+            taker: taker.clone(),
+            hashlocks: hashlocks.clone(),
+            threshold,
+            callback: callback.clone(),
+            callback_data: callback_data.clone(),
+            extension: extension.clone(),
+            rescue_delay,
+            frozen_until: 0,
+            refund_to: refund_to.clone(),
+            commit_reveal,
+            memo: memo.clone(),
+            referral: referral.clone(),
+            referral_bps,
+            vesting: vesting.clone(),
+        };
+
+        // Store HTLC data, fixing the protocol fee its withdrawals will be charged. An
+        // escrow is only stored compactly if its immutables restore it in full, and is left
+        // out of the sender and receiver views, which read HTLCs by ID.
+        if record == Record::Escrow {
+            let immutables = HTLCImmutables {
+                sender: sender.clone(),
+                receiver: receiver.clone(),
+                token_address: token_address.clone(),
+                amount: requested_amount,
+                hashlock: hashlock.clone(),
+                timelock,
+                timestamp: current_timestamp,
+                salt: salt.clone(),
+                extension: extension.clone(),
+            };
+            if Self::is_order_hash_set(&htlc_data.order_hash)
+                || StoredEscrow::pack(&htlc_data).unpack(env, &contract_id, &immutables)
+                    != htlc_data
+            {
+                return Err(HTLCError::InvalidEscrowParams);
+            }
+            Self::save_record(env, &contract_id, &htlc_data, Record::Escrow);
+        } else {
+            Self::save_htlc_data(env, &contract_id, &htlc_data);
+            Self::index_push(env, &DataKey::SenderHtlcs(sender.clone()), &contract_id);
+            Self::index_push(env, &DataKey::ReceiverHtlcs(receiver.clone()), &contract_id);
+        }
+        if let Some(fee) = Self::get_protocol_fee(env.clone()) {
+            let fee_key = DataKey::Fee(contract_id.clone());
+            env.storage().persistent().set(&fee_key, &fee);
+            Self::extend_ttl(env, &fee_key);
+        }
+        Self::index_push(env, &hashlock_key, &contract_id);
+
+        // Emit HTLCNew event with the full immutable set so indexers can run event-only
+        Self::publish_event(
+            env,
+            (Symbol::new(env, "HTLCNew"), contract_id.clone()),
+            HTLCNewEvent {
+                sender: htlc_data.sender,
+                receiver: htlc_data.receiver,
+                amount: htlc_data.amount,
+                token_address: htlc_data.token_address,
+                hashlock: htlc_data.hashlock,
+                hash_algo: htlc_data.hash_algo,
+                timelock: htlc_data.timelock,
+                timestamp: htlc_data.timestamp,
+                created_ledger: htlc_data.created_ledger,
+                safety_deposit: htlc_data.safety_deposit,
+                safety_deposit_token: htlc_data.safety_deposit_token,
+                parts: htlc_data.parts,
+                public_withdrawal: htlc_data.public_withdrawal,
+                public_cancellation: htlc_data.public_cancellation,
+                order_hash: htlc_data.order_hash,
+                resolvers_only: htlc_data.resolvers_only,
+                metadata,
+                basket,
+                safety_deposit_payer,
+                salt,
+                taker,
+                hashlocks,
+                threshold,
+                callback,
+                callback_data,
+                extension,
+                rescue_delay,
+                refund_to,
+                commit_reveal,
+                memo,
+                referral,
+                referral_bps,
+                vesting,
+            },
+        );
+
+        Ok(contract_id)
+    }
+
+    /// Builds the parameters of one leg of a `create_swap`
+    fn swap_leg(
+        env: &Env,
+        sender: &Address,
+        receiver: &Address,
+        token_address: &Address,
+        amount: i128,
+        hashlock: &BytesN<32>,
+        timelock: u64,
+    ) -> CreateParams {
+        CreateParams {
+            sender: sender.clone(),
+            receiver: receiver.clone(),
+            amount,
+            token_address: token_address.clone(),
+            hashlock: hashlock.clone(),
+            hash_algo: HashAlgo::Sha256,
+            timelock,
+            safety_deposit: 0,
+            safety_deposit_token: token_address.clone(),
+            parts: 0,
+            public_withdrawal: 0,
+            public_cancellation: 0,
+            order_hash: BytesN::from_array(env, &[0; 32]),
+            resolvers_only: false,
+            metadata: CrossChainMetadata::none(env),
+            basket: Vec::new(env),
+            safety_deposit_payer: sender.clone(),
+            salt: BytesN::from_array(env, &[0; 32]),
+            allow_reuse: false,
+            taker: receiver.clone(),
+            hashlocks: Vec::new(env),
+            threshold: 0,
+            callback: sender.clone(),
+            callback_data: Bytes::new(env),
+            extension: Bytes::new(env),
+            rescue_delay: 0,
+            refund_to: sender.clone(),
+            commit_reveal: false,
+            memo: Bytes::new(env),
+            referral: sender.clone(),
+            referral_bps: 0,
+            use_deposit_policy: false,
+            vesting: VestingSchedule::none(),
+        }
+    }
+
+    /// Moves funds from the sender into the contract, returning how much actually arrived
+    fn pull_funds(
+        env: &Env,
+        funding: &Funding,
+        token: &Address,
+        from: &Address,
+        amount: i128,
+    ) -> i128 {
+        let token_client = token::Client::new(env, token);
+        let contract_address = env.current_contract_address();
+        let balance_before = token_client.balance(&contract_address);
+        match funding {
+            Funding::Transfer => token_client.transfer(from, &contract_address, &amount),
+            Funding::Allowance => {
+                token_client.transfer_from(&contract_address, from, &contract_address, &amount)
+            }
+            Funding::Held => return amount,
+        }
+        token_client.balance(&contract_address) - balance_before
+    }
+
+    /// Withdraws a loaded HTLC to its receiver by revealing the preimage
+    fn withdraw_htlc(
+        env: &Env,
+        contract_id: &BytesN<32>,
+        htlc_data: HTLCData,
+        record: Record,
+        preimage: BytesN<32>,
+    ) -> Result<(), HTLCError> {
+        // Reentrancy protection
+        Self::ensure_unlocked(env, contract_id)?;
+
+        // Authorization check - the taker during its exclusive window, otherwise the receiver
+        Self::withdrawer(env, &htlc_data).require_auth();
+
+        // Status check
+        Self::ensure_active(&htlc_data)?;
+
+        // Funds go to receiver, safety deposit goes back to its payer unless slashed
+        let destination = htlc_data.receiver.clone();
+        let deposit_recipient = Self::deposit_recipient(env, &htlc_data, &htlc_data.receiver);
+        Self::claim(
+            env,
+            contract_id,
+            htlc_data,
+            record,
+            preimage,
+            &destination,
+            &deposit_recipient,
+        )
+        .map(|_| ())
+    }
+
+    /// Refunds a loaded HTLC to its sender after timelock expiry
+    fn refund_htlc(
+        env: &Env,
+        contract_id: &BytesN<32>,
+        mut htlc_data: HTLCData,
+        record: Record,
+    ) -> Result<(), HTLCError> {
+        // Reentrancy protection
+        Self::ensure_unlocked(env, contract_id)?;
+
+        // Authorization check - only sender can refund
+        htlc_data.sender.require_auth();
+
+        // Status check
+        Self::ensure_active(&htlc_data)?;
+
+        // Timelock check - can only refund after expiry
+        let current_timestamp = env.ledger().timestamp();
+        if current_timestamp < htlc_data.timelock {
+            return Err(HTLCError::TimelockNotExpired);
+        }
+
+        // Past the timelock the sender collects a separately posted deposit
+        let deposit_recipient = Self::deposit_recipient(env, &htlc_data, &htlc_data.sender);
+        Self::execute_refund(env, contract_id, &mut htlc_data, record, &deposit_recipient);
+
+        // Emit HTLCRefund event - 1inch Fusion+ compatible
+        Self::publish_lifecycle_event(
+            env,
+            (Symbol::new(env, "HTLCRefund"), contract_id.clone()),
+            &htlc_data,
+            contract_id.clone(),
+        );
+
+        Ok(())
+    }
+
+    /// Validates the preimage and pays out a single-secret HTLC to `destination`
+    fn execute_withdraw(
+        env: &Env,
+        contract_id: &BytesN<32>,
+        htlc_data: HTLCData,
+        preimage: BytesN<32>,
+        destination: &Address,
+        deposit_recipient: &Address,
+    ) -> Result<(), HTLCError> {
+        Self::claim(
+            env,
+            contract_id,
+            htlc_data,
+            Record::Full,
+            preimage,
+            destination,
+            deposit_recipient,
+        )
+        .map(|_| ())
+    }
+
+    /// Same as `execute_withdraw`, returning what `destination` was paid
+    fn claim(
+        env: &Env,
+        contract_id: &BytesN<32>,
+        mut htlc_data: HTLCData,
+        record: Record,
+        preimage: BytesN<32>,
+        destination: &Address,
+        deposit_recipient: &Address,
+    ) -> Result<i128, HTLCError> {
+        // Timelock check - must withdraw before expiry
+        let current_timestamp = env.ledger().timestamp();
+        if current_timestamp >= htlc_data.timelock {
+            return Err(HTLCError::TimelockExpired);
+        }
+
+        // A Fusion+ fill opens for withdrawal at its `SrcWithdrawal` stage
+        if Self::is_order_hash_set(&htlc_data.order_hash)
+            && current_timestamp < Self::withdrawal_start(env, contract_id)
+        {
+            return Err(HTLCError::WithdrawalNotStarted);
+        }
+
+        // Partially-fillable HTLCs must be claimed through withdraw_partial
+        if htlc_data.parts > 0 {
+            return Err(HTLCError::PartialFillRequired);
+        }
+
+        // k-of-n HTLCs must be claimed through withdraw_threshold
+        if htlc_data.threshold > 0 {
+            return Err(HTLCError::InvalidThreshold);
+        }
+
+        Self::ensure_not_frozen(env, &htlc_data)?;
+
+        Self::ensure_resolver_allowed(env, &htlc_data)?;
+
+        // Validate preimage against hashlock
+        let preimage_bytes: Bytes = preimage.clone().into();
+        let computed_hash = Self::hash(env, htlc_data.hash_algo, &preimage_bytes);
+        if computed_hash != htlc_data.hashlock {
+            return Err(HTLCError::InvalidPreimage);
+        }
+
+        let paid = Self::settle_withdraw(
+            env,
+            contract_id,
+            &mut htlc_data,
+            record,
+            preimage.clone(),
+            destination,
+            deposit_recipient,
+        );
+
+        // Emit HTLCWithdraw event - 1inch Fusion+ compatible
+        Self::publish_lifecycle_event(
+            env,
+            (Symbol::new(env, "HTLCWithdraw"), contract_id.clone()),
+            &htlc_data,
+            preimage,
+        );
+
+        Ok(paid)
+    }
+
+    /// Pays out a validated withdrawal and marks the HTLC withdrawn, recording `preimage`;
+    /// returns what `destination` was paid of the amount
+    fn settle_withdraw(
+        env: &Env,
+        contract_id: &BytesN<32>,
+        htlc_data: &mut HTLCData,
+        record: Record,
+        preimage: BytesN<32>,
+        destination: &Address,
+        deposit_recipient: &Address,
+    ) -> i128 {
+        // Final state is written once, before any transfer, so a re-entrant call already
+        // sees the HTLC as withdrawn and no separate guard write is needed
+        htlc_data.status = HTLCStatus::Withdrawn;
+        htlc_data.completed_at = env.ledger().timestamp();
+        htlc_data.preimage = preimage;
+        Self::save_record(env, contract_id, htlc_data, record);
+        Self::record_completion(env, contract_id, htlc_data);
+
+        // Transfer tokens to destination, less the protocol and referral fees. Vesting HTLCs
+        // pay the first tranche now and the rest through `claim_tranche`.
+        let paid = if htlc_data.vesting.tranches > 0 {
+            let mut progress = VestingProgress {
+                recipient: destination.clone(),
+                started_at: htlc_data.completed_at,
+                released: 0,
+            };
+            Self::release_tranches(env, htlc_data, &mut progress)
+        } else {
+            Self::transfer_with_fee(
+                env,
+                htlc_data,
+                &htlc_data.token_address,
+                destination,
+                htlc_data.amount,
+            )
+        };
+        for leg in htlc_data.basket.iter() {
+            Self::transfer_with_fee(env, htlc_data, &leg.token, destination, leg.amount);
+        }
+
+        // Pay out safety deposit if applicable
+        Self::pay_safety_deposit(
+            env,
+            contract_id,
+            htlc_data,
+            deposit_recipient,
+            htlc_data.safety_deposit,
+        );
+
+        Self::notify_withdrawn(env, contract_id, htlc_data, destination, paid);
+        paid
+    }
+
+    /// Pays out every tranche of a vesting HTLC unlocked since the last release and records
+    /// the progress, returning what the recipient was paid
+    fn release_tranches(env: &Env, htlc_data: &HTLCData, progress: &mut VestingProgress) -> i128 {
+        let tranches = htlc_data.vesting.tranches;
+        let elapsed = env.ledger().timestamp() - progress.started_at;
+        let unlocked = (elapsed / htlc_data.vesting.interval)
+            .saturating_add(1)
+            .min(tranches as u64) as u32;
+        if unlocked <= progress.released {
+            return 0;
+        }
+
+        // Cumulative shares leave any rounding remainder to the last tranche
+        let released_amount = htlc_data.amount * progress.released as i128 / tranches as i128;
+        let unlocked_amount = htlc_data.amount * unlocked as i128 / tranches as i128;
+        progress.released = unlocked;
+
+        // Progress is recorded before any transfer, as the final state in `settle_withdraw`
+        let key = DataKey::Vesting(htlc_data.contract_id.clone());
+        if unlocked == tranches {
+            env.storage().persistent().remove(&key);
+        } else {
+            env.storage().persistent().set(&key, progress);
+            Self::extend_ttl(env, &key);
+        }
+
+        let paid = Self::transfer_with_fee(
+            env,
+            htlc_data,
+            &htlc_data.token_address,
+            &progress.recipient,
+            unlocked_amount - released_amount,
+        );
+        Self::publish_event(
+            env,
+            (
+                Symbol::new(env, "TrancheReleased"),
+                htlc_data.contract_id.clone(),
+            ),
+            (progress.recipient.clone(), unlocked, paid),
+        );
+        paid
+    }
+
+    /// Returns the unfilled amount to sender and pays out the safety deposit, split pro rata
+    /// with the filled amount
+    fn execute_refund(
+        env: &Env,
+        contract_id: &BytesN<32>,
+        htlc_data: &mut HTLCData,
+        record: Record,
+        deposit_recipient: &Address,
+    ) {
+        // Final state is written once, before any transfer, as in `settle_withdraw`
+        htlc_data.status = HTLCStatus::Refunded;
+        htlc_data.completed_at = env.ledger().timestamp();
+        Self::save_record(env, contract_id, htlc_data, record);
+        Self::record_completion(env, contract_id, htlc_data);
+
+        // Transfer unfilled tokens back to sender, or its designated refund address
+        let token_client = token::Client::new(env, &htlc_data.token_address);
+        let remaining_amount = htlc_data.amount - htlc_data.filled_amount;
+        if remaining_amount > 0 {
+            token_client.transfer(
+                &env.current_contract_address(),
+                &htlc_data.refund_to,
+                &remaining_amount,
+            );
+            Self::adjust_locked(env, &htlc_data.token_address, -remaining_amount);
+        }
+        for leg in htlc_data.basket.iter() {
+            token::Client::new(env, &leg.token).transfer(
+                &env.current_contract_address(),
+                &htlc_data.refund_to,
+                &leg.amount,
+            );
+            Self::adjust_locked(env, &leg.token, -leg.amount);
+        }
+
+        // Pay out safety deposit if applicable. After a partial fill, the filled share goes
+        // back to its payer for the part delivered and only the rest follows the refund.
+        let earned = htlc_data.safety_deposit * htlc_data.filled_amount / htlc_data.amount;
+        Self::pay_safety_deposit(
+            env,
+            contract_id,
+            htlc_data,
+            &htlc_data.safety_deposit_payer,
+            earned,
+        );
+        Self::pay_safety_deposit(
+            env,
+            contract_id,
+            htlc_data,
+            deposit_recipient,
+            htlc_data.safety_deposit - earned,
+        );
+    }
 
-#[derive(Clone)]
-#[contracttype]
-pub struct HTLCData {
-    pub contract_id: BytesN<32>,
-    pub sender: Address,
-    pub receiver: Address,
-    pub amount: i128,
-    pub token_address: Address,
-    pub hashlock: BytesN<32>,
-    pub timelock: u64,
-    pub timestamp: u64,
-    pub safety_deposit: i128,
-    pub status: HTLCStatus,
-    pub locked: bool,
-}
+    /// Transfers `amount` to `recipient` after sending the HTLC's protocol fee to its
+    /// collector and its referral fee to its referrer, returning what the recipient was paid
+    fn transfer_with_fee(
+        env: &Env,
+        htlc_data: &HTLCData,
+        token_address: &Address,
+        recipient: &Address,
+        amount: i128,
+    ) -> i128 {
+        let contract_id = &htlc_data.contract_id;
+        let contract_address = env.current_contract_address();
+        let token_client = token::Client::new(env, token_address);
+        let referral_fee = amount * htlc_data.referral_bps as i128 / BPS_DENOMINATOR;
+        if referral_fee > 0 {
+            token_client.transfer(&contract_address, &htlc_data.referral, &referral_fee);
+            Self::publish_event(
+                env,
+                (Symbol::new(env, "ReferralFee"), contract_id.clone()),
+                (
+                    htlc_data.referral.clone(),
+                    token_address.clone(),
+                    referral_fee,
+                ),
+            );
+        }
 
-#[contract]
-pub struct HTLCContract;
+        let fee = match Self::get_htlc_fee(env.clone(), contract_id.clone()) {
+            Some(fee_config) => {
+                let fee = amount * fee_config.fee_bps as i128 / BPS_DENOMINATOR;
+                if fee > 0 {
+                    token_client.transfer(&contract_address, &fee_config.collector, &fee);
+                    Self::publish_event(
+                        env,
+                        (Symbol::new(env, "ProtocolFee"), contract_id.clone()),
+                        (fee_config.collector, fee),
+                    );
+                }
+                fee
+            }
+            None => 0,
+        };
 
-#[contractimpl]
-impl HTLCContract {
-    /// Creates a new HTLC
-    pub fn create_htlc(
-        env: Env,
-        sender: Address,
-        receiver: Address,
+        // Funds relocked in the same call never leave the contract
+        let paid = amount - fee - referral_fee;
+        if *recipient != contract_address {
+            token_client.transfer(&contract_address, recipient, &paid);
+        }
+        Self::adjust_locked(env, token_address, -amount);
+        paid
+    }
+
+    /// Invokes the HTLC's post-withdraw callback, if any, once its state is settled
+    fn notify_withdrawn(
+        env: &Env,
+        contract_id: &BytesN<32>,
+        htlc_data: &HTLCData,
+        recipient: &Address,
         amount: i128,
-        token_address: Address,
-        hashlock: BytesN<32>,
-        timelock: u64,
-        safety_deposit: i128,
-    ) -> BytesN<32> {
-        // Authorization check
-        sender.require_auth();
+    ) {
+        if htlc_data.callback == htlc_data.sender {
+            return;
+        }
 
-        // Input validation
-        if amount <= 0 {
-            panic!("Invalid amount");
+        // A reverting callback must not let the sender block the receiver's claim
+        let result = WithdrawCallbackClient::new(env, &htlc_data.callback).try_on_htlc_withdrawn(
+            contract_id,
+            recipient,
+            &htlc_data.token_address,
+            &amount,
+            &htlc_data.callback_data,
+        );
+        if !matches!(result, Ok(Ok(()))) {
+            Self::publish_event(
+                env,
+                (Symbol::new(env, "CallbackFailed"), contract_id.clone()),
+                htlc_data.callback.clone(),
+            );
         }
+    }
+
+    /// Transfers `amount` of the safety deposit, in its own asset, to `recipient`
+    fn pay_safety_deposit(
+        env: &Env,
+        contract_id: &BytesN<32>,
+        htlc_data: &HTLCData,
+        recipient: &Address,
+        amount: i128,
+    ) {
+        if amount > 0 {
+            token::Client::new(env, &htlc_data.safety_deposit_token).transfer(
+                &env.current_contract_address(),
+                recipient,
+                &amount,
+            );
+            Self::adjust_locked(env, &htlc_data.safety_deposit_token, -amount);
 
-        if safety_deposit < 0 {
-            panic!("Invalid safety deposit");
+            // A deposit paid to anyone but its payer is forfeited by the payer
+            let event = if *recipient == htlc_data.safety_deposit_payer {
+                "SafetyDepositReturned"
+            } else {
+                "SafetyDepositSlashed"
+            };
+            Self::publish_event(
+                env,
+                (Symbol::new(env, event), contract_id.clone()),
+                (
+                    htlc_data.safety_deposit_payer.clone(),
+                    recipient.clone(),
+                    amount,
+                ),
+            );
         }
+    }
 
-        let current_timestamp = env.ledger().timestamp();
-        if timelock <= current_timestamp {
-            panic!("Invalid timelock");
+    /// Returns the safety deposit recipient when `completer` finishes the HTLC: its payer
+    /// before the exclusive deadline, the completer once the resolver has missed it
+    ///
+    /// The deadline is `public_withdrawal` when enabled, otherwise the timelock.
+    fn deposit_recipient(env: &Env, htlc_data: &HTLCData, completer: &Address) -> Address {
+        let deadline = if htlc_data.public_withdrawal != 0 {
+            htlc_data.public_withdrawal
+        } else {
+            htlc_data.timelock
+        };
+        if env.ledger().timestamp() >= deadline {
+            completer.clone()
+        } else {
+            htlc_data.safety_deposit_payer.clone()
         }
+    }
 
-        // Generate contract ID
-        let contract_id = Self::generate_contract_id(
-            &env,
-            &sender,
-            &receiver,
-            amount,
-            &hashlock,
-            timelock,
-            current_timestamp,
+    fn locked_balance(env: &Env, token: &Address) -> i128 {
+        env.storage()
+            .instance()
+            .get(&DataKey::Locked(token.clone()))
+            .unwrap_or(0)
+    }
+
+    fn update_stats(env: &Env, update: impl FnOnce(&mut Stats)) {
+        let mut stats = Self::get_stats(env.clone());
+        update(&mut stats);
+        env.storage().instance().set(&DataKey::Stats, &stats);
+    }
+
+    /// Moves a completed HTLC out of the active set: stats counters and hashlock index
+    fn record_completion(env: &Env, contract_id: &BytesN<32>, htlc_data: &HTLCData) {
+        Self::index_remove(
+            env,
+            &DataKey::HashlockHtlcs(htlc_data.hashlock.clone()),
+            contract_id,
         );
+        Self::update_stats(env, |stats| {
+            stats.active = stats.active.saturating_sub(1);
+            match htlc_data.status {
+                HTLCStatus::Withdrawn => stats.total_withdrawn += 1,
+                _ => stats.total_refunded += 1,
+            }
+        });
+    }
 
-        // Check if contract already exists
-        if env
-            .storage()
-            .persistent()
-            .has(&DataKey::HTLCData(contract_id.clone()))
-        {
-            panic!("Contract already exists");
-        }
+    fn add_volume(env: &Env, token: &Address, amount: i128) {
+        let volume = Self::get_volume(env.clone(), token.clone()).saturating_add(amount);
+        env.storage()
+            .instance()
+            .set(&DataKey::Volume(token.clone()), &volume);
+    }
 
-        // Transfer tokens from sender to contract
-        let token_client = token::Client::new(&env, &token_address);
-        token_client.transfer(&sender, &env.current_contract_address(), &amount);
+    /// Tracks the amount of a token owed to active HTLCs
+    fn adjust_locked(env: &Env, token: &Address, delta: i128) {
+        let locked = Self::locked_balance(env, token) + delta;
+        env.storage()
+            .instance()
+            .set(&DataKey::Locked(token.clone()), &locked);
+    }
 
-        // Transfer safety deposit if required
-        if safety_deposit > 0 {
-            token_client.transfer(&sender, &env.current_contract_address(), &safety_deposit);
+    /// Checks if a designated taker still holds exclusive withdrawal rights
+    fn taker_exclusive(env: &Env, htlc_data: &HTLCData) -> bool {
+        htlc_data.taker != htlc_data.receiver
+            && (htlc_data.public_withdrawal == 0
+                || env.ledger().timestamp() < htlc_data.public_withdrawal)
+    }
+
+    /// Address whose authorization a standard withdrawal needs
+    fn withdrawer(env: &Env, htlc_data: &HTLCData) -> Address {
+        if Self::taker_exclusive(env, htlc_data) {
+            htlc_data.taker.clone()
+        } else {
+            htlc_data.receiver.clone()
         }
+    }
 
-        // Create HTLC data
-        let htlc_data = HTLCData {
-            contract_id: contract_id.clone(),
-            sender: sender.clone(),
-            receiver: receiver.clone(),
-            amount,
-            token_address: token_address.clone(),
-            hashlock: hashlock.clone(),
-            timelock,
-            timestamp: current_timestamp,
-            safety_deposit,
-            status: HTLCStatus::Active,
-            locked: false,
-        };
+    /// Rejects receiver-driven withdrawals while a designated taker holds exclusivity
+    fn ensure_taker_window_closed(env: &Env, htlc_data: &HTLCData) -> Result<(), HTLCError> {
+        if Self::taker_exclusive(env, htlc_data) {
+            return Err(HTLCError::TakerExclusive);
+        }
+        Ok(())
+    }
 
-        // Store HTLC data
+    /// Start ledger and creation count of the sender's live rate-limit window
+    fn creation_window(env: &Env, sender: &Address) -> Option<(u32, u32)> {
+        let window = Self::get_config(env.clone()).map_or(0, |config| config.rate_limit_window);
         env.storage()
-            .persistent()
-            .set(&DataKey::HTLCData(contract_id.clone()), &htlc_data);
+            .temporary()
+            .get::<_, (u32, u32)>(&DataKey::CreationCount(sender.clone()))
+            .filter(|(start, _)| env.ledger().sequence() < start.saturating_add(window))
+    }
+
+    /// Counts a creation against the sender's rolling window, rejecting it with
+    /// `RateLimited` once the configured number of creations is reached
+    fn enforce_rate_limit(env: &Env, sender: &Address) -> Result<(), HTLCError> {
+        let Ok(config) = Self::get_config(env.clone()) else {
+            return Ok(());
+        };
+        if config.max_creations_per_window == 0 {
+            return Ok(());
+        }
+
+        let (start, count) =
+            Self::creation_window(env, sender).unwrap_or((env.ledger().sequence(), 0));
+        if count >= config.max_creations_per_window {
+            return Err(HTLCError::RateLimited);
+        }
 
-        // Emit HTLCNew event - 1inch Fusion+ compatible
-        env.events().publish(
-            (Symbol::new(&env, "HTLCNew"), contract_id.clone()),
-            (sender, receiver, amount, hashlock, timelock, safety_deposit),
+        // The counter lives in temporary storage and expires with its window
+        let key = DataKey::CreationCount(sender.clone());
+        env.storage().temporary().set(&key, &(start, count + 1));
+        env.storage().temporary().extend_ttl(
+            &key,
+            config.rate_limit_window,
+            config.rate_limit_window,
         );
+        Ok(())
+    }
 
-        contract_id
+    fn ensure_token_allowed(env: &Env, token: &Address) -> Result<(), HTLCError> {
+        if !Self::is_token_allowed(env.clone(), token.clone()) {
+            return Err(HTLCError::TokenNotAllowed);
+        }
+        Ok(())
     }
 
-    /// Withdraws funds by revealing the preimage
-    pub fn withdraw(env: Env, contract_id: BytesN<32>, preimage: BytesN<32>) {
-        let mut htlc_data = Self::get_htlc_data(&env, &contract_id);
+    /// Probes the token interface, failing with `InvalidTokenContract` for accounts and
+    /// contracts that do not answer `decimals`
+    fn ensure_token_contract(env: &Env, token: &Address) -> Result<(), HTLCError> {
+        if !matches!(token::Client::new(env, token).try_decimals(), Ok(Ok(_))) {
+            return Err(HTLCError::InvalidTokenContract);
+        }
+        Ok(())
+    }
 
-        // Reentrancy protection
-        if htlc_data.locked {
-            panic!("Reentrancy detected");
+    /// Requires an eligible receiver while a resolvers-only HTLC is in its exclusive period:
+    /// bonded in the resolver registry if one is set, whitelisted otherwise
+    fn ensure_resolver_allowed(env: &Env, htlc_data: &HTLCData) -> Result<(), HTLCError> {
+        if !htlc_data.resolvers_only {
+            return Ok(());
         }
 
-        // Authorization check - only receiver can withdraw
-        htlc_data.receiver.require_auth();
+        let exclusive_period = htlc_data.public_withdrawal == 0
+            || env.ledger().timestamp() < htlc_data.public_withdrawal;
+        if !exclusive_period {
+            return Ok(());
+        }
 
-        // Status check
-        match htlc_data.status {
-            HTLCStatus::Active => {}
-            HTLCStatus::Withdrawn => panic!("Already withdrawn"),
-            HTLCStatus::Refunded => panic!("Already refunded"),
+        let eligible = match Self::get_resolver_registry(env.clone()) {
+            Some(registry) => {
+                ResolverRegistryClient::new(env, &registry).is_bonded(&htlc_data.receiver)
+            }
+            None => Self::is_resolver(env.clone(), htlc_data.receiver.clone()),
+        };
+        if !eligible {
+            return Err(HTLCError::ResolverNotWhitelisted);
         }
+        Ok(())
+    }
 
-        // Timelock check - must withdraw before expiry
-        let current_timestamp = env.ledger().timestamp();
-        if current_timestamp >= htlc_data.timelock {
-            panic!("Timelock expired");
+    /// Fails with `EscrowFrozen` while an admin freeze blocks withdrawal
+    fn ensure_not_frozen(env: &Env, htlc_data: &HTLCData) -> Result<(), HTLCError> {
+        if env.ledger().timestamp() < htlc_data.frozen_until {
+            return Err(HTLCError::EscrowFrozen);
         }
+        Ok(())
+    }
 
-        // Validate preimage against hashlock
-        let preimage_bytes: Bytes = preimage.clone().into();
-        let computed_hash = env.crypto().sha256(&preimage_bytes);
-        let computed_hash_bytes: BytesN<32> = computed_hash.into();
-        if computed_hash_bytes != htlc_data.hashlock {
-            panic!("Invalid preimage");
+    /// Fails while a payout of the HTLC is in progress
+    fn ensure_unlocked(env: &Env, contract_id: &BytesN<32>) -> Result<(), HTLCError> {
+        if env
+            .storage()
+            .temporary()
+            .has(&DataKey::Guard(contract_id.clone()))
+        {
+            return Err(HTLCError::ReentrancyDetected);
         }
+        Ok(())
+    }
 
-        // Set reentrancy lock
-        htlc_data.locked = true;
+    /// Sets the reentrancy guard in temporary storage, so it costs no persistent write and
+    /// never outlives the transaction's effects
+    fn lock(env: &Env, contract_id: &BytesN<32>) {
         env.storage()
-            .persistent()
-            .set(&DataKey::HTLCData(contract_id.clone()), &htlc_data);
+            .temporary()
+            .set(&DataKey::Guard(contract_id.clone()), &true);
+    }
 
-        // Transfer tokens to receiver
-        let token_client = token::Client::new(&env, &htlc_data.token_address);
-        token_client.transfer(
-            &env.current_contract_address(),
-            &htlc_data.receiver,
-            &htlc_data.amount,
-        );
+    fn unlock(env: &Env, contract_id: &BytesN<32>) {
+        env.storage()
+            .temporary()
+            .remove(&DataKey::Guard(contract_id.clone()));
+    }
 
-        // Return safety deposit to sender if applicable
-        if htlc_data.safety_deposit > 0 {
-            token_client.transfer(
-                &env.current_contract_address(),
-                &htlc_data.sender,
-                &htlc_data.safety_deposit,
-            );
+    fn ensure_active(htlc_data: &HTLCData) -> Result<(), HTLCError> {
+        match htlc_data.status {
+            HTLCStatus::Active => Ok(()),
+            HTLCStatus::Withdrawn => Err(HTLCError::AlreadyWithdrawn),
+            HTLCStatus::Refunded => Err(HTLCError::AlreadyRefunded),
+        }
+    }
+
+    /// Stores an HTLC's new state where `record` says it is kept
+    fn save_record(env: &Env, contract_id: &BytesN<32>, htlc_data: &HTLCData, record: Record) {
+        match record {
+            Record::Full => Self::save_htlc_data(env, contract_id, htlc_data),
+            Record::Escrow => {
+                let key = DataKey::Escrow(contract_id.clone());
+                env.storage()
+                    .persistent()
+                    .set(&key, &StoredEscrow::pack(htlc_data));
+                Self::extend_ttl(env, &key);
+            }
         }
+    }
 
-        // Update status to withdrawn
-        htlc_data.status = HTLCStatus::Withdrawn;
-        htlc_data.locked = false;
+    /// Stores an HTLC in its compact layout and extends its TTL so long timelocks cannot
+    /// expire in storage. An entry still in the old layout is replaced.
+    fn save_htlc_data(env: &Env, contract_id: &BytesN<32>, htlc_data: &HTLCData) {
+        let key = match StoredHTLC::pack(htlc_data) {
+            Some(stored) => {
+                if Self::is_legacy_htlc(env, contract_id) {
+                    env.storage()
+                        .persistent()
+                        .remove(&DataKey::HTLCData(contract_id.clone()));
+                }
+                let key = DataKey::HTLCEntry(contract_id.clone());
+                env.storage().persistent().set(&key, &stored);
+                key
+            }
+            None => {
+                let key = DataKey::HTLCData(contract_id.clone());
+                env.storage().persistent().set(&key, htlc_data);
+                key
+            }
+        };
+        Self::extend_ttl(env, &key);
+        Self::record_order_state(env, contract_id, htlc_data);
+    }
+
+    /// Mirrors the HTLC into the order registry when it is linked to an order
+    fn record_order_state(env: &Env, contract_id: &BytesN<32>, htlc_data: &HTLCData) {
+        let order_hash = if Self::is_order_hash_set(&htlc_data.order_hash) {
+            htlc_data.order_hash.clone()
+        } else if htlc_data.metadata.evm_chain_id != 0 {
+            htlc_data.metadata.evm_order_hash.clone()
+        } else {
+            return;
+        };
+
+        let created_ledger = Self::get_order_state(env.clone(), order_hash.clone())
+            .filter(|state| state.contract_id == *contract_id)
+            .map_or(env.ledger().sequence(), |state| state.created_ledger);
+        // Single-secret withdrawals release the whole amount at once
+        let filled_amount = if htlc_data.parts == 0 && htlc_data.status == HTLCStatus::Withdrawn {
+            htlc_data.amount
+        } else {
+            htlc_data.filled_amount
+        };
+        let state = OrderState {
+            contract_id: contract_id.clone(),
+            created_ledger,
+            amount: htlc_data.amount,
+            filled_amount,
+            preimage: htlc_data.preimage.clone(),
+            status: htlc_data.status.clone(),
+            completed_at: htlc_data.completed_at,
+        };
+
+        let key = DataKey::OrderState(order_hash);
+        let extend_to = Self::get_ttl_extension(env.clone());
+        env.storage().persistent().set(&key, &state);
         env.storage()
             .persistent()
-            .set(&DataKey::HTLCData(contract_id.clone()), &htlc_data);
+            .extend_ttl(&key, extend_to - DAY_IN_LEDGERS, extend_to);
+    }
 
-        // Emit HTLCWithdraw event - 1inch Fusion+ compatible
-        env.events().publish(
-            (Symbol::new(&env, "HTLCWithdraw"), contract_id.clone()),
-            preimage,
-        );
+    /// Extends the TTL of an HTLC entry and the contract instance
+    fn extend_ttl(env: &Env, key: &DataKey) {
+        let extend_to = Self::get_ttl_extension(env.clone());
+        let threshold = extend_to - DAY_IN_LEDGERS;
+        env.storage()
+            .persistent()
+            .extend_ttl(key, threshold, extend_to);
+        env.storage().instance().extend_ttl(threshold, extend_to);
     }
 
-    /// Refunds funds after timelock expiry
-    pub fn refund(env: Env, contract_id: BytesN<32>) {
-        let mut htlc_data = Self::get_htlc_data(&env, &contract_id);
+    fn index_get(env: &Env, key: &DataKey) -> Vec<BytesN<32>> {
+        env.storage().persistent().get(key).unwrap_or(Vec::new(env))
+    }
 
-        // Reentrancy protection
-        if htlc_data.locked {
-            panic!("Reentrancy detected");
-        }
+    /// Appends a contract ID to an index
+    fn index_push(env: &Env, key: &DataKey, contract_id: &BytesN<32>) {
+        let mut contract_ids = Self::index_get(env, key);
+        contract_ids.push_back(contract_id.clone());
+        env.storage().persistent().set(key, &contract_ids);
 
-        // Authorization check - only sender can refund
-        htlc_data.sender.require_auth();
+        let extend_to = Self::get_ttl_extension(env.clone());
+        env.storage()
+            .persistent()
+            .extend_ttl(key, extend_to - DAY_IN_LEDGERS, extend_to);
+    }
 
-        // Status check
-        match htlc_data.status {
-            HTLCStatus::Active => {}
-            HTLCStatus::Withdrawn => panic!("Already withdrawn"),
-            HTLCStatus::Refunded => panic!("Already refunded"),
+    /// Removes a contract ID from an index, dropping the index once empty
+    fn index_remove(env: &Env, key: &DataKey, contract_id: &BytesN<32>) {
+        let mut contract_ids = Self::index_get(env, key);
+        if let Some(position) = contract_ids.first_index_of(contract_id) {
+            contract_ids.remove(position);
         }
 
-        // Timelock check - can only refund after expiry
-        let current_timestamp = env.ledger().timestamp();
-        if current_timestamp < htlc_data.timelock {
-            panic!("Timelock not expired");
+        if contract_ids.is_empty() {
+            env.storage().persistent().remove(key);
+        } else {
+            env.storage().persistent().set(key, &contract_ids);
         }
+    }
 
-        // Set reentrancy lock
-        htlc_data.locked = true;
-        env.storage()
-            .persistent()
-            .set(&DataKey::HTLCData(contract_id.clone()), &htlc_data);
+    /// Returns up to `limit` (capped at `MAX_PAGE_SIZE`) entries of an index from `offset`
+    fn index_page(env: &Env, key: &DataKey, offset: u32, limit: u32) -> Vec<BytesN<32>> {
+        let contract_ids = Self::index_get(env, key);
+        let start = offset.min(contract_ids.len());
+        let end = start
+            .saturating_add(limit.min(MAX_PAGE_SIZE))
+            .min(contract_ids.len());
+        contract_ids.slice(start..end)
+    }
 
-        // Transfer tokens back to sender
-        let token_client = token::Client::new(&env, &htlc_data.token_address);
-        token_client.transfer(
-            &env.current_contract_address(),
-            &htlc_data.sender,
-            &htlc_data.amount,
-        );
+    fn get_htlc_data(env: &Env, contract_id: &BytesN<32>) -> Result<HTLCData, HTLCError> {
+        if let Some(stored) = Self::get_stored_htlc(env, contract_id) {
+            return Ok(stored.unpack(contract_id));
+        }
+        let value: Val = env
+            .storage()
+            .persistent()
+            .get(&DataKey::HTLCData(contract_id.clone()))
+            .ok_or(HTLCError::ContractNotFound)?;
 
-        // Transfer safety deposit back to sender
-        if htlc_data.safety_deposit > 0 {
-            token_client.transfer(
-                &env.current_contract_address(),
-                &htlc_data.sender,
-                &htlc_data.safety_deposit,
-            );
+        // Entries under the legacy key are in the first release's layout, unless already
+        // rewritten in full because their timelock is too far out to compact. Only the
+        // first release stored `locked`.
+        let fields = Map::<Symbol, Val>::try_from_val(env, &value).unwrap();
+        if fields.contains_key(Symbol::new(env, "locked")) {
+            BaselineHTLC::try_from_val(env, &value)
+                .unwrap()
+                .upgrade(env)
+        } else {
+            Ok(Self::decode_with_defaults(env, value))
         }
+    }
 
-        // Update status to refunded
-        htlc_data.status = HTLCStatus::Refunded;
-        htlc_data.locked = false;
+    /// Reads the compact HTLC entry, whatever layout version it was written in
+    fn get_stored_htlc(env: &Env, contract_id: &BytesN<32>) -> Option<StoredHTLC> {
         env.storage()
             .persistent()
-            .set(&DataKey::HTLCData(contract_id.clone()), &htlc_data);
+            .get(&DataKey::HTLCEntry(contract_id.clone()))
+            .map(|value| Self::decode_with_defaults(env, value))
+    }
 
-        // Emit HTLCRefund event - 1inch Fusion+ compatible
-        env.events().publish(
-            (Symbol::new(&env, "HTLCRefund"), contract_id.clone()),
-            contract_id.clone(),
-        );
+    /// Decodes a stored HTLC, filling in fields appended to its type after it was written
+    /// with the value older entries stand for
+    fn decode_with_defaults<T: TryFromVal<Env, Val>>(env: &Env, value: Val) -> T {
+        let mut fields = Map::<Symbol, Val>::try_from_val(env, &value).unwrap();
+        let created_ledger = Symbol::new(env, "created_ledger");
+        if !fields.contains_key(created_ledger.clone()) {
+            fields.set(created_ledger, 0u32.into_val(env));
+        }
+        let referral = Symbol::new(env, "referral");
+        if !fields.contains_key(referral.clone()) {
+            fields.set(referral, fields.get_unchecked(Symbol::new(env, "sender")));
+            fields.set(Symbol::new(env, "referral_bps"), 0u32.into_val(env));
+        }
+        let vesting = Symbol::new(env, "vesting");
+        if !fields.contains_key(vesting.clone()) {
+            fields.set(vesting, VestingSchedule::none().into_val(env));
+        }
+        T::try_from_val(env, &fields.to_val()).unwrap()
     }
 
-    /// Gets HTLC data by contract ID
-    pub fn get_htlc(env: Env, contract_id: BytesN<32>) -> HTLCData {
-        Self::get_htlc_data(&env, &contract_id)
+    /// Start of a Fusion+ fill's withdrawal stage, 0 for any other HTLC
+    fn withdrawal_start(env: &Env, contract_id: &BytesN<32>) -> u64 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::WithdrawalStart(contract_id.clone()))
+            .unwrap_or(0)
     }
 
-    /// Checks if contract exists
-    pub fn contract_exists(env: Env, contract_id: BytesN<32>) -> bool {
+    /// Whether the HTLC is still stored as a full `HTLCData` from before compaction
+    fn is_legacy_htlc(env: &Env, contract_id: &BytesN<32>) -> bool {
         env.storage()
             .persistent()
-            .has(&DataKey::HTLCData(contract_id))
+            .has(&DataKey::HTLCData(contract_id.clone()))
     }
 
-    /// Gets contract status
-    pub fn get_status(env: Env, contract_id: BytesN<32>) -> HTLCStatus {
-        let htlc_data = Self::get_htlc_data(&env, &contract_id);
-        htlc_data.status
+    /// Derives the contract ID from the immutables and loads the HTLC stored under it, with
+    /// where it is kept. An escrow from `create_escrow` is rebuilt from them, as the ID
+    /// binds every one; any other HTLC is checked to have been created with all of them.
+    fn load_by_immutables(
+        env: &Env,
+        immutables: &HTLCImmutables,
+    ) -> Result<(BytesN<32>, HTLCData, Record), HTLCError> {
+        let contract_id = Self::generate_contract_id(
+            env,
+            &immutables.sender,
+            &immutables.receiver,
+            immutables.amount,
+            &immutables.hashlock,
+            immutables.timelock,
+            immutables.timestamp,
+            &immutables.salt,
+            &immutables.extension,
+        );
+        if let Some(escrow) = Self::get_stored_escrow(env, &contract_id) {
+            let htlc_data = escrow.unpack(env, &contract_id, immutables);
+            return Ok((contract_id, htlc_data, Record::Escrow));
+        }
+        let htlc_data = Self::get_htlc_data(env, &contract_id)?;
+
+        // An HTLC keyed by an order hash could sit under the ID of unrelated immutables.
+        // Any other was keyed by this same derivation, which binds the requested amount
+        // and original timelock; the stored amount is what arrived and the timelock may
+        // have been extended since, so those two are not compared directly.
+        if Self::is_order_hash_set(&htlc_data.order_hash)
+            || htlc_data.sender != immutables.sender
+            || htlc_data.receiver != immutables.receiver
+            || htlc_data.token_address != immutables.token_address
+            || htlc_data.hashlock != immutables.hashlock
+            || htlc_data.timestamp != immutables.timestamp
+            || htlc_data.salt != immutables.salt
+            || htlc_data.extension != immutables.extension
+        {
+            return Err(HTLCError::ContractNotFound);
+        }
+        Ok((contract_id, htlc_data, Record::Full))
     }
 
-    // Private helper functions
-    fn get_htlc_data(env: &Env, contract_id: &BytesN<32>) -> HTLCData {
+    fn get_stored_escrow(env: &Env, contract_id: &BytesN<32>) -> Option<StoredEscrow> {
         env.storage()
             .persistent()
-            .get(&DataKey::HTLCData(contract_id.clone()))
-            .unwrap_or_else(|| panic!("Contract not found"))
+            .get(&DataKey::Escrow(contract_id.clone()))
+    }
+
+    fn is_order_hash_set(order_hash: &BytesN<32>) -> bool {
+        order_hash.to_array() != [0; 32]
+    }
+
+    /// Derives the deterministic address of the native asset contract
+    fn native_token_address(env: &Env) -> Address {
+        env.deployer()
+            .with_stellar_asset(Bytes::from_array(env, &NATIVE_ASSET_XDR))
+            .deployed_address()
+    }
+
+    /// Computes the secret index a fill must use, mirroring 1inch Fusion+ partial fills
+    fn fill_index(htlc_data: &HTLCData, fill_amount: i128) -> u32 {
+        let filled_after = htlc_data.filled_amount + fill_amount;
+        if filled_after == htlc_data.amount {
+            // The last secret is reserved for the fill that completes the order
+            return htlc_data.parts;
+        }
+        ((filled_after - 1) * htlc_data.parts as i128 / htlc_data.amount) as u32
+    }
+
+    /// Computes the Merkle leaf committing to the secret hash at `index`
+    fn merkle_leaf(
+        env: &Env,
+        hash_algo: HashAlgo,
+        index: u32,
+        secret_hash: &BytesN<32>,
+    ) -> BytesN<32> {
+        let mut packed_data = Bytes::new(env);
+        packed_data.extend_from_slice(&(index as u64).to_be_bytes());
+        packed_data.extend_from_slice(&secret_hash.to_array());
+        Self::hash(env, hash_algo, &packed_data)
+    }
+
+    /// Verifies a Merkle proof using sorted-pair hashing
+    fn verify_merkle_proof(
+        env: &Env,
+        hash_algo: HashAlgo,
+        leaf: &BytesN<32>,
+        proof: &Vec<BytesN<32>>,
+        root: &BytesN<32>,
+    ) -> bool {
+        let mut computed = leaf.clone();
+        for node in proof.iter() {
+            let mut packed_data = Bytes::new(env);
+            if computed.to_array() <= node.to_array() {
+                packed_data.extend_from_slice(&computed.to_array());
+                packed_data.extend_from_slice(&node.to_array());
+            } else {
+                packed_data.extend_from_slice(&node.to_array());
+                packed_data.extend_from_slice(&computed.to_array());
+            }
+            computed = Self::hash(env, hash_algo, &packed_data);
+        }
+        computed == *root
+    }
+
+    /// Hashes data with the given hashlock algorithm
+    fn hash(env: &Env, hash_algo: HashAlgo, data: &Bytes) -> BytesN<32> {
+        match hash_algo {
+            HashAlgo::Sha256 => env.crypto().sha256(data),
+            HashAlgo::Keccak256 => env.crypto().keccak256(data),
+        }
     }
 
-    /// Generates Keccak-256 contract ID matching Ethereum HTLC pattern
+    /// Generates the Keccak-256 contract ID of an HTLC. The network salt and the field
+    /// widths make it differ from `generateContractId` in HTLC.sol for the same parameters.
+    #[allow(clippy::too_many_arguments)]
     fn generate_contract_id(
         env: &Env,
         sender: &Address,
@@ -292,14 +5015,16 @@ impl HTLCContract {
         timelock: u64,
 
         timestamp: u64,
+        salt: &BytesN<32>,
+        extension: &Bytes,
     ) -> BytesN<32> {
         let mut packed_data = Bytes::new(env);
 
-        // Convert addresses to bytes for cross-chain compatibility
+        // Addresses are packed as the SHA-256 of their XDR
         let sender_bytes = Self::address_to_bytes32(env, sender);
         let receiver_bytes = Self::address_to_bytes32(env, receiver);
 
-        // Pack data in Ethereum ABI encoding order
+        // Fields follow the order of HTLC.sol's `generateContractId`
         packed_data.extend_from_slice(&sender_bytes.to_array());
         packed_data.extend_from_slice(&receiver_bytes.to_array());
         packed_data.extend_from_slice(&amount.to_be_bytes());
@@ -307,14 +5032,47 @@ impl HTLCContract {
         packed_data.extend_from_slice(&timelock.to_be_bytes());
         packed_data.extend_from_slice(&timestamp.to_be_bytes());
 
-        // Generate Keccak-256 hash for Ethereum compatibility
-        env.crypto().keccak256(&packed_data).into()
+        // Domain-separate by network so testnet, mainnet and forks never share IDs
+        packed_data.extend_from_slice(&env.ledger().network_id().to_array());
+
+        // Unsalted IDs keep the layout above
+        if salt.to_array() != [0; 32] {
+            packed_data.extend_from_slice(&salt.to_array());
+        }
+        if !extension.is_empty() {
+            packed_data.extend_from_slice(&env.crypto().keccak256(extension).to_array());
+        }
+
+        // Keccak-256 as on the EVM side, though the preimage differs
+        env.crypto().keccak256(&packed_data)
+    }
+
+    /// Builds the Stellar account address controlled by an ed25519 public key
+    fn account_address(env: &Env, public_key: &BytesN<32>) -> Address {
+        let mut address_xdr = Bytes::from_array(env, &ACCOUNT_ADDRESS_XDR_PREFIX);
+        address_xdr.extend_from_slice(&public_key.to_array());
+        Address::from_xdr(env, &address_xdr).unwrap()
+    }
+
+    /// Extracts the ed25519 public key of a Stellar account address; `None` for contracts
+    fn account_public_key(env: &Env, address: &Address) -> Option<BytesN<32>> {
+        let address_xdr = address.clone().to_xdr(env);
+        let prefix_len = ACCOUNT_ADDRESS_XDR_PREFIX.len() as u32;
+        if address_xdr.len() != prefix_len + 32
+            || address_xdr.slice(..prefix_len)
+                != Bytes::from_array(env, &ACCOUNT_ADDRESS_XDR_PREFIX)
+        {
+            return None;
+        }
+        BytesN::try_from(address_xdr.slice(prefix_len..)).ok()
     }
 
     /// Converts Stellar address to consistent 32-byte representation
     fn address_to_bytes32(env: &Env, address: &Address) -> BytesN<32> {
         let address_bytes = address.to_xdr(env);
-        let hash = env.crypto().sha256(&address_bytes);
-        hash.into()
+        env.crypto().sha256(&address_bytes)
     }
 }
+
+mod test;
+pub mod testutils;
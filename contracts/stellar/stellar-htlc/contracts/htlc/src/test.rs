@@ -1,45 +1,65 @@
 #![cfg(test)]
+extern crate std;
 
 use super::*;
+use crate::testutils::*;
 use soroban_sdk::{
-    testutils::{Address as _, Ledger},
-    token, Address, BytesN, Env,
+    contract, contracterror, contractimpl, contracttype, symbol_short,
+    testutils::{
+        Address as _, AuthorizedFunction, AuthorizedInvocation, Events, Ledger, MockAuth,
+        MockAuthInvoke,
+    },
+    token,
+    xdr::{self, FromXdr, ToXdr},
+    Address, Bytes, BytesN, Env, IntoVal, InvokeError, Map, Symbol, TryFromVal, Val, Vec,
 };
+use std::rc::Rc;
+use stellar_htlc_resolver_registry as registry;
 
-// Constants used in most tests
-const AMOUNT: i128 = 1_000_000_000; // 100 XLM (7 decimals)
-const SAFETY_DEPOSIT: i128 = 100_000_000; // 10 XLM
-const TIMELOCK_SECS: u64 = 3_600; // 1 hour
+// Constants used in most tests, on top of those in `testutils`
+const PARTS: u32 = 4;
+const EXCLUSIVE_SECS: u64 = 600; // 10 minutes
 
-fn new_env() -> Env {
-    let e = Env::default();
-    e.mock_all_auths(); // disable signature checks
-    e
+// Layout of balance entries in the built-in asset contract's storage
+#[contracttype]
+enum NativeDataKey {
+    Balance(Address),
 }
 
-fn setup() -> (Env, Address, Address, Address, HTLCContractClient<'static>) {
+#[contracttype]
+struct NativeBalance {
+    amount: i128,
+    authorized: bool,
+    clawback: bool,
+}
+
+fn setup_native() -> (Env, Address, Address, Address, HTLCContractClient<'static>) {
     let env = new_env();
     let sender = Address::generate(&env);
     let receiver = Address::generate(&env);
 
-    // Create a mock token contract
-    let token_address = env.register_stellar_asset_contract(sender.clone());
+    // Deploy the native XLM asset contract at its deterministic address
+    let native_address = env
+        .deployer()
+        .with_stellar_asset(Bytes::from_array(&env, &[0u8; 4]))
+        .deploy();
 
-    // Register HTLC contract
     let htlc_contract_id = env.register_contract(None, HTLCContract);
     let client = HTLCContractClient::new(&env, &htlc_contract_id);
 
-    // Mint tokens to sender for testing
-    let token_client = token::Client::new(&env, &token_address);
-    token_client.mint(&sender, &(AMOUNT + SAFETY_DEPOSIT));
-
-    (env, sender, receiver, token_address, client)
-}
+    // Native XLM has no admin to mint with, so seed the sender's balance directly
+    env.as_contract(&native_address, || {
+        env.storage().persistent().set(
+            &NativeDataKey::Balance(sender.clone()),
+            &NativeBalance {
+                amount: AMOUNT + SAFETY_DEPOSIT,
+                authorized: true,
+                clawback: false,
+            },
+        );
+    });
 
-fn hashlock_pair(env: &Env) -> (BytesN<32>, BytesN<32>) {
-    let preimage = BytesN::from_array(env, &[42u8; 32]);
-    let hashlock: BytesN<32> = env.crypto().sha256(&preimage.clone().into()).into();
-    (hashlock, preimage)
+    (env, sender, receiver, native_address, client)
 }
 
 //------------------------------------------------------------------
@@ -117,13 +137,12 @@ fn refund_success() {
 //  Input-validation tests
 //------------------------------------------------------------------
 #[test]
-#[should_panic(expected = "Invalid amount")]
 fn create_amount_zero() {
     let (env, sender, receiver, token_address, client) = setup();
     let (hashlock, _) = hashlock_pair(&env);
     let timelock = env.ledger().timestamp() + TIMELOCK_SECS;
 
-    client.create_htlc(
+    let result = client.try_create_htlc(
         &sender,
         &receiver,
         &0,
@@ -132,16 +151,16 @@ fn create_amount_zero() {
         &timelock,
         &SAFETY_DEPOSIT,
     );
+    assert_eq!(result, Err(Ok(HTLCError::InvalidAmount)));
 }
 
 #[test]
-#[should_panic(expected = "Invalid safety deposit")]
 fn create_negative_safety() {
     let (env, sender, receiver, token_address, client) = setup();
     let (hashlock, _) = hashlock_pair(&env);
     let timelock = env.ledger().timestamp() + TIMELOCK_SECS;
 
-    client.create_htlc(
+    let result = client.try_create_htlc(
         &sender,
         &receiver,
         &AMOUNT,
@@ -150,16 +169,16 @@ fn create_negative_safety() {
         &timelock,
         &-1,
     );
+    assert_eq!(result, Err(Ok(HTLCError::InvalidSafetyDeposit)));
 }
 
 #[test]
-#[should_panic(expected = "Invalid timelock")]
 fn create_past_timelock() {
     let (env, sender, receiver, token_address, client) = setup();
     let (hashlock, _) = hashlock_pair(&env);
     let past_timelock = env.ledger().timestamp().saturating_sub(5);
 
-    client.create_htlc(
+    let result = client.try_create_htlc(
         &sender,
         &receiver,
         &AMOUNT,
@@ -168,13 +187,41 @@ fn create_past_timelock() {
         &past_timelock,
         &SAFETY_DEPOSIT,
     );
+    assert_eq!(result, Err(Ok(HTLCError::InvalidTimelock)));
+}
+
+#[test]
+fn create_sender_is_receiver() {
+    let (env, sender, _, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &sender, &token_address, &hashlock);
+
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::SenderIsReceiver)));
+}
+
+#[test]
+fn create_degenerate_hashlocks() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let zero = BytesN::from_array(&env, &[0; 32]);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &zero);
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::ZeroHashlock)));
+
+    params.hashlock = env.crypto().sha256(&Bytes::new(&env));
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::EmptyPreimageHashlock)));
+
+    params.hash_algo = HashAlgo::Keccak256;
+    params.hashlock = env.crypto().keccak256(&Bytes::new(&env));
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::EmptyPreimageHashlock)));
 }
 
 //------------------------------------------------------------------
 //  Error-handling / edge cases
 //------------------------------------------------------------------
 #[test]
-#[should_panic(expected = "Contract already exists")]
 fn duplicate_contract() {
     let (env, sender, receiver, token_address, client) = setup();
     let (hashlock, _) = hashlock_pair(&env);
@@ -191,7 +238,7 @@ fn duplicate_contract() {
     );
 
     // Second call with SAME parameters → same contract id
-    client.create_htlc(
+    let result = client.try_create_htlc(
         &sender,
         &receiver,
         &AMOUNT,
@@ -200,10 +247,107 @@ fn duplicate_contract() {
         &timelock,
         &SAFETY_DEPOSIT,
     );
+    assert_eq!(result, Err(Ok(HTLCError::ContractAlreadyExists)));
+}
+
+#[test]
+fn salt_separates_identical_htlcs() {
+    let (env, sender, receiver, token_address, client) = setup();
+    token::StellarAssetClient::new(&env, &token_address).mint(&sender, &(AMOUNT + SAFETY_DEPOSIT));
+    let (hashlock, _) = hashlock_pair(&env);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let unsalted_id = client.create_htlc_with_params(&params);
+
+    params.salt = BytesN::from_array(&env, &[1u8; 32]);
+    params.allow_reuse = true;
+    let salted_id = client.create_htlc_with_params(&params);
+    assert_ne!(salted_id, unsalted_id);
+    assert_eq!(client.get_htlc(&salted_id).salt, params.salt);
+
+    // The same salt still collides
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::ContractAlreadyExists)));
+}
+
+#[test]
+fn extension_bound_to_contract_id() {
+    let (env, sender, receiver, token_address, client) = setup();
+    token::StellarAssetClient::new(&env, &token_address).mint(&sender, &(AMOUNT + SAFETY_DEPOSIT));
+    let (hashlock, _) = hashlock_pair(&env);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let plain_id = client.create_htlc_with_params(&params);
+
+    params.extension = Bytes::from_slice(&env, b"referral:relayer-7");
+    params.allow_reuse = true;
+    let extended_id = client.create_htlc_with_params(&params);
+    assert_ne!(extended_id, plain_id);
+    assert_eq!(client.get_htlc(&extended_id).extension, params.extension);
+
+    params.extension = Bytes::from_array(&env, &[7u8; 257]);
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::ExtensionTooLong)));
+}
+
+#[test]
+fn reused_hashlock_rejected_while_active() {
+    let (env, sender, receiver, token_address, client) = setup();
+    token::StellarAssetClient::new(&env, &token_address)
+        .mint(&sender, &(2 * (AMOUNT + SAFETY_DEPOSIT)));
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let first_id = client.create_htlc_with_params(&params);
+
+    let mut same_hashlock = params.clone();
+    same_hashlock.receiver = Address::generate(&env);
+    let result = client.try_create_htlc_with_params(&same_hashlock);
+    assert_eq!(result, Err(Ok(HTLCError::HashlockInUse)));
+
+    // Opting in allows the reuse
+    same_hashlock.allow_reuse = true;
+    let second_id = client.create_htlc_with_params(&same_hashlock);
+    assert_eq!(
+        client.get_active_htlcs_by_hashlock(&hashlock),
+        Vec::from_array(&env, [first_id.clone(), second_id.clone()])
+    );
+
+    // Completed HTLCs release the hashlock
+    client.withdraw(&first_id, &preimage);
+    client.withdraw(&second_id, &preimage);
+    assert_eq!(client.get_active_htlcs_by_hashlock(&hashlock).len(), 0);
+    same_hashlock.allow_reuse = false;
+    same_hashlock.receiver = Address::generate(&env);
+    client.create_htlc_with_params(&same_hashlock);
+}
+
+#[test]
+fn reentrancy_guard_blocks_payouts() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let contract_id = client.create_htlc_with_params(&create_params(
+        &env,
+        &sender,
+        &receiver,
+        &token_address,
+        &hashlock,
+    ));
+
+    // Simulate a payout of this HTLC already in progress
+    let guard = DataKey::Guard(contract_id.clone());
+    env.as_contract(&client.address, || {
+        env.storage().temporary().set(&guard, &true);
+    });
+    let result = client.try_withdraw(&contract_id, &preimage);
+    assert_eq!(result, Err(Ok(HTLCError::ReentrancyDetected)));
+
+    env.as_contract(&client.address, || env.storage().temporary().remove(&guard));
+    client.withdraw(&contract_id, &preimage);
+
+    // The guard is released with the payout
+    let guard_set = env.as_contract(&client.address, || env.storage().temporary().has(&guard));
+    assert!(!guard_set);
 }
 
 #[test]
-#[should_panic(expected = "Invalid preimage")]
 fn wrong_preimage() {
     let (env, sender, receiver, token_address, client) = setup();
     let (hashlock, _) = hashlock_pair(&env);
@@ -220,11 +364,11 @@ fn wrong_preimage() {
     );
 
     let bad_preimage = BytesN::from_array(&env, &[1u8; 32]);
-    client.withdraw(&contract_id, &bad_preimage);
+    let result = client.try_withdraw(&contract_id, &bad_preimage);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidPreimage)));
 }
 
 #[test]
-#[should_panic(expected = "Timelock expired")]
 fn withdraw_after_timelock() {
     let (env, sender, receiver, token_address, client) = setup();
     let (hashlock, preimage) = hashlock_pair(&env);
@@ -241,11 +385,11 @@ fn withdraw_after_timelock() {
     );
 
     env.ledger().with_mut(|l| l.timestamp = timelock + 1);
-    client.withdraw(&contract_id, &preimage);
+    let result = client.try_withdraw(&contract_id, &preimage);
+    assert_eq!(result, Err(Ok(HTLCError::TimelockExpired)));
 }
 
 #[test]
-#[should_panic(expected = "Timelock not expired")]
 fn refund_before_timelock() {
     let (env, sender, receiver, token_address, client) = setup();
     let (hashlock, _) = hashlock_pair(&env);
@@ -261,11 +405,11 @@ fn refund_before_timelock() {
         &SAFETY_DEPOSIT,
     );
 
-    client.refund(&contract_id); // too early
+    let result = client.try_refund(&contract_id); // too early
+    assert_eq!(result, Err(Ok(HTLCError::TimelockNotExpired)));
 }
 
 #[test]
-#[should_panic(expected = "Already withdrawn")]
 fn double_withdraw() {
     let (env, sender, receiver, token_address, client) = setup();
     let (hashlock, preimage) = hashlock_pair(&env);
@@ -282,11 +426,11 @@ fn double_withdraw() {
     );
 
     client.withdraw(&contract_id, &preimage);
-    client.withdraw(&contract_id, &preimage); // second call should panic
+    let result = client.try_withdraw(&contract_id, &preimage); // second call should fail
+    assert_eq!(result, Err(Ok(HTLCError::AlreadyWithdrawn)));
 }
 
 #[test]
-#[should_panic(expected = "Already refunded")]
 fn double_refund() {
     let (env, sender, receiver, token_address, client) = setup();
     let (hashlock, _) = hashlock_pair(&env);
@@ -304,15 +448,16 @@ fn double_refund() {
 
     env.ledger().with_mut(|l| l.timestamp = timelock + 1);
     client.refund(&contract_id);
-    client.refund(&contract_id); // second call should panic
+    let result = client.try_refund(&contract_id); // second call should fail
+    assert_eq!(result, Err(Ok(HTLCError::AlreadyRefunded)));
 }
 
 #[test]
-#[should_panic(expected = "Contract not found")]
 fn get_nonexistent() {
     let (_, _, _, _, client) = setup();
     let fake_id = BytesN::from_array(&client.env, &[7u8; 32]);
-    client.get_htlc(&fake_id);
+    let result = client.try_get_htlc(&fake_id);
+    assert_eq!(result, Err(Ok(HTLCError::ContractNotFound)));
 }
 
 //------------------------------------------------------------------
@@ -334,9 +479,10 @@ fn contract_id_unique() {
         &SAFETY_DEPOSIT,
     );
 
-    // Bump timestamp to ensure different contract ID
+    // Fund a second escrow and bump timestamp to ensure different contract ID
+    token::StellarAssetClient::new(&env, &token_address).mint(&sender, &(AMOUNT + SAFETY_DEPOSIT));
     env.ledger().with_mut(|l| l.timestamp += 1);
-    let (hashlock2, _) = hashlock_pair(&env);
+    let hashlock2 = BytesN::from_array(&env, &[1u8; 32]);
     let contract_id2 = client.create_htlc(
         &sender,
         &receiver,
@@ -350,6 +496,49 @@ fn contract_id_unique() {
     assert_ne!(contract_id1, contract_id2);
 }
 
+#[test]
+fn compute_contract_id_predicts_created_id() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let timelock = env.ledger().timestamp() + TIMELOCK_SECS;
+
+    let predicted = client.compute_contract_id(
+        &sender,
+        &receiver,
+        &AMOUNT,
+        &hashlock,
+        &timelock,
+        &env.ledger().timestamp(),
+    );
+    let contract_id = client.create_htlc(
+        &sender,
+        &receiver,
+        &AMOUNT,
+        &token_address,
+        &hashlock,
+        &timelock,
+        &SAFETY_DEPOSIT,
+    );
+    assert_eq!(contract_id, predicted);
+}
+
+#[test]
+fn contract_id_bound_to_network() {
+    let (env, sender, receiver, _, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let timelock = env.ledger().timestamp() + TIMELOCK_SECS;
+    let timestamp = env.ledger().timestamp();
+
+    let testnet_id = client.compute_contract_id(
+        &sender, &receiver, &AMOUNT, &hashlock, &timelock, &timestamp,
+    );
+    env.ledger().with_mut(|l| l.network_id = [1u8; 32]);
+    let mainnet_id = client.compute_contract_id(
+        &sender, &receiver, &AMOUNT, &hashlock, &timelock, &timestamp,
+    );
+    assert_ne!(testnet_id, mainnet_id);
+}
+
 #[test]
 fn contract_exists_flag() {
     let (env, sender, receiver, token_address, client) = setup();
@@ -371,3 +560,6394 @@ fn contract_exists_flag() {
     let fake_id = BytesN::from_array(&env, &[0u8; 32]);
     assert!(!client.contract_exists(&fake_id));
 }
+
+#[test]
+fn get_htlc_opt_and_contracts_exist() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+    let fake_id = BytesN::from_array(&env, &[7u8; 32]);
+
+    assert_eq!(
+        client.get_htlc_opt(&contract_id),
+        Some(client.get_htlc(&contract_id))
+    );
+    assert_eq!(client.get_htlc_opt(&fake_id), None);
+
+    let ids = Vec::from_array(&env, [fake_id, contract_id]);
+    assert_eq!(
+        client.contracts_exist(&ids),
+        Vec::from_array(&env, [false, true])
+    );
+}
+
+//------------------------------------------------------------------
+//  Native XLM escrows
+//------------------------------------------------------------------
+#[test]
+fn native_token_matches_deployed_asset() {
+    let (_, _, _, native_address, client) = setup_native();
+    assert_eq!(client.native_token(), native_address);
+}
+
+#[test]
+fn create_htlc_native_success() {
+    let (env, sender, receiver, native_address, client) = setup_native();
+    let (hashlock, _) = hashlock_pair(&env);
+    let timelock = env.ledger().timestamp() + TIMELOCK_SECS;
+
+    let contract_id = client.create_htlc_native(
+        &sender,
+        &receiver,
+        &AMOUNT,
+        &hashlock,
+        &timelock,
+        &SAFETY_DEPOSIT,
+    );
+
+    let htlc_data = client.get_htlc(&contract_id);
+    assert_eq!(htlc_data.token_address, native_address);
+    assert_eq!(htlc_data.status, HTLCStatus::Active);
+
+    let xlm = token::Client::new(&env, &native_address);
+    assert_eq!(xlm.balance(&sender), 0);
+    assert_eq!(xlm.balance(&client.address), AMOUNT + SAFETY_DEPOSIT);
+}
+
+#[test]
+fn withdraw_native_success() {
+    let (env, sender, receiver, native_address, client) = setup_native();
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let timelock = env.ledger().timestamp() + TIMELOCK_SECS;
+
+    let contract_id = client.create_htlc_native(
+        &sender,
+        &receiver,
+        &AMOUNT,
+        &hashlock,
+        &timelock,
+        &SAFETY_DEPOSIT,
+    );
+
+    client.withdraw(&contract_id, &preimage);
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Withdrawn);
+
+    let xlm = token::Client::new(&env, &native_address);
+    assert_eq!(xlm.balance(&receiver), AMOUNT);
+    assert_eq!(xlm.balance(&sender), SAFETY_DEPOSIT);
+    assert_eq!(xlm.balance(&client.address), 0);
+}
+
+#[test]
+fn refund_native_success() {
+    let (env, sender, receiver, native_address, client) = setup_native();
+    let (hashlock, _) = hashlock_pair(&env);
+    let timelock = env.ledger().timestamp() + TIMELOCK_SECS;
+
+    let contract_id = client.create_htlc_native(
+        &sender,
+        &receiver,
+        &AMOUNT,
+        &hashlock,
+        &timelock,
+        &SAFETY_DEPOSIT,
+    );
+
+    env.ledger().with_mut(|l| l.timestamp = timelock + 1);
+    client.refund(&contract_id);
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Refunded);
+
+    let xlm = token::Client::new(&env, &native_address);
+    assert_eq!(xlm.balance(&sender), AMOUNT + SAFETY_DEPOSIT);
+    assert_eq!(xlm.balance(&receiver), 0);
+    assert_eq!(xlm.balance(&client.address), 0);
+}
+
+//------------------------------------------------------------------
+//  Partial fills with a Merkle tree of secrets
+//------------------------------------------------------------------
+fn partial_secrets(env: &Env) -> Vec<BytesN<32>> {
+    let mut secrets = Vec::new(env);
+    for i in 0..=PARTS {
+        secrets.push_back(BytesN::from_array(env, &[100 + i as u8; 32]));
+    }
+    secrets
+}
+
+fn hash_with(env: &Env, hash_algo: HashAlgo, data: &Bytes) -> BytesN<32> {
+    match hash_algo {
+        HashAlgo::Sha256 => env.crypto().sha256(data),
+        HashAlgo::Keccak256 => env.crypto().keccak256(data),
+    }
+}
+
+fn hash_pair(env: &Env, hash_algo: HashAlgo, a: &BytesN<32>, b: &BytesN<32>) -> BytesN<32> {
+    let (first, second) = if a.to_array() <= b.to_array() {
+        (a, b)
+    } else {
+        (b, a)
+    };
+    let mut packed = Bytes::from_array(env, &first.to_array());
+    packed.extend_from_slice(&second.to_array());
+    hash_with(env, hash_algo, &packed)
+}
+
+/// Builds the SHA-256 Merkle root over all secrets and the proof for the leaf at `index`
+fn merkle_root_and_proof(
+    env: &Env,
+    secrets: &Vec<BytesN<32>>,
+    index: u32,
+) -> (BytesN<32>, Vec<BytesN<32>>) {
+    merkle_root_and_proof_with(env, HashAlgo::Sha256, secrets, index)
+}
+
+fn merkle_root_and_proof_with(
+    env: &Env,
+    hash_algo: HashAlgo,
+    secrets: &Vec<BytesN<32>>,
+    index: u32,
+) -> (BytesN<32>, Vec<BytesN<32>>) {
+    let mut level = Vec::new(env);
+    for (i, secret) in secrets.iter().enumerate() {
+        let secret_hash = hash_with(env, hash_algo, &secret.into());
+        let mut packed = Bytes::from_array(env, &(i as u64).to_be_bytes());
+        packed.extend_from_slice(&secret_hash.to_array());
+        level.push_back(hash_with(env, hash_algo, &packed));
+    }
+
+    let mut proof = Vec::new(env);
+    let mut position = index;
+    while level.len() > 1 {
+        let sibling = position ^ 1;
+        if sibling < level.len() {
+            proof.push_back(level.get(sibling).unwrap());
+        }
+
+        let mut next = Vec::new(env);
+        let mut i = 0;
+        while i < level.len() {
+            let left = level.get(i).unwrap();
+            match level.get(i + 1) {
+                Some(right) => next.push_back(hash_pair(env, hash_algo, &left, &right)),
+                None => next.push_back(left),
+            }
+            i += 2;
+        }
+        level = next;
+        position /= 2;
+    }
+
+    (level.get(0).unwrap(), proof)
+}
+
+fn create_partial(
+    env: &Env,
+    sender: &Address,
+    receiver: &Address,
+    token_address: &Address,
+    client: &HTLCContractClient<'static>,
+) -> (BytesN<32>, Vec<BytesN<32>>) {
+    let secrets = partial_secrets(env);
+    let (root, _) = merkle_root_and_proof(env, &secrets, 0);
+    let timelock = env.ledger().timestamp() + TIMELOCK_SECS;
+
+    let contract_id = client.create_htlc_partial(
+        sender,
+        receiver,
+        &AMOUNT,
+        token_address,
+        &root,
+        &PARTS,
+        &timelock,
+        &SAFETY_DEPOSIT,
+    );
+    (contract_id, secrets)
+}
+
+#[test]
+fn partial_fill_in_parts() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (contract_id, secrets) = create_partial(&env, &sender, &receiver, &token_address, &client);
+    let quarter = AMOUNT / PARTS as i128;
+
+    // First quarter uses secret 0, second quarter secret 1
+    for index in 0..2 {
+        let (_, proof) = merkle_root_and_proof(&env, &secrets, index);
+        client.withdraw_partial(
+            &contract_id,
+            &secrets.get(index).unwrap(),
+            &index,
+            &proof,
+            &quarter,
+        );
+    }
+
+    let htlc_data = client.get_htlc(&contract_id);
+    assert_eq!(htlc_data.filled_amount, 2 * quarter);
+    assert_eq!(htlc_data.status, HTLCStatus::Active);
+
+    // Completing the order uses the last secret
+    let (_, proof) = merkle_root_and_proof(&env, &secrets, PARTS);
+    client.withdraw_partial(
+        &contract_id,
+        &secrets.get(PARTS).unwrap(),
+        &PARTS,
+        &proof,
+        &(AMOUNT - 2 * quarter),
+    );
+
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Withdrawn);
+    let token_client = token::Client::new(&env, &token_address);
+    assert_eq!(token_client.balance(&receiver), AMOUNT);
+    assert_eq!(token_client.balance(&sender), SAFETY_DEPOSIT);
+    assert_eq!(token_client.balance(&client.address), 0);
+}
+
+#[test]
+fn partial_fill_wrong_index() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (contract_id, secrets) = create_partial(&env, &sender, &receiver, &token_address, &client);
+
+    // A quarter fill must use secret 0, not secret 1
+    let (_, proof) = merkle_root_and_proof(&env, &secrets, 1);
+    let result = client.try_withdraw_partial(
+        &contract_id,
+        &secrets.get(1).unwrap(),
+        &1,
+        &proof,
+        &(AMOUNT / PARTS as i128),
+    );
+    assert_eq!(result, Err(Ok(HTLCError::InvalidFillIndex)));
+}
+
+#[test]
+fn partial_fill_reused_secret() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (contract_id, secrets) = create_partial(&env, &sender, &receiver, &token_address, &client);
+    let (_, proof) = merkle_root_and_proof(&env, &secrets, 0);
+    let small_fill = AMOUNT / 10;
+
+    client.withdraw_partial(
+        &contract_id,
+        &secrets.get(0).unwrap(),
+        &0,
+        &proof,
+        &small_fill,
+    );
+
+    // A second fill still inside part 0 cannot reuse secret 0
+    let result = client.try_withdraw_partial(
+        &contract_id,
+        &secrets.get(0).unwrap(),
+        &0,
+        &proof,
+        &small_fill,
+    );
+    assert_eq!(result, Err(Ok(HTLCError::InvalidFillIndex)));
+}
+
+#[test]
+fn partial_fill_invalid_proof() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (contract_id, secrets) = create_partial(&env, &sender, &receiver, &token_address, &client);
+
+    // Proof for leaf 2 does not authenticate leaf 0
+    let (_, proof) = merkle_root_and_proof(&env, &secrets, 2);
+    let result = client.try_withdraw_partial(
+        &contract_id,
+        &secrets.get(0).unwrap(),
+        &0,
+        &proof,
+        &(AMOUNT / PARTS as i128),
+    );
+    assert_eq!(result, Err(Ok(HTLCError::InvalidMerkleProof)));
+}
+
+#[test]
+fn partial_fill_exceeds_remaining() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (contract_id, secrets) = create_partial(&env, &sender, &receiver, &token_address, &client);
+    let (_, proof) = merkle_root_and_proof(&env, &secrets, PARTS);
+
+    let result = client.try_withdraw_partial(
+        &contract_id,
+        &secrets.get(PARTS).unwrap(),
+        &PARTS,
+        &proof,
+        &(AMOUNT + 1),
+    );
+    assert_eq!(result, Err(Ok(HTLCError::InvalidFillAmount)));
+}
+
+#[test]
+fn partial_htlc_rejects_plain_withdraw() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (contract_id, secrets) = create_partial(&env, &sender, &receiver, &token_address, &client);
+
+    let result = client.try_withdraw(&contract_id, &secrets.get(0).unwrap());
+    assert_eq!(result, Err(Ok(HTLCError::PartialFillRequired)));
+}
+
+#[test]
+fn single_secret_htlc_rejects_partial_withdraw() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let timelock = env.ledger().timestamp() + TIMELOCK_SECS;
+
+    let contract_id = client.create_htlc(
+        &sender,
+        &receiver,
+        &AMOUNT,
+        &token_address,
+        &hashlock,
+        &timelock,
+        &SAFETY_DEPOSIT,
+    );
+
+    let result = client.try_withdraw_partial(&contract_id, &preimage, &0, &Vec::new(&env), &AMOUNT);
+    assert_eq!(result, Err(Ok(HTLCError::PartialFillNotAllowed)));
+}
+
+#[test]
+fn create_partial_zero_parts() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let timelock = env.ledger().timestamp() + TIMELOCK_SECS;
+
+    let result = client.try_create_htlc_partial(
+        &sender,
+        &receiver,
+        &AMOUNT,
+        &token_address,
+        &hashlock,
+        &0,
+        &timelock,
+        &SAFETY_DEPOSIT,
+    );
+    assert_eq!(result, Err(Ok(HTLCError::InvalidPartsCount)));
+}
+
+#[test]
+fn refund_after_partial_fill() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (contract_id, secrets) = create_partial(&env, &sender, &receiver, &token_address, &client);
+    let quarter = AMOUNT / PARTS as i128;
+    let (_, proof) = merkle_root_and_proof(&env, &secrets, 0);
+
+    client.withdraw_partial(&contract_id, &secrets.get(0).unwrap(), &0, &proof, &quarter);
+
+    let timelock = client.get_htlc(&contract_id).timelock;
+    env.ledger().with_mut(|l| l.timestamp = timelock + 1);
+    client.refund(&contract_id);
+
+    // Only the unfilled remainder goes back to sender
+    let token_client = token::Client::new(&env, &token_address);
+    assert_eq!(token_client.balance(&receiver), quarter);
+    assert_eq!(
+        token_client.balance(&sender),
+        AMOUNT - quarter + SAFETY_DEPOSIT
+    );
+    assert_eq!(token_client.balance(&client.address), 0);
+}
+
+#[test]
+fn refund_after_partial_fill_splits_deposit() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let resolver = Address::generate(&env);
+    token::StellarAssetClient::new(&env, &token_address).mint(&resolver, &SAFETY_DEPOSIT);
+    let secrets = partial_secrets(&env);
+    let (root, proof) = merkle_root_and_proof(&env, &secrets, 0);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &root);
+    params.parts = PARTS;
+    params.safety_deposit_payer = resolver.clone();
+    let contract_id = client.create_htlc_with_params(&params);
+
+    let quarter = AMOUNT / PARTS as i128;
+    client.withdraw_partial(&contract_id, &secrets.get(0).unwrap(), &0, &proof, &quarter);
+    env.ledger().with_mut(|l| l.timestamp = params.timelock);
+    client.refund(&contract_id);
+
+    // The resolver keeps the deposit share of the part it delivered
+    let token_client = token::Client::new(&env, &token_address);
+    let earned = SAFETY_DEPOSIT / PARTS as i128;
+    assert_eq!(token_client.balance(&resolver), earned);
+    assert_eq!(
+        token_client.balance(&sender),
+        AMOUNT + SAFETY_DEPOSIT - quarter + SAFETY_DEPOSIT - earned
+    );
+    assert_eq!(token_client.balance(&client.address), 0);
+    assert_eq!(
+        deposit_events(&env, &contract_id, "SafetyDepositReturned"),
+        [(resolver.clone(), resolver.clone(), earned)]
+    );
+    assert_eq!(
+        deposit_events(&env, &contract_id, "SafetyDepositSlashed"),
+        [(resolver, sender, SAFETY_DEPOSIT - earned)]
+    );
+}
+
+//------------------------------------------------------------------
+//  Public withdrawal after the exclusive period
+//------------------------------------------------------------------
+fn create_public(
+    env: &Env,
+    sender: &Address,
+    receiver: &Address,
+    token_address: &Address,
+    client: &HTLCContractClient<'static>,
+) -> (BytesN<32>, BytesN<32>) {
+    let (hashlock, preimage) = hashlock_pair(env);
+    let mut params = create_params(env, sender, receiver, token_address, &hashlock);
+    params.public_withdrawal = env.ledger().timestamp() + EXCLUSIVE_SECS;
+    (client.create_htlc_with_params(&params), preimage)
+}
+
+#[test]
+fn public_withdraw_after_exclusive_period() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let resolver = Address::generate(&env);
+    let (contract_id, preimage) = create_public(&env, &sender, &receiver, &token_address, &client);
+
+    env.ledger().with_mut(|l| l.timestamp += EXCLUSIVE_SECS);
+    client.public_withdraw(&resolver, &contract_id, &preimage);
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Withdrawn);
+
+    // Receiver gets the amount, the resolver earns the safety deposit
+    let token_client = token::Client::new(&env, &token_address);
+    assert_eq!(token_client.balance(&receiver), AMOUNT);
+    assert_eq!(token_client.balance(&resolver), SAFETY_DEPOSIT);
+    assert_eq!(token_client.balance(&sender), 0);
+}
+
+#[test]
+fn public_withdraw_during_exclusive_period() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let resolver = Address::generate(&env);
+    let (contract_id, preimage) = create_public(&env, &sender, &receiver, &token_address, &client);
+
+    let result = client.try_public_withdraw(&resolver, &contract_id, &preimage);
+    assert_eq!(result, Err(Ok(HTLCError::PublicWithdrawalNotStarted)));
+
+    // The receiver can still withdraw exclusively
+    client.withdraw(&contract_id, &preimage);
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Withdrawn);
+}
+
+#[test]
+fn public_withdraw_disabled_by_default() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let resolver = Address::generate(&env);
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let timelock = env.ledger().timestamp() + TIMELOCK_SECS;
+
+    let contract_id = client.create_htlc(
+        &sender,
+        &receiver,
+        &AMOUNT,
+        &token_address,
+        &hashlock,
+        &timelock,
+        &SAFETY_DEPOSIT,
+    );
+
+    env.ledger().with_mut(|l| l.timestamp = timelock - 1);
+    let result = client.try_public_withdraw(&resolver, &contract_id, &preimage);
+    assert_eq!(result, Err(Ok(HTLCError::PublicWithdrawalNotStarted)));
+}
+
+#[test]
+fn public_withdraw_wrong_preimage() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let resolver = Address::generate(&env);
+    let (contract_id, _) = create_public(&env, &sender, &receiver, &token_address, &client);
+
+    env.ledger().with_mut(|l| l.timestamp += EXCLUSIVE_SECS);
+    let bad_preimage = BytesN::from_array(&env, &[1u8; 32]);
+    let result = client.try_public_withdraw(&resolver, &contract_id, &bad_preimage);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidPreimage)));
+}
+
+#[test]
+fn create_public_withdrawal_after_timelock() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.public_withdrawal = params.timelock;
+
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidPublicWithdrawal)));
+}
+
+//------------------------------------------------------------------
+//  Public cancellation after the cancellation deadline
+//------------------------------------------------------------------
+fn create_public_cancellation(
+    env: &Env,
+    sender: &Address,
+    receiver: &Address,
+    token_address: &Address,
+    client: &HTLCContractClient<'static>,
+) -> BytesN<32> {
+    let (hashlock, _) = hashlock_pair(env);
+    let mut params = create_params(env, sender, receiver, token_address, &hashlock);
+    params.public_cancellation = params.timelock + EXCLUSIVE_SECS;
+    client.create_htlc_with_params(&params)
+}
+
+#[test]
+fn public_refund_after_cancellation_deadline() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let resolver = Address::generate(&env);
+    let contract_id = create_public_cancellation(&env, &sender, &receiver, &token_address, &client);
+
+    let public_cancellation = client.get_htlc(&contract_id).public_cancellation;
+    env.ledger().with_mut(|l| l.timestamp = public_cancellation);
+    client.public_refund(&resolver, &contract_id);
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Refunded);
+
+    // Sender gets the amount back, the caller earns the safety deposit
+    let token_client = token::Client::new(&env, &token_address);
+    assert_eq!(token_client.balance(&sender), AMOUNT);
+    assert_eq!(token_client.balance(&resolver), SAFETY_DEPOSIT);
+    assert_eq!(token_client.balance(&client.address), 0);
+}
+
+#[test]
+fn public_refund_before_cancellation_deadline() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let resolver = Address::generate(&env);
+    let contract_id = create_public_cancellation(&env, &sender, &receiver, &token_address, &client);
+
+    // Past the timelock only the sender may refund
+    let timelock = client.get_htlc(&contract_id).timelock;
+    env.ledger().with_mut(|l| l.timestamp = timelock);
+    let result = client.try_public_refund(&resolver, &contract_id);
+    assert_eq!(result, Err(Ok(HTLCError::PublicCancellationNotStarted)));
+
+    client.refund(&contract_id);
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Refunded);
+}
+
+#[test]
+fn public_refund_disabled_by_default() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let resolver = Address::generate(&env);
+    let (hashlock, _) = hashlock_pair(&env);
+    let timelock = env.ledger().timestamp() + TIMELOCK_SECS;
+
+    let contract_id = client.create_htlc(
+        &sender,
+        &receiver,
+        &AMOUNT,
+        &token_address,
+        &hashlock,
+        &timelock,
+        &SAFETY_DEPOSIT,
+    );
+
+    env.ledger()
+        .with_mut(|l| l.timestamp = timelock + TIMELOCK_SECS);
+    let result = client.try_public_refund(&resolver, &contract_id);
+    assert_eq!(result, Err(Ok(HTLCError::PublicCancellationNotStarted)));
+}
+
+#[test]
+fn create_public_cancellation_before_timelock() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.public_cancellation = params.timelock - 1;
+
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidPublicCancellation)));
+}
+
+//------------------------------------------------------------------
+//  Safety deposit in a separate asset
+//------------------------------------------------------------------
+/// Native XLM deposit environment with a separately minted swap token
+fn setup_native_deposit() -> (
+    Env,
+    Address,
+    Address,
+    Address,
+    Address,
+    HTLCContractClient<'static>,
+) {
+    let (env, sender, receiver, native_address, client) = setup_native();
+    let token_address = env.register_stellar_asset_contract(sender.clone());
+    token::StellarAssetClient::new(&env, &token_address).mint(&sender, &AMOUNT);
+    (env, sender, receiver, token_address, native_address, client)
+}
+
+fn create_with_native_deposit(
+    env: &Env,
+    sender: &Address,
+    receiver: &Address,
+    token_address: &Address,
+    native_address: &Address,
+    client: &HTLCContractClient<'static>,
+) -> (BytesN<32>, BytesN<32>) {
+    let (hashlock, preimage) = hashlock_pair(env);
+    let mut params = create_params(env, sender, receiver, token_address, &hashlock);
+    params.safety_deposit_token = native_address.clone();
+    (client.create_htlc_with_params(&params), preimage)
+}
+
+#[test]
+fn create_htlc_deposits_in_swap_token() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let timelock = env.ledger().timestamp() + TIMELOCK_SECS;
+
+    let contract_id = client.create_htlc(
+        &sender,
+        &receiver,
+        &AMOUNT,
+        &token_address,
+        &hashlock,
+        &timelock,
+        &SAFETY_DEPOSIT,
+    );
+    assert_eq!(
+        client.get_htlc(&contract_id).safety_deposit_token,
+        token_address
+    );
+}
+
+#[test]
+fn withdraw_with_native_safety_deposit() {
+    let (env, sender, receiver, token_address, native_address, client) = setup_native_deposit();
+    let (contract_id, preimage) = create_with_native_deposit(
+        &env,
+        &sender,
+        &receiver,
+        &token_address,
+        &native_address,
+        &client,
+    );
+
+    let token_client = token::Client::new(&env, &token_address);
+    let xlm = token::Client::new(&env, &native_address);
+    assert_eq!(token_client.balance(&client.address), AMOUNT);
+    assert_eq!(xlm.balance(&client.address), SAFETY_DEPOSIT);
+
+    client.withdraw(&contract_id, &preimage);
+
+    assert_eq!(token_client.balance(&receiver), AMOUNT);
+    assert_eq!(xlm.balance(&sender), AMOUNT + SAFETY_DEPOSIT);
+    assert_eq!(xlm.balance(&client.address), 0);
+}
+
+#[test]
+fn refund_with_native_safety_deposit() {
+    let (env, sender, receiver, token_address, native_address, client) = setup_native_deposit();
+    let (contract_id, _) = create_with_native_deposit(
+        &env,
+        &sender,
+        &receiver,
+        &token_address,
+        &native_address,
+        &client,
+    );
+
+    let timelock = client.get_htlc(&contract_id).timelock;
+    env.ledger().with_mut(|l| l.timestamp = timelock);
+    client.refund(&contract_id);
+
+    let token_client = token::Client::new(&env, &token_address);
+    let xlm = token::Client::new(&env, &native_address);
+    assert_eq!(token_client.balance(&sender), AMOUNT);
+    assert_eq!(xlm.balance(&sender), AMOUNT + SAFETY_DEPOSIT);
+    assert_eq!(token_client.balance(&client.address), 0);
+    assert_eq!(xlm.balance(&client.address), 0);
+}
+
+//------------------------------------------------------------------
+//  Order-hash based escrow identifier
+//------------------------------------------------------------------
+#[test]
+fn create_keyed_by_order_hash() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let order_hash = BytesN::from_array(&env, &[7u8; 32]);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.order_hash = order_hash.clone();
+
+    let contract_id = client.create_htlc_with_params(&params);
+    assert_eq!(contract_id, order_hash);
+
+    let htlc_data = client.escrow_by_order_hash(&order_hash);
+    assert_eq!(htlc_data, client.get_htlc(&contract_id));
+    assert_eq!(htlc_data.order_hash, order_hash);
+
+    client.withdraw(&order_hash, &preimage);
+    assert_eq!(client.get_status(&order_hash), HTLCStatus::Withdrawn);
+}
+
+#[test]
+fn create_duplicate_order_hash() {
+    let (env, sender, receiver, token_address, client) = setup();
+    token::StellarAssetClient::new(&env, &token_address).mint(&sender, &(AMOUNT + SAFETY_DEPOSIT));
+    let (hashlock, _) = hashlock_pair(&env);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.order_hash = BytesN::from_array(&env, &[7u8; 32]);
+
+    client.create_htlc_with_params(&params);
+    params.amount = AMOUNT / 2;
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::ContractAlreadyExists)));
+}
+
+#[test]
+fn escrow_by_order_hash_unknown() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let timelock = env.ledger().timestamp() + TIMELOCK_SECS;
+
+    // Derived contract IDs are not registered as order hashes
+    let contract_id = client.create_htlc(
+        &sender,
+        &receiver,
+        &AMOUNT,
+        &token_address,
+        &hashlock,
+        &timelock,
+        &SAFETY_DEPOSIT,
+    );
+    let result = client.try_escrow_by_order_hash(&contract_id);
+    assert_eq!(result, Err(Ok(HTLCError::ContractNotFound)));
+
+    let result = client.try_escrow_by_order_hash(&BytesN::from_array(&env, &[0u8; 32]));
+    assert_eq!(result, Err(Ok(HTLCError::ContractNotFound)));
+}
+
+#[test]
+fn order_state_tracks_lifecycle() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let order_hash = BytesN::from_array(&env, &[7u8; 32]);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.order_hash = order_hash.clone();
+
+    env.ledger().with_mut(|l| l.sequence_number = 100);
+    let contract_id = client.create_htlc_with_params(&params);
+    let state = client.get_order_state(&order_hash).unwrap();
+    assert_eq!(
+        state,
+        OrderState {
+            contract_id: contract_id.clone(),
+            created_ledger: 100,
+            amount: AMOUNT,
+            filled_amount: 0,
+            preimage: BytesN::from_array(&env, &[0u8; 32]),
+            status: HTLCStatus::Active,
+            completed_at: 0,
+        }
+    );
+
+    env.ledger().with_mut(|l| l.sequence_number = 150);
+    client.withdraw(&contract_id, &preimage);
+    let state = client.get_order_state(&order_hash).unwrap();
+    assert_eq!(state.created_ledger, 100);
+    assert_eq!(state.filled_amount, AMOUNT);
+    assert_eq!(state.preimage, preimage);
+    assert_eq!(state.status, HTLCStatus::Withdrawn);
+    assert_eq!(state.completed_at, env.ledger().timestamp());
+
+    // The record outlives the cleaned-up HTLC
+    env.ledger()
+        .with_mut(|l| l.timestamp += CLEANUP_GRACE_PERIOD);
+    client.cleanup(&contract_id);
+    assert_eq!(client.get_order_state(&order_hash), Some(state));
+}
+
+#[test]
+fn order_state_keyed_by_evm_order_hash() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let evm_order_hash = BytesN::from_array(&env, &[5u8; 32]);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.metadata = CrossChainMetadata {
+        evm_chain_id: 1,
+        evm_order_hash: evm_order_hash.clone(),
+        maker: BytesN::from_array(&env, &[0xab; 20]),
+    };
+    let contract_id = client.create_htlc_with_params(&params);
+
+    env.ledger().with_mut(|l| l.timestamp = params.timelock);
+    client.refund(&contract_id);
+    let state = client.get_order_state(&evm_order_hash).unwrap();
+    assert_eq!(state.contract_id, contract_id);
+    assert_eq!(state.filled_amount, 0);
+    assert_eq!(state.status, HTLCStatus::Refunded);
+    assert_eq!(state.completed_at, params.timelock);
+
+    // HTLCs without an order link are not recorded
+    assert_eq!(client.get_order_state(&contract_id), None);
+}
+
+//------------------------------------------------------------------
+//  Keccak-256 hashlocks
+//------------------------------------------------------------------
+fn keccak_params(
+    env: &Env,
+    sender: &Address,
+    receiver: &Address,
+    token_address: &Address,
+) -> (CreateParams, BytesN<32>) {
+    let preimage = BytesN::from_array(env, &[42u8; 32]);
+    let hashlock = env.crypto().keccak256(&preimage.clone().into());
+    let mut params = create_params(env, sender, receiver, token_address, &hashlock);
+    params.hash_algo = HashAlgo::Keccak256;
+    (params, preimage)
+}
+
+#[test]
+fn withdraw_keccak_hashlock() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (params, preimage) = keccak_params(&env, &sender, &receiver, &token_address);
+
+    let contract_id = client.create_htlc_with_params(&params);
+    assert_eq!(client.get_htlc(&contract_id).hash_algo, HashAlgo::Keccak256);
+
+    client.withdraw(&contract_id, &preimage);
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Withdrawn);
+}
+
+#[test]
+fn keccak_hashlock_rejects_sha256_preimage() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, preimage) = hashlock_pair(&env);
+
+    // A SHA-256 hashlock stored as Keccak-256 can never be unlocked
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.hash_algo = HashAlgo::Keccak256;
+    let contract_id = client.create_htlc_with_params(&params);
+
+    let result = client.try_withdraw(&contract_id, &preimage);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidPreimage)));
+}
+
+#[test]
+fn withdraw_partial_keccak_merkle_root() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let secrets = partial_secrets(&env);
+    let (root, proof) = merkle_root_and_proof_with(&env, HashAlgo::Keccak256, &secrets, PARTS);
+
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &root);
+    params.hash_algo = HashAlgo::Keccak256;
+    params.parts = PARTS;
+    let contract_id = client.create_htlc_with_params(&params);
+
+    client.withdraw_partial(
+        &contract_id,
+        &secrets.get(PARTS).unwrap(),
+        &PARTS,
+        &proof,
+        &AMOUNT,
+    );
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Withdrawn);
+}
+
+//------------------------------------------------------------------
+//  Hashlock and contract ID vectors
+//------------------------------------------------------------------
+/// Decodes a 64-digit hex string
+fn hex32(hex: &str) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap();
+    }
+    bytes
+}
+
+/// Hashlocks of fixed 32-byte secrets, recorded from this implementation:
+/// `(preimage, sha256, keccak256)`. `abi.encodePacked` leaves a `bytes32` as is, so
+/// `HTLC.sol` hashes these secrets the same way, but the values were not produced by it.
+const SECRET_VECTORS: [(&str, &str, &str); 3] = [
+    (
+        "2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a",
+        "544e62cee8033709e389e5b2755343d0d0fa8c4850215cfb6331717e80d1aea3",
+        "c017de1f45b08408ded2b451d8a840b4e903916f668080a053fa09d307fa60cf",
+    ),
+    (
+        "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+        "630dcd2966c4336691125448bbb25b4ff412a49c732db2c8abc1b8581bd710dd",
+        "8ae1aa597fa146ebd3aa2ceddf360668dea5e526567e92b0321816a4e895bd2d",
+    ),
+    (
+        // "cross-chain swap secret 00000001"
+        "63726f73732d636861696e207377617020736563726574203030303030303031",
+        "3a7667d834626207d59fabe4d9210fa94533335e3459f9c3ee8c86d697728944",
+        "4185a6f2ba652e95d67667c88479bbd11c6bc081ac24909f95cf8575df841cfd",
+    ),
+];
+
+#[test]
+fn secret_vectors_open_htlcs() {
+    for (preimage, sha256, keccak256) in SECRET_VECTORS {
+        for (hash_algo, hashlock) in [(HashAlgo::Sha256, sha256), (HashAlgo::Keccak256, keccak256)]
+        {
+            let (env, sender, receiver, token_address, client) = setup();
+            let preimage = BytesN::from_array(&env, &hex32(preimage));
+            let hashlock = BytesN::from_array(&env, &hex32(hashlock));
+            let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+            params.hash_algo = hash_algo;
+            let contract_id = client.create_htlc_with_params(&params);
+
+            client.withdraw(&contract_id, &preimage);
+            assert_eq!(client.get_status(&contract_id), HTLCStatus::Withdrawn);
+        }
+    }
+}
+
+/// Snapshot of the contract IDs of ed25519 account parties on testnet, recorded from this
+/// implementation so that any change to the derivation shows up. The ID packs
+/// `sender, receiver, amount, hashlock, timelock, timestamp` with each address as the
+/// SHA-256 of its XDR, the amount as 16 big-endian bytes and the timestamps as 8, then
+/// appends the network ID, so it is not the ID `HTLC.sol` computes for the same swap.
+/// `(sender key, receiver key, amount, hashlock, timelock, timestamp, contract ID)`
+const CONTRACT_ID_SNAPSHOT_VECTORS: [(&str, &str, i128, &str, u64, u64, &str); 3] = [
+    (
+        "0101010101010101010101010101010101010101010101010101010101010101",
+        "0202020202020202020202020202020202020202020202020202020202020202",
+        AMOUNT,
+        "544e62cee8033709e389e5b2755343d0d0fa8c4850215cfb6331717e80d1aea3",
+        1_700_003_600,
+        1_700_000_000,
+        "efbb7efa09125135d582c16e94872af48cee9d200d5db320feea198ff491271b",
+    ),
+    (
+        "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+        "fffefdfcfbfaf9f8f7f6f5f4f3f2f1f0efeeedecebeae9e8e7e6e5e4e3e2e1e0",
+        i128::MAX,
+        "8ae1aa597fa146ebd3aa2ceddf360668dea5e526567e92b0321816a4e895bd2d",
+        u64::MAX,
+        0,
+        "70a31ec74a163cf84ffefe11f3f4300fd7f962d2b9990263a0242cfbfa6246dc",
+    ),
+    (
+        "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+        "5555555555555555555555555555555555555555555555555555555555555555",
+        1,
+        "3a7667d834626207d59fabe4d9210fa94533335e3459f9c3ee8c86d697728944",
+        1_720_086_400,
+        1_720_000_000,
+        "18a03f0c40e4ce99d45abdb5defd06550d5529c46adf0e4a9daa55f2c7f76555",
+    ),
+];
+
+/// SHA-256 of the testnet passphrase, "Test SDF Network ; September 2015"
+const TESTNET_NETWORK_ID: &str = "cee0302d59844d32bdca915c8203dd44b33fbb7edc19051ea37abedf28ecd472";
+
+#[test]
+fn contract_id_snapshot_vectors() {
+    let (env, _, _, _, client) = setup();
+    env.ledger()
+        .with_mut(|l| l.network_id = hex32(TESTNET_NETWORK_ID));
+
+    for (sender, receiver, amount, hashlock, timelock, timestamp, expected) in
+        CONTRACT_ID_SNAPSHOT_VECTORS
+    {
+        let sender = HTLCContract::account_address(&env, &BytesN::from_array(&env, &hex32(sender)));
+        let receiver =
+            HTLCContract::account_address(&env, &BytesN::from_array(&env, &hex32(receiver)));
+        let contract_id = client.compute_contract_id(
+            &sender,
+            &receiver,
+            &amount,
+            &BytesN::from_array(&env, &hex32(hashlock)),
+            &timelock,
+            &timestamp,
+        );
+        assert_eq!(contract_id.to_array(), hex32(expected));
+    }
+}
+
+//------------------------------------------------------------------
+//  Batch creation
+//------------------------------------------------------------------
+#[test]
+fn create_htlc_batch_success() {
+    let (env, sender, receiver, token_address, client) = setup();
+    token::StellarAssetClient::new(&env, &token_address).mint(&sender, &(AMOUNT + SAFETY_DEPOSIT));
+    let (hashlock, preimage) = hashlock_pair(&env);
+
+    let first = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let mut second = first.clone();
+    second.receiver = Address::generate(&env);
+    second.hashlock = BytesN::from_array(&env, &[1u8; 32]);
+    let batch = Vec::from_array(&env, [first, second.clone()]);
+
+    let contract_ids = client.create_htlc_batch(&batch);
+    assert_eq!(contract_ids.len(), 2);
+    assert_ne!(contract_ids.get(0), contract_ids.get(1));
+    assert_eq!(
+        client.get_htlc(&contract_ids.get(1).unwrap()).receiver,
+        second.receiver
+    );
+
+    let token_client = token::Client::new(&env, &token_address);
+    assert_eq!(
+        token_client.balance(&client.address),
+        2 * (AMOUNT + SAFETY_DEPOSIT)
+    );
+
+    client.withdraw(&contract_ids.get(0).unwrap(), &preimage);
+    assert_eq!(token_client.balance(&receiver), AMOUNT);
+}
+
+#[test]
+fn create_htlc_batch_is_atomic() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+
+    let first = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let mut second = first.clone();
+    second.amount = 0;
+    let batch = Vec::from_array(&env, [first.clone(), second]);
+
+    let result = client.try_create_htlc_batch(&batch);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidAmount)));
+
+    // The valid first item is rolled back together with the failing one
+    let token_client = token::Client::new(&env, &token_address);
+    assert_eq!(token_client.balance(&client.address), 0);
+    assert_eq!(token_client.balance(&sender), AMOUNT + SAFETY_DEPOSIT);
+
+    let contract_ids = client.create_htlc_batch(&Vec::from_array(&env, [first]));
+    assert_eq!(contract_ids.len(), 1);
+}
+
+//------------------------------------------------------------------
+//  Batch withdrawal
+//------------------------------------------------------------------
+/// Creates one HTLC per preimage seed, all from `sender` to `receiver`
+fn create_batch(
+    env: &Env,
+    sender: &Address,
+    receiver: &Address,
+    token_address: &Address,
+    client: &HTLCContractClient<'static>,
+    seeds: &[u8],
+) -> Vec<(BytesN<32>, BytesN<32>)> {
+    token::StellarAssetClient::new(env, token_address).mint(
+        sender,
+        &((AMOUNT + SAFETY_DEPOSIT) * (seeds.len() as i128 - 1)),
+    );
+
+    let mut withdrawals = Vec::new(env);
+    for seed in seeds {
+        let preimage = BytesN::from_array(env, &[*seed; 32]);
+        let hashlock = env.crypto().sha256(&preimage.clone().into());
+        let params = create_params(env, sender, receiver, token_address, &hashlock);
+        withdrawals.push_back((client.create_htlc_with_params(&params), preimage));
+    }
+    withdrawals
+}
+
+#[test]
+fn withdraw_batch_success() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let withdrawals = create_batch(
+        &env,
+        &sender,
+        &receiver,
+        &token_address,
+        &client,
+        &[1, 2, 3],
+    );
+
+    client.withdraw_batch(&withdrawals);
+    for (contract_id, _) in withdrawals.iter() {
+        assert_eq!(client.get_status(&contract_id), HTLCStatus::Withdrawn);
+    }
+
+    let token_client = token::Client::new(&env, &token_address);
+    assert_eq!(token_client.balance(&receiver), 3 * AMOUNT);
+    assert_eq!(token_client.balance(&sender), 3 * SAFETY_DEPOSIT);
+    assert_eq!(token_client.balance(&client.address), 0);
+}
+
+#[test]
+fn withdraw_batch_is_atomic() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let mut withdrawals = create_batch(&env, &sender, &receiver, &token_address, &client, &[1, 2]);
+
+    // Swap in a wrong preimage for the last item
+    let (contract_id, _) = withdrawals.get(1).unwrap();
+    withdrawals.set(1, (contract_id, BytesN::from_array(&env, &[9u8; 32])));
+
+    let result = client.try_withdraw_batch(&withdrawals);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidPreimage)));
+
+    // The valid first item stays claimable
+    let (first_id, first_preimage) = withdrawals.get(0).unwrap();
+    assert_eq!(client.get_status(&first_id), HTLCStatus::Active);
+    client.withdraw(&first_id, &first_preimage);
+    assert_eq!(client.get_status(&first_id), HTLCStatus::Withdrawn);
+}
+
+#[test]
+fn withdraw_batch_duplicate_item() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let withdrawals = create_batch(&env, &sender, &receiver, &token_address, &client, &[1]);
+
+    let mut duplicated = withdrawals.clone();
+    duplicated.append(&withdrawals);
+    let result = client.try_withdraw_batch(&duplicated);
+    assert_eq!(result, Err(Ok(HTLCError::AlreadyWithdrawn)));
+}
+
+//------------------------------------------------------------------
+//  Immutables-verified withdrawal and refund
+//------------------------------------------------------------------
+fn immutables_for(env: &Env, params: &CreateParams) -> HTLCImmutables {
+    HTLCImmutables {
+        sender: params.sender.clone(),
+        receiver: params.receiver.clone(),
+        token_address: params.token_address.clone(),
+        amount: params.amount,
+        hashlock: params.hashlock.clone(),
+        timelock: params.timelock,
+        timestamp: env.ledger().timestamp(),
+        salt: params.salt.clone(),
+        extension: params.extension.clone(),
+    }
+}
+
+#[test]
+fn withdraw_with_matching_immutables() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+
+    client.withdraw_with_immutables(&immutables_for(&env, &params), &preimage);
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Withdrawn);
+}
+
+#[test]
+fn refund_with_matching_immutables() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+    let immutables = immutables_for(&env, &params);
+
+    env.ledger().with_mut(|l| l.timestamp = params.timelock);
+    client.refund_with_immutables(&immutables);
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Refunded);
+}
+
+#[test]
+fn mismatched_immutables_rejected() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    client.create_htlc_with_params(&params);
+
+    let immutables = immutables_for(&env, &params);
+    let tampered = [
+        HTLCImmutables {
+            amount: AMOUNT + 1,
+            ..immutables.clone()
+        },
+        HTLCImmutables {
+            timelock: params.timelock + 1,
+            ..immutables.clone()
+        },
+        HTLCImmutables {
+            receiver: Address::generate(&env),
+            ..immutables.clone()
+        },
+        HTLCImmutables {
+            token_address: Address::generate(&env),
+            ..immutables.clone()
+        },
+    ];
+    for tampered in tampered {
+        let result = client.try_withdraw_with_immutables(&tampered, &preimage);
+        assert_eq!(result, Err(Ok(HTLCError::ContractNotFound)));
+    }
+}
+
+#[test]
+fn immutables_never_resolve_to_order_keyed_htlc() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+
+    // Key an HTLC by the very ID that immutables of a half-sized one derive
+    let forged = HTLCImmutables {
+        amount: AMOUNT / 2,
+        ..immutables_for(&env, &params)
+    };
+    let order_hash = client.compute_contract_id(
+        &forged.sender,
+        &forged.receiver,
+        &forged.amount,
+        &forged.hashlock,
+        &forged.timelock,
+        &forged.timestamp,
+    );
+    let contract_id = client.create_htlc_with_params(&CreateParams {
+        order_hash: order_hash.clone(),
+        ..params
+    });
+    assert_eq!(contract_id, order_hash);
+
+    let result = client.try_withdraw_with_immutables(&forged, &preimage);
+    assert_eq!(result, Err(Ok(HTLCError::ContractNotFound)));
+}
+
+#[test]
+fn escrow_withdrawn_with_immutables() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_escrow(&params);
+    let immutables = immutables_for(&env, &params);
+
+    // Only the immutables locate the escrow
+    assert!(client.contract_exists(&contract_id));
+    assert_eq!(
+        client.try_get_htlc(&contract_id),
+        Err(Ok(HTLCError::ContractNotFound))
+    );
+    let htlc_data = client.get_escrow(&immutables);
+    assert_eq!(htlc_data.contract_id, contract_id);
+    assert_eq!(htlc_data.amount, AMOUNT);
+    assert_eq!(htlc_data.safety_deposit, SAFETY_DEPOSIT);
+    client.extend_htlc_ttl(&contract_id);
+
+    let tampered = HTLCImmutables {
+        receiver: sender.clone(),
+        ..immutables.clone()
+    };
+    let result = client.try_withdraw_with_immutables(&tampered, &preimage);
+    assert_eq!(result, Err(Ok(HTLCError::ContractNotFound)));
+
+    client.withdraw_with_immutables(&immutables, &preimage);
+    let htlc_data = client.get_escrow(&immutables);
+    assert_eq!(htlc_data.status, HTLCStatus::Withdrawn);
+    assert_eq!(htlc_data.preimage, preimage);
+    let token_client = token::Client::new(&env, &token_address);
+    assert_eq!(token_client.balance(&receiver), AMOUNT);
+    assert_eq!(token_client.balance(&sender), SAFETY_DEPOSIT);
+
+    let result = client.try_withdraw_with_immutables(&immutables, &preimage);
+    assert_eq!(result, Err(Ok(HTLCError::AlreadyWithdrawn)));
+}
+
+#[test]
+fn escrow_refunded_with_immutables() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    client.create_escrow(&params);
+    let immutables = immutables_for(&env, &params);
+
+    env.ledger().with_mut(|l| l.timestamp = params.timelock);
+    client.refund_with_immutables(&immutables);
+    assert_eq!(client.get_escrow(&immutables).status, HTLCStatus::Refunded);
+    assert_eq!(
+        token::Client::new(&env, &token_address).balance(&sender),
+        AMOUNT + SAFETY_DEPOSIT
+    );
+}
+
+#[test]
+fn escrow_entry_holds_only_mutable_state() {
+    let (env, sender, receiver, token_address, client) = setup();
+    token::StellarAssetClient::new(&env, &token_address).mint(&sender, &(AMOUNT + SAFETY_DEPOSIT));
+    let (hashlock, _) = hashlock_pair(&env);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let htlc_id = client.create_htlc_with_params(&params);
+    params.salt = BytesN::from_array(&env, &[1; 32]);
+    params.allow_reuse = true;
+    let escrow_id = client.create_escrow(&params);
+
+    let entry_len = |key: DataKey| {
+        env.as_contract(&client.address, || {
+            let entry: Val = env.storage().persistent().get(&key).unwrap();
+            entry.to_xdr(&env).len()
+        })
+    };
+    let escrow_len = entry_len(DataKey::Escrow(escrow_id));
+    let htlc_len = entry_len(DataKey::HTLCEntry(htlc_id));
+    assert!(
+        4 * escrow_len < htlc_len,
+        "escrow entry is {escrow_len} bytes, HTLC entry {htlc_len}"
+    );
+}
+
+#[test]
+fn escrow_rejects_params_outside_immutables() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+
+    let unsupported = [
+        CreateParams {
+            memo: Bytes::from_slice(&env, b"order-7"),
+            ..params.clone()
+        },
+        CreateParams {
+            order_hash: BytesN::from_array(&env, &[9; 32]),
+            ..params.clone()
+        },
+        CreateParams {
+            public_withdrawal: params.timelock - 1,
+            ..params.clone()
+        },
+        CreateParams {
+            taker: Address::generate(&env),
+            ..params.clone()
+        },
+    ];
+    for params in unsupported {
+        let result = client.try_create_escrow(&params);
+        assert_eq!(result, Err(Ok(HTLCError::InvalidEscrowParams)));
+    }
+}
+
+//------------------------------------------------------------------
+//  Withdrawal to a destination address
+//------------------------------------------------------------------
+#[test]
+fn withdraw_to_destination() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let cold_wallet = Address::generate(&env);
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+
+    client.withdraw_to(&contract_id, &preimage, &cold_wallet);
+
+    // Only the receiver authorized the claim
+    assert_eq!(env.auths().len(), 1);
+    assert_eq!(env.auths()[0].0, receiver);
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Withdrawn);
+
+    let token_client = token::Client::new(&env, &token_address);
+    assert_eq!(token_client.balance(&cold_wallet), AMOUNT);
+    assert_eq!(token_client.balance(&receiver), 0);
+    assert_eq!(token_client.balance(&sender), SAFETY_DEPOSIT);
+}
+
+#[test]
+fn withdraw_to_wrong_preimage() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+
+    let bad_preimage = BytesN::from_array(&env, &[1u8; 32]);
+    let result = client.try_withdraw_to(&contract_id, &bad_preimage, &Address::generate(&env));
+    assert_eq!(result, Err(Ok(HTLCError::InvalidPreimage)));
+}
+
+//------------------------------------------------------------------
+//  Withdrawal relocked into a new HTLC
+//------------------------------------------------------------------
+#[test]
+fn withdraw_and_relock_chains_escrows() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let next_hop = Address::generate(&env);
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+
+    // The receiver holds no funds of its own to pay a new deposit with
+    let mut new_params = create_params(&env, &receiver, &next_hop, &token_address, &hashlock);
+    new_params.safety_deposit = 0;
+    let new_contract_id = client.withdraw_and_relock(&contract_id, &preimage, &new_params);
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Withdrawn);
+
+    let relocked = client.get_htlc(&new_contract_id);
+    assert_eq!(relocked.sender, receiver);
+    assert_eq!(relocked.receiver, next_hop);
+    assert_eq!(relocked.amount, AMOUNT);
+
+    let token_client = token::Client::new(&env, &token_address);
+    assert_eq!(token_client.balance(&receiver), 0);
+    assert_eq!(token_client.balance(&sender), SAFETY_DEPOSIT);
+    assert_eq!(
+        client.get_token_accounting(&token_address),
+        TokenAccounting {
+            locked: AMOUNT,
+            balance: AMOUNT
+        }
+    );
+
+    client.withdraw(&new_contract_id, &preimage);
+    assert_eq!(token_client.balance(&next_hop), AMOUNT);
+}
+
+#[test]
+fn withdraw_and_relock_locks_amount_after_fees() {
+    let (env, sender, receiver, token_address, _) = setup();
+    let client = initialized(&env, &Address::generate(&env), false);
+    client.set_protocol_fee(&FEE_BPS, &Address::generate(&env));
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+
+    let mut new_params = create_params(
+        &env,
+        &receiver,
+        &Address::generate(&env),
+        &token_address,
+        &hashlock,
+    );
+    new_params.safety_deposit = 0;
+    let new_contract_id = client.withdraw_and_relock(&contract_id, &preimage, &new_params);
+
+    let fee = AMOUNT * FEE_BPS as i128 / BPS_DENOMINATOR;
+    assert_eq!(client.get_htlc(&new_contract_id).amount, AMOUNT - fee);
+}
+
+#[test]
+fn withdraw_and_relock_must_chain_onto_claim() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+
+    // Only the receiver's own claim, in the claimed token, can fund the new HTLC
+    let next_hop = Address::generate(&env);
+    let mut new_params = create_params(&env, &sender, &next_hop, &token_address, &hashlock);
+    new_params.safety_deposit = 0;
+    let result = client.try_withdraw_and_relock(&contract_id, &preimage, &new_params);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidSwap)));
+
+    new_params.sender = receiver.clone();
+    new_params.safety_deposit_payer = receiver.clone();
+    new_params.token_address = env.register_stellar_asset_contract(sender.clone());
+    let result = client.try_withdraw_and_relock(&contract_id, &preimage, &new_params);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidSwap)));
+
+    new_params.token_address = token_address;
+    let bad_preimage = BytesN::from_array(&env, &[1u8; 32]);
+    let result = client.try_withdraw_and_relock(&contract_id, &bad_preimage, &new_params);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidPreimage)));
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Active);
+}
+
+//------------------------------------------------------------------
+//  Admin and pausing
+//------------------------------------------------------------------
+#[test]
+fn initialize_sets_admin() {
+    let env = new_env();
+    let admin = Address::generate(&env);
+    let client = register_htlc(&env, &admin);
+    let salt = BytesN::from_array(&env, &DEPLOY_SALT);
+
+    assert_eq!(client.try_get_admin(), Err(Ok(HTLCError::NotInitialized)));
+    client.initialize(&admin, &false, &salt);
+    assert_eq!(client.get_admin(), admin);
+    assert!(!client.is_paused());
+
+    let result = client.try_initialize(&admin, &false, &salt);
+    assert_eq!(result, Err(Ok(HTLCError::AlreadyInitialized)));
+}
+
+#[test]
+fn initialize_requires_deployer() {
+    let env = new_env();
+    let deployer = Address::generate(&env);
+    let client = register_htlc(&env, &deployer);
+    let salt = BytesN::from_array(&env, &DEPLOY_SALT);
+
+    // A third party cannot claim the admin powers of an uninitialized deployment
+    let result = client.try_initialize(&Address::generate(&env), &false, &salt);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidSigner)));
+    let other_salt = BytesN::from_array(&env, &[8; 32]);
+    let result = client.try_initialize(&deployer, &false, &other_salt);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidSigner)));
+    assert_eq!(client.try_get_admin(), Err(Ok(HTLCError::NotInitialized)));
+
+    client.initialize(&deployer, &false, &salt);
+    assert_eq!(client.get_admin(), deployer);
+}
+
+#[test]
+fn permissionless_deployment_has_no_admin_powers() {
+    let (env, sender, receiver, token_address, _) = setup();
+    let client = initialized(&env, &Address::generate(&env), true);
+    assert!(client.get_config().permissionless);
+
+    let (hashlock, _) = hashlock_pair(&env);
+    let contract_id = client.create_htlc_with_params(&create_params(
+        &env,
+        &sender,
+        &receiver,
+        &token_address,
+        &hashlock,
+    ));
+    assert_eq!(client.try_pause(), Err(Ok(HTLCError::InvalidSigner)));
+    assert_eq!(
+        client.try_freeze(&contract_id),
+        Err(Ok(HTLCError::InvalidSigner))
+    );
+    assert_eq!(
+        client.try_upgrade(&BytesN::from_array(&env, &[0; 32])),
+        Err(Ok(HTLCError::InvalidSigner))
+    );
+    assert_eq!(
+        client.try_set_protocol_fee(&30, &Address::generate(&env)),
+        Err(Ok(HTLCError::InvalidSigner))
+    );
+    assert_eq!(
+        client.try_set_enforce_allowlist(&true),
+        Err(Ok(HTLCError::InvalidSigner))
+    );
+    assert_eq!(
+        client.try_propose_admin(&Address::generate(&env)),
+        Err(Ok(HTLCError::InvalidSigner))
+    );
+}
+
+#[test]
+fn pause_requires_initialization() {
+    let (_, _, _, _, client) = setup();
+
+    assert_eq!(client.try_pause(), Err(Ok(HTLCError::NotInitialized)));
+    assert_eq!(client.try_unpause(), Err(Ok(HTLCError::NotInitialized)));
+}
+
+#[test]
+fn admin_transfer_after_delay() {
+    let (env, _, _, _, _) = setup();
+    let admin = Address::generate(&env);
+    let new_admin = Address::generate(&env);
+    let client = initialized(&env, &admin, false);
+
+    assert_eq!(
+        client.try_accept_admin(),
+        Err(Ok(HTLCError::NoPendingAdmin))
+    );
+    client.propose_admin(&new_admin);
+    assert_eq!(env.auths()[0].0, admin);
+    let pending = client.get_pending_admin().unwrap();
+    assert_eq!(pending.new_admin, new_admin);
+    assert_eq!(
+        pending.valid_after,
+        env.ledger().timestamp() + ADMIN_TRANSFER_DELAY
+    );
+
+    env.ledger()
+        .with_mut(|l| l.timestamp = pending.valid_after - 1);
+    assert_eq!(
+        client.try_accept_admin(),
+        Err(Ok(HTLCError::AdminTransferNotReady))
+    );
+    assert_eq!(client.get_admin(), admin);
+
+    env.ledger().with_mut(|l| l.timestamp = pending.valid_after);
+    client.accept_admin();
+    assert_eq!(env.auths()[0].0, new_admin);
+    assert_eq!(client.get_admin(), new_admin);
+    assert_eq!(client.get_pending_admin(), None);
+}
+
+#[test]
+fn propose_admin_requires_initialization() {
+    let (env, _, _, _, client) = setup();
+
+    let result = client.try_propose_admin(&Address::generate(&env));
+    assert_eq!(result, Err(Ok(HTLCError::NotInitialized)));
+}
+
+#[test]
+fn get_config_tracks_admin_setters() {
+    let env = new_env();
+    let admin = Address::generate(&env);
+    let client = register_htlc(&env, &admin);
+
+    assert_eq!(client.try_get_config(), Err(Ok(HTLCError::NotInitialized)));
+    client.initialize(&admin, &false, &BytesN::from_array(&env, &DEPLOY_SALT));
+    assert_eq!(
+        client.get_config(),
+        Config {
+            admin: admin.clone(),
+            permissionless: false,
+            paused: false,
+            fee_bps: 0,
+            fee_collector: admin.clone(),
+            min_timelock_duration: 0,
+            max_timelock_duration: u64::MAX,
+            max_amount: i128::MAX,
+            min_safety_deposit: 0,
+            ttl_extension: DEFAULT_TTL_EXTENSION,
+            enforce_allowlist: false,
+            validator: client.address.clone(),
+            admin_rescue: false,
+            min_amount: 0,
+            max_freeze_duration: 0,
+            max_creations_per_window: 0,
+            rate_limit_window: 0,
+            resolver_registry: client.address.clone(),
+            deposit_policy_bps: 0,
+            deposit_policy_min: 0,
+            deposit_policy_max: 0,
+        }
+    );
+
+    let collector = Address::generate(&env);
+    client.pause();
+    client.set_protocol_fee(&30, &collector);
+    client.set_timelock_bounds(&TIMELOCK_SECS, &(2 * TIMELOCK_SECS));
+    client.set_max_amount(&AMOUNT);
+    client.set_min_safety_deposit(&SAFETY_DEPOSIT);
+    assert_eq!(
+        client.get_config(),
+        Config {
+            admin,
+            permissionless: false,
+            paused: true,
+            fee_bps: 30,
+            fee_collector: collector,
+            min_timelock_duration: TIMELOCK_SECS,
+            max_timelock_duration: 2 * TIMELOCK_SECS,
+            max_amount: AMOUNT,
+            min_safety_deposit: SAFETY_DEPOSIT,
+            ttl_extension: DEFAULT_TTL_EXTENSION,
+            enforce_allowlist: false,
+            validator: client.address.clone(),
+            admin_rescue: false,
+            min_amount: 0,
+            max_freeze_duration: 0,
+            max_creations_per_window: 0,
+            rate_limit_window: 0,
+            resolver_registry: client.address.clone(),
+            deposit_policy_bps: 0,
+            deposit_policy_min: 0,
+            deposit_policy_max: 0,
+        }
+    );
+}
+
+#[test]
+fn token_allowlist_enforced() {
+    let (env, sender, receiver, token_address, _) = setup();
+    let client = initialized(&env, &Address::generate(&env), false);
+    let (hashlock, _) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+
+    // Unvetted tokens are accepted until enforcement is switched on
+    client.set_enforce_allowlist(&true);
+    assert!(client.get_config().enforce_allowlist);
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::TokenNotAllowed)));
+
+    client.add_allowed_token(&token_address);
+    assert!(client.is_token_allowed(&token_address));
+    client.create_htlc_with_params(&params);
+
+    client.remove_allowed_token(&token_address);
+    assert!(!client.is_token_allowed(&token_address));
+}
+
+#[test]
+fn min_safety_deposit_enforced() {
+    let (env, sender, receiver, token_address, _) = setup();
+    let client = initialized(&env, &Address::generate(&env), false);
+    client.set_min_safety_deposit(&SAFETY_DEPOSIT);
+    assert_eq!(client.get_min_safety_deposit(), SAFETY_DEPOSIT);
+
+    let (hashlock, _) = hashlock_pair(&env);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.safety_deposit = SAFETY_DEPOSIT - 1;
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidSafetyDeposit)));
+
+    params.safety_deposit = SAFETY_DEPOSIT;
+    client.create_htlc_with_params(&params);
+
+    let result = client.try_set_min_safety_deposit(&-1);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidSafetyDeposit)));
+}
+
+#[test]
+fn deposit_policy_sizes_safety_deposit() {
+    let (env, sender, receiver, token_address, _) = setup();
+    let client = initialized(&env, &Address::generate(&env), false);
+    // 10% of the amount, between 1 and 20 XLM
+    client.set_deposit_policy(&1_000, &10_000_000, &200_000_000);
+    assert_eq!(
+        client.get_deposit_policy(),
+        Some(DepositPolicy {
+            bps: 1_000,
+            min: 10_000_000,
+            max: 200_000_000
+        })
+    );
+    assert_eq!(client.get_required_safety_deposit(&AMOUNT), SAFETY_DEPOSIT);
+    assert_eq!(client.get_required_safety_deposit(&1_000), 10_000_000);
+    assert_eq!(client.get_required_safety_deposit(&i128::MAX), 200_000_000);
+
+    let (hashlock, _) = hashlock_pair(&env);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.safety_deposit = 0;
+    params.use_deposit_policy = true;
+    let contract_id = client.create_htlc_with_params(&params);
+    assert_eq!(client.get_htlc(&contract_id).safety_deposit, SAFETY_DEPOSIT);
+    assert_eq!(token::Client::new(&env, &token_address).balance(&sender), 0);
+}
+
+#[test]
+fn deposit_policy_rejects_low_explicit_deposit() {
+    let (env, sender, receiver, token_address, _) = setup();
+    let client = initialized(&env, &Address::generate(&env), false);
+    client.set_deposit_policy(&1_000, &0, &i128::MAX);
+
+    let (hashlock, _) = hashlock_pair(&env);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.safety_deposit = SAFETY_DEPOSIT - 1;
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidSafetyDeposit)));
+
+    // The flag only fills in a missing deposit and never lowers an explicit one
+    params.use_deposit_policy = true;
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidSafetyDeposit)));
+
+    params.safety_deposit = SAFETY_DEPOSIT;
+    client.create_htlc_with_params(&params);
+}
+
+#[test]
+fn set_deposit_policy_validation() {
+    let (env, _, _, _, _) = setup();
+    let client = initialized(&env, &Address::generate(&env), false);
+
+    let result = client.try_set_deposit_policy(&10_001, &0, &0);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidSafetyDeposit)));
+    let result = client.try_set_deposit_policy(&100, &-1, &0);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidSafetyDeposit)));
+    let result = client.try_set_deposit_policy(&100, &2, &1);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidSafetyDeposit)));
+
+    // A zero share turns the policy off, leaving only the configured minimum
+    client.set_deposit_policy(&100, &SAFETY_DEPOSIT, &SAFETY_DEPOSIT);
+    client.set_min_safety_deposit(&1);
+    client.set_deposit_policy(&0, &0, &0);
+    assert_eq!(client.get_deposit_policy(), None);
+    assert_eq!(client.get_required_safety_deposit(&AMOUNT), 1);
+}
+
+#[test]
+fn pause_blocks_creation() {
+    let (env, sender, receiver, token_address, _) = setup();
+    let admin = Address::generate(&env);
+    let client = initialized(&env, &admin, false);
+    let (hashlock, _) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+
+    client.pause();
+    assert_eq!(env.auths()[0].0, admin);
+    assert!(client.is_paused());
+
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::ContractPaused)));
+    let batch = Vec::from_array(&env, [params.clone()]);
+    let result = client.try_create_htlc_batch(&batch);
+    assert_eq!(result, Err(Ok(HTLCError::ContractPaused)));
+
+    client.unpause();
+    assert!(!client.is_paused());
+    client.create_htlc_with_params(&params);
+}
+
+#[test]
+fn pause_allows_withdraw_and_refund() {
+    let (env, sender, receiver, token_address, _) = setup();
+    token::StellarAssetClient::new(&env, &token_address).mint(&sender, &(AMOUNT + SAFETY_DEPOSIT));
+    let client = initialized(&env, &Address::generate(&env), false);
+    let withdrawn = create_batch(&env, &sender, &receiver, &token_address, &client, &[1]);
+    let refunded = create_batch(&env, &sender, &receiver, &token_address, &client, &[2]);
+
+    client.pause();
+
+    let (withdrawn_id, preimage) = withdrawn.get(0).unwrap();
+    client.withdraw(&withdrawn_id, &preimage);
+    assert_eq!(client.get_status(&withdrawn_id), HTLCStatus::Withdrawn);
+
+    let (refunded_id, _) = refunded.get(0).unwrap();
+    let timelock = client.get_htlc(&refunded_id).timelock;
+    env.ledger().with_mut(|l| l.timestamp = timelock);
+    client.refund(&refunded_id);
+    assert_eq!(client.get_status(&refunded_id), HTLCStatus::Refunded);
+}
+
+//------------------------------------------------------------------
+//  Upgrades
+//------------------------------------------------------------------
+#[test]
+fn upgrade_keeps_htlc_data() {
+    let (env, sender, receiver, token_address, _) = setup();
+    let admin = Address::generate(&env);
+    let client = initialized(&env, &admin, false);
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+    let htlc_data = client.get_htlc(&contract_id);
+
+    // Native test contracts are upgraded to the empty Wasm, then re-attached
+    let new_wasm_hash = env.deployer().upload_contract_wasm(Bytes::new(&env));
+    client.upgrade(&new_wasm_hash);
+    assert_eq!(env.auths()[0].0, admin);
+    env.register_contract(Some(&client.address), HTLCContract);
+
+    assert_eq!(client.version(), 1);
+    assert_eq!(client.get_admin(), admin);
+    assert_eq!(client.get_htlc(&contract_id), htlc_data);
+    client.withdraw(&contract_id, &preimage);
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Withdrawn);
+}
+
+#[test]
+fn upgrade_requires_initialization() {
+    let (env, _, _, _, client) = setup();
+
+    let new_wasm_hash = env.deployer().upload_contract_wasm(Bytes::new(&env));
+    let result = client.try_upgrade(&new_wasm_hash);
+    assert_eq!(result, Err(Ok(HTLCError::NotInitialized)));
+}
+
+//------------------------------------------------------------------
+//  Resolver whitelist
+//------------------------------------------------------------------
+fn create_resolvers_only(
+    env: &Env,
+    sender: &Address,
+    receiver: &Address,
+    token_address: &Address,
+    client: &HTLCContractClient<'static>,
+) -> (BytesN<32>, BytesN<32>) {
+    let (hashlock, preimage) = hashlock_pair(env);
+    let mut params = create_params(env, sender, receiver, token_address, &hashlock);
+    params.public_withdrawal = env.ledger().timestamp() + EXCLUSIVE_SECS;
+    params.resolvers_only = true;
+    (client.create_htlc_with_params(&params), preimage)
+}
+
+#[test]
+fn add_and_remove_resolver() {
+    let (env, _, _, _, _) = setup();
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let client = initialized(&env, &admin, false);
+
+    client.add_resolver(&resolver);
+    assert_eq!(env.auths()[0].0, admin);
+    assert!(client.is_resolver(&resolver));
+
+    client.remove_resolver(&resolver);
+    assert!(!client.is_resolver(&resolver));
+}
+
+#[test]
+fn resolvers_only_withdraw_by_whitelisted_receiver() {
+    let (env, sender, receiver, token_address, _) = setup();
+    let client = initialized(&env, &Address::generate(&env), false);
+    client.add_resolver(&receiver);
+    let (contract_id, preimage) =
+        create_resolvers_only(&env, &sender, &receiver, &token_address, &client);
+
+    client.withdraw(&contract_id, &preimage);
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Withdrawn);
+}
+
+#[test]
+fn resolvers_only_rejects_unlisted_receiver() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (contract_id, preimage) =
+        create_resolvers_only(&env, &sender, &receiver, &token_address, &client);
+
+    let result = client.try_withdraw(&contract_id, &preimage);
+    assert_eq!(result, Err(Ok(HTLCError::ResolverNotWhitelisted)));
+
+    // The restriction ends with the exclusive period
+    let public_withdrawal = client.get_htlc(&contract_id).public_withdrawal;
+    env.ledger().with_mut(|l| l.timestamp = public_withdrawal);
+    client.withdraw(&contract_id, &preimage);
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Withdrawn);
+}
+
+#[test]
+fn resolvers_only_partial_fill() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let secrets = partial_secrets(&env);
+    let (root, proof) = merkle_root_and_proof(&env, &secrets, PARTS);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &root);
+    params.parts = PARTS;
+    params.resolvers_only = true;
+    let contract_id = client.create_htlc_with_params(&params);
+
+    let secret = secrets.get(PARTS).unwrap();
+    let result = client.try_withdraw_partial(&contract_id, &secret, &PARTS, &proof, &AMOUNT);
+    assert_eq!(result, Err(Ok(HTLCError::ResolverNotWhitelisted)));
+}
+
+/// Deploys a bonding registry over `bond_token` and points the HTLC contract at it
+fn setup_resolver_registry(
+    env: &Env,
+    bond_token: &Address,
+    client: &HTLCContractClient<'static>,
+) -> registry::ResolverRegistryClient<'static> {
+    let registry_id = env.register_contract(None, registry::ResolverRegistry);
+    let registry_client = registry::ResolverRegistryClient::new(env, &registry_id);
+    registry_client.initialize(&Address::generate(env), bond_token, &SAFETY_DEPOSIT, &0);
+    client.set_resolver_registry(&registry_id);
+    registry_client
+}
+
+#[test]
+fn resolver_registry_set_and_removed() {
+    let (env, _, _, token_address, _) = setup();
+    let client = initialized(&env, &Address::generate(&env), false);
+    assert_eq!(client.get_resolver_registry(), None);
+
+    let registry_client = setup_resolver_registry(&env, &token_address, &client);
+    assert_eq!(
+        client.get_resolver_registry(),
+        Some(registry_client.address)
+    );
+
+    client.remove_resolver_registry();
+    assert_eq!(client.get_resolver_registry(), None);
+}
+
+#[test]
+fn resolvers_only_withdraw_by_bonded_receiver() {
+    let (env, sender, receiver, token_address, _) = setup();
+    let client = initialized(&env, &Address::generate(&env), false);
+    let registry_client = setup_resolver_registry(&env, &token_address, &client);
+    token::StellarAssetClient::new(&env, &token_address).mint(&receiver, &SAFETY_DEPOSIT);
+    let (contract_id, preimage) =
+        create_resolvers_only(&env, &sender, &receiver, &token_address, &client);
+
+    // The registry replaces the whitelist rather than extending it
+    client.add_resolver(&receiver);
+    let result = client.try_withdraw(&contract_id, &preimage);
+    assert_eq!(result, Err(Ok(HTLCError::ResolverNotWhitelisted)));
+
+    registry_client.stake(&receiver, &SAFETY_DEPOSIT);
+    client.withdraw(&contract_id, &preimage);
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Withdrawn);
+}
+
+#[test]
+fn resolvers_only_rejects_unbonding_receiver() {
+    let (env, sender, receiver, token_address, _) = setup();
+    let client = initialized(&env, &Address::generate(&env), false);
+    let registry_client = setup_resolver_registry(&env, &token_address, &client);
+    token::StellarAssetClient::new(&env, &token_address).mint(&receiver, &SAFETY_DEPOSIT);
+    registry_client.stake(&receiver, &SAFETY_DEPOSIT);
+    let (contract_id, preimage) =
+        create_resolvers_only(&env, &sender, &receiver, &token_address, &client);
+
+    registry_client.unstake(&receiver, &1);
+    let result = client.try_withdraw(&contract_id, &preimage);
+    assert_eq!(result, Err(Ok(HTLCError::ResolverNotWhitelisted)));
+}
+
+//------------------------------------------------------------------
+//  Protocol fee
+//------------------------------------------------------------------
+const FEE_BPS: u32 = 30; // 0.3%
+
+#[test]
+fn protocol_fee_taken_on_withdraw() {
+    let (env, sender, receiver, token_address, _) = setup();
+    let collector = Address::generate(&env);
+    let client = initialized(&env, &Address::generate(&env), false);
+    client.set_protocol_fee(&FEE_BPS, &collector);
+    assert_eq!(
+        client.get_protocol_fee(),
+        Some(FeeConfig {
+            fee_bps: FEE_BPS,
+            collector: collector.clone()
+        })
+    );
+
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+    client.withdraw(&contract_id, &preimage);
+
+    let fee = AMOUNT * FEE_BPS as i128 / BPS_DENOMINATOR;
+    let token_client = token::Client::new(&env, &token_address);
+    assert_eq!(token_client.balance(&collector), fee);
+    assert_eq!(token_client.balance(&receiver), AMOUNT - fee);
+    assert_eq!(token_client.balance(&sender), SAFETY_DEPOSIT);
+    assert_eq!(token_client.balance(&client.address), 0);
+}
+
+#[test]
+fn protocol_fee_fixed_at_creation() {
+    let (env, sender, receiver, token_address, _) = setup();
+    token::StellarAssetClient::new(&env, &token_address).mint(&sender, &(AMOUNT + SAFETY_DEPOSIT));
+    let collector = Address::generate(&env);
+    let client = initialized(&env, &Address::generate(&env), false);
+
+    // Locked without a fee, so raising it afterwards takes nothing
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+    client.set_protocol_fee(&MAX_FEE_BPS, &collector);
+    assert_eq!(client.get_htlc_fee(&contract_id), None);
+    client.withdraw(&contract_id, &preimage);
+    let token_client = token::Client::new(&env, &token_address);
+    assert_eq!(token_client.balance(&receiver), AMOUNT);
+    assert_eq!(token_client.balance(&collector), 0);
+
+    // Locked at `FEE_BPS`, so neither a higher fee nor a new collector applies to it
+    client.set_protocol_fee(&FEE_BPS, &collector);
+    let (contract_id, secrets) = create_partial(&env, &sender, &receiver, &token_address, &client);
+    client.set_protocol_fee(&MAX_FEE_BPS, &Address::generate(&env));
+    assert_eq!(
+        client.get_htlc_fee(&contract_id),
+        Some(FeeConfig {
+            fee_bps: FEE_BPS,
+            collector: collector.clone()
+        })
+    );
+    let fill_amount = AMOUNT / PARTS as i128;
+    let (_, proof) = merkle_root_and_proof(&env, &secrets, 0);
+    client.withdraw_partial(
+        &contract_id,
+        &secrets.get(0).unwrap(),
+        &0,
+        &proof,
+        &fill_amount,
+    );
+    let fee = fill_amount * FEE_BPS as i128 / BPS_DENOMINATOR;
+    assert_eq!(token_client.balance(&collector), fee);
+    assert_eq!(token_client.balance(&receiver), AMOUNT + fill_amount - fee);
+}
+
+#[test]
+fn protocol_fee_taken_per_partial_fill() {
+    let (env, sender, receiver, token_address, _) = setup();
+    let collector = Address::generate(&env);
+    let client = initialized(&env, &Address::generate(&env), false);
+    client.set_protocol_fee(&FEE_BPS, &collector);
+    let (contract_id, secrets) = create_partial(&env, &sender, &receiver, &token_address, &client);
+
+    let fill_amount = AMOUNT / PARTS as i128;
+    let (_, proof) = merkle_root_and_proof(&env, &secrets, 0);
+    client.withdraw_partial(
+        &contract_id,
+        &secrets.get(0).unwrap(),
+        &0,
+        &proof,
+        &fill_amount,
+    );
+
+    let fee = fill_amount * FEE_BPS as i128 / BPS_DENOMINATOR;
+    let token_client = token::Client::new(&env, &token_address);
+    assert_eq!(token_client.balance(&collector), fee);
+    assert_eq!(token_client.balance(&receiver), fill_amount - fee);
+}
+
+#[test]
+fn protocol_fee_not_taken_on_refund() {
+    let (env, sender, receiver, token_address, _) = setup();
+    let collector = Address::generate(&env);
+    let client = initialized(&env, &Address::generate(&env), false);
+    client.set_protocol_fee(&FEE_BPS, &collector);
+
+    let (hashlock, _) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+    env.ledger().with_mut(|l| l.timestamp = params.timelock);
+    client.refund(&contract_id);
+
+    let token_client = token::Client::new(&env, &token_address);
+    assert_eq!(token_client.balance(&collector), 0);
+    assert_eq!(token_client.balance(&sender), AMOUNT + SAFETY_DEPOSIT);
+}
+
+#[test]
+fn protocol_fee_above_cap() {
+    let (env, _, _, _, _) = setup();
+    let client = initialized(&env, &Address::generate(&env), false);
+
+    let result = client.try_set_protocol_fee(&(MAX_FEE_BPS + 1), &Address::generate(&env));
+    assert_eq!(result, Err(Ok(HTLCError::InvalidFee)));
+    assert_eq!(client.get_protocol_fee(), None);
+}
+
+//------------------------------------------------------------------
+//  Referral fee
+//------------------------------------------------------------------
+const REFERRAL_BPS: u32 = 50; // 0.5%
+
+#[test]
+fn referral_fee_paid_on_withdraw() {
+    let (env, sender, receiver, token_address, _) = setup();
+    let collector = Address::generate(&env);
+    let referral = Address::generate(&env);
+    let client = initialized(&env, &Address::generate(&env), false);
+    client.set_protocol_fee(&FEE_BPS, &collector);
+
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.referral = referral.clone();
+    params.referral_bps = REFERRAL_BPS;
+    let contract_id = client.create_htlc_with_params(&params);
+    client.withdraw(&contract_id, &preimage);
+
+    // Both fees are taken from the amount, leaving the safety deposit whole
+    let referral_fee = AMOUNT * REFERRAL_BPS as i128 / BPS_DENOMINATOR;
+    let fee = AMOUNT * FEE_BPS as i128 / BPS_DENOMINATOR;
+    let token_client = token::Client::new(&env, &token_address);
+    assert_eq!(token_client.balance(&referral), referral_fee);
+    assert_eq!(token_client.balance(&collector), fee);
+    assert_eq!(token_client.balance(&receiver), AMOUNT - fee - referral_fee);
+    assert_eq!(token_client.balance(&sender), SAFETY_DEPOSIT);
+
+    let expected_topics: Vec<Val> =
+        (Symbol::new(&env, "ReferralFee"), contract_id.clone()).into_val(&env);
+    let (_, _, data) = env
+        .events()
+        .all()
+        .iter()
+        .find(|(_, topics, _)| *topics == expected_topics)
+        .unwrap();
+    let (_, _, payload) = event_payload(&env, &data);
+    assert_eq!(
+        <(Address, Address, i128)>::try_from_val(&env, &payload).unwrap(),
+        (referral, token_address, referral_fee)
+    );
+}
+
+#[test]
+fn referral_fee_taken_per_partial_fill() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let referral = Address::generate(&env);
+    let secrets = partial_secrets(&env);
+    let (root, proof) = merkle_root_and_proof(&env, &secrets, 0);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &root);
+    params.parts = PARTS;
+    params.referral = referral.clone();
+    params.referral_bps = REFERRAL_BPS;
+    let contract_id = client.create_htlc_with_params(&params);
+
+    let fill_amount = AMOUNT / PARTS as i128;
+    client.withdraw_partial(
+        &contract_id,
+        &secrets.get(0).unwrap(),
+        &0,
+        &proof,
+        &fill_amount,
+    );
+
+    let referral_fee = fill_amount * REFERRAL_BPS as i128 / BPS_DENOMINATOR;
+    let token_client = token::Client::new(&env, &token_address);
+    assert_eq!(token_client.balance(&referral), referral_fee);
+    assert_eq!(token_client.balance(&receiver), fill_amount - referral_fee);
+}
+
+#[test]
+fn referral_fee_not_taken_on_refund() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let referral = Address::generate(&env);
+    let (hashlock, _) = hashlock_pair(&env);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.referral = referral.clone();
+    params.referral_bps = REFERRAL_BPS;
+    let contract_id = client.create_htlc_with_params(&params);
+
+    env.ledger().with_mut(|l| l.timestamp = params.timelock);
+    client.refund(&contract_id);
+
+    let token_client = token::Client::new(&env, &token_address);
+    assert_eq!(token_client.balance(&referral), 0);
+    assert_eq!(token_client.balance(&sender), AMOUNT + SAFETY_DEPOSIT);
+}
+
+#[test]
+fn referral_fee_above_cap() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.referral = Address::generate(&env);
+    params.referral_bps = MAX_FEE_BPS + 1;
+
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidFee)));
+}
+
+//------------------------------------------------------------------
+//  Storage TTL
+//------------------------------------------------------------------
+fn advance_ledgers(env: &Env, ledgers: u32) {
+    env.ledger().with_mut(|l| l.sequence_number += ledgers);
+}
+
+/// Reads the ledger the HTLC entry stays live until from the test ledger
+fn htlc_live_until(env: &Env, contract_id: &BytesN<32>) -> u32 {
+    let key: Val = DataKey::HTLCEntry(contract_id.clone()).into_val(env);
+    let key = xdr::ScVal::try_from_val(env, &key).unwrap();
+    env.to_ledger_snapshot()
+        .ledger_entries
+        .iter()
+        .find_map(|(ledger_key, (_, live_until))| match ledger_key.as_ref() {
+            xdr::LedgerKey::ContractData(data) if data.key == key => *live_until,
+            _ => None,
+        })
+        .unwrap()
+}
+
+#[test]
+fn create_extends_htlc_ttl() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+
+    let sequence = env.ledger().sequence();
+    assert_eq!(
+        htlc_live_until(&env, &contract_id),
+        sequence + DEFAULT_TTL_EXTENSION
+    );
+}
+
+#[test]
+fn extend_htlc_ttl_by_anyone() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+
+    advance_ledgers(&env, DEFAULT_TTL_EXTENSION - DAY_IN_LEDGERS);
+    client.extend_htlc_ttl(&contract_id);
+    assert!(env.auths().is_empty());
+
+    let sequence = env.ledger().sequence();
+    assert_eq!(
+        htlc_live_until(&env, &contract_id),
+        sequence + DEFAULT_TTL_EXTENSION
+    );
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Active);
+}
+
+#[test]
+fn extend_htlc_ttl_unknown_contract() {
+    let (env, _, _, _, client) = setup();
+
+    let result = client.try_extend_htlc_ttl(&BytesN::from_array(&env, &[1u8; 32]));
+    assert_eq!(result, Err(Ok(HTLCError::ContractNotFound)));
+}
+
+// ==================== Compact storage ====================
+
+#[test]
+fn stored_entry_smaller_than_htlc_data() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+
+    let stored: StoredHTLC = env.as_contract(&client.address, || {
+        env.storage()
+            .persistent()
+            .get(&DataKey::HTLCEntry(contract_id.clone()))
+            .unwrap()
+    });
+    let htlc_data = client.get_htlc(&contract_id);
+    assert!(stored.clone().to_xdr(&env).len() < htlc_data.clone().to_xdr(&env).len());
+    assert_eq!(stored.unpack(&contract_id), htlc_data);
+}
+
+/// `HTLCData` exactly as the first release declared and stored it
+#[derive(Clone)]
+#[contracttype(export = false)]
+struct FirstReleaseHTLCData {
+    contract_id: BytesN<32>,
+    sender: Address,
+    receiver: Address,
+    amount: i128,
+    token_address: Address,
+    hashlock: BytesN<32>,
+    timelock: u64,
+    timestamp: u64,
+    safety_deposit: i128,
+    status: HTLCStatus,
+    locked: bool,
+}
+
+/// Rewrites an HTLC as the first release would have stored it
+fn write_first_release_entry(
+    env: &Env,
+    client: &HTLCContractClient<'static>,
+    contract_id: &BytesN<32>,
+    status: HTLCStatus,
+    locked: bool,
+) {
+    let htlc_data = client.get_htlc(contract_id);
+    let entry = FirstReleaseHTLCData {
+        contract_id: contract_id.clone(),
+        sender: htlc_data.sender,
+        receiver: htlc_data.receiver,
+        amount: htlc_data.amount,
+        token_address: htlc_data.token_address,
+        hashlock: htlc_data.hashlock,
+        timelock: htlc_data.timelock,
+        timestamp: htlc_data.timestamp,
+        safety_deposit: htlc_data.safety_deposit,
+        status,
+        locked,
+    };
+    env.as_contract(&client.address, || {
+        let storage = env.storage().persistent();
+        storage.remove(&DataKey::HTLCEntry(contract_id.clone()));
+        storage.set(&DataKey::HTLCData(contract_id.clone()), &entry);
+    });
+}
+
+#[test]
+fn first_release_entry_read_and_compacted() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+    let htlc_data = client.get_htlc(&contract_id);
+    write_first_release_entry(&env, &client, &contract_id, HTLCStatus::Active, false);
+
+    let has_keys = || {
+        env.as_contract(&client.address, || {
+            let storage = env.storage().persistent();
+            (
+                storage.has(&DataKey::HTLCData(contract_id.clone())),
+                storage.has(&DataKey::HTLCEntry(contract_id.clone())),
+            )
+        })
+    };
+    assert_eq!(has_keys(), (true, false));
+    assert!(client.contract_exists(&contract_id));
+
+    // Every later field takes the value that leaves its feature unused
+    let htlc_data = HTLCData {
+        created_ledger: 0,
+        ..htlc_data
+    };
+    assert_eq!(client.get_htlc(&contract_id), htlc_data);
+
+    // Keeping the entry alive moves it into the compact layout
+    client.extend_htlc_ttl(&contract_id);
+    assert_eq!(has_keys(), (false, true));
+    assert_eq!(client.get_htlc(&contract_id), htlc_data);
+
+    client.withdraw(&contract_id, &preimage);
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Withdrawn);
+    assert_eq!(
+        token::Client::new(&env, &token_address).balance(&receiver),
+        AMOUNT
+    );
+}
+
+#[test]
+fn first_release_status_and_lock_carried_over() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+    let timestamp = client.get_htlc(&contract_id).timestamp;
+
+    // The completion time was not recorded, so cleanup counts from creation
+    write_first_release_entry(&env, &client, &contract_id, HTLCStatus::Refunded, false);
+    let htlc_data = client.get_htlc(&contract_id);
+    assert_eq!(htlc_data.status, HTLCStatus::Refunded);
+    assert_eq!(htlc_data.completed_at, timestamp);
+    let result = client.try_withdraw(&contract_id, &preimage);
+    assert_eq!(result, Err(Ok(HTLCError::AlreadyRefunded)));
+
+    // An entry stored mid-payout stays unusable
+    write_first_release_entry(&env, &client, &contract_id, HTLCStatus::Active, true);
+    let result = client.try_withdraw(&contract_id, &preimage);
+    assert_eq!(result, Err(Ok(HTLCError::ReentrancyDetected)));
+    env.ledger().with_mut(|l| l.timestamp = params.timelock);
+    let result = client.try_refund(&contract_id);
+    assert_eq!(result, Err(Ok(HTLCError::ReentrancyDetected)));
+}
+
+#[test]
+fn migrate_first_release_entry_is_idempotent() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+    let htlc_data = client.get_htlc(&contract_id);
+    assert_eq!(
+        client.get_storage_version(&contract_id),
+        HTLC_STORAGE_VERSION
+    );
+
+    write_first_release_entry(&env, &client, &contract_id, HTLCStatus::Active, false);
+    assert_eq!(client.get_storage_version(&contract_id), 0);
+    let htlc_data = HTLCData {
+        created_ledger: 0,
+        ..htlc_data
+    };
+
+    assert_eq!(client.migrate(&contract_id), HTLC_STORAGE_VERSION);
+    let (_, topics, data) = env.events().all().last().unwrap();
+    let expected_topics: Vec<Val> =
+        (Symbol::new(&env, "HTLCMigrated"), contract_id.clone()).into_val(&env);
+    assert_eq!(topics, expected_topics);
+    let (_, _, payload) = event_payload(&env, &data);
+    assert_eq!(
+        <(u32, u32)>::try_from_val(&env, &payload).unwrap(),
+        (0, HTLC_STORAGE_VERSION)
+    );
+    assert_eq!(client.get_htlc(&contract_id), htlc_data);
+
+    // Migrating a current entry changes nothing
+    let event_count = env.events().all().len();
+    assert_eq!(client.migrate(&contract_id), HTLC_STORAGE_VERSION);
+    assert_eq!(env.events().all().len(), event_count);
+
+    let result = client.try_migrate(&BytesN::from_array(&env, &[1u8; 32]));
+    assert_eq!(result, Err(Ok(HTLCError::ContractNotFound)));
+}
+
+#[test]
+fn first_release_entry_with_long_timelock_stays_version_zero() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+
+    // The first release accepted any timelock
+    let timestamp = env.ledger().timestamp();
+    let entry = FirstReleaseHTLCData {
+        contract_id: contract_id.clone(),
+        sender: sender.clone(),
+        receiver: receiver.clone(),
+        amount: AMOUNT,
+        token_address: token_address.clone(),
+        hashlock,
+        timelock: timestamp + u32::MAX as u64 + 1,
+        timestamp,
+        safety_deposit: SAFETY_DEPOSIT,
+        status: HTLCStatus::Active,
+        locked: false,
+    };
+    env.as_contract(&client.address, || {
+        let storage = env.storage().persistent();
+        storage.remove(&DataKey::HTLCEntry(contract_id.clone()));
+        storage.set(&DataKey::HTLCData(contract_id.clone()), &entry);
+    });
+
+    let event_count = env.events().all().len();
+    assert_eq!(client.migrate(&contract_id), 0);
+    assert_eq!(env.events().all().len(), event_count);
+    let htlc_data = client.get_htlc(&contract_id);
+    assert_eq!(htlc_data.timelock, entry.timelock);
+
+    client.withdraw(&contract_id, &preimage);
+    assert_eq!(client.get_storage_version(&contract_id), 0);
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Withdrawn);
+    assert_eq!(client.get_htlc(&contract_id).preimage, preimage);
+}
+
+#[test]
+fn version_one_entry_read_and_migrated() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+
+    // Version 1 entries were written before the creation ledger was recorded
+    downgrade_entry(&env, &client, &contract_id, 1, &LATER_FIELDS);
+    assert_eq!(client.get_storage_version(&contract_id), 1);
+    assert_eq!(client.get_htlc(&contract_id).created_ledger, 0);
+
+    assert_eq!(client.migrate(&contract_id), HTLC_STORAGE_VERSION);
+    client.withdraw(&contract_id, &preimage);
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Withdrawn);
+}
+
+#[test]
+fn version_two_entry_read_and_migrated() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+    let created_ledger = client.get_htlc(&contract_id).created_ledger;
+
+    // Version 2 entries were written before referral fees existed
+    downgrade_entry(&env, &client, &contract_id, 2, &LATER_FIELDS[1..]);
+    assert_eq!(client.get_storage_version(&contract_id), 2);
+    let htlc_data = client.get_htlc(&contract_id);
+    assert_eq!(htlc_data.created_ledger, created_ledger);
+    assert_eq!(htlc_data.referral, sender);
+    assert_eq!(htlc_data.referral_bps, 0);
+
+    assert_eq!(client.migrate(&contract_id), HTLC_STORAGE_VERSION);
+    client.withdraw(&contract_id, &preimage);
+    assert_eq!(
+        token::Client::new(&env, &token_address).balance(&receiver),
+        AMOUNT
+    );
+}
+
+/// Fields added to stored HTLCs after compaction, in the order they were introduced
+const LATER_FIELDS: [&str; 4] = ["created_ledger", "referral", "referral_bps", "vesting"];
+
+/// Drops fields from an encoded struct, as if it was written before they existed
+fn without_fields(env: &Env, value: Val, names: &[&str]) -> Val {
+    let mut fields: Map<Symbol, Val> = Map::try_from_val(env, &value).unwrap();
+    for name in names {
+        fields.remove(Symbol::new(env, name));
+    }
+    fields.into_val(env)
+}
+
+/// Rewrites a stored HTLC entry as an older layout version lacking `names`
+fn downgrade_entry(
+    env: &Env,
+    client: &HTLCContractClient<'static>,
+    contract_id: &BytesN<32>,
+    version: u32,
+    names: &[&str],
+) {
+    env.as_contract(&client.address, || {
+        let storage = env.storage().persistent();
+        let key = DataKey::HTLCEntry(contract_id.clone());
+        let stored: Val = storage.get(&key).unwrap();
+        let mut fields: Map<Symbol, Val> =
+            Map::try_from_val(env, &without_fields(env, stored, names)).unwrap();
+        fields.set(Symbol::new(env, "version"), version.into_val(env));
+        storage.set(&key, &fields);
+    });
+}
+
+#[test]
+fn timelock_must_fit_stored_offset() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.timelock = env.ledger().timestamp() + u32::MAX as u64 + 1;
+
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidTimelock)));
+}
+
+#[test]
+fn set_ttl_extension_bounds() {
+    let (env, sender, receiver, token_address, _) = setup();
+    let client = initialized(&env, &Address::generate(&env), false);
+    assert_eq!(client.get_ttl_extension(), DEFAULT_TTL_EXTENSION);
+
+    client.set_ttl_extension(&(90 * DAY_IN_LEDGERS));
+    assert_eq!(client.get_ttl_extension(), 90 * DAY_IN_LEDGERS);
+
+    let (hashlock, _) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+    assert_eq!(
+        htlc_live_until(&env, &contract_id),
+        env.ledger().sequence() + 90 * DAY_IN_LEDGERS
+    );
+
+    let result = client.try_set_ttl_extension(&(DAY_IN_LEDGERS - 1));
+    assert_eq!(result, Err(Ok(HTLCError::InvalidTtlExtension)));
+    let result = client.try_set_ttl_extension(&u32::MAX);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidTtlExtension)));
+}
+
+#[test]
+fn timelock_bounds_enforced() {
+    let (env, sender, receiver, token_address, _) = setup();
+    let client = initialized(&env, &Address::generate(&env), false);
+    client.set_timelock_bounds(&TIMELOCK_SECS, &(2 * TIMELOCK_SECS));
+
+    let (hashlock, _) = hashlock_pair(&env);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.timelock = env.ledger().timestamp() + TIMELOCK_SECS - 1;
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidTimelock)));
+
+    params.timelock = env.ledger().timestamp() + 2 * TIMELOCK_SECS + 1;
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidTimelock)));
+
+    params.timelock = env.ledger().timestamp() + 2 * TIMELOCK_SECS;
+    client.create_htlc_with_params(&params);
+}
+
+#[test]
+fn set_timelock_bounds_validation() {
+    let (env, _, _, _, _) = setup();
+    let client = initialized(&env, &Address::generate(&env), false);
+    assert_eq!(
+        client.get_timelock_bounds(),
+        TimelockBounds {
+            min_timelock_duration: 0,
+            max_timelock_duration: u64::MAX,
+        }
+    );
+
+    let result = client.try_set_timelock_bounds(&TIMELOCK_SECS, &(TIMELOCK_SECS - 1));
+    assert_eq!(result, Err(Ok(HTLCError::InvalidTimelockBounds)));
+}
+
+//------------------------------------------------------------------
+//  Cleanup of completed HTLCs
+//------------------------------------------------------------------
+#[test]
+fn cleanup_after_grace_period() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+    client.withdraw(&contract_id, &preimage);
+
+    let completed_at = client.get_htlc(&contract_id).completed_at;
+    assert_eq!(completed_at, env.ledger().timestamp());
+
+    // Readable until the grace period has passed
+    env.ledger()
+        .with_mut(|l| l.timestamp = completed_at + CLEANUP_GRACE_PERIOD - 1);
+    let result = client.try_cleanup(&contract_id);
+    assert_eq!(result, Err(Ok(HTLCError::CleanupTooEarly)));
+
+    env.ledger()
+        .with_mut(|l| l.timestamp = completed_at + CLEANUP_GRACE_PERIOD);
+    client.cleanup(&contract_id);
+    assert!(env.auths().is_empty());
+    assert!(!client.contract_exists(&contract_id));
+}
+
+#[test]
+fn cleanup_refunded_htlc() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+
+    env.ledger().with_mut(|l| l.timestamp = params.timelock);
+    client.refund(&contract_id);
+
+    env.ledger()
+        .with_mut(|l| l.timestamp = params.timelock + CLEANUP_GRACE_PERIOD);
+    client.cleanup(&contract_id);
+    assert!(!client.contract_exists(&contract_id));
+}
+
+#[test]
+fn cleanup_active_htlc() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+
+    env.ledger()
+        .with_mut(|l| l.timestamp = params.timelock + CLEANUP_GRACE_PERIOD);
+    let result = client.try_cleanup(&contract_id);
+    assert_eq!(result, Err(Ok(HTLCError::ContractNotCompleted)));
+}
+
+//------------------------------------------------------------------
+//  Sender index
+//------------------------------------------------------------------
+#[test]
+fn get_htlcs_by_sender_paginated() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let withdrawals = create_batch(
+        &env,
+        &sender,
+        &receiver,
+        &token_address,
+        &client,
+        &[1, 2, 3],
+    );
+    let mut contract_ids = Vec::new(&env);
+    for (contract_id, _) in withdrawals.iter() {
+        contract_ids.push_back(contract_id);
+    }
+
+    assert_eq!(client.get_htlcs_by_sender(&sender, &0, &10), contract_ids);
+    assert_eq!(
+        client.get_htlcs_by_sender(&sender, &1, &1),
+        contract_ids.slice(1..2)
+    );
+    assert_eq!(client.get_htlcs_by_sender(&sender, &3, &10).len(), 0);
+    assert_eq!(client.get_htlcs_by_sender(&receiver, &0, &10).len(), 0);
+}
+
+#[test]
+fn get_htlcs_by_sender_page_size_capped() {
+    let (env, sender, receiver, token_address, client) = setup();
+    create_batch(&env, &sender, &receiver, &token_address, &client, &[1, 2]);
+
+    assert_eq!(client.get_htlcs_by_sender(&sender, &0, &u32::MAX).len(), 2);
+    assert_eq!(
+        client
+            .get_htlcs_by_sender(&sender, &u32::MAX, &u32::MAX)
+            .len(),
+        0
+    );
+}
+
+#[test]
+fn cleanup_removes_from_sender_index() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let withdrawals = create_batch(&env, &sender, &receiver, &token_address, &client, &[1, 2]);
+    let (first_id, preimage) = withdrawals.get(0).unwrap();
+    let (second_id, _) = withdrawals.get(1).unwrap();
+
+    client.withdraw(&first_id, &preimage);
+    env.ledger()
+        .with_mut(|l| l.timestamp += CLEANUP_GRACE_PERIOD);
+    client.cleanup(&first_id);
+
+    assert_eq!(
+        client.get_htlcs_by_sender(&sender, &0, &10),
+        Vec::from_array(&env, [second_id])
+    );
+}
+
+//------------------------------------------------------------------
+//  Receiver index
+//------------------------------------------------------------------
+#[test]
+fn get_htlcs_by_receiver_paginated() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let withdrawals = create_batch(
+        &env,
+        &sender,
+        &receiver,
+        &token_address,
+        &client,
+        &[1, 2, 3],
+    );
+    let (first_id, _) = withdrawals.get(0).unwrap();
+    let (third_id, _) = withdrawals.get(2).unwrap();
+
+    assert_eq!(
+        client
+            .get_htlcs_by_receiver(&receiver, &0, &10, &false)
+            .len(),
+        3
+    );
+    assert_eq!(
+        client.get_htlcs_by_receiver(&receiver, &2, &10, &false),
+        Vec::from_array(&env, [third_id])
+    );
+    assert_eq!(
+        client.get_htlcs_by_receiver(&receiver, &0, &1, &false),
+        Vec::from_array(&env, [first_id])
+    );
+    assert_eq!(
+        client.get_htlcs_by_receiver(&sender, &0, &10, &false).len(),
+        0
+    );
+}
+
+#[test]
+fn get_htlcs_by_receiver_active_only() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let withdrawals = create_batch(
+        &env,
+        &sender,
+        &receiver,
+        &token_address,
+        &client,
+        &[1, 2, 3],
+    );
+    let (first_id, preimage) = withdrawals.get(0).unwrap();
+    let (second_id, _) = withdrawals.get(1).unwrap();
+    let (third_id, _) = withdrawals.get(2).unwrap();
+    client.withdraw(&first_id, &preimage);
+
+    assert_eq!(
+        client.get_htlcs_by_receiver(&receiver, &0, &10, &true),
+        Vec::from_array(&env, [second_id.clone(), third_id.clone()])
+    );
+    assert_eq!(
+        client.get_htlcs_by_receiver(&receiver, &1, &10, &true),
+        Vec::from_array(&env, [third_id])
+    );
+    assert_eq!(
+        client.get_htlcs_by_receiver(&receiver, &0, &1, &true),
+        Vec::from_array(&env, [second_id])
+    );
+    assert_eq!(
+        client
+            .get_htlcs_by_receiver(&receiver, &0, &10, &false)
+            .len(),
+        3
+    );
+}
+
+//------------------------------------------------------------------
+//  Expiry views
+//------------------------------------------------------------------
+#[test]
+fn remaining_time_counts_down_to_expiry() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+
+    assert_eq!(client.get_remaining_time(&contract_id), TIMELOCK_SECS);
+    assert!(!client.is_expired(&contract_id));
+
+    env.ledger().with_mut(|l| l.timestamp = params.timelock - 1);
+    assert_eq!(client.get_remaining_time(&contract_id), 1);
+    assert!(!client.is_expired(&contract_id));
+
+    env.ledger().with_mut(|l| l.timestamp = params.timelock);
+    assert_eq!(client.get_remaining_time(&contract_id), 0);
+    assert!(client.is_expired(&contract_id));
+
+    env.ledger()
+        .with_mut(|l| l.timestamp = params.timelock + TIMELOCK_SECS);
+    assert_eq!(client.get_remaining_time(&contract_id), 0);
+}
+
+#[test]
+fn expiry_views_unknown_contract() {
+    let (env, _, _, _, client) = setup();
+    let contract_id = BytesN::from_array(&env, &[1u8; 32]);
+
+    let result = client.try_get_remaining_time(&contract_id);
+    assert_eq!(result, Err(Ok(HTLCError::ContractNotFound)));
+    let result = client.try_is_expired(&contract_id);
+    assert_eq!(result, Err(Ok(HTLCError::ContractNotFound)));
+}
+
+//------------------------------------------------------------------
+//  Stored preimage
+//------------------------------------------------------------------
+#[test]
+fn get_preimage_after_withdraw() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+
+    assert_eq!(client.get_preimage(&contract_id), None);
+    client.withdraw(&contract_id, &preimage);
+    assert_eq!(client.get_preimage(&contract_id), Some(preimage));
+}
+
+#[test]
+fn get_preimage_latest_partial_secret() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (contract_id, secrets) = create_partial(&env, &sender, &receiver, &token_address, &client);
+    let quarter = AMOUNT / PARTS as i128;
+
+    let (_, proof) = merkle_root_and_proof(&env, &secrets, 0);
+    client.withdraw_partial(&contract_id, &secrets.get(0).unwrap(), &0, &proof, &quarter);
+    assert_eq!(client.get_preimage(&contract_id), secrets.get(0));
+
+    let (_, proof) = merkle_root_and_proof(&env, &secrets, 1);
+    client.withdraw_partial(&contract_id, &secrets.get(1).unwrap(), &1, &proof, &quarter);
+    assert_eq!(client.get_preimage(&contract_id), secrets.get(1));
+}
+
+#[test]
+fn get_preimage_after_refund() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+
+    env.ledger().with_mut(|l| l.timestamp = params.timelock);
+    client.refund(&contract_id);
+    assert_eq!(client.get_preimage(&contract_id), None);
+    assert_eq!(
+        client.get_preimage(&BytesN::from_array(&env, &[1u8; 32])),
+        None
+    );
+}
+
+//------------------------------------------------------------------
+//  Events
+//------------------------------------------------------------------
+/// Splits event data into its schema version, sequence number and payload
+fn event_payload(env: &Env, data: &Val) -> (u32, u64, Val) {
+    <(u32, u64, Val)>::try_from_val(env, data).unwrap()
+}
+
+/// Unwraps a lifecycle event's payload into its data, the HTLC's memo and its creation ledger
+fn lifecycle_payload(env: &Env, data: &Val) -> (Val, Bytes, u32) {
+    let (_, _, payload) = event_payload(env, data);
+    <(Val, Bytes, u32)>::try_from_val(env, &payload).unwrap()
+}
+
+#[test]
+fn events_carry_schema_version_and_sequence() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    assert_eq!(client.get_event_sequence(), 0);
+
+    let contract_id = client.create_htlc_with_params(&params);
+    client.withdraw(&contract_id, &preimage);
+
+    let sequences: std::vec::Vec<u64> = env
+        .events()
+        .all()
+        .iter()
+        .filter(|(emitter, _, _)| *emitter == client.address)
+        .map(|(_, _, data)| {
+            let (version, sequence, _) = event_payload(&env, &data);
+            assert_eq!(version, EVENT_SCHEMA_VERSION);
+            sequence
+        })
+        .collect();
+    assert_eq!(sequences, [1, 2, 3, 4]);
+    assert_eq!(client.get_event_sequence(), 4);
+}
+
+#[test]
+fn lifecycle_events_carry_creation_ledger() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let created_ledger = env.ledger().sequence();
+    let contract_id = client.create_htlc_with_params(&params);
+    assert_eq!(client.get_htlc(&contract_id).created_ledger, created_ledger);
+
+    advance_ledgers(&env, 10);
+    client.withdraw(&contract_id, &preimage);
+    let (_, _, data) = env.events().all().last().unwrap();
+    let (_, _, event_ledger) = lifecycle_payload(&env, &data);
+    assert_eq!(event_ledger, created_ledger);
+    assert_eq!(client.get_htlc(&contract_id).created_ledger, created_ledger);
+}
+
+#[test]
+fn htlc_new_event_carries_immutables() {
+    let (env, sender, receiver, token_address, native_address, client) = setup_native_deposit();
+    let (hashlock, _) = hashlock_pair(&env);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.safety_deposit_token = native_address.clone();
+    params.order_hash = BytesN::from_array(&env, &[7u8; 32]);
+    params.public_cancellation = params.timelock + EXCLUSIVE_SECS;
+    let contract_id = client.create_htlc_with_params(&params);
+
+    let (emitter, topics, data) = env.events().all().last().unwrap();
+    assert_eq!(emitter, client.address);
+    let expected_topics: Vec<Val> = (Symbol::new(&env, "HTLCNew"), contract_id).into_val(&env);
+    assert_eq!(topics, expected_topics);
+
+    let (_, _, payload) = event_payload(&env, &data);
+    let event = HTLCNewEvent::try_from_val(&env, &payload).unwrap();
+    assert_eq!(
+        event,
+        HTLCNewEvent {
+            sender: sender.clone(),
+            receiver: receiver.clone(),
+            amount: AMOUNT,
+            token_address,
+            hashlock,
+            hash_algo: HashAlgo::Sha256,
+            timelock: params.timelock,
+            timestamp: env.ledger().timestamp(),
+            created_ledger: env.ledger().sequence(),
+            safety_deposit: SAFETY_DEPOSIT,
+            safety_deposit_token: native_address,
+            parts: 0,
+            public_withdrawal: 0,
+            public_cancellation: params.public_cancellation,
+            order_hash: params.order_hash,
+            resolvers_only: false,
+            metadata: CrossChainMetadata::none(&env),
+            basket: Vec::new(&env),
+            safety_deposit_payer: sender.clone(),
+            salt: BytesN::from_array(&env, &[0; 32]),
+            taker: receiver,
+            hashlocks: Vec::new(&env),
+            threshold: 0,
+            callback: sender.clone(),
+            callback_data: Bytes::new(&env),
+            extension: Bytes::new(&env),
+            rescue_delay: 0,
+            refund_to: sender.clone(),
+            commit_reveal: false,
+            memo: Bytes::new(&env),
+            referral: sender,
+            referral_bps: 0,
+            vesting: VestingSchedule::none(),
+        }
+    );
+}
+
+//------------------------------------------------------------------
+//  Cross-chain metadata
+//------------------------------------------------------------------
+#[test]
+fn create_with_cross_chain_metadata() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let metadata = CrossChainMetadata {
+        evm_chain_id: 1,
+        evm_order_hash: BytesN::from_array(&env, &[5u8; 32]),
+        maker: BytesN::from_array(&env, &[0xab; 20]),
+    };
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.metadata = metadata.clone();
+    let contract_id = client.create_htlc_with_params(&params);
+
+    assert_eq!(client.get_htlc(&contract_id).metadata, metadata);
+
+    let (_, _, data) = env.events().all().last().unwrap();
+    let (_, _, payload) = event_payload(&env, &data);
+    let event = HTLCNewEvent::try_from_val(&env, &payload).unwrap();
+    assert_eq!(event.metadata, metadata);
+}
+
+#[test]
+fn create_htlc_without_metadata() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let timelock = env.ledger().timestamp() + TIMELOCK_SECS;
+
+    let contract_id = client.create_htlc(
+        &sender,
+        &receiver,
+        &AMOUNT,
+        &token_address,
+        &hashlock,
+        &timelock,
+        &SAFETY_DEPOSIT,
+    );
+    assert_eq!(
+        client.get_htlc(&contract_id).metadata,
+        CrossChainMetadata::none(&env)
+    );
+}
+
+//------------------------------------------------------------------
+//  Rescue of stray tokens
+//------------------------------------------------------------------
+#[test]
+fn rescue_stray_tokens_only() {
+    let (env, sender, receiver, token_address, _) = setup();
+    let treasury = Address::generate(&env);
+    let client = initialized(&env, &Address::generate(&env), false);
+    let (hashlock, _) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    client.create_htlc_with_params(&params);
+
+    // Tokens sent straight to the contract are not owed to any HTLC
+    let stray = 5_000;
+    token::StellarAssetClient::new(&env, &token_address).mint(&client.address, &stray);
+    assert_eq!(
+        client.get_token_accounting(&token_address),
+        TokenAccounting {
+            locked: AMOUNT + SAFETY_DEPOSIT,
+            balance: AMOUNT + SAFETY_DEPOSIT + stray,
+        }
+    );
+
+    let result = client.try_rescue(&token_address, &(stray + 1), &treasury);
+    assert_eq!(result, Err(Ok(HTLCError::InsufficientRescuableBalance)));
+
+    client.rescue(&token_address, &stray, &treasury);
+    let token_client = token::Client::new(&env, &token_address);
+    assert_eq!(token_client.balance(&treasury), stray);
+    assert_eq!(
+        token_client.balance(&client.address),
+        AMOUNT + SAFETY_DEPOSIT
+    );
+}
+
+#[test]
+fn token_accounting_released_on_completion() {
+    let (env, sender, receiver, token_address, client) = setup();
+    token::StellarAssetClient::new(&env, &token_address).mint(&sender, &(AMOUNT + SAFETY_DEPOSIT));
+    let withdrawals = create_batch(&env, &sender, &receiver, &token_address, &client, &[1, 2]);
+    assert_eq!(
+        client.get_token_accounting(&token_address).locked,
+        2 * (AMOUNT + SAFETY_DEPOSIT)
+    );
+
+    let (first_id, preimage) = withdrawals.get(0).unwrap();
+    client.withdraw(&first_id, &preimage);
+    assert_eq!(
+        client.get_token_accounting(&token_address).locked,
+        AMOUNT + SAFETY_DEPOSIT
+    );
+
+    let (second_id, _) = withdrawals.get(1).unwrap();
+    let timelock = client.get_htlc(&second_id).timelock;
+    env.ledger().with_mut(|l| l.timestamp = timelock);
+    client.refund(&second_id);
+    assert_eq!(
+        client.get_token_accounting(&token_address),
+        TokenAccounting {
+            locked: 0,
+            balance: 0
+        }
+    );
+}
+
+#[test]
+fn token_accounting_tracks_partial_fills() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (contract_id, secrets) = create_partial(&env, &sender, &receiver, &token_address, &client);
+    let quarter = AMOUNT / PARTS as i128;
+
+    let (_, proof) = merkle_root_and_proof(&env, &secrets, 0);
+    client.withdraw_partial(&contract_id, &secrets.get(0).unwrap(), &0, &proof, &quarter);
+    assert_eq!(
+        client.get_token_accounting(&token_address).locked,
+        AMOUNT - quarter + SAFETY_DEPOSIT
+    );
+
+    let timelock = client.get_htlc(&contract_id).timelock;
+    env.ledger().with_mut(|l| l.timestamp = timelock);
+    client.refund(&contract_id);
+    assert_eq!(client.get_token_accounting(&token_address).locked, 0);
+}
+
+//------------------------------------------------------------------
+//  Dutch auction orders
+//------------------------------------------------------------------
+const AUCTION_SECS: u64 = 1_000;
+
+fn auction_order(
+    env: &Env,
+    sender: &Address,
+    receiver: &Address,
+    token_address: &Address,
+) -> AuctionOrder {
+    let (hashlock, _) = hashlock_pair(env);
+    let start_time = env.ledger().timestamp();
+    AuctionOrder {
+        params: create_params(env, sender, receiver, token_address, &hashlock),
+        start_amount: AMOUNT,
+        end_amount: AMOUNT / 2,
+        start_time,
+        end_time: start_time + AUCTION_SECS,
+    }
+}
+
+#[test]
+fn auction_amount_follows_curve() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let order = auction_order(&env, &sender, &receiver, &token_address);
+
+    assert_eq!(client.get_current_fill_amount(&order), AMOUNT);
+
+    env.ledger()
+        .with_mut(|l| l.timestamp = order.start_time + AUCTION_SECS / 4);
+    assert_eq!(client.get_current_fill_amount(&order), AMOUNT * 7 / 8);
+
+    env.ledger().with_mut(|l| l.timestamp = order.end_time);
+    assert_eq!(client.get_current_fill_amount(&order), AMOUNT / 2);
+
+    env.ledger()
+        .with_mut(|l| l.timestamp = order.end_time + AUCTION_SECS);
+    assert_eq!(client.get_current_fill_amount(&order), AMOUNT / 2);
+}
+
+#[test]
+fn create_htlc_from_order_locks_current_amount() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let order = auction_order(&env, &sender, &receiver, &token_address);
+
+    env.ledger()
+        .with_mut(|l| l.timestamp = order.start_time + AUCTION_SECS / 2);
+    let contract_id = client.create_htlc_from_order(&order);
+
+    let expected = AMOUNT * 3 / 4;
+    assert_eq!(client.get_htlc(&contract_id).amount, expected);
+    let token_client = token::Client::new(&env, &token_address);
+    assert_eq!(
+        token_client.balance(&client.address),
+        expected + SAFETY_DEPOSIT
+    );
+}
+
+#[test]
+fn auction_order_invalid_curve() {
+    let (env, sender, receiver, token_address, client) = setup();
+
+    let mut order = auction_order(&env, &sender, &receiver, &token_address);
+    order.end_amount = order.start_amount + 1;
+    let result = client.try_create_htlc_from_order(&order);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidAuction)));
+
+    let mut order = auction_order(&env, &sender, &receiver, &token_address);
+    order.end_time = order.start_time;
+    let result = client.try_get_current_fill_amount(&order);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidAuction)));
+}
+
+#[test]
+fn auction_amount_matches_fusion_curve() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let order = auction_order(&env, &sender, &receiver, &token_address);
+    let details = fusion_auction::AuctionDetails::from_amounts(
+        order.start_amount as u128,
+        order.end_amount as u128,
+        order.start_time,
+        AUCTION_SECS,
+    )
+    .unwrap();
+
+    for elapsed in (0..=AUCTION_SECS).step_by(125) {
+        let now = order.start_time + elapsed;
+        env.ledger().with_mut(|l| l.timestamp = now);
+        let bump = details.rate_bump(now, 0);
+        let expected = fusion_auction::bumped_amount(order.end_amount as u128, bump).unwrap();
+        assert_eq!(client.get_current_fill_amount(&order) as u128, expected);
+    }
+}
+
+//------------------------------------------------------------------
+//  Maker-signed orders
+//------------------------------------------------------------------
+fn maker_key() -> ed25519_dalek::SigningKey {
+    ed25519_dalek::SigningKey::from_bytes(&[7u8; 32])
+}
+
+/// Writes a classic account entry, which the asset contract reads for its issuer flags
+fn create_account(env: &Env, public_key: &[u8; 32]) {
+    let account_id = xdr::AccountId(xdr::PublicKey::PublicKeyTypeEd25519(xdr::Uint256(
+        *public_key,
+    )));
+    let key = Rc::new(xdr::LedgerKey::Account(xdr::LedgerKeyAccount {
+        account_id: account_id.clone(),
+    }));
+    let entry = Rc::new(xdr::LedgerEntry {
+        data: xdr::LedgerEntryData::Account(xdr::AccountEntry {
+            account_id,
+            balance: 0,
+            flags: 0,
+            home_domain: Default::default(),
+            inflation_dest: None,
+            num_sub_entries: 0,
+            seq_num: xdr::SequenceNumber(0),
+            thresholds: xdr::Thresholds([1; 4]),
+            signers: Default::default(),
+            ext: xdr::AccountEntryExt::V0,
+        }),
+        last_modified_ledger_seq: 0,
+        ext: xdr::LedgerEntryExt::V0,
+    });
+    env.host()
+        .with_mut_storage(|storage| storage.put(&key, &entry, None, &env.host().budget_cloned()))
+        .unwrap();
+}
+
+/// Creates an ed25519 account together with an asset it issues, returning both addresses.
+///
+/// Issuers hold and receive their own asset without a trustline.
+fn issuer_account(env: &Env, public_key: &[u8; 32]) -> (Address, Address) {
+    create_account(env, public_key);
+
+    let mut address_xdr = Bytes::from_array(env, &[0, 0, 0, 18, 0, 0, 0, 0, 0, 0, 0, 0]);
+    address_xdr.extend_from_slice(public_key);
+    let account = Address::from_xdr(env, &address_xdr).unwrap();
+
+    // `Asset::CreditAlphanum4` with code "TEST" issued by the account
+    let mut asset_xdr = Bytes::from_array(env, &[0, 0, 0, 1]);
+    asset_xdr.extend_from_slice(b"TEST");
+    asset_xdr.extend_from_slice(&[0, 0, 0, 0]);
+    asset_xdr.extend_from_slice(public_key);
+    let token_address = env.deployer().with_stellar_asset(asset_xdr).deploy();
+
+    (account, token_address)
+}
+
+/// Sets up a maker account issuing its own asset, so it holds a balance without a trustline
+fn setup_signed_order() -> (Env, SignedOrder, Address, HTLCContractClient<'static>) {
+    let env = new_env();
+    let public_key = maker_key().verifying_key().to_bytes();
+    let (maker, token_address) = issuer_account(&env, &public_key);
+
+    let htlc_contract_id = env.register_contract(None, HTLCContract);
+    let client = HTLCContractClient::new(&env, &htlc_contract_id);
+
+    // The maker's one-time approval of the HTLC contract as spender
+    token::Client::new(&env, &token_address).approve(
+        &maker,
+        &client.address,
+        &(AMOUNT + SAFETY_DEPOSIT),
+        &(env.ledger().sequence() + DAY_IN_LEDGERS),
+    );
+
+    let (hashlock, _) = hashlock_pair(&env);
+    let receiver = Address::generate(&env);
+    let order = SignedOrder {
+        maker: BytesN::from_array(&env, &public_key),
+        params: create_params(&env, &maker, &receiver, &token_address, &hashlock),
+        nonce: 1,
+        deadline: env.ledger().timestamp() + EXCLUSIVE_SECS,
+    };
+    (env, order, token_address, client)
+}
+
+fn sign_order(env: &Env, client: &HTLCContractClient, order: &SignedOrder) -> BytesN<64> {
+    use ed25519_dalek::Signer;
+    let digest = client.get_order_digest(order);
+    BytesN::from_array(env, &maker_key().sign(&digest.to_array()).to_bytes())
+}
+
+#[test]
+fn signed_order_creates_htlc_from_allowance() {
+    let (env, order, token_address, client) = setup_signed_order();
+    let signature = sign_order(&env, &client, &order);
+
+    let contract_id = client.create_htlc_from_signed_order(&order, &signature);
+    // The maker's signature replaces any Stellar-side authorization
+    assert!(env.auths().is_empty());
+
+    let htlc_data = client.get_htlc(&contract_id);
+    assert_eq!(htlc_data.sender, order.params.sender);
+    assert_eq!(htlc_data.status, HTLCStatus::Active);
+
+    let token_client = token::Client::new(&env, &token_address);
+    assert_eq!(
+        token_client.balance(&client.address),
+        AMOUNT + SAFETY_DEPOSIT
+    );
+    assert_eq!(
+        token_client.allowance(&order.params.sender, &client.address),
+        0
+    );
+}
+
+#[test]
+fn signed_order_bound_to_contract() {
+    let (env, order, _, client) = setup_signed_order();
+    let other = HTLCContractClient::new(&env, &env.register_contract(None, HTLCContract));
+    assert_ne!(
+        client.get_order_digest(&order),
+        other.get_order_digest(&order)
+    );
+
+    let signature = sign_order(&env, &client, &order);
+    client.create_htlc_from_signed_order(&order, &signature);
+
+    assert_fails_in_child("test::signed_order_replayed_on_other_contract");
+}
+
+#[test]
+#[ignore = "aborts the process; run by signed_order_bound_to_contract"]
+fn signed_order_replayed_on_other_contract() {
+    let (env, order, token_address, client) = setup_signed_order();
+    let signature = sign_order(&env, &client, &order);
+
+    // A second deployment the maker also approved must not accept the signature
+    let other = HTLCContractClient::new(&env, &env.register_contract(None, HTLCContract));
+    token::Client::new(&env, &token_address).approve(
+        &order.params.sender,
+        &other.address,
+        &(AMOUNT + SAFETY_DEPOSIT),
+        &(env.ledger().sequence() + DAY_IN_LEDGERS),
+    );
+    other.create_htlc_from_signed_order(&order, &signature);
+}
+
+#[test]
+fn signed_order_replay() {
+    let (env, order, token_address, client) = setup_signed_order();
+    let signature = sign_order(&env, &client, &order);
+    token::Client::new(&env, &token_address).approve(
+        &order.params.sender,
+        &client.address,
+        &(2 * (AMOUNT + SAFETY_DEPOSIT)),
+        &(env.ledger().sequence() + DAY_IN_LEDGERS),
+    );
+
+    client.create_htlc_from_signed_order(&order, &signature);
+    let result = client.try_create_htlc_from_signed_order(&order, &signature);
+    assert_eq!(result, Err(Ok(HTLCError::OrderAlreadyUsed)));
+}
+
+#[test]
+fn signed_order_expired() {
+    let (env, order, _, client) = setup_signed_order();
+    let signature = sign_order(&env, &client, &order);
+
+    env.ledger().with_mut(|l| l.timestamp = order.deadline + 1);
+    let result = client.try_create_htlc_from_signed_order(&order, &signature);
+    assert_eq!(result, Err(Ok(HTLCError::OrderExpired)));
+}
+
+#[test]
+fn signed_order_sender_mismatch() {
+    let (env, mut order, _, client) = setup_signed_order();
+    order.params.sender = Address::generate(&env);
+    let signature = sign_order(&env, &client, &order);
+
+    let result = client.try_create_htlc_from_signed_order(&order, &signature);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidOrderSigner)));
+}
+
+//------------------------------------------------------------------
+//  Fusion+ order fills
+//------------------------------------------------------------------
+/// Sets up a maker-signed Fusion+ order over the maker's own asset and a resolver holding
+/// native XLM for the safety deposit
+fn setup_fusion_order() -> (Env, FusionOrder, Address, HTLCContractClient<'static>) {
+    let (env, resolver, _, _, client) = setup_native();
+    let public_key = maker_key().verifying_key().to_bytes();
+    let (maker, token_address) = issuer_account(&env, &public_key);
+    token::Client::new(&env, &token_address).approve(
+        &maker,
+        &client.address,
+        &AMOUNT,
+        &(env.ledger().sequence() + DAY_IN_LEDGERS),
+    );
+
+    let (hashlock, _) = hashlock_pair(&env);
+    let order = FusionOrder {
+        maker: BytesN::from_array(&env, &public_key),
+        salt: BytesN::from_array(&env, &[3u8; 32]),
+        maker_asset: token_address,
+        taker_asset: BytesN::from_array(&env, &[4u8; 32]),
+        making_amount: AMOUNT,
+        taking_amount: 3,
+        hashlock,
+        hash_algo: HashAlgo::Sha256,
+        timelocks: Timelocks {
+            deployed_at: 0,
+            src_withdrawal: 60,
+            src_public_withdrawal: EXCLUSIVE_SECS as u32,
+            src_cancellation: TIMELOCK_SECS as u32,
+            src_public_cancellation: 0,
+            dst_withdrawal: 60,
+            dst_public_withdrawal: 300,
+            dst_cancellation: 1_800,
+        },
+        safety_deposit: SAFETY_DEPOSIT,
+        allowed_resolvers: Vec::from_array(&env, [resolver.clone()]),
+        expiry: env.ledger().timestamp() + EXCLUSIVE_SECS,
+        metadata: CrossChainMetadata::none(&env),
+    };
+    (env, order, resolver, client)
+}
+
+fn sign_fusion_order(env: &Env, client: &HTLCContractClient, order: &FusionOrder) -> BytesN<64> {
+    use ed25519_dalek::Signer;
+    let order_hash = client.get_fusion_order_hash(order);
+    BytesN::from_array(env, &maker_key().sign(&order_hash.to_array()).to_bytes())
+}
+
+#[test]
+fn fill_order_opens_source_escrow() {
+    let (env, order, resolver, client) = setup_fusion_order();
+    let signature = sign_fusion_order(&env, &client, &order);
+    let fill_amount = AMOUNT / 2;
+
+    let contract_id = client.fill_order(
+        &resolver,
+        &order,
+        &signature,
+        &fill_amount,
+        &Bytes::new(&env),
+    );
+    let order_hash = client.get_fusion_order_hash(&order);
+    assert_eq!(contract_id, order_hash);
+
+    // Half of a 3-unit taking amount rounds up in the maker's favour
+    let (_, topics, data) = env.events().all().last().unwrap();
+    let expected_topics: Vec<Val> = (Symbol::new(&env, "OrderFilled"), order_hash).into_val(&env);
+    assert_eq!(topics, expected_topics);
+    let (_, _, payload) = event_payload(&env, &data);
+    assert_eq!(
+        <(Address, i128, i128)>::try_from_val(&env, &payload).unwrap(),
+        (resolver.clone(), fill_amount, 2)
+    );
+
+    let now = env.ledger().timestamp();
+    let htlc_data = client.get_htlc(&contract_id);
+    assert_eq!(htlc_data.receiver, resolver);
+    assert_eq!(htlc_data.safety_deposit_payer, resolver);
+    assert_eq!(htlc_data.safety_deposit_token, client.native_token());
+    assert_eq!(htlc_data.amount, fill_amount);
+    assert_eq!(htlc_data.hashlock, order.hashlock);
+    assert_eq!(htlc_data.timelock, now + TIMELOCK_SECS);
+    assert_eq!(htlc_data.public_withdrawal, now + EXCLUSIVE_SECS);
+    assert_eq!(htlc_data.public_cancellation, 0);
+
+    let token_client = token::Client::new(&env, &order.maker_asset);
+    assert_eq!(token_client.balance(&client.address), fill_amount);
+    let native_client = token::Client::new(&env, &client.native_token());
+    assert_eq!(native_client.balance(&client.address), SAFETY_DEPOSIT);
+}
+
+#[test]
+fn fill_order_partial_fills() {
+    let (env, order, resolver, client) = setup_fusion_order();
+    let signature = sign_fusion_order(&env, &client, &order);
+    let order_hash = client.get_fusion_order_hash(&order);
+    let fill = |fill_amount: i128| {
+        client.try_fill_order(
+            &resolver,
+            &order,
+            &signature,
+            &fill_amount,
+            &Bytes::new(&env),
+        )
+    };
+
+    let first_id = fill(AMOUNT / 4).unwrap().unwrap();
+    assert_eq!(first_id, order_hash);
+    assert_eq!(client.get_order_filled_amount(&order_hash), AMOUNT / 4);
+
+    // The second fill gets its own escrow, and may not exceed what is left
+    assert_eq!(fill(AMOUNT), Err(Ok(HTLCError::InvalidFillAmount)));
+    let second_id = fill(3 * AMOUNT / 4).unwrap().unwrap();
+    assert_eq!(second_id, client.get_fill_id(&order_hash, &(AMOUNT / 4)));
+    assert_ne!(second_id, first_id);
+    assert_eq!(client.get_order_filled_amount(&order_hash), AMOUNT);
+    assert_eq!(client.get_htlc(&first_id).amount, AMOUNT / 4);
+    let second = client.get_htlc(&second_id);
+    assert_eq!(second.amount, 3 * AMOUNT / 4);
+    assert_eq!(second.order_hash, second_id);
+    assert_eq!(
+        token::Client::new(&env, &order.maker_asset).balance(&client.address),
+        AMOUNT
+    );
+
+    // Nothing is left once the making amount is used up
+    assert_eq!(fill(1), Err(Ok(HTLCError::OrderAlreadyUsed)));
+}
+
+#[test]
+fn fill_order_withdrawal_opens_at_src_withdrawal() {
+    let (env, order, resolver, client) = setup_fusion_order();
+    let signature = sign_fusion_order(&env, &client, &order);
+    let (_, preimage) = hashlock_pair(&env);
+    let contract_id = client.fill_order(&resolver, &order, &signature, &AMOUNT, &Bytes::new(&env));
+
+    let withdrawal_start = env.ledger().timestamp() + order.timelocks.src_withdrawal as u64;
+    env.ledger()
+        .with_mut(|l| l.timestamp = withdrawal_start - 1);
+    let result = client.try_withdraw(&contract_id, &preimage);
+    assert_eq!(result, Err(Ok(HTLCError::WithdrawalNotStarted)));
+
+    env.ledger().with_mut(|l| l.timestamp = withdrawal_start);
+    client.withdraw(&contract_id, &preimage);
+    assert_eq!(
+        token::Client::new(&env, &order.maker_asset).balance(&resolver),
+        AMOUNT
+    );
+}
+
+#[test]
+fn fill_order_unlisted_resolver() {
+    let (env, order, _, client) = setup_fusion_order();
+    let signature = sign_fusion_order(&env, &client, &order);
+
+    let result = client.try_fill_order(
+        &Address::generate(&env),
+        &order,
+        &signature,
+        &AMOUNT,
+        &Bytes::new(&env),
+    );
+    assert_eq!(result, Err(Ok(HTLCError::ResolverNotWhitelisted)));
+}
+
+#[test]
+fn fill_order_invalid_amount_or_expired() {
+    let (env, order, resolver, client) = setup_fusion_order();
+    let signature = sign_fusion_order(&env, &client, &order);
+
+    for fill_amount in [0, AMOUNT + 1] {
+        let result = client.try_fill_order(
+            &resolver,
+            &order,
+            &signature,
+            &fill_amount,
+            &Bytes::new(&env),
+        );
+        assert_eq!(result, Err(Ok(HTLCError::InvalidFillAmount)));
+    }
+
+    env.ledger().with_mut(|l| l.timestamp = order.expiry + 1);
+    let result = client.try_fill_order(&resolver, &order, &signature, &AMOUNT, &Bytes::new(&env));
+    assert_eq!(result, Err(Ok(HTLCError::OrderExpired)));
+}
+
+#[test]
+fn cancelled_order_cannot_be_filled() {
+    let (env, order, resolver, client) = setup_fusion_order();
+    let signature = sign_fusion_order(&env, &client, &order);
+    let order_hash = client.get_fusion_order_hash(&order);
+    // The maker issues the order's asset
+    let maker = token::StellarAssetClient::new(&env, &order.maker_asset).admin();
+
+    assert!(!client.is_order_cancelled(&maker, &order_hash));
+    client.cancel_order(&maker, &order_hash);
+    assert_eq!(env.auths()[0].0, maker);
+    assert!(client.is_order_cancelled(&maker, &order_hash));
+
+    let result = client.try_fill_order(&resolver, &order, &signature, &AMOUNT, &Bytes::new(&env));
+    assert_eq!(result, Err(Ok(HTLCError::OrderAlreadyUsed)));
+}
+
+#[test]
+fn order_cancellation_scoped_to_maker() {
+    let (env, order, resolver, client) = setup_fusion_order();
+    let signature = sign_fusion_order(&env, &client, &order);
+    let order_hash = client.get_fusion_order_hash(&order);
+
+    // Someone else cancelling the same hash does not affect the maker's order
+    let other = Address::generate(&env);
+    client.cancel_order(&other, &order_hash);
+    assert!(client.is_order_cancelled(&other, &order_hash));
+
+    let contract_id = client.fill_order(&resolver, &order, &signature, &AMOUNT, &Bytes::new(&env));
+    assert_eq!(contract_id, order_hash);
+}
+
+#[test]
+fn cancelled_signed_order_rejected() {
+    let (env, order, _, client) = setup_signed_order();
+    let signature = sign_order(&env, &client, &order);
+
+    client.cancel_order(&order.params.sender, &client.get_order_digest(&order));
+    let result = client.try_create_htlc_from_signed_order(&order, &signature);
+    assert_eq!(result, Err(Ok(HTLCError::OrderAlreadyUsed)));
+}
+
+//------------------------------------------------------------------
+//  Receiver-signed withdrawals
+//------------------------------------------------------------------
+/// Sets up an active HTLC paying an ed25519 receiver account
+fn setup_signed_withdraw() -> (
+    Env,
+    BytesN<32>,
+    BytesN<32>,
+    Address,
+    HTLCContractClient<'static>,
+) {
+    let env = new_env();
+    let public_key = maker_key().verifying_key().to_bytes();
+    let (receiver, token_address) = issuer_account(&env, &public_key);
+
+    let sender = Address::generate(&env);
+    token::StellarAssetClient::new(&env, &token_address).mint(&sender, &(AMOUNT + SAFETY_DEPOSIT));
+
+    let htlc_contract_id = env.register_contract(None, HTLCContract);
+    let client = HTLCContractClient::new(&env, &htlc_contract_id);
+
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let contract_id = client.create_htlc_with_params(&create_params(
+        &env,
+        &sender,
+        &receiver,
+        &token_address,
+        &hashlock,
+    ));
+    (env, contract_id, preimage, token_address, client)
+}
+
+fn sign_withdraw(
+    env: &Env,
+    client: &HTLCContractClient,
+    contract_id: &BytesN<32>,
+    preimage: &BytesN<32>,
+    expiry: u64,
+) -> BytesN<64> {
+    use ed25519_dalek::Signer;
+    let digest = client.get_withdraw_digest(contract_id, preimage, &expiry);
+    BytesN::from_array(env, &maker_key().sign(&digest.to_array()).to_bytes())
+}
+
+#[test]
+fn withdraw_with_sig_success() {
+    let (env, contract_id, preimage, token_address, client) = setup_signed_withdraw();
+    let expiry = env.ledger().timestamp() + EXCLUSIVE_SECS;
+    let signature = sign_withdraw(&env, &client, &contract_id, &preimage, expiry);
+
+    client.withdraw_with_sig(&contract_id, &preimage, &signature, &expiry);
+    // The receiver's signature replaces any Stellar-side authorization
+    assert!(env.auths().is_empty());
+
+    let htlc_data = client.get_htlc(&contract_id);
+    assert_eq!(htlc_data.status, HTLCStatus::Withdrawn);
+    assert_eq!(client.get_preimage(&contract_id), Some(preimage));
+
+    let token_client = token::Client::new(&env, &token_address);
+    assert_eq!(token_client.balance(&client.address), 0);
+    assert_eq!(token_client.balance(&htlc_data.sender), SAFETY_DEPOSIT);
+}
+
+#[test]
+fn withdraw_with_sig_expired() {
+    let (env, contract_id, preimage, _, client) = setup_signed_withdraw();
+    let expiry = env.ledger().timestamp() + EXCLUSIVE_SECS;
+    let signature = sign_withdraw(&env, &client, &contract_id, &preimage, expiry);
+
+    env.ledger().with_mut(|l| l.timestamp = expiry + 1);
+    let result = client.try_withdraw_with_sig(&contract_id, &preimage, &signature, &expiry);
+    assert_eq!(result, Err(Ok(HTLCError::SignatureExpired)));
+}
+
+#[test]
+fn withdraw_with_sig_contract_receiver() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let contract_id = client.create_htlc_with_params(&create_params(
+        &env,
+        &sender,
+        &receiver,
+        &token_address,
+        &hashlock,
+    ));
+
+    // Contract receivers have no ed25519 key to sign with
+    let expiry = env.ledger().timestamp() + EXCLUSIVE_SECS;
+    let signature = sign_withdraw(&env, &client, &contract_id, &preimage, expiry);
+    let result = client.try_withdraw_with_sig(&contract_id, &preimage, &signature, &expiry);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidSigner)));
+}
+
+#[test]
+fn withdraw_with_sig_twice() {
+    let (env, contract_id, preimage, _, client) = setup_signed_withdraw();
+    let expiry = env.ledger().timestamp() + EXCLUSIVE_SECS;
+    let signature = sign_withdraw(&env, &client, &contract_id, &preimage, expiry);
+
+    client.withdraw_with_sig(&contract_id, &preimage, &signature, &expiry);
+    let result = client.try_withdraw_with_sig(&contract_id, &preimage, &signature, &expiry);
+    assert_eq!(result, Err(Ok(HTLCError::AlreadyWithdrawn)));
+}
+
+//------------------------------------------------------------------
+//  Amount limits and overflow
+//------------------------------------------------------------------
+#[test]
+fn amount_plus_deposit_overflow() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.amount = i128::MAX;
+    params.safety_deposit = 1;
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::AmountOverflow)));
+
+    params.amount = 1;
+    params.safety_deposit = i128::MAX;
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::AmountOverflow)));
+}
+
+#[test]
+fn amount_above_cap() {
+    let (env, sender, receiver, token_address, _) = setup();
+    let client = initialized(&env, &Address::generate(&env), false);
+    assert_eq!(client.get_max_amount(), i128::MAX);
+
+    client.set_max_amount(&(AMOUNT - 1));
+    let (hashlock, _) = hashlock_pair(&env);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::AmountAboveCap)));
+
+    params.amount = AMOUNT - 1;
+    client.create_htlc_with_params(&params);
+
+    let result = client.try_set_max_amount(&0);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidAmount)));
+}
+
+#[test]
+fn amount_below_minimum() {
+    let (env, sender, receiver, token_address, _) = setup();
+    let client = initialized(&env, &Address::generate(&env), false);
+    client.set_min_amount(&(AMOUNT + 1));
+    assert_eq!(client.get_min_amount(), AMOUNT + 1);
+
+    let (hashlock, _) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidAmount)));
+
+    client.set_min_amount(&AMOUNT);
+    client.create_htlc_with_params(&params);
+}
+
+#[test]
+fn creations_rate_limited_per_sender() {
+    let (env, sender, receiver, token_address, _) = setup();
+    token::StellarAssetClient::new(&env, &token_address)
+        .mint(&sender, &(2 * (AMOUNT + SAFETY_DEPOSIT)));
+    let client = initialized(&env, &Address::generate(&env), false);
+    let result = client.try_set_rate_limit(&2, &0);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidRateLimit)));
+    client.set_rate_limit(&2, &100);
+
+    let (hashlock, _) = hashlock_pair(&env);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.allow_reuse = true;
+    for salt in 1..=2u8 {
+        params.salt = BytesN::from_array(&env, &[salt; 32]);
+        client.create_htlc_with_params(&params);
+    }
+    assert_eq!(client.get_creation_count(&sender), 2);
+
+    params.salt = BytesN::from_array(&env, &[3; 32]);
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::RateLimited)));
+
+    // The window rolls over after its length in ledgers
+    env.ledger().with_mut(|l| l.sequence_number += 100);
+    assert_eq!(client.get_creation_count(&sender), 0);
+    client.create_htlc_with_params(&params);
+}
+
+#[test]
+fn same_token_deposit_single_transfer() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+
+    client.create_htlc_with_params(&create_params(
+        &env,
+        &sender,
+        &receiver,
+        &token_address,
+        &hashlock,
+    ));
+
+    let transfer = Symbol::new(&env, "transfer");
+    let transfers = env
+        .events()
+        .all()
+        .iter()
+        .filter(|(emitter, topics, _)| {
+            *emitter == token_address
+                && Symbol::try_from_val(&env, &topics.get(0).unwrap()) == Ok(transfer.clone())
+        })
+        .count();
+    assert_eq!(transfers, 1);
+    assert_eq!(
+        client.get_token_accounting(&token_address).locked,
+        AMOUNT + SAFETY_DEPOSIT
+    );
+}
+
+//------------------------------------------------------------------
+//  Multi-asset baskets
+//------------------------------------------------------------------
+const REWARD_AMOUNT: i128 = 50_000_000;
+
+/// Creates an HTLC locking the swap token plus a reward token leg
+fn create_basket(
+    env: &Env,
+    sender: &Address,
+    receiver: &Address,
+    token_address: &Address,
+    client: &HTLCContractClient<'static>,
+) -> (BytesN<32>, BytesN<32>, Address) {
+    let reward_address = env.register_stellar_asset_contract(sender.clone());
+    token::StellarAssetClient::new(env, &reward_address).mint(sender, &REWARD_AMOUNT);
+
+    let (hashlock, preimage) = hashlock_pair(env);
+    let mut params = create_params(env, sender, receiver, token_address, &hashlock);
+    params.basket.push_back(BasketLeg {
+        token: reward_address.clone(),
+        amount: REWARD_AMOUNT,
+    });
+    (
+        client.create_htlc_with_params(&params),
+        preimage,
+        reward_address,
+    )
+}
+
+#[test]
+fn basket_withdraw_releases_all_legs() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (contract_id, preimage, reward_address) =
+        create_basket(&env, &sender, &receiver, &token_address, &client);
+
+    let reward_client = token::Client::new(&env, &reward_address);
+    assert_eq!(reward_client.balance(&client.address), REWARD_AMOUNT);
+    assert_eq!(
+        client.get_token_accounting(&reward_address).locked,
+        REWARD_AMOUNT
+    );
+
+    client.withdraw(&contract_id, &preimage);
+    let token_client = token::Client::new(&env, &token_address);
+    assert_eq!(token_client.balance(&receiver), AMOUNT);
+    assert_eq!(reward_client.balance(&receiver), REWARD_AMOUNT);
+    assert_eq!(client.get_token_accounting(&reward_address).locked, 0);
+}
+
+#[test]
+fn basket_refund_returns_all_legs() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (contract_id, _, reward_address) =
+        create_basket(&env, &sender, &receiver, &token_address, &client);
+
+    env.ledger().with_mut(|l| l.timestamp += TIMELOCK_SECS);
+    client.refund(&contract_id);
+
+    let token_client = token::Client::new(&env, &token_address);
+    assert_eq!(token_client.balance(&sender), AMOUNT + SAFETY_DEPOSIT);
+    let reward_client = token::Client::new(&env, &reward_address);
+    assert_eq!(reward_client.balance(&sender), REWARD_AMOUNT);
+    assert_eq!(reward_client.balance(&client.address), 0);
+}
+
+#[test]
+fn basket_invalid_legs() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let reward_address = env.register_stellar_asset_contract(sender.clone());
+
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.basket.push_back(BasketLeg {
+        token: reward_address.clone(),
+        amount: 0,
+    });
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidBasket)));
+
+    // The primary token cannot be repeated as a leg
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.basket.push_back(BasketLeg {
+        token: token_address.clone(),
+        amount: REWARD_AMOUNT,
+    });
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidBasket)));
+
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.parts = PARTS;
+    params.basket.push_back(BasketLeg {
+        token: reward_address,
+        amount: REWARD_AMOUNT,
+    });
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidBasket)));
+}
+
+//------------------------------------------------------------------
+//  Cancellation by agreement
+//------------------------------------------------------------------
+#[test]
+fn cancel_by_agreement_refunds_sender() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let contract_id = client.create_htlc_with_params(&create_params(
+        &env,
+        &sender,
+        &receiver,
+        &token_address,
+        &hashlock,
+    ));
+
+    // No need to wait for the timelock
+    client.cancel_by_agreement(&contract_id);
+    let auths = env.auths();
+    assert_eq!(auths.len(), 2);
+    assert_eq!(auths[0].0, sender);
+    assert_eq!(auths[1].0, receiver);
+
+    let (_, topics, _) = env.events().all().last().unwrap();
+    let expected_topics: Vec<Val> =
+        (Symbol::new(&env, "HTLCCancelled"), contract_id.clone()).into_val(&env);
+    assert_eq!(topics, expected_topics);
+
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Refunded);
+    let token_client = token::Client::new(&env, &token_address);
+    assert_eq!(token_client.balance(&sender), AMOUNT + SAFETY_DEPOSIT);
+    assert_eq!(token_client.balance(&client.address), 0);
+}
+
+#[test]
+fn cancel_by_agreement_after_withdraw() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let contract_id = client.create_htlc_with_params(&create_params(
+        &env,
+        &sender,
+        &receiver,
+        &token_address,
+        &hashlock,
+    ));
+
+    client.withdraw(&contract_id, &preimage);
+    let result = client.try_cancel_by_agreement(&contract_id);
+    assert_eq!(result, Err(Ok(HTLCError::AlreadyWithdrawn)));
+}
+
+//------------------------------------------------------------------
+//  Timelock extension by agreement
+//------------------------------------------------------------------
+#[test]
+fn extend_timelock_by_agreement() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+
+    let new_timelock = params.timelock + TIMELOCK_SECS;
+    client.extend_timelock(&contract_id, &new_timelock);
+    let auths = env.auths();
+    assert_eq!(auths.len(), 2);
+    assert_eq!(auths[0].0, sender);
+    assert_eq!(auths[1].0, receiver);
+
+    let (_, topics, data) = env.events().all().last().unwrap();
+    let expected_topics: Vec<Val> = (
+        Symbol::new(&env, "HTLCTimelockExtended"),
+        contract_id.clone(),
+    )
+        .into_val(&env);
+    assert_eq!(topics, expected_topics);
+    let (payload, _, _) = lifecycle_payload(&env, &data);
+    assert_eq!(
+        <(u64, u64)>::try_from_val(&env, &payload).unwrap(),
+        (params.timelock, new_timelock)
+    );
+
+    // The receiver can still claim past the original timelock
+    env.ledger().with_mut(|l| l.timestamp = params.timelock);
+    client.withdraw(&contract_id, &preimage);
+}
+
+#[test]
+fn extend_timelock_invalid() {
+    let (env, sender, receiver, token_address, _) = setup();
+    let client = initialized(&env, &Address::generate(&env), false);
+    client.set_timelock_bounds(&0, &(2 * TIMELOCK_SECS));
+
+    let (hashlock, _) = hashlock_pair(&env);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.public_cancellation = params.timelock + EXCLUSIVE_SECS;
+    let contract_id = client.create_htlc_with_params(&params);
+
+    let result = client.try_extend_timelock(&contract_id, &params.timelock);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidTimelock)));
+
+    // Bounded by the maximum timelock duration
+    let too_late = env.ledger().timestamp() + 2 * TIMELOCK_SECS + 1;
+    let result = client.try_extend_timelock(&contract_id, &too_late);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidTimelock)));
+
+    let past_cancellation = params.public_cancellation + 1;
+    let result = client.try_extend_timelock(&contract_id, &past_cancellation);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidPublicCancellation)));
+}
+
+//------------------------------------------------------------------
+//  Resolver-funded safety deposits
+//------------------------------------------------------------------
+/// Creates an HTLC whose safety deposit is posted by a separate resolver
+fn create_resolver_funded(
+    env: &Env,
+    sender: &Address,
+    receiver: &Address,
+    token_address: &Address,
+    client: &HTLCContractClient<'static>,
+) -> (BytesN<32>, BytesN<32>, Address) {
+    let resolver = Address::generate(env);
+    token::StellarAssetClient::new(env, token_address).mint(&resolver, &SAFETY_DEPOSIT);
+
+    let (hashlock, preimage) = hashlock_pair(env);
+    let mut params = create_params(env, sender, receiver, token_address, &hashlock);
+    params.safety_deposit_payer = resolver.clone();
+    (client.create_htlc_with_params(&params), preimage, resolver)
+}
+
+#[test]
+fn resolver_funded_deposit_on_creation() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (contract_id, _, resolver) =
+        create_resolver_funded(&env, &sender, &receiver, &token_address, &client);
+
+    let auths = env.auths();
+    assert_eq!(auths.len(), 2);
+    assert_eq!(auths[0].0, sender);
+    assert_eq!(auths[1].0, resolver);
+    assert_eq!(client.get_htlc(&contract_id).safety_deposit_payer, resolver);
+
+    let token_client = token::Client::new(&env, &token_address);
+    assert_eq!(token_client.balance(&sender), SAFETY_DEPOSIT);
+    assert_eq!(token_client.balance(&resolver), 0);
+    assert_eq!(
+        token_client.balance(&client.address),
+        AMOUNT + SAFETY_DEPOSIT
+    );
+}
+
+#[test]
+fn resolver_funded_deposit_returned_on_withdraw() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (contract_id, preimage, resolver) =
+        create_resolver_funded(&env, &sender, &receiver, &token_address, &client);
+
+    client.withdraw(&contract_id, &preimage);
+    let token_client = token::Client::new(&env, &token_address);
+    assert_eq!(token_client.balance(&receiver), AMOUNT);
+    assert_eq!(token_client.balance(&resolver), SAFETY_DEPOSIT);
+    assert_eq!(token_client.balance(&sender), SAFETY_DEPOSIT);
+}
+
+#[test]
+fn resolver_funded_deposit_slashed_on_refund() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (contract_id, _, resolver) =
+        create_resolver_funded(&env, &sender, &receiver, &token_address, &client);
+
+    // The resolver let the timelock pass, so the refunding sender takes its deposit
+    env.ledger().with_mut(|l| l.timestamp += TIMELOCK_SECS);
+    client.refund(&contract_id);
+    let token_client = token::Client::new(&env, &token_address);
+    assert_eq!(token_client.balance(&sender), AMOUNT + 2 * SAFETY_DEPOSIT);
+    assert_eq!(token_client.balance(&resolver), 0);
+}
+
+#[test]
+fn resolver_deposit_slashed_after_exclusive_deadline() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let resolver = Address::generate(&env);
+    token::StellarAssetClient::new(&env, &token_address).mint(&resolver, &SAFETY_DEPOSIT);
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.safety_deposit_payer = resolver.clone();
+    params.public_withdrawal = env.ledger().timestamp() + EXCLUSIVE_SECS;
+    let contract_id = client.create_htlc_with_params(&params);
+
+    env.ledger().with_mut(|l| l.timestamp += EXCLUSIVE_SECS);
+    client.withdraw(&contract_id, &preimage);
+    let token_client = token::Client::new(&env, &token_address);
+    assert_eq!(token_client.balance(&receiver), AMOUNT + SAFETY_DEPOSIT);
+    assert_eq!(token_client.balance(&resolver), 0);
+
+    assert_eq!(
+        deposit_events(&env, &contract_id, "SafetyDepositSlashed"),
+        [(resolver, receiver, SAFETY_DEPOSIT)]
+    );
+}
+
+/// Collects the `(payer, recipient, amount)` payloads of an HTLC's safety deposit events
+fn deposit_events(
+    env: &Env,
+    contract_id: &BytesN<32>,
+    name: &str,
+) -> std::vec::Vec<(Address, Address, i128)> {
+    let expected_topics: Vec<Val> = (Symbol::new(env, name), contract_id.clone()).into_val(env);
+    env.events()
+        .all()
+        .iter()
+        .filter(|(_, topics, _)| *topics == expected_topics)
+        .map(|(_, _, data)| {
+            let (_, _, payload) = event_payload(env, &data);
+            <(Address, Address, i128)>::try_from_val(env, &payload).unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn safety_deposit_paid_and_returned_events() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (contract_id, preimage, resolver) =
+        create_resolver_funded(&env, &sender, &receiver, &token_address, &client);
+    assert_eq!(
+        deposit_events(&env, &contract_id, "SafetyDepositPaid"),
+        [(resolver.clone(), client.address.clone(), SAFETY_DEPOSIT)]
+    );
+
+    client.withdraw(&contract_id, &preimage);
+    assert_eq!(
+        deposit_events(&env, &contract_id, "SafetyDepositReturned"),
+        [(resolver.clone(), resolver, SAFETY_DEPOSIT)]
+    );
+    assert!(deposit_events(&env, &contract_id, "SafetyDepositSlashed").is_empty());
+}
+
+//------------------------------------------------------------------
+//  Intra-Stellar swaps
+//------------------------------------------------------------------
+fn swap_params(env: &Env) -> (SwapParams, BytesN<32>) {
+    let initiator = Address::generate(env);
+    let counterparty = Address::generate(env);
+    let initiator_token = env.register_stellar_asset_contract(initiator.clone());
+    let counterparty_token = env.register_stellar_asset_contract(counterparty.clone());
+    token::StellarAssetClient::new(env, &initiator_token).mint(&initiator, &AMOUNT);
+    token::StellarAssetClient::new(env, &counterparty_token).mint(&counterparty, &(2 * AMOUNT));
+
+    let (hashlock, preimage) = hashlock_pair(env);
+    let now = env.ledger().timestamp();
+    let swap = SwapParams {
+        initiator,
+        counterparty,
+        hashlock,
+        initiator_token,
+        initiator_amount: AMOUNT,
+        initiator_timelock: now + 2 * TIMELOCK_SECS,
+        counterparty_token,
+        counterparty_amount: 2 * AMOUNT,
+        counterparty_timelock: now + TIMELOCK_SECS,
+    };
+    (swap, preimage)
+}
+
+#[test]
+fn create_swap_mirrors_legs() {
+    let env = new_env();
+    let client = HTLCContractClient::new(&env, &env.register_contract(None, HTLCContract));
+    let (swap, preimage) = swap_params(&env);
+
+    let (initiator_id, counterparty_id) = client.create_swap(&swap);
+    let initiator_leg = client.get_htlc(&initiator_id);
+    assert_eq!(initiator_leg.sender, swap.initiator);
+    assert_eq!(initiator_leg.receiver, swap.counterparty);
+    assert_eq!(initiator_leg.timelock, swap.initiator_timelock);
+    let counterparty_leg = client.get_htlc(&counterparty_id);
+    assert_eq!(counterparty_leg.sender, swap.counterparty);
+    assert_eq!(counterparty_leg.receiver, swap.initiator);
+    assert_eq!(counterparty_leg.hashlock, initiator_leg.hashlock);
+
+    // Initiator claims first, revealing the secret for the counterparty
+    client.withdraw(&counterparty_id, &preimage);
+    let revealed = client.get_preimage(&counterparty_id).unwrap();
+    client.withdraw(&initiator_id, &revealed);
+
+    let initiator_token = token::Client::new(&env, &swap.initiator_token);
+    let counterparty_token = token::Client::new(&env, &swap.counterparty_token);
+    assert_eq!(initiator_token.balance(&swap.counterparty), AMOUNT);
+    assert_eq!(counterparty_token.balance(&swap.initiator), 2 * AMOUNT);
+}
+
+#[test]
+fn create_swap_requires_staggered_timelocks() {
+    let env = new_env();
+    let client = HTLCContractClient::new(&env, &env.register_contract(None, HTLCContract));
+    let (mut swap, _) = swap_params(&env);
+
+    swap.counterparty_timelock = swap.initiator_timelock;
+    let result = client.try_create_swap(&swap);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidTimelock)));
+
+    let (mut swap, _) = swap_params(&env);
+    swap.counterparty = swap.initiator.clone();
+    let result = client.try_create_swap(&swap);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidSwap)));
+}
+
+#[test]
+fn create_swap_is_atomic() {
+    let env = new_env();
+    let client = HTLCContractClient::new(&env, &env.register_contract(None, HTLCContract));
+    let (mut swap, _) = swap_params(&env);
+
+    // An invalid counterparty leg reverts the initiator leg as well
+    swap.counterparty_amount = 0;
+    let result = client.try_create_swap(&swap);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidAmount)));
+    assert_eq!(
+        client.get_htlcs_by_sender(&swap.initiator, &0, &10).len(),
+        0
+    );
+}
+
+//------------------------------------------------------------------
+//  Designated taker
+//------------------------------------------------------------------
+fn create_with_taker(
+    env: &Env,
+    sender: &Address,
+    receiver: &Address,
+    token_address: &Address,
+    taker: &Address,
+    client: &HTLCContractClient<'static>,
+) -> (BytesN<32>, BytesN<32>) {
+    let (hashlock, preimage) = hashlock_pair(env);
+    let mut params = create_params(env, sender, receiver, token_address, &hashlock);
+    params.public_withdrawal = env.ledger().timestamp() + EXCLUSIVE_SECS;
+    params.taker = taker.clone();
+    (client.create_htlc_with_params(&params), preimage)
+}
+
+#[test]
+fn taker_withdraws_during_exclusive_window() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let taker = Address::generate(&env);
+    let (contract_id, preimage) =
+        create_with_taker(&env, &sender, &receiver, &token_address, &taker, &client);
+    assert_eq!(client.get_htlc(&contract_id).taker, taker);
+
+    client.withdraw(&contract_id, &preimage);
+    assert_eq!(env.auths()[0].0, taker);
+
+    // Funds still go to the receiver
+    let token_client = token::Client::new(&env, &token_address);
+    assert_eq!(token_client.balance(&receiver), AMOUNT);
+    assert_eq!(token_client.balance(&sender), SAFETY_DEPOSIT);
+}
+
+#[test]
+fn receiver_withdraws_after_taker_window() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let taker = Address::generate(&env);
+    let (contract_id, preimage) =
+        create_with_taker(&env, &sender, &receiver, &token_address, &taker, &client);
+
+    let destination = Address::generate(&env);
+    let result = client.try_withdraw_to(&contract_id, &preimage, &destination);
+    assert_eq!(result, Err(Ok(HTLCError::TakerExclusive)));
+
+    env.ledger().with_mut(|l| l.timestamp += EXCLUSIVE_SECS);
+    client.withdraw_to(&contract_id, &preimage, &destination);
+    assert_eq!(env.auths()[0].0, receiver);
+    assert_eq!(
+        token::Client::new(&env, &token_address).balance(&destination),
+        AMOUNT
+    );
+}
+
+#[test]
+fn withdraw_without_taker_needs_receiver() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+
+    client.withdraw(&contract_id, &preimage);
+    assert_eq!(env.auths()[0].0, receiver);
+}
+
+//------------------------------------------------------------------
+//  k-of-n hashlocks
+//------------------------------------------------------------------
+/// Creates a 2-of-3 HTLC, returning its ID and the three secrets
+fn create_threshold(
+    env: &Env,
+    sender: &Address,
+    receiver: &Address,
+    token_address: &Address,
+    client: &HTLCContractClient<'static>,
+) -> (BytesN<32>, Vec<BytesN<32>>) {
+    let secrets = partial_secrets(env).slice(0..3);
+    let mut hashlocks = Vec::new(env);
+    let mut packed = Bytes::new(env);
+    for secret in secrets.iter() {
+        let leaf = env.crypto().sha256(&secret.into());
+        packed.extend_from_slice(&leaf.to_array());
+        hashlocks.push_back(leaf);
+    }
+
+    let commitment = env.crypto().sha256(&packed);
+    let mut params = create_params(env, sender, receiver, token_address, &commitment);
+    params.hashlocks = hashlocks;
+    params.threshold = 2;
+    (client.create_htlc_with_params(&params), secrets)
+}
+
+#[test]
+fn threshold_withdraw_with_k_preimages() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (contract_id, secrets) =
+        create_threshold(&env, &sender, &receiver, &token_address, &client);
+
+    // Any two of the three secrets unlock the escrow
+    let preimages = Vec::from_array(&env, [secrets.get(2).unwrap(), secrets.get(0).unwrap()]);
+    client.withdraw_threshold(&contract_id, &preimages);
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Withdrawn);
+    assert_eq!(
+        token::Client::new(&env, &token_address).balance(&receiver),
+        AMOUNT
+    );
+}
+
+#[test]
+fn threshold_withdraw_rejects_insufficient_preimages() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (contract_id, secrets) =
+        create_threshold(&env, &sender, &receiver, &token_address, &client);
+    let first = secrets.get(0).unwrap();
+
+    let result =
+        client.try_withdraw_threshold(&contract_id, &Vec::from_array(&env, [first.clone()]));
+    assert_eq!(result, Err(Ok(HTLCError::InvalidPreimage)));
+
+    // Repeating a secret does not count twice
+    let repeated = Vec::from_array(&env, [first.clone(), first.clone()]);
+    let result = client.try_withdraw_threshold(&contract_id, &repeated);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidPreimage)));
+
+    let result = client.try_withdraw(&contract_id, &first);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidThreshold)));
+}
+
+#[test]
+fn create_threshold_validation() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.hashlocks = Vec::from_array(&env, [hashlock.clone()]);
+
+    // Threshold above the set size, and a main hashlock that is not the set commitment
+    params.threshold = 2;
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidThreshold)));
+    params.threshold = 1;
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidThreshold)));
+}
+
+//------------------------------------------------------------------
+//  Misbehaving tokens
+//------------------------------------------------------------------
+#[test]
+fn fee_on_transfer_token_records_received_amount() {
+    let env = new_env();
+    let sender = Address::generate(&env);
+    let receiver = Address::generate(&env);
+    let token_address = env.register_contract(None, MockToken);
+    let fee_token = MockTokenClient::new(&env, &token_address);
+    fee_token.set_mode(&MockTokenMode::FeeOnTransfer(100)); // 1%
+    fee_token.mint(&sender, &(AMOUNT + SAFETY_DEPOSIT));
+    let client = HTLCContractClient::new(&env, &env.register_contract(None, HTLCContract));
+
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+
+    // The transfer fee is charged to the swap amount, the deposit stays whole
+    let received = AMOUNT + SAFETY_DEPOSIT - (AMOUNT + SAFETY_DEPOSIT) / 100;
+    let htlc = client.get_htlc(&contract_id);
+    assert_eq!(htlc.amount, received - SAFETY_DEPOSIT);
+    assert_eq!(htlc.safety_deposit, SAFETY_DEPOSIT);
+    assert_eq!(client.get_token_accounting(&token_address).locked, received);
+
+    // Payouts never exceed what the contract holds
+    client.withdraw(&contract_id, &preimage);
+    assert_eq!(fee_token.balance(&client.address), 0);
+}
+
+#[test]
+fn reentrant_token_cannot_withdraw_twice() {
+    let env = new_env();
+    let sender = Address::generate(&env);
+    let receiver = Address::generate(&env);
+    let mock_token = MockTokenClient::new(&env, &env.register_contract(None, MockToken));
+    mock_token.mint(&sender, &(AMOUNT + SAFETY_DEPOSIT));
+    let client = HTLCContractClient::new(&env, &env.register_contract(None, HTLCContract));
+
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &mock_token.address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+
+    // The payout transfer calls back into withdraw for the same HTLC
+    let args = (contract_id.clone(), preimage.clone()).into_val(&env);
+    mock_token.set_mode(&MockTokenMode::Reentrant(
+        client.address.clone(),
+        Symbol::new(&env, "withdraw"),
+        args,
+    ));
+    client.withdraw(&contract_id, &preimage);
+
+    assert!(!mock_token.reentered());
+    assert_eq!(mock_token.balance(&receiver), AMOUNT);
+    assert_eq!(mock_token.balance(&sender), SAFETY_DEPOSIT);
+    assert_eq!(mock_token.balance(&client.address), 0);
+
+    // A partial fill's transfer calls back into withdraw_partial with the same secret
+    mock_token.mint(&sender, &(AMOUNT + SAFETY_DEPOSIT));
+    let (contract_id, secrets) =
+        create_partial(&env, &sender, &receiver, &mock_token.address, &client);
+    let (_, proof) = merkle_root_and_proof(&env, &secrets, 0);
+    let small_fill = AMOUNT / 10;
+    let args = (
+        contract_id.clone(),
+        secrets.get(0).unwrap(),
+        0u32,
+        proof.clone(),
+        small_fill,
+    )
+        .into_val(&env);
+    mock_token.set_mode(&MockTokenMode::Reentrant(
+        client.address.clone(),
+        Symbol::new(&env, "withdraw_partial"),
+        args,
+    ));
+    client.withdraw_partial(
+        &contract_id,
+        &secrets.get(0).unwrap(),
+        &0,
+        &proof,
+        &small_fill,
+    );
+
+    assert!(!mock_token.reentered());
+    assert_eq!(client.get_htlc(&contract_id).filled_amount, small_fill);
+    assert_eq!(mock_token.balance(&receiver), AMOUNT + small_fill);
+    assert_eq!(
+        mock_token.balance(&client.address),
+        AMOUNT - small_fill + SAFETY_DEPOSIT
+    );
+}
+
+//------------------------------------------------------------------
+//  Statistics
+//------------------------------------------------------------------
+#[test]
+fn stats_track_lifecycle_and_volume() {
+    let (env, sender, receiver, token_address, client) = setup();
+    token::StellarAssetClient::new(&env, &token_address)
+        .mint(&sender, &(2 * (AMOUNT + SAFETY_DEPOSIT)));
+    assert_eq!(client.get_stats(), Stats::default());
+
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let withdrawn_id = client.create_htlc_with_params(&create_params(
+        &env,
+        &sender,
+        &receiver,
+        &token_address,
+        &hashlock,
+    ));
+    let refunded_id = client.create_htlc_with_params(&create_params(
+        &env,
+        &sender,
+        &receiver,
+        &token_address,
+        &BytesN::from_array(&env, &[1u8; 32]),
+    ));
+    client.create_htlc_with_params(&create_params(
+        &env,
+        &sender,
+        &receiver,
+        &token_address,
+        &BytesN::from_array(&env, &[2u8; 32]),
+    ));
+
+    client.withdraw(&withdrawn_id, &preimage);
+    env.ledger().with_mut(|l| l.timestamp += TIMELOCK_SECS);
+    client.refund(&refunded_id);
+
+    assert_eq!(
+        client.get_stats(),
+        Stats {
+            total_created: 3,
+            total_withdrawn: 1,
+            total_refunded: 1,
+            active: 1,
+        }
+    );
+    // Safety deposits are not counted as volume
+    assert_eq!(client.get_volume(&token_address), 3 * AMOUNT);
+    assert_eq!(client.get_volume(&Address::generate(&env)), 0);
+}
+
+//------------------------------------------------------------------
+//  Post-withdraw callbacks
+//------------------------------------------------------------------
+/// Error returned by the mock contracts below
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u32)]
+enum MockError {
+    Rejected = 1,
+}
+
+/// Records every `on_htlc_withdrawn` call; rejects calls whose data is `reject`
+#[contract]
+struct WithdrawRecorder;
+
+#[contractimpl]
+impl WithdrawRecorder {
+    pub fn on_htlc_withdrawn(
+        env: Env,
+        contract_id: BytesN<32>,
+        recipient: Address,
+        token: Address,
+        amount: i128,
+        callback_data: Bytes,
+    ) -> Result<(), MockError> {
+        if callback_data == Bytes::from_slice(&env, b"reject") {
+            return Err(MockError::Rejected);
+        }
+        let mut calls: Vec<(BytesN<32>, Address, Address, i128, Bytes)> = Self::calls(env.clone());
+        calls.push_back((contract_id, recipient, token, amount, callback_data));
+        env.storage()
+            .instance()
+            .set(&symbol_short!("calls"), &calls);
+        Ok(())
+    }
+
+    pub fn calls(env: Env) -> Vec<(BytesN<32>, Address, Address, i128, Bytes)> {
+        env.storage()
+            .instance()
+            .get(&symbol_short!("calls"))
+            .unwrap_or(Vec::new(&env))
+    }
+}
+
+#[test]
+fn withdraw_invokes_callback() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let recorder =
+        WithdrawRecorderClient::new(&env, &env.register_contract(None, WithdrawRecorder));
+
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.callback = recorder.address.clone();
+    params.callback_data = Bytes::from_slice(&env, b"deposit");
+    let contract_id = client.create_htlc_with_params(&params);
+    assert_eq!(client.get_htlc(&contract_id).callback, recorder.address);
+    assert!(recorder.calls().is_empty());
+
+    client.withdraw(&contract_id, &preimage);
+    assert_eq!(
+        recorder.calls(),
+        Vec::from_array(
+            &env,
+            [(
+                contract_id,
+                receiver,
+                token_address,
+                AMOUNT,
+                Bytes::from_slice(&env, b"deposit"),
+            )],
+        )
+    );
+}
+
+#[test]
+fn failing_callback_does_not_block_withdraw() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let recorder =
+        WithdrawRecorderClient::new(&env, &env.register_contract(None, WithdrawRecorder));
+
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.callback = recorder.address.clone();
+    params.callback_data = Bytes::from_slice(&env, b"reject");
+    let contract_id = client.create_htlc_with_params(&params);
+
+    client.withdraw(&contract_id, &preimage);
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Withdrawn);
+    assert!(recorder.calls().is_empty());
+
+    let failed = env
+        .events()
+        .all()
+        .iter()
+        .find(|(_, topics, _)| {
+            Symbol::try_from_val(&env, &topics.get(0).unwrap())
+                == Ok(Symbol::new(&env, "CallbackFailed"))
+        })
+        .unwrap();
+    let (_, _, payload) = event_payload(&env, &failed.2);
+    assert_eq!(
+        Address::try_from_val(&env, &payload).unwrap(),
+        recorder.address
+    );
+}
+
+//------------------------------------------------------------------
+//  Creation validator
+//------------------------------------------------------------------
+/// Policy contract recording the parameters of every HTLC it approves
+#[contract]
+struct RecordingValidator;
+
+#[contractimpl]
+impl RecordingValidator {
+    pub fn validate_htlc(env: Env, params: CreateParams) {
+        let mut seen = Self::seen(env.clone());
+        seen.push_back(params);
+        env.storage().instance().set(&symbol_short!("seen"), &seen);
+    }
+
+    pub fn seen(env: Env) -> Vec<CreateParams> {
+        env.storage()
+            .instance()
+            .get(&symbol_short!("seen"))
+            .unwrap_or(Vec::new(&env))
+    }
+}
+
+#[test]
+fn validator_consulted_on_creation() {
+    let (env, sender, receiver, token_address, _) = setup();
+    token::StellarAssetClient::new(&env, &token_address).mint(&sender, &(AMOUNT + SAFETY_DEPOSIT));
+    let client = initialized(&env, &Address::generate(&env), false);
+    let validator =
+        RecordingValidatorClient::new(&env, &env.register_contract(None, RecordingValidator));
+    assert_eq!(client.get_validator(), None);
+
+    client.set_validator(&validator.address);
+    assert_eq!(client.get_validator(), Some(validator.address.clone()));
+
+    // The validator sees the parameters exactly as submitted
+    let (hashlock, _) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    client.create_htlc_with_params(&params);
+    assert_eq!(validator.seen(), Vec::from_array(&env, [params.clone()]));
+
+    client.remove_validator();
+    assert_eq!(client.get_validator(), None);
+    let mut params = params;
+    params.allow_reuse = true;
+    params.salt = BytesN::from_array(&env, &[1; 32]);
+    client.create_htlc_with_params(&params);
+    assert_eq!(validator.seen().len(), 1);
+}
+
+//------------------------------------------------------------------
+//  1inch Timelocks encoding
+//------------------------------------------------------------------
+fn sample_timelocks() -> Timelocks {
+    Timelocks {
+        deployed_at: 1_700_000_000,
+        src_withdrawal: 10,
+        src_public_withdrawal: 120,
+        src_cancellation: 3_600,
+        src_public_cancellation: 3_700,
+        dst_withdrawal: 5,
+        dst_public_withdrawal: 100,
+        dst_cancellation: 3_000,
+    }
+}
+
+#[test]
+fn timelocks_pack_like_timelocks_lib() {
+    let (env, _, _, _, client) = setup();
+    let timelocks = sample_timelocks();
+
+    // deployedAt << 224 | offset(stage) << (32 * stage), big-endian
+    let mut expected = [0u8; 32];
+    let words: [u32; 8] = [1_700_000_000, 3_000, 100, 5, 3_700, 3_600, 120, 10];
+    for (i, word) in words.iter().enumerate() {
+        expected[4 * i..4 * i + 4].copy_from_slice(&word.to_be_bytes());
+    }
+    let packed = client.pack_timelocks(&timelocks);
+    assert_eq!(packed, BytesN::from_array(&env, &expected));
+    assert_eq!(client.unpack_timelocks(&packed), timelocks);
+}
+
+#[test]
+fn timelocks_stage_lookup() {
+    let (env, _, _, _, client) = setup();
+    let timelocks = sample_timelocks();
+    let packed = timelocks.pack(&env);
+
+    assert_eq!(
+        client.get_timelock_stage(&packed, &TimelockStage::SrcCancellation),
+        1_700_003_600
+    );
+    env.ledger().with_mut(|l| l.timestamp = 1_700_000_100);
+    assert!(timelocks.reached(&env, TimelockStage::DstPublicWithdrawal));
+    assert!(!timelocks.reached(&env, TimelockStage::SrcPublicWithdrawal));
+}
+
+//------------------------------------------------------------------
+//  Rescue of stuck HTLCs
+//------------------------------------------------------------------
+const RESCUE_DELAY: u64 = 30 * 24 * 60 * 60; // 30 days
+
+#[test]
+fn sender_rescues_after_delay() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+
+    // The rescue window cannot open before the receiver's claim window closes
+    params.rescue_delay = TIMELOCK_SECS;
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidTimelock)));
+
+    params.rescue_delay = RESCUE_DELAY;
+    let contract_id = client.create_htlc_with_params(&params);
+    let result = client.try_extend_timelock(&contract_id, &(params.timelock + RESCUE_DELAY));
+    assert_eq!(result, Err(Ok(HTLCError::InvalidTimelock)));
+
+    env.ledger().with_mut(|l| l.timestamp = params.timelock);
+    let result = client.try_rescue_htlc(&contract_id);
+    assert_eq!(result, Err(Ok(HTLCError::TimelockNotExpired)));
+
+    env.ledger().with_mut(|l| l.timestamp += RESCUE_DELAY);
+    client.rescue_htlc(&contract_id);
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Refunded);
+    assert_eq!(
+        token::Client::new(&env, &token_address).balance(&sender),
+        AMOUNT + SAFETY_DEPOSIT
+    );
+}
+
+#[test]
+fn admin_rescue_skips_frozen_leg() {
+    let (env, sender, receiver, token_address, _) = setup();
+    let admin = Address::generate(&env);
+    let client = initialized(&env, &admin, false);
+    client.set_admin_rescue(&true);
+    assert!(client.get_config().admin_rescue);
+
+    let reward_address = env.register_contract(None, MockToken);
+    let reward_token = MockTokenClient::new(&env, &reward_address);
+    reward_token.mint(&sender, &REWARD_AMOUNT);
+    let (hashlock, _) = hashlock_pair(&env);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.basket.push_back(BasketLeg {
+        token: reward_address.clone(),
+        amount: REWARD_AMOUNT,
+    });
+    params.rescue_delay = RESCUE_DELAY;
+    let contract_id = client.create_htlc_with_params(&params);
+
+    // The reward issuer freezes transfers, which would make refund revert
+    reward_token.set_mode(&MockTokenMode::FailTransfers);
+    env.ledger().with_mut(|l| l.timestamp += RESCUE_DELAY);
+    client.rescue_htlc(&contract_id);
+
+    assert_eq!(
+        token::Client::new(&env, &token_address).balance(&sender),
+        AMOUNT + SAFETY_DEPOSIT
+    );
+    let (_, topics, data) = env.events().all().last().unwrap();
+    let expected_topics: Vec<Val> = (Symbol::new(&env, "HTLCRescued"), contract_id).into_val(&env);
+    assert_eq!(topics, expected_topics);
+    let (payload, _, _) = lifecycle_payload(&env, &data);
+    assert_eq!(
+        Vec::<Address>::try_from_val(&env, &payload).unwrap(),
+        Vec::from_array(&env, [reward_address.clone()])
+    );
+
+    // The frozen leg is no longer locked and stays sweepable by the admin
+    let accounting = client.get_token_accounting(&reward_address);
+    assert_eq!((accounting.locked, accounting.balance), (0, REWARD_AMOUNT));
+}
+
+//------------------------------------------------------------------
+//  Allowance funding
+//------------------------------------------------------------------
+#[test]
+fn create_htlc_from_allowance_spends_approval() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let token_client = token::Client::new(&env, &token_address);
+    let approved = 2 * (AMOUNT + SAFETY_DEPOSIT);
+    token_client.approve(
+        &sender,
+        &client.address,
+        &approved,
+        &(env.ledger().sequence() + DAY_IN_LEDGERS),
+    );
+
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_from_allowance(&params);
+    assert_eq!(
+        token_client.allowance(&sender, &client.address),
+        approved - (AMOUNT + SAFETY_DEPOSIT)
+    );
+    assert_eq!(
+        token_client.balance(&client.address),
+        AMOUNT + SAFETY_DEPOSIT
+    );
+
+    client.withdraw(&contract_id, &preimage);
+    assert_eq!(token_client.balance(&receiver), AMOUNT);
+}
+
+//------------------------------------------------------------------
+//  Dispute freeze
+//------------------------------------------------------------------
+const FREEZE_SECS: u64 = 1_800; // 30 minutes
+
+/// Creates an HTLC on a new deployment allowing admin freezes
+fn create_freezable(
+    env: &Env,
+    sender: &Address,
+    receiver: &Address,
+    token_address: &Address,
+) -> (HTLCContractClient<'static>, BytesN<32>, BytesN<32>) {
+    let client = initialized(env, &Address::generate(env), false);
+    client.set_max_freeze_duration(&FREEZE_SECS);
+    let (hashlock, preimage) = hashlock_pair(env);
+    let params = create_params(env, sender, receiver, token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+    (client, contract_id, preimage)
+}
+
+#[test]
+fn freeze_blocks_withdraw_for_bounded_time() {
+    let (env, sender, receiver, token_address, _) = setup();
+    let (client, contract_id, preimage) =
+        create_freezable(&env, &sender, &receiver, &token_address);
+
+    client.freeze(&contract_id);
+    assert_eq!(
+        client.get_htlc(&contract_id).frozen_until,
+        env.ledger().timestamp() + FREEZE_SECS
+    );
+    let result = client.try_withdraw(&contract_id, &preimage);
+    assert_eq!(result, Err(Ok(HTLCError::EscrowFrozen)));
+
+    // The freeze lapses on its own and cannot be renewed
+    env.ledger().with_mut(|l| l.timestamp += FREEZE_SECS);
+    let result = client.try_freeze(&contract_id);
+    assert_eq!(result, Err(Ok(HTLCError::AlreadyFrozen)));
+    client.withdraw(&contract_id, &preimage);
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Withdrawn);
+}
+
+#[test]
+fn unfreeze_lifts_freeze_early() {
+    let (env, sender, receiver, token_address, _) = setup();
+    let (client, contract_id, preimage) =
+        create_freezable(&env, &sender, &receiver, &token_address);
+
+    client.freeze(&contract_id);
+    client.unfreeze(&contract_id);
+    client.withdraw(&contract_id, &preimage);
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Withdrawn);
+}
+
+#[test]
+fn freeze_ends_before_timelock() {
+    let (env, sender, receiver, token_address, _) = setup();
+    let (client, contract_id, preimage) =
+        create_freezable(&env, &sender, &receiver, &token_address);
+    client.set_max_freeze_duration(&(2 * TIMELOCK_SECS));
+
+    // A freeze longer than the swap still leaves the receiver a window to withdraw
+    client.freeze(&contract_id);
+    let timelock = client.get_htlc(&contract_id).timelock;
+    let frozen_until = client.get_htlc(&contract_id).frozen_until;
+    assert_eq!(frozen_until, timelock - FREEZE_WITHDRAWAL_GRACE);
+    let result = client.try_refund(&contract_id);
+    assert_eq!(result, Err(Ok(HTLCError::TimelockNotExpired)));
+
+    env.ledger().with_mut(|l| l.timestamp = frozen_until);
+    client.withdraw(&contract_id, &preimage);
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Withdrawn);
+}
+
+#[test]
+fn freeze_rejected_inside_grace_window() {
+    let (env, sender, receiver, token_address, _) = setup();
+    let (client, contract_id, preimage) =
+        create_freezable(&env, &sender, &receiver, &token_address);
+
+    let timelock = client.get_htlc(&contract_id).timelock;
+    env.ledger()
+        .with_mut(|l| l.timestamp = timelock - FREEZE_WITHDRAWAL_GRACE);
+    let result = client.try_freeze(&contract_id);
+    assert_eq!(result, Err(Ok(HTLCError::FreezeTooLate)));
+    client.withdraw(&contract_id, &preimage);
+}
+
+#[test]
+fn freeze_disabled_without_duration() {
+    let (env, sender, receiver, token_address, _) = setup();
+    let (client, contract_id, _) = create_freezable(&env, &sender, &receiver, &token_address);
+    client.set_max_freeze_duration(&0);
+
+    let result = client.try_freeze(&contract_id);
+    assert_eq!(result, Err(Ok(HTLCError::FreezeDisabled)));
+}
+
+//------------------------------------------------------------------
+//  Alternate refund address
+//------------------------------------------------------------------
+#[test]
+fn refund_pays_principal_to_refund_to() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let recovery = Address::generate(&env);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.refund_to = recovery.clone();
+    let contract_id = client.create_htlc_with_params(&params);
+    assert_eq!(client.get_htlc(&contract_id).refund_to, recovery);
+
+    let token_client = token::Client::new(&env, &token_address);
+    let sender_balance = token_client.balance(&sender);
+    env.ledger().with_mut(|l| l.timestamp = params.timelock);
+    client.refund(&contract_id);
+
+    // The safety deposit still follows its usual routing
+    assert_eq!(token_client.balance(&recovery), AMOUNT);
+    assert_eq!(
+        token_client.balance(&sender),
+        sender_balance + SAFETY_DEPOSIT
+    );
+}
+
+#[test]
+fn cancel_by_agreement_pays_refund_to() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let recovery = Address::generate(&env);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.refund_to = recovery.clone();
+    let contract_id = client.create_htlc_with_params(&params);
+
+    client.cancel_by_agreement(&contract_id);
+    let token_client = token::Client::new(&env, &token_address);
+    assert_eq!(token_client.balance(&recovery), AMOUNT);
+    assert_eq!(token_client.balance(&receiver), 0);
+}
+
+//------------------------------------------------------------------
+//  Commit-reveal public withdrawal
+//------------------------------------------------------------------
+fn create_commit_reveal(
+    env: &Env,
+    sender: &Address,
+    receiver: &Address,
+    token_address: &Address,
+    client: &HTLCContractClient<'static>,
+) -> (BytesN<32>, BytesN<32>) {
+    let (hashlock, preimage) = hashlock_pair(env);
+    let mut params = create_params(env, sender, receiver, token_address, &hashlock);
+    params.public_withdrawal = env.ledger().timestamp() + EXCLUSIVE_SECS;
+    params.commit_reveal = true;
+    (client.create_htlc_with_params(&params), preimage)
+}
+
+#[test]
+fn commit_reveal_withdraw() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let resolver = Address::generate(&env);
+    let (contract_id, preimage) =
+        create_commit_reveal(&env, &sender, &receiver, &token_address, &client);
+
+    let commitment = client.get_withdraw_commitment(&preimage, &resolver);
+    client.commit_withdraw(&resolver, &contract_id, &commitment);
+
+    // The commitment must age before it can be revealed
+    env.ledger().with_mut(|l| l.timestamp += EXCLUSIVE_SECS);
+    let result = client.try_reveal_withdraw(&resolver, &contract_id, &preimage);
+    assert_eq!(result, Err(Ok(HTLCError::PublicWithdrawalNotStarted)));
+
+    env.ledger().with_mut(|l| l.sequence_number += 2);
+    client.reveal_withdraw(&resolver, &contract_id, &preimage);
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Withdrawn);
+
+    let token_client = token::Client::new(&env, &token_address);
+    assert_eq!(token_client.balance(&receiver), AMOUNT);
+    assert_eq!(token_client.balance(&resolver), SAFETY_DEPOSIT);
+}
+
+#[test]
+fn commit_reveal_blocks_front_running() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let resolver = Address::generate(&env);
+    let front_runner = Address::generate(&env);
+    let (contract_id, preimage) =
+        create_commit_reveal(&env, &sender, &receiver, &token_address, &client);
+    env.ledger().with_mut(|l| l.timestamp += EXCLUSIVE_SECS);
+
+    // The single-step public path is closed
+    let result = client.try_public_withdraw(&front_runner, &contract_id, &preimage);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidPublicWithdrawal)));
+
+    // Another caller's commitment is bound to its committer
+    let commitment = client.get_withdraw_commitment(&preimage, &resolver);
+    client.commit_withdraw(&front_runner, &contract_id, &commitment);
+    env.ledger().with_mut(|l| l.sequence_number += 2);
+    let result = client.try_reveal_withdraw(&front_runner, &contract_id, &preimage);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidPreimage)));
+
+    // A commitment made after seeing the preimage is still too young
+    let commitment = client.get_withdraw_commitment(&preimage, &front_runner);
+    client.commit_withdraw(&front_runner, &contract_id, &commitment);
+    let result = client.try_reveal_withdraw(&front_runner, &contract_id, &preimage);
+    assert_eq!(result, Err(Ok(HTLCError::PublicWithdrawalNotStarted)));
+
+    // Revealing without a commitment fails
+    let result = client.try_reveal_withdraw(&resolver, &contract_id, &preimage);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidPreimage)));
+}
+
+#[test]
+fn commit_reveal_requires_public_withdrawal() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.commit_reveal = true;
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidPublicWithdrawal)));
+
+    // HTLCs without commit-reveal do not accept commitments
+    params.commit_reveal = false;
+    let contract_id = client.create_htlc_with_params(&params);
+    let commitment = client.get_withdraw_commitment(&preimage, &receiver);
+    let result = client.try_commit_withdraw(&receiver, &contract_id, &commitment);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidPublicWithdrawal)));
+}
+
+//------------------------------------------------------------------
+//  Escrow memo
+//------------------------------------------------------------------
+#[test]
+fn memo_echoed_in_lifecycle_events() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let memo = Bytes::from_slice(&env, b"order-42");
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.memo = memo.clone();
+    let contract_id = client.create_htlc_with_params(&params);
+    assert_eq!(client.get_htlc(&contract_id).memo, memo);
+
+    let (_, _, data) = env.events().all().last().unwrap();
+    let (_, _, payload) = event_payload(&env, &data);
+    assert_eq!(
+        HTLCNewEvent::try_from_val(&env, &payload).unwrap().memo,
+        memo
+    );
+
+    client.withdraw(&contract_id, &preimage);
+    let (_, topics, data) = env.events().all().last().unwrap();
+    let expected_topics: Vec<Val> =
+        (Symbol::new(&env, "HTLCWithdraw"), contract_id.clone()).into_val(&env);
+    assert_eq!(topics, expected_topics);
+    let (payload, event_memo, _) = lifecycle_payload(&env, &data);
+    assert_eq!(
+        BytesN::<32>::try_from_val(&env, &payload).unwrap(),
+        preimage
+    );
+    assert_eq!(event_memo, memo);
+
+    env.ledger()
+        .with_mut(|l| l.timestamp += CLEANUP_GRACE_PERIOD);
+    client.cleanup(&contract_id);
+    let (_, _, data) = env.events().all().last().unwrap();
+    assert_eq!(lifecycle_payload(&env, &data).1, memo);
+}
+
+#[test]
+fn memo_too_long() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.memo = Bytes::from_array(&env, &[1u8; 65]);
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::MemoTooLong)));
+
+    params.memo = Bytes::from_array(&env, &[1u8; 64]);
+    assert!(client.try_create_htlc_with_params(&params).is_ok());
+}
+
+/// `error_spec` must publish every `HTLCError` under its own code and name
+#[test]
+fn error_spec_covers_every_error() {
+    let spec_name = |code| {
+        let error = InvokeError::Contract(code);
+        error_spec::HTLCError::try_from(error)
+            .map(|error| std::format!("{error:?}"))
+            .or_else(|_| {
+                error_spec::HTLCErrorExt::try_from(error).map(|error| std::format!("{error:?}"))
+            })
+            .ok()
+    };
+    let mut code = 1;
+    while let Ok(error) = HTLCError::try_from(InvokeError::Contract(code)) {
+        assert_eq!(spec_name(code), Some(std::format!("{error:?}")));
+        code += 1;
+    }
+    assert_eq!(spec_name(code), None);
+}
+
+//------------------------------------------------------------------
+//  Token interface validation
+//------------------------------------------------------------------
+#[contract]
+struct NotAToken;
+
+#[contractimpl]
+impl NotAToken {
+    pub fn ping(_env: Env) -> u32 {
+        1
+    }
+}
+
+#[test]
+fn create_with_non_token_contract() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let not_a_token = env.register_contract(None, NotAToken);
+
+    let mut params = create_params(&env, &sender, &receiver, &not_a_token, &hashlock);
+    params.safety_deposit_token = token_address.clone();
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidTokenContract)));
+
+    // The safety deposit token and basket legs are probed as well
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.safety_deposit_token = not_a_token.clone();
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidTokenContract)));
+
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.basket = Vec::from_array(
+        &env,
+        [BasketLeg {
+            token: not_a_token,
+            amount: AMOUNT,
+        }],
+    );
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidTokenContract)));
+}
+
+#[test]
+fn create_with_account_as_token() {
+    let (env, sender, receiver, _, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let account = Address::generate(&env);
+
+    let params = create_params(&env, &sender, &receiver, &account, &hashlock);
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidTokenContract)));
+}
+
+//------------------------------------------------------------------
+//  Vesting release
+//------------------------------------------------------------------
+const TRANCHES: u32 = 4;
+const TRANCHE_INTERVAL: u64 = 24 * 3_600; // 1 day
+
+fn create_vesting(
+    env: &Env,
+    sender: &Address,
+    receiver: &Address,
+    token_address: &Address,
+    client: &HTLCContractClient<'static>,
+) -> (BytesN<32>, BytesN<32>) {
+    let (hashlock, preimage) = hashlock_pair(env);
+    let mut params = create_params(env, sender, receiver, token_address, &hashlock);
+    params.vesting = VestingSchedule {
+        tranches: TRANCHES,
+        interval: TRANCHE_INTERVAL,
+    };
+    (client.create_htlc_with_params(&params), preimage)
+}
+
+#[test]
+fn vesting_releases_tranches_over_time() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let cold_wallet = Address::generate(&env);
+    let (contract_id, preimage) = create_vesting(&env, &sender, &receiver, &token_address, &client);
+    let tranche = AMOUNT / TRANCHES as i128;
+    let token_client = token::Client::new(&env, &token_address);
+
+    // The reveal pays the first tranche and fixes the recipient of the rest
+    client.withdraw_to(&contract_id, &preimage, &cold_wallet);
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Withdrawn);
+    assert_eq!(token_client.balance(&cold_wallet), tranche);
+    assert_eq!(token_client.balance(&sender), SAFETY_DEPOSIT);
+    assert_eq!(
+        client.get_vesting_progress(&contract_id),
+        Some(VestingProgress {
+            recipient: cold_wallet.clone(),
+            started_at: env.ledger().timestamp(),
+            released: 1,
+        })
+    );
+
+    let result = client.try_claim_tranche(&contract_id);
+    assert_eq!(result, Err(Ok(HTLCError::NothingVested)));
+
+    // Tranches unlocked since the last claim are paid together
+    env.ledger()
+        .with_mut(|l| l.timestamp += 2 * TRANCHE_INTERVAL);
+    assert_eq!(client.claim_tranche(&contract_id), 2 * tranche);
+    assert_eq!(token_client.balance(&cold_wallet), 3 * tranche);
+
+    env.ledger()
+        .with_mut(|l| l.timestamp += 10 * TRANCHE_INTERVAL);
+    assert_eq!(client.claim_tranche(&contract_id), AMOUNT - 3 * tranche);
+    assert_eq!(token_client.balance(&cold_wallet), AMOUNT);
+    assert_eq!(token_client.balance(&client.address), 0);
+    assert_eq!(client.get_vesting_progress(&contract_id), None);
+
+    let result = client.try_claim_tranche(&contract_id);
+    assert_eq!(result, Err(Ok(HTLCError::AlreadyWithdrawn)));
+}
+
+#[test]
+fn vesting_refundable_until_reveal() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (contract_id, _) = create_vesting(&env, &sender, &receiver, &token_address, &client);
+
+    let result = client.try_claim_tranche(&contract_id);
+    assert_eq!(result, Err(Ok(HTLCError::ContractNotCompleted)));
+
+    let timelock = client.get_htlc(&contract_id).timelock;
+    env.ledger().with_mut(|l| l.timestamp = timelock);
+    client.refund(&contract_id);
+    assert_eq!(
+        token::Client::new(&env, &token_address).balance(&sender),
+        AMOUNT + SAFETY_DEPOSIT
+    );
+
+    let result = client.try_claim_tranche(&contract_id);
+    assert_eq!(result, Err(Ok(HTLCError::AlreadyRefunded)));
+}
+
+#[test]
+fn vesting_blocks_cleanup_until_released() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (contract_id, preimage) = create_vesting(&env, &sender, &receiver, &token_address, &client);
+    client.withdraw(&contract_id, &preimage);
+
+    env.ledger()
+        .with_mut(|l| l.timestamp += CLEANUP_GRACE_PERIOD);
+    let result = client.try_cleanup(&contract_id);
+    assert_eq!(result, Err(Ok(HTLCError::ContractNotCompleted)));
+
+    client.claim_tranche(&contract_id);
+    client.cleanup(&contract_id);
+    assert!(!client.contract_exists(&contract_id));
+}
+
+#[test]
+fn vesting_schedule_validation() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+
+    params.vesting = VestingSchedule {
+        tranches: TRANCHES,
+        interval: 0,
+    };
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidVestingSchedule)));
+
+    params.vesting = VestingSchedule {
+        tranches: MAX_TRANCHES + 1,
+        interval: TRANCHE_INTERVAL,
+    };
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidVestingSchedule)));
+
+    // Every tranche must release at least one unit
+    params.vesting.tranches = TRANCHES;
+    params.amount = TRANCHES as i128 - 1;
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidVestingSchedule)));
+
+    // Partial fills pay per part and can't also vest
+    params.amount = AMOUNT;
+    params.parts = PARTS;
+    let result = client.try_create_htlc_with_params(&params);
+    assert_eq!(result, Err(Ok(HTLCError::InvalidVestingSchedule)));
+
+    let result = client.try_claim_tranche(&client.create_htlc_with_params(&create_params(
+        &env,
+        &sender,
+        &receiver,
+        &token_address,
+        &hashlock,
+    )));
+    assert_eq!(result, Err(Ok(HTLCError::NotVesting)));
+}
+
+//------------------------------------------------------------------
+//  Storage writes per state transition
+//------------------------------------------------------------------
+/// Keys of the HTLC contract's own storage entries the test host has recorded as written
+fn htlc_written_keys(env: &Env, client: &HTLCContractClient) -> std::vec::Vec<xdr::ScVal> {
+    let contract = xdr::ScAddress::try_from(client.address.clone()).unwrap();
+    let budget = env.host().budget_cloned();
+    env.host()
+        .with_mut_storage(|storage| {
+            Ok(storage
+                .footprint
+                .0
+                .iter(&budget)?
+                // The host's access type is not re-exported by the SDK
+                .filter(|(_, access)| std::format!("{access:?}") == "ReadWrite")
+                .filter_map(|(key, _)| match key.as_ref() {
+                    xdr::LedgerKey::ContractData(data) if data.contract == contract => {
+                        Some(data.key.clone())
+                    }
+                    _ => None,
+                })
+                .collect())
+        })
+        .unwrap()
+}
+
+#[test]
+fn withdraw_writes_htlc_entry_once() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+    let written = htlc_written_keys(&env, &client);
+
+    env.budget().reset_unlimited();
+    client.withdraw(&contract_id, &preimage);
+
+    // No reentrancy guard entry is written on top of the final state; the guard's extra
+    // writes cost about 19k CPU instructions and 4 KB of memory on this call
+    assert_eq!(htlc_written_keys(&env, &client), written);
+    assert!(env.budget().cpu_instruction_cost() < 691_000);
+    assert!(env.budget().memory_bytes_cost() < 98_000);
+}
+
+#[test]
+fn refund_writes_htlc_entry_once() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+    let written = htlc_written_keys(&env, &client);
+
+    env.ledger().with_mut(|l| l.timestamp = params.timelock);
+    env.budget().reset_unlimited();
+    client.refund(&contract_id);
+
+    // The guard's extra writes cost about 18k CPU instructions and 4 KB of memory here
+    assert_eq!(htlc_written_keys(&env, &client), written);
+    assert!(env.budget().cpu_instruction_cost() < 664_000);
+    assert!(env.budget().memory_bytes_cost() < 94_000);
+}
+
+//------------------------------------------------------------------
+//  Authorization paths
+//------------------------------------------------------------------
+/// Authorization tree signed for a call to `function` on `contract`
+fn invocation(
+    contract: &Address,
+    function: &str,
+    args: Vec<Val>,
+    sub_invocations: std::vec::Vec<AuthorizedInvocation>,
+) -> AuthorizedInvocation {
+    AuthorizedInvocation {
+        function: AuthorizedFunction::Contract((
+            contract.clone(),
+            Symbol::new(contract.env(), function),
+            args,
+        )),
+        sub_invocations,
+    }
+}
+
+/// Runs the ignored test `name` in a child process and asserts it fails. A failed
+/// `require_auth` panics inside the contract, which aborts the whole test process with this
+/// SDK, so unauthorized calls cannot be caught in-process.
+fn assert_fails_in_child(name: &str) {
+    let status = std::process::Command::new(std::env::current_exe().unwrap())
+        .args([name, "--exact", "--ignored", "--test-threads=1"])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .unwrap();
+    assert!(!status.success(), "{name} was expected to fail");
+}
+
+/// Replaces mocked authorizations with `address` signing just `function(args)`
+fn sign_only(env: &Env, address: &Address, contract: &Address, function: &str, args: Vec<Val>) {
+    env.mock_auths(&[MockAuth {
+        address,
+        invoke: &MockAuthInvoke {
+            contract,
+            fn_name: function,
+            args,
+            sub_invokes: &[],
+        },
+    }]);
+}
+
+#[test]
+fn create_requires_sender_auth() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, _) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let args: Vec<Val> = (params.clone(),).into_val(&env);
+    let transfer_args: Vec<Val> = (
+        sender.clone(),
+        client.address.clone(),
+        AMOUNT + SAFETY_DEPOSIT,
+    )
+        .into_val(&env);
+
+    // The sender signs the creation and the transfer it makes on their behalf
+    env.mock_auths(&[MockAuth {
+        address: &sender,
+        invoke: &MockAuthInvoke {
+            contract: &client.address,
+            fn_name: "create_htlc_with_params",
+            args: args.clone(),
+            sub_invokes: &[MockAuthInvoke {
+                contract: &token_address,
+                fn_name: "transfer",
+                args: transfer_args.clone(),
+                sub_invokes: &[],
+            }],
+        },
+    }]);
+    let contract_id = client.create_htlc_with_params(&params);
+    assert_eq!(
+        env.auths(),
+        std::vec![(
+            sender.clone(),
+            invocation(
+                &client.address,
+                "create_htlc_with_params",
+                args,
+                std::vec![invocation(
+                    &token_address,
+                    "transfer",
+                    transfer_args,
+                    std::vec![]
+                )],
+            )
+        )]
+    );
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Active);
+
+    assert_fails_in_child("test::create_unsigned");
+    assert_fails_in_child("test::create_signed_by_receiver");
+}
+
+/// Sets up the default HTLC parameters without mocking any authorization
+fn unsigned_create_setup() -> (Env, Address, HTLCContractClient<'static>, CreateParams) {
+    let (env, sender, receiver, token_address, client) = setup_in(new_env_without_snapshot());
+    let (hashlock, _) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    env.mock_auths(&[]);
+    (env, receiver, client, params)
+}
+
+#[test]
+#[ignore = "aborts the process; run by create_requires_sender_auth"]
+fn create_unsigned() {
+    let (_, _, client, params) = unsigned_create_setup();
+    client.create_htlc_with_params(&params);
+}
+
+#[test]
+#[ignore = "aborts the process; run by create_requires_sender_auth"]
+fn create_signed_by_receiver() {
+    let (env, receiver, client, params) = unsigned_create_setup();
+    let args = (params.clone(),).into_val(&env);
+    sign_only(
+        &env,
+        &receiver,
+        &client.address,
+        "create_htlc_with_params",
+        args,
+    );
+    client.create_htlc_with_params(&params);
+}
+
+/// Creates the default HTLC with every authorization mocked, then stops mocking
+fn create_then_require_auths(
+    env: Env,
+) -> (
+    Env,
+    Address,
+    Address,
+    HTLCContractClient<'static>,
+    BytesN<32>,
+    BytesN<32>,
+) {
+    let (env, sender, receiver, token_address, client) = setup_in(env);
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    let contract_id = client.create_htlc_with_params(&params);
+    env.mock_auths(&[]);
+    (env, sender, receiver, client, contract_id, preimage)
+}
+
+#[test]
+fn withdraw_requires_receiver_auth() {
+    let (env, _, receiver, client, contract_id, preimage) = create_then_require_auths(new_env());
+    let args: Vec<Val> = (contract_id.clone(), preimage.clone()).into_val(&env);
+
+    sign_only(&env, &receiver, &client.address, "withdraw", args.clone());
+    client.withdraw(&contract_id, &preimage);
+    assert_eq!(
+        env.auths(),
+        std::vec![(
+            receiver.clone(),
+            invocation(&client.address, "withdraw", args, std::vec![])
+        )]
+    );
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Withdrawn);
+
+    assert_fails_in_child("test::withdraw_unsigned");
+    assert_fails_in_child("test::withdraw_signed_by_sender");
+}
+
+#[test]
+#[ignore = "aborts the process; run by withdraw_requires_receiver_auth"]
+fn withdraw_unsigned() {
+    let (_, _, _, client, contract_id, preimage) =
+        create_then_require_auths(new_env_without_snapshot());
+    client.withdraw(&contract_id, &preimage);
+}
+
+#[test]
+#[ignore = "aborts the process; run by withdraw_requires_receiver_auth"]
+fn withdraw_signed_by_sender() {
+    let (env, sender, _, client, contract_id, preimage) =
+        create_then_require_auths(new_env_without_snapshot());
+    let args = (contract_id.clone(), preimage.clone()).into_val(&env);
+    sign_only(&env, &sender, &client.address, "withdraw", args);
+    client.withdraw(&contract_id, &preimage);
+}
+
+#[test]
+fn refund_requires_sender_auth() {
+    let (env, sender, _, client, contract_id, _) = create_then_require_auths(new_env());
+    env.ledger().with_mut(|l| l.timestamp += TIMELOCK_SECS);
+    let args: Vec<Val> = (contract_id.clone(),).into_val(&env);
+
+    sign_only(&env, &sender, &client.address, "refund", args.clone());
+    client.refund(&contract_id);
+    assert_eq!(
+        env.auths(),
+        std::vec![(
+            sender.clone(),
+            invocation(&client.address, "refund", args, std::vec![])
+        )]
+    );
+    assert_eq!(client.get_status(&contract_id), HTLCStatus::Refunded);
+
+    assert_fails_in_child("test::refund_unsigned");
+    assert_fails_in_child("test::refund_signed_by_receiver");
+}
+
+#[test]
+#[ignore = "aborts the process; run by refund_requires_sender_auth"]
+fn refund_unsigned() {
+    let (env, _, _, client, contract_id, _) = create_then_require_auths(new_env_without_snapshot());
+    env.ledger().with_mut(|l| l.timestamp += TIMELOCK_SECS);
+    client.refund(&contract_id);
+}
+
+#[test]
+#[ignore = "aborts the process; run by refund_requires_sender_auth"]
+fn refund_signed_by_receiver() {
+    let (env, _, receiver, client, contract_id, _) =
+        create_then_require_auths(new_env_without_snapshot());
+    env.ledger().with_mut(|l| l.timestamp += TIMELOCK_SECS);
+    let args = (contract_id.clone(),).into_val(&env);
+    sign_only(&env, &receiver, &client.address, "refund", args);
+    client.refund(&contract_id);
+}
+
+//------------------------------------------------------------------
+//  Budget benchmarks
+//------------------------------------------------------------------
+/// CPU, memory and ledger I/O of one contract call
+#[derive(Debug)]
+struct Cost {
+    cpu: u64,
+    memory: u64,
+    /// Sizes of every ledger entry the call touched, as XDR after the call
+    read_bytes: usize,
+    /// Sizes of the entries the call wrote
+    write_bytes: usize,
+}
+
+/// Growth over a recorded baseline a benchmark tolerates, in percent
+const BENCH_TOLERANCE_PCT: u64 = 5;
+
+/// Runs `call` with a fresh budget and footprint and returns what it cost
+fn measure(env: &Env, call: impl FnOnce()) -> Cost {
+    env.host()
+        .with_mut_storage(|storage| {
+            storage.footprint = Default::default();
+            Ok(())
+        })
+        .unwrap();
+    env.budget().reset_unlimited();
+    call();
+    let cpu = env.budget().cpu_instruction_cost();
+    let memory = env.budget().memory_bytes_cost();
+
+    let budget = env.host().budget_cloned();
+    let (read_bytes, write_bytes) = env
+        .host()
+        .with_mut_storage(|storage| {
+            let (mut read_bytes, mut write_bytes) = (0, 0);
+            for (key, access) in storage.footprint.0.iter(&budget)? {
+                let size = match storage.map.get::<Rc<xdr::LedgerKey>>(key, &budget)? {
+                    Some(Some((entry, _))) => {
+                        xdr::WriteXdr::to_xdr(entry.as_ref(), xdr::Limits::none())
+                            .unwrap()
+                            .len()
+                    }
+                    _ => 0,
+                };
+                read_bytes += size;
+                // The host's access type is not re-exported by the SDK
+                if std::format!("{access:?}") == "ReadWrite" {
+                    write_bytes += size;
+                }
+            }
+            Ok((read_bytes, write_bytes))
+        })
+        .unwrap();
+    Cost {
+        cpu,
+        memory,
+        read_bytes,
+        write_bytes,
+    }
+}
+
+/// Fails if any cost exceeds its baseline by more than `BENCH_TOLERANCE_PCT`; run with
+/// `--nocapture` to print the measured costs when updating baselines
+fn assert_within_baseline(name: &str, cost: Cost, baseline: Cost) {
+    std::println!("{name}: {cost:?}");
+    let limit = |value: u64| value * (100 + BENCH_TOLERANCE_PCT) / 100;
+    assert!(cost.cpu <= limit(baseline.cpu), "{name}: {cost:?}");
+    assert!(cost.memory <= limit(baseline.memory), "{name}: {cost:?}");
+    assert!(
+        cost.read_bytes as u64 <= limit(baseline.read_bytes as u64),
+        "{name}: {cost:?}"
+    );
+    assert!(
+        cost.write_bytes as u64 <= limit(baseline.write_bytes as u64),
+        "{name}: {cost:?}"
+    );
+}
+
+/// Creates, then withdraws or refunds, a plain HTLC with and without a safety deposit
+fn bench_single(refund: bool, safety_deposit: i128) -> (Cost, Cost) {
+    let (env, sender, receiver, token_address, client) = setup();
+    let (hashlock, preimage) = hashlock_pair(&env);
+    let mut params = create_params(&env, &sender, &receiver, &token_address, &hashlock);
+    params.safety_deposit = safety_deposit;
+
+    let mut contract_id = None;
+    let create = measure(&env, || {
+        contract_id = Some(client.create_htlc_with_params(&params));
+    });
+    let contract_id = contract_id.unwrap();
+    let settle = if refund {
+        env.ledger().with_mut(|l| l.timestamp = params.timelock);
+        measure(&env, || client.refund(&contract_id))
+    } else {
+        measure(&env, || client.withdraw(&contract_id, &preimage))
+    };
+    (create, settle)
+}
+
+#[test]
+fn bench_create_withdraw_refund() {
+    let cases = [
+        // (name, refund, safety deposit, create baseline, settle baseline)
+        (
+            "withdraw",
+            false,
+            SAFETY_DEPOSIT,
+            Cost {
+                cpu: 734_606,
+                memory: 102_026,
+                read_bytes: 4_148,
+                write_bytes: 3_536,
+            },
+            Cost {
+                cpu: 693_763,
+                memory: 99_174,
+                read_bytes: 3_824,
+                write_bytes: 3_212,
+            },
+        ),
+        (
+            "withdraw without deposit",
+            false,
+            0,
+            Cost {
+                cpu: 712_801,
+                memory: 100_678,
+                read_bytes: 4_148,
+                write_bytes: 3_536,
+            },
+            Cost {
+                cpu: 509_949,
+                memory: 76_730,
+                read_bytes: 3_600,
+                write_bytes: 2_988,
+            },
+        ),
+        (
+            "refund",
+            true,
+            SAFETY_DEPOSIT,
+            Cost {
+                cpu: 734_606,
+                memory: 102_026,
+                read_bytes: 4_148,
+                write_bytes: 3_536,
+            },
+            Cost {
+                cpu: 668_390,
+                memory: 93_528,
+                read_bytes: 3_508,
+                write_bytes: 2_988,
+            },
+        ),
+        (
+            "refund without deposit",
+            true,
+            0,
+            Cost {
+                cpu: 712_801,
+                memory: 100_678,
+                read_bytes: 4_148,
+                write_bytes: 3_536,
+            },
+            Cost {
+                cpu: 492_046,
+                memory: 72_199,
+                read_bytes: 3_508,
+                write_bytes: 2_988,
+            },
+        ),
+    ];
+    for (name, refund, safety_deposit, create_baseline, settle_baseline) in cases {
+        let (create, settle) = bench_single(refund, safety_deposit);
+        assert_within_baseline(&std::format!("create for {name}"), create, create_baseline);
+        assert_within_baseline(name, settle, settle_baseline);
+    }
+}
+
+#[test]
+fn bench_partial_fill() {
+    let (env, sender, receiver, token_address, client) = setup();
+    let secrets = partial_secrets(&env);
+    let (root, proof) = merkle_root_and_proof(&env, &secrets, 0);
+    let timelock = env.ledger().timestamp() + TIMELOCK_SECS;
+    let mut contract_id = None;
+    let create = measure(&env, || {
+        contract_id = Some(client.create_htlc_partial(
+            &sender,
+            &receiver,
+            &AMOUNT,
+            &token_address,
+            &root,
+            &PARTS,
+            &timelock,
+            &SAFETY_DEPOSIT,
+        ));
+    });
+    let contract_id = contract_id.unwrap();
+    let fill = measure(&env, || {
+        client.withdraw_partial(
+            &contract_id,
+            &secrets.get(0).unwrap(),
+            &0,
+            &proof,
+            &(AMOUNT / PARTS as i128),
+        )
+    });
+
+    assert_within_baseline(
+        "create partial",
+        create,
+        Cost {
+            cpu: 710_391,
+            memory: 100_194,
+            read_bytes: 4_148,
+            write_bytes: 3_536,
+        },
+    );
+    assert_within_baseline(
+        "first partial fill",
+        fill,
+        Cost {
+            cpu: 523_279,
+            memory: 74_532,
+            read_bytes: 3_600,
+            write_bytes: 2_988,
+        },
+    );
+}
+
+#[test]
+fn bench_batches() {
+    let cases = [
+        // (batch size, create baseline, withdraw baseline)
+        (
+            1,
+            Cost {
+                cpu: 720_693,
+                memory: 101_476,
+                read_bytes: 4_148,
+                write_bytes: 3_536,
+            },
+            Cost {
+                cpu: 780_045,
+                memory: 107_261,
+                read_bytes: 3_824,
+                write_bytes: 3_212,
+            },
+        ),
+        (
+            4,
+            Cost {
+                cpu: 2_988_700,
+                memory: 402_928,
+                read_bytes: 10_820,
+                write_bytes: 10_208,
+            },
+            Cost {
+                cpu: 3_020_617,
+                memory: 422_993,
+                read_bytes: 9_704,
+                write_bytes: 9_092,
+            },
+        ),
+        (
+            8,
+            Cost {
+                cpu: 6_294_489,
+                memory: 884_160,
+                read_bytes: 19_716,
+                write_bytes: 19_104,
+            },
+            Cost {
+                cpu: 6_289_614,
+                memory: 952_231,
+                read_bytes: 17_544,
+                write_bytes: 16_932,
+            },
+        ),
+    ];
+    for (size, create_baseline, withdraw_baseline) in cases {
+        let (env, sender, receiver, token_address, client) = setup();
+        token::StellarAssetClient::new(&env, &token_address)
+            .mint(&sender, &((size - 1) * (AMOUNT + SAFETY_DEPOSIT)));
+
+        let mut params = Vec::new(&env);
+        let mut preimages = std::vec::Vec::new();
+        for i in 0..size {
+            let preimage = BytesN::from_array(&env, &[i as u8 + 1; 32]);
+            let hashlock = env.crypto().sha256(&preimage.clone().into());
+            params.push_back(create_params(
+                &env,
+                &sender,
+                &receiver,
+                &token_address,
+                &hashlock,
+            ));
+            preimages.push(preimage);
+        }
+        let mut contract_ids = None;
+        let create = measure(&env, || {
+            contract_ids = Some(client.create_htlc_batch(&params));
+        });
+        let mut withdrawals = Vec::new(&env);
+        for (contract_id, preimage) in contract_ids.unwrap().iter().zip(preimages) {
+            withdrawals.push_back((contract_id, preimage));
+        }
+        let withdraw = measure(&env, || client.withdraw_batch(&withdrawals));
+
+        assert_within_baseline(
+            &std::format!("create batch of {size}"),
+            create,
+            create_baseline,
+        );
+        assert_within_baseline(
+            &std::format!("withdraw batch of {size}"),
+            withdraw,
+            withdraw_baseline,
+        );
+    }
+}
+
+//------------------------------------------------------------------
+//  Balance conservation
+//------------------------------------------------------------------
+/// Deterministic xorshift generator driving the mixed workload
+struct Xorshift(u64);
+
+impl Xorshift {
+    /// A value in `0..bound`
+    fn below(&mut self, bound: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % bound
+    }
+}
+
+/// An escrow of the workload, with the secrets opening it
+struct Escrow {
+    id: BytesN<32>,
+    preimage: BytesN<32>,
+    secrets: Vec<BytesN<32>>,
+    htlc: HTLCData,
+}
+
+const WORKLOAD_ESCROWS: usize = 300;
+
+#[test]
+fn mixed_workload_conserves_balances() {
+    let env = new_env_without_snapshot();
+    env.budget().reset_unlimited();
+    let issuer = Address::generate(&env);
+    let tokens: std::vec::Vec<Address> = (0..3)
+        .map(|_| env.register_stellar_asset_contract(issuer.clone()))
+        .collect();
+    let senders: std::vec::Vec<Address> = (0..4).map(|_| Address::generate(&env)).collect();
+    let receivers: std::vec::Vec<Address> = (0..4).map(|_| Address::generate(&env)).collect();
+    let (resolver, collector, referral) = (
+        Address::generate(&env),
+        Address::generate(&env),
+        Address::generate(&env),
+    );
+    let minted = 1_000_000 * AMOUNT;
+    for token in &tokens {
+        for sender in &senders {
+            token::StellarAssetClient::new(&env, token).mint(sender, &minted);
+        }
+    }
+    let client = initialized(&env, &Address::generate(&env), false);
+    client.set_protocol_fee(&FEE_BPS, &collector);
+
+    let mut rng = Xorshift(0x9e37_79b9_7f4a_7c15);
+    let mut escrows: std::vec::Vec<Escrow> = std::vec::Vec::new();
+    while escrows.len() < WORKLOAD_ESCROWS {
+        let now = env.ledger().timestamp();
+        let roll = rng.below(100);
+        if roll < 40 || escrows.is_empty() {
+            let n = escrows.len() as u64;
+            let preimage = BytesN::from_array(&env, &sha256_seed(&env, n));
+            let token = &tokens[rng.below(3) as usize];
+            let mut params = create_params(
+                &env,
+                &senders[rng.below(4) as usize],
+                &receivers[rng.below(4) as usize],
+                token,
+                &env.crypto().sha256(&preimage.clone().into()),
+            );
+            params.amount = PARTS as i128 * (1 + rng.below(25_000_000) as i128);
+            params.safety_deposit = rng.below(10_000_000) as i128;
+            params.safety_deposit_token = tokens[rng.below(3) as usize].clone();
+            params.timelock = now + TIMELOCK_SECS / 2 + rng.below(TIMELOCK_SECS);
+            if rng.below(2) == 0 {
+                params.public_withdrawal = now + EXCLUSIVE_SECS;
+            }
+            if rng.below(2) == 0 {
+                params.public_cancellation = params.timelock + EXCLUSIVE_SECS;
+            }
+            if rng.below(3) == 0 {
+                params.referral = referral.clone();
+                params.referral_bps = 1 + rng.below(100) as u32;
+            }
+            let mut secrets = Vec::new(&env);
+            if rng.below(4) == 0 {
+                for i in 0..=PARTS {
+                    let seed = sha256_seed(&env, 1_000_000 + n * 10 + i as u64);
+                    secrets.push_back(BytesN::from_array(&env, &seed));
+                }
+                params.parts = PARTS;
+                params.hashlock = merkle_root_and_proof(&env, &secrets, 0).0;
+            }
+            let id = client.create_htlc_with_params(&params);
+            escrows.push(Escrow {
+                htlc: client.get_htlc(&id),
+                id,
+                preimage,
+                secrets,
+            });
+        } else if roll < 85 {
+            let picked = rng.below(escrows.len() as u64) as usize;
+            let escrow = &mut escrows[picked];
+            let htlc = &escrow.htlc;
+            let _ = match (roll, htlc.parts) {
+                (40..=54, 0) => client.try_withdraw(&escrow.id, &escrow.preimage).is_ok(),
+                (40..=54, _) => {
+                    // Fill the next quarter, or complete the order with the last secret
+                    let quarter = htlc.amount / PARTS as i128;
+                    let (index, fill_amount) = if htlc.filled_amount + quarter < htlc.amount {
+                        ((htlc.filled_amount / quarter) as u32, quarter)
+                    } else {
+                        (PARTS, htlc.amount - htlc.filled_amount)
+                    };
+                    let (_, proof) = merkle_root_and_proof(&env, &escrow.secrets, index);
+                    client
+                        .try_withdraw_partial(
+                            &escrow.id,
+                            &escrow.secrets.get(index).unwrap(),
+                            &index,
+                            &proof,
+                            &fill_amount,
+                        )
+                        .is_ok()
+                }
+                (55..=64, 0) => client
+                    .try_public_withdraw(&resolver, &escrow.id, &escrow.preimage)
+                    .is_ok(),
+                (65..=74, _) => client.try_refund(&escrow.id).is_ok(),
+                (75..=84, _) => client.try_public_refund(&resolver, &escrow.id).is_ok(),
+                _ => false,
+            };
+            escrow.htlc = client.get_htlc(&escrow.id);
+        } else {
+            env.ledger()
+                .with_mut(|l| l.timestamp += rng.below(TIMELOCK_SECS / 2));
+        }
+
+        // The contract holds exactly what its active escrows still owe
+        for token in &tokens {
+            let owed: i128 = escrows
+                .iter()
+                .filter(|escrow| escrow.htlc.status == HTLCStatus::Active)
+                .map(|escrow| {
+                    let htlc = &escrow.htlc;
+                    let mut owed = 0;
+                    if htlc.token_address == *token {
+                        owed += htlc.amount - htlc.filled_amount;
+                    }
+                    if htlc.safety_deposit_token == *token {
+                        owed += htlc.safety_deposit;
+                    }
+                    owed
+                })
+                .sum();
+            let held = token::Client::new(&env, token).balance(&client.address);
+            assert_eq!(held, owed);
+            assert_eq!(client.get_token_accounting(token).locked, owed);
+        }
+    }
+
+    // Every escrow's final state matches what the workload last read
+    for escrow in &escrows {
+        assert_eq!(client.get_htlc(&escrow.id), escrow.htlc);
+    }
+
+    // Once every remaining escrow is refunded the contract holds nothing, and no token was
+    // created or lost along the way
+    env.ledger().with_mut(|l| l.timestamp += 2 * TIMELOCK_SECS);
+    for escrow in escrows
+        .iter()
+        .filter(|escrow| escrow.htlc.status == HTLCStatus::Active)
+    {
+        client.refund(&escrow.id);
+    }
+    let holders: std::vec::Vec<&Address> = senders
+        .iter()
+        .chain(&receivers)
+        .chain([&resolver, &collector, &referral, &client.address])
+        .collect();
+    for token in &tokens {
+        let token_client = token::Client::new(&env, token);
+        let total: i128 = holders
+            .iter()
+            .map(|holder| token_client.balance(holder))
+            .sum();
+        assert_eq!(total, minted * senders.len() as i128);
+        assert_eq!(token_client.balance(&client.address), 0);
+        assert_eq!(client.get_token_accounting(token).locked, 0);
+    }
+}
+
+/// A distinct 32-byte secret per seed
+fn sha256_seed(env: &Env, seed: u64) -> [u8; 32] {
+    env.crypto()
+        .sha256(&Bytes::from_array(env, &seed.to_be_bytes()))
+        .to_array()
+}
+
+//------------------------------------------------------------------
+//  Lifecycle state machine
+//------------------------------------------------------------------
+/// Random sequences of lifecycle calls checked against a model of each HTLC; in its own
+/// module as the proptest prelude shadows names used above
+mod lifecycle {
+    use super::*;
+    use proptest::prelude::*;
+
+    const MAX_OPS: usize = 24;
+
+    #[derive(Clone, Debug)]
+    enum Op {
+        Create {
+            secs: u64,
+        },
+        Withdraw {
+            htlc: prop::sample::Index,
+            right_secret: bool,
+        },
+        Refund {
+            htlc: prop::sample::Index,
+        },
+        Extend {
+            htlc: prop::sample::Index,
+            secs: u64,
+        },
+        Cleanup {
+            htlc: prop::sample::Index,
+        },
+        Advance {
+            secs: u64,
+        },
+    }
+
+    fn op() -> impl Strategy<Value = Op> {
+        prop_oneof![
+            (60..2 * TIMELOCK_SECS).prop_map(|secs| Op::Create { secs }),
+            (any::<prop::sample::Index>(), any::<bool>())
+                .prop_map(|(htlc, right_secret)| Op::Withdraw { htlc, right_secret }),
+            any::<prop::sample::Index>().prop_map(|htlc| Op::Refund { htlc }),
+            (any::<prop::sample::Index>(), 0..TIMELOCK_SECS)
+                .prop_map(|(htlc, secs)| Op::Extend { htlc, secs }),
+            any::<prop::sample::Index>().prop_map(|htlc| Op::Cleanup { htlc }),
+            // Mostly short hops, sometimes past the cleanup grace period
+            prop_oneof![
+                3 => 0..2 * TIMELOCK_SECS,
+                1 => Just(CLEANUP_GRACE_PERIOD),
+            ]
+            .prop_map(|secs| Op::Advance { secs }),
+        ]
+    }
+
+    /// What the test expects of one HTLC
+    struct Model {
+        id: BytesN<32>,
+        preimage: BytesN<32>,
+        timelock: u64,
+        status: HTLCStatus,
+        completed_at: u64,
+        cleaned: bool,
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn lifecycle_preserves_invariants(ops in prop::collection::vec(op(), 1..MAX_OPS)) {
+            let (env, sender, receiver, token_address, client) =
+                setup_in(new_env_without_snapshot());
+            let token_client = token::Client::new(&env, &token_address);
+            let per_htlc = AMOUNT + SAFETY_DEPOSIT;
+            token::StellarAssetClient::new(&env, &token_address)
+                .mint(&sender, &(per_htlc * (MAX_OPS as i128 - 1)));
+            let total = per_htlc * MAX_OPS as i128;
+            let mut htlcs: std::vec::Vec<Model> = std::vec::Vec::new();
+
+            for op in ops {
+                let now = env.ledger().timestamp();
+                let count = htlcs.len();
+                match op {
+                    Op::Create { secs } => {
+                        let mut seed = [1u8; 32];
+                        seed[0] = htlcs.len() as u8;
+                        let preimage = BytesN::from_array(&env, &seed);
+                        let hashlock = env.crypto().sha256(&preimage.clone().into());
+                        let mut params =
+                            create_params(&env, &sender, &receiver, &token_address, &hashlock);
+                        params.timelock = now + secs;
+                        let id = client.create_htlc_with_params(&params);
+                        htlcs.push(Model {
+                            id,
+                            preimage,
+                            timelock: params.timelock,
+                            status: HTLCStatus::Active,
+                            completed_at: 0,
+                            cleaned: false,
+                        });
+                    }
+                    Op::Withdraw { htlc, right_secret } if count > 0 => {
+                        let model = &mut htlcs[htlc.index(count)];
+                        let preimage = if right_secret {
+                            model.preimage.clone()
+                        } else {
+                            BytesN::from_array(&env, &[0xff; 32])
+                        };
+                        let expected = right_secret
+                            && !model.cleaned
+                            && model.status == HTLCStatus::Active
+                            && now < model.timelock;
+                        let result = client.try_withdraw(&model.id, &preimage);
+                        prop_assert_eq!(result.is_ok(), expected);
+                        if expected {
+                            model.status = HTLCStatus::Withdrawn;
+                            model.completed_at = now;
+                        }
+                    }
+                    Op::Refund { htlc } if count > 0 => {
+                        let model = &mut htlcs[htlc.index(count)];
+                        let expected = !model.cleaned
+                            && model.status == HTLCStatus::Active
+                            && now >= model.timelock;
+                        prop_assert_eq!(client.try_refund(&model.id).is_ok(), expected);
+                        if expected {
+                            model.status = HTLCStatus::Refunded;
+                            model.completed_at = now;
+                        }
+                    }
+                    Op::Extend { htlc, secs } if count > 0 => {
+                        let model = &mut htlcs[htlc.index(count)];
+                        let new_timelock = model.timelock + secs;
+                        let expected = !model.cleaned
+                            && model.status == HTLCStatus::Active
+                            && new_timelock > model.timelock
+                            && new_timelock > now;
+                        let result = client.try_extend_timelock(&model.id, &new_timelock);
+                        prop_assert_eq!(result.is_ok(), expected);
+                        if expected {
+                            model.timelock = new_timelock;
+                        }
+                    }
+                    Op::Cleanup { htlc } if count > 0 => {
+                        let model = &mut htlcs[htlc.index(count)];
+                        let expected = !model.cleaned
+                            && model.status != HTLCStatus::Active
+                            && now >= model.completed_at + CLEANUP_GRACE_PERIOD;
+                        prop_assert_eq!(client.try_cleanup(&model.id).is_ok(), expected);
+                        model.cleaned |= expected;
+                    }
+                    Op::Advance { secs } => env.ledger().with_mut(|l| l.timestamp += secs),
+                    _ => {}
+                }
+
+                // Terminal states are absorbing and cleaned-up HTLCs stay gone
+                for model in &htlcs {
+                    match client.get_htlc_opt(&model.id) {
+                        Some(htlc) => {
+                            prop_assert!(!model.cleaned);
+                            prop_assert_eq!(htlc.status, model.status.clone());
+                            prop_assert_eq!(htlc.timelock, model.timelock);
+                        }
+                        None => prop_assert!(model.cleaned),
+                    }
+                }
+
+                // Tokens are conserved and the contract holds exactly the active escrows
+                let active = htlcs
+                    .iter()
+                    .filter(|model| model.status == HTLCStatus::Active)
+                    .count() as i128;
+                let held = token_client.balance(&client.address);
+                prop_assert_eq!(held, active * per_htlc);
+                prop_assert_eq!(client.get_token_accounting(&token_address).locked, held);
+                prop_assert_eq!(
+                    token_client.balance(&sender) + token_client.balance(&receiver) + held,
+                    total
+                );
+                prop_assert_eq!(
+                    token_client.balance(&receiver),
+                    htlcs
+                        .iter()
+                        .filter(|model| model.status == HTLCStatus::Withdrawn)
+                        .count() as i128
+                        * AMOUNT
+                );
+            }
+        }
+    }
+}
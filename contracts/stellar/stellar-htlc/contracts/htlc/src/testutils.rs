@@ -0,0 +1,254 @@
+#![cfg(any(test, feature = "testutils"))]
+//! Fixtures shared by this crate's tests and by integrators testing against the HTLC
+//! contract: a funded environment, default creation parameters and a mock token that can
+//! misbehave on demand.
+
+use crate::{
+    CreateParams, CrossChainMetadata, HTLCContract, HTLCContractClient, HashAlgo, VestingSchedule,
+};
+use soroban_sdk::{
+    contract, contracterror, contractimpl, contracttype,
+    testutils::{Address as _, EnvTestConfig},
+    token, Address, Bytes, BytesN, Env, Symbol, Val, Vec,
+};
+
+/// Amount locked by `create_params`, 100 XLM at 7 decimals
+pub const AMOUNT: i128 = 1_000_000_000;
+
+/// Safety deposit posted by `create_params`, 10 XLM
+pub const SAFETY_DEPOSIT: i128 = 100_000_000;
+
+/// Seconds from creation to the timelock in `create_params`
+pub const TIMELOCK_SECS: u64 = 3_600;
+
+/// Creates an environment with every authorization mocked
+pub fn new_env() -> Env {
+    let e = Env::default();
+    e.mock_all_auths(); // disable signature checks
+    e
+}
+
+/// Like `new_env`, but writes no test snapshot on drop; for property tests and fuzzers
+/// running many short-lived environments
+pub fn new_env_without_snapshot() -> Env {
+    let e = Env::new_with_config(EnvTestConfig {
+        capture_snapshot_at_drop: false,
+    });
+    e.mock_all_auths();
+    e
+}
+
+/// Registers the HTLC contract and a Stellar asset, and funds a sender with `AMOUNT` plus
+/// `SAFETY_DEPOSIT` of it. Returns the environment, sender, receiver, asset and client.
+pub fn setup() -> (Env, Address, Address, Address, HTLCContractClient<'static>) {
+    setup_in(new_env())
+}
+
+/// `setup` in a given environment
+pub fn setup_in(env: Env) -> (Env, Address, Address, Address, HTLCContractClient<'static>) {
+    let sender = Address::generate(&env);
+    let receiver = Address::generate(&env);
+
+    // Create a mock token contract
+    let token_address = env.register_stellar_asset_contract(sender.clone());
+
+    // Register HTLC contract
+    let htlc_contract_id = env.register_contract(None, HTLCContract);
+    let client = HTLCContractClient::new(&env, &htlc_contract_id);
+
+    // Mint tokens to sender for testing
+    let token_admin = token::StellarAssetClient::new(&env, &token_address);
+    token_admin.mint(&sender, &(AMOUNT + SAFETY_DEPOSIT));
+
+    (env, sender, receiver, token_address, client)
+}
+
+/// Salt `register_htlc` deploys the contract with, to be passed to `initialize`
+pub const DEPLOY_SALT: [u8; 32] = [7; 32];
+
+/// Registers the HTLC contract at the address `deployer` deploys it to with `DEPLOY_SALT`,
+/// so that `deployer` may `initialize` it
+pub fn register_htlc(env: &Env, deployer: &Address) -> HTLCContractClient<'static> {
+    let address = env
+        .deployer()
+        .with_address(deployer.clone(), BytesN::from_array(env, &DEPLOY_SALT))
+        .deployed_address();
+    HTLCContractClient::new(env, &env.register_contract(Some(&address), HTLCContract))
+}
+
+/// An HTLC contract deployed and initialized by `admin`
+pub fn initialized(
+    env: &Env,
+    admin: &Address,
+    permissionless: bool,
+) -> HTLCContractClient<'static> {
+    let client = register_htlc(env, admin);
+    client.initialize(
+        admin,
+        &permissionless,
+        &BytesN::from_array(env, &DEPLOY_SALT),
+    );
+    client
+}
+
+/// Parameters of a plain single-secret HTLC locking `AMOUNT` with a `SAFETY_DEPOSIT` in
+/// the same token, expiring `TIMELOCK_SECS` from now
+pub fn create_params(
+    env: &Env,
+    sender: &Address,
+    receiver: &Address,
+    token_address: &Address,
+    hashlock: &BytesN<32>,
+) -> CreateParams {
+    CreateParams {
+        sender: sender.clone(),
+        receiver: receiver.clone(),
+        amount: AMOUNT,
+        token_address: token_address.clone(),
+        hashlock: hashlock.clone(),
+        hash_algo: HashAlgo::Sha256,
+        timelock: env.ledger().timestamp() + TIMELOCK_SECS,
+        safety_deposit: SAFETY_DEPOSIT,
+        safety_deposit_token: token_address.clone(),
+        parts: 0,
+        public_withdrawal: 0,
+        public_cancellation: 0,
+        order_hash: BytesN::from_array(env, &[0; 32]),
+        resolvers_only: false,
+        metadata: CrossChainMetadata::none(env),
+        basket: Vec::new(env),
+        safety_deposit_payer: sender.clone(),
+        salt: BytesN::from_array(env, &[0; 32]),
+        allow_reuse: false,
+        taker: receiver.clone(),
+        hashlocks: Vec::new(env),
+        threshold: 0,
+        callback: sender.clone(),
+        callback_data: Bytes::new(env),
+        extension: Bytes::new(env),
+        rescue_delay: 0,
+        refund_to: sender.clone(),
+        commit_reveal: false,
+        memo: Bytes::new(env),
+        referral: sender.clone(),
+        referral_bps: 0,
+        use_deposit_policy: false,
+        vesting: VestingSchedule::none(),
+    }
+}
+
+/// A fixed SHA-256 hashlock and the preimage opening it
+pub fn hashlock_pair(env: &Env) -> (BytesN<32>, BytesN<32>) {
+    let preimage = BytesN::from_array(env, &[42u8; 32]);
+    let hashlock: BytesN<32> = env.crypto().sha256(&preimage.clone().into());
+    (hashlock, preimage)
+}
+
+/// Misbehavior of a `MockToken`, applied to every transfer
+#[derive(Clone, Debug, PartialEq)]
+#[contracttype]
+pub enum MockTokenMode {
+    /// Moves exactly the requested amount
+    Honest,
+    /// Rejects every transfer with `MockTokenError::TransferFailed`
+    FailTransfers,
+    /// Burns this share of every transfer, in basis points
+    FeeOnTransfer(u32),
+    /// Moves the funds, then calls `function(args)` on this contract once, e.g. to try
+    /// re-entering the HTLC mid-payout
+    Reentrant(Address, Symbol, Vec<Val>),
+}
+
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u32)]
+pub enum MockTokenError {
+    TransferFailed = 1,
+}
+
+#[derive(Clone)]
+#[contracttype]
+enum MockTokenKey {
+    Mode,
+    Balance(Address),
+    /// Whether the last re-entrant call succeeded
+    Reentered,
+}
+
+/// Minimal token implementing what the HTLC contract calls, with a configurable
+/// `MockTokenMode`
+#[contract]
+pub struct MockToken;
+
+#[contractimpl]
+impl MockToken {
+    pub fn set_mode(env: Env, mode: MockTokenMode) {
+        env.storage().instance().set(&MockTokenKey::Mode, &mode);
+    }
+
+    pub fn mint(env: Env, to: Address, amount: i128) {
+        let balance = Self::balance(env.clone(), to.clone());
+        env.storage()
+            .persistent()
+            .set(&MockTokenKey::Balance(to), &(balance + amount));
+    }
+
+    pub fn balance(env: Env, id: Address) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&MockTokenKey::Balance(id))
+            .unwrap_or(0)
+    }
+
+    pub fn transfer(
+        env: Env,
+        from: Address,
+        to: Address,
+        amount: i128,
+    ) -> Result<(), MockTokenError> {
+        from.require_auth();
+        let mode = env
+            .storage()
+            .instance()
+            .get(&MockTokenKey::Mode)
+            .unwrap_or(MockTokenMode::Honest);
+        if mode == MockTokenMode::FailTransfers {
+            return Err(MockTokenError::TransferFailed);
+        }
+
+        let from_balance = Self::balance(env.clone(), from.clone());
+        env.storage()
+            .persistent()
+            .set(&MockTokenKey::Balance(from), &(from_balance - amount));
+        let received = match mode {
+            MockTokenMode::FeeOnTransfer(bps) => amount - amount * bps as i128 / 10_000,
+            _ => amount,
+        };
+        Self::mint(env.clone(), to, received);
+
+        // Re-enter once, recording the outcome instead of failing the transfer
+        if let MockTokenMode::Reentrant(contract, function, args) = mode {
+            env.storage()
+                .instance()
+                .set(&MockTokenKey::Mode, &MockTokenMode::Honest);
+            let result =
+                env.try_invoke_contract::<Val, soroban_sdk::Error>(&contract, &function, args);
+            env.storage()
+                .instance()
+                .set(&MockTokenKey::Reentered, &matches!(result, Ok(Ok(_))));
+        }
+        Ok(())
+    }
+
+    /// Whether the last re-entrant call of a `Reentrant` transfer succeeded
+    pub fn reentered(env: Env) -> bool {
+        env.storage()
+            .instance()
+            .get(&MockTokenKey::Reentered)
+            .unwrap_or(false)
+    }
+
+    pub fn decimals(_env: Env) -> u32 {
+        7
+    }
+}
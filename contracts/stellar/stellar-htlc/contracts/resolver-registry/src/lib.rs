@@ -0,0 +1,239 @@
+#![no_std]
+use soroban_sdk::{
+    contract, contracterror, contractimpl, contracttype, token, Address, Env, Symbol,
+};
+
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum RegistryError {
+    AlreadyInitialized = 1,
+    NotInitialized = 2,
+    InvalidAmount = 3,
+    InsufficientBond = 4,
+    NothingToWithdraw = 5,
+    UnbondingNotFinished = 6,
+}
+
+/// Approximate number of ledgers closed per day (5 s per ledger)
+const DAY_IN_LEDGERS: u32 = 17_280;
+
+/// Ledgers a bond entry is kept alive for after each change
+const BOND_TTL: u32 = 30 * DAY_IN_LEDGERS;
+
+#[derive(Clone)]
+#[contracttype]
+pub enum DataKey {
+    Config,
+    Bond(Address),
+}
+
+/// Registry configuration, set on initialization
+#[derive(Clone, Debug, PartialEq)]
+#[contracttype]
+pub struct Config {
+    pub admin: Address,
+    /// Token resolvers bond in
+    pub bond_token: Address,
+    /// Stake a resolver needs to count as bonded
+    pub min_bond: i128,
+    /// Seconds unstaked funds stay locked before they can be withdrawn
+    pub unbonding_delay: u64,
+}
+
+/// A resolver's stake
+#[derive(Clone, Debug, PartialEq)]
+#[contracttype]
+pub struct Bond {
+    /// Stake counting towards eligibility
+    pub staked: i128,
+    /// Unstaked funds waiting out the unbonding delay
+    pub unbonding: i128,
+    /// Timestamp the unbonding funds can be withdrawn from
+    pub unlock_at: u64,
+}
+
+#[contract]
+pub struct ResolverRegistry;
+
+#[contractimpl]
+impl ResolverRegistry {
+    /// Sets the admin, bond token, minimum bond and unbonding delay
+    pub fn initialize(
+        env: Env,
+        admin: Address,
+        bond_token: Address,
+        min_bond: i128,
+        unbonding_delay: u64,
+    ) -> Result<(), RegistryError> {
+        if env.storage().instance().has(&DataKey::Config) {
+            return Err(RegistryError::AlreadyInitialized);
+        }
+
+        if min_bond <= 0 {
+            return Err(RegistryError::InvalidAmount);
+        }
+
+        env.storage().instance().set(
+            &DataKey::Config,
+            &Config {
+                admin,
+                bond_token,
+                min_bond,
+                unbonding_delay,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Changes the stake a resolver needs to count as bonded; existing bonds below the new
+    /// minimum stop counting immediately
+    pub fn set_min_bond(env: Env, min_bond: i128) -> Result<(), RegistryError> {
+        let mut config = Self::get_config(env.clone())?;
+        config.admin.require_auth();
+
+        if min_bond <= 0 {
+            return Err(RegistryError::InvalidAmount);
+        }
+
+        config.min_bond = min_bond;
+        env.storage().instance().set(&DataKey::Config, &config);
+
+        env.events()
+            .publish((Symbol::new(&env, "MinBondSet"),), min_bond);
+
+        Ok(())
+    }
+
+    /// Adds to a resolver's stake, transferring the bond token from the resolver
+    pub fn stake(env: Env, resolver: Address, amount: i128) -> Result<Bond, RegistryError> {
+        let config = Self::get_config(env.clone())?;
+
+        // Authorization check
+        resolver.require_auth();
+
+        if amount <= 0 {
+            return Err(RegistryError::InvalidAmount);
+        }
+
+        token::Client::new(&env, &config.bond_token).transfer(
+            &resolver,
+            &env.current_contract_address(),
+            &amount,
+        );
+
+        let mut bond = Self::get_bond(env.clone(), resolver.clone());
+        bond.staked += amount;
+        Self::save_bond(&env, &resolver, &bond);
+
+        env.events().publish(
+            (Symbol::new(&env, "Staked"), resolver),
+            (amount, bond.staked),
+        );
+
+        Ok(bond)
+    }
+
+    /// Moves part of a resolver's stake into unbonding; it stops counting at once and can
+    /// be withdrawn after the unbonding delay, which restarts with every unstake
+    pub fn unstake(env: Env, resolver: Address, amount: i128) -> Result<Bond, RegistryError> {
+        let config = Self::get_config(env.clone())?;
+
+        // Authorization check
+        resolver.require_auth();
+
+        let mut bond = Self::get_bond(env.clone(), resolver.clone());
+        if amount <= 0 {
+            return Err(RegistryError::InvalidAmount);
+        }
+        if amount > bond.staked {
+            return Err(RegistryError::InsufficientBond);
+        }
+
+        bond.staked -= amount;
+        bond.unbonding += amount;
+        bond.unlock_at = env.ledger().timestamp() + config.unbonding_delay;
+        Self::save_bond(&env, &resolver, &bond);
+
+        env.events().publish(
+            (Symbol::new(&env, "Unstaked"), resolver),
+            (amount, bond.unlock_at),
+        );
+
+        Ok(bond)
+    }
+
+    /// Pays out a resolver's unbonded funds once the unbonding delay has passed, returning
+    /// the amount withdrawn
+    pub fn withdraw(env: Env, resolver: Address) -> Result<i128, RegistryError> {
+        let config = Self::get_config(env.clone())?;
+
+        // Authorization check
+        resolver.require_auth();
+
+        let mut bond = Self::get_bond(env.clone(), resolver.clone());
+        if bond.unbonding == 0 {
+            return Err(RegistryError::NothingToWithdraw);
+        }
+        if env.ledger().timestamp() < bond.unlock_at {
+            return Err(RegistryError::UnbondingNotFinished);
+        }
+
+        // Record the new balance before moving funds
+        let amount = bond.unbonding;
+        bond.unbonding = 0;
+        Self::save_bond(&env, &resolver, &bond);
+
+        token::Client::new(&env, &config.bond_token).transfer(
+            &env.current_contract_address(),
+            &resolver,
+            &amount,
+        );
+
+        env.events()
+            .publish((Symbol::new(&env, "BondWithdrawn"), resolver), amount);
+
+        Ok(amount)
+    }
+
+    /// Checks if a resolver's stake meets the minimum bond; this is the view the HTLC
+    /// contract consults for exclusive-window fills
+    pub fn is_bonded(env: Env, resolver: Address) -> bool {
+        match Self::get_config(env.clone()) {
+            Ok(config) => Self::get_bond(env, resolver).staked >= config.min_bond,
+            Err(_) => false,
+        }
+    }
+
+    /// Gets a resolver's stake, empty if it never staked
+    pub fn get_bond(env: Env, resolver: Address) -> Bond {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Bond(resolver))
+            .unwrap_or(Bond {
+                staked: 0,
+                unbonding: 0,
+                unlock_at: 0,
+            })
+    }
+
+    /// Gets the registry configuration
+    pub fn get_config(env: Env) -> Result<Config, RegistryError> {
+        env.storage()
+            .instance()
+            .get(&DataKey::Config)
+            .ok_or(RegistryError::NotInitialized)
+    }
+
+    // Private helper functions
+    fn save_bond(env: &Env, resolver: &Address, bond: &Bond) {
+        let key = DataKey::Bond(resolver.clone());
+        env.storage().persistent().set(&key, bond);
+        env.storage()
+            .persistent()
+            .extend_ttl(&key, BOND_TTL - DAY_IN_LEDGERS, BOND_TTL);
+    }
+}
+
+mod test;
@@ -0,0 +1,107 @@
+#![cfg(test)]
+
+use super::*;
+use soroban_sdk::{
+    testutils::{Address as _, Ledger},
+    token, Address, Env,
+};
+
+const MIN_BOND: i128 = 1_000_000_000; // 100 XLM (7 decimals)
+const UNBONDING_DELAY: u64 = 7 * 24 * 3_600; // 1 week
+
+/// Deploys an initialized registry and a resolver holding twice the minimum bond
+fn setup() -> (Env, Address, Address, ResolverRegistryClient<'static>) {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let resolver = Address::generate(&env);
+    let bond_token = env.register_stellar_asset_contract(admin.clone());
+    token::StellarAssetClient::new(&env, &bond_token).mint(&resolver, &(2 * MIN_BOND));
+
+    let registry_id = env.register_contract(None, ResolverRegistry);
+    let client = ResolverRegistryClient::new(&env, &registry_id);
+    client.initialize(&admin, &bond_token, &MIN_BOND, &UNBONDING_DELAY);
+
+    (env, resolver, bond_token, client)
+}
+
+#[test]
+fn stake_makes_resolver_bonded() {
+    let (env, resolver, bond_token, client) = setup();
+    assert!(!client.is_bonded(&resolver));
+
+    client.stake(&resolver, &(MIN_BOND - 1));
+    assert!(!client.is_bonded(&resolver));
+
+    let bond = client.stake(&resolver, &1);
+    assert_eq!(bond.staked, MIN_BOND);
+    assert!(client.is_bonded(&resolver));
+
+    let token_client = token::Client::new(&env, &bond_token);
+    assert_eq!(token_client.balance(&client.address), MIN_BOND);
+    assert_eq!(token_client.balance(&resolver), MIN_BOND);
+}
+
+#[test]
+fn unstake_waits_out_unbonding_delay() {
+    let (env, resolver, bond_token, client) = setup();
+    client.stake(&resolver, &MIN_BOND);
+
+    // Unstaked funds stop counting at once
+    let bond = client.unstake(&resolver, &(MIN_BOND / 2));
+    assert!(!client.is_bonded(&resolver));
+    assert_eq!(bond.unbonding, MIN_BOND / 2);
+    assert_eq!(bond.unlock_at, env.ledger().timestamp() + UNBONDING_DELAY);
+
+    let result = client.try_withdraw(&resolver);
+    assert_eq!(result, Err(Ok(RegistryError::UnbondingNotFinished)));
+
+    env.ledger().with_mut(|l| l.timestamp = bond.unlock_at);
+    assert_eq!(client.withdraw(&resolver), MIN_BOND / 2);
+    assert_eq!(
+        token::Client::new(&env, &bond_token).balance(&resolver),
+        MIN_BOND + MIN_BOND / 2
+    );
+
+    let result = client.try_withdraw(&resolver);
+    assert_eq!(result, Err(Ok(RegistryError::NothingToWithdraw)));
+}
+
+#[test]
+fn unstake_more_than_staked() {
+    let (_, resolver, _, client) = setup();
+    client.stake(&resolver, &MIN_BOND);
+
+    let result = client.try_unstake(&resolver, &(MIN_BOND + 1));
+    assert_eq!(result, Err(Ok(RegistryError::InsufficientBond)));
+    let result = client.try_unstake(&resolver, &0);
+    assert_eq!(result, Err(Ok(RegistryError::InvalidAmount)));
+}
+
+#[test]
+fn min_bond_change_applies_to_existing_bonds() {
+    let (_, resolver, _, client) = setup();
+    client.stake(&resolver, &MIN_BOND);
+    assert!(client.is_bonded(&resolver));
+
+    client.set_min_bond(&(2 * MIN_BOND));
+    assert!(!client.is_bonded(&resolver));
+    assert_eq!(client.get_config().min_bond, 2 * MIN_BOND);
+
+    let result = client.try_set_min_bond(&0);
+    assert_eq!(result, Err(Ok(RegistryError::InvalidAmount)));
+}
+
+#[test]
+fn initialize_twice() {
+    let (env, _, bond_token, client) = setup();
+
+    let result = client.try_initialize(
+        &Address::generate(&env),
+        &bond_token,
+        &MIN_BOND,
+        &UNBONDING_DELAY,
+    );
+    assert_eq!(result, Err(Ok(RegistryError::AlreadyInitialized)));
+}
@@ -0,0 +1,362 @@
+//! The Dutch auction of 1inch Fusion+ orders, priced the way the settlement extension
+//! prices fills.
+//!
+//! An auction raises the rate a resolver pays above the order's rate: a fill of the whole
+//! order takes `taking_amount * (1 + rate_bump / 10^7)` from the resolver. The bump starts
+//! at `initial_rate_bump`, moves linearly through the curve's points and reaches 0 at
+//! `start_time + duration`. The gas bump is subtracted from it. It grows with the block's
+//! base fee, so a resolver's gas cost in an expensive block is paid for by the maker.
+//!
+//! The integer arithmetic and rounding are those of the settlement contract and the
+//! Fusion SDK's `AuctionCalculator`, so the amounts match theirs to the unit. That lets the
+//! Stellar leg of a swap lock exactly what the EVM leg's auction asks for.
+
+use std::fmt;
+
+/// A rate bump of `RATE_BUMP_DENOMINATOR` doubles the rate
+pub const RATE_BUMP_DENOMINATOR: u64 = 10_000_000;
+/// `GasCost::gas_price_estimate` is in units of 1/1000 gwei, a base fee in wei
+pub const GAS_PRICE_BASE: u128 = 1_000_000;
+
+const MAX_U24: u64 = (1 << 24) - 1;
+/// Gas cost, start time, duration and initial rate bump
+const HEADER_LEN: usize = 17;
+const POINT_LEN: usize = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuctionError {
+    /// Encoded details of this length are not a header followed by whole points
+    BadLength(usize),
+    /// The field does not fit its width in the encoding
+    OutOfRange(&'static str),
+    /// An order amount is zero
+    ZeroAmount,
+    /// The resulting amount exceeds `u128`
+    Overflow,
+}
+
+impl fmt::Display for AuctionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuctionError::BadLength(len) => {
+                write!(f, "auction details of {len} bytes are malformed")
+            }
+            AuctionError::OutOfRange(field) => write!(f, "{field} is out of range"),
+            AuctionError::ZeroAmount => f.write_str("order amounts must be non-zero"),
+            AuctionError::Overflow => f.write_str("auction amount overflows u128"),
+        }
+    }
+}
+
+impl std::error::Error for AuctionError {}
+
+/// A point of the curve: the bump reached `delay` seconds after the previous point, or
+/// after the start for the first one
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct AuctionPoint {
+    /// Bump in `RATE_BUMP_DENOMINATOR` units; 24 bits
+    pub rate_bump: u32,
+    pub delay: u16,
+}
+
+/// What the maker allows for the resolver's gas. Either field at 0 disables the gas bump.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct GasCost {
+    /// Rate bump the gas costs at `gas_price_estimate`; 24 bits
+    pub gas_bump_estimate: u32,
+    /// Base fee the order was quoted at, in units of 1/1000 gwei
+    pub gas_price_estimate: u32,
+}
+
+/// The auction of one order, as carried in its extension's auction details
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct AuctionDetails {
+    /// Unix time; 32 bits
+    pub start_time: u64,
+    /// Seconds until the bump reaches 0; 24 bits
+    pub duration: u64,
+    /// Bump up to `start_time`; 24 bits
+    pub initial_rate_bump: u32,
+    pub points: Vec<AuctionPoint>,
+    pub gas_cost: GasCost,
+}
+
+/// The amounts an order was signed with
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct OrderAmounts {
+    pub making_amount: u128,
+    pub taking_amount: u128,
+}
+
+impl AuctionDetails {
+    /// A curve falling straight from `initial_rate_bump` to 0, without a gas bump
+    pub fn linear(start_time: u64, duration: u64, initial_rate_bump: u32) -> Self {
+        AuctionDetails {
+            start_time,
+            duration,
+            initial_rate_bump,
+            ..AuctionDetails::default()
+        }
+    }
+
+    /// The linear curve asking `start_amount` at `start_time` for an order taking
+    /// `end_amount`, like the Stellar contract's `AuctionOrder`
+    pub fn from_amounts(
+        start_amount: u128,
+        end_amount: u128,
+        start_time: u64,
+        duration: u64,
+    ) -> Result<Self, AuctionError> {
+        let bump = initial_rate_bump(start_amount, end_amount)?;
+        let bump = u32::try_from(bump)
+            .ok()
+            .filter(|bump| u64::from(*bump) <= MAX_U24)
+            .ok_or(AuctionError::OutOfRange("initial_rate_bump"))?;
+        Ok(AuctionDetails::linear(start_time, duration, bump))
+    }
+
+    pub fn end_time(&self) -> u64 {
+        self.start_time.saturating_add(self.duration)
+    }
+
+    /// The curve's bump at `time`, before the gas bump
+    pub fn auction_bump(&self, time: u64) -> u64 {
+        let end_time = self.end_time();
+        if time <= self.start_time {
+            return self.initial_rate_bump.into();
+        }
+        if time >= end_time {
+            return 0;
+        }
+        let mut point_time = self.start_time;
+        let mut rate_bump = u64::from(self.initial_rate_bump);
+        for point in &self.points {
+            let next_time = point_time + u64::from(point.delay);
+            let next_bump = u64::from(point.rate_bump);
+            if time <= next_time {
+                // `time` is past `point_time`, so the segment is at least a second long
+                let bump = (time - point_time) as u128 * next_bump as u128
+                    + (next_time - time) as u128 * rate_bump as u128;
+                return (bump / (next_time - point_time) as u128) as u64;
+            }
+            point_time = next_time;
+            rate_bump = next_bump;
+        }
+        ((end_time - time) as u128 * rate_bump as u128 / (end_time - point_time) as u128) as u64
+    }
+
+    /// Bump the resolver's gas costs in a block with `base_fee` wei, 0 without a base fee
+    pub fn gas_bump(&self, base_fee: u128) -> u64 {
+        let GasCost {
+            gas_bump_estimate,
+            gas_price_estimate,
+        } = self.gas_cost;
+        if gas_bump_estimate == 0 || gas_price_estimate == 0 || base_fee == 0 {
+            return 0;
+        }
+        let bump = u128::from(gas_bump_estimate).saturating_mul(base_fee)
+            / u128::from(gas_price_estimate)
+            / GAS_PRICE_BASE;
+        u64::try_from(bump).unwrap_or(u64::MAX)
+    }
+
+    /// The bump a fill at `time` in a block with `base_fee` pays
+    pub fn rate_bump(&self, time: u64, base_fee: u128) -> u64 {
+        self.auction_bump(time)
+            .saturating_sub(self.gas_bump(base_fee))
+    }
+
+    /// What a fill of `making_amount` takes from the resolver, rounded up
+    pub fn taking_amount(
+        &self,
+        order: OrderAmounts,
+        making_amount: u128,
+        time: u64,
+        base_fee: u128,
+    ) -> Result<u128, AuctionError> {
+        order.check()?;
+        let bump = RATE_BUMP_DENOMINATOR + self.rate_bump(time, base_fee);
+        let numerator = U256::mul(order.taking_amount, making_amount).mul_u64(bump)?;
+        let denominator = order
+            .making_amount
+            .checked_mul(RATE_BUMP_DENOMINATOR.into())
+            .ok_or(AuctionError::Overflow)?;
+        numerator.div(denominator, true)
+    }
+
+    /// What a resolver paying `taking_amount` fills of the order, rounded down
+    pub fn making_amount(
+        &self,
+        order: OrderAmounts,
+        taking_amount: u128,
+        time: u64,
+        base_fee: u128,
+    ) -> Result<u128, AuctionError> {
+        order.check()?;
+        let bump = RATE_BUMP_DENOMINATOR + self.rate_bump(time, base_fee);
+        let numerator =
+            U256::mul(order.making_amount, taking_amount).mul_u64(RATE_BUMP_DENOMINATOR)?;
+        let denominator = order
+            .taking_amount
+            .checked_mul(bump.into())
+            .ok_or(AuctionError::Overflow)?;
+        numerator.div(denominator, false)
+    }
+
+    /// The details as they are laid out in the order's extension: the 3-byte gas bump
+    /// estimate, 4-byte gas price estimate, 4-byte start time, 3-byte duration, 3-byte
+    /// initial rate bump, then each point's 3-byte rate bump and 2-byte delay, big-endian
+    pub fn encode(&self) -> Result<Vec<u8>, AuctionError> {
+        let fits = |value: u64, max: u64, field| {
+            if value <= max {
+                Ok(value)
+            } else {
+                Err(AuctionError::OutOfRange(field))
+            }
+        };
+        let mut out = Vec::with_capacity(HEADER_LEN + POINT_LEN * self.points.len());
+        let gas_bump = fits(
+            self.gas_cost.gas_bump_estimate.into(),
+            MAX_U24,
+            "gas_bump_estimate",
+        )?;
+        out.extend_from_slice(&gas_bump.to_be_bytes()[5..]);
+        out.extend_from_slice(&self.gas_cost.gas_price_estimate.to_be_bytes());
+        let start_time = fits(self.start_time, u32::MAX.into(), "start_time")?;
+        out.extend_from_slice(&start_time.to_be_bytes()[4..]);
+        let duration = fits(self.duration, MAX_U24, "duration")?;
+        out.extend_from_slice(&duration.to_be_bytes()[5..]);
+        let initial = fits(self.initial_rate_bump.into(), MAX_U24, "initial_rate_bump")?;
+        out.extend_from_slice(&initial.to_be_bytes()[5..]);
+        for point in &self.points {
+            let bump = fits(point.rate_bump.into(), MAX_U24, "rate_bump")?;
+            out.extend_from_slice(&bump.to_be_bytes()[5..]);
+            out.extend_from_slice(&point.delay.to_be_bytes());
+        }
+        Ok(out)
+    }
+
+    /// Reads details laid out as `encode` writes them
+    pub fn decode(bytes: &[u8]) -> Result<Self, AuctionError> {
+        if bytes.len() < HEADER_LEN || (bytes.len() - HEADER_LEN) % POINT_LEN != 0 {
+            return Err(AuctionError::BadLength(bytes.len()));
+        }
+        let points = bytes[HEADER_LEN..]
+            .chunks(POINT_LEN)
+            .map(|point| AuctionPoint {
+                rate_bump: be(&point[..3]) as u32,
+                delay: be(&point[3..]) as u16,
+            })
+            .collect();
+        Ok(AuctionDetails {
+            start_time: be(&bytes[7..11]),
+            duration: be(&bytes[11..14]),
+            initial_rate_bump: be(&bytes[14..17]) as u32,
+            points,
+            gas_cost: GasCost {
+                gas_bump_estimate: be(&bytes[..3]) as u32,
+                gas_price_estimate: be(&bytes[3..7]) as u32,
+            },
+        })
+    }
+}
+
+/// Big-endian integer of up to 8 bytes
+fn be(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(0, |value, byte| value << 8 | u64::from(*byte))
+}
+
+impl OrderAmounts {
+    fn check(&self) -> Result<(), AuctionError> {
+        if self.making_amount == 0 || self.taking_amount == 0 {
+            return Err(AuctionError::ZeroAmount);
+        }
+        Ok(())
+    }
+}
+
+/// `taking_amount` raised by `rate_bump`, rounded up
+pub fn bumped_amount(taking_amount: u128, rate_bump: u64) -> Result<u128, AuctionError> {
+    U256::mul(taking_amount, (RATE_BUMP_DENOMINATOR + rate_bump).into())
+        .div(RATE_BUMP_DENOMINATOR.into(), true)
+}
+
+/// The initial bump of an auction starting at `start_amount` for an order taking
+/// `end_amount`
+pub fn initial_rate_bump(start_amount: u128, end_amount: u128) -> Result<u64, AuctionError> {
+    if end_amount == 0 {
+        return Err(AuctionError::ZeroAmount);
+    }
+    let rate = U256::mul(start_amount, RATE_BUMP_DENOMINATOR.into()).div(end_amount, false)?;
+    let bump = rate.saturating_sub(RATE_BUMP_DENOMINATOR.into());
+    u64::try_from(bump).map_err(|_| AuctionError::OutOfRange("initial_rate_bump"))
+}
+
+/// The `gas_bump_estimate` of gas costing `gas_cost` in the taker asset, for an order
+/// taking `end_taking_amount`
+pub fn gas_bump_estimate(end_taking_amount: u128, gas_cost: u128) -> Result<u64, AuctionError> {
+    if end_taking_amount == 0 {
+        return Err(AuctionError::ZeroAmount);
+    }
+    let bump = U256::mul(gas_cost, RATE_BUMP_DENOMINATOR.into()).div(end_taking_amount, false)?;
+    u64::try_from(bump).map_err(|_| AuctionError::OutOfRange("gas_bump_estimate"))
+}
+
+/// Unsigned 256-bit intermediate of `mul_div`, as the contract's `Math.mulDiv` keeps
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct U256 {
+    high: u128,
+    low: u128,
+}
+
+impl U256 {
+    fn mul(a: u128, b: u128) -> Self {
+        const LOW: u128 = u64::MAX as u128;
+        let (a_high, a_low) = (a >> 64, a & LOW);
+        let (b_high, b_low) = (b >> 64, b & LOW);
+        let low = a_low * b_low;
+        let cross_a = a_high * b_low;
+        let cross_b = a_low * b_high;
+        let (cross, cross_carry) = cross_a.overflowing_add(cross_b);
+        let (low, low_carry) = low.overflowing_add(cross << 64);
+        let high =
+            a_high * b_high + (cross >> 64) + ((cross_carry as u128) << 64) + low_carry as u128;
+        U256 { high, low }
+    }
+
+    fn mul_u64(self, factor: u64) -> Result<Self, AuctionError> {
+        let low = U256::mul(self.low, factor.into());
+        let high = self
+            .high
+            .checked_mul(factor.into())
+            .and_then(|high| high.checked_add(low.high))
+            .ok_or(AuctionError::Overflow)?;
+        Ok(U256 { high, low: low.low })
+    }
+
+    /// `self / divisor`, rounded up if `ceil`; the quotient must fit `u128`
+    fn div(self, divisor: u128, ceil: bool) -> Result<u128, AuctionError> {
+        if self.high >= divisor {
+            return Err(AuctionError::Overflow);
+        }
+        // Long division, one bit of the low half at a time; the remainder stays below
+        // `divisor`, so doubling it can carry out of `u128` at most once
+        let (mut quotient, mut remainder) = (0u128, self.high);
+        for bit in (0..128).rev() {
+            let carry = remainder >> 127;
+            remainder = remainder << 1 | (self.low >> bit) & 1;
+            if carry == 1 || remainder >= divisor {
+                remainder = remainder.wrapping_sub(divisor);
+                quotient |= 1 << bit;
+            }
+        }
+        if ceil && remainder != 0 {
+            quotient = quotient.checked_add(1).ok_or(AuctionError::Overflow)?;
+        }
+        Ok(quotient)
+    }
+}
+
+mod test;
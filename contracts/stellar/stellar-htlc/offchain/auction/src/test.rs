@@ -0,0 +1,210 @@
+#![cfg(test)]
+
+use crate::*;
+
+/// 1 ETH for 3000 USDC
+const ORDER: OrderAmounts = OrderAmounts {
+    making_amount: 1_000_000_000_000_000_000,
+    taking_amount: 3_000_000_000,
+};
+
+fn curve() -> AuctionDetails {
+    AuctionDetails {
+        start_time: 0,
+        duration: 1_000,
+        initial_rate_bump: 2_000_000,
+        points: vec![
+            AuctionPoint {
+                rate_bump: 1_000_000,
+                delay: 100,
+            },
+            AuctionPoint {
+                rate_bump: 500_000,
+                delay: 200,
+            },
+        ],
+        gas_cost: GasCost::default(),
+    }
+}
+
+//------------------------------------------------------------------
+//  Rate bumps
+//------------------------------------------------------------------
+#[test]
+fn linear_curve_falls_to_zero() {
+    let details = AuctionDetails::linear(1_000, 100, 1_000_000);
+    assert_eq!(details.end_time(), 1_100);
+    assert_eq!(details.auction_bump(0), 1_000_000);
+    assert_eq!(details.auction_bump(1_000), 1_000_000);
+    assert_eq!(details.auction_bump(1_025), 750_000);
+    assert_eq!(details.auction_bump(1_099), 10_000);
+    assert_eq!(details.auction_bump(1_100), 0);
+    assert_eq!(details.auction_bump(u64::MAX), 0);
+}
+
+#[test]
+fn bump_moves_through_points() {
+    let details = curve();
+    assert_eq!(details.auction_bump(50), 1_500_000);
+    assert_eq!(details.auction_bump(100), 1_000_000);
+    assert_eq!(details.auction_bump(200), 750_000);
+    assert_eq!(details.auction_bump(300), 500_000);
+    // Past the last point the bump falls to 0 by the end of the auction
+    assert_eq!(details.auction_bump(650), 250_000);
+    assert_eq!(details.auction_bump(1_000), 0);
+}
+
+#[test]
+fn gas_bump_is_subtracted() {
+    let details = AuctionDetails {
+        gas_cost: GasCost {
+            gas_bump_estimate: 100_000,
+            // 10 gwei
+            gas_price_estimate: 10_000,
+        },
+        ..AuctionDetails::linear(0, 100, 1_000_000)
+    };
+    let base_fee = 20_000_000_000;
+    assert_eq!(details.gas_bump(base_fee), 200_000);
+    assert_eq!(details.rate_bump(50, base_fee), 300_000);
+    assert_eq!(details.rate_bump(90, base_fee), 0);
+    assert_eq!(details.rate_bump(50, 0), 500_000);
+
+    let unpriced = AuctionDetails {
+        gas_cost: GasCost {
+            gas_bump_estimate: 100_000,
+            gas_price_estimate: 0,
+        },
+        ..details
+    };
+    assert_eq!(unpriced.rate_bump(50, base_fee), 500_000);
+}
+
+//------------------------------------------------------------------
+//  Amounts
+//------------------------------------------------------------------
+#[test]
+fn taking_rounds_up_and_making_down() {
+    let details = AuctionDetails::linear(0, 100, 1_000_000);
+    let third = ORDER.making_amount / 3;
+    assert_eq!(
+        details.taking_amount(ORDER, ORDER.making_amount, 0, 0),
+        Ok(3_300_000_000)
+    );
+    assert_eq!(details.taking_amount(ORDER, third, 0, 0), Ok(1_100_000_000));
+    assert_eq!(
+        details.making_amount(ORDER, 1_100_000_000, 0, 0),
+        Ok(333_333_333_333_333_333)
+    );
+    assert_eq!(
+        details.making_amount(ORDER, 3_300_000_000, 0, 0),
+        Ok(ORDER.making_amount)
+    );
+    assert_eq!(
+        details.taking_amount(ORDER, ORDER.making_amount, 100, 0),
+        Ok(ORDER.taking_amount)
+    );
+
+    let empty = OrderAmounts {
+        making_amount: 0,
+        ..ORDER
+    };
+    assert_eq!(
+        details.taking_amount(empty, 1, 0, 0),
+        Err(AuctionError::ZeroAmount)
+    );
+}
+
+#[test]
+fn amounts_keep_full_precision() {
+    let details = AuctionDetails::linear(0, 100, 0);
+    let order = OrderAmounts {
+        making_amount: 10u128.pow(30),
+        taking_amount: 2 * 10u128.pow(30),
+    };
+    // The intermediate products are far beyond `u128`
+    assert_eq!(
+        details.taking_amount(order, 10u128.pow(30), 0, 0),
+        Ok(2 * 10u128.pow(30))
+    );
+    assert_eq!(
+        details.making_amount(order, 2 * 10u128.pow(30), 0, 0),
+        Ok(10u128.pow(30))
+    );
+    assert_eq!(
+        details.taking_amount(order, u128::MAX, 0, 0),
+        Err(AuctionError::Overflow)
+    );
+
+    assert_eq!(bumped_amount(10, 1), Ok(11));
+    assert_eq!(bumped_amount(20_000_000, 5_000_000), Ok(30_000_000));
+    assert_eq!(bumped_amount(u128::MAX, 1), Err(AuctionError::Overflow));
+}
+
+#[test]
+fn curves_follow_from_amounts() {
+    assert_eq!(initial_rate_bump(15_000_000, 10_000_000), Ok(5_000_000));
+    assert_eq!(initial_rate_bump(1, 3), Ok(0));
+    assert_eq!(initial_rate_bump(1, 0), Err(AuctionError::ZeroAmount));
+    assert_eq!(gas_bump_estimate(3_000_000_000, 3_000_000), Ok(10_000));
+
+    let details = AuctionDetails::from_amounts(1_500, 1_000, 10, 100).unwrap();
+    assert_eq!(details, AuctionDetails::linear(10, 100, 5_000_000));
+    let bumped = |time| bumped_amount(1_000, details.rate_bump(time, 0)).unwrap();
+    assert_eq!(bumped(10), 1_500);
+    assert_eq!(bumped(35), 1_375);
+    assert_eq!(bumped(110), 1_000);
+    assert_eq!(
+        AuctionDetails::from_amounts(3, 1, 0, 100),
+        Err(AuctionError::OutOfRange("initial_rate_bump"))
+    );
+}
+
+//------------------------------------------------------------------
+//  Encoding
+//------------------------------------------------------------------
+#[test]
+fn details_round_trip_through_bytes() {
+    let details = AuctionDetails {
+        start_time: 1_700_000_000,
+        duration: 180,
+        initial_rate_bump: 500_000,
+        points: vec![AuctionPoint {
+            rate_bump: 250_000,
+            delay: 60,
+        }],
+        gas_cost: GasCost {
+            gas_bump_estimate: 100_000,
+            gas_price_estimate: 10_000,
+        },
+    };
+    let bytes = details.encode().unwrap();
+    let expected: [u8; 22] = [
+        0x01, 0x86, 0xa0, // gas bump estimate
+        0x00, 0x00, 0x27, 0x10, // gas price estimate
+        0x65, 0x53, 0xf1, 0x00, // start time
+        0x00, 0x00, 0xb4, // duration
+        0x07, 0xa1, 0x20, // initial rate bump
+        0x03, 0xd0, 0x90, 0x00, 0x3c, // point
+    ];
+    assert_eq!(bytes, expected);
+    assert_eq!(AuctionDetails::decode(&bytes), Ok(details.clone()));
+    assert_eq!(
+        AuctionDetails::decode(&bytes[..17]).unwrap().points,
+        Vec::new()
+    );
+
+    assert_eq!(
+        AuctionDetails::decode(&bytes[..20]),
+        Err(AuctionError::BadLength(20))
+    );
+    assert_eq!(
+        AuctionDetails::decode(&bytes[..16]),
+        Err(AuctionError::BadLength(16))
+    );
+    let long = AuctionDetails {
+        duration: 1 << 24,
+        ..details
+    };
+    assert_eq!(long.encode(), Err(AuctionError::OutOfRange("duration")));
+}
@@ -0,0 +1,269 @@
+//! Command-line arguments
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+use stellar_htlc_sdk::{
+    keystore::DEFAULT_ITERATIONS, types::CrossChainMetadata, CreateHtlc, Hash32, HashAlgo,
+};
+
+/// Ledgers `events` looks back without `--start-ledger`, about a day
+pub const DEFAULT_LOOKBACK_LEDGERS: u32 = 17_280;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "htlc-cli",
+    version,
+    about = "Operate swaps on the Stellar HTLC contract"
+)]
+pub struct Cli {
+    /// Soroban RPC endpoint
+    #[arg(long, env = "STELLAR_RPC_URL", global = true)]
+    pub rpc_url: Option<String>,
+    /// Strkey of the HTLC contract
+    #[arg(long, env = "STELLAR_CONTRACT_ID", global = true)]
+    pub contract_id: Option<String>,
+    /// File holding the signer's `S...` secret seed; STELLAR_SECRET_KEY is read otherwise
+    #[arg(long, env = "STELLAR_KEY_FILE", global = true)]
+    pub key_file: Option<PathBuf>,
+    /// Keystore file holding the signer's key
+    #[arg(
+        long,
+        env = "STELLAR_KEYSTORE",
+        global = true,
+        conflicts_with = "key_file"
+    )]
+    pub keystore: Option<PathBuf>,
+    /// File holding the keystore password; STELLAR_KEYSTORE_PASSWORD is read otherwise
+    #[arg(long, env = "STELLAR_KEYSTORE_PASSWORD_FILE", global = true)]
+    pub password_file: Option<PathBuf>,
+    /// ID, alias or ARN of the AWS KMS key signing for the signer, called with the AWS_*
+    /// environment variables
+    #[arg(
+        long,
+        env = "STELLAR_KMS_KEY_ID",
+        global = true,
+        conflicts_with_all = ["key_file", "keystore"]
+    )]
+    pub kms_key_id: Option<String>,
+    /// Print JSON instead of tables
+    #[arg(long, global = true)]
+    pub json: bool,
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Lock tokens from the signer's account in a new HTLC
+    Create(Box<CreateArgs>),
+    /// Withdraw an HTLC to its receiver by revealing the preimage
+    Withdraw {
+        /// Hex ID of the HTLC
+        htlc_id: Hash32,
+        /// Hex secret the hashlock was derived from
+        #[arg(long)]
+        preimage: Hash32,
+    },
+    /// Refund an expired HTLC to its sender
+    Refund {
+        /// Hex ID of the HTLC
+        htlc_id: Hash32,
+    },
+    /// Show an HTLC
+    Status {
+        /// Hex ID of the HTLC
+        htlc_id: Hash32,
+    },
+    /// List the HTLCs of a sender or receiver
+    List(ListArgs),
+    /// Show the contract's lifecycle events
+    Events(EventsArgs),
+    /// Generate and hash swap secrets, offline
+    #[command(subcommand)]
+    Secret(SecretCommand),
+    /// Encrypt signing keys into keystore files, offline
+    #[command(subcommand)]
+    Keystore(KeystoreCommand),
+}
+
+#[derive(Debug, Subcommand)]
+pub enum KeystoreCommand {
+    /// Generate a Stellar account key into a new keystore file
+    New {
+        path: PathBuf,
+        /// PBKDF2 iterations deriving the encryption key from the password
+        #[arg(long, default_value_t = DEFAULT_ITERATIONS, value_parser = clap::value_parser!(u32).range(1..))]
+        iterations: u32,
+    },
+    /// Encrypt a key read from stdin into a new keystore file: an `S...` secret seed, or a
+    /// hex 32-byte key such as an EVM private key
+    Import {
+        path: PathBuf,
+        /// PBKDF2 iterations deriving the encryption key from the password
+        #[arg(long, default_value_t = DEFAULT_ITERATIONS, value_parser = clap::value_parser!(u32).range(1..))]
+        iterations: u32,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SecretCommand {
+    /// Generate a secret and its hashlock, or the secrets of a partial fill and their
+    /// Merkle root
+    New {
+        #[arg(long, value_enum, default_value_t = Algo::Sha256)]
+        algo: Algo,
+        /// Parts of a partial fill, committed to with one more secret than parts
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        parts: Option<u32>,
+    },
+    /// Print the hashlock of a hex secret, read from stdin unless given
+    Hash {
+        secret: Option<String>,
+        #[arg(long, value_enum, default_value_t = Algo::Sha256)]
+        algo: Algo,
+    },
+    /// Print the Merkle root of a partial fill's hex secrets, one per line in part order,
+    /// read from FILE or stdin
+    Merkle {
+        file: Option<PathBuf>,
+        #[arg(long, value_enum, default_value_t = Algo::Sha256)]
+        algo: Algo,
+        /// Also print the proof `withdraw_partial` takes for this part
+        #[arg(long)]
+        proof: Option<u32>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Algo {
+    Sha256,
+    Keccak256,
+}
+
+impl From<Algo> for HashAlgo {
+    fn from(algo: Algo) -> Self {
+        match algo {
+            Algo::Sha256 => HashAlgo::Sha256,
+            Algo::Keccak256 => HashAlgo::Keccak256,
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct CreateArgs {
+    /// Strkey of the receiver
+    #[arg(long)]
+    pub receiver: String,
+    /// Amount in the token's smallest unit
+    #[arg(long)]
+    pub amount: i128,
+    /// Strkey of the token contract
+    #[arg(long)]
+    pub token: String,
+    /// Hex hashlock, or the Merkle root of the parts' secrets with `--parts`
+    #[arg(long)]
+    pub hashlock: Hash32,
+    #[arg(long, value_enum, default_value_t = Algo::Sha256)]
+    pub hash_algo: Algo,
+    /// Unix time from which the sender may refund
+    #[arg(long)]
+    pub timelock: u64,
+    #[arg(long, default_value_t = 0)]
+    pub safety_deposit: i128,
+    /// Token of the safety deposit; `--token` if unset
+    #[arg(long)]
+    pub safety_deposit_token: Option<String>,
+    /// Number of parts a partial fill is split into; 0 for a single secret
+    #[arg(long, default_value_t = 0)]
+    pub parts: u32,
+    /// Unix time from which anyone may withdraw; 0 disables it
+    #[arg(long, default_value_t = 0)]
+    pub public_withdrawal: u64,
+    /// Unix time from which anyone may refund; 0 disables it
+    #[arg(long, default_value_t = 0)]
+    pub public_cancellation: u64,
+    /// Hex order hash used as the contract ID; derived when unset
+    #[arg(long)]
+    pub order_hash: Option<Hash32>,
+    /// Only address allowed to withdraw during the exclusive window; the receiver if unset
+    #[arg(long)]
+    pub taker: Option<String>,
+    /// Chain ID of the swap's EVM leg
+    #[arg(long, default_value_t = 0)]
+    pub evm_chain_id: u64,
+    /// Hex 1inch order hash of the swap's EVM leg
+    #[arg(long)]
+    pub evm_order_hash: Option<Hash32>,
+    /// EVM address of the maker
+    #[arg(long)]
+    pub maker: Option<String>,
+    #[arg(long)]
+    pub memo: Option<String>,
+    /// Accept a hashlock already guarding another active HTLC
+    #[arg(long)]
+    pub allow_reuse: bool,
+}
+
+impl CreateArgs {
+    pub fn params(&self) -> CreateHtlc {
+        CreateHtlc {
+            hash_algo: self.hash_algo.into(),
+            safety_deposit: self.safety_deposit,
+            safety_deposit_token: self.safety_deposit_token.clone(),
+            parts: self.parts,
+            public_withdrawal: self.public_withdrawal,
+            public_cancellation: self.public_cancellation,
+            order_hash: self.order_hash.unwrap_or_default(),
+            taker: self.taker.clone(),
+            metadata: CrossChainMetadata {
+                evm_chain_id: self.evm_chain_id,
+                evm_order_hash: self.evm_order_hash.unwrap_or_default(),
+                maker: self.maker.clone().unwrap_or_default(),
+            },
+            memo: self.memo.clone().unwrap_or_default().into_bytes(),
+            allow_reuse: self.allow_reuse,
+            ..CreateHtlc::new(
+                &self.receiver,
+                self.amount,
+                &self.token,
+                self.hashlock,
+                self.timelock,
+            )
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct ListArgs {
+    /// List the HTLCs this address created
+    #[arg(
+        long,
+        required_unless_present = "receiver",
+        conflicts_with = "receiver"
+    )]
+    pub sender: Option<String>,
+    /// List the HTLCs this address may claim
+    #[arg(long)]
+    pub receiver: Option<String>,
+    /// Only list active HTLCs, with `--receiver`
+    #[arg(long, conflicts_with = "sender")]
+    pub active: bool,
+    #[arg(long, default_value_t = 0)]
+    pub offset: u32,
+    /// Page size; the contract caps it at 100
+    #[arg(long, default_value_t = 20)]
+    pub limit: u32,
+}
+
+#[derive(Debug, Args)]
+pub struct EventsArgs {
+    /// First ledger to scan; about a day back if unset
+    #[arg(long, conflicts_with = "cursor")]
+    pub start_ledger: Option<u32>,
+    /// Cursor printed by a previous call, to continue from
+    #[arg(long)]
+    pub cursor: Option<String>,
+    /// Most events to show
+    #[arg(long, default_value_t = 100)]
+    pub limit: u32,
+}
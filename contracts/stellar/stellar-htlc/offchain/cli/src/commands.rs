@@ -0,0 +1,269 @@
+//! Execution of the parsed commands
+
+use crate::{
+    args::{
+        Cli, Command, EventsArgs, KeystoreCommand, ListArgs, SecretCommand,
+        DEFAULT_LOOKBACK_LEDGERS,
+    },
+    keys,
+    output::{
+        self, EventPage, EventView, Hashlock, HtlcList, KeystoreFile, PartProof, SecretSet,
+        Submitted,
+    },
+    CliError,
+};
+use std::{fs, io::Read};
+use stellar_htlc_sdk::{
+    events,
+    keystore::Keystore,
+    rpc::{GetEventsRequest, Pagination, RpcClient, PAGE_LIMIT},
+    HashAlgo, HtlcClient, MerkleTree, Secret,
+};
+use zeroize::Zeroizing;
+
+/// Runs `cli`'s command, returning what it prints
+pub async fn run(cli: &Cli) -> Result<String, CliError> {
+    if let Command::Secret(command) = &cli.command {
+        return secret(command, cli.json, &mut std::io::stdin());
+    }
+    if let Command::Keystore(command) = &cli.command {
+        let password = keys::password(cli.password_file.as_deref())?;
+        return keystore(command, &password, cli.json, &mut std::io::stdin());
+    }
+    let client = connect(cli).await?;
+    match &cli.command {
+        Command::Create(args) => {
+            let signer = keys::signer(cli).await?;
+            let receipt = client.create_htlc(&*signer, &args.params()).await?;
+            output::render(&Submitted::new(receipt.value, receipt), cli.json)
+        }
+        Command::Withdraw { htlc_id, preimage } => {
+            let signer = keys::signer(cli).await?;
+            let receipt = client.withdraw(&*signer, *htlc_id, *preimage).await?;
+            output::render(&Submitted::new(*htlc_id, receipt), cli.json)
+        }
+        Command::Refund { htlc_id } => {
+            let signer = keys::signer(cli).await?;
+            let receipt = client.refund(&*signer, *htlc_id).await?;
+            output::render(&Submitted::new(*htlc_id, receipt), cli.json)
+        }
+        Command::Status { htlc_id } => output::render(&client.get_htlc(*htlc_id).await?, cli.json),
+        Command::List(args) => output::render(&list(&client, args).await?, cli.json),
+        Command::Events(args) => output::render(&event_page(&client, args).await?, cli.json),
+        Command::Secret(_) | Command::Keystore(_) => unreachable!("handled offline"),
+    }
+}
+
+/// Runs a `secret` command, which needs no RPC; secrets not passed as arguments are read
+/// from `stdin`
+pub fn secret(
+    command: &SecretCommand,
+    json: bool,
+    stdin: &mut dyn Read,
+) -> Result<String, CliError> {
+    match command {
+        SecretCommand::New { algo, parts } => {
+            let algo = HashAlgo::from(*algo);
+            let count = parts.map_or(1, |parts| parts as usize + 1);
+            let secrets = (0..count)
+                .map(|_| Secret::generate())
+                .collect::<Result<Vec<_>, _>>()?;
+            let hashlock = match parts {
+                Some(_) => MerkleTree::new(algo, &secrets)?.root(),
+                None => secrets[0].hashlock(algo),
+            };
+            let set = SecretSet {
+                hash_algo: algo,
+                hashlock,
+                parts: parts.unwrap_or(0),
+                secrets,
+            };
+            output::render(&set, json)
+        }
+        SecretCommand::Hash { secret, algo } => {
+            let secret = match secret {
+                Some(secret) => parse_secret(1, secret)?,
+                None => parse_secret(1, &read(stdin, "stdin")?)?,
+            };
+            let hashlock = Hashlock {
+                hash_algo: (*algo).into(),
+                hashlock: secret.hashlock((*algo).into()),
+                parts: None,
+                proof: None,
+            };
+            output::render(&hashlock, json)
+        }
+        SecretCommand::Merkle { file, algo, proof } => {
+            let input = match file {
+                Some(path) => {
+                    Zeroizing::new(fs::read_to_string(path).map_err(|source| CliError::Read {
+                        from: path.display().to_string(),
+                        source,
+                    })?)
+                }
+                None => read(stdin, "stdin")?,
+            };
+            let secrets = input
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| !line.trim().is_empty())
+                .map(|(i, line)| parse_secret(i + 1, line))
+                .collect::<Result<Vec<_>, _>>()?;
+            let tree = MerkleTree::new((*algo).into(), &secrets)?;
+            let proof = proof
+                .map(|index| {
+                    tree.proof(index)
+                        .map(|proof| PartProof { index, proof })
+                        .ok_or(CliError::NoSuchPart(index))
+                })
+                .transpose()?;
+            let root = Hashlock {
+                hash_algo: (*algo).into(),
+                hashlock: tree.root(),
+                parts: Some(tree.parts()),
+                proof,
+            };
+            output::render(&root, json)
+        }
+    }
+}
+
+/// Runs a `keystore` command, which needs no RPC, encrypting under `password`; imported
+/// keys are read from `stdin`
+pub fn keystore(
+    command: &KeystoreCommand,
+    password: &str,
+    json: bool,
+    stdin: &mut dyn Read,
+) -> Result<String, CliError> {
+    let (path, keystore) = match command {
+        KeystoreCommand::New { path, iterations } => {
+            (path, Keystore::generate(password, *iterations)?)
+        }
+        KeystoreCommand::Import { path, iterations } => {
+            let input = read(stdin, "stdin")?;
+            (path, import(input.trim(), password, *iterations)?)
+        }
+    };
+    keystore.save(path)?;
+    let file = KeystoreFile {
+        path: path.display().to_string(),
+        address: keystore.address,
+    };
+    output::render(&file, json)
+}
+
+/// Keystore of an `S...` secret seed, or of a hex 32-byte key
+fn import(key: &str, password: &str, iterations: u32) -> Result<Keystore, CliError> {
+    if key.starts_with('S') {
+        let seed = stellar_strkey::ed25519::PrivateKey::from_string(key)
+            .map_err(|_| CliError::Key("not a Stellar secret seed".into()))?;
+        let seed = Zeroizing::new(seed.0);
+        return Ok(Keystore::encrypt_seed(&seed, password, iterations)?);
+    }
+    let bytes = Zeroizing::new(
+        hex::decode(key.strip_prefix("0x").unwrap_or(key))
+            .map_err(|_| CliError::Key("not an `S...` seed or hex key".into()))?,
+    );
+    let key: &[u8; 32] = bytes
+        .as_slice()
+        .try_into()
+        .map_err(|_| CliError::Key("a hex key must be 32 bytes".into()))?;
+    Ok(Keystore::encrypt(key, password, iterations)?)
+}
+
+fn read(input: &mut dyn Read, from: &str) -> Result<Zeroizing<String>, CliError> {
+    let mut text = Zeroizing::new(String::new());
+    input
+        .read_to_string(&mut text)
+        .map_err(|source| CliError::Read {
+            from: from.into(),
+            source,
+        })?;
+    Ok(text)
+}
+
+fn parse_secret(line: usize, hex: &str) -> Result<Secret, CliError> {
+    Secret::from_hex(hex).map_err(|source| CliError::Secret { line, source })
+}
+
+async fn connect(cli: &Cli) -> Result<HtlcClient, CliError> {
+    let rpc_url = cli
+        .rpc_url
+        .as_deref()
+        .ok_or(CliError::Missing("--rpc-url or STELLAR_RPC_URL"))?;
+    let contract_id = cli
+        .contract_id
+        .as_deref()
+        .ok_or(CliError::Missing("--contract-id or STELLAR_CONTRACT_ID"))?;
+    Ok(HtlcClient::connect(rpc_url, contract_id).await?)
+}
+
+async fn list(client: &HtlcClient, args: &ListArgs) -> Result<HtlcList, CliError> {
+    let contract_ids = match (&args.sender, &args.receiver) {
+        (Some(sender), _) => {
+            client
+                .htlcs_by_sender(sender, args.offset, args.limit)
+                .await?
+        }
+        (None, Some(receiver)) => {
+            client
+                .htlcs_by_receiver(receiver, args.offset, args.limit, args.active)
+                .await?
+        }
+        (None, None) => return Err(CliError::Missing("--sender or --receiver")),
+    };
+    let mut htlcs = Vec::with_capacity(contract_ids.len());
+    for contract_id in contract_ids {
+        htlcs.push(client.get_htlc(contract_id).await?);
+    }
+    Ok(HtlcList(htlcs))
+}
+
+/// Lifecycle events from the start ledger or cursor, up to `args.limit` of them
+async fn event_page(client: &HtlcClient, args: &EventsArgs) -> Result<EventPage, CliError> {
+    let rpc = client.rpc();
+    let mut start_ledger = match (&args.cursor, args.start_ledger) {
+        (Some(_), _) => None,
+        (None, Some(ledger)) => Some(ledger),
+        (None, None) => Some(lookback(rpc).await?),
+    };
+    let mut cursor = args.cursor.clone();
+    let mut page = EventPage {
+        events: Vec::new(),
+        cursor: String::new(),
+    };
+    while page.events.len() < args.limit as usize {
+        let response = rpc
+            .get_events(&GetEventsRequest {
+                start_ledger: start_ledger.take(),
+                filters: vec![events::lifecycle_filter(&client.contract_id())],
+                pagination: Pagination {
+                    cursor: cursor.clone(),
+                    limit: (args.limit as usize - page.events.len()).min(PAGE_LIMIT as usize)
+                        as u32,
+                },
+            })
+            .await?;
+        let done = response.events.is_empty();
+        for info in &response.events {
+            let decoded = events::decode(info).map_err(|source| CliError::Decode {
+                id: info.id.clone(),
+                source,
+            })?;
+            page.events.extend(decoded.map(EventView::from));
+        }
+        // Pages never run past the limit, so continuing from the cursor skips nothing
+        page.cursor = response.cursor;
+        cursor = Some(page.cursor.clone());
+        if done {
+            break;
+        }
+    }
+    Ok(page)
+}
+
+async fn lookback(rpc: &RpcClient) -> Result<u32, CliError> {
+    let latest = rpc.get_latest_ledger().await?.sequence;
+    Ok(latest.saturating_sub(DEFAULT_LOOKBACK_LEDGERS).max(1))
+}
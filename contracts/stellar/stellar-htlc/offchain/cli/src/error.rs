@@ -0,0 +1,28 @@
+use std::{io, path::PathBuf};
+use stellar_htlc_sdk::types::DecodeError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum CliError {
+    #[error("{0} is not set")]
+    Missing(&'static str),
+    #[error("cannot read key file {}: {source}", path.display())]
+    KeyFile { path: PathBuf, source: io::Error },
+    #[error("invalid signing key: {0}")]
+    Key(String),
+    #[error("cannot read secrets from {from}: {source}")]
+    Read { from: String, source: io::Error },
+    #[error("invalid secret on line {line}: {source}")]
+    Secret {
+        line: usize,
+        source: stellar_htlc_sdk::Error,
+    },
+    #[error("no part {0} among the secrets")]
+    NoSuchPart(u32),
+    #[error(transparent)]
+    Stellar(#[from] stellar_htlc_sdk::Error),
+    #[error("undecodable event {id}: {source}")]
+    Decode { id: String, source: DecodeError },
+    #[error("cannot encode JSON output: {0}")]
+    Json(#[from] serde_json::Error),
+}
@@ -0,0 +1,55 @@
+//! Loading of the signing key, kept off the command line so it stays out of shell history
+
+use crate::{args::Cli, CliError};
+use std::{env, fs, path::Path};
+use stellar_htlc_sdk::{keystore, Keypair, Signer, SignerConfig};
+use zeroize::Zeroizing;
+
+/// Environment variable holding the signer's `S...` secret seed
+pub const SECRET_KEY_VAR: &str = "STELLAR_SECRET_KEY";
+
+/// Environment variable holding the keystore password, without `--password-file`
+pub const PASSWORD_VAR: &str = "STELLAR_KEYSTORE_PASSWORD";
+
+/// Signer `cli` selects: its KMS key, its keystore, or else the key `load` reads
+pub async fn signer(cli: &Cli) -> Result<Box<dyn Signer>, CliError> {
+    let config = match (&cli.kms_key_id, &cli.keystore) {
+        (Some(key_id), _) => SignerConfig::Kms {
+            key_id: key_id.clone(),
+        },
+        (None, Some(path)) => SignerConfig::Keystore {
+            path: path.clone(),
+            password: password(cli.password_file.as_deref())?,
+        },
+        (None, None) => return Ok(Box::new(load(cli.key_file.as_deref())?)),
+    };
+    config
+        .load()
+        .await
+        .map_err(|error| CliError::Key(error.to_string()))
+}
+
+/// Keystore password in `password_file` when given, otherwise in
+/// `STELLAR_KEYSTORE_PASSWORD`
+pub fn password(password_file: Option<&Path>) -> Result<Zeroizing<String>, CliError> {
+    match password_file {
+        Some(path) => {
+            keystore::read_password(path).map_err(|error| CliError::Key(error.to_string()))
+        }
+        None => env::var(PASSWORD_VAR)
+            .map(Zeroizing::new)
+            .map_err(|_| CliError::Missing("--password-file or STELLAR_KEYSTORE_PASSWORD")),
+    }
+}
+
+/// Signer of `key_file` when given, otherwise of `STELLAR_SECRET_KEY`
+pub fn load(key_file: Option<&Path>) -> Result<Keypair, CliError> {
+    let secret = match key_file {
+        Some(path) => fs::read_to_string(path).map_err(|source| CliError::KeyFile {
+            path: path.into(),
+            source,
+        })?,
+        None => env::var(SECRET_KEY_VAR).map_err(|_| CliError::Missing(SECRET_KEY_VAR))?,
+    };
+    Keypair::from_secret(&secret).map_err(|error| CliError::Key(error.to_string()))
+}
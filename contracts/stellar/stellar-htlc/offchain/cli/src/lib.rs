@@ -0,0 +1,15 @@
+//! `htlc-cli`, a command-line front end of the Stellar HTLC contract.
+//!
+//! Creates, withdraws and refunds HTLCs through `stellar-htlc-sdk`, and reads them back by
+//! contract ID, by sender or receiver, or from the contract's lifecycle events. Results
+//! are printed as tables for people, or as JSON with `--json` for scripts.
+
+pub mod args;
+pub mod commands;
+pub mod error;
+pub mod keys;
+pub mod output;
+
+pub use error::CliError;
+
+mod test;
@@ -0,0 +1,18 @@
+use clap::Parser;
+use std::process::ExitCode;
+use stellar_htlc_cli::{args::Cli, commands};
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match commands::run(&cli).await {
+        Ok(output) => {
+            print!("{output}");
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
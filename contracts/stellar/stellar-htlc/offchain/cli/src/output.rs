@@ -0,0 +1,345 @@
+//! Results of the commands, rendered as tables or JSON
+
+use crate::CliError;
+use serde::{Serialize, Serializer};
+use stellar_htlc_sdk::{
+    events::{ContractEvent, HtlcEvent},
+    Hash32, HashAlgo, Htlc, Receipt, Secret,
+};
+
+/// Text columns padded to their widest cell
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Table {
+    header: Option<Vec<String>>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    /// A table with a header row
+    pub fn new(header: &[&str]) -> Self {
+        Table {
+            header: Some(header.iter().map(|cell| cell.to_string()).collect()),
+            rows: Vec::new(),
+        }
+    }
+
+    /// A two-column table of one record's fields
+    pub fn fields(fields: Vec<(impl Into<String>, String)>) -> Self {
+        Table {
+            header: None,
+            rows: fields
+                .into_iter()
+                .map(|(name, value)| vec![name.into(), value])
+                .collect(),
+        }
+    }
+
+    pub fn row(&mut self, cells: Vec<String>) {
+        self.rows.push(cells);
+    }
+
+    pub fn render(&self) -> String {
+        let lines: Vec<&Vec<String>> = self.header.iter().chain(&self.rows).collect();
+        let mut widths = Vec::new();
+        for line in &lines {
+            for (i, cell) in line.iter().enumerate() {
+                match widths.get_mut(i) {
+                    Some(width) => *width = cell.chars().count().max(*width),
+                    None => widths.push(cell.chars().count()),
+                }
+            }
+        }
+        let mut out = String::new();
+        for line in lines {
+            let cells: Vec<String> = line
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{cell:width$}"))
+                .collect();
+            out.push_str(cells.join("  ").trim_end());
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// A command result
+pub trait Report: Serialize {
+    /// Human-readable form
+    fn text(&self) -> String;
+}
+
+pub fn render(report: &impl Report, json: bool) -> Result<String, CliError> {
+    if json {
+        Ok(serde_json::to_string_pretty(report)? + "\n")
+    } else {
+        Ok(report.text())
+    }
+}
+
+/// An included transaction, with the HTLC it acted on
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Submitted {
+    pub contract_id: Hash32,
+    pub tx_hash: String,
+    pub ledger: u32,
+}
+
+impl Submitted {
+    pub fn new<T>(contract_id: Hash32, receipt: Receipt<T>) -> Self {
+        Submitted {
+            contract_id,
+            tx_hash: receipt.tx_hash,
+            ledger: receipt.ledger,
+        }
+    }
+}
+
+impl Report for Submitted {
+    fn text(&self) -> String {
+        Table::fields(vec![
+            ("contract_id", self.contract_id.to_string()),
+            ("tx_hash", self.tx_hash.clone()),
+            ("ledger", self.ledger.to_string()),
+        ])
+        .render()
+    }
+}
+
+impl Report for Htlc {
+    fn text(&self) -> String {
+        let optional = |value: u64| match value {
+            0 => "-".to_string(),
+            value => value.to_string(),
+        };
+        Table::fields(vec![
+            ("contract_id", self.contract_id.to_string()),
+            ("status", format!("{:?}", self.status)),
+            ("sender", self.sender.clone()),
+            ("receiver", self.receiver.clone()),
+            ("taker", self.taker.clone()),
+            ("token", self.token_address.clone()),
+            ("amount", self.amount.to_string()),
+            ("filled_amount", self.filled_amount.to_string()),
+            ("parts", self.parts.to_string()),
+            ("hashlock", self.hashlock.to_string()),
+            ("hash_algo", format!("{:?}", self.hash_algo)),
+            ("timelock", self.timelock.to_string()),
+            ("public_withdrawal", optional(self.public_withdrawal)),
+            ("public_cancellation", optional(self.public_cancellation)),
+            ("safety_deposit", self.safety_deposit.to_string()),
+            ("created_ledger", self.created_ledger.to_string()),
+            ("completed_at", optional(self.completed_at)),
+            (
+                "preimage",
+                self.preimage
+                    .map_or("-".into(), |preimage| preimage.to_string()),
+            ),
+            ("evm_chain_id", optional(self.metadata.evm_chain_id)),
+            ("evm_order_hash", self.metadata.evm_order_hash.to_string()),
+            ("maker", self.metadata.maker.clone()),
+        ])
+        .render()
+    }
+}
+
+/// A page of HTLCs
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct HtlcList(pub Vec<Htlc>);
+
+impl Report for HtlcList {
+    fn text(&self) -> String {
+        let mut table = Table::new(&["CONTRACT ID", "STATUS", "AMOUNT", "RECEIVER", "TIMELOCK"]);
+        for htlc in &self.0 {
+            table.row(vec![
+                htlc.contract_id.to_string(),
+                format!("{:?}", htlc.status),
+                htlc.amount.to_string(),
+                htlc.receiver.clone(),
+                htlc.timelock.to_string(),
+            ]);
+        }
+        table.render()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum EventDetails {
+    New {
+        sender: String,
+        receiver: String,
+        amount: i128,
+        token: String,
+        hashlock: Hash32,
+        timelock: u64,
+    },
+    Withdraw {
+        preimage: Hash32,
+    },
+    Refund,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct EventView {
+    pub id: String,
+    pub ledger: u32,
+    pub tx_hash: String,
+    pub contract_id: Hash32,
+    pub sequence: u64,
+    #[serde(flatten)]
+    pub details: EventDetails,
+}
+
+impl From<ContractEvent> for EventView {
+    fn from(event: ContractEvent) -> Self {
+        let details = match event.event {
+            HtlcEvent::New(htlc) => EventDetails::New {
+                sender: htlc.sender,
+                receiver: htlc.receiver,
+                amount: htlc.amount,
+                token: htlc.token_address,
+                hashlock: htlc.hashlock,
+                timelock: htlc.timelock,
+            },
+            HtlcEvent::Withdraw { preimage } => EventDetails::Withdraw { preimage },
+            HtlcEvent::Refund => EventDetails::Refund,
+        };
+        EventView {
+            id: event.id,
+            ledger: event.ledger,
+            tx_hash: event.tx_hash,
+            contract_id: event.contract_id,
+            sequence: event.sequence,
+            details,
+        }
+    }
+}
+
+/// A page of events, with the cursor the next page starts from
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct EventPage {
+    pub events: Vec<EventView>,
+    pub cursor: String,
+}
+
+impl Report for EventPage {
+    fn text(&self) -> String {
+        let mut table = Table::new(&["LEDGER", "EVENT", "CONTRACT ID", "DETAILS"]);
+        for event in &self.events {
+            let (kind, details) = match &event.details {
+                EventDetails::New {
+                    amount, hashlock, ..
+                } => ("new", format!("amount={amount} hashlock={hashlock}")),
+                EventDetails::Withdraw { preimage } => ("withdraw", format!("preimage={preimage}")),
+                EventDetails::Refund => ("refund", String::new()),
+            };
+            table.row(vec![
+                event.ledger.to_string(),
+                kind.into(),
+                event.contract_id.to_string(),
+                details,
+            ]);
+        }
+        format!("{}\ncursor: {}\n", table.render(), self.cursor)
+    }
+}
+
+/// Freshly generated secrets with the hashlock they are locked under: the hash of the
+/// only secret, or the Merkle root of a partial fill's secrets
+#[derive(Debug, Serialize)]
+pub struct SecretSet {
+    pub hash_algo: HashAlgo,
+    pub hashlock: Hash32,
+    /// 0 for a single secret
+    pub parts: u32,
+    #[serde(serialize_with = "hex_secrets")]
+    pub secrets: Vec<Secret>,
+}
+
+fn hex_secrets<S: Serializer>(secrets: &[Secret], serializer: S) -> Result<S::Ok, S::Error> {
+    struct Hex<'a>(&'a Secret);
+
+    impl Serialize for Hex<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_str(&self.0.to_hex())
+        }
+    }
+
+    serializer.collect_seq(secrets.iter().map(Hex))
+}
+
+impl Report for SecretSet {
+    fn text(&self) -> String {
+        let mut fields = vec![
+            ("hash_algo".into(), format!("{:?}", self.hash_algo)),
+            ("hashlock".into(), self.hashlock.to_string()),
+            ("parts".into(), self.parts.to_string()),
+        ];
+        for (index, secret) in self.secrets.iter().enumerate() {
+            let name: String = match self.parts {
+                0 => "secret".into(),
+                _ => format!("secret {index}"),
+            };
+            fields.push((name, secret.to_hex().to_string()));
+        }
+        Table::fields(fields).render()
+    }
+}
+
+/// The hashlock of a secret, or the Merkle root of a partial fill's secrets
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Hashlock {
+    pub hash_algo: HashAlgo,
+    pub hashlock: Hash32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parts: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proof: Option<PartProof>,
+}
+
+/// What `withdraw_partial` takes besides the secret of part `index`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct PartProof {
+    pub index: u32,
+    pub proof: Vec<Hash32>,
+}
+
+impl Report for Hashlock {
+    fn text(&self) -> String {
+        let mut fields = vec![
+            ("hash_algo", format!("{:?}", self.hash_algo)),
+            ("hashlock", self.hashlock.to_string()),
+        ];
+        if let Some(parts) = self.parts {
+            fields.push(("parts", parts.to_string()));
+        }
+        if let Some(proof) = &self.proof {
+            fields.push(("index", proof.index.to_string()));
+            let nodes: Vec<String> = proof.proof.iter().map(Hash32::to_string).collect();
+            fields.push(("proof", nodes.join(",")));
+        }
+        Table::fields(fields).render()
+    }
+}
+
+/// A keystore file written, with the account of its key when it is a Stellar seed
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct KeystoreFile {
+    pub path: String,
+    pub address: Option<String>,
+}
+
+impl Report for KeystoreFile {
+    fn text(&self) -> String {
+        Table::fields(vec![
+            ("path", self.path.clone()),
+            (
+                "address",
+                self.address.clone().unwrap_or_else(|| "-".into()),
+            ),
+        ])
+        .render()
+    }
+}
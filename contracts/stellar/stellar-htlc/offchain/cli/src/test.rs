@@ -0,0 +1,373 @@
+#![cfg(test)]
+
+use crate::{
+    args::{Cli, Command},
+    commands, keys,
+    output::{self, EventPage, EventView, HtlcList, Submitted, Table},
+    CliError,
+};
+use clap::{error::ErrorKind, Parser};
+use std::{io::Write, path::Path};
+use stellar_htlc_sdk::{
+    events, testutils::*, Hash32, HashAlgo, Htlc, HtlcStatus, Keypair, Receipt,
+};
+
+/// Account of the seed `[2; 32]`
+const RECEIVER: &str = "GCATS5YOVB6ROX2WUNKGNQ2MP3GMXDMKSG2O4N5CLX3A6W4PZGZZI55U";
+
+fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+    Cli::try_parse_from([&["htlc-cli", "--rpc-url", "http://localhost:8000"], args].concat())
+}
+
+fn htlc(status: &str, preimage: [u8; 32]) -> Htlc {
+    Htlc::from_scval(&htlc_data_payload(status, preimage)).unwrap()
+}
+
+//------------------------------------------------------------------
+//  Arguments
+//------------------------------------------------------------------
+#[test]
+fn create_args_become_params() {
+    let hashlock = HASHLOCK.to_string();
+    let cli = parse(&[
+        "create",
+        "--receiver",
+        RECEIVER,
+        "--amount",
+        "1000000000",
+        "--token",
+        "CDLZFC3SYJYDZT7K67VZ75HPJVIEUVNIXF47ZG2FB2RMQQVU2HHGCYSC",
+        "--hashlock",
+        &format!("0x{hashlock}"),
+        "--hash-algo",
+        "keccak256",
+        "--timelock",
+        "1700003600",
+        "--evm-chain-id",
+        "1",
+        "--memo",
+        "order-1",
+    ])
+    .unwrap();
+    let Command::Create(args) = &cli.command else {
+        panic!("expected create");
+    };
+    let params = args.params();
+    assert_eq!(params.receiver, RECEIVER);
+    assert_eq!(params.amount, 1_000_000_000);
+    assert_eq!(params.hashlock, HASHLOCK);
+    assert_eq!(params.hash_algo, HashAlgo::Keccak256);
+    assert_eq!(params.timelock, 1_700_003_600);
+    assert_eq!(params.metadata.evm_chain_id, 1);
+    assert_eq!(params.memo, b"order-1");
+    // Everything else keeps the SDK's defaults
+    assert_eq!(params.taker, None);
+    assert_eq!(params.order_hash, Hash32::default());
+    assert_eq!(params.safety_deposit, 0);
+    assert!(!params.allow_reuse);
+
+    assert!(parse(&["create", "--receiver", RECEIVER, "--amount", "1"]).is_err());
+    let cli = parse(&["withdraw", &CONTRACT.to_string(), "--preimage", &hashlock]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Command::Withdraw { htlc_id, preimage } if htlc_id == CONTRACT && preimage == HASHLOCK
+    ));
+    assert!(parse(&["withdraw", "abcd", "--preimage", &hashlock]).is_err());
+}
+
+#[test]
+fn list_takes_one_party() {
+    let cli = parse(&["list", "--receiver", RECEIVER, "--active", "--json"]).unwrap();
+    assert!(cli.json);
+    let Command::List(args) = &cli.command else {
+        panic!("expected list");
+    };
+    assert_eq!(args.receiver.as_deref(), Some(RECEIVER));
+    assert!(args.active);
+    assert_eq!((args.offset, args.limit), (0, 20));
+
+    let kind = |args: &[&str]| parse(args).unwrap_err().kind();
+    assert_eq!(kind(&["list"]), ErrorKind::MissingRequiredArgument);
+    assert_eq!(
+        kind(&["list", "--sender", RECEIVER, "--receiver", RECEIVER]),
+        ErrorKind::ArgumentConflict
+    );
+    assert_eq!(
+        kind(&["list", "--sender", RECEIVER, "--active"]),
+        ErrorKind::ArgumentConflict
+    );
+    assert_eq!(
+        kind(&["events", "--start-ledger", "1", "--cursor", "0001"]),
+        ErrorKind::ArgumentConflict
+    );
+}
+
+//------------------------------------------------------------------
+//  Keys
+//------------------------------------------------------------------
+#[test]
+fn key_file_holds_the_secret_seed() {
+    let keypair = Keypair::from_seed(&[3; 32]);
+    let secret =
+        stellar_strkey::Unredacted(stellar_strkey::ed25519::PrivateKey([3; 32])).to_string();
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    writeln!(file, "{secret}").unwrap();
+    assert_eq!(
+        keys::load(Some(file.path())).unwrap().address(),
+        keypair.address()
+    );
+
+    let mut public = tempfile::NamedTempFile::new().unwrap();
+    write!(public, "{}", keypair.address()).unwrap();
+    assert!(matches!(
+        keys::load(Some(public.path())),
+        Err(CliError::Key(_))
+    ));
+    assert!(matches!(
+        keys::load(Some("/nonexistent/key".as_ref())),
+        Err(CliError::KeyFile { .. })
+    ));
+}
+
+fn keystore(dir: &Path, args: &[&str], stdin: &str) -> Result<serde_json::Value, CliError> {
+    let password = dir.join("password");
+    std::fs::write(&password, "hunter2\n").unwrap();
+    let global = [
+        "--json",
+        "--password-file",
+        password.to_str().unwrap(),
+        "keystore",
+    ];
+    let cli = parse(&[&global, args, &["--iterations", "1000"]].concat()).unwrap();
+    let Command::Keystore(command) = &cli.command else {
+        panic!("expected keystore");
+    };
+    let password = keys::password(cli.password_file.as_deref())?;
+    let out = commands::keystore(command, &password, cli.json, &mut stdin.as_bytes())?;
+    Ok(serde_json::from_str(&out).unwrap())
+}
+
+#[tokio::test]
+async fn keystores_hold_the_signing_key() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+    let created = keystore(dir.path(), &["new", &path("new.json")], "").unwrap();
+    assert!(created["address"].as_str().unwrap().starts_with('G'));
+
+    let secret =
+        stellar_strkey::Unredacted(stellar_strkey::ed25519::PrivateKey([3; 32])).to_string();
+    let imported = keystore(dir.path(), &["import", &path("seed.json")], &secret).unwrap();
+    assert_eq!(imported["address"], Keypair::from_seed(&[3; 32]).address());
+    let evm_key = format!("0x{}", "09".repeat(32));
+    let imported = keystore(dir.path(), &["import", &path("evm.json")], &evm_key).unwrap();
+    assert_eq!(imported["address"], serde_json::Value::Null);
+    assert!(matches!(
+        keystore(dir.path(), &["import", &path("short.json")], "0x0909"),
+        Err(CliError::Key(_))
+    ));
+    // Keystores are never overwritten
+    assert!(keystore(dir.path(), &["new", &path("seed.json")], "").is_err());
+
+    // The signer of a keystore is unlocked with its password
+    let id = "00".repeat(32);
+    let password = path("password");
+    let refund = [
+        "--keystore",
+        &path("seed.json"),
+        "--password-file",
+        &password,
+        "refund",
+        &id,
+    ];
+    let signer = keys::signer(&parse(&refund).unwrap()).await.unwrap();
+    assert_eq!(signer.address(), Keypair::from_seed(&[3; 32]).address());
+    std::fs::write(&password, "hunter3").unwrap();
+    assert!(matches!(
+        keys::signer(&parse(&refund).unwrap()).await,
+        Err(CliError::Key(_))
+    ));
+
+    let kind = |args: &[&str]| parse(args).unwrap_err().kind();
+    assert_eq!(
+        kind(&["--keystore", "a.json", "--key-file", "b.key", "refund", &id]),
+        ErrorKind::ArgumentConflict
+    );
+    assert_eq!(
+        kind(&[
+            "--kms-key-id",
+            "alias/cli",
+            "--keystore",
+            "a.json",
+            "refund",
+            &id
+        ]),
+        ErrorKind::ArgumentConflict
+    );
+}
+
+//------------------------------------------------------------------
+//  Output
+//------------------------------------------------------------------
+#[test]
+fn tables_pad_columns() {
+    let mut table = Table::new(&["ID", "STATUS"]);
+    table.row(vec!["1".into(), "Active".into()]);
+    table.row(vec!["1234".into(), "-".into()]);
+    assert_eq!(table.render(), "ID    STATUS\n1     Active\n1234  -\n");
+    assert_eq!(
+        Table::fields(vec![("ledger", "12".into()), ("tx", "ab".into())]).render(),
+        "ledger  12\ntx      ab\n"
+    );
+}
+
+#[test]
+fn htlcs_render_as_tables_or_json() {
+    let withdrawn = htlc("Withdrawn", PREIMAGE.0);
+    let text = output::render(&withdrawn, false).unwrap();
+    assert!(text.contains(&format!("preimage             {PREIMAGE}\n")));
+    assert!(text.contains("status               Withdrawn\n"));
+
+    let json: serde_json::Value =
+        serde_json::from_str(&output::render(&withdrawn, true).unwrap()).unwrap();
+    assert_eq!(json["contract_id"], CONTRACT.to_string());
+    assert_eq!(json["status"], "Withdrawn");
+    assert_eq!(json["preimage"], PREIMAGE.to_string());
+
+    let list = HtlcList(vec![htlc("Active", [0; 32]), withdrawn]);
+    let text = output::render(&list, false).unwrap();
+    assert_eq!(text.lines().count(), 3);
+    assert!(text.starts_with("CONTRACT ID"));
+    let json: serde_json::Value =
+        serde_json::from_str(&output::render(&list, true).unwrap()).unwrap();
+    assert_eq!(json.as_array().unwrap().len(), 2);
+    assert_eq!(
+        serde_json::from_value::<HtlcStatus>(json[0]["status"].clone()).unwrap(),
+        HtlcStatus::Active
+    );
+}
+
+#[test]
+fn events_render_with_their_cursor() {
+    let decode = |info| EventView::from(events::decode(&info).unwrap().unwrap());
+    let page = EventPage {
+        events: vec![
+            decode(event_info("0001-1", "HTLCNew", htlc_new_payload())),
+            decode(event_info(
+                "0002-1",
+                "HTLCWithdraw",
+                lifecycle(bytes(&PREIMAGE.0)),
+            )),
+            decode(event_info(
+                "0003-1",
+                "HTLCRefund",
+                lifecycle(bytes(&CONTRACT.0)),
+            )),
+        ],
+        cursor: "0003-1".into(),
+    };
+    let text = output::render(&page, false).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert!(lines[1].contains("new") && lines[1].contains("amount=1000000000"));
+    assert!(lines[2].contains(&format!("preimage={PREIMAGE}")));
+    assert!(lines[3].contains("refund"));
+    assert_eq!(lines.last(), Some(&"cursor: 0003-1"));
+
+    let json: serde_json::Value =
+        serde_json::from_str(&output::render(&page, true).unwrap()).unwrap();
+    assert_eq!(json["cursor"], "0003-1");
+    assert_eq!(json["events"][0]["kind"], "new");
+    assert_eq!(json["events"][0]["hashlock"], HASHLOCK.to_string());
+    assert_eq!(json["events"][1]["kind"], "withdraw");
+    assert_eq!(json["events"][2]["contract_id"], CONTRACT.to_string());
+
+    let submitted = Submitted::new(
+        CONTRACT,
+        Receipt {
+            value: (),
+            tx_hash: "ab".repeat(32),
+            ledger: 120,
+        },
+    );
+    assert!(output::render(&submitted, false)
+        .unwrap()
+        .ends_with("ledger       120\n"));
+}
+
+//------------------------------------------------------------------
+//  Secrets
+//------------------------------------------------------------------
+fn secret(args: &[&str], stdin: &str) -> Result<serde_json::Value, CliError> {
+    let cli = parse(&[&["--json", "secret"], args].concat()).unwrap();
+    let Command::Secret(command) = &cli.command else {
+        panic!("expected secret");
+    };
+    let out = commands::secret(command, cli.json, &mut stdin.as_bytes())?;
+    Ok(serde_json::from_str(&out).unwrap())
+}
+
+#[test]
+fn new_secrets_match_their_hashlock() {
+    let single = secret(&["new", "--algo", "keccak256"], "").unwrap();
+    assert_eq!(single["parts"], 0);
+    assert_eq!(single["hash_algo"], "Keccak256");
+    let value = single["secrets"][0].as_str().unwrap();
+    let hashed = secret(&["hash", value, "--algo", "keccak256"], "").unwrap();
+    assert_eq!(hashed["hashlock"], single["hashlock"]);
+    assert_eq!(hashed.get("parts"), None);
+
+    // A partial fill commits to one secret more than it has parts
+    let partial = secret(&["new", "--parts", "4"], "").unwrap();
+    let secrets: Vec<&str> = partial["secrets"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|secret| secret.as_str().unwrap())
+        .collect();
+    assert_eq!(secrets.len(), 5);
+    let mut unique = secrets.clone();
+    unique.sort();
+    unique.dedup();
+    assert_eq!(unique.len(), 5);
+    let root = secret(&["merkle"], &secrets.join("\n")).unwrap();
+    assert_eq!(root["hashlock"], partial["hashlock"]);
+    assert_eq!(root["parts"], 4);
+
+    assert!(parse(&["secret", "new", "--parts", "0"]).is_err());
+}
+
+#[test]
+fn secrets_are_hashed_from_stdin_or_file() {
+    let zero = "00".repeat(32);
+    let hashed = secret(&["hash"], &format!("0x{zero}\n")).unwrap();
+    assert_eq!(
+        hashed["hashlock"],
+        "66687aadf862bd776c8fc18b8e9f8e20089714856ee233b3902a591d0d5f2925"
+    );
+    assert!(matches!(
+        secret(&["hash", "abcd"], ""),
+        Err(CliError::Secret { line: 1, .. })
+    ));
+
+    // The contract tests' partial-fill secrets and their root
+    let secrets: Vec<String> = (100..=104u8)
+        .map(|byte| format!("{byte:02x}").repeat(32))
+        .collect();
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    writeln!(file, "{}\n", secrets.join("\n")).unwrap();
+    let path = file.path().to_str().unwrap();
+    let root = secret(&["merkle", path, "--proof", "4"], "").unwrap();
+    assert_eq!(
+        root["hashlock"],
+        "e643eb4b5e5f8984175d02759204aba301b3c223f7ecf6a965b257590bd1a918"
+    );
+    assert_eq!(root["proof"]["index"], 4);
+    assert_eq!(root["proof"]["proof"].as_array().unwrap().len(), 1);
+    assert!(matches!(
+        secret(&["merkle", path, "--proof", "5"], ""),
+        Err(CliError::NoSuchPart(5))
+    ));
+    assert!(matches!(
+        secret(&["merkle"], &format!("{}\nxyz", secrets[0])),
+        Err(CliError::Secret { line: 2, .. })
+    ));
+}
@@ -0,0 +1,175 @@
+//! REST routes, under the paths of the 1inch Fusion+ API
+//!
+//! | Route | |
+//! |-------|-|
+//! | `GET /quoter/v1.0/quote/receive` | Quote a swap |
+//! | `POST /relayer/v1.0/submit` | Submit a signed order |
+//! | `POST /relayer/v1.0/submit/secret` | Share an order's secret |
+//! | `GET /orders/v1.0/order/active` | Active orders, paginated |
+//! | `GET /orders/v1.0/order/maker/{address}` | A maker's orders, paginated |
+//! | `GET /orders/v1.0/order/status/{orderHash}` | An order's status and fills |
+//! | `POST /orders/v1.0/order/status/{orderHash}` | A resolver reporting its fill |
+//! | `POST /orders/v1.0/order/claim/{orderHash}` | A resolver claiming an order |
+//! | `GET /orders/v1.0/order/secrets/{orderHash}` | Secrets shared so far |
+
+use crate::{
+    book::{OrderBook, Paging},
+    store::OrderStore,
+    OrderbookError,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use stellar_htlc_sdk::{
+    fusion::{
+        ChainId, Claim, ClaimRequest, CrossChainOrder, OrderInfo, OrderSecrets, Page, Quote,
+        QuoteRequest, StatusReport, SubmitSecret,
+    },
+    Hash32,
+};
+
+/// What the handlers share: the book and the clock it runs on
+pub struct AppState<S> {
+    pub book: OrderBook<S>,
+    /// Unix time in seconds
+    pub clock: fn() -> u64,
+}
+
+impl<S> AppState<S> {
+    /// State on the system clock
+    pub fn new(book: OrderBook<S>) -> Self {
+        AppState {
+            book,
+            clock: unix_now,
+        }
+    }
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("clock after 1970")
+        .as_secs()
+}
+
+type Shared<S> = State<Arc<AppState<S>>>;
+type ApiResult<T> = Result<Json<T>, OrderbookError>;
+
+pub fn router<S: OrderStore>(state: Arc<AppState<S>>) -> Router {
+    Router::new()
+        .route("/quoter/v1.0/quote/receive", get(quote::<S>))
+        .route("/relayer/v1.0/submit", post(submit::<S>))
+        .route("/relayer/v1.0/submit/secret", post(submit_secret::<S>))
+        .route("/orders/v1.0/order/active", get(active::<S>))
+        .route("/orders/v1.0/order/maker/{address}", get(by_maker::<S>))
+        .route(
+            "/orders/v1.0/order/status/{order_hash}",
+            get(status::<S>).post(report::<S>),
+        )
+        .route("/orders/v1.0/order/claim/{order_hash}", post(claim::<S>))
+        .route("/orders/v1.0/order/secrets/{order_hash}", get(secrets::<S>))
+        .with_state(state)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListQuery {
+    page: Option<u32>,
+    limit: Option<u32>,
+    src_chain: Option<ChainId>,
+    dst_chain: Option<ChainId>,
+}
+
+async fn quote<S: OrderStore>(
+    State(state): Shared<S>,
+    Query(request): Query<QuoteRequest>,
+) -> ApiResult<Quote> {
+    Ok(Json(state.book.quote(&request, (state.clock)())?))
+}
+
+async fn submit<S: OrderStore>(
+    State(state): Shared<S>,
+    Json(order): Json<CrossChainOrder>,
+) -> Result<(StatusCode, Json<OrderInfo>), OrderbookError> {
+    let info = state.book.submit(order, (state.clock)()).await?;
+    Ok((StatusCode::CREATED, Json(info)))
+}
+
+async fn submit_secret<S: OrderStore>(
+    State(state): Shared<S>,
+    Json(shared): Json<SubmitSecret>,
+) -> Result<StatusCode, OrderbookError> {
+    state.book.share_secret(&shared, (state.clock)()).await?;
+    Ok(StatusCode::CREATED)
+}
+
+async fn active<S: OrderStore>(
+    State(state): Shared<S>,
+    Query(query): Query<ListQuery>,
+) -> ApiResult<Page<CrossChainOrder>> {
+    let paging = Paging::new(query.page, query.limit)?;
+    let page = state
+        .book
+        .active(query.src_chain, query.dst_chain, paging, (state.clock)())
+        .await?;
+    Ok(Json(page))
+}
+
+async fn by_maker<S: OrderStore>(
+    State(state): Shared<S>,
+    Path(address): Path<String>,
+    Query(query): Query<ListQuery>,
+) -> ApiResult<Page<OrderInfo>> {
+    let paging = Paging::new(query.page, query.limit)?;
+    let page = state
+        .book
+        .by_maker(&address, paging, (state.clock)())
+        .await?;
+    Ok(Json(page))
+}
+
+async fn status<S: OrderStore>(
+    State(state): Shared<S>,
+    Path(order_hash): Path<Hash32>,
+) -> ApiResult<OrderInfo> {
+    Ok(Json(state.book.status(order_hash, (state.clock)()).await?))
+}
+
+async fn report<S: OrderStore>(
+    State(state): Shared<S>,
+    Path(order_hash): Path<Hash32>,
+    Json(report): Json<StatusReport>,
+) -> ApiResult<OrderInfo> {
+    let info = state
+        .book
+        .report(order_hash, &report, (state.clock)())
+        .await?;
+    Ok(Json(info))
+}
+
+async fn claim<S: OrderStore>(
+    State(state): Shared<S>,
+    Path(order_hash): Path<Hash32>,
+    Json(request): Json<ClaimRequest>,
+) -> ApiResult<Claim> {
+    let claim = state
+        .book
+        .claim(order_hash, &request.resolver, (state.clock)())
+        .await?;
+    Ok(Json(claim))
+}
+
+async fn secrets<S: OrderStore>(
+    State(state): Shared<S>,
+    Path(order_hash): Path<Hash32>,
+) -> ApiResult<OrderSecrets> {
+    Ok(Json(state.book.secrets(order_hash).await?))
+}
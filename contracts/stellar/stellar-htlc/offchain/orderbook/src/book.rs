@@ -0,0 +1,433 @@
+//! What makers and resolvers may do to an order, on top of any `OrderStore`
+
+use crate::{
+    quote::Quoter,
+    store::{OrderFilter, OrderRecord, OrderStore},
+    OrderbookError,
+};
+use stellar_htlc_sdk::{
+    fusion::{
+        ChainId, Claim, CrossChainOrder, OrderFill, OrderInfo, OrderSecrets, OrderStatus, Page,
+        PageMeta, Quote, QuoteRequest, SharedSecret, StatusReport, SubmitSecret, TimeLocks,
+    },
+    secret, Hash32, HashAlgo,
+};
+
+/// Seconds a resolver keeps an order to itself after claiming it
+pub const DEFAULT_CLAIM_SECS: u64 = 60;
+
+/// Largest page the listings return
+pub const MAX_PAGE_LIMIT: u32 = 500;
+
+/// Updates of one order retried after losing a race with another
+const UPDATE_RETRIES: usize = 3;
+
+fn invalid(reason: impl Into<String>) -> OrderbookError {
+    OrderbookError::Invalid(reason.into())
+}
+
+fn conflict(reason: impl Into<String>) -> OrderbookError {
+    OrderbookError::Conflict(reason.into())
+}
+
+fn is_hex(value: &str, lengths: &[usize]) -> bool {
+    hex::decode(value.trim_start_matches("0x")).is_ok_and(|bytes| lengths.contains(&bytes.len()))
+}
+
+fn is_strkey(value: &str) -> bool {
+    matches!(
+        stellar_strkey::Strkey::from_string(value),
+        Ok(stellar_strkey::Strkey::PublicKeyEd25519(_) | stellar_strkey::Strkey::Contract(_))
+    )
+}
+
+/// Stages of each escrow must come in order, and the destination one must expire before
+/// the source one can be cancelled, so the resolver can still withdraw it with the secret
+fn check_time_locks(time_locks: &TimeLocks) -> Result<(), OrderbookError> {
+    let src = [
+        time_locks.src_withdrawal,
+        time_locks.src_public_withdrawal,
+        time_locks.src_cancellation,
+        time_locks.src_public_cancellation,
+    ];
+    let dst = [
+        time_locks.dst_withdrawal,
+        time_locks.dst_public_withdrawal,
+        time_locks.dst_cancellation,
+    ];
+    if src.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return Err(invalid("source time locks are not increasing"));
+    }
+    if dst.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return Err(invalid("destination time locks are not increasing"));
+    }
+    if time_locks.dst_cancellation >= time_locks.src_cancellation {
+        return Err(invalid(
+            "destination cancellation must come before source cancellation",
+        ));
+    }
+    Ok(())
+}
+
+/// Checks a submitted order at unix time `now`. The maker's signature is only checked for
+/// its shape: the Limit Order Protocol verifies it when the order is filled.
+pub fn validate(order: &CrossChainOrder, now: u64) -> Result<(), OrderbookError> {
+    if order.order_hash == Hash32::default() {
+        return Err(invalid("orderHash is not set"));
+    }
+    match (order.src_chain_id, order.dst_chain_id) {
+        (ChainId::Evm(_), ChainId::Stellar) | (ChainId::Stellar, ChainId::Evm(_)) => {}
+        _ => return Err(invalid("orders swap between Stellar and an EVM chain")),
+    }
+    if order.order.making_amount == 0 || order.order.taking_amount == 0 {
+        return Err(invalid("amounts must not be zero"));
+    }
+    if !is_hex(&order.signature, &[64, 65]) {
+        return Err(invalid("signature is not a 64 or 65-byte hex signature"));
+    }
+    if hex::decode(order.extension.trim_start_matches("0x")).is_err() {
+        return Err(invalid("extension is not hex"));
+    }
+    if order.deadline <= now {
+        return Err(invalid("deadline has passed"));
+    }
+    if order.auction.duration == 0 || order.auction.start_time > order.deadline {
+        return Err(invalid("auction must run before the deadline"));
+    }
+    if matches!(&order.secret_hashes, Some(hashes) if hashes.len() < 2) {
+        return Err(invalid("multi-fill orders need at least 2 secret hashes"));
+    }
+    check_time_locks(&order.time_locks)?;
+    let stellar_leg = match order.dst_chain_id {
+        ChainId::Stellar => [&order.dst_token, &order.dst_receiver],
+        ChainId::Evm(_) => [&order.order.maker_asset, &order.order.maker],
+    };
+    if !stellar_leg.iter().all(|address| is_strkey(address)) {
+        return Err(invalid("Stellar token and account must be strkeys"));
+    }
+    Ok(())
+}
+
+/// Gives `resolver` the order until `now + claim_secs`. A resolver may renew its own
+/// claim, but not take one that has not expired yet.
+pub fn claim(
+    record: &mut OrderRecord,
+    resolver: &str,
+    now: u64,
+    claim_secs: u64,
+) -> Result<Claim, OrderbookError> {
+    if record.status_at(now) != OrderStatus::Pending {
+        return Err(conflict("order is not pending"));
+    }
+    if record.order.remaining_maker_amount == 0 {
+        return Err(conflict("order is filled"));
+    }
+    match &record.claim {
+        Some(claim) if claim.resolver != resolver && claim.expires_at > now => {
+            return Err(conflict(format!(
+                "order is claimed by {} until {}",
+                claim.resolver, claim.expires_at
+            )));
+        }
+        _ => {}
+    }
+    let claim = Claim {
+        resolver: resolver.into(),
+        expires_at: now + claim_secs,
+    };
+    record.claim = Some(claim.clone());
+    Ok(claim)
+}
+
+/// Applies a resolver's report on the fill it claimed
+pub fn report(
+    record: &mut OrderRecord,
+    report: &StatusReport,
+    now: u64,
+) -> Result<(), OrderbookError> {
+    let resolver = match report {
+        StatusReport::Filled { resolver, .. }
+        | StatusReport::Executed { resolver }
+        | StatusReport::Refunded { resolver } => resolver,
+    };
+    if record
+        .claim
+        .as_ref()
+        .is_none_or(|claim| &claim.resolver != resolver)
+    {
+        return Err(conflict(format!("order is not claimed by {resolver}")));
+    }
+    if record.status != OrderStatus::Pending {
+        return Err(conflict("order is not pending"));
+    }
+    match report {
+        StatusReport::Filled {
+            tx_hash,
+            filled_maker_amount,
+            ..
+        } => {
+            if *filled_maker_amount == 0
+                || *filled_maker_amount > record.order.remaining_maker_amount
+            {
+                return Err(invalid("filled amount exceeds the remaining amount"));
+            }
+            if record.fills.iter().any(|fill| &fill.tx_hash == tx_hash) {
+                return Err(conflict(format!("fill {tx_hash} was already reported")));
+            }
+            record.order.remaining_maker_amount -= filled_maker_amount;
+            record.fills.push(OrderFill {
+                resolver: resolver.clone(),
+                tx_hash: tx_hash.clone(),
+                filled_maker_amount: *filled_maker_amount,
+            });
+        }
+        StatusReport::Executed { .. } | StatusReport::Refunded { .. }
+            if record.fills.is_empty() =>
+        {
+            return Err(conflict("no fill was reported"));
+        }
+        StatusReport::Executed { .. } => record.status = OrderStatus::Executed,
+        StatusReport::Refunded { .. } => record.status = OrderStatus::Refunded,
+    }
+    record.updated_at = now;
+    Ok(())
+}
+
+/// Records a secret the maker shared once a resolver filled the order. It must hash
+/// with Keccak-256, as on the EVM escrows, to the hashlock or to its part's secret hash.
+pub fn share_secret(
+    record: &mut OrderRecord,
+    shared: &SubmitSecret,
+    now: u64,
+) -> Result<(), OrderbookError> {
+    if record.fills.is_empty() {
+        return Err(conflict("order has no fill to share a secret with"));
+    }
+    let expected = match &record.order.secret_hashes {
+        None if shared.idx == 0 => record.order.hashlock,
+        None => return Err(invalid("single-fill orders only have secret 0")),
+        Some(hashes) => *hashes
+            .get(shared.idx as usize)
+            .ok_or_else(|| invalid(format!("order has no secret {}", shared.idx)))?,
+    };
+    if secret::hash(HashAlgo::Keccak256, &shared.secret.0) != expected {
+        return Err(invalid("secret does not match its hash"));
+    }
+    if !record.secrets.iter().any(|known| known.idx == shared.idx) {
+        record.secrets.push(SharedSecret {
+            idx: shared.idx,
+            secret: shared.secret,
+        });
+        record.secrets.sort_by_key(|known| known.idx);
+    }
+    record.updated_at = now;
+    Ok(())
+}
+
+pub fn info(record: &OrderRecord, now: u64) -> OrderInfo {
+    OrderInfo {
+        order_hash: record.order.order_hash,
+        status: record.status_at(now),
+        order: record.order.clone(),
+        claim: record.claim.clone(),
+        fills: record.fills.clone(),
+        created_at: record.created_at,
+        updated_at: record.updated_at,
+    }
+}
+
+/// Page `page` of `limit` items, counting from 1
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Paging {
+    pub page: u32,
+    pub limit: u32,
+}
+
+impl Paging {
+    pub fn new(page: Option<u32>, limit: Option<u32>) -> Result<Self, OrderbookError> {
+        let paging = Paging {
+            page: page.unwrap_or(1),
+            limit: limit.unwrap_or(100),
+        };
+        if paging.page == 0 || paging.limit == 0 || paging.limit > MAX_PAGE_LIMIT {
+            return Err(invalid(format!(
+                "page starts at 1 and limit is between 1 and {MAX_PAGE_LIMIT}"
+            )));
+        }
+        Ok(paging)
+    }
+
+    fn offset(&self) -> u64 {
+        u64::from(self.page - 1) * u64::from(self.limit)
+    }
+
+    fn meta(&self, total_items: u64) -> PageMeta {
+        PageMeta {
+            total_items,
+            items_per_page: self.limit,
+            total_pages: total_items.div_ceil(self.limit.into()) as u32,
+            current_page: self.page,
+        }
+    }
+}
+
+/// The order book's operations, over store `S`
+pub struct OrderBook<S> {
+    store: S,
+    quoter: Option<Quoter>,
+    claim_secs: u64,
+}
+
+impl<S: OrderStore> OrderBook<S> {
+    /// A book answering quotes with `quoter`, if any
+    pub fn new(store: S, quoter: Option<Quoter>, claim_secs: u64) -> Self {
+        OrderBook {
+            store,
+            quoter,
+            claim_secs,
+        }
+    }
+
+    pub fn quote(&self, request: &QuoteRequest, now: u64) -> Result<Quote, OrderbookError> {
+        self.quoter
+            .as_ref()
+            .ok_or_else(|| OrderbookError::Unavailable("quotes are not configured".into()))?
+            .quote(request, now)
+    }
+
+    pub async fn submit(
+        &self,
+        mut order: CrossChainOrder,
+        now: u64,
+    ) -> Result<OrderInfo, OrderbookError> {
+        validate(&order, now)?;
+        order.remaining_maker_amount = order.order.making_amount;
+        let record = OrderRecord::new(order, now);
+        self.store.insert(record.clone()).await?;
+        Ok(info(&record, now))
+    }
+
+    async fn record(&self, order_hash: Hash32) -> Result<OrderRecord, OrderbookError> {
+        self.store
+            .get(order_hash)
+            .await?
+            .ok_or_else(|| OrderbookError::NotFound(format!("order {order_hash}")))
+    }
+
+    /// Applies `change` to the stored order, again on a newer version if another request
+    /// updated it in between
+    async fn modify<T>(
+        &self,
+        order_hash: Hash32,
+        change: impl Fn(&mut OrderRecord) -> Result<T, OrderbookError>,
+    ) -> Result<(T, OrderRecord), OrderbookError> {
+        for _ in 0..UPDATE_RETRIES {
+            let mut record = self.record(order_hash).await?;
+            let changed = change(&mut record)?;
+            record.version += 1;
+            if self.store.update(record.clone()).await? {
+                return Ok((changed, record));
+            }
+        }
+        Err(conflict(format!("order {order_hash} is being updated")))
+    }
+
+    pub async fn status(&self, order_hash: Hash32, now: u64) -> Result<OrderInfo, OrderbookError> {
+        Ok(info(&self.record(order_hash).await?, now))
+    }
+
+    pub async fn active(
+        &self,
+        src_chain: Option<ChainId>,
+        dst_chain: Option<ChainId>,
+        paging: Paging,
+        now: u64,
+    ) -> Result<Page<CrossChainOrder>, OrderbookError> {
+        let filter = OrderFilter {
+            active_at: Some(now),
+            src_chain,
+            dst_chain,
+            maker: None,
+        };
+        let (total, records) = self
+            .store
+            .list(&filter, paging.offset(), paging.limit)
+            .await?;
+        Ok(Page {
+            meta: paging.meta(total),
+            items: records.into_iter().map(|record| record.order).collect(),
+        })
+    }
+
+    pub async fn by_maker(
+        &self,
+        maker: &str,
+        paging: Paging,
+        now: u64,
+    ) -> Result<Page<OrderInfo>, OrderbookError> {
+        let filter = OrderFilter {
+            maker: Some(maker.into()),
+            ..OrderFilter::default()
+        };
+        let (total, records) = self
+            .store
+            .list(&filter, paging.offset(), paging.limit)
+            .await?;
+        Ok(Page {
+            meta: paging.meta(total),
+            items: records.iter().map(|record| info(record, now)).collect(),
+        })
+    }
+
+    pub async fn claim(
+        &self,
+        order_hash: Hash32,
+        resolver: &str,
+        now: u64,
+    ) -> Result<Claim, OrderbookError> {
+        let (claim, _) = self
+            .modify(order_hash, |record| {
+                claim(record, resolver, now, self.claim_secs)
+            })
+            .await?;
+        Ok(claim)
+    }
+
+    pub async fn report(
+        &self,
+        order_hash: Hash32,
+        status: &StatusReport,
+        now: u64,
+    ) -> Result<OrderInfo, OrderbookError> {
+        let ((), record) = self
+            .modify(order_hash, |record| report(record, status, now))
+            .await?;
+        Ok(info(&record, now))
+    }
+
+    pub async fn share_secret(
+        &self,
+        shared: &SubmitSecret,
+        now: u64,
+    ) -> Result<(), OrderbookError> {
+        self.modify(shared.order_hash, |record| {
+            share_secret(record, shared, now)
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// The secrets shared so far; `NotFound` before the first
+    pub async fn secrets(&self, order_hash: Hash32) -> Result<OrderSecrets, OrderbookError> {
+        let record = self.record(order_hash).await?;
+        if record.secrets.is_empty() {
+            return Err(OrderbookError::NotFound(format!(
+                "secrets of order {order_hash}"
+            )));
+        }
+        Ok(OrderSecrets {
+            order_hash,
+            secrets: record.secrets,
+        })
+    }
+}
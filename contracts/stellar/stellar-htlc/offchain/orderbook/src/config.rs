@@ -0,0 +1,60 @@
+use crate::{
+    book::DEFAULT_CLAIM_SECS,
+    quote::{QuoteSettings, DEFAULT_SPREAD_BPS, DEFAULT_TIME_LOCKS},
+    OrderbookError,
+};
+use std::{env, net::SocketAddr, path::PathBuf};
+
+/// Order book settings, read from the environment
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Address the API listens on, `ORDERBOOK_LISTEN`
+    pub listen: SocketAddr,
+    /// Snapshot of the orders, `ORDERBOOK_STATE_PATH`; kept in memory only if unset
+    pub state_path: Option<PathBuf>,
+    /// Price list quotes are priced from, `ORDERBOOK_PRICES`; quotes are disabled if unset
+    pub prices: Option<PathBuf>,
+    /// `ORDERBOOK_QUOTE_SPREAD_BPS`, `ORDERBOOK_SRC_SAFETY_DEPOSIT` and
+    /// `ORDERBOOK_DST_SAFETY_DEPOSIT`
+    pub quotes: QuoteSettings,
+    /// Seconds a claim lasts, `ORDERBOOK_CLAIM_SECS`
+    pub claim_secs: u64,
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self, OrderbookError> {
+        let spread_bps = optional("ORDERBOOK_QUOTE_SPREAD_BPS")?.unwrap_or(DEFAULT_SPREAD_BPS);
+        if spread_bps >= 10_000 {
+            return Err(OrderbookError::Config(
+                "ORDERBOOK_QUOTE_SPREAD_BPS must be below 10000".into(),
+            ));
+        }
+        Ok(Config {
+            listen: env::var("ORDERBOOK_LISTEN")
+                .unwrap_or_else(|_| "0.0.0.0:8080".into())
+                .parse()
+                .map_err(|_| {
+                    OrderbookError::Config("ORDERBOOK_LISTEN is not a socket address".into())
+                })?,
+            state_path: env::var("ORDERBOOK_STATE_PATH").ok().map(Into::into),
+            prices: env::var("ORDERBOOK_PRICES").ok().map(Into::into),
+            quotes: QuoteSettings {
+                spread_bps,
+                time_locks: DEFAULT_TIME_LOCKS,
+                src_safety_deposit: optional("ORDERBOOK_SRC_SAFETY_DEPOSIT")?.unwrap_or(0),
+                dst_safety_deposit: optional("ORDERBOOK_DST_SAFETY_DEPOSIT")?.unwrap_or(0),
+            },
+            claim_secs: optional("ORDERBOOK_CLAIM_SECS")?.unwrap_or(DEFAULT_CLAIM_SECS),
+        })
+    }
+}
+
+fn optional<T: std::str::FromStr>(name: &str) -> Result<Option<T>, OrderbookError> {
+    match env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| OrderbookError::Config(format!("{name} is not a valid number"))),
+        Err(_) => Ok(None),
+    }
+}
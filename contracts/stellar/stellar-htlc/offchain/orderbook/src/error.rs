@@ -0,0 +1,58 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum OrderbookError {
+    #[error("invalid configuration: {0}")]
+    Config(String),
+    #[error("{0}")]
+    Invalid(String),
+    #[error("{0} not found")]
+    NotFound(String),
+    #[error("{0}")]
+    Conflict(String),
+    #[error("{0}")]
+    Unavailable(String),
+    #[error("storage error: {0}")]
+    Store(String),
+    #[error("storage I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+impl OrderbookError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            OrderbookError::Invalid(_) => StatusCode::BAD_REQUEST,
+            OrderbookError::NotFound(_) => StatusCode::NOT_FOUND,
+            OrderbookError::Conflict(_) => StatusCode::CONFLICT,
+            OrderbookError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            OrderbookError::Config(_)
+            | OrderbookError::Store(_)
+            | OrderbookError::Io(_)
+            | OrderbookError::Json(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// Errors in the API's `{statusCode, error, description}` shape
+impl IntoResponse for OrderbookError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        if status.is_server_error() {
+            tracing::error!(error = %self, "request failed");
+        }
+        let body = json!({
+            "statusCode": status.as_u16(),
+            "error": status.canonical_reason().unwrap_or_default(),
+            "description": self.to_string(),
+        });
+        (status, Json(body)).into_response()
+    }
+}
@@ -0,0 +1,19 @@
+//! Order book service for cross-chain orders between Stellar and EVM chains.
+//!
+//! Makers submit signed orders and, once a resolver filled them, the secrets opening their
+//! escrows. Resolvers list the active orders, claim one for an exclusive window and report
+//! the fill's progress. The REST API follows the paths and the JSON schemas of the 1inch
+//! Fusion+ API, shared with the resolver through `stellar_htlc_sdk::fusion`, so a resolver
+//! can point at either. Orders live behind the `OrderStore` trait; `MemoryStore` keeps
+//! them in memory with an optional JSON snapshot.
+
+pub mod api;
+pub mod book;
+pub mod config;
+pub mod error;
+pub mod quote;
+pub mod store;
+
+pub use error::OrderbookError;
+
+mod test;
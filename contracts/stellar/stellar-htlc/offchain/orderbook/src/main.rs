@@ -0,0 +1,40 @@
+use std::sync::Arc;
+use stellar_htlc_orderbook::{
+    api::{self, AppState},
+    book::OrderBook,
+    config::Config,
+    quote::{PriceList, Quoter},
+    store::MemoryStore,
+    OrderbookError,
+};
+use tracing::info;
+use tracing_subscriber::EnvFilter;
+
+#[tokio::main]
+async fn main() -> Result<(), OrderbookError> {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+
+    let config = Config::from_env()?;
+    let store = match &config.state_path {
+        Some(path) => MemoryStore::open(path)?,
+        None => MemoryStore::default(),
+    };
+    let quoter = match &config.prices {
+        Some(path) => Some(Quoter::new(PriceList::read(path)?, config.quotes)),
+        None => None,
+    };
+    let book = OrderBook::new(store, quoter, config.claim_secs);
+    let app = api::router(Arc::new(AppState::new(book)));
+
+    let listener = tokio::net::TcpListener::bind(config.listen).await?;
+    info!(listen = %config.listen, quotes = config.prices.is_some(), "order book listening");
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+    info!("order book stopped");
+    Ok(())
+}
@@ -0,0 +1,155 @@
+//! Quotes priced from a static price list, with auction presets of increasing length
+
+use crate::OrderbookError;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use stellar_htlc_sdk::fusion::{
+    GasCostConfig, Preset, Quote, QuotePresets, QuoteRequest, TimeLocks,
+};
+
+/// Spread below the spot price the auctions end at, in basis points
+pub const DEFAULT_SPREAD_BPS: u32 = 50;
+
+/// Seconds after submission the auctions start
+pub const START_AUCTION_IN: u64 = 12;
+
+/// Auction length of the fast preset; medium and slow run twice and four times as long
+pub const FAST_AUCTION_SECS: u64 = 180;
+
+/// Escrow stages the quotes propose
+pub const DEFAULT_TIME_LOCKS: TimeLocks = TimeLocks {
+    src_withdrawal: 36,
+    src_public_withdrawal: 372,
+    src_cancellation: 528,
+    src_public_cancellation: 648,
+    dst_withdrawal: 36,
+    dst_public_withdrawal: 336,
+    dst_cancellation: 456,
+};
+
+/// Spot price of a token
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TokenPrice {
+    pub decimals: u8,
+    pub usd: f64,
+}
+
+/// Token prices, in the shape of the `tokens` of the resolver's price book
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PriceList {
+    /// By EVM address or Stellar contract strkey, in any case
+    pub tokens: HashMap<String, TokenPrice>,
+}
+
+impl PriceList {
+    pub fn read(path: &Path) -> Result<Self, OrderbookError> {
+        Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+
+    pub fn token(&self, token: &str) -> Option<&TokenPrice> {
+        self.tokens
+            .iter()
+            .find(|(address, _)| address.eq_ignore_ascii_case(token))
+            .map(|(_, price)| price)
+    }
+}
+
+/// How quotes are priced, besides the prices
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QuoteSettings {
+    pub spread_bps: u32,
+    pub time_locks: TimeLocks,
+    /// In the source token's smallest unit
+    pub src_safety_deposit: u128,
+    /// In the destination token's smallest unit
+    pub dst_safety_deposit: u128,
+}
+
+impl Default for QuoteSettings {
+    fn default() -> Self {
+        QuoteSettings {
+            spread_bps: DEFAULT_SPREAD_BPS,
+            time_locks: DEFAULT_TIME_LOCKS,
+            src_safety_deposit: 0,
+            dst_safety_deposit: 0,
+        }
+    }
+}
+
+pub struct Quoter {
+    prices: PriceList,
+    settings: QuoteSettings,
+    issued: AtomicU64,
+}
+
+impl Quoter {
+    pub fn new(prices: PriceList, settings: QuoteSettings) -> Self {
+        Quoter {
+            prices,
+            settings,
+            issued: AtomicU64::new(0),
+        }
+    }
+
+    /// Quotes `request` at unix time `now`. The auctions start at the spot value of the
+    /// source amount in the destination token and end `spread_bps` below it.
+    pub fn quote(&self, request: &QuoteRequest, now: u64) -> Result<Quote, OrderbookError> {
+        if request.src_chain == request.dst_chain {
+            return Err(OrderbookError::Invalid(
+                "source and destination chains are the same".into(),
+            ));
+        }
+        if request.amount == 0 {
+            return Err(OrderbookError::Invalid("amount must not be zero".into()));
+        }
+        let price = |token: &str| {
+            self.prices
+                .token(token)
+                .ok_or_else(|| OrderbookError::NotFound(format!("price of {token}")))
+        };
+        let src = price(&request.src_token_address)?;
+        let dst = price(&request.dst_token_address)?;
+        let usd = request.amount as f64 / 10f64.powi(src.decimals.into()) * src.usd;
+        let start_amount = (usd / dst.usd * 10f64.powi(dst.decimals.into())) as u128;
+        let end_amount =
+            start_amount - start_amount * u128::from(self.settings.spread_bps) / 10_000;
+        if end_amount == 0 {
+            return Err(OrderbookError::Invalid(
+                "amount is too small to quote".into(),
+            ));
+        }
+        let initial_rate_bump = fusion_auction::initial_rate_bump(start_amount, end_amount)
+            .ok()
+            .and_then(|bump| u32::try_from(bump).ok())
+            .ok_or_else(|| OrderbookError::Config("quote spread is too wide".into()))?;
+        let preset = |auction_duration| Preset {
+            auction_duration,
+            start_auction_in: START_AUCTION_IN,
+            initial_rate_bump,
+            auction_start_amount: start_amount,
+            auction_end_amount: end_amount,
+            points: Vec::new(),
+            gas_cost: GasCostConfig::default(),
+            secrets_count: 1,
+        };
+        let issued = self.issued.fetch_add(1, Ordering::Relaxed);
+        Ok(Quote {
+            quote_id: format!("{now:x}-{issued}"),
+            src_token_amount: request.amount,
+            dst_token_amount: start_amount,
+            presets: QuotePresets {
+                fast: preset(FAST_AUCTION_SECS),
+                medium: preset(FAST_AUCTION_SECS * 2),
+                slow: preset(FAST_AUCTION_SECS * 4),
+            },
+            recommended_preset: "fast".into(),
+            time_locks: self.settings.time_locks,
+            src_safety_deposit: self.settings.src_safety_deposit,
+            dst_safety_deposit: self.settings.dst_safety_deposit,
+        })
+    }
+}
@@ -0,0 +1,213 @@
+//! Storage of the order book, behind the `OrderStore` trait
+//!
+//! A store only keeps records; the book decides what changes. Updates are optimistic: each
+//! record carries a version, and a store only replaces the version it was read at, so two
+//! requests changing one order cannot both succeed.
+
+use crate::OrderbookError;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    future::Future,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+use stellar_htlc_sdk::{
+    fusion::{ChainId, Claim, CrossChainOrder, OrderFill, OrderStatus, SharedSecret},
+    Hash32,
+};
+
+/// An order and everything the book learned about it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrderRecord {
+    pub order: CrossChainOrder,
+    /// Stored status; `Expired` is never stored, see `status_at`
+    pub status: OrderStatus,
+    pub claim: Option<Claim>,
+    pub fills: Vec<OrderFill>,
+    /// Secrets the maker shared, by part
+    pub secrets: Vec<SharedSecret>,
+    pub created_at: u64,
+    pub updated_at: u64,
+    /// Incremented by every update
+    pub version: u64,
+}
+
+impl OrderRecord {
+    pub fn new(order: CrossChainOrder, now: u64) -> Self {
+        OrderRecord {
+            order,
+            status: OrderStatus::Pending,
+            claim: None,
+            fills: Vec::new(),
+            secrets: Vec::new(),
+            created_at: now,
+            updated_at: now,
+            version: 0,
+        }
+    }
+
+    /// Status at unix time `now`: a pending order nobody filled expires after its deadline
+    pub fn status_at(&self, now: u64) -> OrderStatus {
+        match self.status {
+            OrderStatus::Pending if self.fills.is_empty() && now > self.order.deadline => {
+                OrderStatus::Expired
+            }
+            status => status,
+        }
+    }
+
+    /// Whether an unclaimed, or no longer claimed, resolver may fill it at `now`
+    pub fn is_active(&self, now: u64) -> bool {
+        self.status_at(now) == OrderStatus::Pending
+            && self.order.remaining_maker_amount > 0
+            && self
+                .claim
+                .as_ref()
+                .is_none_or(|claim| claim.expires_at <= now)
+    }
+}
+
+/// Which orders a listing returns
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OrderFilter {
+    /// Only orders active at this unix time
+    pub active_at: Option<u64>,
+    pub src_chain: Option<ChainId>,
+    pub dst_chain: Option<ChainId>,
+    /// Only orders of this maker, in any case
+    pub maker: Option<String>,
+}
+
+impl OrderFilter {
+    pub fn matches(&self, record: &OrderRecord) -> bool {
+        let order = &record.order;
+        self.active_at.is_none_or(|now| record.is_active(now))
+            && self
+                .src_chain
+                .is_none_or(|chain| order.src_chain_id == chain)
+            && self
+                .dst_chain
+                .is_none_or(|chain| order.dst_chain_id == chain)
+            && self
+                .maker
+                .as_ref()
+                .is_none_or(|maker| order.order.maker.eq_ignore_ascii_case(maker))
+    }
+}
+
+/// Backend of the order book. Listings are in creation order, oldest first.
+pub trait OrderStore: Send + Sync + 'static {
+    /// Adds a new order; fails with `Conflict` if its hash is taken
+    fn insert(
+        &self,
+        record: OrderRecord,
+    ) -> impl Future<Output = Result<(), OrderbookError>> + Send;
+
+    fn get(
+        &self,
+        order_hash: Hash32,
+    ) -> impl Future<Output = Result<Option<OrderRecord>, OrderbookError>> + Send;
+
+    /// Replaces the order with `record` if it is still at `record.version - 1`. Returns
+    /// whether it was replaced.
+    fn update(
+        &self,
+        record: OrderRecord,
+    ) -> impl Future<Output = Result<bool, OrderbookError>> + Send;
+
+    /// The matching orders from `offset` on, at most `limit` of them, and how many match
+    fn list(
+        &self,
+        filter: &OrderFilter,
+        offset: u64,
+        limit: u32,
+    ) -> impl Future<Output = Result<(u64, Vec<OrderRecord>), OrderbookError>> + Send;
+}
+
+/// Orders held in memory, optionally snapshotted to a JSON file after every change
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    orders: RwLock<BTreeMap<Hash32, OrderRecord>>,
+    path: Option<PathBuf>,
+}
+
+impl MemoryStore {
+    /// A store persisted at `path`, loaded from it if it exists
+    pub fn open(path: &Path) -> Result<Self, OrderbookError> {
+        let orders = match fs::read(path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(e) if e.kind() == ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(MemoryStore {
+            orders: RwLock::new(orders),
+            path: Some(path.into()),
+        })
+    }
+
+    /// Saves the snapshot atomically, so a crash leaves either the old or the new one
+    fn save(&self, orders: &BTreeMap<Hash32, OrderRecord>) -> Result<(), OrderbookError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let tmp = path.with_extension("tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(&serde_json::to_vec_pretty(orders)?)?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+impl OrderStore for MemoryStore {
+    async fn insert(&self, record: OrderRecord) -> Result<(), OrderbookError> {
+        let mut orders = self.orders.write().expect("store lock poisoned");
+        let order_hash = record.order.order_hash;
+        if orders.contains_key(&order_hash) {
+            return Err(OrderbookError::Conflict(format!(
+                "order {order_hash} already exists"
+            )));
+        }
+        orders.insert(order_hash, record);
+        self.save(&orders)
+    }
+
+    async fn get(&self, order_hash: Hash32) -> Result<Option<OrderRecord>, OrderbookError> {
+        let orders = self.orders.read().expect("store lock poisoned");
+        Ok(orders.get(&order_hash).cloned())
+    }
+
+    async fn update(&self, record: OrderRecord) -> Result<bool, OrderbookError> {
+        let mut orders = self.orders.write().expect("store lock poisoned");
+        match orders.get_mut(&record.order.order_hash) {
+            Some(stored) if stored.version + 1 == record.version => *stored = record,
+            _ => return Ok(false),
+        }
+        self.save(&orders)?;
+        Ok(true)
+    }
+
+    async fn list(
+        &self,
+        filter: &OrderFilter,
+        offset: u64,
+        limit: u32,
+    ) -> Result<(u64, Vec<OrderRecord>), OrderbookError> {
+        let orders = self.orders.read().expect("store lock poisoned");
+        let mut matching: Vec<_> = orders
+            .values()
+            .filter(|record| filter.matches(record))
+            .collect();
+        matching.sort_by_key(|record| (record.created_at, record.order.order_hash));
+        let page = matching
+            .iter()
+            .skip(offset.try_into().unwrap_or(usize::MAX))
+            .take(limit as usize)
+            .map(|record| (*record).clone())
+            .collect();
+        Ok((matching.len() as u64, page))
+    }
+}
@@ -0,0 +1,517 @@
+#![cfg(test)]
+
+use crate::{
+    api::{self, AppState},
+    book::{self, OrderBook},
+    quote::{PriceList, QuoteSettings, Quoter, TokenPrice, DEFAULT_TIME_LOCKS},
+    store::{MemoryStore, OrderFilter, OrderRecord, OrderStore},
+    OrderbookError,
+};
+use axum::{
+    body::Body,
+    http::{Method, Request, StatusCode},
+    Router,
+};
+use http_body_util::BodyExt;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use stellar_htlc_sdk::{
+    fusion::{ChainId, CrossChainOrder, OrderStatus, QuoteRequest, StatusReport, SubmitSecret},
+    secret,
+    testutils::PREIMAGE,
+    Hash32, HashAlgo,
+};
+use tower::ServiceExt;
+
+const WETH: &str = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2";
+const USDC: &str = "CDLZFC3SYJYDZT7K67VZ75HPJVIEUVNIXF47ZG2FB2RMQQVU2HHGCYSC";
+const RECEIVER: &str = "GCATS5YOVB6ROX2WUNKGNQ2MP3GMXDMKSG2O4N5CLX3A6W4PZGZZI55U";
+const NOW: u64 = 1000;
+
+/// 1 WETH for 2900 USDC on Stellar, as its maker submits it
+fn order_json(order_hash: u8) -> Value {
+    json!({
+        "orderHash": hex::encode([order_hash; 32]),
+        "quoteId": "quote",
+        "srcChainId": 1,
+        "dstChainId": "stellar",
+        "order": {
+            "salt": "42",
+            "maker": "0x00000000000000000000000000000000000000aa",
+            "receiver": "0x0000000000000000000000000000000000000000",
+            "makerAsset": WETH,
+            "takerAsset": "0x00000000000000000000000000000000000000bb",
+            "makingAmount": "1000000000000000000",
+            "takingAmount": "29000000000",
+            "makerTraits": "0"
+        },
+        "signature": format!("0x{}1b", "01".repeat(64)),
+        "extension": "0x",
+        "deadline": NOW + 600,
+        "auction": {"startTime": NOW, "duration": 180, "initialRateBump": 50000},
+        "hashlock": secret::hash(HashAlgo::Keccak256, &PREIMAGE.0),
+        "timeLocks": DEFAULT_TIME_LOCKS,
+        "srcSafetyDeposit": "0",
+        "dstSafetyDeposit": "0",
+        "dstToken": USDC,
+        "dstReceiver": RECEIVER
+    })
+}
+
+fn order(order_hash: u8) -> CrossChainOrder {
+    let mut order: CrossChainOrder = serde_json::from_value(order_json(order_hash)).unwrap();
+    order.remaining_maker_amount = order.order.making_amount;
+    order
+}
+
+fn quoter() -> Quoter {
+    let token = |decimals, usd| TokenPrice { decimals, usd };
+    let prices = PriceList {
+        tokens: [
+            (WETH.to_lowercase(), token(18, 3000.0)),
+            (USDC.into(), token(7, 1.0)),
+        ]
+        .into_iter()
+        .collect(),
+    };
+    Quoter::new(prices, QuoteSettings::default())
+}
+
+fn filled(resolver: &str, tx_hash: &str) -> StatusReport {
+    StatusReport::Filled {
+        resolver: resolver.into(),
+        tx_hash: tx_hash.into(),
+        filled_maker_amount: 1_000_000_000_000_000_000,
+    }
+}
+
+//------------------------------------------------------------------
+//  Orders
+//------------------------------------------------------------------
+#[test]
+fn submitted_orders_are_validated() {
+    assert!(book::validate(&order(1), NOW).is_ok());
+    let rejected = |change: fn(&mut CrossChainOrder), reason: &str| {
+        let mut order = order(1);
+        change(&mut order);
+        match book::validate(&order, NOW) {
+            Err(OrderbookError::Invalid(error)) => assert!(error.contains(reason), "{error}"),
+            result => panic!("{result:?}"),
+        }
+    };
+    rejected(|order| order.order_hash = Hash32::default(), "orderHash");
+    rejected(
+        |order| order.dst_chain_id = ChainId::Evm(10),
+        "Stellar and an EVM",
+    );
+    rejected(|order| order.order.taking_amount = 0, "zero");
+    rejected(|order| order.signature = "0x1234".into(), "signature");
+    rejected(|order| order.deadline = NOW, "deadline");
+    rejected(|order| order.auction.duration = 0, "auction");
+    rejected(
+        |order| order.secret_hashes = Some(vec![PREIMAGE]),
+        "multi-fill",
+    );
+    rejected(
+        |order| order.time_locks.src_public_withdrawal = 10,
+        "source time locks",
+    );
+    rejected(
+        |order| order.time_locks.dst_cancellation = 600,
+        "destination",
+    );
+    rejected(
+        |order| order.time_locks.dst_cancellation = order.time_locks.src_cancellation,
+        "before source cancellation",
+    );
+    rejected(|order| order.dst_receiver = WETH.into(), "strkeys");
+}
+
+#[test]
+fn claims_are_exclusive_until_they_expire() {
+    let mut record = OrderRecord::new(order(1), NOW);
+    assert!(record.is_active(NOW));
+    let claim = book::claim(&mut record, "resolver-a", NOW, 60).unwrap();
+    assert_eq!(claim.expires_at, NOW + 60);
+    assert!(!record.is_active(NOW + 59));
+    assert!(matches!(
+        book::claim(&mut record, "resolver-b", NOW + 59, 60),
+        Err(OrderbookError::Conflict(_))
+    ));
+    // Renewed by its holder, and open to others once expired
+    assert_eq!(
+        book::claim(&mut record, "resolver-a", NOW + 30, 60)
+            .unwrap()
+            .expires_at,
+        NOW + 90
+    );
+    assert!(record.is_active(NOW + 90));
+    assert_eq!(
+        book::claim(&mut record, "resolver-b", NOW + 90, 60)
+            .unwrap()
+            .resolver,
+        "resolver-b"
+    );
+
+    // Unfilled orders expire after their deadline
+    let record = OrderRecord::new(order(2), NOW);
+    assert_eq!(record.status_at(NOW + 600), OrderStatus::Pending);
+    assert_eq!(record.status_at(NOW + 601), OrderStatus::Expired);
+    assert!(!record.is_active(NOW + 601));
+    let mut expired = record.clone();
+    assert!(book::claim(&mut expired, "resolver-a", NOW + 601, 60).is_err());
+}
+
+#[test]
+fn fills_are_reported_by_their_claimant() {
+    let mut record = OrderRecord::new(order(1), NOW);
+    let executed = StatusReport::Executed {
+        resolver: "resolver-a".into(),
+    };
+    assert!(book::report(&mut record, &filled("resolver-a", "0x01"), NOW).is_err());
+    book::claim(&mut record, "resolver-a", NOW, 60).unwrap();
+    assert!(book::report(&mut record, &filled("resolver-b", "0x01"), NOW).is_err());
+    assert!(book::report(&mut record, &executed, NOW).is_err());
+
+    book::report(&mut record, &filled("resolver-a", "0x01"), NOW + 5).unwrap();
+    assert_eq!(record.order.remaining_maker_amount, 0);
+    assert_eq!(record.fills[0].tx_hash, "0x01");
+    assert_eq!(record.updated_at, NOW + 5);
+    // Filled orders stay pending past the deadline, and cannot be filled again
+    assert_eq!(record.status_at(NOW + 601), OrderStatus::Pending);
+    assert!(!record.is_active(NOW + 61));
+    assert!(book::report(&mut record, &filled("resolver-a", "0x02"), NOW).is_err());
+
+    book::report(&mut record, &executed, NOW + 10).unwrap();
+    assert_eq!(record.status, OrderStatus::Executed);
+    assert!(book::report(&mut record, &executed, NOW + 10).is_err());
+}
+
+#[test]
+fn secrets_must_match_their_hash() {
+    let shared = |idx, secret| SubmitSecret {
+        order_hash: order(1).order_hash,
+        idx,
+        secret,
+    };
+    let mut record = OrderRecord::new(order(1), NOW);
+    assert!(matches!(
+        book::share_secret(&mut record, &shared(0, PREIMAGE), NOW),
+        Err(OrderbookError::Conflict(_))
+    ));
+    book::claim(&mut record, "resolver-a", NOW, 60).unwrap();
+    book::report(&mut record, &filled("resolver-a", "0x01"), NOW).unwrap();
+    assert!(book::share_secret(&mut record, &shared(0, Hash32([1; 32])), NOW).is_err());
+    assert!(book::share_secret(&mut record, &shared(1, PREIMAGE), NOW).is_err());
+    book::share_secret(&mut record, &shared(0, PREIMAGE), NOW).unwrap();
+    book::share_secret(&mut record, &shared(0, PREIMAGE), NOW).unwrap();
+    assert_eq!(record.secrets.len(), 1);
+
+    // Parts of a multi-fill order open with their own secret
+    let parts = [Hash32([1; 32]), Hash32([2; 32])];
+    let mut record = OrderRecord::new(order(2), NOW);
+    record.order.secret_hashes = Some(
+        parts
+            .iter()
+            .map(|part| secret::hash(HashAlgo::Keccak256, &part.0))
+            .collect(),
+    );
+    book::claim(&mut record, "resolver-a", NOW, 60).unwrap();
+    book::report(&mut record, &filled("resolver-a", "0x01"), NOW).unwrap();
+    let order_hash = record.order.order_hash;
+    let part = |idx, secret| SubmitSecret {
+        order_hash,
+        idx,
+        secret,
+    };
+    assert!(book::share_secret(&mut record.clone(), &part(0, parts[1]), NOW).is_err());
+    book::share_secret(&mut record, &part(1, parts[1]), NOW).unwrap();
+    book::share_secret(&mut record, &part(0, parts[0]), NOW).unwrap();
+    assert_eq!(
+        record.secrets.iter().map(|s| s.idx).collect::<Vec<_>>(),
+        [0, 1]
+    );
+}
+
+//------------------------------------------------------------------
+//  Quotes
+//------------------------------------------------------------------
+#[test]
+fn quotes_price_from_spot() {
+    let request = QuoteRequest {
+        src_chain: ChainId::Evm(1),
+        dst_chain: ChainId::Stellar,
+        src_token_address: WETH.into(),
+        dst_token_address: USDC.into(),
+        amount: 1_000_000_000_000_000_000,
+        wallet_address: "0x00000000000000000000000000000000000000aa".into(),
+    };
+    let quoter = quoter();
+    let quote = quoter.quote(&request, NOW).unwrap();
+    // 3000 USDC at spot, down to 0.5% below it
+    assert_eq!(quote.dst_token_amount, 30_000_000_000);
+    let fast = &quote.presets.fast;
+    assert_eq!(fast.auction_start_amount, 30_000_000_000);
+    assert_eq!(fast.auction_end_amount, 29_850_000_000);
+    assert_eq!(fast.initial_rate_bump, 50_251);
+    assert_eq!(
+        quote.presets.slow.auction_duration,
+        4 * fast.auction_duration
+    );
+    assert_eq!(quote.time_locks, DEFAULT_TIME_LOCKS);
+    assert_ne!(
+        quoter.quote(&request, NOW).unwrap().quote_id,
+        quote.quote_id
+    );
+
+    let unpriced = QuoteRequest {
+        dst_token_address: "CA...".into(),
+        ..request.clone()
+    };
+    assert!(matches!(
+        quoter.quote(&unpriced, NOW),
+        Err(OrderbookError::NotFound(_))
+    ));
+    let same_chain = QuoteRequest {
+        dst_chain: ChainId::Evm(1),
+        ..request
+    };
+    assert!(quoter.quote(&same_chain, NOW).is_err());
+}
+
+//------------------------------------------------------------------
+//  Storage
+//------------------------------------------------------------------
+#[tokio::test]
+async fn memory_store_updates_only_the_version_read() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("orders.json");
+    let store = MemoryStore::open(&path).unwrap();
+    let record = OrderRecord::new(order(1), NOW);
+    store.insert(record.clone()).await.unwrap();
+    assert!(matches!(
+        store.insert(record.clone()).await,
+        Err(OrderbookError::Conflict(_))
+    ));
+
+    let mut newer = record.clone();
+    newer.version = 1;
+    newer.updated_at = NOW + 1;
+    assert!(store.update(newer.clone()).await.unwrap());
+    // A second writer that read version 0 loses
+    assert!(!store.update(newer.clone()).await.unwrap());
+    store
+        .insert(OrderRecord::new(order(2), NOW + 1))
+        .await
+        .unwrap();
+
+    let reopened = MemoryStore::open(&path).unwrap();
+    assert_eq!(
+        reopened.get(record.order.order_hash).await.unwrap(),
+        Some(newer)
+    );
+    let (total, page) = reopened.list(&OrderFilter::default(), 1, 10).await.unwrap();
+    assert_eq!(total, 2);
+    assert_eq!(page[0].order.order_hash, order(2).order_hash);
+    let stellar_to_evm = OrderFilter {
+        src_chain: Some(ChainId::Stellar),
+        ..OrderFilter::default()
+    };
+    assert_eq!(reopened.list(&stellar_to_evm, 0, 10).await.unwrap().0, 0);
+}
+
+//------------------------------------------------------------------
+//  API
+//------------------------------------------------------------------
+fn app() -> Router {
+    let book = OrderBook::new(MemoryStore::default(), Some(quoter()), 60);
+    api::router(Arc::new(AppState {
+        book,
+        clock: || NOW,
+    }))
+}
+
+async fn call(app: &Router, method: Method, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("content-type", "application/json");
+    let body = body.map_or_else(Body::empty, |body| Body::from(body.to_string()));
+    let response = app
+        .clone()
+        .oneshot(request.body(body).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    let json = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+    (status, json)
+}
+
+#[tokio::test]
+async fn api_serves_the_fill_of_an_order() {
+    let app = app();
+    let order_hash = hex::encode([1; 32]);
+    let (status, info) = call(
+        &app,
+        Method::POST,
+        "/relayer/v1.0/submit",
+        Some(order_json(1)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(info["status"], "pending");
+    assert_eq!(info["order"]["remainingMakerAmount"], "1000000000000000000");
+    for hash in 2..=3 {
+        call(
+            &app,
+            Method::POST,
+            "/relayer/v1.0/submit",
+            Some(order_json(hash)),
+        )
+        .await;
+    }
+
+    let (status, page) = call(
+        &app,
+        Method::GET,
+        "/orders/v1.0/order/active?page=1&limit=2&srcChain=1&dstChain=stellar",
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        page["meta"],
+        json!({"totalItems": 3, "itemsPerPage": 2, "totalPages": 2, "currentPage": 1})
+    );
+    assert_eq!(page["items"][0]["orderHash"], order_hash);
+
+    let claim_uri = format!("/orders/v1.0/order/claim/0x{order_hash}");
+    let (status, claim) = call(
+        &app,
+        Method::POST,
+        &claim_uri,
+        Some(json!({"resolver": "resolver-a"})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(claim["expiresAt"], NOW + 60);
+    let (status, _) = call(
+        &app,
+        Method::POST,
+        &claim_uri,
+        Some(json!({"resolver": "resolver-b"})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (_, page) = call(&app, Method::GET, "/orders/v1.0/order/active", None).await;
+    assert_eq!(page["meta"]["totalItems"], 2);
+
+    let secrets_uri = format!("/orders/v1.0/order/secrets/{order_hash}");
+    let (status, _) = call(&app, Method::GET, &secrets_uri, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let status_uri = format!("/orders/v1.0/order/status/{order_hash}");
+    let (status, info) = call(
+        &app,
+        Method::POST,
+        &status_uri,
+        Some(json!({
+            "status": "filled",
+            "resolver": "resolver-a",
+            "txHash": "0x01",
+            "filledMakerAmount": "1000000000000000000"
+        })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(info["fills"][0]["txHash"], "0x01");
+
+    let (status, _) = call(
+        &app,
+        Method::POST,
+        "/relayer/v1.0/submit/secret",
+        Some(json!({"orderHash": order_hash, "secret": PREIMAGE})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let (_, secrets) = call(&app, Method::GET, &secrets_uri, None).await;
+    assert_eq!(secrets["secrets"], json!([{"idx": 0, "secret": PREIMAGE}]));
+
+    call(
+        &app,
+        Method::POST,
+        &status_uri,
+        Some(json!({"status": "executed", "resolver": "resolver-a"})),
+    )
+    .await;
+    let (_, info) = call(&app, Method::GET, &status_uri, None).await;
+    assert_eq!(info["status"], "executed");
+    let (_, page) = call(
+        &app,
+        Method::GET,
+        "/orders/v1.0/order/maker/0x00000000000000000000000000000000000000AA?limit=1",
+        None,
+    )
+    .await;
+    assert_eq!(page["meta"]["totalPages"], 3);
+    assert_eq!(page["items"][0]["status"], "executed");
+}
+
+#[tokio::test]
+async fn api_errors_follow_the_fusion_shape() {
+    let app = app();
+    let (status, error) = call(
+        &app,
+        Method::GET,
+        &format!("/orders/v1.0/order/status/{}", hex::encode([9; 32])),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(error["statusCode"], 404);
+    assert_eq!(error["error"], "Not Found");
+
+    let (status, error) = call(
+        &app,
+        Method::GET,
+        "/orders/v1.0/order/active?limit=501",
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(error["description"]
+        .as_str()
+        .unwrap()
+        .contains("limit is between 1 and 500"));
+
+    let mut expired = order_json(1);
+    expired["deadline"] = json!(NOW);
+    let (status, _) = call(&app, Method::POST, "/relayer/v1.0/submit", Some(expired)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, quote) = call(
+        &app,
+        Method::GET,
+        &format!(
+            "/quoter/v1.0/quote/receive?srcChain=1&dstChain=stellar&srcTokenAddress={WETH}\
+             &dstTokenAddress={USDC}&amount=1000000000000000000&walletAddress=0xaa"
+        ),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(quote["dstTokenAmount"], "30000000000");
+    let quoteless = api::router(Arc::new(AppState {
+        book: OrderBook::new(MemoryStore::default(), None, 60),
+        clock: || NOW,
+    }));
+    let (status, _) = call(
+        &quoteless,
+        Method::GET,
+        "/quoter/v1.0/quote/receive?srcChain=1&dstChain=stellar&srcTokenAddress=a\
+         &dstTokenAddress=b&amount=1&walletAddress=c",
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+}
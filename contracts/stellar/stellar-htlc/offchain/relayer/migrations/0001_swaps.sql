@@ -0,0 +1,72 @@
+-- Swaps, their orders and escrows, the events that changed them and their state history.
+-- Hashes are raw 32 bytes, amounts base units, heights ledgers or blocks. Chains are an EVM
+-- chain ID or 'stellar'.
+
+CREATE TABLE swaps (
+    hashlock        BYTEA PRIMARY KEY,
+    state           TEXT NOT NULL,
+    order_hash      BYTEA,
+    -- Where and when the secret became public; the secret itself is never stored
+    secret_revealed BOOLEAN NOT NULL DEFAULT FALSE,
+    revealed_chain  TEXT,
+    revealed_height BIGINT,
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at      TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX swaps_state ON swaps (state);
+
+CREATE TABLE orders (
+    order_hash BYTEA PRIMARY KEY,
+    hashlock   BYTEA NOT NULL REFERENCES swaps (hashlock),
+    maker      TEXT NOT NULL,
+    src_chain  TEXT NOT NULL,
+    src_token  TEXT,
+    src_amount NUMERIC(78, 0),
+    dst_chain  TEXT NOT NULL
+);
+
+CREATE INDEX orders_maker ON orders (lower(maker));
+
+CREATE TABLE escrows (
+    chain          TEXT NOT NULL,
+    -- HTLC contract ID in hex on Stellar, escrow address on EVM chains
+    escrow         TEXT NOT NULL,
+    hashlock       BYTEA NOT NULL REFERENCES swaps (hashlock),
+    -- 'source' or 'destination' on EVM chains
+    side           TEXT,
+    status         TEXT NOT NULL,
+    amount         NUMERIC(78, 0),
+    created_height BIGINT NOT NULL,
+    settled_height BIGINT,
+    PRIMARY KEY (chain, escrow)
+);
+
+CREATE INDEX escrows_hashlock ON escrows (hashlock);
+
+CREATE TABLE events (
+    id          BIGSERIAL PRIMARY KEY,
+    hashlock    BYTEA NOT NULL REFERENCES swaps (hashlock),
+    chain       TEXT NOT NULL,
+    escrow      TEXT NOT NULL,
+    kind        TEXT NOT NULL,
+    height      BIGINT NOT NULL,
+    tx_hash     TEXT NOT NULL,
+    amount      NUMERIC(78, 0),
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    -- An escrow is created, reveals and settles once, so replays are dropped
+    UNIQUE (chain, escrow, kind)
+);
+
+CREATE INDEX events_hashlock ON events (hashlock, id);
+
+CREATE TABLE swap_transitions (
+    id         BIGSERIAL PRIMARY KEY,
+    hashlock   BYTEA NOT NULL REFERENCES swaps (hashlock),
+    -- NULL when the swap was first seen
+    from_state TEXT,
+    to_state   TEXT NOT NULL,
+    at         TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX swap_transitions_hashlock ON swap_transitions (hashlock, id);
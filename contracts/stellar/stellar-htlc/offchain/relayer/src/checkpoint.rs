@@ -0,0 +1,50 @@
+//! Persistence of the event cursor together with the swap view it produced
+
+use crate::{propagation::Submission, swaps::SwapBook, RelayerError};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{ErrorKind, Write},
+    path::Path,
+};
+use stellar_htlc_sdk::Hash32;
+
+/// Where the relayer stands: the cursor of the next `getEvents` page, the next EVM block
+/// to scan, the view built from every event before them and the withdrawals submitted
+/// for it
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Cursor to resume from; `None` before the first page
+    pub cursor: Option<String>,
+    /// Latest ledger the RPC reported with the last page
+    pub latest_ledger: u32,
+    /// Next EVM block to scan; `None` before the first scan
+    #[serde(default)]
+    pub evm_block: Option<u64>,
+    pub swaps: SwapBook,
+    /// Withdrawals the propagation engine submitted, by hashlock
+    #[serde(default)]
+    pub submissions: BTreeMap<Hash32, Submission>,
+}
+
+impl Checkpoint {
+    /// Loads the checkpoint at `path`, or `None` if there is none yet
+    pub fn load(path: &Path) -> Result<Option<Self>, RelayerError> {
+        match fs::read(path) {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Saves the checkpoint atomically, so a crash leaves either the old or the new one
+    pub fn save(&self, path: &Path) -> Result<(), RelayerError> {
+        let tmp = path.with_extension("tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(&serde_json::to_vec_pretty(self)?)?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,119 @@
+use crate::{
+    evm::{EvmConfig, DEFAULT_BLOCK_RANGE, DEFAULT_CONFIRMATIONS},
+    propagation::DEFAULT_MAX_ATTEMPTS,
+    retry::{RetryPolicy, DEFAULT_BACKOFF_SECS, DEFAULT_MAX_FEE_BUMPS, DEFAULT_STUCK_AFTER_SECS},
+    RelayerError,
+};
+use std::{env, net::SocketAddr, path::PathBuf, time::Duration};
+use stellar_htlc_sdk::{keystore, SignerConfig};
+use zeroize::Zeroizing;
+
+/// Relayer settings, read from the environment. Not `Debug`, as it holds signing keys.
+#[derive(Clone)]
+pub struct Config {
+    /// Soroban RPC endpoint, `STELLAR_RPC_URL`
+    pub rpc_url: String,
+    /// Strkey of the HTLC contract to watch, `STELLAR_CONTRACT_ID`
+    pub contract_id: String,
+    /// Ledger to start from when there is no checkpoint yet, `RELAYER_START_LEDGER`;
+    /// defaults to the latest ledger
+    pub start_ledger: Option<u32>,
+    /// File holding the cursor and swap view, `RELAYER_STATE_PATH`
+    pub state_path: PathBuf,
+    /// Wait between polls once caught up, `RELAYER_POLL_INTERVAL_SECS`
+    pub poll_interval: Duration,
+    /// EVM watcher, enabled by setting `ETHEREUM_RPC_URL`
+    pub evm: Option<EvmConfig>,
+    /// Signer of withdrawals on Stellar, set up by `STELLAR_KMS_KEY_ID`, `STELLAR_KEYSTORE`
+    /// or `STELLAR_SECRET_KEY`
+    pub stellar_signer: Option<SignerConfig>,
+    /// `RELAYER_MAX_ATTEMPTS`, `RELAYER_RETRY_BACKOFF_SECS`, `RELAYER_STUCK_AFTER_SECS` and
+    /// `RELAYER_MAX_FEE_BUMPS`
+    pub retry: RetryPolicy,
+    /// Address the WebSocket update stream listens on, `RELAYER_WS_LISTEN`; disabled if
+    /// unset
+    pub ws_listen: Option<SocketAddr>,
+    /// Address Prometheus metrics are served on, `RELAYER_METRICS_LISTEN`; disabled if unset
+    pub metrics_listen: Option<SocketAddr>,
+    /// PostgreSQL database swaps are recorded in, `DATABASE_URL`; disabled if unset
+    pub database_url: Option<String>,
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self, RelayerError> {
+        Ok(Config {
+            rpc_url: required("STELLAR_RPC_URL")?,
+            contract_id: required("STELLAR_CONTRACT_ID")?,
+            start_ledger: optional("RELAYER_START_LEDGER")?,
+            state_path: env::var("RELAYER_STATE_PATH")
+                .unwrap_or_else(|_| "relayer-state.json".into())
+                .into(),
+            poll_interval: Duration::from_secs(
+                optional("RELAYER_POLL_INTERVAL_SECS")?.unwrap_or(5),
+            ),
+            evm: match env::var("ETHEREUM_RPC_URL") {
+                Ok(rpc_url) => Some(EvmConfig {
+                    rpc_url,
+                    factory: required("EVM_ESCROW_FACTORY")?.parse().map_err(|_| {
+                        RelayerError::Config("EVM_ESCROW_FACTORY is not an address".into())
+                    })?,
+                    start_block: optional("EVM_START_BLOCK")?,
+                    confirmations: optional("EVM_CONFIRMATIONS")?.unwrap_or(DEFAULT_CONFIRMATIONS),
+                    block_range: optional("EVM_BLOCK_RANGE")?.unwrap_or(DEFAULT_BLOCK_RANGE),
+                    private_key: evm_private_key()?,
+                }),
+                Err(_) => None,
+            },
+            stellar_signer: SignerConfig::from_env()
+                .map_err(|error| RelayerError::Config(error.to_string()))?,
+            retry: RetryPolicy {
+                max_attempts: optional("RELAYER_MAX_ATTEMPTS")?.unwrap_or(DEFAULT_MAX_ATTEMPTS),
+                backoff: optional("RELAYER_RETRY_BACKOFF_SECS")?.unwrap_or(DEFAULT_BACKOFF_SECS),
+                stuck_after: optional("RELAYER_STUCK_AFTER_SECS")?
+                    .unwrap_or(DEFAULT_STUCK_AFTER_SECS),
+                max_fee_bumps: optional("RELAYER_MAX_FEE_BUMPS")?.unwrap_or(DEFAULT_MAX_FEE_BUMPS),
+            },
+            ws_listen: listen("RELAYER_WS_LISTEN")?,
+            metrics_listen: listen("RELAYER_METRICS_LISTEN")?,
+            database_url: env::var("DATABASE_URL").ok(),
+        })
+    }
+}
+
+/// Key of the keystore `EVM_KEYSTORE`, or else `EVM_PRIVATE_KEY`
+fn evm_private_key() -> Result<Option<Zeroizing<String>>, RelayerError> {
+    if env::var_os("EVM_KEYSTORE").is_some() && env::var_os("EVM_PRIVATE_KEY").is_some() {
+        return Err(RelayerError::Config(
+            "only one of EVM_KEYSTORE, EVM_PRIVATE_KEY may be set".into(),
+        ));
+    }
+    match keystore::key_from_env("EVM_KEYSTORE") {
+        Ok(Some(key)) => Ok(Some(Zeroizing::new(alloy::hex::encode_prefixed(*key)))),
+        Ok(None) => Ok(env::var("EVM_PRIVATE_KEY").ok().map(Zeroizing::new)),
+        Err(error) => Err(RelayerError::Config(format!("EVM_KEYSTORE: {error}"))),
+    }
+}
+
+fn required(name: &str) -> Result<String, RelayerError> {
+    env::var(name).map_err(|_| RelayerError::Config(format!("{name} is not set")))
+}
+
+fn listen(name: &str) -> Result<Option<SocketAddr>, RelayerError> {
+    match env::var(name) {
+        Ok(listen) => listen
+            .parse()
+            .map(Some)
+            .map_err(|_| RelayerError::Config(format!("{name} is not a socket address"))),
+        Err(_) => Ok(None),
+    }
+}
+
+fn optional<T: std::str::FromStr>(name: &str) -> Result<Option<T>, RelayerError> {
+    match env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| RelayerError::Config(format!("{name} is not a valid number"))),
+        Err(_) => Ok(None),
+    }
+}
@@ -0,0 +1,458 @@
+//! PostgreSQL record of the swap view, for historical queries
+//!
+//! The checkpoint file stays the relayer's working state; the database keeps what it saw in
+//! queryable form: swaps with their state history, orders, escrows and the updates of every
+//! event. Secrets are never stored, only where and when they became public. Every write is
+//! an upsert keyed by hashlock, escrow or event, so writing a swap or an update twice
+//! changes nothing, and `reconcile` levels the database with a checkpoint after a restart
+//! or an outage.
+
+use crate::{
+    evm::EscrowSide,
+    stream::{SwapUpdate, UpdateKind},
+    swaps::{EscrowStatus, Swap, SwapBook, SwapState},
+    RelayerError,
+};
+use sqlx::{
+    postgres::{PgPool, PgPoolOptions, PgRow},
+    Postgres, Row, Transaction,
+};
+use std::collections::BTreeSet;
+use stellar_htlc_sdk::{fusion::ChainId, Hash32};
+
+/// Connections the relayer keeps open; it writes from one task
+const MAX_CONNECTIONS: u32 = 4;
+
+/// `chain` column value: an EVM chain ID or `stellar`
+pub fn chain_name(chain: ChainId) -> String {
+    match chain {
+        ChainId::Stellar => "stellar".into(),
+        ChainId::Evm(chain_id) => chain_id.to_string(),
+    }
+}
+
+fn status_name(status: EscrowStatus) -> &'static str {
+    match status {
+        EscrowStatus::Active => "active",
+        EscrowStatus::Withdrawn => "withdrawn",
+        EscrowStatus::Refunded => "refunded",
+    }
+}
+
+/// A row of `escrows`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EscrowRow {
+    pub chain: String,
+    pub escrow: String,
+    pub side: Option<String>,
+    pub status: String,
+    /// Base units; unknown for EVM destination escrows
+    pub amount: Option<String>,
+    pub created_height: i64,
+    pub settled_height: Option<i64>,
+}
+
+/// A row of `orders`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OrderRow {
+    pub order_hash: Hash32,
+    pub maker: String,
+    pub src_chain: String,
+    pub src_token: Option<String>,
+    pub src_amount: Option<String>,
+    pub dst_chain: String,
+}
+
+/// The escrows of `swap`, Stellar's first
+pub fn escrow_rows(swap: &Swap) -> Vec<EscrowRow> {
+    let stellar = swap.stellar.as_ref().map(|escrow| EscrowRow {
+        chain: chain_name(ChainId::Stellar),
+        escrow: escrow.contract_id.to_string(),
+        side: None,
+        status: status_name(escrow.status).into(),
+        amount: Some(escrow.htlc.amount.to_string()),
+        created_height: escrow.htlc.created_ledger.into(),
+        settled_height: escrow.settled_ledger.map(Into::into),
+    });
+    let evm = swap.evm.as_ref().map(|escrow| EscrowRow {
+        chain: chain_name(ChainId::Evm(escrow.chain_id)),
+        escrow: escrow.address.to_string(),
+        side: Some(
+            match escrow.side {
+                EscrowSide::Source => "source",
+                EscrowSide::Destination => "destination",
+            }
+            .into(),
+        ),
+        status: status_name(escrow.status).into(),
+        amount: escrow
+            .immutables
+            .as_ref()
+            .map(|immutables| immutables.amount.to_string()),
+        created_height: height(escrow.created_block),
+        settled_height: escrow.settled_block.map(height),
+    });
+    stellar.into_iter().chain(evm).collect()
+}
+
+/// The order `swap` fills, from its EVM source escrow or else from a Stellar HTLC carrying
+/// an order hash
+pub fn order_row(swap: &Swap) -> Option<OrderRow> {
+    if let Some((escrow, immutables)) = swap
+        .evm
+        .as_ref()
+        .and_then(|escrow| Some((escrow, escrow.immutables.as_ref()?)))
+    {
+        return Some(OrderRow {
+            order_hash: immutables.order_hash,
+            maker: immutables.maker.to_string(),
+            src_chain: chain_name(ChainId::Evm(escrow.chain_id)),
+            src_token: Some(immutables.token.to_string()),
+            src_amount: Some(immutables.amount.to_string()),
+            dst_chain: match swap.stellar {
+                Some(_) => chain_name(ChainId::Stellar),
+                None => immutables.dst_chain_id.to_string(),
+            },
+        });
+    }
+    let htlc = &swap.stellar.as_ref()?.htlc;
+    (htlc.order_hash != Hash32::default()).then(|| OrderRow {
+        order_hash: htlc.order_hash,
+        maker: htlc.sender.clone(),
+        src_chain: chain_name(ChainId::Stellar),
+        src_token: Some(htlc.token_address.clone()),
+        src_amount: Some(htlc.amount.to_string()),
+        dst_chain: chain_name(ChainId::Evm(htlc.metadata.evm_chain_id)),
+    })
+}
+
+fn height(height: u64) -> i64 {
+    i64::try_from(height).expect("heights fit in BIGINT")
+}
+
+fn hash(row: &PgRow, column: &str) -> Result<Hash32, sqlx::Error> {
+    let bytes: Vec<u8> = row.try_get(column)?;
+    bytes
+        .try_into()
+        .map(Hash32)
+        .map_err(|_| sqlx::Error::Decode(format!("{column} is not 32 bytes").into()))
+}
+
+/// A swap as recorded, with its escrows
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SwapRecord {
+    pub hashlock: Hash32,
+    pub state: String,
+    pub order_hash: Option<Hash32>,
+    pub secret_revealed: bool,
+    pub revealed_chain: Option<String>,
+    pub revealed_height: Option<i64>,
+    pub escrows: Vec<EscrowRow>,
+}
+
+/// An event as recorded, without its secret
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EventRecord {
+    pub chain: String,
+    pub escrow: String,
+    pub kind: String,
+    pub height: i64,
+    pub tx_hash: String,
+    pub amount: Option<String>,
+}
+
+/// A change of a swap's state, at unix time `at`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Transition {
+    pub from_state: Option<String>,
+    pub to_state: String,
+    pub at: i64,
+}
+
+pub struct Database {
+    pool: PgPool,
+}
+
+impl Database {
+    /// Connects to `url` and applies the migrations not applied yet
+    pub async fn connect(url: &str) -> Result<Self, RelayerError> {
+        let pool = PgPoolOptions::new()
+            .max_connections(MAX_CONNECTIONS)
+            .connect(url)
+            .await?;
+        sqlx::migrate!().run(&pool).await?;
+        Ok(Database { pool })
+    }
+
+    /// Records `updates` and the swaps of `view` they concern, in one transaction
+    pub async fn record(
+        &self,
+        view: &SwapBook,
+        updates: &[SwapUpdate],
+    ) -> Result<(), RelayerError> {
+        if updates.is_empty() {
+            return Ok(());
+        }
+        let mut tx = self.pool.begin().await?;
+        let hashlocks = updates
+            .iter()
+            .map(|update| update.hashlock)
+            .collect::<BTreeSet<_>>();
+        for swap in hashlocks.iter().filter_map(|hashlock| view.get(hashlock)) {
+            upsert_swap(&mut tx, swap).await?;
+        }
+        for update in updates {
+            insert_event(&mut tx, update).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Upserts every swap of `swaps`, returning how many changed state. Events applied
+    /// before the database was set up are not recovered, only their effect.
+    pub async fn reconcile<'a>(
+        &self,
+        swaps: impl IntoIterator<Item = &'a Swap>,
+    ) -> Result<usize, RelayerError> {
+        let mut tx = self.pool.begin().await?;
+        let mut changed = 0;
+        for swap in swaps {
+            changed += usize::from(upsert_swap(&mut tx, swap).await?);
+        }
+        tx.commit().await?;
+        Ok(changed)
+    }
+
+    pub async fn swap(&self, hashlock: &Hash32) -> Result<Option<SwapRecord>, RelayerError> {
+        let Some(row) = sqlx::query(
+            "SELECT state, order_hash, secret_revealed, revealed_chain, revealed_height \
+             FROM swaps WHERE hashlock = $1",
+        )
+        .bind(&hashlock.0[..])
+        .fetch_optional(&self.pool)
+        .await?
+        else {
+            return Ok(None);
+        };
+        let escrows = sqlx::query(
+            "SELECT chain, escrow, side, status, amount::TEXT AS amount, created_height, \
+             settled_height FROM escrows WHERE hashlock = $1 ORDER BY created_height, chain",
+        )
+        .bind(&hashlock.0[..])
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(|row| {
+            Ok(EscrowRow {
+                chain: row.try_get("chain")?,
+                escrow: row.try_get("escrow")?,
+                side: row.try_get("side")?,
+                status: row.try_get("status")?,
+                amount: row.try_get("amount")?,
+                created_height: row.try_get("created_height")?,
+                settled_height: row.try_get("settled_height")?,
+            })
+        })
+        .collect::<Result<_, sqlx::Error>>()?;
+        let order_hash = match row.try_get::<Option<Vec<u8>>, _>("order_hash")? {
+            Some(_) => Some(hash(&row, "order_hash")?),
+            None => None,
+        };
+        Ok(Some(SwapRecord {
+            hashlock: *hashlock,
+            state: row.try_get("state")?,
+            order_hash,
+            secret_revealed: row.try_get("secret_revealed")?,
+            revealed_chain: row.try_get("revealed_chain")?,
+            revealed_height: row.try_get("revealed_height")?,
+            escrows,
+        }))
+    }
+
+    /// Hashlocks of the swaps in `state`, most recently updated first
+    pub async fn swaps_in(
+        &self,
+        state: SwapState,
+        limit: i64,
+    ) -> Result<Vec<Hash32>, RelayerError> {
+        let rows = sqlx::query(
+            "SELECT hashlock FROM swaps WHERE state = $1 ORDER BY updated_at DESC LIMIT $2",
+        )
+        .bind(state.as_str())
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .iter()
+            .map(|row| hash(row, "hashlock"))
+            .collect::<Result<_, _>>()?)
+    }
+
+    /// Events of a swap, in the order they were recorded
+    pub async fn events(&self, hashlock: &Hash32) -> Result<Vec<EventRecord>, RelayerError> {
+        let rows = sqlx::query(
+            "SELECT chain, escrow, kind, height, tx_hash, amount::TEXT AS amount \
+             FROM events WHERE hashlock = $1 ORDER BY id",
+        )
+        .bind(&hashlock.0[..])
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .iter()
+            .map(|row| {
+                Ok(EventRecord {
+                    chain: row.try_get("chain")?,
+                    escrow: row.try_get("escrow")?,
+                    kind: row.try_get("kind")?,
+                    height: row.try_get("height")?,
+                    tx_hash: row.try_get("tx_hash")?,
+                    amount: row.try_get("amount")?,
+                })
+            })
+            .collect::<Result<_, sqlx::Error>>()?)
+    }
+
+    /// State history of a swap, oldest first
+    pub async fn transitions(&self, hashlock: &Hash32) -> Result<Vec<Transition>, RelayerError> {
+        let rows = sqlx::query(
+            "SELECT from_state, to_state, EXTRACT(EPOCH FROM at)::BIGINT AS at \
+             FROM swap_transitions WHERE hashlock = $1 ORDER BY id",
+        )
+        .bind(&hashlock.0[..])
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .iter()
+            .map(|row| {
+                Ok(Transition {
+                    from_state: row.try_get("from_state")?,
+                    to_state: row.try_get("to_state")?,
+                    at: row.try_get("at")?,
+                })
+            })
+            .collect::<Result<_, sqlx::Error>>()?)
+    }
+}
+
+/// Upserts a swap with its order and escrows, recording a transition if its state changed.
+/// Returns whether it did.
+async fn upsert_swap(tx: &mut Transaction<'_, Postgres>, swap: &Swap) -> Result<bool, sqlx::Error> {
+    let hashlock = &swap.hashlock.0[..];
+    let state = swap.state().as_str();
+    let order = order_row(swap);
+    let previous: Option<String> =
+        sqlx::query_scalar("SELECT state FROM swaps WHERE hashlock = $1 FOR UPDATE")
+            .bind(hashlock)
+            .fetch_optional(&mut **tx)
+            .await?;
+
+    sqlx::query(
+        "INSERT INTO swaps (hashlock, state, order_hash, secret_revealed) \
+         VALUES ($1, $2, $3, $4) \
+         ON CONFLICT (hashlock) DO UPDATE SET state = excluded.state, \
+         order_hash = COALESCE(excluded.order_hash, swaps.order_hash), \
+         secret_revealed = swaps.secret_revealed OR excluded.secret_revealed, \
+         updated_at = CASE WHEN swaps.state = excluded.state THEN swaps.updated_at \
+         ELSE now() END",
+    )
+    .bind(hashlock)
+    .bind(state)
+    .bind(order.as_ref().map(|order| order.order_hash.0.to_vec()))
+    .bind(swap.secret.is_some())
+    .execute(&mut **tx)
+    .await?;
+
+    if let Some(order) = &order {
+        sqlx::query(
+            "INSERT INTO orders (order_hash, hashlock, maker, src_chain, src_token, \
+             src_amount, dst_chain) VALUES ($1, $2, $3, $4, $5, $6::NUMERIC, $7) \
+             ON CONFLICT (order_hash) DO UPDATE SET \
+             src_token = COALESCE(excluded.src_token, orders.src_token), \
+             src_amount = COALESCE(excluded.src_amount, orders.src_amount)",
+        )
+        .bind(&order.order_hash.0[..])
+        .bind(hashlock)
+        .bind(&order.maker)
+        .bind(&order.src_chain)
+        .bind(&order.src_token)
+        .bind(&order.src_amount)
+        .bind(&order.dst_chain)
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    for escrow in escrow_rows(swap) {
+        sqlx::query(
+            "INSERT INTO escrows (chain, escrow, hashlock, side, status, amount, \
+             created_height, settled_height) VALUES ($1, $2, $3, $4, $5, $6::NUMERIC, $7, $8) \
+             ON CONFLICT (chain, escrow) DO UPDATE SET status = excluded.status, \
+             settled_height = excluded.settled_height",
+        )
+        .bind(&escrow.chain)
+        .bind(&escrow.escrow)
+        .bind(hashlock)
+        .bind(&escrow.side)
+        .bind(&escrow.status)
+        .bind(&escrow.amount)
+        .bind(escrow.created_height)
+        .bind(escrow.settled_height)
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    if previous.as_deref() == Some(state) {
+        return Ok(false);
+    }
+    sqlx::query(
+        "INSERT INTO swap_transitions (hashlock, from_state, to_state) VALUES ($1, $2, $3)",
+    )
+    .bind(hashlock)
+    .bind(previous)
+    .bind(state)
+    .execute(&mut **tx)
+    .await?;
+    Ok(true)
+}
+
+/// Inserts an update unless it was recorded before; a revealed secret only marks its swap
+async fn insert_event(
+    tx: &mut Transaction<'_, Postgres>,
+    update: &SwapUpdate,
+) -> Result<(), sqlx::Error> {
+    let hashlock = &update.hashlock.0[..];
+    let chain = chain_name(update.chain);
+    let height = height(update.height);
+    let amount = match &update.kind {
+        UpdateKind::EscrowCreated { amount } => amount.clone(),
+        _ => None,
+    };
+    let inserted = sqlx::query(
+        "INSERT INTO events (hashlock, chain, escrow, kind, height, tx_hash, amount) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7::NUMERIC) \
+         ON CONFLICT (chain, escrow, kind) DO NOTHING",
+    )
+    .bind(hashlock)
+    .bind(&chain)
+    .bind(&update.escrow)
+    .bind(update.kind.as_str())
+    .bind(height)
+    .bind(&update.tx_hash)
+    .bind(amount)
+    .execute(&mut **tx)
+    .await?
+    .rows_affected()
+        > 0;
+
+    if inserted && matches!(update.kind, UpdateKind::SecretRevealed { .. }) {
+        sqlx::query(
+            "UPDATE swaps SET secret_revealed = TRUE, revealed_chain = $2, \
+             revealed_height = $3 WHERE hashlock = $1",
+        )
+        .bind(hashlock)
+        .bind(&chain)
+        .bind(height)
+        .execute(&mut **tx)
+        .await?;
+    }
+    Ok(())
+}
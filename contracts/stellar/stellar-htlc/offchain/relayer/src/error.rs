@@ -0,0 +1,31 @@
+use stellar_htlc_sdk::types::DecodeError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum RelayerError {
+    #[error("invalid configuration: {0}")]
+    Config(String),
+    #[error("Stellar error: {0}")]
+    Stellar(#[from] stellar_htlc_sdk::Error),
+    #[error("undecodable event {id}: {source}")]
+    Decode { id: String, source: DecodeError },
+    #[error("EVM RPC error: {0}")]
+    EvmRpc(#[from] alloy::transports::TransportError),
+    #[error("EVM contract call failed: {0}")]
+    EvmCall(#[from] alloy::contract::Error),
+    #[error("EVM transaction signing failed: {0}")]
+    EvmSign(String),
+    #[error("undecodable EVM log in {tx_hash}: {source}")]
+    EvmDecode {
+        tx_hash: String,
+        source: alloy::sol_types::Error,
+    },
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+    #[error("database migration failed: {0}")]
+    Migrate(#[from] sqlx::migrate::MigrateError),
+    #[error("checkpoint I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("checkpoint is not valid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+}
@@ -0,0 +1,557 @@
+//! Watcher for the 1inch cross-chain escrows on an EVM chain
+//!
+//! Scans confirmed blocks with `eth_getLogs` for the `EscrowFactory` creation events and
+//! the escrows' withdrawal and cancellation events, and normalizes them into the same
+//! swap view the Stellar events feed. Also signs the escrow withdrawals the propagation
+//! engine submits.
+
+use crate::{
+    checkpoint::Checkpoint,
+    propagation::{SignedTx, TxStatus},
+    stream::{self, UpdateStream},
+    swaps::{Applied, EscrowStatus},
+    RelayerError,
+};
+use alloy::{
+    consensus::TxEnvelope,
+    eips::eip2718::{Decodable2718, Encodable2718},
+    network::{EthereumWallet, TransactionBuilder},
+    primitives::{Address, Bytes, B256, U256},
+    providers::{DynProvider, Provider, ProviderBuilder},
+    rpc::types::{Filter, Log, TransactionRequest},
+    signers::local::PrivateKeySigner,
+    sol,
+    sol_types::{SolCall, SolEvent},
+};
+use serde::{Deserialize, Serialize};
+use stellar_htlc_sdk::Hash32;
+use tracing::debug;
+use zeroize::Zeroizing;
+
+/// Raise of both fees of an EVM transaction replacing a stuck one; nodes require 10%
+pub const FEE_BUMP_PERCENT: u128 = 25;
+
+sol! {
+    /// Parameters of a 1inch escrow; makers, takers and tokens are addresses packed in
+    /// `uint256` (the protocol's `Address` type)
+    #[derive(Debug, PartialEq, Eq)]
+    struct Immutables {
+        bytes32 orderHash;
+        bytes32 hashlock;
+        uint256 maker;
+        uint256 taker;
+        uint256 token;
+        uint256 amount;
+        uint256 safetyDeposit;
+        uint256 timelocks;
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    struct DstImmutablesComplement {
+        uint256 maker;
+        uint256 amount;
+        uint256 token;
+        uint256 safetyDeposit;
+        uint256 chainId;
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    event SrcEscrowCreated(Immutables srcImmutables, DstImmutablesComplement dstImmutablesComplement);
+    #[derive(Debug, PartialEq, Eq)]
+    event DstEscrowCreated(address escrow, bytes32 hashlock, uint256 taker);
+    /// Withdrawal event of escrows up to v1.0
+    #[derive(Debug, PartialEq, Eq)]
+    event Withdrawal(bytes32 secret);
+    #[derive(Debug, PartialEq, Eq)]
+    event EscrowWithdrawal(bytes32 secret);
+    #[derive(Debug, PartialEq, Eq)]
+    event EscrowCancelled();
+
+    #[sol(rpc)]
+    interface IEscrowFactory {
+        function addressOfEscrowSrc(Immutables immutables) external view returns (address);
+    }
+
+    interface IEscrow {
+        function withdraw(bytes32 secret, Immutables immutables) external;
+    }
+}
+
+/// Default confirmations a block needs before its logs are applied
+pub const DEFAULT_CONFIRMATIONS: u64 = 12;
+
+/// Default number of blocks scanned per `eth_getLogs` call
+pub const DEFAULT_BLOCK_RANGE: u64 = 1_000;
+
+/// Stages packed into an escrow's `timelocks`, in their bit order
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimelockStage {
+    SrcWithdrawal,
+    SrcPublicWithdrawal,
+    SrcCancellation,
+    SrcPublicCancellation,
+    DstWithdrawal,
+    DstPublicWithdrawal,
+    DstCancellation,
+}
+
+/// Timestamp a stage starts at: each stage is a 32-bit offset from the deployment time
+/// held in the top 32 bits
+pub fn timelock(timelocks: U256, stage: TimelockStage) -> u64 {
+    let deployed_at: u64 = (timelocks >> 224usize).to();
+    let offset: u64 = ((timelocks >> (stage as usize * 32)) & U256::from(u32::MAX)).to();
+    deployed_at + offset
+}
+
+/// Side of the swap an EVM escrow is on
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EscrowSide {
+    /// Holds the maker's funds
+    Source,
+    /// Holds the resolver's funds for the maker
+    Destination,
+}
+
+/// Parameters of a source escrow, as carried by `SrcEscrowCreated`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EscrowImmutables {
+    pub order_hash: Hash32,
+    pub maker: Address,
+    pub token: Address,
+    pub amount: U256,
+    pub safety_deposit: U256,
+    pub timelocks: U256,
+    /// Chain the destination escrow is created on
+    pub dst_chain_id: U256,
+}
+
+/// The EVM escrow of a swap
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EvmEscrow {
+    pub chain_id: u64,
+    pub side: EscrowSide,
+    pub address: Address,
+    pub hashlock: Hash32,
+    pub taker: Address,
+    /// Known for source escrows only; `DstEscrowCreated` carries no immutables
+    pub immutables: Option<EscrowImmutables>,
+    pub status: EscrowStatus,
+    pub created_block: u64,
+    /// Block of the withdrawal or cancellation
+    pub settled_block: Option<u64>,
+}
+
+/// A factory or escrow log decoded into its event
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EscrowLog {
+    SrcCreated(Box<SrcEscrowCreated>),
+    DstCreated(DstEscrowCreated),
+    Withdrawn { secret: Hash32 },
+    Cancelled,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EvmEventKind {
+    Created(Box<EvmEscrow>),
+    Withdrawn { secret: Hash32 },
+    Cancelled,
+}
+
+/// An escrow event normalized for the swap view
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EvmEvent {
+    pub block: u64,
+    pub tx_hash: B256,
+    pub escrow: Address,
+    pub kind: EvmEventKind,
+}
+
+/// Address held in the low 160 bits of a packed `Address` value
+fn unpack_address(value: U256) -> Address {
+    Address::from_word(B256::from(value))
+}
+
+/// An address packed as the protocol's `Address` value, without flags
+fn pack_address(address: Address) -> U256 {
+    U256::from_be_bytes(address.into_word().0)
+}
+
+/// Immutables a source escrow validates its calls against, or `None` for escrows created
+/// without them
+pub fn src_immutables(escrow: &EvmEscrow) -> Option<Immutables> {
+    let immutables = escrow.immutables.as_ref()?;
+    Some(Immutables {
+        orderHash: B256::from(immutables.order_hash.0),
+        hashlock: B256::from(escrow.hashlock.0),
+        maker: pack_address(immutables.maker),
+        taker: pack_address(escrow.taker),
+        token: pack_address(immutables.token),
+        amount: immutables.amount,
+        safetyDeposit: immutables.safety_deposit,
+        timelocks: immutables.timelocks,
+    })
+}
+
+/// Decodes a log of the factory or of any escrow, returning `None` for other events.
+/// Creation events only count when emitted by `factory`.
+pub fn decode_log(log: &Log, factory: Address) -> Result<Option<EscrowLog>, RelayerError> {
+    let Some(topic0) = log.topic0() else {
+        return Ok(None);
+    };
+    let data = &log.inner.data;
+    let decode_error = |source| RelayerError::EvmDecode {
+        tx_hash: log.transaction_hash.unwrap_or_default().to_string(),
+        source,
+    };
+    let from_factory = log.address() == factory;
+    let decoded = match *topic0 {
+        SrcEscrowCreated::SIGNATURE_HASH if from_factory => EscrowLog::SrcCreated(Box::new(
+            SrcEscrowCreated::decode_log_data(data).map_err(decode_error)?,
+        )),
+        DstEscrowCreated::SIGNATURE_HASH if from_factory => {
+            EscrowLog::DstCreated(DstEscrowCreated::decode_log_data(data).map_err(decode_error)?)
+        }
+        Withdrawal::SIGNATURE_HASH => EscrowLog::Withdrawn {
+            secret: Hash32(
+                Withdrawal::decode_log_data(data)
+                    .map_err(decode_error)?
+                    .secret
+                    .0,
+            ),
+        },
+        EscrowWithdrawal::SIGNATURE_HASH => EscrowLog::Withdrawn {
+            secret: Hash32(
+                EscrowWithdrawal::decode_log_data(data)
+                    .map_err(decode_error)?
+                    .secret
+                    .0,
+            ),
+        },
+        EscrowCancelled::SIGNATURE_HASH => EscrowLog::Cancelled,
+        _ => return Ok(None),
+    };
+    Ok(Some(decoded))
+}
+
+impl EscrowLog {
+    /// Normalizes a decoded log of `log`. `escrow` is the escrow the event concerns: the
+    /// emitter for escrow events, the created escrow for creations.
+    pub fn into_event(self, log: &Log, escrow: Address, chain_id: u64) -> EvmEvent {
+        let block = log.block_number.unwrap_or_default();
+        let escrow_of = |side, hashlock: B256, taker, immutables| EvmEscrow {
+            chain_id,
+            side,
+            address: escrow,
+            hashlock: Hash32(hashlock.0),
+            taker,
+            immutables,
+            status: EscrowStatus::Active,
+            created_block: block,
+            settled_block: None,
+        };
+        let kind = match self {
+            EscrowLog::SrcCreated(created) => {
+                let src = &created.srcImmutables;
+                let dst = &created.dstImmutablesComplement;
+                EvmEventKind::Created(Box::new(escrow_of(
+                    EscrowSide::Source,
+                    src.hashlock,
+                    unpack_address(src.taker),
+                    Some(EscrowImmutables {
+                        order_hash: Hash32(src.orderHash.0),
+                        maker: unpack_address(src.maker),
+                        token: unpack_address(src.token),
+                        amount: src.amount,
+                        safety_deposit: src.safetyDeposit,
+                        timelocks: src.timelocks,
+                        dst_chain_id: dst.chainId,
+                    }),
+                )))
+            }
+            EscrowLog::DstCreated(created) => EvmEventKind::Created(Box::new(escrow_of(
+                EscrowSide::Destination,
+                created.hashlock,
+                unpack_address(created.taker),
+                None,
+            ))),
+            EscrowLog::Withdrawn { secret } => EvmEventKind::Withdrawn { secret },
+            EscrowLog::Cancelled => EvmEventKind::Cancelled,
+        };
+        EvmEvent {
+            block,
+            tx_hash: log.transaction_hash.unwrap_or_default(),
+            escrow,
+            kind,
+        }
+    }
+}
+
+/// EVM watcher settings
+#[derive(Clone)]
+pub struct EvmConfig {
+    pub rpc_url: String,
+    pub factory: Address,
+    /// Block to start from when there is no checkpoint yet; defaults to the confirmed head
+    pub start_block: Option<u64>,
+    pub confirmations: u64,
+    pub block_range: u64,
+    /// Key withdrawals on the EVM chain are submitted with
+    pub private_key: Option<Zeroizing<String>>,
+}
+
+pub struct EvmWatcher {
+    provider: DynProvider,
+    config: EvmConfig,
+    chain_id: u64,
+    updates: Option<UpdateStream>,
+}
+
+impl EvmWatcher {
+    pub async fn connect(config: EvmConfig) -> Result<Self, RelayerError> {
+        let url = config
+            .rpc_url
+            .parse()
+            .map_err(|_| RelayerError::Config(format!("invalid EVM RPC URL {}", config.rpc_url)))?;
+        let provider = ProviderBuilder::new().connect_http(url).erased();
+        let chain_id = provider.get_chain_id().await?;
+        Ok(EvmWatcher {
+            provider,
+            config,
+            chain_id,
+            updates: None,
+        })
+    }
+
+    /// Publishes the updates of every log that changes the view to `updates`
+    pub fn with_updates(mut self, updates: UpdateStream) -> Self {
+        self.updates = Some(updates);
+        self
+    }
+
+    pub fn chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    /// Logs the watcher follows in a block range
+    pub fn filter(&self, from: u64, to: u64) -> Filter {
+        Filter::new()
+            .from_block(from)
+            .to_block(to)
+            .event_signature(vec![
+                SrcEscrowCreated::SIGNATURE_HASH,
+                DstEscrowCreated::SIGNATURE_HASH,
+                Withdrawal::SIGNATURE_HASH,
+                EscrowWithdrawal::SIGNATURE_HASH,
+                EscrowCancelled::SIGNATURE_HASH,
+            ])
+    }
+
+    /// Scans the next range of confirmed blocks into `checkpoint`, returning how many
+    /// confirmed blocks are left to scan
+    pub async fn poll(&self, checkpoint: &mut Checkpoint) -> Result<u64, RelayerError> {
+        let head = self.provider.get_block_number().await?;
+        let confirmed = head.saturating_sub(self.config.confirmations);
+        let from = checkpoint
+            .evm_block
+            .or(self.config.start_block)
+            .unwrap_or(confirmed);
+        if from > confirmed {
+            return Ok(0);
+        }
+        let to = confirmed.min(from + self.config.block_range - 1);
+
+        // Creations come before the escrow's own logs, so one ordered pass resolves both
+        for log in self.provider.get_logs(&self.filter(from, to)).await? {
+            let Some(decoded) = decode_log(&log, self.config.factory)? else {
+                continue;
+            };
+            let escrow = match &decoded {
+                EscrowLog::SrcCreated(created) => {
+                    IEscrowFactory::new(self.config.factory, &self.provider)
+                        .addressOfEscrowSrc(created.srcImmutables.clone())
+                        .call()
+                        .await?
+                }
+                EscrowLog::DstCreated(created) => created.escrow,
+                _ if checkpoint.swaps.by_evm_escrow(&log.address()).is_some() => log.address(),
+                // Same event from a contract that is not a tracked escrow
+                _ => continue,
+            };
+            let event = decoded.into_event(&log, escrow, self.chain_id);
+            let applied = checkpoint.swaps.apply_evm(&event);
+            debug!(escrow = %event.escrow, block = event.block, ?applied, "applied EVM log");
+            if let (Applied::Updated, Some(updates), Some(swap)) = (
+                applied,
+                &self.updates,
+                checkpoint.swaps.by_evm_escrow(&event.escrow),
+            ) {
+                updates.publish(stream::evm_updates(&event, swap));
+            }
+        }
+
+        checkpoint.evm_block = Some(to + 1);
+        Ok(confirmed - to)
+    }
+}
+
+/// Signs and sends escrow withdrawals from the relayer's EVM account
+pub struct EvmSubmitter {
+    provider: DynProvider,
+    wallet: EthereumWallet,
+    address: Address,
+    chain_id: u64,
+}
+
+impl EvmSubmitter {
+    pub async fn connect(rpc_url: &str, private_key: &str) -> Result<Self, RelayerError> {
+        let signer: PrivateKeySigner = private_key
+            .parse()
+            .map_err(|_| RelayerError::Config("the EVM key is not a private key".into()))?;
+        let url = rpc_url
+            .parse()
+            .map_err(|_| RelayerError::Config(format!("invalid EVM RPC URL {rpc_url}")))?;
+        let provider = ProviderBuilder::new().connect_http(url).erased();
+        let chain_id = provider.get_chain_id().await?;
+        Ok(EvmSubmitter {
+            provider,
+            address: signer.address(),
+            wallet: EthereumWallet::from(signer),
+            chain_id,
+        })
+    }
+
+    /// Address of the submitting account
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    /// Native balance of the submitting account, in wei
+    pub async fn balance(&self) -> Result<U256, RelayerError> {
+        Ok(self.provider.get_balance(self.address).await?)
+    }
+
+    /// Signs the taker's `withdraw` of a source escrow. Gas is estimated against the
+    /// escrow, so a withdrawal it would revert fails here instead of on chain.
+    pub async fn withdraw(
+        &self,
+        escrow: Address,
+        immutables: Immutables,
+        secret: Hash32,
+    ) -> Result<SignedTx, RelayerError> {
+        let call = IEscrow::withdrawCall {
+            secret: B256::from(secret.0),
+            immutables,
+        };
+        let nonce = self
+            .provider
+            .get_transaction_count(self.address)
+            .pending()
+            .await?;
+        let request = TransactionRequest::default()
+            .with_from(self.address)
+            .with_to(escrow)
+            .with_input(call.abi_encode())
+            .with_chain_id(self.chain_id)
+            .with_nonce(nonce);
+        let gas = self.provider.estimate_gas(request.clone()).await?;
+        let fees = self.provider.estimate_eip1559_fees().await?;
+        let request = request
+            .with_gas_limit(gas)
+            .with_max_fee_per_gas(fees.max_fee_per_gas)
+            .with_max_priority_fee_per_gas(fees.max_priority_fee_per_gas);
+        self.sign(request, nonce).await
+    }
+
+    /// Signs `tx` again with the same nonce, both fees raised by `FEE_BUMP_PERCENT` or to
+    /// the current estimate if that is higher
+    pub async fn bump(&self, tx: &SignedTx) -> Result<SignedTx, RelayerError> {
+        let malformed = || RelayerError::EvmSign(format!("malformed transaction {}", tx.hash));
+        let raw: Bytes = tx.raw.parse().map_err(|_| malformed())?;
+        let envelope = TxEnvelope::decode_2718(&mut raw.as_ref()).map_err(|_| malformed())?;
+        let Some(signed) = envelope.as_eip1559() else {
+            return Err(RelayerError::EvmSign(format!(
+                "transaction {} is not an EIP-1559 transaction",
+                tx.hash
+            )));
+        };
+        let mut unsigned = signed.tx().clone();
+        let fees = self.provider.estimate_eip1559_fees().await?;
+        unsigned.max_priority_fee_per_gas =
+            raised_fee(unsigned.max_priority_fee_per_gas).max(fees.max_priority_fee_per_gas);
+        unsigned.max_fee_per_gas = raised_fee(unsigned.max_fee_per_gas)
+            .max(fees.max_fee_per_gas)
+            .max(unsigned.max_priority_fee_per_gas);
+        let nonce = unsigned.nonce;
+        let request: TransactionRequest = unsigned.into();
+        self.sign(request.with_from(self.address), nonce).await
+    }
+
+    async fn sign(
+        &self,
+        request: TransactionRequest,
+        nonce: u64,
+    ) -> Result<SignedTx, RelayerError> {
+        let envelope = request
+            .build(&self.wallet)
+            .await
+            .map_err(|e| RelayerError::EvmSign(e.to_string()))?;
+        Ok(SignedTx {
+            hash: envelope.tx_hash().to_string(),
+            raw: Bytes::from(envelope.encoded_2718()).to_string(),
+            valid_until: None,
+            nonce: Some(nonce),
+        })
+    }
+
+    /// Sends a signed transaction; sending one the node already has is a no-op
+    pub async fn broadcast(&self, tx: &SignedTx) -> Result<(), RelayerError> {
+        let raw: Bytes = tx
+            .raw
+            .parse()
+            .map_err(|_| RelayerError::EvmSign(format!("malformed transaction {}", tx.hash)))?;
+        match self.provider.send_raw_transaction(&raw).await {
+            Ok(_) => Ok(()),
+            // Known to the node already, its nonce used or an underpriced replacement: the
+            // status checks tell which transaction of the nonce lands
+            Err(e) if is_known(&e.to_string()) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn status(&self, tx: &SignedTx) -> Result<TxStatus, RelayerError> {
+        let hash: B256 = tx
+            .hash
+            .parse()
+            .map_err(|_| RelayerError::EvmSign(format!("malformed hash {}", tx.hash)))?;
+        if let Some(receipt) = self.provider.get_transaction_receipt(hash).await? {
+            return Ok(if receipt.status() {
+                TxStatus::Succeeded
+            } else {
+                TxStatus::Failed
+            });
+        }
+        let nonce = self.provider.get_transaction_count(self.address).await?;
+        if tx.nonce.is_some_and(|tx_nonce| nonce > tx_nonce) {
+            // The nonce went to another transaction, unless this one landed meanwhile
+            if self.provider.get_transaction_receipt(hash).await?.is_none() {
+                return Ok(TxStatus::Dropped);
+            }
+            return Ok(TxStatus::Pending);
+        }
+        Ok(TxStatus::Pending)
+    }
+}
+
+/// `fee` raised by `FEE_BUMP_PERCENT`, rounded up
+pub fn raised_fee(fee: u128) -> u128 {
+    fee.saturating_add((fee.saturating_mul(FEE_BUMP_PERCENT)).div_ceil(100))
+}
+
+/// Whether a rejection leaves the outcome to the status check: the transaction is known
+/// already, its nonce was used, or it replaces a pending one without paying enough more
+fn is_known(error: &str) -> bool {
+    let error = error.to_lowercase();
+    error.contains("already known")
+        || error.contains("nonce too low")
+        || error.contains("replacement transaction underpriced")
+}
@@ -0,0 +1,28 @@
+//! Off-chain relayer for the Stellar HTLC contract.
+//!
+//! Polls Soroban RPC `getEvents` for the contract's `HTLCNew`, `HTLCWithdraw` and
+//! `HTLCRefund` events, decodes their XDR payloads and keeps a local view of every swap.
+//! An optional EVM watcher feeds the 1inch escrows of the other leg into the same view.
+//! The view is checkpointed together with both chains' cursors, so a restart resumes
+//! exactly where the last run stopped. With accounts on both chains, a secret revealed
+//! on one of them is propagated by withdrawing the swap's other leg, with backoff and fee
+//! raises until the withdrawal lands. Changes to the view can be streamed to front-ends
+//! over a WebSocket and recorded in PostgreSQL, and its progress is exposed as Prometheus
+//! metrics.
+
+pub mod checkpoint;
+pub mod config;
+pub mod db;
+pub mod error;
+pub mod evm;
+pub mod metrics;
+pub mod propagation;
+pub mod retry;
+pub mod stellar;
+pub mod stream;
+pub mod swaps;
+pub mod watcher;
+
+pub use error::RelayerError;
+
+mod test;
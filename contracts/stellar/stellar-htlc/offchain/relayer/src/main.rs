@@ -0,0 +1,174 @@
+use axum::Router;
+use std::{
+    net::SocketAddr,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use stellar_htlc_relayer::{
+    checkpoint::Checkpoint,
+    config::Config,
+    db::Database,
+    evm::{EvmSubmitter, EvmWatcher},
+    metrics::{self, Metrics},
+    propagation::{Chain, Propagator},
+    stellar::StellarSubmitter,
+    stream::{self, UpdateStream},
+    watcher::StellarWatcher,
+    RelayerError,
+};
+use stellar_htlc_sdk::rpc::RpcClient;
+use tracing::{info, warn};
+use tracing_subscriber::EnvFilter;
+
+#[tokio::main]
+async fn main() -> Result<(), RelayerError> {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+
+    let config = Config::from_env()?;
+    let mut checkpoint = Checkpoint::load(&config.state_path)?.unwrap_or_default();
+    info!(
+        swaps = checkpoint.swaps.len(),
+        cursor = ?checkpoint.cursor,
+        "relayer starting"
+    );
+    let database = match &config.database_url {
+        Some(url) => {
+            let database = Database::connect(url).await?;
+            let changed = database.reconcile(checkpoint.swaps.iter()).await?;
+            info!(changed, "database reconciled");
+            Some(database)
+        }
+        None => None,
+    };
+    // Journaled for the metrics and the database, whether or not anyone subscribes
+    let updates = UpdateStream::default().with_journal();
+    let metrics = Arc::new(Metrics::new());
+    if let Some(listen) = config.ws_listen {
+        serve(listen, stream::router(updates.clone())).await?;
+        info!(%listen, "streaming swap updates");
+    }
+    if let Some(listen) = config.metrics_listen {
+        serve(listen, metrics::router(metrics.clone())).await?;
+        info!(%listen, "serving metrics");
+    }
+    let watcher = StellarWatcher::new(
+        RpcClient::new(&config.rpc_url),
+        config.contract_id.clone(),
+        config.start_ledger,
+    )
+    .with_updates(updates.clone());
+    let evm_watcher = match config.evm.clone() {
+        Some(evm) => {
+            let evm_watcher = EvmWatcher::connect(evm)
+                .await?
+                .with_updates(updates.clone());
+            info!(chain_id = evm_watcher.chain_id(), "watching EVM escrows");
+            Some(evm_watcher)
+        }
+        None => None,
+    };
+    let stellar_submitter = match &config.stellar_signer {
+        Some(signer) => Some(
+            StellarSubmitter::connect(
+                RpcClient::new(&config.rpc_url),
+                &config.contract_id,
+                signer.load().await?,
+            )
+            .await?,
+        ),
+        None => None,
+    };
+    let evm_submitter = match config
+        .evm
+        .as_ref()
+        .and_then(|evm| Some((evm, evm.private_key.as_ref()?)))
+    {
+        Some((evm, private_key)) => Some(EvmSubmitter::connect(&evm.rpc_url, private_key).await?),
+        None => None,
+    };
+    let propagator = (stellar_submitter.is_some() || evm_submitter.is_some())
+        .then(|| Propagator::new(stellar_submitter, evm_submitter, config.retry));
+    if let Some(propagator) = &propagator {
+        let identity = propagator.identity();
+        info!(stellar = ?identity.stellar, evm = ?identity.evm, "propagating secrets");
+    }
+
+    let mut unrecorded = Vec::new();
+    loop {
+        let mut caught_up = match watcher.poll(&mut checkpoint).await {
+            Ok(events) => events == 0,
+            Err(e) => {
+                warn!(error = %e, "Stellar poll failed, retrying");
+                metrics.rpc_error(Chain::Stellar);
+                true
+            }
+        };
+        if let Some(evm_watcher) = &evm_watcher {
+            caught_up &= match evm_watcher.poll(&mut checkpoint).await {
+                Ok(blocks_left) => blocks_left == 0,
+                Err(e) => {
+                    warn!(error = %e, "EVM poll failed, retrying");
+                    metrics.rpc_error(Chain::Evm);
+                    true
+                }
+            };
+        }
+        let journal = updates.take_journal();
+        metrics.observe(&checkpoint.swaps, &journal, unix_now());
+        metrics.heights(checkpoint.latest_ledger, checkpoint.evm_block);
+        // Updates a failed write left behind are retried with the next ones
+        if let Some(database) = &database {
+            unrecorded.extend(journal);
+            match database.record(&checkpoint.swaps, &unrecorded).await {
+                Ok(()) => unrecorded.clear(),
+                Err(e) => {
+                    warn!(error = %e, pending = unrecorded.len(), "recording swaps failed, retrying")
+                }
+            }
+        }
+        checkpoint.save(&config.state_path)?;
+
+        // Withdrawals are saved once signed, before they are first broadcast
+        if let Some(propagator) = &propagator {
+            if propagator.prepare(&mut checkpoint, unix_now()).await > 0 {
+                checkpoint.save(&config.state_path)?;
+            }
+            propagator.follow(&mut checkpoint, unix_now()).await;
+            checkpoint.save(&config.state_path)?;
+            metrics.submissions(checkpoint.submissions.values());
+        }
+
+        if caught_up {
+            if let Some(propagator) = &propagator {
+                metrics.refresh_balances(propagator).await;
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(config.poll_interval) => {}
+                _ = tokio::signal::ctrl_c() => break,
+            }
+        }
+    }
+
+    info!(swaps = checkpoint.swaps.len(), "relayer stopped");
+    Ok(())
+}
+
+/// Serves `app` on `listen` in the background
+async fn serve(listen: SocketAddr, app: Router) -> Result<(), RelayerError> {
+    let listener = tokio::net::TcpListener::bind(listen).await?;
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            warn!(%listen, error = %e, "HTTP server stopped");
+        }
+    });
+    Ok(())
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("clock after 1970")
+        .as_secs()
+}
@@ -0,0 +1,249 @@
+//! Prometheus metrics of the relayer, served as text at `GET /metrics`
+
+use crate::{
+    propagation::{Chain, Propagator, Submission, SubmissionStatus},
+    stream::{SwapUpdate, UpdateKind},
+    swaps::{SwapBook, SwapState},
+};
+use axum::{extract::State, http::header, response::IntoResponse, routing::get, Router};
+use prometheus::{
+    exponential_buckets, Encoder, GaugeVec, Histogram, HistogramOpts, IntCounterVec, IntGauge,
+    IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use std::{collections::BTreeSet, sync::Arc};
+use stellar_htlc_sdk::fusion::ChainId;
+use tracing::warn;
+
+const SUBMISSION_STATES: [SubmissionStatus; 5] = [
+    SubmissionStatus::Pending,
+    SubmissionStatus::Succeeded,
+    SubmissionStatus::Failed,
+    SubmissionStatus::AlreadyApplied,
+    SubmissionStatus::Abandoned,
+];
+
+const STATES: [SwapState; 6] = [
+    SwapState::Pending,
+    SwapState::Locked,
+    SwapState::Revealed,
+    SwapState::Settled,
+    SwapState::Refunded,
+    SwapState::Diverged,
+];
+
+fn chain_label(chain: Chain) -> &'static str {
+    match chain {
+        Chain::Stellar => "stellar",
+        Chain::Evm => "evm",
+    }
+}
+
+pub struct Metrics {
+    registry: Registry,
+    /// Updates applied to the view, by chain and update type
+    updates: IntCounterVec,
+    /// Failed polls and balance reads, by chain
+    rpc_errors: IntCounterVec,
+    /// Swaps in the view, by state
+    swaps: IntGaugeVec,
+    /// Withdrawals submitted, by status
+    submissions: IntGaugeVec,
+    /// From a swap's Stellar HTLC creation to its last leg's withdrawal
+    settlement: Histogram,
+    /// Latest ledger the Soroban RPC reported
+    latest_ledger: IntGauge,
+    /// Next EVM block to scan
+    evm_block: IntGauge,
+    /// Native balances of the submitting accounts, in stroops and wei
+    balances: GaugeVec,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry =
+            Registry::new_custom(Some("relayer".into()), None).expect("valid registry prefix");
+        let metrics = Metrics {
+            updates: IntCounterVec::new(
+                Opts::new("updates_total", "Swap updates applied, by chain and type"),
+                &["chain", "type"],
+            )
+            .expect("valid metric"),
+            rpc_errors: IntCounterVec::new(
+                Opts::new(
+                    "rpc_errors_total",
+                    "Failed RPC polls and balance reads, by chain",
+                ),
+                &["chain"],
+            )
+            .expect("valid metric"),
+            swaps: IntGaugeVec::new(
+                Opts::new("swaps", "Swaps in the view, by state"),
+                &["state"],
+            )
+            .expect("valid metric"),
+            submissions: IntGaugeVec::new(
+                Opts::new(
+                    "submissions",
+                    "Withdrawals the relayer submitted, by status",
+                ),
+                &["status"],
+            )
+            .expect("valid metric"),
+            settlement: Histogram::with_opts(
+                HistogramOpts::new(
+                    "settlement_seconds",
+                    "Time from a swap's Stellar HTLC creation to its settlement",
+                )
+                // 15 seconds to about 8.5 hours
+                .buckets(exponential_buckets(15.0, 2.0, 12).expect("valid buckets")),
+            )
+            .expect("valid metric"),
+            latest_ledger: IntGauge::new("latest_ledger", "Latest ledger the Soroban RPC reported")
+                .expect("valid metric"),
+            evm_block: IntGauge::new("evm_block", "Next EVM block to scan").expect("valid metric"),
+            balances: GaugeVec::new(
+                Opts::new(
+                    "wallet_balance",
+                    "Native balance of the submitting account, in stroops or wei",
+                ),
+                &["chain"],
+            )
+            .expect("valid metric"),
+            registry,
+        };
+        metrics.register();
+        metrics
+    }
+
+    fn register(&self) {
+        let collectors: [Box<dyn prometheus::core::Collector>; 8] = [
+            Box::new(self.updates.clone()),
+            Box::new(self.rpc_errors.clone()),
+            Box::new(self.swaps.clone()),
+            Box::new(self.submissions.clone()),
+            Box::new(self.settlement.clone()),
+            Box::new(self.latest_ledger.clone()),
+            Box::new(self.evm_block.clone()),
+            Box::new(self.balances.clone()),
+        ];
+        for collector in collectors {
+            self.registry
+                .register(collector)
+                .expect("metrics registered once");
+        }
+    }
+
+    pub fn rpc_error(&self, chain: Chain) {
+        self.rpc_errors
+            .with_label_values(&[chain_label(chain)])
+            .inc();
+    }
+
+    /// Counts the updates of a poll and refreshes the gauges from the view they left.
+    /// A swap settled by them is timed against unix time `now`.
+    pub fn observe(&self, swaps: &SwapBook, updates: &[SwapUpdate], now: u64) {
+        for update in updates {
+            let chain = match update.chain {
+                ChainId::Stellar => Chain::Stellar,
+                ChainId::Evm(_) => Chain::Evm,
+            };
+            self.updates
+                .with_label_values(&[chain_label(chain), update.kind.as_str()])
+                .inc();
+        }
+
+        // Both legs of a swap may settle in one poll
+        let settled = updates
+            .iter()
+            .filter(|update| update.kind == UpdateKind::Settled)
+            .map(|update| update.hashlock)
+            .collect::<BTreeSet<_>>();
+        for swap in settled.iter().filter_map(|hashlock| swaps.get(hashlock)) {
+            if let (SwapState::Settled, Some(stellar)) = (swap.state(), &swap.stellar) {
+                let seconds = now.saturating_sub(stellar.htlc.timestamp);
+                self.settlement.observe(seconds as f64);
+            }
+        }
+
+        let mut counts = [0i64; STATES.len()];
+        for swap in swaps.iter() {
+            let state = swap.state();
+            counts[STATES
+                .iter()
+                .position(|s| *s == state)
+                .expect("every state listed")] += 1;
+        }
+        for (state, count) in STATES.iter().zip(counts) {
+            self.swaps.with_label_values(&[state.as_str()]).set(count);
+        }
+    }
+
+    /// Counts the withdrawals of the checkpoint by status; abandoned ones need an operator
+    pub fn submissions<'a>(&self, submissions: impl IntoIterator<Item = &'a Submission>) {
+        let mut counts = [0i64; SUBMISSION_STATES.len()];
+        for submission in submissions {
+            counts[SUBMISSION_STATES
+                .iter()
+                .position(|status| *status == submission.status)
+                .expect("every status listed")] += 1;
+        }
+        for (status, count) in SUBMISSION_STATES.iter().zip(counts) {
+            self.submissions
+                .with_label_values(&[status.as_str()])
+                .set(count);
+        }
+    }
+
+    /// Records where both watchers stand
+    pub fn heights(&self, latest_ledger: u32, evm_block: Option<u64>) {
+        self.latest_ledger.set(latest_ledger.into());
+        if let Some(block) = evm_block {
+            self.evm_block.set(i64::try_from(block).unwrap_or(i64::MAX));
+        }
+    }
+
+    /// Reads the balances of the propagator's accounts
+    pub async fn refresh_balances(&self, propagator: &Propagator) {
+        for (chain, balance) in propagator.balances().await {
+            match balance {
+                Ok(balance) => self
+                    .balances
+                    .with_label_values(&[chain_label(chain)])
+                    .set(balance),
+                Err(e) => {
+                    warn!(chain = chain_label(chain), error = %e, "balance read failed");
+                    self.rpc_error(chain);
+                }
+            }
+        }
+    }
+
+    /// Every metric in the Prometheus text format
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("text encoding cannot fail");
+        String::from_utf8(buffer).expect("text format is UTF-8")
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics::new()
+    }
+}
+
+/// `GET /metrics`
+pub fn router(metrics: Arc<Metrics>) -> Router {
+    Router::new()
+        .route("/metrics", get(serve))
+        .with_state(metrics)
+}
+
+async fn serve(State(metrics): State<Arc<Metrics>>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)],
+        metrics.render(),
+    )
+}
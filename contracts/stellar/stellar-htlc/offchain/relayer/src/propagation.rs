@@ -0,0 +1,569 @@
+//! Propagation of revealed secrets to the other leg of a swap
+//!
+//! Once a withdrawal on one chain reveals a swap's preimage, the engine checks it against
+//! the hashlock and withdraws the leg still locked on the other chain. Every withdrawal
+//! is signed and recorded in the checkpoint before it is broadcast, keyed by hashlock:
+//! a swap gets at most one transaction in flight, a restart rebroadcasts the recorded
+//! transaction instead of signing a new one, and a fresh one is only signed once the
+//! previous one failed or can no longer be included. The `retry` module decides when that
+//! is, and when a stuck transaction has its fee raised instead.
+
+use crate::{
+    checkpoint::Checkpoint,
+    evm::{self, EscrowSide, EvmEscrow, EvmSubmitter, TimelockStage},
+    retry::{self, Failure, RetryPolicy},
+    stellar::StellarSubmitter,
+    swaps::{EscrowStatus, StellarEscrow, Swap},
+    RelayerError,
+};
+use alloy::primitives::Address;
+use serde::{Deserialize, Serialize};
+use stellar_htlc_sdk::{secret, Hash32, HashAlgo};
+use tracing::{debug, error, info, warn};
+
+/// Default number of transactions signed for one swap before giving up on it
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Chain {
+    Stellar,
+    Evm,
+}
+
+/// A signed transaction, kept verbatim so that it can be rebroadcast as is
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedTx {
+    /// Hex transaction hash
+    pub hash: String,
+    /// Base64 XDR envelope on Stellar, hex RLP on EVM chains
+    pub raw: String,
+    /// Upper time bound of a Stellar transaction
+    pub valid_until: Option<u64>,
+    /// Nonce of an EVM transaction
+    pub nonce: Option<u64>,
+}
+
+/// Inclusion status of a broadcast transaction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TxStatus {
+    /// Not included yet
+    Pending,
+    Succeeded,
+    /// Included but failed
+    Failed,
+    /// Can no longer be included: expired, or its nonce went to another transaction
+    Dropped,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SubmissionStatus {
+    /// Signed, possibly broadcast, not yet included
+    Pending,
+    Succeeded,
+    /// Failed, dropped or rejected; retried with a new transaction once `retry_at` passed
+    Failed,
+    /// The leg was withdrawn by a transaction the submission does not follow
+    AlreadyApplied,
+    /// Given up: out of attempts, or failed in a way no retry can fix
+    Abandoned,
+}
+
+impl SubmissionStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SubmissionStatus::Pending => "pending",
+            SubmissionStatus::Succeeded => "succeeded",
+            SubmissionStatus::Failed => "failed",
+            SubmissionStatus::AlreadyApplied => "alreadyApplied",
+            SubmissionStatus::Abandoned => "abandoned",
+        }
+    }
+}
+
+/// The relayer's withdrawal of one swap's remaining leg
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Submission {
+    pub chain: Chain,
+    /// Latest transaction signed, `None` when signing itself failed
+    pub tx: Option<SignedTx>,
+    /// Transactions signed or attempted so far
+    pub attempts: u32,
+    pub status: SubmissionStatus,
+    pub error: Option<String>,
+    /// When `tx` was first broadcast
+    #[serde(default)]
+    pub broadcast_at: Option<u64>,
+    /// Fee raises of the current attempt
+    #[serde(default)]
+    pub fee_bumps: u32,
+    /// Hashes of the transactions of the current attempt `tx` replaced; any of them may
+    /// still be included instead
+    #[serde(default)]
+    pub replaced: Vec<String>,
+    /// When a failed submission may be signed again
+    #[serde(default)]
+    pub retry_at: Option<u64>,
+}
+
+impl Submission {
+    /// A pending `attempts`-th attempt on `chain`, before its transaction is signed
+    pub fn new(chain: Chain, attempts: u32) -> Self {
+        Submission {
+            chain,
+            tx: None,
+            attempts,
+            status: SubmissionStatus::Pending,
+            error: None,
+            broadcast_at: None,
+            fee_bumps: 0,
+            replaced: Vec::new(),
+            retry_at: None,
+        }
+    }
+
+    /// Records a failed attempt at unix time `now`. It is retried after the policy's
+    /// backoff while attempts remain, unless `failure` ends the submission.
+    pub fn fail(&mut self, failure: Failure, error: String, policy: &RetryPolicy, now: u64) {
+        self.error = Some(error);
+        self.status = match failure {
+            Failure::AlreadyApplied => SubmissionStatus::AlreadyApplied,
+            Failure::Terminal => SubmissionStatus::Abandoned,
+            Failure::Retry if self.attempts >= policy.max_attempts => SubmissionStatus::Abandoned,
+            Failure::Retry => {
+                self.retry_at = Some(now + policy.backoff(self.attempts));
+                SubmissionStatus::Failed
+            }
+        };
+    }
+}
+
+/// Accounts the relayer submits from, `None` for chains it does not submit to
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Identity {
+    /// Strkey of the Stellar account
+    pub stellar: Option<String>,
+    pub evm: Option<Address>,
+}
+
+/// Withdrawal the relayer can submit for a swap
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Target {
+    /// `withdraw`, or `public_withdraw` once the public period started
+    Stellar { contract_id: Hash32, public: bool },
+    /// The taker's `withdraw` of a source escrow
+    Evm(Box<EvmEscrow>),
+}
+
+impl Target {
+    pub fn chain(&self) -> Chain {
+        match self {
+            Target::Stellar { .. } => Chain::Stellar,
+            Target::Evm(_) => Chain::Evm,
+        }
+    }
+}
+
+/// What to do about a swap
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Plan {
+    /// No secret to propagate, or a submission already in flight or settled
+    Idle,
+    Withdraw(Target),
+    /// A secret the relayer cannot propagate, at least for now
+    Ineligible(&'static str),
+}
+
+/// Decides what to submit for `swap` at unix time `now`, given the relayer's submission
+/// so far. Only the leg opposite the one whose withdrawal revealed the secret is
+/// withdrawn, only with a secret matching the hashlock, and not before a failed
+/// submission's backoff passed.
+pub fn plan(
+    swap: &Swap,
+    submission: Option<&Submission>,
+    identity: &Identity,
+    now: u64,
+    max_attempts: u32,
+) -> Plan {
+    let Some(secret) = &swap.secret else {
+        return Plan::Idle;
+    };
+    match submission {
+        Some(submission) if submission.status != SubmissionStatus::Failed => return Plan::Idle,
+        Some(submission) if submission.attempts >= max_attempts => return Plan::Idle,
+        Some(submission) if submission.retry_at.is_some_and(|retry_at| now < retry_at) => {
+            return Plan::Idle
+        }
+        _ => {}
+    }
+    let status = |status: Option<EscrowStatus>| status.unwrap_or(EscrowStatus::Refunded);
+    let stellar = status(swap.stellar.as_ref().map(|escrow| escrow.status));
+    let evm = status(swap.evm.as_ref().map(|escrow| escrow.status));
+    match (stellar, evm) {
+        (EscrowStatus::Active, EscrowStatus::Withdrawn) => {
+            let escrow = swap.stellar.as_ref().expect("active legs exist");
+            plan_stellar(escrow, secret, identity, now)
+        }
+        (EscrowStatus::Withdrawn, EscrowStatus::Active) => {
+            let escrow = swap.evm.as_ref().expect("active legs exist");
+            plan_evm(escrow, secret, identity, now)
+        }
+        _ => Plan::Idle,
+    }
+}
+
+fn plan_stellar(escrow: &StellarEscrow, secret: &Hash32, identity: &Identity, now: u64) -> Plan {
+    let htlc = &escrow.htlc;
+    let Some(account) = &identity.stellar else {
+        return Plan::Ineligible("no Stellar account to submit from");
+    };
+    if secret::hash(htlc.hash_algo, &secret.0) != htlc.hashlock {
+        return Plan::Ineligible("secret does not match the hashlock");
+    }
+    if htlc.parts > 0 {
+        return Plan::Ineligible("partial fills need a Merkle proof per part");
+    }
+    if now >= htlc.timelock {
+        return Plan::Ineligible("timelock expired");
+    }
+    // Mirrors the contract: a designated taker withdraws alone until the public period
+    let public_started = htlc.public_withdrawal != 0 && now >= htlc.public_withdrawal;
+    let withdrawer = if htlc.taker != htlc.receiver && !public_started {
+        &htlc.taker
+    } else {
+        &htlc.receiver
+    };
+    if account == withdrawer || public_started {
+        Plan::Withdraw(Target::Stellar {
+            contract_id: escrow.contract_id,
+            public: account != withdrawer,
+        })
+    } else {
+        Plan::Ineligible("not the HTLC's withdrawer")
+    }
+}
+
+fn plan_evm(escrow: &EvmEscrow, secret: &Hash32, identity: &Identity, now: u64) -> Plan {
+    let Some(account) = identity.evm else {
+        return Plan::Ineligible("no EVM account to submit from");
+    };
+    if secret::hash(HashAlgo::Keccak256, &secret.0) != escrow.hashlock {
+        return Plan::Ineligible("secret does not match the hashlock");
+    }
+    let (EscrowSide::Source, Some(immutables)) = (escrow.side, &escrow.immutables) else {
+        return Plan::Ineligible("destination escrow immutables are unknown");
+    };
+    if escrow.taker != account {
+        return Plan::Ineligible("not the escrow's taker");
+    }
+    if now < evm::timelock(immutables.timelocks, TimelockStage::SrcWithdrawal) {
+        return Plan::Ineligible("withdrawal period not started");
+    }
+    if now >= evm::timelock(immutables.timelocks, TimelockStage::SrcCancellation) {
+        return Plan::Ineligible("cancellation period started");
+    }
+    Plan::Withdraw(Target::Evm(Box::new(escrow.clone())))
+}
+
+/// Submits withdrawals for revealed secrets, from the chains it has an account on
+pub struct Propagator {
+    stellar: Option<StellarSubmitter>,
+    evm: Option<EvmSubmitter>,
+    policy: RetryPolicy,
+}
+
+impl Propagator {
+    pub fn new(
+        stellar: Option<StellarSubmitter>,
+        evm: Option<EvmSubmitter>,
+        policy: RetryPolicy,
+    ) -> Self {
+        Propagator {
+            stellar,
+            evm,
+            policy,
+        }
+    }
+
+    pub fn identity(&self) -> Identity {
+        Identity {
+            stellar: self.stellar.as_ref().map(StellarSubmitter::address),
+            evm: self.evm.as_ref().map(EvmSubmitter::address),
+        }
+    }
+
+    /// Native balances of the submitting accounts, in stroops and wei
+    pub async fn balances(&self) -> Vec<(Chain, Result<f64, RelayerError>)> {
+        let mut balances = Vec::with_capacity(2);
+        if let Some(stellar) = &self.stellar {
+            balances.push((Chain::Stellar, stellar.balance().await.map(|b| b as f64)));
+        }
+        if let Some(evm) = &self.evm {
+            balances.push((Chain::Evm, evm.balance().await.map(f64::from)));
+        }
+        balances
+    }
+
+    /// Signs a withdrawal for every swap that needs one, raises the fee of stuck ones and
+    /// closes failed ones whose leg was withdrawn anyway, returning how many submissions
+    /// changed. Nothing is broadcast: the checkpoint is meant to be saved before `follow`
+    /// sends them.
+    pub async fn prepare(&self, checkpoint: &mut Checkpoint, now: u64) -> usize {
+        let mut changed = settle_applied(checkpoint) + self.bump_stuck(checkpoint, now).await;
+        let identity = self.identity();
+        let plans: Vec<_> = checkpoint
+            .swaps
+            .iter()
+            .filter_map(|swap| {
+                let submission = checkpoint.submissions.get(&swap.hashlock);
+                match plan(swap, submission, &identity, now, self.policy.max_attempts) {
+                    Plan::Withdraw(target) => Some((swap.hashlock, swap.secret?, target)),
+                    Plan::Ineligible(reason) => {
+                        debug!(hashlock = %swap.hashlock, reason, "secret not propagated");
+                        None
+                    }
+                    Plan::Idle => None,
+                }
+            })
+            .collect();
+
+        for (hashlock, secret, target) in plans {
+            let attempts = checkpoint
+                .submissions
+                .get(&hashlock)
+                .map_or(0, |submission| submission.attempts)
+                + 1;
+            let mut submission = Submission::new(target.chain(), attempts);
+            match self.sign(&target, secret, now).await {
+                Ok(tx) => {
+                    info!(%hashlock, chain = ?target.chain(), attempts, "signed withdrawal");
+                    submission.tx = Some(tx);
+                }
+                Err(e) => {
+                    warn!(%hashlock, chain = ?target.chain(), attempts, error = %e, "withdrawal not signed");
+                    self.fail(
+                        &hashlock,
+                        &mut submission,
+                        retry::classify(&e),
+                        e.to_string(),
+                        now,
+                    );
+                }
+            }
+            checkpoint.submissions.insert(hashlock, submission);
+            changed += 1;
+        }
+        changed
+    }
+
+    /// Replaces every stuck transaction with one paying a higher fee, returning how many
+    /// were. The transactions they replace are still followed, as either may be included.
+    async fn bump_stuck(&self, checkpoint: &mut Checkpoint, now: u64) -> usize {
+        let mut bumped = 0;
+        for (hashlock, submission) in checkpoint.submissions.iter_mut() {
+            if submission.status != SubmissionStatus::Pending
+                || !self.policy.is_stuck(submission, now)
+            {
+                continue;
+            }
+            let tx = submission
+                .tx
+                .clone()
+                .expect("stuck submissions have a transaction");
+            let fee_bumps = submission.fee_bumps + 1;
+            match self.bump(submission.chain, &tx, fee_bumps).await {
+                Ok(replacement) => {
+                    info!(%hashlock, tx = replacement.hash, replaced = tx.hash, fee_bumps, "raised withdrawal fee");
+                    submission.replaced.push(tx.hash);
+                    submission.tx = Some(replacement);
+                    submission.fee_bumps = fee_bumps;
+                    submission.broadcast_at = None;
+                    bumped += 1;
+                }
+                // The stuck transaction is still rebroadcast meanwhile
+                Err(e) => warn!(%hashlock, tx = tx.hash, error = %e, "fee raise failed"),
+            }
+        }
+        bumped
+    }
+
+    /// Follows every pending submission: records its outcome once included or dropped,
+    /// and broadcasts it again while it is not
+    pub async fn follow(&self, checkpoint: &mut Checkpoint, now: u64) {
+        for (hashlock, submission) in checkpoint.submissions.iter_mut() {
+            if submission.status != SubmissionStatus::Pending {
+                continue;
+            }
+            let Some(tx) = submission.tx.clone() else {
+                continue;
+            };
+            let status = match self.included(submission, &tx, now).await {
+                Ok(status) => status,
+                Err(e) => {
+                    warn!(%hashlock, tx = tx.hash, error = %e, "status check failed");
+                    continue;
+                }
+            };
+            match status {
+                TxStatus::Succeeded => {
+                    info!(%hashlock, tx = tx.hash, "withdrawal included");
+                    submission.status = SubmissionStatus::Succeeded;
+                }
+                TxStatus::Failed | TxStatus::Dropped => {
+                    warn!(%hashlock, tx = tx.hash, ?status, "withdrawal not included");
+                    let error = format!("transaction {status:?}");
+                    self.fail(hashlock, submission, Failure::Retry, error, now);
+                }
+                TxStatus::Pending => match self.broadcast(submission.chain, &tx).await {
+                    Ok(()) => {
+                        submission.broadcast_at.get_or_insert(now);
+                    }
+                    Err(e) => {
+                        warn!(%hashlock, tx = tx.hash, error = %e, "broadcast rejected");
+                        self.fail(
+                            hashlock,
+                            submission,
+                            retry::classify(&e),
+                            e.to_string(),
+                            now,
+                        );
+                    }
+                },
+            }
+        }
+    }
+
+    /// Records a failed attempt, logging it if it ended the submission
+    fn fail(
+        &self,
+        hashlock: &Hash32,
+        submission: &mut Submission,
+        failure: Failure,
+        error: String,
+        now: u64,
+    ) {
+        submission.fail(failure, error, &self.policy, now);
+        match submission.status {
+            SubmissionStatus::Abandoned => error!(
+                %hashlock,
+                attempts = submission.attempts,
+                error = submission.error,
+                "withdrawal abandoned"
+            ),
+            SubmissionStatus::AlreadyApplied => info!(%hashlock, "withdrawal already applied"),
+            _ => {}
+        }
+    }
+
+    async fn sign(
+        &self,
+        target: &Target,
+        secret: Hash32,
+        now: u64,
+    ) -> Result<SignedTx, RelayerError> {
+        match target {
+            Target::Stellar {
+                contract_id,
+                public,
+            } => {
+                self.stellar_submitter()?
+                    .withdraw(*contract_id, secret, *public, now)
+                    .await
+            }
+            Target::Evm(escrow) => {
+                let immutables = evm::src_immutables(escrow).expect("planned escrows have them");
+                self.evm_submitter()?
+                    .withdraw(escrow.address, immutables, secret)
+                    .await
+            }
+        }
+    }
+
+    /// Status of the current attempt: included as soon as any of its transactions is,
+    /// otherwise that of its latest transaction
+    async fn included(
+        &self,
+        submission: &Submission,
+        tx: &SignedTx,
+        now: u64,
+    ) -> Result<TxStatus, RelayerError> {
+        for hash in &submission.replaced {
+            let replaced = SignedTx {
+                hash: hash.clone(),
+                ..tx.clone()
+            };
+            match self.status(submission.chain, &replaced, now).await? {
+                status @ (TxStatus::Succeeded | TxStatus::Failed) => return Ok(status),
+                TxStatus::Pending | TxStatus::Dropped => {}
+            }
+        }
+        self.status(submission.chain, tx, now).await
+    }
+
+    async fn status(
+        &self,
+        chain: Chain,
+        tx: &SignedTx,
+        now: u64,
+    ) -> Result<TxStatus, RelayerError> {
+        match chain {
+            Chain::Stellar => self.stellar_submitter()?.status(tx, now).await,
+            Chain::Evm => self.evm_submitter()?.status(tx).await,
+        }
+    }
+
+    /// Replacement of `tx` paying a higher fee, the `fee_bumps`-th of its attempt
+    async fn bump(
+        &self,
+        chain: Chain,
+        tx: &SignedTx,
+        fee_bumps: u32,
+    ) -> Result<SignedTx, RelayerError> {
+        match chain {
+            Chain::Stellar => self.stellar_submitter()?.bump(tx, fee_bumps).await,
+            Chain::Evm => self.evm_submitter()?.bump(tx).await,
+        }
+    }
+
+    async fn broadcast(&self, chain: Chain, tx: &SignedTx) -> Result<(), RelayerError> {
+        match chain {
+            Chain::Stellar => self.stellar_submitter()?.broadcast(tx).await,
+            Chain::Evm => self.evm_submitter()?.broadcast(tx).await,
+        }
+    }
+
+    fn stellar_submitter(&self) -> Result<&StellarSubmitter, RelayerError> {
+        self.stellar
+            .as_ref()
+            .ok_or_else(|| RelayerError::Config("no Stellar signer is configured".into()))
+    }
+
+    fn evm_submitter(&self) -> Result<&EvmSubmitter, RelayerError> {
+        self.evm
+            .as_ref()
+            .ok_or_else(|| RelayerError::Config("no EVM key is configured".into()))
+    }
+}
+
+/// Closes the failed submissions whose leg was withdrawn anyway, by another withdrawer or
+/// by a transaction of theirs that was lost track of, returning how many were
+fn settle_applied(checkpoint: &mut Checkpoint) -> usize {
+    let mut settled = 0;
+    for (hashlock, submission) in checkpoint.submissions.iter_mut() {
+        if submission.status != SubmissionStatus::Failed {
+            continue;
+        }
+        let Some(swap) = checkpoint.swaps.get(hashlock) else {
+            continue;
+        };
+        let status = match submission.chain {
+            Chain::Stellar => swap.stellar.as_ref().map(|escrow| escrow.status),
+            Chain::Evm => swap.evm.as_ref().map(|escrow| escrow.status),
+        };
+        if status == Some(EscrowStatus::Withdrawn) {
+            info!(%hashlock, "withdrawal already applied");
+            submission.status = SubmissionStatus::AlreadyApplied;
+            settled += 1;
+        }
+    }
+    settled
+}
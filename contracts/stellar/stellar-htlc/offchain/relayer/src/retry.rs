@@ -0,0 +1,103 @@
+//! Retries of the relayer's withdrawals
+//!
+//! A withdrawal that failed is signed again once a delay doubling with every attempt has
+//! passed, until its attempts run out. One that stays pending is rebroadcast as is, and
+//! once stuck its fee is raised: Stellar transactions are wrapped in a fee bump, EVM ones
+//! signed again with the same nonce and a higher gas price. A withdrawal the contract
+//! already applied ends its submission, and so does an error no retry can fix.
+
+use crate::{
+    propagation::{Submission, DEFAULT_MAX_ATTEMPTS},
+    RelayerError,
+};
+use stellar_htlc_sdk::ContractError;
+
+/// Default wait after the first failed attempt, doubled after each next one
+pub const DEFAULT_BACKOFF_SECS: u64 = 30;
+
+/// Longest wait between two attempts
+pub const MAX_BACKOFF_SECS: u64 = 3_600;
+
+/// Default time a transaction stays pending before its fee is raised
+pub const DEFAULT_STUCK_AFTER_SECS: u64 = 60;
+
+/// Default number of fee raises per attempt
+pub const DEFAULT_MAX_FEE_BUMPS: u32 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Transactions signed per swap before giving up on it
+    pub max_attempts: u32,
+    /// Seconds to wait after the first failed attempt
+    pub backoff: u64,
+    /// Seconds a transaction stays pending before its fee is raised
+    pub stuck_after: u64,
+    /// Fee raises of one attempt; a transaction still pending after them is only
+    /// rebroadcast
+    pub max_fee_bumps: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            backoff: DEFAULT_BACKOFF_SECS,
+            stuck_after: DEFAULT_STUCK_AFTER_SECS,
+            max_fee_bumps: DEFAULT_MAX_FEE_BUMPS,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Seconds to wait after `attempts` failed attempts
+    pub fn backoff(&self, attempts: u32) -> u64 {
+        let doublings = attempts.saturating_sub(1).min(31);
+        self.backoff
+            .saturating_mul(1 << doublings)
+            .min(MAX_BACKOFF_SECS)
+    }
+
+    /// Whether the pending transaction of `submission` should have its fee raised at unix
+    /// time `now`
+    pub fn is_stuck(&self, submission: &Submission, now: u64) -> bool {
+        let Some(tx) = &submission.tx else {
+            return false;
+        };
+        submission.fee_bumps < self.max_fee_bumps
+            && submission
+                .broadcast_at
+                .is_some_and(|broadcast_at| now >= broadcast_at + self.stuck_after)
+            // An expired transaction is replaced by a new attempt instead
+            && tx.valid_until.is_none_or(|valid_until| now < valid_until)
+    }
+}
+
+/// What a failed attempt means for its submission
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Failure {
+    /// Worth another attempt once the backoff passed
+    Retry,
+    /// The leg was withdrawn already, by another transaction
+    AlreadyApplied,
+    /// No attempt can succeed
+    Terminal,
+}
+
+/// Sorts the error of a signing or broadcast attempt. Only contract errors and missing
+/// accounts are conclusive; anything else may be transient.
+pub fn classify(error: &RelayerError) -> Failure {
+    match error {
+        RelayerError::Stellar(error) => match error.contract_error() {
+            Some(ContractError::AlreadyWithdrawn) => Failure::AlreadyApplied,
+            Some(
+                ContractError::AlreadyRefunded
+                | ContractError::ContractNotFound
+                | ContractError::InvalidPreimage
+                | ContractError::TimelockExpired,
+            ) => Failure::Terminal,
+            _ => Failure::Retry,
+        },
+        RelayerError::Config(_) => Failure::Terminal,
+        _ => Failure::Retry,
+    }
+}
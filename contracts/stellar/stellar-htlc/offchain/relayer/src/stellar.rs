@@ -0,0 +1,113 @@
+//! Signing and submission of withdrawals to the Stellar HTLC contract
+
+use crate::{
+    propagation::{SignedTx, TxStatus},
+    RelayerError,
+};
+use stellar_htlc_sdk::{
+    client::TransactionStatus,
+    rpc::RpcClient,
+    transaction::{Invocation, SignedTransaction, BASE_FEE, FEE_BUMP_MULTIPLIER, TX_VALIDITY_SECS},
+    Hash32, HtlcClient, Signer,
+};
+
+pub struct StellarSubmitter {
+    client: HtlcClient,
+    key: Box<dyn Signer>,
+}
+
+impl StellarSubmitter {
+    /// Submits to `contract_id` as the account of `key`
+    pub async fn connect(
+        rpc: RpcClient,
+        contract_id: &str,
+        key: Box<dyn Signer>,
+    ) -> Result<Self, RelayerError> {
+        let passphrase = rpc.get_network().await?.passphrase;
+        let client = HtlcClient::new(rpc, contract_id, &passphrase)
+            .map_err(|error| RelayerError::Config(error.to_string()))?;
+        Ok(StellarSubmitter { client, key })
+    }
+
+    /// Strkey of the submitting account
+    pub fn address(&self) -> String {
+        self.key.address()
+    }
+
+    /// Native balance of the submitting account, in stroops
+    pub async fn balance(&self) -> Result<i64, RelayerError> {
+        Ok(self.client.balance(&*self.key).await?)
+    }
+
+    /// Signs a `withdraw`, or a `public_withdraw` claiming the safety deposit, of
+    /// `contract_id` revealing `preimage`. The invocation is simulated first, so a
+    /// withdrawal the contract would reject fails here instead of on chain.
+    pub async fn withdraw(
+        &self,
+        contract_id: Hash32,
+        preimage: Hash32,
+        public: bool,
+        now: u64,
+    ) -> Result<SignedTx, RelayerError> {
+        let invocation = if public {
+            Invocation::public_withdraw(&self.address(), contract_id, preimage)?
+        } else {
+            Invocation::withdraw(contract_id, preimage)
+        };
+        let tx = self
+            .client
+            .prepare(&*self.key, &invocation, now + TX_VALIDITY_SECS)
+            .await?;
+        Ok(SignedTx {
+            hash: tx.hash,
+            raw: tx.envelope,
+            valid_until: Some(tx.valid_until),
+            nonce: None,
+        })
+    }
+
+    /// Fee bump of `tx`, the `fee_bumps`-th of its transaction, paid by the submitting
+    /// account. Each one offers `FEE_BUMP_MULTIPLIER` times the fee rate of the last, the
+    /// least that replaces it while pending.
+    pub async fn bump(&self, tx: &SignedTx, fee_bumps: u32) -> Result<SignedTx, RelayerError> {
+        let inclusion_fee =
+            i64::from(BASE_FEE).saturating_mul(FEE_BUMP_MULTIPLIER.saturating_pow(fee_bumps));
+        let tx = self
+            .client
+            .fee_bump(&*self.key, &signed_transaction(tx), inclusion_fee)
+            .await?;
+        Ok(SignedTx {
+            hash: tx.hash,
+            raw: tx.envelope,
+            valid_until: Some(tx.valid_until),
+            nonce: None,
+        })
+    }
+
+    /// Sends a signed transaction; sending one the network already has is a no-op
+    pub async fn broadcast(&self, tx: &SignedTx) -> Result<(), RelayerError> {
+        Ok(self.client.send(&signed_transaction(tx)).await?)
+    }
+
+    pub async fn status(&self, tx: &SignedTx, now: u64) -> Result<TxStatus, RelayerError> {
+        Ok(match self.client.status(&tx.hash).await? {
+            TransactionStatus::Succeeded { .. } => TxStatus::Succeeded,
+            TransactionStatus::Failed { .. } => TxStatus::Failed,
+            // Past its time bounds an unseen transaction can no longer be included
+            TransactionStatus::NotFound
+                if tx.valid_until.is_some_and(|valid_until| now > valid_until) =>
+            {
+                TxStatus::Dropped
+            }
+            TransactionStatus::NotFound => TxStatus::Pending,
+        })
+    }
+}
+
+fn signed_transaction(tx: &SignedTx) -> SignedTransaction {
+    SignedTransaction {
+        hash: tx.hash.clone(),
+        envelope: tx.raw.clone(),
+        valid_until: tx.valid_until.unwrap_or(u64::MAX),
+    }
+}
@@ -0,0 +1,247 @@
+//! Swap lifecycle updates, normalized across both chains and broadcast over WebSocket
+//!
+//! Updates are only derived from events that changed the view, so events replayed after a
+//! restart are not sent twice. A subscriber that falls more than the channel's capacity
+//! behind misses the oldest updates.
+
+use crate::{
+    evm::{EvmEvent, EvmEventKind},
+    swaps::{EscrowStatus, Swap},
+};
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
+    response::Response,
+    routing::get,
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use stellar_htlc_sdk::{
+    events::{ContractEvent, HtlcEvent},
+    fusion::ChainId,
+    Hash32,
+};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{debug, warn};
+
+/// Updates held for subscribers that are behind
+pub const DEFAULT_UPDATE_CAPACITY: usize = 1_024;
+
+/// What happened to an escrow
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum UpdateKind {
+    /// The escrow locked `amount` of its token, in base units; unknown for EVM
+    /// destination escrows
+    EscrowCreated { amount: Option<String> },
+    /// The first withdrawal of the swap made its secret public
+    SecretRevealed { secret: Hash32 },
+    /// The escrow was withdrawn to its receiver
+    Settled,
+    /// The escrow was refunded, or cancelled on EVM chains
+    Refunded,
+}
+
+impl UpdateKind {
+    /// The `type` it is tagged with
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UpdateKind::EscrowCreated { .. } => "escrowCreated",
+            UpdateKind::SecretRevealed { .. } => "secretRevealed",
+            UpdateKind::Settled => "settled",
+            UpdateKind::Refunded => "refunded",
+        }
+    }
+}
+
+/// A change to one escrow of a swap
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SwapUpdate {
+    pub hashlock: Hash32,
+    pub chain: ChainId,
+    /// HTLC contract ID in hex on Stellar, escrow address on EVM chains
+    pub escrow: String,
+    /// Ledger or block of the event
+    pub height: u64,
+    pub tx_hash: String,
+    #[serde(flatten)]
+    pub kind: UpdateKind,
+}
+
+/// Updates of a Stellar event, given its swap after the event was applied
+pub fn stellar_updates(event: &ContractEvent, swap: &Swap) -> Vec<SwapUpdate> {
+    let update = |kind| SwapUpdate {
+        hashlock: swap.hashlock,
+        chain: ChainId::Stellar,
+        escrow: event.contract_id.to_string(),
+        height: event.ledger.into(),
+        tx_hash: event.tx_hash.clone(),
+        kind,
+    };
+    match &event.event {
+        HtlcEvent::New(htlc) => vec![update(UpdateKind::EscrowCreated {
+            amount: Some(htlc.amount.to_string()),
+        })],
+        HtlcEvent::Withdraw { preimage } => {
+            let first = swap
+                .evm
+                .as_ref()
+                .is_none_or(|escrow| escrow.status != EscrowStatus::Withdrawn);
+            withdrawal(first, *preimage, update)
+        }
+        HtlcEvent::Refund => vec![update(UpdateKind::Refunded)],
+    }
+}
+
+/// Updates of an EVM event, given its swap after the event was applied
+pub fn evm_updates(event: &EvmEvent, swap: &Swap) -> Vec<SwapUpdate> {
+    let chain_id = swap.evm.as_ref().map_or(0, |escrow| escrow.chain_id);
+    let update = |kind| SwapUpdate {
+        hashlock: swap.hashlock,
+        chain: ChainId::Evm(chain_id),
+        escrow: event.escrow.to_string(),
+        height: event.block,
+        tx_hash: event.tx_hash.to_string(),
+        kind,
+    };
+    match &event.kind {
+        EvmEventKind::Created(escrow) => vec![update(UpdateKind::EscrowCreated {
+            amount: escrow
+                .immutables
+                .as_ref()
+                .map(|immutables| immutables.amount.to_string()),
+        })],
+        EvmEventKind::Withdrawn { secret } => {
+            let first = swap
+                .stellar
+                .as_ref()
+                .is_none_or(|escrow| escrow.status != EscrowStatus::Withdrawn);
+            withdrawal(first, *secret, update)
+        }
+        EvmEventKind::Cancelled => vec![update(UpdateKind::Refunded)],
+    }
+}
+
+/// A withdrawal settles its escrow, after revealing the secret if no other leg did before
+fn withdrawal(
+    first: bool,
+    secret: Hash32,
+    update: impl Fn(UpdateKind) -> SwapUpdate,
+) -> Vec<SwapUpdate> {
+    let mut updates = Vec::with_capacity(2);
+    if first {
+        updates.push(update(UpdateKind::SecretRevealed { secret }));
+    }
+    updates.push(update(UpdateKind::Settled));
+    updates
+}
+
+/// Broadcast channel the watchers publish updates to
+#[derive(Clone, Debug)]
+pub struct UpdateStream {
+    sender: broadcast::Sender<SwapUpdate>,
+    /// Updates published since the last `take_journal`, if journaled
+    journal: Option<Arc<Mutex<Vec<SwapUpdate>>>>,
+}
+
+impl UpdateStream {
+    /// A stream holding up to `capacity` updates for subscribers that are behind
+    pub fn new(capacity: usize) -> Self {
+        UpdateStream {
+            sender: broadcast::channel(capacity).0,
+            journal: None,
+        }
+    }
+
+    /// Also keeps every update until `take_journal`, so none is lost to a slow reader
+    pub fn with_journal(mut self) -> Self {
+        self.journal = Some(Arc::default());
+        self
+    }
+
+    /// Sends `updates` to every current subscriber
+    pub fn publish(&self, updates: Vec<SwapUpdate>) {
+        if let Some(journal) = &self.journal {
+            journal
+                .lock()
+                .expect("journal lock")
+                .extend(updates.iter().cloned());
+        }
+        for update in updates {
+            // Without subscribers the update is dropped, which is fine
+            let _ = self.sender.send(update);
+        }
+    }
+
+    /// Updates published since the last call, in order; empty without a journal
+    pub fn take_journal(&self) -> Vec<SwapUpdate> {
+        self.journal
+            .as_ref()
+            .map(|journal| std::mem::take(&mut *journal.lock().expect("journal lock")))
+            .unwrap_or_default()
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<SwapUpdate> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for UpdateStream {
+    fn default() -> Self {
+        UpdateStream::new(DEFAULT_UPDATE_CAPACITY)
+    }
+}
+
+/// Query of the WebSocket endpoint; without a hashlock, every swap's updates are sent
+#[derive(Debug, Deserialize)]
+struct Subscription {
+    hashlock: Option<Hash32>,
+}
+
+/// `GET /ws`, upgraded to a WebSocket sending each update as a JSON text message
+pub fn router(stream: UpdateStream) -> Router {
+    Router::new()
+        .route("/ws", get(subscribe))
+        .with_state(stream)
+}
+
+async fn subscribe(
+    State(stream): State<UpdateStream>,
+    Query(subscription): Query<Subscription>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    let updates = stream.subscribe();
+    upgrade.on_upgrade(move |socket| forward(socket, updates, subscription.hashlock))
+}
+
+async fn forward(
+    mut socket: WebSocket,
+    mut updates: broadcast::Receiver<SwapUpdate>,
+    hashlock: Option<Hash32>,
+) {
+    loop {
+        tokio::select! {
+            update = updates.recv() => match update {
+                Ok(update) if hashlock.is_none_or(|hashlock| hashlock == update.hashlock) => {
+                    let text = serde_json::to_string(&update).expect("updates serialize");
+                    if socket.send(Message::Text(text.into())).await.is_err() {
+                        break;
+                    }
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(missed)) => warn!(missed, "WebSocket subscriber lagged"),
+                Err(RecvError::Closed) => break,
+            },
+            // Subscribers only listen; anything but a close is ignored
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+    debug!("WebSocket subscriber left");
+}
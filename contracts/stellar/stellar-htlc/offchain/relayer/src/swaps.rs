@@ -0,0 +1,257 @@
+//! The relayer's local view of swaps, built by applying decoded events in order
+
+use crate::evm::{EvmEscrow, EvmEvent, EvmEventKind};
+use alloy::primitives::Address;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use stellar_htlc_sdk::{
+    events::{ContractEvent, HtlcEvent, HtlcNew},
+    Hash32,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EscrowStatus {
+    Active,
+    Withdrawn,
+    Refunded,
+}
+
+/// The Stellar HTLC of a swap
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StellarEscrow {
+    pub contract_id: Hash32,
+    pub htlc: HtlcNew,
+    pub status: EscrowStatus,
+    /// Ledger of the withdrawal or refund
+    pub settled_ledger: Option<u32>,
+}
+
+/// A cross-chain swap, identified by the hashlock both of its legs share
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Swap {
+    pub hashlock: Hash32,
+    pub stellar: Option<StellarEscrow>,
+    #[serde(default)]
+    pub evm: Option<EvmEscrow>,
+    /// Preimage, once revealed by a withdrawal on either chain
+    pub secret: Option<Hash32>,
+}
+
+impl Swap {
+    fn new(hashlock: Hash32) -> Self {
+        Swap {
+            hashlock,
+            stellar: None,
+            evm: None,
+            secret: None,
+        }
+    }
+
+    /// Statuses of the legs seen so far, Stellar's first
+    pub fn legs(&self) -> impl Iterator<Item = EscrowStatus> {
+        let stellar = self.stellar.as_ref().map(|escrow| escrow.status);
+        let evm = self.evm.as_ref().map(|escrow| escrow.status);
+        stellar.into_iter().chain(evm)
+    }
+
+    pub fn state(&self) -> SwapState {
+        let legs = self.legs().collect::<Vec<_>>();
+        let all = |status| legs.iter().all(|leg| *leg == status);
+        if legs.contains(&EscrowStatus::Active) {
+            if self.secret.is_some() {
+                SwapState::Revealed
+            } else if legs.len() == 2 {
+                SwapState::Locked
+            } else {
+                SwapState::Pending
+            }
+        } else if all(EscrowStatus::Withdrawn) {
+            SwapState::Settled
+        } else if all(EscrowStatus::Refunded) {
+            SwapState::Refunded
+        } else {
+            SwapState::Diverged
+        }
+    }
+}
+
+/// Where a swap stands, derived from its legs
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SwapState {
+    /// One leg is locked
+    Pending,
+    /// Both legs are locked
+    Locked,
+    /// The secret is public and a leg is still locked
+    Revealed,
+    /// Every leg was withdrawn
+    Settled,
+    /// Every leg was refunded
+    Refunded,
+    /// One leg was withdrawn and the other refunded
+    Diverged,
+}
+
+impl SwapState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SwapState::Pending => "pending",
+            SwapState::Locked => "locked",
+            SwapState::Revealed => "revealed",
+            SwapState::Settled => "settled",
+            SwapState::Refunded => "refunded",
+            SwapState::Diverged => "diverged",
+        }
+    }
+}
+
+/// Outcome of applying an event to the view
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Applied {
+    /// The view changed
+    Updated,
+    /// The event was already reflected, e.g. when replayed after a restart
+    Unchanged,
+    /// The event concerns an escrow created before the relayer started watching
+    UnknownEscrow,
+}
+
+/// Every swap seen so far, with indexes from Stellar contract ID and EVM escrow address
+/// to hashlock
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SwapBook {
+    swaps: BTreeMap<Hash32, Swap>,
+    by_contract_id: BTreeMap<Hash32, Hash32>,
+    #[serde(default)]
+    by_evm_escrow: BTreeMap<Address, Hash32>,
+}
+
+impl SwapBook {
+    pub fn get(&self, hashlock: &Hash32) -> Option<&Swap> {
+        self.swaps.get(hashlock)
+    }
+
+    pub fn by_contract_id(&self, contract_id: &Hash32) -> Option<&Swap> {
+        self.by_contract_id
+            .get(contract_id)
+            .and_then(|hashlock| self.swaps.get(hashlock))
+    }
+
+    pub fn by_evm_escrow(&self, escrow: &Address) -> Option<&Swap> {
+        self.by_evm_escrow
+            .get(escrow)
+            .and_then(|hashlock| self.swaps.get(hashlock))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Swap> {
+        self.swaps.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.swaps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.swaps.is_empty()
+    }
+
+    /// Applies an event; replaying an event already applied leaves the view unchanged
+    pub fn apply(&mut self, event: &ContractEvent) -> Applied {
+        match &event.event {
+            HtlcEvent::New(htlc) => {
+                let escrow = StellarEscrow {
+                    contract_id: event.contract_id,
+                    htlc: (**htlc).clone(),
+                    status: EscrowStatus::Active,
+                    settled_ledger: None,
+                };
+                if self.by_contract_id(&event.contract_id).is_some() {
+                    return Applied::Unchanged;
+                }
+                self.by_contract_id.insert(event.contract_id, htlc.hashlock);
+                let swap = self
+                    .swaps
+                    .entry(htlc.hashlock)
+                    .or_insert_with(|| Swap::new(htlc.hashlock));
+                // A reused hashlock moves the swap on to its newest HTLC
+                swap.stellar = Some(escrow);
+                Applied::Updated
+            }
+            HtlcEvent::Withdraw { preimage } => {
+                self.settle(event, EscrowStatus::Withdrawn, Some(*preimage))
+            }
+            HtlcEvent::Refund => self.settle(event, EscrowStatus::Refunded, None),
+        }
+    }
+
+    /// Applies a normalized EVM escrow event, with the same replay guarantees as `apply`
+    pub fn apply_evm(&mut self, event: &EvmEvent) -> Applied {
+        let (status, secret) = match &event.kind {
+            EvmEventKind::Created(escrow) => {
+                if self.by_evm_escrow.contains_key(&event.escrow) {
+                    return Applied::Unchanged;
+                }
+                self.by_evm_escrow.insert(event.escrow, escrow.hashlock);
+                let swap = self
+                    .swaps
+                    .entry(escrow.hashlock)
+                    .or_insert_with(|| Swap::new(escrow.hashlock));
+                swap.evm = Some((**escrow).clone());
+                return Applied::Updated;
+            }
+            EvmEventKind::Withdrawn { secret } => (EscrowStatus::Withdrawn, Some(*secret)),
+            EvmEventKind::Cancelled => (EscrowStatus::Refunded, None),
+        };
+
+        let Some(hashlock) = self.by_evm_escrow.get(&event.escrow) else {
+            return Applied::UnknownEscrow;
+        };
+        let swap = self.swaps.get_mut(hashlock).expect("indexed swaps exist");
+        let Some(escrow) = swap
+            .evm
+            .as_mut()
+            .filter(|escrow| escrow.address == event.escrow)
+        else {
+            return Applied::Unchanged;
+        };
+        if escrow.status != EscrowStatus::Active {
+            return Applied::Unchanged;
+        }
+        escrow.status = status;
+        escrow.settled_block = Some(event.block);
+        if secret.is_some() {
+            swap.secret = secret;
+        }
+        Applied::Updated
+    }
+
+    fn settle(
+        &mut self,
+        event: &ContractEvent,
+        status: EscrowStatus,
+        secret: Option<Hash32>,
+    ) -> Applied {
+        let Some(hashlock) = self.by_contract_id.get(&event.contract_id) else {
+            return Applied::UnknownEscrow;
+        };
+        let swap = self.swaps.get_mut(hashlock).expect("indexed swaps exist");
+        let Some(escrow) = swap
+            .stellar
+            .as_mut()
+            .filter(|escrow| escrow.contract_id == event.contract_id)
+        else {
+            // Settlement of an older HTLC whose hashlock has since been reused
+            return Applied::Unchanged;
+        };
+        if escrow.status != EscrowStatus::Active {
+            return Applied::Unchanged;
+        }
+        escrow.status = status;
+        escrow.settled_ledger = Some(event.ledger);
+        if secret.is_some() {
+            swap.secret = secret;
+        }
+        Applied::Updated
+    }
+}
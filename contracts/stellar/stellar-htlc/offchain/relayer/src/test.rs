@@ -0,0 +1,999 @@
+#![cfg(test)]
+
+use crate::{
+    checkpoint::Checkpoint,
+    db::{self, Database},
+    evm::{
+        self, DstImmutablesComplement, EscrowLog, EscrowSide, EscrowWithdrawal, EvmEvent,
+        EvmEventKind, Immutables, SrcEscrowCreated, TimelockStage, Withdrawal,
+    },
+    metrics::Metrics,
+    propagation::{
+        self, Chain, Identity, Plan, Propagator, SignedTx, Submission, SubmissionStatus, Target,
+        DEFAULT_MAX_ATTEMPTS,
+    },
+    retry::{self, Failure, RetryPolicy, MAX_BACKOFF_SECS},
+    stream::{self, SwapUpdate, UpdateKind, UpdateStream},
+    swaps::{Applied, EscrowStatus, StellarEscrow, Swap, SwapBook, SwapState},
+    watcher::StellarWatcher,
+    RelayerError,
+};
+use alloy::{
+    primitives::{address, Address, LogData, B256, U256},
+    rpc::types::Log,
+    sol_types::SolEvent,
+};
+use futures_util::StreamExt;
+use stellar_htlc_sdk::{
+    events::{self, ContractEvent, HtlcEvent},
+    fusion::ChainId,
+    rpc::{EventInfo, RpcClient},
+    secret,
+    testutils::*,
+    ContractError, Hash32, HashAlgo,
+};
+use stellar_xdr::ScVal;
+
+fn decoded(info: &EventInfo) -> ContractEvent {
+    events::decode(info).unwrap().unwrap()
+}
+
+//------------------------------------------------------------------
+//  Swap view
+//------------------------------------------------------------------
+#[test]
+fn withdraw_reveals_secret() {
+    let mut book = SwapBook::default();
+    let new = decoded(&event_info("1", "HTLCNew", htlc_new_payload()));
+    assert_eq!(book.apply(&new), Applied::Updated);
+
+    let withdraw = decoded(&event_info(
+        "2",
+        "HTLCWithdraw",
+        lifecycle(bytes(&PREIMAGE.0)),
+    ));
+    assert_eq!(book.apply(&withdraw), Applied::Updated);
+
+    let swap = book.get(&HASHLOCK).unwrap();
+    let escrow = swap.stellar.as_ref().unwrap();
+    assert_eq!(escrow.status, EscrowStatus::Withdrawn);
+    assert_eq!(escrow.settled_ledger, Some(100));
+    assert_eq!(swap.secret, Some(PREIMAGE));
+    assert_eq!(book.by_contract_id(&CONTRACT), Some(swap));
+}
+
+#[test]
+fn replayed_events_change_nothing() {
+    let mut book = SwapBook::default();
+    let new = decoded(&event_info("1", "HTLCNew", htlc_new_payload()));
+    let refund = decoded(&event_info(
+        "2",
+        "HTLCRefund",
+        lifecycle(bytes(&CONTRACT.0)),
+    ));
+    book.apply(&new);
+    book.apply(&refund);
+    let settled = book.clone();
+
+    assert_eq!(book.apply(&new), Applied::Unchanged);
+    assert_eq!(book.apply(&refund), Applied::Unchanged);
+    assert_eq!(book, settled);
+    assert_eq!(
+        book.get(&HASHLOCK)
+            .unwrap()
+            .stellar
+            .as_ref()
+            .unwrap()
+            .status,
+        EscrowStatus::Refunded
+    );
+}
+
+#[test]
+fn settlement_of_unseen_htlc() {
+    let mut book = SwapBook::default();
+    let refund = decoded(&event_info(
+        "2",
+        "HTLCRefund",
+        lifecycle(bytes(&CONTRACT.0)),
+    ));
+    assert_eq!(book.apply(&refund), Applied::UnknownEscrow);
+    assert!(book.is_empty());
+}
+
+//------------------------------------------------------------------
+//  EVM escrows
+//------------------------------------------------------------------
+const FACTORY: Address = address!("a7bcb4eac8964306f9e3764f67db6a7af6ddf99a");
+const SRC_ESCROW: Address = address!("00000000000000000000000000000000000e5c01");
+const MAKER: Address = address!("00000000000000000000000000000000000000aa");
+
+/// `timelocks` deployed at `deployed_at` with the given stage offsets
+fn pack_timelocks(deployed_at: u64, offsets: [u32; 7]) -> U256 {
+    offsets.iter().enumerate().fold(
+        U256::from(deployed_at) << 224usize,
+        |packed, (stage, offset)| packed | (U256::from(*offset) << (stage * 32)),
+    )
+}
+
+/// An address packed as the protocol's `Address`, with flag bits above the low 160
+fn packed(address: Address) -> U256 {
+    U256::from_be_bytes(address.into_word().0) | (U256::from(1) << 200usize)
+}
+
+fn evm_log(emitter: Address, data: LogData, block: u64) -> Log {
+    Log {
+        inner: alloy::primitives::Log {
+            address: emitter,
+            data,
+        },
+        block_number: Some(block),
+        transaction_hash: Some(B256::repeat_byte(0xee)),
+        log_index: Some(0),
+        ..Default::default()
+    }
+}
+
+fn src_escrow_created() -> SrcEscrowCreated {
+    SrcEscrowCreated {
+        srcImmutables: Immutables {
+            orderHash: B256::repeat_byte(5),
+            hashlock: B256::from(HASHLOCK.0),
+            maker: packed(MAKER),
+            taker: packed(Address::repeat_byte(0xbb)),
+            token: packed(Address::repeat_byte(0xcc)),
+            amount: U256::from(10u64.pow(18)),
+            safetyDeposit: U256::from(10u64.pow(16)),
+            timelocks: pack_timelocks(1_700_000_000, [60, 600, 3_600, 4_200, 30, 300, 1_800]),
+        },
+        dstImmutablesComplement: DstImmutablesComplement {
+            maker: packed(MAKER),
+            amount: U256::from(1_000_000_000u64),
+            token: U256::ZERO,
+            safetyDeposit: U256::from(100_000_000u64),
+            chainId: U256::from(148u64),
+        },
+    }
+}
+
+/// Normalized event of a log emitted by `emitter` concerning `escrow`
+fn evm_event(emitter: Address, data: LogData, escrow: Address) -> EvmEvent {
+    let log = evm_log(emitter, data, 100);
+    evm::decode_log(&log, FACTORY)
+        .unwrap()
+        .unwrap()
+        .into_event(&log, escrow, 1)
+}
+
+#[test]
+fn timelock_stages_unpack() {
+    let timelocks = src_escrow_created().srcImmutables.timelocks;
+    assert_eq!(
+        evm::timelock(timelocks, TimelockStage::SrcWithdrawal),
+        1_700_000_060
+    );
+    assert_eq!(
+        evm::timelock(timelocks, TimelockStage::SrcCancellation),
+        1_700_003_600
+    );
+    assert_eq!(
+        evm::timelock(timelocks, TimelockStage::DstCancellation),
+        1_700_001_800
+    );
+}
+
+#[test]
+fn src_escrow_created_normalizes() {
+    let event = evm_event(FACTORY, src_escrow_created().encode_log_data(), SRC_ESCROW);
+    assert_eq!(event.escrow, SRC_ESCROW);
+    assert_eq!(event.block, 100);
+    let EvmEventKind::Created(escrow) = event.kind else {
+        panic!("expected a creation");
+    };
+    assert_eq!(escrow.side, EscrowSide::Source);
+    assert_eq!(escrow.chain_id, 1);
+    assert_eq!(escrow.hashlock, HASHLOCK);
+    assert_eq!(escrow.taker, Address::repeat_byte(0xbb));
+    let immutables = escrow.immutables.unwrap();
+    assert_eq!(immutables.maker, MAKER);
+    assert_eq!(immutables.token, Address::repeat_byte(0xcc));
+    assert_eq!(immutables.dst_chain_id, U256::from(148u64));
+}
+
+#[test]
+fn creations_count_only_from_factory() {
+    let log = evm_log(
+        Address::repeat_byte(1),
+        src_escrow_created().encode_log_data(),
+        100,
+    );
+    assert_eq!(evm::decode_log(&log, FACTORY).unwrap(), None);
+
+    // Escrow events are decoded from any emitter and matched against tracked escrows
+    let withdrawal = Withdrawal {
+        secret: B256::from(PREIMAGE.0),
+    };
+    let log = evm_log(Address::repeat_byte(1), withdrawal.encode_log_data(), 100);
+    assert_eq!(
+        evm::decode_log(&log, FACTORY).unwrap(),
+        Some(EscrowLog::Withdrawn { secret: PREIMAGE })
+    );
+}
+
+#[test]
+fn both_legs_share_a_swap() {
+    let mut book = SwapBook::default();
+    book.apply(&decoded(&event_info("1", "HTLCNew", htlc_new_payload())));
+    let created = evm_event(FACTORY, src_escrow_created().encode_log_data(), SRC_ESCROW);
+    assert_eq!(book.apply_evm(&created), Applied::Updated);
+    assert_eq!(book.len(), 1);
+
+    // The maker's withdrawal on the EVM chain reveals the secret for the Stellar leg
+    let withdrawal = EscrowWithdrawal {
+        secret: B256::from(PREIMAGE.0),
+    };
+    let withdrawn = evm_event(SRC_ESCROW, withdrawal.encode_log_data(), SRC_ESCROW);
+    assert_eq!(book.apply_evm(&withdrawn), Applied::Updated);
+    assert_eq!(book.apply_evm(&withdrawn), Applied::Unchanged);
+    assert_eq!(book.apply_evm(&created), Applied::Unchanged);
+
+    let swap = book.by_evm_escrow(&SRC_ESCROW).unwrap();
+    assert_eq!(swap.secret, Some(PREIMAGE));
+    assert_eq!(swap.evm.as_ref().unwrap().status, EscrowStatus::Withdrawn);
+    assert_eq!(swap.evm.as_ref().unwrap().settled_block, Some(100));
+    assert_eq!(swap.stellar.as_ref().unwrap().status, EscrowStatus::Active);
+    assert_eq!(book.by_contract_id(&CONTRACT), Some(swap));
+}
+
+#[test]
+fn cancellation_of_unseen_escrow() {
+    let mut book = SwapBook::default();
+    let cancelled = evm_event(
+        SRC_ESCROW,
+        evm::EscrowCancelled {}.encode_log_data(),
+        SRC_ESCROW,
+    );
+    assert_eq!(book.apply_evm(&cancelled), Applied::UnknownEscrow);
+}
+
+//------------------------------------------------------------------
+//  Secret propagation
+//------------------------------------------------------------------
+const RESOLVER: Address = address!("00000000000000000000000000000000000000bb");
+
+fn stellar_account(byte: u8) -> String {
+    let ScVal::Address(address) = account(byte) else {
+        unreachable!();
+    };
+    address.to_string()
+}
+
+/// A swap whose EVM source escrow was withdrawn with `PREIMAGE`, Stellar HTLC still locked
+fn revealed_swap() -> Swap {
+    let hashlock = secret::hash(HashAlgo::Keccak256, &PREIMAGE.0);
+    let HtlcEvent::New(mut htlc) = decoded(&event_info("1", "HTLCNew", htlc_new_payload())).event
+    else {
+        unreachable!();
+    };
+    htlc.hashlock = hashlock;
+    let EvmEventKind::Created(mut escrow) =
+        evm_event(FACTORY, src_escrow_created().encode_log_data(), SRC_ESCROW).kind
+    else {
+        unreachable!();
+    };
+    escrow.hashlock = hashlock;
+    escrow.taker = RESOLVER;
+    escrow.status = EscrowStatus::Withdrawn;
+    Swap {
+        hashlock,
+        stellar: Some(StellarEscrow {
+            contract_id: CONTRACT,
+            htlc: *htlc,
+            status: EscrowStatus::Active,
+            settled_ledger: None,
+        }),
+        evm: Some(*escrow),
+        secret: Some(PREIMAGE),
+    }
+}
+
+fn identity() -> Identity {
+    Identity {
+        stellar: Some(stellar_account(2)),
+        evm: Some(RESOLVER),
+    }
+}
+
+/// Swap the other way round: Stellar HTLC withdrawn, EVM source escrow still locked
+fn reversed(mut swap: Swap) -> Swap {
+    swap.stellar.as_mut().unwrap().status = EscrowStatus::Withdrawn;
+    swap.evm.as_mut().unwrap().status = EscrowStatus::Active;
+    swap
+}
+
+fn plan(swap: &Swap, submission: Option<&Submission>, identity: &Identity, now: u64) -> Plan {
+    propagation::plan(swap, submission, identity, now, DEFAULT_MAX_ATTEMPTS)
+}
+
+#[test]
+fn secret_withdraws_the_other_leg() {
+    let now = 1_700_000_100;
+    let swap = revealed_swap();
+    assert_eq!(
+        plan(&swap, None, &identity(), now),
+        Plan::Withdraw(Target::Stellar {
+            contract_id: CONTRACT,
+            public: false,
+        })
+    );
+
+    let swap = reversed(swap);
+    let Plan::Withdraw(Target::Evm(escrow)) = plan(&swap, None, &identity(), now) else {
+        panic!("expected an EVM withdrawal");
+    };
+    assert_eq!(escrow.address, SRC_ESCROW);
+    let immutables = evm::src_immutables(&escrow).unwrap();
+    assert_eq!(immutables.hashlock, B256::from(swap.hashlock.0));
+    assert_eq!(
+        immutables.taker,
+        U256::from_be_bytes(RESOLVER.into_word().0)
+    );
+
+    // The escrow only takes the secret between its withdrawal and cancellation stages
+    assert_eq!(
+        plan(&swap, None, &identity(), 1_700_000_059),
+        Plan::Ineligible("withdrawal period not started")
+    );
+    assert_eq!(
+        plan(&swap, None, &identity(), 1_700_003_600),
+        Plan::Ineligible("cancellation period started")
+    );
+
+    // Nothing is left to withdraw once both legs settled, or before any secret
+    let mut settled = swap.clone();
+    settled.evm.as_mut().unwrap().status = EscrowStatus::Withdrawn;
+    assert_eq!(plan(&settled, None, &identity(), now), Plan::Idle);
+    let mut unrevealed = revealed_swap();
+    unrevealed.secret = None;
+    assert_eq!(plan(&unrevealed, None, &identity(), now), Plan::Idle);
+}
+
+#[test]
+fn secret_is_checked_before_submission() {
+    let now = 1_700_000_100;
+    let mut wrong = revealed_swap();
+    wrong.secret = Some(HASHLOCK);
+    assert_eq!(
+        plan(&wrong, None, &identity(), now),
+        Plan::Ineligible("secret does not match the hashlock")
+    );
+    assert_eq!(
+        plan(&reversed(wrong), None, &identity(), now),
+        Plan::Ineligible("secret does not match the hashlock")
+    );
+
+    // A SHA-256 HTLC sharing the hashlock cannot have been created for this secret
+    let mut sha256 = revealed_swap();
+    sha256.stellar.as_mut().unwrap().htlc.hash_algo = HashAlgo::Sha256;
+    assert_eq!(
+        plan(&sha256, None, &identity(), now),
+        Plan::Ineligible("secret does not match the hashlock")
+    );
+}
+
+#[test]
+fn withdrawals_need_the_right_account() {
+    let now = 1_700_000_100;
+    let stranger = Identity {
+        stellar: Some(stellar_account(4)),
+        evm: Some(Address::repeat_byte(4)),
+    };
+    let swap = revealed_swap();
+    assert_eq!(
+        plan(&swap, None, &stranger, now),
+        Plan::Ineligible("not the HTLC's withdrawer")
+    );
+    assert_eq!(
+        plan(&swap, None, &Identity::default(), now),
+        Plan::Ineligible("no Stellar account to submit from")
+    );
+    assert_eq!(
+        plan(&reversed(swap.clone()), None, &stranger, now),
+        Plan::Ineligible("not the escrow's taker")
+    );
+
+    // Anyone may withdraw once the public period started, claiming the safety deposit
+    let mut public = swap.clone();
+    public.stellar.as_mut().unwrap().htlc.public_withdrawal = now;
+    assert_eq!(
+        plan(&public, None, &stranger, now),
+        Plan::Withdraw(Target::Stellar {
+            contract_id: CONTRACT,
+            public: true,
+        })
+    );
+
+    // A designated taker withdraws alone until then, the receiver after
+    let mut taker = swap.clone();
+    taker.stellar.as_mut().unwrap().htlc.taker = stellar_account(4);
+    assert_eq!(
+        plan(&taker, None, &identity(), now),
+        Plan::Ineligible("not the HTLC's withdrawer")
+    );
+    assert!(matches!(
+        plan(&taker, None, &stranger, now),
+        Plan::Withdraw(Target::Stellar { public: false, .. })
+    ));
+
+    let mut partial = swap.clone();
+    partial.stellar.as_mut().unwrap().htlc.parts = 4;
+    assert_eq!(
+        plan(&partial, None, &identity(), now),
+        Plan::Ineligible("partial fills need a Merkle proof per part")
+    );
+
+    let mut destination = reversed(swap);
+    let escrow = destination.evm.as_mut().unwrap();
+    escrow.side = EscrowSide::Destination;
+    escrow.immutables = None;
+    assert_eq!(
+        plan(&destination, None, &identity(), now),
+        Plan::Ineligible("destination escrow immutables are unknown")
+    );
+}
+
+#[test]
+fn submissions_are_not_repeated() {
+    let now = 1_700_000_100;
+    let swap = revealed_swap();
+    let submission = |status, attempts| Submission {
+        status,
+        ..Submission::new(Chain::Stellar, attempts)
+    };
+
+    // One transaction in flight per swap, none after it succeeded
+    let pending = submission(SubmissionStatus::Pending, 1);
+    assert_eq!(plan(&swap, Some(&pending), &identity(), now), Plan::Idle);
+    let succeeded = submission(SubmissionStatus::Succeeded, 1);
+    assert_eq!(plan(&swap, Some(&succeeded), &identity(), now), Plan::Idle);
+
+    // A failed one is replaced until the attempts run out
+    let failed = submission(SubmissionStatus::Failed, DEFAULT_MAX_ATTEMPTS - 1);
+    assert!(matches!(
+        plan(&swap, Some(&failed), &identity(), now),
+        Plan::Withdraw(_)
+    ));
+    let exhausted = submission(SubmissionStatus::Failed, DEFAULT_MAX_ATTEMPTS);
+    assert_eq!(plan(&swap, Some(&exhausted), &identity(), now), Plan::Idle);
+
+    // Nor before its backoff passed, or once given up
+    let backing_off = Submission {
+        retry_at: Some(now + 1),
+        ..failed.clone()
+    };
+    assert_eq!(
+        plan(&swap, Some(&backing_off), &identity(), now),
+        Plan::Idle
+    );
+    assert!(matches!(
+        plan(&swap, Some(&backing_off), &identity(), now + 1),
+        Plan::Withdraw(_)
+    ));
+    for status in [
+        SubmissionStatus::AlreadyApplied,
+        SubmissionStatus::Abandoned,
+    ] {
+        let ended = submission(status, 1);
+        assert_eq!(plan(&swap, Some(&ended), &identity(), now), Plan::Idle);
+    }
+}
+
+//------------------------------------------------------------------
+//  Retries
+//------------------------------------------------------------------
+fn stellar_tx(valid_until: u64) -> SignedTx {
+    SignedTx {
+        hash: "ab".repeat(32),
+        raw: "AAAAAg==".into(),
+        valid_until: Some(valid_until),
+        nonce: None,
+    }
+}
+
+#[test]
+fn failed_attempts_back_off_until_given_up() {
+    let policy = RetryPolicy::default();
+    assert_eq!(policy.backoff(1), 30);
+    assert_eq!(policy.backoff(2), 60);
+    assert_eq!(policy.backoff(3), 120);
+    assert_eq!(policy.backoff(40), MAX_BACKOFF_SECS);
+
+    let now = 1_700_000_000;
+    let mut submission = Submission::new(Chain::Stellar, 2);
+    submission.fail(Failure::Retry, "transaction Dropped".into(), &policy, now);
+    assert_eq!(submission.status, SubmissionStatus::Failed);
+    assert_eq!(submission.retry_at, Some(now + 60));
+    assert_eq!(submission.error.as_deref(), Some("transaction Dropped"));
+
+    // The last attempt, or an error no retry fixes, ends the submission
+    let mut last = Submission::new(Chain::Stellar, policy.max_attempts);
+    last.fail(Failure::Retry, "transaction Failed".into(), &policy, now);
+    assert_eq!(last.status, SubmissionStatus::Abandoned);
+    let mut terminal = Submission::new(Chain::Stellar, 1);
+    terminal.fail(Failure::Terminal, "timelock expired".into(), &policy, now);
+    assert_eq!(terminal.status, SubmissionStatus::Abandoned);
+    let mut applied = Submission::new(Chain::Stellar, 1);
+    applied.fail(Failure::AlreadyApplied, "withdrawn".into(), &policy, now);
+    assert_eq!(applied.status, SubmissionStatus::AlreadyApplied);
+}
+
+#[test]
+fn errors_are_classified() {
+    let contract = |error| RelayerError::Stellar(stellar_htlc_sdk::Error::Contract(error));
+    assert_eq!(
+        retry::classify(&contract(ContractError::AlreadyWithdrawn)),
+        Failure::AlreadyApplied
+    );
+    assert_eq!(
+        retry::classify(&contract(ContractError::TimelockExpired)),
+        Failure::Terminal
+    );
+    assert_eq!(
+        retry::classify(&contract(ContractError::PublicWithdrawalNotStarted)),
+        Failure::Retry
+    );
+    assert_eq!(
+        retry::classify(&RelayerError::Stellar(stellar_htlc_sdk::Error::Rpc {
+            code: -32600,
+            message: "busy".into(),
+        })),
+        Failure::Retry
+    );
+    assert_eq!(
+        retry::classify(&RelayerError::Config("no EVM key is configured".into())),
+        Failure::Terminal
+    );
+}
+
+#[test]
+fn stuck_transactions_have_their_fee_raised() {
+    let policy = RetryPolicy::default();
+    let now = 1_700_000_000;
+    let mut submission = Submission {
+        tx: Some(stellar_tx(now + 300)),
+        broadcast_at: Some(now),
+        ..Submission::new(Chain::Stellar, 1)
+    };
+    assert!(!policy.is_stuck(&submission, now + policy.stuck_after - 1));
+    assert!(policy.is_stuck(&submission, now + policy.stuck_after));
+
+    // Not before it was broadcast, once expired or after the last raise
+    submission.broadcast_at = None;
+    assert!(!policy.is_stuck(&submission, now + policy.stuck_after));
+    submission.broadcast_at = Some(now);
+    assert!(!policy.is_stuck(&submission, now + 300));
+    submission.fee_bumps = policy.max_fee_bumps;
+    assert!(!policy.is_stuck(&submission, now + policy.stuck_after));
+
+    // EVM fees rise by a quarter, enough for nodes to replace the pending transaction
+    assert_eq!(evm::raised_fee(1_000_000_000), 1_250_000_000);
+    assert_eq!(evm::raised_fee(3), 4);
+}
+
+#[tokio::test]
+async fn failed_withdrawals_of_settled_legs_are_applied() {
+    let now = 1_700_000_100;
+    let mut checkpoint = Checkpoint::default();
+    checkpoint
+        .swaps
+        .apply(&decoded(&event_info("1", "HTLCNew", htlc_new_payload())));
+    checkpoint.swaps.apply(&decoded(&event_info(
+        "2",
+        "HTLCWithdraw",
+        lifecycle(bytes(&PREIMAGE.0)),
+    )));
+    let mut failed = Submission::new(Chain::Stellar, 1);
+    failed.fail(
+        Failure::Retry,
+        "transaction Dropped".into(),
+        &RetryPolicy::default(),
+        now,
+    );
+    checkpoint.submissions.insert(HASHLOCK, failed);
+    // Raising a fee needs an account; the stuck transaction is left as it is
+    let stuck = Submission {
+        tx: Some(stellar_tx(now + 300)),
+        broadcast_at: Some(now - 600),
+        ..Submission::new(Chain::Stellar, 1)
+    };
+    checkpoint
+        .submissions
+        .insert(Hash32([5; 32]), stuck.clone());
+
+    let propagator = Propagator::new(None, None, RetryPolicy::default());
+    assert_eq!(propagator.prepare(&mut checkpoint, now).await, 1);
+    assert_eq!(
+        checkpoint.submissions[&HASHLOCK].status,
+        SubmissionStatus::AlreadyApplied
+    );
+    assert_eq!(checkpoint.submissions[&Hash32([5; 32])], stuck);
+    assert_eq!(propagator.prepare(&mut checkpoint, now).await, 0);
+}
+
+//------------------------------------------------------------------
+//  Checkpointing
+//------------------------------------------------------------------
+#[test]
+fn checkpoint_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("state.json");
+    assert_eq!(Checkpoint::load(&path).unwrap(), None);
+
+    let mut checkpoint = Checkpoint {
+        cursor: Some("0000000429496729600-0000000001".into()),
+        latest_ledger: 120,
+        evm_block: Some(19_000_000),
+        swaps: SwapBook::default(),
+        submissions: [(
+            HASHLOCK,
+            Submission {
+                tx: Some(SignedTx {
+                    hash: format!("0x{}", "cd".repeat(32)),
+                    raw: "0x02f8".into(),
+                    valid_until: None,
+                    nonce: Some(7),
+                }),
+                broadcast_at: Some(1_700_000_000),
+                fee_bumps: 1,
+                replaced: vec![format!("0x{}", "ef".repeat(32))],
+                ..Submission::new(Chain::Evm, 1)
+            },
+        )]
+        .into(),
+    };
+    checkpoint
+        .swaps
+        .apply(&decoded(&event_info("1", "HTLCNew", htlc_new_payload())));
+    checkpoint.save(&path).unwrap();
+    assert_eq!(Checkpoint::load(&path).unwrap(), Some(checkpoint.clone()));
+
+    // Checkpoints written before the EVM watcher and propagation existed still load
+    let mut legacy = serde_json::to_value(&checkpoint).unwrap();
+    legacy.as_object_mut().unwrap().remove("evm_block");
+    legacy.as_object_mut().unwrap().remove("submissions");
+    legacy["swaps"]
+        .as_object_mut()
+        .unwrap()
+        .remove("by_evm_escrow");
+    for swap in legacy["swaps"]["swaps"]
+        .as_object_mut()
+        .unwrap()
+        .values_mut()
+    {
+        swap.as_object_mut().unwrap().remove("evm");
+    }
+    let legacy: Checkpoint = serde_json::from_value(legacy).unwrap();
+    assert_eq!(legacy.evm_block, None);
+    assert!(legacy.submissions.is_empty());
+    assert_eq!(legacy.swaps, checkpoint.swaps);
+
+    // So do submissions written before retries existed
+    let mut submission = serde_json::to_value(&checkpoint.submissions[&HASHLOCK]).unwrap();
+    for field in ["broadcast_at", "fee_bumps", "replaced", "retry_at"] {
+        submission.as_object_mut().unwrap().remove(field);
+    }
+    let submission: Submission = serde_json::from_value(submission).unwrap();
+    assert_eq!((submission.broadcast_at, submission.fee_bumps), (None, 0));
+    assert!(submission.replaced.is_empty());
+
+    // Saving again replaces the file whole
+    checkpoint.cursor = Some("0000000429496729600-0000000002".into());
+    checkpoint.save(&path).unwrap();
+    assert_eq!(Checkpoint::load(&path).unwrap(), Some(checkpoint));
+    assert!(!path.with_extension("tmp").exists());
+}
+
+#[test]
+fn requests_resume_from_cursor() {
+    let watcher = StellarWatcher::new(
+        RpcClient::new("http://localhost:8000/soroban/rpc"),
+        contract_address(8).to_string(),
+        Some(90),
+    );
+    let mut checkpoint = Checkpoint::default();
+    let first = serde_json::to_value(watcher.request(&checkpoint, 90)).unwrap();
+    assert_eq!(first["startLedger"], 90);
+    assert!(first["pagination"].get("cursor").is_none());
+    assert_eq!(first["filters"][0]["topics"].as_array().unwrap().len(), 3);
+    assert_eq!(
+        first["filters"][0]["topics"][0][0],
+        events::topic_symbol("HTLCNew")
+    );
+
+    checkpoint.cursor = Some("0000000429496729600-0000000001".into());
+    let next = serde_json::to_value(watcher.request(&checkpoint, 0)).unwrap();
+    assert!(next.get("startLedger").is_none());
+    assert_eq!(
+        next["pagination"]["cursor"],
+        "0000000429496729600-0000000001"
+    );
+}
+
+//------------------------------------------------------------------
+//  Update stream
+//------------------------------------------------------------------
+#[test]
+fn updates_follow_both_legs() {
+    let mut book = SwapBook::default();
+    let new = decoded(&event_info("1", "HTLCNew", htlc_new_payload()));
+    book.apply(&new);
+    let updates = stream::stellar_updates(&new, book.get(&HASHLOCK).unwrap());
+    assert_eq!(
+        updates,
+        vec![SwapUpdate {
+            hashlock: HASHLOCK,
+            chain: ChainId::Stellar,
+            escrow: CONTRACT.to_string(),
+            height: 100,
+            tx_hash: "ab".repeat(32),
+            kind: UpdateKind::EscrowCreated {
+                amount: Some("1000000000".into()),
+            },
+        }]
+    );
+
+    let created = evm_event(FACTORY, src_escrow_created().encode_log_data(), SRC_ESCROW);
+    book.apply_evm(&created);
+    let updates = stream::evm_updates(&created, book.get(&HASHLOCK).unwrap());
+    assert_eq!(updates[0].chain, ChainId::Evm(1));
+    assert_eq!(
+        updates[0].kind,
+        UpdateKind::EscrowCreated {
+            amount: Some(10u64.pow(18).to_string()),
+        }
+    );
+
+    // The first withdrawal reveals the secret, the second only settles its escrow
+    let withdrawal = EscrowWithdrawal {
+        secret: B256::from(PREIMAGE.0),
+    };
+    let withdrawn = evm_event(SRC_ESCROW, withdrawal.encode_log_data(), SRC_ESCROW);
+    book.apply_evm(&withdrawn);
+    let kinds = stream::evm_updates(&withdrawn, book.get(&HASHLOCK).unwrap())
+        .into_iter()
+        .map(|update| update.kind)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [
+            UpdateKind::SecretRevealed { secret: PREIMAGE },
+            UpdateKind::Settled
+        ]
+    );
+
+    let withdraw = decoded(&event_info(
+        "2",
+        "HTLCWithdraw",
+        lifecycle(bytes(&PREIMAGE.0)),
+    ));
+    book.apply(&withdraw);
+    let updates = stream::stellar_updates(&withdraw, book.get(&HASHLOCK).unwrap());
+    assert_eq!(updates.len(), 1);
+    assert_eq!(updates[0].kind, UpdateKind::Settled);
+
+    let json = serde_json::to_value(&updates[0]).unwrap();
+    assert_eq!(json["type"], "settled");
+    assert_eq!(json["chain"], "stellar");
+    assert_eq!(json["hashlock"], HASHLOCK.to_string());
+}
+
+#[tokio::test]
+async fn websocket_sends_subscribed_updates() {
+    let updates = UpdateStream::default();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = stream::router(updates.clone());
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let (mut socket, _) =
+        tokio_tungstenite::connect_async(format!("ws://{addr}/ws?hashlock={HASHLOCK}"))
+            .await
+            .unwrap();
+    let update = |hashlock| SwapUpdate {
+        hashlock,
+        chain: ChainId::Stellar,
+        escrow: CONTRACT.to_string(),
+        height: 100,
+        tx_hash: "ab".repeat(32),
+        kind: UpdateKind::Refunded,
+    };
+    updates.publish(vec![update(Hash32([5; 32])), update(HASHLOCK)]);
+
+    // Updates of other swaps are filtered out
+    let message = socket.next().await.unwrap().unwrap();
+    let received: SwapUpdate = serde_json::from_str(message.to_text().unwrap()).unwrap();
+    assert_eq!(received, update(HASHLOCK));
+}
+
+#[test]
+fn journal_keeps_every_update() {
+    let updates = UpdateStream::new(1).with_journal();
+    let mut book = SwapBook::default();
+    let new = decoded(&event_info("1", "HTLCNew", htlc_new_payload()));
+    book.apply(&new);
+    let created = stream::stellar_updates(&new, book.get(&HASHLOCK).unwrap());
+    // More updates than the channel holds, and no subscriber
+    updates.publish(created.clone());
+    updates.publish(created.clone());
+    assert_eq!(updates.take_journal(), [created.clone(), created].concat());
+    assert!(updates.take_journal().is_empty());
+    assert!(UpdateStream::default().take_journal().is_empty());
+}
+
+//------------------------------------------------------------------
+//  Database
+//------------------------------------------------------------------
+#[test]
+fn swap_state_follows_legs() {
+    let mut swap = revealed_swap();
+    assert_eq!(swap.state(), SwapState::Revealed);
+    swap.stellar.as_mut().unwrap().status = EscrowStatus::Withdrawn;
+    assert_eq!(swap.state(), SwapState::Settled);
+    swap.evm.as_mut().unwrap().status = EscrowStatus::Refunded;
+    assert_eq!(swap.state(), SwapState::Diverged);
+    swap.stellar.as_mut().unwrap().status = EscrowStatus::Refunded;
+    assert_eq!(swap.state(), SwapState::Refunded);
+
+    swap.secret = None;
+    swap.evm.as_mut().unwrap().status = EscrowStatus::Active;
+    swap.stellar.as_mut().unwrap().status = EscrowStatus::Active;
+    assert_eq!(swap.state(), SwapState::Locked);
+    swap.evm = None;
+    assert_eq!(swap.state(), SwapState::Pending);
+}
+
+#[test]
+fn rows_describe_both_legs() {
+    let swap = revealed_swap();
+    let escrows = db::escrow_rows(&swap);
+    assert_eq!(escrows.len(), 2);
+    assert_eq!(escrows[0].chain, "stellar");
+    assert_eq!(escrows[0].escrow, CONTRACT.to_string());
+    assert_eq!(escrows[0].status, "active");
+    assert_eq!(escrows[0].amount.as_deref(), Some("1000000000"));
+    assert_eq!(escrows[0].created_height, 100);
+    assert_eq!(escrows[1].chain, "1");
+    assert_eq!(escrows[1].escrow, SRC_ESCROW.to_string());
+    assert_eq!(escrows[1].side.as_deref(), Some("source"));
+    assert_eq!(escrows[1].status, "withdrawn");
+    assert_eq!(escrows[1].amount, Some(10u64.pow(18).to_string()));
+
+    // The order comes from the source escrow's immutables
+    let order = db::order_row(&swap).unwrap();
+    assert_eq!(order.order_hash, Hash32([5; 32]));
+    assert_eq!(order.maker, MAKER.to_string());
+    assert_eq!(order.src_chain, "1");
+    assert_eq!(order.dst_chain, "stellar");
+    assert_eq!(order.src_amount, Some(10u64.pow(18).to_string()));
+}
+
+/// Runs against the PostgreSQL database at `TEST_DATABASE_URL`, and is skipped without one
+#[tokio::test]
+async fn database_records_swaps_once() {
+    let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+        return;
+    };
+    let database = Database::connect(&url).await.unwrap();
+    let mut book = SwapBook::default();
+    // Fresh per run, as the database outlives it
+    let hashlock = secret::Secret::generate().unwrap().preimage();
+    let mut new = decoded(&event_info("1", "HTLCNew", htlc_new_payload()));
+    let HtlcEvent::New(htlc) = &mut new.event else {
+        unreachable!();
+    };
+    htlc.hashlock = hashlock;
+    new.contract_id = hashlock;
+    book.apply(&new);
+    let created = stream::stellar_updates(&new, book.get(&hashlock).unwrap());
+    database.record(&book, &created).await.unwrap();
+    // Recording the same updates again changes nothing
+    database.record(&book, &created).await.unwrap();
+
+    let withdraw = ContractEvent {
+        event: HtlcEvent::Withdraw { preimage: PREIMAGE },
+        ..new.clone()
+    };
+    book.apply(&withdraw);
+    let withdrawn = stream::stellar_updates(&withdraw, book.get(&hashlock).unwrap());
+    database.record(&book, &withdrawn).await.unwrap();
+    assert_eq!(database.reconcile(book.iter()).await.unwrap(), 0);
+
+    let record = database.swap(&hashlock).await.unwrap().unwrap();
+    assert_eq!(record.state, "settled");
+    assert!(record.secret_revealed);
+    assert_eq!(record.revealed_chain.as_deref(), Some("stellar"));
+    assert_eq!(record.revealed_height, Some(100));
+    assert_eq!(
+        record.escrows,
+        db::escrow_rows(book.get(&hashlock).unwrap())
+    );
+
+    let kinds = database
+        .events(&hashlock)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|event| event.kind)
+        .collect::<Vec<_>>();
+    assert_eq!(kinds, ["escrowCreated", "secretRevealed", "settled"]);
+    let states = database
+        .transitions(&hashlock)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|transition| (transition.from_state, transition.to_state))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        states,
+        [
+            (None, "pending".to_string()),
+            (Some("pending".to_string()), "settled".to_string())
+        ]
+    );
+    assert!(database
+        .swaps_in(SwapState::Settled, 100)
+        .await
+        .unwrap()
+        .contains(&hashlock));
+}
+
+//------------------------------------------------------------------
+//  Metrics
+//------------------------------------------------------------------
+#[test]
+fn metrics_follow_the_view() {
+    let metrics = Metrics::new();
+    let mut book = SwapBook::default();
+    let new = decoded(&event_info("1", "HTLCNew", htlc_new_payload()));
+    book.apply(&new);
+    let created = stream::stellar_updates(&new, book.get(&HASHLOCK).unwrap());
+    metrics.observe(&book, &created, 1_700_000_000);
+    let text = metrics.render();
+    assert!(text.contains("relayer_updates_total{chain=\"stellar\",type=\"escrowCreated\"} 1"));
+    assert!(text.contains("relayer_swaps{state=\"pending\"} 1"));
+    assert!(text.contains("relayer_settlement_seconds_count 0"));
+
+    let withdraw = decoded(&event_info(
+        "2",
+        "HTLCWithdraw",
+        lifecycle(bytes(&PREIMAGE.0)),
+    ));
+    book.apply(&withdraw);
+    let withdrawn = stream::stellar_updates(&withdraw, book.get(&HASHLOCK).unwrap());
+    let created_at = book
+        .get(&HASHLOCK)
+        .unwrap()
+        .stellar
+        .as_ref()
+        .unwrap()
+        .htlc
+        .timestamp;
+    metrics.observe(&book, &withdrawn, created_at + 90);
+    metrics.rpc_error(Chain::Evm);
+    let mut abandoned = Submission::new(Chain::Stellar, DEFAULT_MAX_ATTEMPTS);
+    abandoned.fail(
+        Failure::Retry,
+        "transaction Failed".into(),
+        &RetryPolicy::default(),
+        0,
+    );
+    metrics.submissions([&abandoned]);
+    let text = metrics.render();
+    assert!(text.contains("relayer_swaps{state=\"pending\"} 0"));
+    assert!(text.contains("relayer_swaps{state=\"settled\"} 1"));
+    assert!(text.contains("relayer_settlement_seconds_sum 90"));
+    assert!(text.contains("relayer_settlement_seconds_count 1"));
+    assert!(text.contains("relayer_rpc_errors_total{chain=\"evm\"} 1"));
+    assert!(text.contains("relayer_submissions{status=\"abandoned\"} 1"));
+    assert!(text.contains("relayer_submissions{status=\"pending\"} 0"));
+}
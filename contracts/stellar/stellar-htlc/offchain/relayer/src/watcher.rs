@@ -0,0 +1,100 @@
+//! Polling of the HTLC contract's events into a checkpoint
+
+use crate::{
+    checkpoint::Checkpoint,
+    stream::{self, UpdateStream},
+    swaps::Applied,
+    RelayerError,
+};
+use stellar_htlc_sdk::{
+    events,
+    rpc::{GetEventsRequest, Pagination, RpcClient, PAGE_LIMIT},
+};
+use tracing::{debug, warn};
+
+pub struct StellarWatcher {
+    rpc: RpcClient,
+    contract_id: String,
+    start_ledger: Option<u32>,
+    updates: Option<UpdateStream>,
+}
+
+impl StellarWatcher {
+    /// Watches `contract_id`; without a checkpoint, starts at `start_ledger` or else at
+    /// the latest ledger
+    pub fn new(rpc: RpcClient, contract_id: String, start_ledger: Option<u32>) -> Self {
+        StellarWatcher {
+            rpc,
+            contract_id,
+            start_ledger,
+            updates: None,
+        }
+    }
+
+    /// Publishes the updates of every event that changes the view to `updates`
+    pub fn with_updates(mut self, updates: UpdateStream) -> Self {
+        self.updates = Some(updates);
+        self
+    }
+
+    /// The next page request after `checkpoint`
+    pub fn request(&self, checkpoint: &Checkpoint, start_ledger: u32) -> GetEventsRequest {
+        GetEventsRequest {
+            start_ledger: checkpoint.cursor.is_none().then_some(start_ledger),
+            filters: vec![events::lifecycle_filter(&self.contract_id)],
+            pagination: Pagination {
+                cursor: checkpoint.cursor.clone(),
+                limit: PAGE_LIMIT,
+            },
+        }
+    }
+
+    /// Fetches the next page of events and applies it to `checkpoint`, returning how many
+    /// events it held. The checkpoint only moves forward once the whole page decoded.
+    pub async fn poll(&self, checkpoint: &mut Checkpoint) -> Result<usize, RelayerError> {
+        let start_ledger = match (&checkpoint.cursor, self.start_ledger) {
+            (Some(_), _) => 0,
+            (None, Some(ledger)) => ledger,
+            (None, None) => self.rpc.get_latest_ledger().await?.sequence,
+        };
+        let page = self
+            .rpc
+            .get_events(&self.request(checkpoint, start_ledger))
+            .await?;
+
+        let decoded = page
+            .events
+            .iter()
+            .filter_map(|info| match events::decode(info) {
+                Ok(event) => event.map(Ok),
+                Err(source) => Some(Err(RelayerError::Decode {
+                    id: info.id.clone(),
+                    source,
+                })),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        for event in &decoded {
+            match checkpoint.swaps.apply(event) {
+                Applied::Updated => {
+                    debug!(id = event.id, contract_id = %event.contract_id, "applied event");
+                    if let (Some(updates), Some(swap)) = (
+                        &self.updates,
+                        checkpoint.swaps.by_contract_id(&event.contract_id),
+                    ) {
+                        updates.publish(stream::stellar_updates(event, swap));
+                    }
+                }
+                Applied::Unchanged => debug!(id = event.id, "event already applied"),
+                Applied::UnknownEscrow => warn!(
+                    id = event.id,
+                    contract_id = %event.contract_id,
+                    "event for an HTLC created before the start ledger"
+                ),
+            }
+        }
+
+        checkpoint.cursor = Some(page.cursor);
+        checkpoint.latest_ledger = page.latest_ledger;
+        Ok(page.events.len())
+    }
+}
@@ -0,0 +1,273 @@
+//! The resolver loop's steps: accepting profitable orders and driving each fill through
+//! both chains
+
+use crate::{
+    evm::EvmFiller,
+    fills::{plan, Action, DstHtlc, Fill, Stage, State},
+    metrics::Metrics,
+    orders::OrderSource,
+    pricing::{evaluate, FillCosts, Market, PriceBook, Verdict},
+    stellar::{dst_params, StellarFiller},
+    ResolverError,
+};
+use std::sync::Arc;
+use stellar_htlc_sdk::{fusion::ChainId, secret, HashAlgo};
+use tracing::{debug, info, warn};
+
+/// Failed attempts at a step before its fill is given up
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+
+/// Fills with funds locked at once
+pub const DEFAULT_MAX_OPEN_FILLS: usize = 4;
+
+/// How the resolver picks orders
+#[derive(Clone, Debug)]
+pub struct Settings {
+    /// URL or file of the `PriceBook`
+    pub prices: String,
+    pub costs: FillCosts,
+    pub min_profit_usd: f64,
+    pub max_attempts: u32,
+    pub max_open_fills: usize,
+}
+
+pub struct Bot {
+    source: OrderSource,
+    evm: EvmFiller,
+    stellar: StellarFiller,
+    chain_id: u64,
+    http: reqwest::Client,
+    settings: Settings,
+    metrics: Arc<Metrics>,
+}
+
+impl Bot {
+    pub async fn new(
+        source: OrderSource,
+        evm: EvmFiller,
+        stellar: StellarFiller,
+        settings: Settings,
+    ) -> Result<Self, ResolverError> {
+        let chain_id = evm.chain_id().await?;
+        Ok(Bot {
+            source,
+            evm,
+            stellar,
+            chain_id,
+            http: reqwest::Client::new(),
+            settings,
+            metrics: Arc::default(),
+        })
+    }
+
+    /// Chain ID of the EVM chain orders are filled from
+    pub fn chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    /// Metrics of the orders priced and the steps taken
+    pub fn metrics(&self) -> &Arc<Metrics> {
+        &self.metrics
+    }
+
+    /// Reads the native balances of both accounts into the metrics
+    pub async fn refresh_balances(&self) {
+        match self.stellar.balance().await {
+            Ok(balance) => self.metrics.balance("stellar", balance as f64),
+            Err(e) => {
+                warn!(error = %e, "Stellar balance read failed");
+                self.metrics.rpc_error("stellar");
+            }
+        }
+        match self.evm.balance().await {
+            Ok(balance) => self.metrics.balance("evm", f64::from(balance)),
+            Err(e) => {
+                warn!(error = %e, "EVM balance read failed");
+                self.metrics.rpc_error("evm");
+            }
+        }
+    }
+
+    /// Prices the active orders from the resolver's EVM chain it has not seen yet and
+    /// accepts the profitable ones, up to the open fill limit. Returns the number accepted.
+    pub async fn discover(&self, state: &mut State, now: u64) -> Result<usize, ResolverError> {
+        let open = state.open_fills();
+        if open >= self.settings.max_open_fills {
+            return Ok(0);
+        }
+        let orders = self.source.active_orders().await?;
+        let prices = PriceBook::fetch(&self.http, &self.settings.prices).await?;
+        let (base_fee, gas_price) = self.evm.fees().await?;
+        let market = Market {
+            now,
+            base_fee,
+            gas_price,
+        };
+
+        let mut accepted = 0;
+        for order in orders {
+            if open + accepted >= self.settings.max_open_fills {
+                break;
+            }
+            if state.fills.contains_key(&order.order_hash)
+                || order.src_chain_id != ChainId::Evm(self.chain_id)
+            {
+                continue;
+            }
+            let verdict = evaluate(
+                &order,
+                &prices,
+                self.settings.costs,
+                market,
+                self.settings.min_profit_usd,
+            );
+            match verdict {
+                Ok(Verdict::Fill(evaluation)) => {
+                    self.metrics.order("fill");
+                    info!(
+                        order_hash = %order.order_hash,
+                        making_amount = evaluation.making_amount,
+                        taking_amount = evaluation.taking_amount,
+                        profit_usd = evaluation.profit_usd(),
+                        "accepting order"
+                    );
+                    state
+                        .fills
+                        .insert(order.order_hash, Fill::new(order, evaluation));
+                    accepted += 1;
+                }
+                Ok(Verdict::Skip(reason)) => {
+                    self.metrics.order("skip");
+                    debug!(order_hash = %order.order_hash, reason, "skipping order");
+                }
+                Err(e) => {
+                    self.metrics.order("unpriced");
+                    debug!(order_hash = %order.order_hash, error = %e, "cannot price order");
+                }
+            }
+        }
+        Ok(accepted)
+    }
+
+    /// Takes the next step of `fill`. A failed step is retried on the next call until it
+    /// failed `max_attempts` times, which fails the fill. Returns whether the fill changed.
+    pub async fn advance(&self, fill: &mut Fill, now: u64) -> bool {
+        let action = plan(fill, now);
+        let order_hash = fill.order.order_hash;
+        match action {
+            Action::Wait | Action::Done => return false,
+            Action::Fail(reason) => {
+                warn!(%order_hash, reason, "fill failed");
+                fill.stage = Stage::Failed;
+                fill.error = Some(reason.into());
+                return true;
+            }
+            _ => {}
+        }
+        let stage = fill.stage;
+        let result = self.execute(fill, action, now).await;
+        self.metrics.step(action, result.is_ok());
+        match result {
+            Ok(()) => {
+                if let (Stage::Completed, Some(src)) = (fill.stage, &fill.src) {
+                    self.metrics.settled(now.saturating_sub(src.deployed_at()));
+                }
+                if fill.stage != stage {
+                    info!(%order_hash, stage = ?fill.stage, "fill advanced");
+                    fill.attempts = 0;
+                    fill.error = None;
+                    return true;
+                }
+                false
+            }
+            Err(e) => {
+                fill.attempts += 1;
+                fill.error = Some(e.to_string());
+                warn!(%order_hash, ?action, attempts = fill.attempts, error = %e, "fill step failed");
+                if fill.attempts >= self.settings.max_attempts {
+                    fill.stage = Stage::Failed;
+                }
+                true
+            }
+        }
+    }
+
+    async fn execute(
+        &self,
+        fill: &mut Fill,
+        action: Action,
+        now: u64,
+    ) -> Result<(), ResolverError> {
+        match action {
+            Action::DeploySrc => {
+                let src = self
+                    .evm
+                    .deploy_src(
+                        &fill.order,
+                        fill.evaluation.making_amount,
+                        fill.evaluation.taking_amount,
+                    )
+                    .await?;
+                fill.src = Some(src);
+                fill.stage = Stage::SrcDeployed;
+            }
+            Action::CreateDst => {
+                let src = fill
+                    .src
+                    .as_ref()
+                    .expect("planned fills have a source escrow");
+                let params = dst_params(&fill.order, src, &self.stellar.address(), now)?;
+                let contract_id = self.stellar.create_dst(&params).await?;
+                fill.dst = Some(DstHtlc {
+                    contract_id,
+                    timelock: params.timelock,
+                });
+                fill.stage = Stage::DstCreated;
+            }
+            Action::AwaitSecret => {
+                let Some(secret) = self.source.secret(fill.order.order_hash).await? else {
+                    return Ok(());
+                };
+                if secret::hash(HashAlgo::Keccak256, &secret.0) != fill.order.hashlock {
+                    return Err(ResolverError::InvalidOrder {
+                        order_hash: fill.order.order_hash.to_string(),
+                        reason: "shared secret does not match the hashlock".into(),
+                    });
+                }
+                fill.secret = Some(secret);
+                // Withdrawn right away rather than on the next call
+                let dst = fill.dst.expect("planned fills have a destination HTLC");
+                self.stellar.withdraw_dst(dst.contract_id, secret).await?;
+                fill.stage = Stage::DstWithdrawn;
+            }
+            Action::WithdrawDst(secret) => {
+                let dst = fill.dst.expect("planned fills have a destination HTLC");
+                self.stellar.withdraw_dst(dst.contract_id, secret).await?;
+                fill.stage = Stage::DstWithdrawn;
+            }
+            Action::WithdrawSrc(secret) => {
+                let src = fill
+                    .src
+                    .as_ref()
+                    .expect("planned fills have a source escrow");
+                self.evm.withdraw_src(src, secret).await?;
+                fill.stage = Stage::Completed;
+            }
+            Action::RefundDst => {
+                let dst = fill.dst.expect("planned fills have a destination HTLC");
+                self.stellar.refund_dst(dst.contract_id).await?;
+                fill.stage = Stage::DstRefunded;
+            }
+            Action::CancelSrc => {
+                let src = fill
+                    .src
+                    .as_ref()
+                    .expect("planned fills have a source escrow");
+                self.evm.cancel_src(src).await?;
+                fill.stage = Stage::Cancelled;
+            }
+            Action::Wait | Action::Fail(_) | Action::Done => {}
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+use crate::{
+    bot::{Settings, DEFAULT_MAX_ATTEMPTS, DEFAULT_MAX_OPEN_FILLS},
+    evm::EvmConfig,
+    pricing::{FillCosts, DEFAULT_EVM_GAS, DEFAULT_STELLAR_FEE},
+    ResolverError,
+};
+use alloy::primitives::Address;
+use std::{env, net::SocketAddr, path::PathBuf, time::Duration};
+use stellar_htlc_sdk::{keystore, SignerConfig};
+use zeroize::Zeroizing;
+
+/// Resolver settings, read from the environment. Not `Debug`, as it holds signing keys.
+#[derive(Clone)]
+pub struct Config {
+    /// Soroban RPC endpoint, `STELLAR_RPC_URL`
+    pub rpc_url: String,
+    /// Strkey of the HTLC contract, `STELLAR_CONTRACT_ID`
+    pub contract_id: String,
+    /// Signer of the account locking the maker's tokens, set up by `STELLAR_KMS_KEY_ID`,
+    /// `STELLAR_KEYSTORE` or `STELLAR_SECRET_KEY`
+    pub stellar_signer: SignerConfig,
+    /// `ETHEREUM_RPC_URL`, `EVM_KEYSTORE` or `EVM_PRIVATE_KEY`, `EVM_ESCROW_FACTORY` and
+    /// `EVM_RESOLVER_CONTRACT`
+    pub evm: EvmConfig,
+    /// Fusion+ API base URL or local order book file, `RESOLVER_ORDERS`
+    pub orders: String,
+    /// Bearer token of the order API, `RESOLVER_API_KEY`
+    pub api_key: Option<String>,
+    /// File holding the resolver's fills, `RESOLVER_STATE_PATH`
+    pub state_path: PathBuf,
+    /// Wait between polls, `RESOLVER_POLL_INTERVAL_SECS`
+    pub poll_interval: Duration,
+    /// `RESOLVER_PRICES`, `RESOLVER_MIN_PROFIT_USD`, `RESOLVER_EVM_GAS`,
+    /// `RESOLVER_STELLAR_FEE`, `RESOLVER_MAX_ATTEMPTS` and `RESOLVER_MAX_OPEN_FILLS`
+    pub settings: Settings,
+    /// Address Prometheus metrics are served on, `RESOLVER_METRICS_LISTEN`; disabled if
+    /// unset
+    pub metrics_listen: Option<SocketAddr>,
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self, ResolverError> {
+        Ok(Config {
+            rpc_url: required("STELLAR_RPC_URL")?,
+            contract_id: required("STELLAR_CONTRACT_ID")?,
+            stellar_signer: SignerConfig::from_env()
+                .map_err(|error| ResolverError::Config(error.to_string()))?
+                .ok_or_else(|| {
+                    ResolverError::Config(
+                        "STELLAR_KMS_KEY_ID, STELLAR_KEYSTORE or STELLAR_SECRET_KEY is not set"
+                            .into(),
+                    )
+                })?,
+            evm: EvmConfig {
+                rpc_url: required("ETHEREUM_RPC_URL")?,
+                private_key: evm_private_key()?,
+                factory: address("EVM_ESCROW_FACTORY")?,
+                resolver: address("EVM_RESOLVER_CONTRACT")?,
+            },
+            orders: required("RESOLVER_ORDERS")?,
+            api_key: env::var("RESOLVER_API_KEY").ok(),
+            state_path: env::var("RESOLVER_STATE_PATH")
+                .unwrap_or_else(|_| "resolver-state.json".into())
+                .into(),
+            poll_interval: Duration::from_secs(
+                optional("RESOLVER_POLL_INTERVAL_SECS")?.unwrap_or(5),
+            ),
+            settings: Settings {
+                prices: required("RESOLVER_PRICES")?,
+                costs: FillCosts {
+                    evm_gas: optional("RESOLVER_EVM_GAS")?.unwrap_or(DEFAULT_EVM_GAS),
+                    stellar_fee: optional("RESOLVER_STELLAR_FEE")?.unwrap_or(DEFAULT_STELLAR_FEE),
+                },
+                min_profit_usd: optional("RESOLVER_MIN_PROFIT_USD")?.unwrap_or(1.0),
+                max_attempts: optional("RESOLVER_MAX_ATTEMPTS")?.unwrap_or(DEFAULT_MAX_ATTEMPTS),
+                max_open_fills: optional("RESOLVER_MAX_OPEN_FILLS")?
+                    .unwrap_or(DEFAULT_MAX_OPEN_FILLS),
+            },
+            metrics_listen: match env::var("RESOLVER_METRICS_LISTEN") {
+                Ok(listen) => Some(listen.parse().map_err(|_| {
+                    ResolverError::Config("RESOLVER_METRICS_LISTEN is not a socket address".into())
+                })?),
+                Err(_) => None,
+            },
+        })
+    }
+}
+
+/// Key of the keystore `EVM_KEYSTORE`, or else `EVM_PRIVATE_KEY`
+fn evm_private_key() -> Result<Zeroizing<String>, ResolverError> {
+    if env::var_os("EVM_KEYSTORE").is_some() && env::var_os("EVM_PRIVATE_KEY").is_some() {
+        return Err(ResolverError::Config(
+            "only one of EVM_KEYSTORE, EVM_PRIVATE_KEY may be set".into(),
+        ));
+    }
+    match keystore::key_from_env("EVM_KEYSTORE") {
+        Ok(Some(key)) => Ok(Zeroizing::new(alloy::hex::encode_prefixed(*key))),
+        Ok(None) => required("EVM_PRIVATE_KEY").map(Zeroizing::new),
+        Err(error) => Err(ResolverError::Config(format!("EVM_KEYSTORE: {error}"))),
+    }
+}
+
+fn required(name: &str) -> Result<String, ResolverError> {
+    env::var(name).map_err(|_| ResolverError::Config(format!("{name} is not set")))
+}
+
+fn address(name: &str) -> Result<Address, ResolverError> {
+    required(name)?
+        .parse()
+        .map_err(|_| ResolverError::Config(format!("{name} is not an address")))
+}
+
+fn optional<T: std::str::FromStr>(name: &str) -> Result<Option<T>, ResolverError> {
+    match env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| ResolverError::Config(format!("{name} is not a valid number"))),
+        Err(_) => Ok(None),
+    }
+}
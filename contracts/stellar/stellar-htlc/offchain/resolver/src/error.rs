@@ -0,0 +1,29 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ResolverError {
+    #[error("invalid configuration: {0}")]
+    Config(String),
+    #[error("Stellar error: {0}")]
+    Stellar(#[from] stellar_htlc_sdk::Error),
+    #[error("order API error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("invalid order {order_hash}: {reason}")]
+    InvalidOrder { order_hash: String, reason: String },
+    #[error("auction error: {0}")]
+    Auction(#[from] fusion_auction::AuctionError),
+    #[error("EVM RPC error: {0}")]
+    EvmRpc(#[from] alloy::transports::TransportError),
+    #[error("EVM contract call failed: {0}")]
+    EvmCall(#[from] alloy::contract::Error),
+    #[error("EVM transaction not confirmed: {0}")]
+    EvmPending(#[from] alloy::providers::PendingTransactionError),
+    #[error("EVM transaction {0} reverted")]
+    EvmReverted(String),
+    #[error("EVM transaction {0} created no source escrow")]
+    NoEscrow(String),
+    #[error("state I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+}
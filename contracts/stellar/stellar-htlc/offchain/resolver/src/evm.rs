@@ -0,0 +1,450 @@
+//! The EVM leg of a fill: the source escrow, deployed through the resolver contract
+//!
+//! The resolver contract is the 1inch cross-chain resolver: `deploySrc` funds the escrow's
+//! safety deposit and fills the maker's order through the Limit Order Protocol, whose
+//! escrow extension moves the maker's funds into the escrow. The contract is the escrow's
+//! taker, so it also relays the withdrawal and the cancellation.
+
+use crate::ResolverError;
+use alloy::{
+    eips::BlockNumberOrTag,
+    network::EthereumWallet,
+    primitives::{Address, B256, U256},
+    providers::{DynProvider, Provider, ProviderBuilder},
+    rpc::types::TransactionReceipt,
+    signers::local::PrivateKeySigner,
+    sol,
+    sol_types::SolEvent,
+};
+use serde::{Deserialize, Serialize};
+use stellar_htlc_sdk::{
+    fusion::{CrossChainOrder, LimitOrder, TimeLocks},
+    Hash32,
+};
+use zeroize::Zeroizing;
+
+sol! {
+    /// Parameters of a 1inch escrow; makers, takers and tokens are addresses packed in
+    /// `uint256` (the protocol's `Address` type)
+    #[derive(Debug, PartialEq, Eq)]
+    struct Immutables {
+        bytes32 orderHash;
+        bytes32 hashlock;
+        uint256 maker;
+        uint256 taker;
+        uint256 token;
+        uint256 amount;
+        uint256 safetyDeposit;
+        uint256 timelocks;
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    struct DstImmutablesComplement {
+        uint256 maker;
+        uint256 amount;
+        uint256 token;
+        uint256 safetyDeposit;
+        uint256 chainId;
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    event SrcEscrowCreated(Immutables srcImmutables, DstImmutablesComplement dstImmutablesComplement);
+
+    /// Limit Order Protocol v4 order, with its `Address` fields packed in `uint256`
+    #[derive(Debug, PartialEq, Eq)]
+    struct Order {
+        uint256 salt;
+        uint256 maker;
+        uint256 receiver;
+        uint256 makerAsset;
+        uint256 takerAsset;
+        uint256 makingAmount;
+        uint256 takingAmount;
+        uint256 makerTraits;
+    }
+
+    #[sol(rpc)]
+    #[allow(clippy::too_many_arguments)]
+    interface IResolver {
+        function deploySrc(Immutables immutables, Order order, bytes32 r, bytes32 vs, uint256 amount, uint256 takerTraits, bytes args) external payable;
+        function withdraw(address escrow, bytes32 secret, Immutables immutables) external;
+        function cancel(address escrow, Immutables immutables) external;
+    }
+
+    #[sol(rpc)]
+    interface IEscrowFactory {
+        function addressOfEscrowSrc(Immutables immutables) external view returns (address);
+    }
+}
+
+/// Taker traits flag making the fill amount a making amount
+pub const MAKER_AMOUNT_FLAG: U256 = U256::from_limbs([0, 0, 0, 1 << 63]);
+
+/// Bit offset of the extension length in the taker traits
+const EXTENSION_LENGTH_OFFSET: usize = 224;
+
+/// Stages packed into an escrow's `timelocks`, in their bit order
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimelockStage {
+    SrcWithdrawal,
+    SrcPublicWithdrawal,
+    SrcCancellation,
+    SrcPublicCancellation,
+    DstWithdrawal,
+    DstPublicWithdrawal,
+    DstCancellation,
+}
+
+/// An order's stage offsets packed into `timelocks`, without a deployment time: the
+/// escrow sets it when it is deployed
+pub fn pack_timelocks(time_locks: &TimeLocks) -> U256 {
+    [
+        time_locks.src_withdrawal,
+        time_locks.src_public_withdrawal,
+        time_locks.src_cancellation,
+        time_locks.src_public_cancellation,
+        time_locks.dst_withdrawal,
+        time_locks.dst_public_withdrawal,
+        time_locks.dst_cancellation,
+    ]
+    .iter()
+    .enumerate()
+    .fold(U256::ZERO, |packed, (stage, offset)| {
+        packed | U256::from(*offset) << (stage * 32)
+    })
+}
+
+/// Timestamp a stage starts at: each stage is a 32-bit offset from the deployment time
+/// held in the top 32 bits
+pub fn timelock(timelocks: U256, stage: TimelockStage) -> u64 {
+    let deployed_at: u64 = (timelocks >> 224usize).to();
+    let offset: u64 = ((timelocks >> (stage as usize * 32)) & U256::from(u32::MAX)).to();
+    deployed_at + offset
+}
+
+/// Taker traits of a fill of a making amount that pays at most `max_taking_amount`,
+/// followed in the fill's arguments by an extension of `extension_len` bytes. The amount
+/// threshold takes the low 185 bits, which any `u128` fits.
+pub fn taker_traits(extension_len: usize, max_taking_amount: u128) -> U256 {
+    MAKER_AMOUNT_FLAG
+        | U256::from(extension_len) << EXTENSION_LENGTH_OFFSET
+        | U256::from(max_taking_amount)
+}
+
+/// The `r` and `vs` of an EIP-2098 compact signature, from a 65-byte `r || s || v` or an
+/// already compact 64-byte hex signature
+pub fn compact_signature(signature: &str) -> Option<(B256, B256)> {
+    let bytes = hex_bytes(signature)?;
+    let (r, s) = (B256::from_slice(bytes.get(..32)?), bytes.get(32..64)?);
+    let mut vs = B256::from_slice(s);
+    match bytes.get(64) {
+        None if bytes.len() == 64 => {}
+        Some(27 | 0) if bytes.len() == 65 => {}
+        Some(28 | 1) if bytes.len() == 65 => vs.0[0] |= 0x80,
+        _ => return None,
+    }
+    Some((r, vs))
+}
+
+/// An address packed as the protocol's `Address` value, without flags
+fn pack_address(address: Address) -> U256 {
+    U256::from_be_bytes(address.into_word().0)
+}
+
+/// Address held in the low 160 bits of a packed `Address` value
+fn unpack_address(value: U256) -> Address {
+    Address::from_word(B256::from(value))
+}
+
+fn hex_bytes(hex: &str) -> Option<Vec<u8>> {
+    alloy::hex::decode(hex.trim_start_matches("0x")).ok()
+}
+
+fn invalid(order: &CrossChainOrder, reason: impl Into<String>) -> ResolverError {
+    ResolverError::InvalidOrder {
+        order_hash: order.order_hash.to_string(),
+        reason: reason.into(),
+    }
+}
+
+/// The signed order in its ABI form
+pub fn order_struct(order: &CrossChainOrder) -> Result<Order, ResolverError> {
+    let LimitOrder {
+        salt,
+        maker,
+        receiver,
+        maker_asset,
+        taker_asset,
+        making_amount,
+        taking_amount,
+        maker_traits,
+    } = &order.order;
+    let number = |value: &str, field: &str| {
+        value
+            .parse::<U256>()
+            .map_err(|_| invalid(order, format!("{field} is not a uint256")))
+    };
+    let address = |value: &str, field: &str| {
+        value
+            .parse::<Address>()
+            .map(pack_address)
+            .map_err(|_| invalid(order, format!("{field} is not an address")))
+    };
+    Ok(Order {
+        salt: number(salt, "salt")?,
+        maker: address(maker, "maker")?,
+        receiver: address(receiver, "receiver")?,
+        makerAsset: address(maker_asset, "makerAsset")?,
+        takerAsset: address(taker_asset, "takerAsset")?,
+        makingAmount: U256::from(*making_amount),
+        takingAmount: U256::from(*taking_amount),
+        makerTraits: number(maker_traits, "makerTraits")?,
+    })
+}
+
+/// Immutables of the source escrow a fill of `making_amount` deploys, with `taker` the
+/// resolver contract
+pub fn src_immutables(
+    order: &CrossChainOrder,
+    making_amount: u128,
+    taker: Address,
+) -> Result<Immutables, ResolverError> {
+    let abi = order_struct(order)?;
+    Ok(Immutables {
+        orderHash: B256::from(order.order_hash.0),
+        hashlock: B256::from(order.hashlock.0),
+        maker: abi.maker,
+        taker: pack_address(taker),
+        token: abi.makerAsset,
+        amount: U256::from(making_amount),
+        safetyDeposit: U256::from(order.src_safety_deposit),
+        timelocks: pack_timelocks(&order.time_locks),
+    })
+}
+
+/// Parameters of a deployed source escrow
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EscrowImmutables {
+    pub order_hash: Hash32,
+    pub hashlock: Hash32,
+    pub maker: Address,
+    pub taker: Address,
+    pub token: Address,
+    pub amount: U256,
+    pub safety_deposit: U256,
+    /// With the deployment time set
+    pub timelocks: U256,
+}
+
+impl From<&Immutables> for EscrowImmutables {
+    fn from(immutables: &Immutables) -> Self {
+        EscrowImmutables {
+            order_hash: Hash32(immutables.orderHash.0),
+            hashlock: Hash32(immutables.hashlock.0),
+            maker: unpack_address(immutables.maker),
+            taker: unpack_address(immutables.taker),
+            token: unpack_address(immutables.token),
+            amount: immutables.amount,
+            safety_deposit: immutables.safetyDeposit,
+            timelocks: immutables.timelocks,
+        }
+    }
+}
+
+impl From<&EscrowImmutables> for Immutables {
+    fn from(immutables: &EscrowImmutables) -> Self {
+        Immutables {
+            orderHash: B256::from(immutables.order_hash.0),
+            hashlock: B256::from(immutables.hashlock.0),
+            maker: pack_address(immutables.maker),
+            taker: pack_address(immutables.taker),
+            token: pack_address(immutables.token),
+            amount: immutables.amount,
+            safetyDeposit: immutables.safety_deposit,
+            timelocks: immutables.timelocks,
+        }
+    }
+}
+
+/// A deployed source escrow
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SrcEscrow {
+    pub address: Address,
+    pub tx_hash: B256,
+    pub immutables: EscrowImmutables,
+    /// What the destination escrow must lock for the maker, as the fill priced it
+    pub dst_amount: U256,
+}
+
+impl SrcEscrow {
+    pub fn stage(&self, stage: TimelockStage) -> u64 {
+        timelock(self.immutables.timelocks, stage)
+    }
+
+    /// When the escrow was deployed, held in the top 32 bits of its timelocks
+    pub fn deployed_at(&self) -> u64 {
+        (self.immutables.timelocks >> 224usize).to()
+    }
+}
+
+/// The source escrow created by the fill in `receipt`, as the factory logged it
+pub fn created_escrow(
+    receipt: &TransactionReceipt,
+    factory: Address,
+) -> Option<(Immutables, DstImmutablesComplement)> {
+    receipt.inner.logs().iter().find_map(|log| {
+        if log.address() != factory || log.topic0() != Some(&SrcEscrowCreated::SIGNATURE_HASH) {
+            return None;
+        }
+        let created = SrcEscrowCreated::decode_log_data(&log.inner.data).ok()?;
+        Some((created.srcImmutables, created.dstImmutablesComplement))
+    })
+}
+
+/// EVM settings of the resolver
+#[derive(Clone)]
+pub struct EvmConfig {
+    pub rpc_url: String,
+    /// Key of the resolver contract's owner
+    pub private_key: Zeroizing<String>,
+    pub factory: Address,
+    /// The 1inch resolver contract fills go through
+    pub resolver: Address,
+}
+
+/// Sends the source-escrow transactions of fills from the resolver contract's owner
+pub struct EvmFiller {
+    provider: DynProvider,
+    address: Address,
+    factory: Address,
+    resolver: Address,
+}
+
+impl EvmFiller {
+    pub async fn connect(config: &EvmConfig) -> Result<Self, ResolverError> {
+        let signer: PrivateKeySigner = config
+            .private_key
+            .parse()
+            .map_err(|_| ResolverError::Config("the EVM key is not a private key".into()))?;
+        let url = config.rpc_url.parse().map_err(|_| {
+            ResolverError::Config(format!("invalid EVM RPC URL {}", config.rpc_url))
+        })?;
+        let address = signer.address();
+        let provider = ProviderBuilder::new()
+            .wallet(EthereumWallet::from(signer))
+            .connect_http(url)
+            .erased();
+        Ok(EvmFiller {
+            provider,
+            address,
+            factory: config.factory,
+            resolver: config.resolver,
+        })
+    }
+
+    /// Address of the sending account
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    /// Native balance of the sending account, in wei
+    pub async fn balance(&self) -> Result<U256, ResolverError> {
+        Ok(self.provider.get_balance(self.address).await?)
+    }
+
+    pub async fn chain_id(&self) -> Result<u64, ResolverError> {
+        Ok(self.provider.get_chain_id().await?)
+    }
+
+    /// Base fee of the latest block and the gas price a transaction pays on top of it,
+    /// both in wei
+    pub async fn fees(&self) -> Result<(u128, u128), ResolverError> {
+        let base_fee = self
+            .provider
+            .get_block_by_number(BlockNumberOrTag::Latest)
+            .await?
+            .and_then(|block| block.header.base_fee_per_gas)
+            .unwrap_or_default();
+        let fees = self.provider.estimate_eip1559_fees().await?;
+        Ok((base_fee.into(), fees.max_fee_per_gas))
+    }
+
+    /// Fills `making_amount` of `order`, deploying its source escrow. The fill reverts
+    /// rather than pay more than `max_taking_amount` for it.
+    pub async fn deploy_src(
+        &self,
+        order: &CrossChainOrder,
+        making_amount: u128,
+        max_taking_amount: u128,
+    ) -> Result<SrcEscrow, ResolverError> {
+        let immutables = src_immutables(order, making_amount, self.resolver)?;
+        let (r, vs) = compact_signature(&order.signature)
+            .ok_or_else(|| invalid(order, "signature is not a 64 or 65-byte hex signature"))?;
+        let extension =
+            hex_bytes(&order.extension).ok_or_else(|| invalid(order, "extension is not hex"))?;
+        let deposit = immutables.safetyDeposit;
+        let receipt = IResolver::new(self.resolver, &self.provider)
+            .deploySrc(
+                immutables,
+                order_struct(order)?,
+                r,
+                vs,
+                U256::from(making_amount),
+                taker_traits(extension.len(), max_taking_amount),
+                extension.into(),
+            )
+            .value(deposit)
+            .send()
+            .await?
+            .get_receipt()
+            .await?;
+        let tx_hash = receipt.transaction_hash;
+        if !receipt.status() {
+            return Err(ResolverError::EvmReverted(tx_hash.to_string()));
+        }
+        let (immutables, dst) = created_escrow(&receipt, self.factory)
+            .ok_or_else(|| ResolverError::NoEscrow(tx_hash.to_string()))?;
+        let address = IEscrowFactory::new(self.factory, &self.provider)
+            .addressOfEscrowSrc(immutables.clone())
+            .call()
+            .await?;
+        Ok(SrcEscrow {
+            address,
+            tx_hash,
+            immutables: EscrowImmutables::from(&immutables),
+            dst_amount: dst.amount,
+        })
+    }
+
+    /// Withdraws the source escrow to the resolver contract with the revealed `secret`
+    pub async fn withdraw_src(
+        &self,
+        escrow: &SrcEscrow,
+        secret: Hash32,
+    ) -> Result<B256, ResolverError> {
+        let resolver = IResolver::new(self.resolver, &self.provider);
+        let call = resolver.withdraw(
+            escrow.address,
+            B256::from(secret.0),
+            Immutables::from(&escrow.immutables),
+        );
+        confirmed(call.send().await?.get_receipt().await?)
+    }
+
+    /// Cancels the source escrow, returning the maker's funds and the safety deposit
+    pub async fn cancel_src(&self, escrow: &SrcEscrow) -> Result<B256, ResolverError> {
+        let resolver = IResolver::new(self.resolver, &self.provider);
+        let call = resolver.cancel(escrow.address, Immutables::from(&escrow.immutables));
+        confirmed(call.send().await?.get_receipt().await?)
+    }
+}
+
+fn confirmed(receipt: TransactionReceipt) -> Result<B256, ResolverError> {
+    if receipt.status() {
+        Ok(receipt.transaction_hash)
+    } else {
+        Err(ResolverError::EvmReverted(
+            receipt.transaction_hash.to_string(),
+        ))
+    }
+}
@@ -0,0 +1,162 @@
+//! Fills the resolver committed to, what to do next about each, and their persistence
+
+use crate::{
+    evm::{SrcEscrow, TimelockStage},
+    pricing::Evaluation,
+    ResolverError,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{ErrorKind, Write},
+    path::Path,
+};
+use stellar_htlc_sdk::{fusion::CrossChainOrder, Hash32};
+
+/// How far a fill got
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Stage {
+    /// Priced as profitable, nothing sent yet
+    Accepted,
+    /// The source escrow holds the maker's funds
+    SrcDeployed,
+    /// The destination HTLC holds the resolver's tokens for the maker
+    DstCreated,
+    /// The maker was paid on Stellar, revealing the secret
+    DstWithdrawn,
+    /// The resolver withdrew the source escrow
+    Completed,
+    /// No secret before the destination timelock; the resolver took its tokens back
+    DstRefunded,
+    /// The source escrow returned the maker's funds
+    Cancelled,
+    /// Given up on; see `Fill::error`
+    Failed,
+}
+
+/// The destination HTLC of a fill
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DstHtlc {
+    pub contract_id: Hash32,
+    /// When the resolver can refund it
+    pub timelock: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Fill {
+    pub order: CrossChainOrder,
+    /// Pricing the fill was accepted at
+    pub evaluation: Evaluation,
+    pub stage: Stage,
+    pub src: Option<SrcEscrow>,
+    pub dst: Option<DstHtlc>,
+    /// The order's secret, once its maker shared it and it matched the hashlock
+    pub secret: Option<Hash32>,
+    /// Last failure of the next step
+    pub error: Option<String>,
+    /// Failed attempts at the next step
+    pub attempts: u32,
+}
+
+impl Fill {
+    pub fn new(order: CrossChainOrder, evaluation: Evaluation) -> Self {
+        Fill {
+            order,
+            evaluation,
+            stage: Stage::Accepted,
+            src: None,
+            dst: None,
+            secret: None,
+            error: None,
+            attempts: 0,
+        }
+    }
+
+    /// Whether the fill still has funds locked in either escrow
+    pub fn is_open(&self) -> bool {
+        !matches!(
+            self.stage,
+            Stage::Completed | Stage::Cancelled | Stage::Failed
+        )
+    }
+}
+
+/// What to do next about a fill
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    DeploySrc,
+    CreateDst,
+    /// Ask the order source for the maker's secret
+    AwaitSecret,
+    WithdrawDst(Hash32),
+    WithdrawSrc(Hash32),
+    RefundDst,
+    CancelSrc,
+    /// Nothing to do until a timelock passes
+    Wait,
+    /// The fill cannot go on
+    Fail(&'static str),
+    Done,
+}
+
+/// Decides the next step of `fill` at unix time `now`. A source escrow past its
+/// cancellation without the maker paid is cancelled; a destination HTLC still waiting
+/// for the secret at its timelock is refunded.
+pub fn plan(fill: &Fill, now: u64) -> Action {
+    let src_cancellation = fill
+        .src
+        .as_ref()
+        .map(|src| src.stage(TimelockStage::SrcCancellation));
+    match (fill.stage, src_cancellation) {
+        (Stage::Accepted, _) => Action::DeploySrc,
+        (Stage::SrcDeployed, Some(cancellation)) if now >= cancellation => Action::CancelSrc,
+        (Stage::SrcDeployed, Some(_)) => Action::CreateDst,
+        (Stage::DstCreated, Some(_)) => match (fill.dst, fill.secret) {
+            (Some(dst), _) if now >= dst.timelock => Action::RefundDst,
+            (Some(_), Some(secret)) => Action::WithdrawDst(secret),
+            (Some(_), None) => Action::AwaitSecret,
+            (None, _) => Action::Fail("no destination HTLC recorded"),
+        },
+        (Stage::DstWithdrawn, Some(_)) => match fill.secret {
+            Some(secret) => Action::WithdrawSrc(secret),
+            None => Action::Fail("no secret recorded"),
+        },
+        (Stage::DstRefunded, Some(cancellation)) if now >= cancellation => Action::CancelSrc,
+        (Stage::DstRefunded, Some(_)) => Action::Wait,
+        (Stage::Completed | Stage::Cancelled | Stage::Failed, _) => Action::Done,
+        (_, None) => Action::Fail("no source escrow recorded"),
+    }
+}
+
+/// Every fill the resolver accepted, by order hash
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct State {
+    pub fills: BTreeMap<Hash32, Fill>,
+}
+
+impl State {
+    /// Number of fills with funds still locked
+    pub fn open_fills(&self) -> usize {
+        self.fills.values().filter(|fill| fill.is_open()).count()
+    }
+
+    /// Loads the state at `path`, or `None` if there is none yet
+    pub fn load(path: &Path) -> Result<Option<Self>, ResolverError> {
+        match fs::read(path) {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Saves the state atomically, so a crash leaves either the old or the new one
+    pub fn save(&self, path: &Path) -> Result<(), ResolverError> {
+        let tmp = path.with_extension("tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(&serde_json::to_vec_pretty(self)?)?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
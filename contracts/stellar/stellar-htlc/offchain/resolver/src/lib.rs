@@ -0,0 +1,25 @@
+//! Resolver bot for Fusion+ orders from an EVM chain to Stellar.
+//!
+//! Polls a Fusion+-compatible order API, or a local order book file, for active orders
+//! and prices each one with the auction curve of `fusion-auction` against spot prices
+//! and both chains' gas and fees. A profitable order is filled by deploying its source
+//! escrow through the 1inch resolver contract and locking the maker's tokens in a
+//! Stellar HTLC under the same hashlock. Once the maker shares the secret, the resolver
+//! pays the maker on Stellar and withdraws the source escrow with it; without one, both
+//! legs are refunded after their timelocks. Fills are persisted, so a restart resumes
+//! each from the step it reached. Orders, steps, fills and balances are exposed as
+//! Prometheus metrics.
+
+pub mod bot;
+pub mod config;
+pub mod error;
+pub mod evm;
+pub mod fills;
+pub mod metrics;
+pub mod orders;
+pub mod pricing;
+pub mod stellar;
+
+pub use error::ResolverError;
+
+mod test;
@@ -0,0 +1,88 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use stellar_htlc_resolver::{
+    bot::Bot, config::Config, evm::EvmFiller, fills::State, metrics, orders::OrderSource,
+    stellar::StellarFiller, ResolverError,
+};
+use tracing::{info, warn};
+use tracing_subscriber::EnvFilter;
+
+#[tokio::main]
+async fn main() -> Result<(), ResolverError> {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+
+    let config = Config::from_env()?;
+    let mut state = State::load(&config.state_path)?.unwrap_or_default();
+    let evm = EvmFiller::connect(&config.evm).await?;
+    let stellar = StellarFiller::connect(
+        &config.rpc_url,
+        &config.contract_id,
+        config.stellar_signer.load().await?,
+    )
+    .await?;
+    info!(
+        fills = state.fills.len(),
+        open = state.open_fills(),
+        evm = %evm.address(),
+        stellar = stellar.address(),
+        "resolver starting"
+    );
+    let source = OrderSource::new(&config.orders, config.api_key.clone());
+    let bot = Bot::new(source, evm, stellar, config.settings.clone()).await?;
+    info!(chain_id = bot.chain_id(), "filling orders");
+    if let Some(listen) = config.metrics_listen {
+        let listener = tokio::net::TcpListener::bind(listen).await?;
+        let app = metrics::router(bot.metrics().clone());
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                warn!(error = %e, "metrics server stopped");
+            }
+        });
+        info!(%listen, "serving metrics");
+    }
+
+    loop {
+        match bot.discover(&mut state, unix_now()).await {
+            Ok(0) => {}
+            Ok(_) => state.save(&config.state_path)?,
+            Err(e) => {
+                warn!(error = %e, "order poll failed, retrying");
+                bot.metrics().rpc_error("orders");
+            }
+        }
+        // Each step is saved as soon as it is taken, before the next one is sent
+        let open: Vec<_> = state
+            .fills
+            .iter()
+            .filter(|(_, fill)| fill.is_open())
+            .map(|(order_hash, _)| *order_hash)
+            .collect();
+        for order_hash in open {
+            let fill = state
+                .fills
+                .get_mut(&order_hash)
+                .expect("listed fills exist");
+            if bot.advance(fill, unix_now()).await {
+                state.save(&config.state_path)?;
+            }
+        }
+        bot.metrics().observe(&state);
+        bot.refresh_balances().await;
+
+        tokio::select! {
+            _ = tokio::time::sleep(config.poll_interval) => {}
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+
+    info!(open = state.open_fills(), "resolver stopped");
+    Ok(())
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("clock after 1970")
+        .as_secs()
+}
@@ -0,0 +1,208 @@
+//! Prometheus metrics of the resolver, served as text at `GET /metrics`
+
+use crate::fills::{Action, Stage, State};
+use axum::{extract::State as Shared, http::header, response::IntoResponse, routing::get, Router};
+use prometheus::{
+    exponential_buckets, Encoder, GaugeVec, Histogram, HistogramOpts, IntCounterVec, IntGaugeVec,
+    Opts, Registry, TextEncoder,
+};
+use std::sync::Arc;
+
+const STAGES: [Stage; 8] = [
+    Stage::Accepted,
+    Stage::SrcDeployed,
+    Stage::DstCreated,
+    Stage::DstWithdrawn,
+    Stage::Completed,
+    Stage::DstRefunded,
+    Stage::Cancelled,
+    Stage::Failed,
+];
+
+fn stage_label(stage: Stage) -> &'static str {
+    match stage {
+        Stage::Accepted => "accepted",
+        Stage::SrcDeployed => "srcDeployed",
+        Stage::DstCreated => "dstCreated",
+        Stage::DstWithdrawn => "dstWithdrawn",
+        Stage::Completed => "completed",
+        Stage::DstRefunded => "dstRefunded",
+        Stage::Cancelled => "cancelled",
+        Stage::Failed => "failed",
+    }
+}
+
+/// Name of a step, and the service it calls: `evm`, `stellar` or `orders`
+fn action_labels(action: Action) -> (&'static str, &'static str) {
+    match action {
+        Action::DeploySrc => ("deploySrc", "evm"),
+        Action::CreateDst => ("createDst", "stellar"),
+        Action::AwaitSecret => ("awaitSecret", "orders"),
+        Action::WithdrawDst(_) => ("withdrawDst", "stellar"),
+        Action::WithdrawSrc(_) => ("withdrawSrc", "evm"),
+        Action::RefundDst => ("refundDst", "stellar"),
+        Action::CancelSrc => ("cancelSrc", "evm"),
+        Action::Wait => ("wait", "none"),
+        Action::Fail(_) => ("fail", "none"),
+        Action::Done => ("done", "none"),
+    }
+}
+
+pub struct Metrics {
+    registry: Registry,
+    /// Orders priced, by verdict
+    orders: IntCounterVec,
+    /// Steps taken, by step and outcome
+    steps: IntCounterVec,
+    /// Failed calls, by service
+    rpc_errors: IntCounterVec,
+    /// Fills in the state, by stage
+    fills: IntGaugeVec,
+    /// From a fill's source escrow deployment to its completion
+    settlement: Histogram,
+    /// Native balances of the resolver's accounts, in stroops and wei
+    balances: GaugeVec,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry =
+            Registry::new_custom(Some("resolver".into()), None).expect("valid registry prefix");
+        let metrics = Metrics {
+            orders: IntCounterVec::new(
+                Opts::new("orders_total", "Orders priced, by verdict"),
+                &["verdict"],
+            )
+            .expect("valid metric"),
+            steps: IntCounterVec::new(
+                Opts::new("fill_steps_total", "Fill steps taken, by step and outcome"),
+                &["step", "outcome"],
+            )
+            .expect("valid metric"),
+            rpc_errors: IntCounterVec::new(
+                Opts::new(
+                    "rpc_errors_total",
+                    "Failed calls to the order API or either chain, by service",
+                ),
+                &["source"],
+            )
+            .expect("valid metric"),
+            fills: IntGaugeVec::new(
+                Opts::new("fills", "Fills in the state, by stage"),
+                &["stage"],
+            )
+            .expect("valid metric"),
+            settlement: Histogram::with_opts(
+                HistogramOpts::new(
+                    "settlement_seconds",
+                    "Time from a fill's source escrow deployment to its completion",
+                )
+                // 15 seconds to about 8.5 hours
+                .buckets(exponential_buckets(15.0, 2.0, 12).expect("valid buckets")),
+            )
+            .expect("valid metric"),
+            balances: GaugeVec::new(
+                Opts::new(
+                    "wallet_balance",
+                    "Native balance of the resolver's account, in stroops or wei",
+                ),
+                &["chain"],
+            )
+            .expect("valid metric"),
+            registry,
+        };
+        metrics.register();
+        metrics
+    }
+
+    fn register(&self) {
+        let collectors: [Box<dyn prometheus::core::Collector>; 6] = [
+            Box::new(self.orders.clone()),
+            Box::new(self.steps.clone()),
+            Box::new(self.rpc_errors.clone()),
+            Box::new(self.fills.clone()),
+            Box::new(self.settlement.clone()),
+            Box::new(self.balances.clone()),
+        ];
+        for collector in collectors {
+            self.registry
+                .register(collector)
+                .expect("metrics registered once");
+        }
+    }
+
+    /// An order priced as `verdict`: `fill`, `skip` or `unpriced`
+    pub fn order(&self, verdict: &str) {
+        self.orders.with_label_values(&[verdict]).inc();
+    }
+
+    /// A step of a fill that succeeded or not; failures count as errors of the service it
+    /// called
+    pub fn step(&self, action: Action, ok: bool) {
+        let (step, source) = action_labels(action);
+        let outcome = if ok { "ok" } else { "error" };
+        self.steps.with_label_values(&[step, outcome]).inc();
+        if !ok {
+            self.rpc_error(source);
+        }
+    }
+
+    /// A failed call to `source`: `orders`, `evm` or `stellar`
+    pub fn rpc_error(&self, source: &str) {
+        self.rpc_errors.with_label_values(&[source]).inc();
+    }
+
+    /// A fill completed `seconds` after its source escrow was deployed
+    pub fn settled(&self, seconds: u64) {
+        self.settlement.observe(seconds as f64);
+    }
+
+    pub fn balance(&self, chain: &str, balance: f64) {
+        self.balances.with_label_values(&[chain]).set(balance);
+    }
+
+    /// Counts the fills of `state` by stage
+    pub fn observe(&self, state: &State) {
+        let mut counts = [0i64; STAGES.len()];
+        for fill in state.fills.values() {
+            counts[STAGES
+                .iter()
+                .position(|stage| *stage == fill.stage)
+                .expect("every stage listed")] += 1;
+        }
+        for (stage, count) in STAGES.iter().zip(counts) {
+            self.fills
+                .with_label_values(&[stage_label(*stage)])
+                .set(count);
+        }
+    }
+
+    /// Every metric in the Prometheus text format
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("text encoding cannot fail");
+        String::from_utf8(buffer).expect("text format is UTF-8")
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics::new()
+    }
+}
+
+/// `GET /metrics`
+pub fn router(metrics: Arc<Metrics>) -> Router {
+    Router::new()
+        .route("/metrics", get(serve))
+        .with_state(metrics)
+}
+
+async fn serve(Shared(metrics): Shared<Arc<Metrics>>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)],
+        metrics.render(),
+    )
+}
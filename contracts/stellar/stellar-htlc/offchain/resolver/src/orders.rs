@@ -0,0 +1,134 @@
+//! Sources of Fusion+ orders and of the secrets makers share for them
+
+use crate::ResolverError;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::path::PathBuf;
+use stellar_htlc_sdk::{
+    fusion::{CrossChainOrder, OrderSecrets, Page},
+    Hash32,
+};
+
+/// Active orders listed per API page
+pub const PAGE_LIMIT: u32 = 100;
+
+/// Contents of a local order book file
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LocalBook {
+    pub orders: Vec<CrossChainOrder>,
+    /// Secrets makers shared, by order
+    #[serde(default)]
+    pub secrets: Vec<OrderSecrets>,
+}
+
+/// Where orders are read from
+pub enum OrderSource {
+    /// A Fusion+-compatible API under `base_url`, e.g. `.../fusion-plus`
+    Api {
+        http: reqwest::Client,
+        base_url: String,
+        api_key: Option<String>,
+    },
+    /// A `LocalBook` JSON file, read again on every poll
+    File(PathBuf),
+}
+
+impl OrderSource {
+    /// The API at `location` if it is an HTTP(S) URL, the local order book file otherwise
+    pub fn new(location: &str, api_key: Option<String>) -> Self {
+        if is_url(location) {
+            OrderSource::Api {
+                http: reqwest::Client::new(),
+                base_url: location.trim_end_matches('/').into(),
+                api_key,
+            }
+        } else {
+            OrderSource::File(location.into())
+        }
+    }
+
+    pub async fn active_orders(&self) -> Result<Vec<CrossChainOrder>, ResolverError> {
+        match self {
+            OrderSource::Api {
+                http,
+                base_url,
+                api_key,
+            } => {
+                let mut orders = Vec::new();
+                for page in 1.. {
+                    let url = format!(
+                        "{base_url}/orders/v1.0/order/active?page={page}&limit={PAGE_LIMIT}"
+                    );
+                    let listed: Page<CrossChainOrder> =
+                        read_json(http, &url, api_key.as_deref()).await?;
+                    orders.extend(listed.items);
+                    if listed.meta.current_page >= listed.meta.total_pages {
+                        break;
+                    }
+                }
+                Ok(orders)
+            }
+            OrderSource::File(path) => Ok(LocalBook::read(path)?.orders),
+        }
+    }
+
+    /// The secret of a single-fill order, once its maker shared it
+    pub async fn secret(&self, order_hash: Hash32) -> Result<Option<Hash32>, ResolverError> {
+        let secrets = match self {
+            OrderSource::Api {
+                http,
+                base_url,
+                api_key,
+            } => {
+                let url = format!("{base_url}/orders/v1.0/order/secrets/0x{order_hash}");
+                let mut request = http.get(&url);
+                if let Some(api_key) = api_key {
+                    request = request.bearer_auth(api_key);
+                }
+                let response = request.send().await?;
+                if response.status() == reqwest::StatusCode::NOT_FOUND {
+                    return Ok(None);
+                }
+                response.error_for_status()?.json::<OrderSecrets>().await?
+            }
+            OrderSource::File(path) => match LocalBook::read(path)?
+                .secrets
+                .into_iter()
+                .find(|secrets| secrets.order_hash == order_hash)
+            {
+                Some(secrets) => secrets,
+                None => return Ok(None),
+            },
+        };
+        Ok(secrets
+            .secrets
+            .iter()
+            .find(|shared| shared.idx == 0)
+            .map(|shared| shared.secret))
+    }
+}
+
+impl LocalBook {
+    pub fn read(path: &std::path::Path) -> Result<Self, ResolverError> {
+        Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+}
+
+fn is_url(location: &str) -> bool {
+    location.starts_with("http://") || location.starts_with("https://")
+}
+
+/// Reads JSON from `location`, a URL or a file path
+pub(crate) async fn read_json<T: DeserializeOwned>(
+    http: &reqwest::Client,
+    location: &str,
+    api_key: Option<&str>,
+) -> Result<T, ResolverError> {
+    if !is_url(location) {
+        return Ok(serde_json::from_slice(&std::fs::read(location)?)?);
+    }
+    let mut request = http.get(location);
+    if let Some(api_key) = api_key {
+        request = request.bearer_auth(api_key);
+    }
+    Ok(request.send().await?.error_for_status()?.json().await?)
+}
@@ -0,0 +1,170 @@
+//! Profitability of a fill: what the auction asks at the current time and base fee,
+//! valued at spot prices, against the gas and fees both chains charge for the fill
+
+use crate::{orders, ResolverError};
+use fusion_auction::{AuctionDetails, OrderAmounts};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use stellar_htlc_sdk::fusion::{ChainId, CrossChainOrder};
+
+/// Default gas of `deploySrc` and of the source escrow's withdrawal together
+pub const DEFAULT_EVM_GAS: u64 = 400_000;
+
+/// Default fees of `create_htlc` and `withdraw` together, in stroops
+pub const DEFAULT_STELLAR_FEE: u64 = 2_000_000;
+
+/// Spot price of a token
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TokenPrice {
+    pub decimals: u8,
+    pub usd: f64,
+}
+
+impl TokenPrice {
+    /// USD value of `amount` in the token's smallest unit
+    pub fn value(&self, amount: u128) -> f64 {
+        amount as f64 / 10f64.powi(self.decimals.into()) * self.usd
+    }
+}
+
+/// Spot prices fills are valued at
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PriceBook {
+    /// Gas token of the EVM chain
+    pub evm_native: TokenPrice,
+    /// XLM
+    pub stellar_native: TokenPrice,
+    /// By EVM address or Stellar contract strkey, in any case
+    pub tokens: HashMap<String, TokenPrice>,
+}
+
+impl PriceBook {
+    /// Reads the price book served at `location`, a URL or a JSON file
+    pub async fn fetch(http: &reqwest::Client, location: &str) -> Result<Self, ResolverError> {
+        orders::read_json(http, location, None).await
+    }
+
+    pub fn token(&self, token: &str) -> Option<&TokenPrice> {
+        self.tokens
+            .iter()
+            .find(|(address, _)| address.eq_ignore_ascii_case(token))
+            .map(|(_, price)| price)
+    }
+}
+
+/// Gas and fees a fill spends, estimated before it is executed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FillCosts {
+    /// Gas of `deploySrc` and of the source escrow's withdrawal
+    pub evm_gas: u64,
+    /// Fees of `create_htlc` and `withdraw`, in stroops
+    pub stellar_fee: u64,
+}
+
+impl Default for FillCosts {
+    fn default() -> Self {
+        FillCosts {
+            evm_gas: DEFAULT_EVM_GAS,
+            stellar_fee: DEFAULT_STELLAR_FEE,
+        }
+    }
+}
+
+/// Conditions a fill is priced under
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Market {
+    /// Unix time the fill is expected at
+    pub now: u64,
+    /// Base fee of the latest EVM block, in wei
+    pub base_fee: u128,
+    /// Gas price the EVM transactions pay, in wei
+    pub gas_price: u128,
+}
+
+/// A fill priced at one moment
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Evaluation {
+    /// Maker asset the resolver receives on the EVM chain
+    pub making_amount: u128,
+    /// Destination token the resolver locks for the maker on Stellar
+    pub taking_amount: u128,
+    pub revenue_usd: f64,
+    /// Value of the taking amount and of the gas and fees
+    pub cost_usd: f64,
+}
+
+impl Evaluation {
+    pub fn profit_usd(&self) -> f64 {
+        self.revenue_usd - self.cost_usd
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Verdict {
+    Fill(Evaluation),
+    /// Not filled, at least for now
+    Skip(String),
+}
+
+/// Prices a fill of all of `order` in `market`. Only single-fill orders from an EVM chain
+/// to Stellar are filled, and only with at least `min_profit_usd` profit.
+pub fn evaluate(
+    order: &CrossChainOrder,
+    prices: &PriceBook,
+    costs: FillCosts,
+    market: Market,
+    min_profit_usd: f64,
+) -> Result<Verdict, ResolverError> {
+    let skip = |reason: &str| Ok(Verdict::Skip(reason.into()));
+    if !matches!(
+        (order.src_chain_id, order.dst_chain_id),
+        (ChainId::Evm(_), ChainId::Stellar)
+    ) {
+        return skip("only orders from an EVM chain to Stellar are filled");
+    }
+    if order.secret_hashes.is_some() {
+        return skip("multi-fill orders are not filled");
+    }
+    if order.remaining_maker_amount != order.order.making_amount {
+        return skip("order is partially filled");
+    }
+    if market.now > order.deadline {
+        return skip("order expired");
+    }
+    let (Some(maker_asset), Some(dst_token)) = (
+        prices.token(&order.order.maker_asset),
+        prices.token(&order.dst_token),
+    ) else {
+        return skip("no spot price for the order's tokens");
+    };
+
+    let amounts = OrderAmounts {
+        making_amount: order.order.making_amount,
+        taking_amount: order.order.taking_amount,
+    };
+    let making_amount = order.remaining_maker_amount;
+    let taking_amount = AuctionDetails::from(&order.auction).taking_amount(
+        amounts,
+        making_amount,
+        market.now,
+        market.base_fee,
+    )?;
+    let gas_usd = prices
+        .evm_native
+        .value(u128::from(costs.evm_gas).saturating_mul(market.gas_price))
+        + prices.stellar_native.value(costs.stellar_fee.into());
+    let evaluation = Evaluation {
+        making_amount,
+        taking_amount,
+        revenue_usd: maker_asset.value(making_amount),
+        cost_usd: dst_token.value(taking_amount) + gas_usd,
+    };
+    if evaluation.profit_usd() < min_profit_usd {
+        return Ok(Verdict::Skip(format!(
+            "profit of {:.2} USD is below {min_profit_usd:.2} USD",
+            evaluation.profit_usd()
+        )));
+    }
+    Ok(Verdict::Fill(evaluation))
+}
@@ -0,0 +1,121 @@
+//! The Stellar leg of a fill: the HTLC the resolver locks the maker's tokens in
+
+use crate::{
+    evm::{SrcEscrow, TimelockStage},
+    ResolverError,
+};
+use stellar_htlc_sdk::{
+    fusion::{ChainId, CrossChainOrder},
+    rpc::RpcClient,
+    types::CrossChainMetadata,
+    CreateHtlc, Hash32, HashAlgo, HtlcClient, Signer,
+};
+
+/// The destination HTLC of a fill whose source escrow is `src`, created by `resolver` at
+/// unix time `now`. The maker is the receiver and the resolver the taker, so only the
+/// resolver can withdraw it until the public withdrawal, revealing the secret. It expires
+/// before the source escrow's cancellation, which leaves the resolver time to withdraw
+/// that escrow with the secret.
+pub fn dst_params(
+    order: &CrossChainOrder,
+    src: &SrcEscrow,
+    resolver: &str,
+    now: u64,
+) -> Result<CreateHtlc, ResolverError> {
+    let invalid = |reason: &str| ResolverError::InvalidOrder {
+        order_hash: order.order_hash.to_string(),
+        reason: reason.into(),
+    };
+    let time_locks = &order.time_locks;
+    let timelock = now + u64::from(time_locks.dst_cancellation);
+    if timelock >= src.stage(TimelockStage::SrcCancellation) {
+        return Err(invalid(
+            "the destination HTLC would expire after the source escrow's cancellation",
+        ));
+    }
+    let amount =
+        i128::try_from(src.dst_amount).map_err(|_| invalid("destination amount exceeds i128"))?;
+    let safety_deposit = i128::try_from(order.dst_safety_deposit)
+        .map_err(|_| invalid("destination safety deposit exceeds i128"))?;
+    let evm_chain_id = match order.src_chain_id {
+        ChainId::Evm(chain_id) => chain_id,
+        ChainId::Stellar => return Err(invalid("the source chain is not an EVM chain")),
+    };
+    let public_withdrawal = match time_locks.dst_public_withdrawal {
+        0 => 0,
+        offset => now + u64::from(offset),
+    };
+    Ok(CreateHtlc {
+        hash_algo: HashAlgo::Keccak256,
+        safety_deposit,
+        public_withdrawal,
+        order_hash: order.order_hash,
+        taker: Some(resolver.into()),
+        metadata: CrossChainMetadata {
+            evm_chain_id,
+            evm_order_hash: order.order_hash,
+            maker: order.order.maker.clone(),
+        },
+        ..CreateHtlc::new(
+            &order.dst_receiver,
+            amount,
+            &order.dst_token,
+            order.hashlock,
+            timelock,
+        )
+    })
+}
+
+/// Creates and settles destination HTLCs from the resolver's Stellar account
+pub struct StellarFiller {
+    client: HtlcClient,
+    key: Box<dyn Signer>,
+}
+
+impl StellarFiller {
+    /// Submits to `contract_id` as the account of `key`
+    pub async fn connect(
+        rpc_url: &str,
+        contract_id: &str,
+        key: Box<dyn Signer>,
+    ) -> Result<Self, ResolverError> {
+        let rpc = RpcClient::new(rpc_url);
+        let passphrase = rpc.get_network().await?.passphrase;
+        let client = HtlcClient::new(rpc, contract_id, &passphrase)
+            .map_err(|error| ResolverError::Config(error.to_string()))?;
+        Ok(StellarFiller { client, key })
+    }
+
+    /// Strkey of the resolver's account
+    pub fn address(&self) -> String {
+        self.key.address()
+    }
+
+    /// Native balance of the resolver's account, in stroops
+    pub async fn balance(&self) -> Result<i64, ResolverError> {
+        Ok(self.client.balance(&*self.key).await?)
+    }
+
+    /// Locks the maker's tokens, returning the HTLC's contract ID
+    pub async fn create_dst(&self, params: &CreateHtlc) -> Result<Hash32, ResolverError> {
+        Ok(self.client.create_htlc(&*self.key, params).await?.value)
+    }
+
+    /// Withdraws to the maker, revealing `secret`
+    pub async fn withdraw_dst(
+        &self,
+        contract_id: Hash32,
+        secret: Hash32,
+    ) -> Result<String, ResolverError> {
+        Ok(self
+            .client
+            .withdraw(&*self.key, contract_id, secret)
+            .await?
+            .tx_hash)
+    }
+
+    /// Refunds the resolver past the timelock
+    pub async fn refund_dst(&self, contract_id: Hash32) -> Result<String, ResolverError> {
+        Ok(self.client.refund(&*self.key, contract_id).await?.tx_hash)
+    }
+}
@@ -0,0 +1,379 @@
+#![cfg(test)]
+
+use crate::{
+    evm::{
+        self, pack_timelocks, taker_traits, EscrowImmutables, SrcEscrow, TimelockStage,
+        MAKER_AMOUNT_FLAG,
+    },
+    fills::{plan, Action, DstHtlc, Fill, Stage, State},
+    metrics::Metrics,
+    orders::{LocalBook, OrderSource},
+    pricing::{evaluate, FillCosts, Market, PriceBook, TokenPrice, Verdict},
+    stellar::dst_params,
+    ResolverError,
+};
+use alloy::primitives::{address, B256, U256};
+use stellar_htlc_sdk::{
+    fusion::{ChainId, CrossChainOrder, OrderSecrets, SharedSecret},
+    secret,
+    testutils::PREIMAGE,
+    HashAlgo,
+};
+
+const WETH: &str = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2";
+const USDC: &str = "CDLZFC3SYJYDZT7K67VZ75HPJVIEUVNIXF47ZG2FB2RMQQVU2HHGCYSC";
+const RECEIVER: &str = "GCATS5YOVB6ROX2WUNKGNQ2MP3GMXDMKSG2O4N5CLX3A6W4PZGZZI55U";
+
+/// 1 WETH for 2900 USDC on Stellar, auctioned from a 0.5% bump at 1000 over 180 seconds
+fn order() -> CrossChainOrder {
+    let mut order: CrossChainOrder = serde_json::from_value(serde_json::json!({
+        "orderHash": "11".repeat(32),
+        "quoteId": "quote",
+        "srcChainId": 1,
+        "dstChainId": "stellar",
+        "order": {
+            "salt": "42",
+            "maker": "0x00000000000000000000000000000000000000aa",
+            "receiver": "0x0000000000000000000000000000000000000000",
+            "makerAsset": WETH,
+            "takerAsset": "0x00000000000000000000000000000000000000bb",
+            "makingAmount": "1000000000000000000",
+            "takingAmount": "29000000000",
+            "makerTraits": "0"
+        },
+        "signature": format!("0x{}{}1c", "01".repeat(32), "02".repeat(32)),
+        "extension": "0xdeadbeef",
+        "deadline": 2000,
+        "auction": {"startTime": 1000, "duration": 180, "initialRateBump": 50000},
+        "hashlock": "00".repeat(32),
+        "timeLocks": {
+            "srcWithdrawal": 12,
+            "srcPublicWithdrawal": 120,
+            "srcCancellation": 600,
+            "srcPublicCancellation": 720,
+            "dstWithdrawal": 10,
+            "dstPublicWithdrawal": 100,
+            "dstCancellation": 300
+        },
+        "srcSafetyDeposit": "1000000000000000",
+        "dstSafetyDeposit": "10000000",
+        "dstToken": USDC,
+        "dstReceiver": RECEIVER,
+        "remainingMakerAmount": "1000000000000000000"
+    }))
+    .unwrap();
+    order.hashlock = secret::hash(HashAlgo::Keccak256, &PREIMAGE.0);
+    order
+}
+
+fn prices() -> PriceBook {
+    let token = |decimals, usd| TokenPrice { decimals, usd };
+    PriceBook {
+        evm_native: token(18, 3000.0),
+        stellar_native: token(7, 0.1),
+        tokens: [
+            (WETH.to_lowercase(), token(18, 3000.0)),
+            (USDC.into(), token(7, 1.0)),
+        ]
+        .into_iter()
+        .collect(),
+    }
+}
+
+/// Halfway through the auction, at 1 gwei
+fn market() -> Market {
+    Market {
+        now: 1090,
+        base_fee: 0,
+        gas_price: 1_000_000_000,
+    }
+}
+
+/// The order's source escrow, deployed at 1100
+fn src_escrow() -> SrcEscrow {
+    let order = order();
+    let timelocks = pack_timelocks(&order.time_locks) | U256::from(1100) << 224;
+    SrcEscrow {
+        address: address!("0x00000000000000000000000000000000000000ee"),
+        tx_hash: B256::repeat_byte(3),
+        immutables: EscrowImmutables {
+            order_hash: order.order_hash,
+            hashlock: order.hashlock,
+            maker: address!("0x00000000000000000000000000000000000000aa"),
+            taker: address!("0x00000000000000000000000000000000000000cc"),
+            token: WETH.parse().unwrap(),
+            amount: U256::from(order.order.making_amount),
+            safety_deposit: U256::from(order.src_safety_deposit),
+            timelocks,
+        },
+        dst_amount: U256::from(29_072_500_000u64),
+    }
+}
+
+fn fill() -> Fill {
+    let Verdict::Fill(evaluation) =
+        evaluate(&order(), &prices(), FillCosts::default(), market(), 1.0).unwrap()
+    else {
+        panic!("order is profitable");
+    };
+    Fill::new(order(), evaluation)
+}
+
+//------------------------------------------------------------------
+//  Pricing
+//------------------------------------------------------------------
+#[test]
+fn profitable_orders_are_filled() {
+    let fill = fill();
+    // Half of the 0.5% bump is left halfway through the auction
+    assert_eq!(fill.evaluation.making_amount, 1_000_000_000_000_000_000);
+    assert_eq!(fill.evaluation.taking_amount, 29_072_500_000);
+    assert_eq!(fill.evaluation.revenue_usd, 3000.0);
+    // 400k gas at 1 gwei and 0.2 XLM of Stellar fees
+    let cost = 2907.25 + 1.2 + 0.02;
+    assert!((fill.evaluation.cost_usd - cost).abs() < 1e-6);
+    assert!((fill.evaluation.profit_usd() - (3000.0 - cost)).abs() < 1e-6);
+}
+
+#[test]
+fn unfillable_orders_are_skipped() {
+    let skipped = |order: CrossChainOrder, prices: PriceBook, min_profit_usd| match evaluate(
+        &order,
+        &prices,
+        FillCosts::default(),
+        market(),
+        min_profit_usd,
+    ) {
+        Ok(Verdict::Skip(reason)) => reason,
+        verdict => panic!("{verdict:?}"),
+    };
+    let mut reversed = order();
+    reversed.src_chain_id = ChainId::Stellar;
+    reversed.dst_chain_id = ChainId::Evm(1);
+    assert!(skipped(reversed, prices(), 1.0).contains("EVM chain to Stellar"));
+
+    let mut multi_fill = order();
+    multi_fill.secret_hashes = Some(vec![multi_fill.hashlock]);
+    assert!(skipped(multi_fill, prices(), 1.0).contains("multi-fill"));
+
+    let mut partial = order();
+    partial.remaining_maker_amount /= 2;
+    assert!(skipped(partial, prices(), 1.0).contains("partially filled"));
+
+    let mut expired = order();
+    expired.deadline = 1089;
+    assert!(skipped(expired, prices(), 1.0).contains("expired"));
+
+    let mut unpriced = prices();
+    unpriced.tokens.remove(USDC);
+    assert!(skipped(order(), unpriced, 1.0).contains("no spot price"));
+
+    assert!(skipped(order(), prices(), 100.0).contains("below 100.00 USD"));
+}
+
+//------------------------------------------------------------------
+//  EVM leg
+//------------------------------------------------------------------
+#[test]
+fn taker_traits_pack_threshold_and_extension() {
+    let traits = taker_traits(4, 29_072_500_000);
+    assert_eq!(traits & MAKER_AMOUNT_FLAG, MAKER_AMOUNT_FLAG);
+    assert_eq!(
+        (traits >> 224usize) & U256::from(u32::MAX >> 8),
+        U256::from(4)
+    );
+    assert_eq!(
+        traits & ((U256::from(1) << 185usize) - U256::from(1)),
+        U256::from(29_072_500_000u64)
+    );
+}
+
+#[test]
+fn signatures_are_made_compact() {
+    let r = B256::repeat_byte(1);
+    let s = B256::repeat_byte(2);
+    let (_, vs) = evm::compact_signature(&order().signature).unwrap();
+    assert_eq!(vs.0[0], 0x82);
+    assert_eq!(vs.0[1..], s.0[1..]);
+    // v = 27 leaves the top bit clear, and compact signatures pass through
+    let rs = format!("{r}{}", alloy::hex::encode(s));
+    assert_eq!(evm::compact_signature(&format!("{rs}1b")), Some((r, s)));
+    assert_eq!(evm::compact_signature(&rs), Some((r, s)));
+    assert_eq!(evm::compact_signature(&format!("{rs}1d")), None);
+    assert_eq!(evm::compact_signature("0x1234"), None);
+}
+
+#[test]
+fn timelocks_unpack_from_the_deployment_time() {
+    let src = src_escrow();
+    assert_eq!(src.stage(TimelockStage::SrcWithdrawal), 1112);
+    assert_eq!(src.stage(TimelockStage::SrcCancellation), 1700);
+    assert_eq!(src.stage(TimelockStage::SrcPublicCancellation), 1820);
+    assert_eq!(src.stage(TimelockStage::DstCancellation), 1400);
+
+    let immutables = evm::src_immutables(&order(), 5, src.immutables.taker).unwrap();
+    assert_eq!(immutables.amount, U256::from(5));
+    assert_eq!(immutables.timelocks >> 224usize, U256::ZERO);
+    assert_eq!(
+        EscrowImmutables::from(&immutables),
+        EscrowImmutables {
+            amount: U256::from(5),
+            timelocks: pack_timelocks(&order().time_locks),
+            ..src.immutables
+        }
+    );
+}
+
+//------------------------------------------------------------------
+//  Stellar leg
+//------------------------------------------------------------------
+#[test]
+fn dst_htlc_expires_before_src_cancellation() {
+    let resolver = "GBRPYHIL2CI3FNQ4BXLFMNDLFJUNPU2HY3ZMFSHONUCEOASW7QC7OX2H";
+    let params = dst_params(&order(), &src_escrow(), resolver, 1110).unwrap();
+    assert_eq!(params.receiver, RECEIVER);
+    assert_eq!(params.amount, 29_072_500_000);
+    assert_eq!(params.token_address, USDC);
+    assert_eq!(params.hash_algo, HashAlgo::Keccak256);
+    assert_eq!(params.hashlock, order().hashlock);
+    assert_eq!(params.timelock, 1410);
+    assert_eq!(params.public_withdrawal, 1210);
+    assert_eq!(params.safety_deposit, 10_000_000);
+    assert_eq!(params.taker.as_deref(), Some(resolver));
+    assert_eq!(params.order_hash, order().order_hash);
+    assert_eq!(params.metadata.evm_chain_id, 1);
+
+    // Created this late, it would outlive the source escrow's cancellation at 1700
+    assert!(matches!(
+        dst_params(&order(), &src_escrow(), resolver, 1400),
+        Err(ResolverError::InvalidOrder { .. })
+    ));
+}
+
+//------------------------------------------------------------------
+//  Fills
+//------------------------------------------------------------------
+#[test]
+fn fills_advance_through_both_chains() {
+    let mut fill = fill();
+    assert_eq!(plan(&fill, 1090), Action::DeploySrc);
+    fill.stage = Stage::SrcDeployed;
+    assert_eq!(plan(&fill, 1100), Action::Fail("no source escrow recorded"));
+    fill.src = Some(src_escrow());
+    assert_eq!(plan(&fill, 1100), Action::CreateDst);
+
+    fill.stage = Stage::DstCreated;
+    fill.dst = Some(DstHtlc {
+        contract_id: fill.order.order_hash,
+        timelock: 1410,
+    });
+    assert_eq!(plan(&fill, 1200), Action::AwaitSecret);
+    fill.secret = Some(PREIMAGE);
+    assert_eq!(plan(&fill, 1200), Action::WithdrawDst(PREIMAGE));
+
+    fill.stage = Stage::DstWithdrawn;
+    assert_eq!(plan(&fill, 1200), Action::WithdrawSrc(PREIMAGE));
+    fill.stage = Stage::Completed;
+    assert_eq!(plan(&fill, 1200), Action::Done);
+    assert!(!fill.is_open());
+}
+
+#[test]
+fn unanswered_fills_are_refunded() {
+    let mut fill = fill();
+    fill.src = Some(src_escrow());
+    // Too late to create the destination HTLC
+    fill.stage = Stage::SrcDeployed;
+    assert_eq!(plan(&fill, 1700), Action::CancelSrc);
+
+    // No secret before the destination timelock, nor a withdrawal after it
+    fill.stage = Stage::DstCreated;
+    fill.dst = Some(DstHtlc {
+        contract_id: fill.order.order_hash,
+        timelock: 1410,
+    });
+    assert_eq!(plan(&fill, 1410), Action::RefundDst);
+    fill.secret = Some(PREIMAGE);
+    assert_eq!(plan(&fill, 1410), Action::RefundDst);
+
+    fill.stage = Stage::DstRefunded;
+    assert_eq!(plan(&fill, 1500), Action::Wait);
+    assert_eq!(plan(&fill, 1700), Action::CancelSrc);
+    assert!(fill.is_open());
+}
+
+#[test]
+fn state_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("state.json");
+    assert_eq!(State::load(&path).unwrap(), None);
+
+    let mut state = State::default();
+    let mut fill = fill();
+    fill.stage = Stage::DstCreated;
+    fill.src = Some(src_escrow());
+    fill.dst = Some(DstHtlc {
+        contract_id: fill.order.order_hash,
+        timelock: 1410,
+    });
+    fill.error = Some("timed out".into());
+    fill.attempts = 1;
+    state.fills.insert(fill.order.order_hash, fill);
+    state.save(&path).unwrap();
+    assert_eq!(State::load(&path).unwrap(), Some(state.clone()));
+    assert_eq!(state.open_fills(), 1);
+}
+
+#[test]
+fn metrics_count_fills_and_steps() {
+    let metrics = Metrics::new();
+    let mut state = State::default();
+    let mut fill = fill();
+    fill.src = Some(src_escrow());
+    fill.stage = Stage::Completed;
+    state.fills.insert(fill.order.order_hash, fill.clone());
+    metrics.observe(&state);
+    metrics.order("fill");
+    metrics.step(Action::DeploySrc, true);
+    metrics.step(Action::CreateDst, false);
+    metrics.settled(1400 - src_escrow().deployed_at());
+
+    let text = metrics.render();
+    assert!(text.contains("resolver_fills{stage=\"completed\"} 1"));
+    assert!(text.contains("resolver_fills{stage=\"accepted\"} 0"));
+    assert!(text.contains("resolver_orders_total{verdict=\"fill\"} 1"));
+    assert!(text.contains("resolver_fill_steps_total{outcome=\"ok\",step=\"deploySrc\"} 1"));
+    assert!(text.contains("resolver_rpc_errors_total{source=\"stellar\"} 1"));
+    assert!(text.contains("resolver_settlement_seconds_sum 300"));
+}
+
+#[tokio::test]
+async fn local_book_lists_orders_and_secrets() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("orders.json");
+    let order = order();
+    let book = LocalBook {
+        orders: vec![order.clone()],
+        secrets: vec![OrderSecrets {
+            order_hash: order.order_hash,
+            secrets: vec![SharedSecret {
+                idx: 0,
+                secret: PREIMAGE,
+            }],
+        }],
+    };
+    std::fs::write(&path, serde_json::to_vec(&book).unwrap()).unwrap();
+
+    let source = OrderSource::new(path.to_str().unwrap(), None);
+    assert!(matches!(source, OrderSource::File(_)));
+    assert_eq!(source.active_orders().await.unwrap(), vec![order.clone()]);
+    assert_eq!(
+        source.secret(order.order_hash).await.unwrap(),
+        Some(PREIMAGE)
+    );
+    assert_eq!(source.secret(PREIMAGE).await.unwrap(), None);
+    assert!(matches!(
+        OrderSource::new("https://api.1inch.dev/fusion-plus/", None),
+        OrderSource::Api { base_url, .. } if base_url == "https://api.1inch.dev/fusion-plus"
+    ));
+}
@@ -0,0 +1,333 @@
+//! Async client of one deployed HTLC contract
+
+use crate::{
+    rpc::{GetTransactionResponse, RpcClient},
+    scval,
+    signer::Signer,
+    transaction::{self, Invocation, SignedTransaction, TX_VALIDITY_SECS},
+    types::{CreateHtlc, Hash32, Htlc},
+    Error,
+};
+use std::time::Duration;
+use stellar_xdr::{
+    AccountEntry, LedgerEntryData, LedgerKey, LedgerKeyAccount, Limits, ReadXdr, ScAddress, ScVal,
+    TransactionMeta, WriteXdr,
+};
+
+/// Wait between `getTransaction` polls while a transaction is pending
+pub const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Outcome of an included transaction
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Receipt<T> {
+    pub value: T,
+    pub tx_hash: String,
+    pub ledger: u32,
+}
+
+/// Inclusion status of a transaction as reported by `getTransaction`
+#[derive(Clone, Debug, PartialEq)]
+pub enum TransactionStatus {
+    /// Not included, or not included yet
+    NotFound,
+    Succeeded {
+        ledger: u32,
+        return_value: Option<ScVal>,
+    },
+    Failed {
+        ledger: u32,
+    },
+}
+
+pub struct HtlcClient {
+    rpc: RpcClient,
+    contract: ScAddress,
+    network_id: [u8; 32],
+}
+
+impl HtlcClient {
+    /// Client of the contract `contract_id` on the network `rpc_url` serves
+    pub async fn connect(rpc_url: &str, contract_id: &str) -> Result<Self, Error> {
+        let rpc = RpcClient::new(rpc_url);
+        let passphrase = rpc.get_network().await?.passphrase;
+        HtlcClient::new(rpc, contract_id, &passphrase)
+    }
+
+    pub fn new(rpc: RpcClient, contract_id: &str, network_passphrase: &str) -> Result<Self, Error> {
+        Ok(HtlcClient {
+            rpc,
+            contract: contract_id.parse().map_err(|_| {
+                Error::InvalidArgument(format!("invalid contract ID {contract_id}"))
+            })?,
+            network_id: transaction::network_id(network_passphrase),
+        })
+    }
+
+    pub fn rpc(&self) -> &RpcClient {
+        &self.rpc
+    }
+
+    /// Strkey of the contract
+    pub fn contract_id(&self) -> String {
+        self.contract.to_string()
+    }
+
+    /// Locks `params.amount` from the signer's account, returning the new contract ID
+    pub async fn create_htlc(
+        &self,
+        signer: &dyn Signer,
+        params: &CreateHtlc,
+    ) -> Result<Receipt<Hash32>, Error> {
+        let receipt = self
+            .submit(signer, &Invocation::create_htlc(&signer.address(), params)?)
+            .await?;
+        let value = receipt
+            .value
+            .ok_or_else(|| Error::Simulation("no contract ID returned".into()))?;
+        Ok(Receipt {
+            value: scval::hash(&value, "contract_id")?,
+            tx_hash: receipt.tx_hash,
+            ledger: receipt.ledger,
+        })
+    }
+
+    /// Withdraws to the receiver by revealing `preimage`; the signer must be the HTLC's
+    /// current withdrawer
+    pub async fn withdraw(
+        &self,
+        signer: &dyn Signer,
+        contract_id: Hash32,
+        preimage: Hash32,
+    ) -> Result<Receipt<()>, Error> {
+        self.submit_unit(signer, &Invocation::withdraw(contract_id, preimage))
+            .await
+    }
+
+    /// Refunds the sender past the timelock; the signer must be the sender
+    pub async fn refund(
+        &self,
+        signer: &dyn Signer,
+        contract_id: Hash32,
+    ) -> Result<Receipt<()>, Error> {
+        self.submit_unit(signer, &Invocation::refund(contract_id))
+            .await
+    }
+
+    /// Reads an HTLC by simulating `get_htlc`; nothing is signed or submitted
+    pub async fn get_htlc(&self, contract_id: Hash32) -> Result<Htlc, Error> {
+        let value = self.read(&Invocation::get_htlc(contract_id)).await?;
+        Ok(Htlc::from_scval(&value)?)
+    }
+
+    /// Contract IDs `sender` created, oldest first; the contract caps pages at 100
+    pub async fn htlcs_by_sender(
+        &self,
+        sender: &str,
+        offset: u32,
+        limit: u32,
+    ) -> Result<Vec<Hash32>, Error> {
+        let value = self
+            .read(&Invocation::htlcs_by_sender(sender, offset, limit)?)
+            .await?;
+        Ok(scval::hashes(&value, "contract_ids")?)
+    }
+
+    /// Contract IDs `receiver` may claim, oldest first, optionally only the active ones
+    pub async fn htlcs_by_receiver(
+        &self,
+        receiver: &str,
+        offset: u32,
+        limit: u32,
+        active_only: bool,
+    ) -> Result<Vec<Hash32>, Error> {
+        let invocation = Invocation::htlcs_by_receiver(receiver, offset, limit, active_only)?;
+        let value = self.read(&invocation).await?;
+        Ok(scval::hashes(&value, "contract_ids")?)
+    }
+
+    /// Return value of a simulated invocation. The source is the all-zero account,
+    /// which the simulation does not need to exist.
+    pub async fn read(&self, invocation: &Invocation) -> Result<ScVal, Error> {
+        let tx = transaction::build([0; 32], 0, &self.contract, invocation, 0)?;
+        let simulation = self
+            .rpc
+            .simulate_transaction(&transaction::envelope(tx, vec![])?)
+            .await?;
+        if let Some(error) = simulation.error {
+            return Err(Error::from_simulation(error));
+        }
+        let result = simulation
+            .results
+            .first()
+            .ok_or_else(|| Error::Simulation("no result in simulation".into()))?;
+        Ok(ScVal::from_xdr_base64(&result.xdr, Limits::none())?)
+    }
+
+    /// Builds, simulates and signs an invocation from the signer's account, valid until
+    /// `valid_until`. A call the contract would reject fails here with its contract error.
+    pub async fn prepare(
+        &self,
+        signer: &dyn Signer,
+        invocation: &Invocation,
+        valid_until: u64,
+    ) -> Result<SignedTransaction, Error> {
+        let sequence = self.sequence(signer).await? + 1;
+        let tx = transaction::build(
+            signer.public_key(),
+            sequence,
+            &self.contract,
+            invocation,
+            valid_until,
+        )?;
+        let simulation = self
+            .rpc
+            .simulate_transaction(&transaction::envelope(tx.clone(), vec![])?)
+            .await?;
+        let tx = transaction::assemble(tx, &simulation)?;
+        transaction::sign(tx, signer, self.network_id).await
+    }
+
+    /// Fee bump of `tx` paid by `fee_source`, offering `inclusion_fee` stroops per
+    /// operation. Broadcast while `tx` is pending, it replaces `tx` if it offers at least
+    /// `FEE_BUMP_MULTIPLIER` times its fee rate.
+    pub async fn fee_bump(
+        &self,
+        fee_source: &dyn Signer,
+        tx: &SignedTransaction,
+        inclusion_fee: i64,
+    ) -> Result<SignedTransaction, Error> {
+        transaction::fee_bump(&tx.envelope, fee_source, inclusion_fee, self.network_id).await
+    }
+
+    /// Broadcasts a signed transaction; sending one the network already has is a no-op
+    pub async fn send(&self, tx: &SignedTransaction) -> Result<(), Error> {
+        let response = self.rpc.send_transaction(&tx.envelope).await?;
+        match response.status.as_str() {
+            "PENDING" | "DUPLICATE" | "TRY_AGAIN_LATER" => Ok(()),
+            _ => Err(Error::Rejected {
+                tx_hash: response.hash,
+                reason: response
+                    .error_result_xdr
+                    .unwrap_or_else(|| response.status.clone()),
+            }),
+        }
+    }
+
+    pub async fn status(&self, tx_hash: &str) -> Result<TransactionStatus, Error> {
+        let response = self.rpc.get_transaction(tx_hash).await?;
+        let ledger = response.ledger.unwrap_or(response.latest_ledger);
+        Ok(match response.status.as_str() {
+            "SUCCESS" => TransactionStatus::Succeeded {
+                ledger,
+                return_value: return_value(&response)?,
+            },
+            "FAILED" => TransactionStatus::Failed { ledger },
+            _ => TransactionStatus::NotFound,
+        })
+    }
+
+    /// Sends `tx` and waits for its inclusion, returning what the invocation returned
+    pub async fn send_and_wait(
+        &self,
+        tx: &SignedTransaction,
+    ) -> Result<Receipt<Option<ScVal>>, Error> {
+        self.send(tx).await?;
+        loop {
+            match self.status(&tx.hash).await? {
+                TransactionStatus::Succeeded {
+                    ledger,
+                    return_value,
+                } => {
+                    return Ok(Receipt {
+                        value: return_value,
+                        tx_hash: tx.hash.clone(),
+                        ledger,
+                    })
+                }
+                TransactionStatus::Failed { ledger } => {
+                    return Err(Error::Failed {
+                        tx_hash: tx.hash.clone(),
+                        ledger,
+                    })
+                }
+                TransactionStatus::NotFound if transaction::unix_now() > tx.valid_until => {
+                    return Err(Error::Expired {
+                        tx_hash: tx.hash.clone(),
+                    })
+                }
+                TransactionStatus::NotFound => tokio::time::sleep(POLL_INTERVAL).await,
+            }
+        }
+    }
+
+    /// Prepares, sends and waits for an invocation
+    pub async fn submit(
+        &self,
+        signer: &dyn Signer,
+        invocation: &Invocation,
+    ) -> Result<Receipt<Option<ScVal>>, Error> {
+        let valid_until = transaction::unix_now() + TX_VALIDITY_SECS;
+        let tx = self.prepare(signer, invocation, valid_until).await?;
+        self.send_and_wait(&tx).await
+    }
+
+    async fn submit_unit(
+        &self,
+        signer: &dyn Signer,
+        invocation: &Invocation,
+    ) -> Result<Receipt<()>, Error> {
+        let receipt = self.submit(signer, invocation).await?;
+        Ok(Receipt {
+            value: (),
+            tx_hash: receipt.tx_hash,
+            ledger: receipt.ledger,
+        })
+    }
+
+    /// Native balance of `account`, in stroops
+    pub async fn balance(&self, account: &dyn Signer) -> Result<i64, Error> {
+        Ok(self.account(account).await?.balance)
+    }
+
+    /// Current sequence number of the signer's account
+    async fn sequence(&self, signer: &dyn Signer) -> Result<i64, Error> {
+        Ok(self.account(signer).await?.seq_num.0)
+    }
+
+    async fn account(&self, account: &dyn Signer) -> Result<AccountEntry, Error> {
+        let key = LedgerKey::Account(LedgerKeyAccount {
+            account_id: account.account_id(),
+        })
+        .to_xdr_base64(Limits::none())?;
+        let response = self.rpc.get_ledger_entries(&[key]).await?;
+        let entry = response
+            .entries
+            .first()
+            .ok_or_else(|| Error::AccountNotFound(account.address()))?;
+        match LedgerEntryData::from_xdr_base64(&entry.xdr, Limits::none())? {
+            LedgerEntryData::Account(account) => Ok(account),
+            _ => Err(Error::Rpc {
+                code: 0,
+                message: "getLedgerEntries returned a non-account entry".into(),
+            }),
+        }
+    }
+}
+
+/// Return value of an included invocation, from `returnValue` where the RPC reports it
+/// and from the transaction meta otherwise
+fn return_value(response: &GetTransactionResponse) -> Result<Option<ScVal>, Error> {
+    if let Some(value) = &response.return_value {
+        return Ok(Some(ScVal::from_xdr_base64(value, Limits::none())?));
+    }
+    let Some(meta) = &response.result_meta_xdr else {
+        return Ok(None);
+    };
+    Ok(
+        match TransactionMeta::from_xdr_base64(meta, Limits::none())? {
+            TransactionMeta::V3(meta) => meta.soroban_meta.map(|soroban| soroban.return_value),
+            TransactionMeta::V4(meta) => meta.soroban_meta.and_then(|soroban| soroban.return_value),
+            _ => None,
+        },
+    )
+}
@@ -0,0 +1,211 @@
+use crate::types::DecodeError;
+use std::fmt;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("RPC transport error: {0}")]
+    Transport(#[from] reqwest::Error),
+    #[error("RPC error {code}: {message}")]
+    Rpc { code: i64, message: String },
+    #[error("invalid XDR: {0}")]
+    Xdr(#[from] stellar_xdr::Error),
+    #[error("undecodable contract value: {0}")]
+    Decode(#[from] DecodeError),
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+    #[error("no randomness from the operating system: {0}")]
+    Random(String),
+    #[error("account {0} not found")]
+    AccountNotFound(String),
+    #[error("contract error #{} {0}", *.0 as u32)]
+    Contract(ContractError),
+    #[error("unknown contract error #{0}")]
+    UnknownContractError(u32),
+    #[error("transaction simulation failed: {0}")]
+    Simulation(String),
+    #[error("transaction {tx_hash} rejected: {reason}")]
+    Rejected { tx_hash: String, reason: String },
+    #[error("transaction {tx_hash} failed in ledger {ledger}")]
+    Failed { tx_hash: String, ledger: u32 },
+    #[error("transaction {tx_hash} expired before inclusion")]
+    Expired { tx_hash: String },
+    #[error("keystore error: {0}")]
+    Keystore(String),
+    #[error("KMS error: {0}")]
+    Kms(String),
+}
+
+/// The contract's `HTLCError`, numbered as in the contract
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum ContractError {
+    InvalidAmount = 1,
+    InvalidSafetyDeposit = 2,
+    InvalidTimelock = 3,
+    ContractAlreadyExists = 4,
+    ContractNotFound = 5,
+    ReentrancyDetected = 6,
+    AlreadyWithdrawn = 7,
+    AlreadyRefunded = 8,
+    TimelockExpired = 9,
+    TimelockNotExpired = 10,
+    InvalidPreimage = 11,
+    InvalidPartsCount = 12,
+    InvalidFillAmount = 13,
+    InvalidFillIndex = 14,
+    InvalidMerkleProof = 15,
+    PartialFillRequired = 16,
+    PartialFillNotAllowed = 17,
+    InvalidPublicWithdrawal = 18,
+    PublicWithdrawalNotStarted = 19,
+    InvalidPublicCancellation = 20,
+    PublicCancellationNotStarted = 21,
+    AlreadyInitialized = 22,
+    NotInitialized = 23,
+    ContractPaused = 24,
+    ResolverNotWhitelisted = 25,
+    InvalidFee = 26,
+    InvalidTtlExtension = 27,
+    ContractNotCompleted = 28,
+    CleanupTooEarly = 29,
+    InsufficientRescuableBalance = 30,
+    InvalidAuction = 31,
+    InvalidOrderSigner = 32,
+    OrderExpired = 33,
+    OrderAlreadyUsed = 34,
+    SignatureExpired = 35,
+    InvalidSigner = 36,
+    InvalidTimelockBounds = 37,
+    AmountOverflow = 38,
+    AmountAboveCap = 39,
+    InvalidBasket = 40,
+    InvalidSwap = 41,
+    NoPendingAdmin = 42,
+    AdminTransferNotReady = 43,
+    HashlockInUse = 44,
+    TakerExclusive = 45,
+    TokenNotAllowed = 46,
+    SenderIsReceiver = 47,
+    ZeroHashlock = 48,
+    EmptyPreimageHashlock = 49,
+    InvalidThreshold = 50,
+    MemoTooLong = 51,
+    RateLimited = 52,
+    InvalidRateLimit = 53,
+    FreezeDisabled = 54,
+    AlreadyFrozen = 55,
+    EscrowFrozen = 56,
+    ExtensionTooLong = 57,
+    InvalidTokenContract = 58,
+    InvalidVestingSchedule = 59,
+    NotVesting = 60,
+    NothingVested = 61,
+    FreezeTooLate = 62,
+    InvalidEscrowParams = 63,
+    WithdrawalNotStarted = 64,
+}
+
+impl ContractError {
+    pub fn from_code(code: u32) -> Option<Self> {
+        use ContractError::*;
+        Some(match code {
+            1 => InvalidAmount,
+            2 => InvalidSafetyDeposit,
+            3 => InvalidTimelock,
+            4 => ContractAlreadyExists,
+            5 => ContractNotFound,
+            6 => ReentrancyDetected,
+            7 => AlreadyWithdrawn,
+            8 => AlreadyRefunded,
+            9 => TimelockExpired,
+            10 => TimelockNotExpired,
+            11 => InvalidPreimage,
+            12 => InvalidPartsCount,
+            13 => InvalidFillAmount,
+            14 => InvalidFillIndex,
+            15 => InvalidMerkleProof,
+            16 => PartialFillRequired,
+            17 => PartialFillNotAllowed,
+            18 => InvalidPublicWithdrawal,
+            19 => PublicWithdrawalNotStarted,
+            20 => InvalidPublicCancellation,
+            21 => PublicCancellationNotStarted,
+            22 => AlreadyInitialized,
+            23 => NotInitialized,
+            24 => ContractPaused,
+            25 => ResolverNotWhitelisted,
+            26 => InvalidFee,
+            27 => InvalidTtlExtension,
+            28 => ContractNotCompleted,
+            29 => CleanupTooEarly,
+            30 => InsufficientRescuableBalance,
+            31 => InvalidAuction,
+            32 => InvalidOrderSigner,
+            33 => OrderExpired,
+            34 => OrderAlreadyUsed,
+            35 => SignatureExpired,
+            36 => InvalidSigner,
+            37 => InvalidTimelockBounds,
+            38 => AmountOverflow,
+            39 => AmountAboveCap,
+            40 => InvalidBasket,
+            41 => InvalidSwap,
+            42 => NoPendingAdmin,
+            43 => AdminTransferNotReady,
+            44 => HashlockInUse,
+            45 => TakerExclusive,
+            46 => TokenNotAllowed,
+            47 => SenderIsReceiver,
+            48 => ZeroHashlock,
+            49 => EmptyPreimageHashlock,
+            50 => InvalidThreshold,
+            51 => MemoTooLong,
+            52 => RateLimited,
+            53 => InvalidRateLimit,
+            54 => FreezeDisabled,
+            55 => AlreadyFrozen,
+            56 => EscrowFrozen,
+            57 => ExtensionTooLong,
+            58 => InvalidTokenContract,
+            59 => InvalidVestingSchedule,
+            60 => NotVesting,
+            61 => NothingVested,
+            62 => FreezeTooLate,
+            63 => InvalidEscrowParams,
+            64 => WithdrawalNotStarted,
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for ContractError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+impl Error {
+    /// The contract error of a rejected invocation
+    pub fn contract_error(&self) -> Option<ContractError> {
+        match self {
+            Error::Contract(error) => Some(*error),
+            _ => None,
+        }
+    }
+
+    /// The contract error a failed simulation reports as `Error(Contract, #code)`, or a
+    /// plain simulation error for any other host error
+    pub fn from_simulation(message: String) -> Self {
+        let code = message
+            .split_once("Error(Contract, #")
+            .and_then(|(_, rest)| rest.split_once(')'))
+            .and_then(|(code, _)| code.parse().ok());
+        match code {
+            Some(code) => ContractError::from_code(code)
+                .map(Error::Contract)
+                .unwrap_or(Error::UnknownContractError(code)),
+            None => Error::Simulation(message),
+        }
+    }
+}
@@ -0,0 +1,151 @@
+//! Decoding of the HTLC contract's lifecycle events from their XDR form
+//!
+//! Every event's data is wrapped as `(schema_version, sequence, payload)`; lifecycle
+//! payloads other than `HTLCNew` are further paired as `(data, memo, created_ledger)`.
+
+use crate::{
+    rpc::{EventFilter, EventInfo},
+    scval::*,
+    types::{CrossChainMetadata, DecodeError, Hash32, HashAlgo},
+};
+use serde::{Deserialize, Serialize};
+use stellar_xdr::{Limits, ScVal, WriteXdr};
+
+/// Event schema version this decoder understands
+pub const EVENT_SCHEMA_VERSION: u32 = 5;
+
+/// Topic names of the lifecycle events
+pub const TOPIC_NEW: &str = "HTLCNew";
+pub const TOPIC_WITHDRAW: &str = "HTLCWithdraw";
+pub const TOPIC_REFUND: &str = "HTLCRefund";
+
+/// Data of `HTLCNew`, limited to what is needed to follow a swap
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HtlcNew {
+    pub sender: String,
+    pub receiver: String,
+    pub amount: i128,
+    pub token_address: String,
+    pub hashlock: Hash32,
+    pub hash_algo: HashAlgo,
+    pub timelock: u64,
+    pub timestamp: u64,
+    pub created_ledger: u32,
+    pub safety_deposit: i128,
+    pub safety_deposit_token: String,
+    pub parts: u32,
+    pub public_withdrawal: u64,
+    pub public_cancellation: u64,
+    pub order_hash: Hash32,
+    pub taker: String,
+    pub metadata: CrossChainMetadata,
+    pub memo: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HtlcEvent {
+    New(Box<HtlcNew>),
+    /// The receiver withdrew, revealing the preimage
+    Withdraw {
+        preimage: Hash32,
+    },
+    Refund,
+}
+
+/// A decoded lifecycle event of one HTLC
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContractEvent {
+    /// RPC event ID, ordered within the ledger history
+    pub id: String,
+    pub ledger: u32,
+    pub tx_hash: String,
+    pub contract_id: Hash32,
+    /// Contract-wide event sequence number
+    pub sequence: u64,
+    pub event: HtlcEvent,
+}
+
+/// Base64 XDR of a topic symbol, as used in `getEvents` filters
+pub fn topic_symbol(name: &str) -> String {
+    symbol_val(name)
+        .to_xdr_base64(Limits::none())
+        .expect("symbols always encode")
+}
+
+/// `getEvents` filter matching the lifecycle events of the contract `contract_id`
+pub fn lifecycle_filter(contract_id: &str) -> EventFilter {
+    EventFilter {
+        kind: "contract".into(),
+        contract_ids: vec![contract_id.into()],
+        topics: [TOPIC_NEW, TOPIC_WITHDRAW, TOPIC_REFUND]
+            .iter()
+            .map(|name| vec![topic_symbol(name), "*".into()])
+            .collect(),
+    }
+}
+
+/// Decodes an RPC event, returning `None` for events other than the lifecycle ones
+pub fn decode(info: &EventInfo) -> Result<Option<ContractEvent>, DecodeError> {
+    let topics = info
+        .topic
+        .iter()
+        .map(|topic| from_base64(topic))
+        .collect::<Result<Vec<_>, _>>()?;
+    let (name, contract_id) = match topics.as_slice() {
+        [ScVal::Symbol(name), contract_id] => (name.to_string(), hash(contract_id, "topic")?),
+        _ => return Ok(None),
+    };
+    if ![TOPIC_NEW, TOPIC_WITHDRAW, TOPIC_REFUND].contains(&name.as_str()) {
+        return Ok(None);
+    }
+
+    let [version, sequence, payload] = vec_of::<3>(&from_base64(&info.value)?, "data")?;
+    let version = u32_of(&version, "schema_version")?;
+    if version != EVENT_SCHEMA_VERSION {
+        return Err(DecodeError::SchemaVersion(version));
+    }
+
+    let event = match name.as_str() {
+        TOPIC_NEW => HtlcEvent::New(Box::new(decode_new(&payload)?)),
+        TOPIC_WITHDRAW => {
+            let [preimage, _, _] = vec_of::<3>(&payload, "payload")?;
+            HtlcEvent::Withdraw {
+                preimage: hash(&preimage, "preimage")?,
+            }
+        }
+        _ => HtlcEvent::Refund,
+    };
+    Ok(Some(ContractEvent {
+        id: info.id.clone(),
+        ledger: info.ledger,
+        tx_hash: info.tx_hash.clone(),
+        contract_id,
+        sequence: u64_of(&sequence, "sequence")?,
+        event,
+    }))
+}
+
+fn decode_new(payload: &ScVal) -> Result<HtlcNew, DecodeError> {
+    let map = map_of(payload, "payload")?;
+    let metadata = metadata(field(map, "metadata")?)?;
+    Ok(HtlcNew {
+        sender: address(field(map, "sender")?, "sender")?,
+        receiver: address(field(map, "receiver")?, "receiver")?,
+        amount: i128_of(field(map, "amount")?, "amount")?,
+        token_address: address(field(map, "token_address")?, "token_address")?,
+        hashlock: hash(field(map, "hashlock")?, "hashlock")?,
+        hash_algo: hash_algo(field(map, "hash_algo")?)?,
+        timelock: u64_of(field(map, "timelock")?, "timelock")?,
+        timestamp: u64_of(field(map, "timestamp")?, "timestamp")?,
+        created_ledger: u32_of(field(map, "created_ledger")?, "created_ledger")?,
+        safety_deposit: i128_of(field(map, "safety_deposit")?, "safety_deposit")?,
+        safety_deposit_token: address(field(map, "safety_deposit_token")?, "safety_deposit_token")?,
+        parts: u32_of(field(map, "parts")?, "parts")?,
+        public_withdrawal: u64_of(field(map, "public_withdrawal")?, "public_withdrawal")?,
+        public_cancellation: u64_of(field(map, "public_cancellation")?, "public_cancellation")?,
+        order_hash: hash(field(map, "order_hash")?, "order_hash")?,
+        taker: address(field(map, "taker")?, "taker")?,
+        metadata,
+        memo: bytes(field(map, "memo")?, "memo")?,
+    })
+}
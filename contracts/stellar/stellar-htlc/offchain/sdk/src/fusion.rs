@@ -0,0 +1,354 @@
+//! Fusion+ cross-chain orders, in the JSON shape of the 1inch Fusion+ API
+//!
+//! Field names and the paging envelope follow the API's camelCase schemas, and amounts are
+//! decimal strings as in the API. A Stellar leg adds what the API keeps in the order's
+//! extension or cannot express as an EVM address: the hashlock, the escrow timelocks and
+//! safety deposits, the auction, and the destination token and receiver as strkeys.
+
+use crate::types::Hash32;
+use fusion_auction::{AuctionDetails, AuctionPoint, GasCost};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Chain of one leg: an EVM chain ID, or `"stellar"`. Chain IDs are also read from
+/// strings, as query parameters carry them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ChainId {
+    Evm(u64),
+    Stellar,
+}
+
+impl Serialize for ChainId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            ChainId::Evm(chain_id) => serializer.serialize_u64(*chain_id),
+            ChainId::Stellar => serializer.serialize_str("stellar"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ChainId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Evm(u64),
+            Name(String),
+        }
+        match Repr::deserialize(deserializer)? {
+            Repr::Evm(chain_id) => Ok(ChainId::Evm(chain_id)),
+            Repr::Name(name) if name.eq_ignore_ascii_case("stellar") => Ok(ChainId::Stellar),
+            Repr::Name(name) => name
+                .parse()
+                .map(ChainId::Evm)
+                .map_err(|_| serde::de::Error::custom(format!("unknown chain {name}"))),
+        }
+    }
+}
+
+/// Amounts as decimal strings, the way the API encodes `uint256` values
+pub mod amount {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(amount: &u128, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(amount)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u128, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// The Limit Order Protocol order the maker signed on the source chain
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LimitOrder {
+    /// Decimal `uint256`
+    pub salt: String,
+    pub maker: String,
+    pub receiver: String,
+    pub maker_asset: String,
+    pub taker_asset: String,
+    #[serde(with = "amount")]
+    pub making_amount: u128,
+    #[serde(with = "amount")]
+    pub taking_amount: u128,
+    /// Decimal `uint256`
+    pub maker_traits: String,
+}
+
+/// Escrow stage offsets in seconds: source stages from the source escrow's deployment,
+/// destination stages from the destination escrow's
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeLocks {
+    pub src_withdrawal: u32,
+    pub src_public_withdrawal: u32,
+    pub src_cancellation: u32,
+    pub src_public_cancellation: u32,
+    pub dst_withdrawal: u32,
+    pub dst_public_withdrawal: u32,
+    pub dst_cancellation: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Point {
+    pub delay: u16,
+    /// Rate bump reached at this point
+    pub coefficient: u32,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GasCostConfig {
+    pub gas_bump_estimate: u32,
+    pub gas_price_estimate: u32,
+}
+
+/// The order's Dutch auction, as a Fusion+ quote preset describes it
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Auction {
+    pub start_time: u64,
+    pub duration: u64,
+    pub initial_rate_bump: u32,
+    #[serde(default)]
+    pub points: Vec<Point>,
+    #[serde(default)]
+    pub gas_cost: GasCostConfig,
+}
+
+impl From<&Auction> for AuctionDetails {
+    fn from(auction: &Auction) -> Self {
+        AuctionDetails {
+            start_time: auction.start_time,
+            duration: auction.duration,
+            initial_rate_bump: auction.initial_rate_bump,
+            points: auction
+                .points
+                .iter()
+                .map(|point| AuctionPoint {
+                    rate_bump: point.coefficient,
+                    delay: point.delay,
+                })
+                .collect(),
+            gas_cost: GasCost {
+                gas_bump_estimate: auction.gas_cost.gas_bump_estimate,
+                gas_price_estimate: auction.gas_cost.gas_price_estimate,
+            },
+        }
+    }
+}
+
+/// An order open for resolvers, as listed by the API's active orders
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CrossChainOrder {
+    pub order_hash: Hash32,
+    pub quote_id: String,
+    pub src_chain_id: ChainId,
+    pub dst_chain_id: ChainId,
+    pub order: LimitOrder,
+    /// Hex signature of the order by the maker
+    pub signature: String,
+    /// Hex extension the order was signed with, passed verbatim to the fill
+    pub extension: String,
+    /// Last unix time the order may be filled at
+    pub deadline: u64,
+    pub auction: Auction,
+    /// Hash of the secret, or the Merkle root of `secret_hashes`, both escrows lock with
+    pub hashlock: Hash32,
+    /// Hashes of the secrets of a multi-fill order, in part order
+    #[serde(default)]
+    pub secret_hashes: Option<Vec<Hash32>>,
+    pub time_locks: TimeLocks,
+    #[serde(with = "amount")]
+    pub src_safety_deposit: u128,
+    #[serde(with = "amount")]
+    pub dst_safety_deposit: u128,
+    /// Token the maker receives on the destination chain
+    pub dst_token: String,
+    /// Account the maker receives it at
+    pub dst_receiver: String,
+    /// Set by the order book; makers leave it out when submitting
+    #[serde(default, with = "amount")]
+    pub remaining_maker_amount: u128,
+}
+
+/// Lifecycle of an order in the order book
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OrderStatus {
+    /// Open for resolvers, or being filled by the one that claimed it
+    Pending,
+    /// Both escrows were withdrawn
+    Executed,
+    /// Past its deadline without a fill
+    Expired,
+    /// Cancelled by its maker
+    Cancelled,
+    /// A fill was started and both escrows were refunded
+    Refunded,
+}
+
+/// A resolver's exclusive right to fill an order, until `expires_at`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Claim {
+    /// Address the resolver fills from
+    pub resolver: String,
+    pub expires_at: u64,
+}
+
+/// A fill of an order, as its resolver reported it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderFill {
+    pub resolver: String,
+    /// Source chain transaction that deployed the source escrow
+    pub tx_hash: String,
+    #[serde(with = "amount")]
+    pub filled_maker_amount: u128,
+}
+
+/// An order with its status, as the order status endpoint returns it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderInfo {
+    pub order_hash: Hash32,
+    pub status: OrderStatus,
+    pub order: CrossChainOrder,
+    pub claim: Option<Claim>,
+    pub fills: Vec<OrderFill>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+/// A resolver claiming an order
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClaimRequest {
+    pub resolver: String,
+}
+
+/// A resolver reporting the progress of the fill it claimed, tagged by `status`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum StatusReport {
+    /// The source escrow was deployed for `filled_maker_amount`
+    #[serde(rename_all = "camelCase")]
+    Filled {
+        resolver: String,
+        tx_hash: String,
+        #[serde(with = "amount")]
+        filled_maker_amount: u128,
+    },
+    /// Both escrows were withdrawn
+    Executed { resolver: String },
+    /// Both escrows were refunded
+    Refunded { resolver: String },
+}
+
+/// A maker sharing the secret of an order, or of part `idx` of a multi-fill order
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmitSecret {
+    pub order_hash: Hash32,
+    #[serde(default)]
+    pub idx: u32,
+    pub secret: Hash32,
+}
+
+/// Query of the quote endpoint
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuoteRequest {
+    pub src_chain: ChainId,
+    pub dst_chain: ChainId,
+    pub src_token_address: String,
+    pub dst_token_address: String,
+    #[serde(with = "amount")]
+    pub amount: u128,
+    pub wallet_address: String,
+}
+
+/// An auction a quote offers, from `auction_start_amount` down to `auction_end_amount`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Preset {
+    pub auction_duration: u64,
+    /// Seconds after the order's submission the auction starts
+    pub start_auction_in: u64,
+    pub initial_rate_bump: u32,
+    #[serde(with = "amount")]
+    pub auction_start_amount: u128,
+    #[serde(with = "amount")]
+    pub auction_end_amount: u128,
+    #[serde(default)]
+    pub points: Vec<Point>,
+    #[serde(default)]
+    pub gas_cost: GasCostConfig,
+    /// Secrets the maker generates: 1 for a single fill
+    pub secrets_count: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotePresets {
+    pub fast: Preset,
+    pub medium: Preset,
+    pub slow: Preset,
+}
+
+/// Price of a swap and the escrow parameters an order for it uses
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Quote {
+    pub quote_id: String,
+    #[serde(with = "amount")]
+    pub src_token_amount: u128,
+    /// Destination amount at the recommended preset's auction start
+    #[serde(with = "amount")]
+    pub dst_token_amount: u128,
+    pub presets: QuotePresets,
+    pub recommended_preset: String,
+    pub time_locks: TimeLocks,
+    #[serde(with = "amount")]
+    pub src_safety_deposit: u128,
+    #[serde(with = "amount")]
+    pub dst_safety_deposit: u128,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PageMeta {
+    pub total_items: u64,
+    pub items_per_page: u32,
+    pub total_pages: u32,
+    pub current_page: u32,
+}
+
+/// A page of a paginated listing
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub meta: PageMeta,
+    pub items: Vec<T>,
+}
+
+/// A secret the maker shared once both escrows of a fill were confirmed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SharedSecret {
+    /// Part of a multi-fill order the secret opens, 0 otherwise
+    pub idx: u32,
+    pub secret: Hash32,
+}
+
+/// The secrets shared for an order so far
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderSecrets {
+    pub order_hash: Hash32,
+    pub secrets: Vec<SharedSecret>,
+}
@@ -0,0 +1,51 @@
+//! Ed25519 keys of the Stellar accounts transactions are signed with
+
+use crate::{signer, Error};
+use ed25519_dalek::{Signer as _, SigningKey};
+use std::fmt;
+use stellar_xdr::{AccountId, DecoratedSignature, PublicKey, ScAddress, Uint256};
+
+/// A Stellar account's signing key, held in memory. The secret is zeroed on drop and never
+/// printed.
+pub struct Keypair {
+    key: SigningKey,
+}
+
+impl Keypair {
+    /// Key of an `S...` secret seed
+    pub fn from_secret(secret: &str) -> Result<Self, Error> {
+        let seed = stellar_strkey::ed25519::PrivateKey::from_string(secret.trim())
+            .map_err(|_| Error::InvalidArgument("not a Stellar secret seed".into()))?;
+        Ok(Keypair::from_seed(&seed.0))
+    }
+
+    pub fn from_seed(seed: &[u8; 32]) -> Self {
+        Keypair {
+            key: SigningKey::from_bytes(seed),
+        }
+    }
+
+    pub fn public_key(&self) -> [u8; 32] {
+        self.key.verifying_key().to_bytes()
+    }
+
+    pub fn account_id(&self) -> AccountId {
+        AccountId(PublicKey::PublicKeyTypeEd25519(Uint256(self.public_key())))
+    }
+
+    /// `G...` strkey of the account
+    pub fn address(&self) -> String {
+        ScAddress::Account(self.account_id()).to_string()
+    }
+
+    /// Signature of a transaction hash, tagged with the key's hint
+    pub fn sign_hash(&self, hash: &[u8; 32]) -> DecoratedSignature {
+        signer::decorated(&self.public_key(), self.key.sign(hash).to_bytes())
+    }
+}
+
+impl fmt::Debug for Keypair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Keypair").field(&self.address()).finish()
+    }
+}
@@ -0,0 +1,204 @@
+//! Password-encrypted key files
+//!
+//! A keystore holds one 32-byte key, a Stellar secret seed or any other such as an EVM
+//! private key, encrypted with AES-256-GCM under a key derived from a password with
+//! PBKDF2-HMAC-SHA256. The file is JSON, and records the account's address when the key
+//! is a Stellar seed, so that it can be told apart without the password.
+
+use crate::{keypair::Keypair, Error};
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    pbkdf2,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::Write,
+    num::NonZeroU32,
+    path::Path,
+};
+use zeroize::Zeroizing;
+
+/// Version of the keystore format
+pub const KEYSTORE_VERSION: u32 = 1;
+
+/// PBKDF2 iterations of new keystores
+pub const DEFAULT_ITERATIONS: u32 = 600_000;
+
+const KDF: &str = "pbkdf2-hmac-sha256";
+const CIPHER: &str = "aes-256-gcm";
+const SALT_LEN: usize = 16;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Keystore {
+    pub version: u32,
+    /// `G...` strkey of the account, when the key is a Stellar seed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    pub kdf: String,
+    pub iterations: u32,
+    /// Hex salt of the key derivation
+    pub salt: String,
+    pub cipher: String,
+    /// Hex nonce of the encryption
+    pub nonce: String,
+    /// Hex encrypted key followed by its authentication tag
+    pub ciphertext: String,
+}
+
+impl Keystore {
+    /// `key` encrypted under `password`, derived with `iterations` PBKDF2 iterations
+    pub fn encrypt(key: &[u8; 32], password: &str, iterations: u32) -> Result<Self, Error> {
+        if password.is_empty() {
+            return Err(Error::InvalidArgument("empty keystore password".into()));
+        }
+        let mut salt = [0; SALT_LEN];
+        let mut nonce = [0; NONCE_LEN];
+        getrandom::fill(&mut salt).map_err(|e| Error::Random(e.to_string()))?;
+        getrandom::fill(&mut nonce).map_err(|e| Error::Random(e.to_string()))?;
+        let mut ciphertext = key.to_vec();
+        cipher(password, &salt, iterations)?
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::empty(),
+                &mut ciphertext,
+            )
+            .map_err(|_| Error::Keystore("encryption failed".into()))?;
+        Ok(Keystore {
+            version: KEYSTORE_VERSION,
+            address: None,
+            kdf: KDF.into(),
+            iterations,
+            salt: hex::encode(salt),
+            cipher: CIPHER.into(),
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(ciphertext),
+        })
+    }
+
+    /// A fresh Stellar seed from the operating system's CSPRNG, encrypted under `password`
+    pub fn generate(password: &str, iterations: u32) -> Result<Self, Error> {
+        let mut seed = Zeroizing::new([0; 32]);
+        getrandom::fill(seed.as_mut()).map_err(|e| Error::Random(e.to_string()))?;
+        Keystore::encrypt_seed(&seed, password, iterations)
+    }
+
+    /// Stellar secret `seed` encrypted under `password`, recording its account's address
+    pub fn encrypt_seed(seed: &[u8; 32], password: &str, iterations: u32) -> Result<Self, Error> {
+        Ok(Keystore {
+            address: Some(Keypair::from_seed(seed).address()),
+            ..Keystore::encrypt(seed, password, iterations)?
+        })
+    }
+
+    /// The key, if `password` is right and the file untouched
+    pub fn decrypt(&self, password: &str) -> Result<Zeroizing<[u8; 32]>, Error> {
+        if self.version != KEYSTORE_VERSION || self.kdf != KDF || self.cipher != CIPHER {
+            return Err(Error::Keystore(format!(
+                "unsupported keystore version {} with {} and {}",
+                self.version, self.kdf, self.cipher
+            )));
+        }
+        let salt = hex::decode(&self.salt).map_err(|_| Error::Keystore("invalid salt".into()))?;
+        let nonce = hex::decode(&self.nonce)
+            .ok()
+            .and_then(|nonce| Nonce::try_assume_unique_for_key(&nonce).ok())
+            .ok_or_else(|| Error::Keystore("invalid nonce".into()))?;
+        let mut ciphertext = Zeroizing::new(
+            hex::decode(&self.ciphertext)
+                .map_err(|_| Error::Keystore("invalid ciphertext".into()))?,
+        );
+        let key = cipher(password, &salt, self.iterations)?
+            .open_in_place(nonce, Aad::empty(), &mut ciphertext)
+            .map_err(|_| Error::Keystore("wrong password or corrupted keystore".into()))?;
+        Ok(Zeroizing::new(key.try_into().map_err(|_| {
+            Error::Keystore("the key is not 32 bytes".into())
+        })?))
+    }
+
+    /// The Stellar key of the keystore, checked against its recorded address
+    pub fn unlock(&self, password: &str) -> Result<Keypair, Error> {
+        let keypair = Keypair::from_seed(&*self.decrypt(password)?);
+        match &self.address {
+            Some(address) if *address != keypair.address() => Err(Error::Keystore(format!(
+                "the key does not belong to {address}"
+            ))),
+            _ => Ok(keypair),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let json = fs::read(path)
+            .map_err(|e| Error::Keystore(format!("cannot read {}: {e}", path.display())))?;
+        serde_json::from_slice(&json)
+            .map_err(|e| Error::Keystore(format!("invalid keystore {}: {e}", path.display())))
+    }
+
+    /// Writes the keystore to a new file only its owner can read; an existing file is
+    /// never overwritten
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let json = serde_json::to_vec_pretty(self).expect("keystores serialize");
+        options
+            .open(path)
+            .and_then(|mut file| {
+                file.write_all(&json)?;
+                file.sync_all()
+            })
+            .map_err(|e| Error::Keystore(format!("cannot write {}: {e}", path.display())))
+    }
+}
+
+/// Password of the keystore named by the environment variable `var`: the contents of the
+/// file `{var}_PASSWORD_FILE`, less a trailing newline, or else `{var}_PASSWORD`
+pub fn password_from_env(var: &str) -> Result<Zeroizing<String>, Error> {
+    if let Some(path) = env::var_os(format!("{var}_PASSWORD_FILE")) {
+        return read_password(Path::new(&path));
+    }
+    env::var(format!("{var}_PASSWORD"))
+        .map(Zeroizing::new)
+        .map_err(|_| {
+            Error::InvalidArgument(format!("{var}_PASSWORD_FILE or {var}_PASSWORD is not set"))
+        })
+}
+
+/// Password in the file `path`, less a trailing newline
+pub fn read_password(path: &Path) -> Result<Zeroizing<String>, Error> {
+    let mut password = Zeroizing::new(fs::read_to_string(path).map_err(|e| {
+        Error::Keystore(format!("cannot read password file {}: {e}", path.display()))
+    })?);
+    let len = password.trim_end_matches(['\r', '\n']).len();
+    password.truncate(len);
+    Ok(password)
+}
+
+/// Key of the keystore the environment variable `var` names, unlocked with the password
+/// `password_from_env` reads; `None` if `var` is unset
+pub fn key_from_env(var: &str) -> Result<Option<Zeroizing<[u8; 32]>>, Error> {
+    let Some(path) = env::var_os(var) else {
+        return Ok(None);
+    };
+    let password = password_from_env(var)?;
+    Keystore::load(Path::new(&path))?
+        .decrypt(&password)
+        .map(Some)
+}
+
+fn cipher(password: &str, salt: &[u8], iterations: u32) -> Result<LessSafeKey, Error> {
+    let iterations = NonZeroU32::new(iterations)
+        .ok_or_else(|| Error::Keystore("zero PBKDF2 iterations".into()))?;
+    let mut key = Zeroizing::new([0; 32]);
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        iterations,
+        salt,
+        password.as_bytes(),
+        key.as_mut(),
+    );
+    let key = UnboundKey::new(&AES_256_GCM, key.as_ref()).expect("32-byte AES key");
+    Ok(LessSafeKey::new(key))
+}
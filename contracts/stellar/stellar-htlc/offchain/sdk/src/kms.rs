@@ -0,0 +1,359 @@
+//! Signing with Ed25519 keys kept in AWS KMS
+//!
+//! The key never leaves KMS: every transaction hash is sent to its `Sign` action over the
+//! KMS JSON API, authenticated with Signature Version 4. The key must have the
+//! `ECC_NIST_EDWARDS25519` spec and `SIGN_VERIFY` usage, and signs with `ED25519_SHA_512`,
+//! the plain Ed25519 Stellar verifies. Signatures are checked against the key's public key
+//! before use.
+
+use crate::{
+    signer::{self, Signer},
+    transaction::unix_now,
+    Error,
+};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ed25519_dalek::{Signature, VerifyingKey};
+use hmac::{Hmac, Mac};
+use serde::{de::DeserializeOwned, Deserialize};
+use sha2::{Digest, Sha256};
+use std::{env, fmt};
+use stellar_xdr::DecoratedSignature;
+use zeroize::Zeroizing;
+
+const CONTENT_TYPE: &str = "application/x-amz-json-1.1";
+
+/// DER prefix of an Ed25519 `SubjectPublicKeyInfo`, followed by the 32-byte key
+const ED25519_SPKI_PREFIX: [u8; 12] = [
+    0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00,
+];
+
+/// Credentials of the AWS identity calling KMS. The secret is zeroed on drop and never
+/// printed.
+#[derive(Clone)]
+pub struct AwsCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: Zeroizing<String>,
+    /// Token of temporary credentials
+    pub session_token: Option<String>,
+}
+
+impl AwsCredentials {
+    /// Credentials of `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and, for temporary ones,
+    /// `AWS_SESSION_TOKEN`
+    pub fn from_env() -> Result<Self, Error> {
+        let var = |name: &str| {
+            env::var(name).map_err(|_| Error::InvalidArgument(format!("{name} is not set")))
+        };
+        Ok(AwsCredentials {
+            access_key_id: var("AWS_ACCESS_KEY_ID")?,
+            secret_access_key: Zeroizing::new(var("AWS_SECRET_ACCESS_KEY")?),
+            session_token: env::var("AWS_SESSION_TOKEN").ok(),
+        })
+    }
+}
+
+impl fmt::Debug for AwsCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AwsCredentials")
+            .field(&self.access_key_id)
+            .finish()
+    }
+}
+
+/// A signer whose key is kept in AWS KMS
+pub struct KmsSigner {
+    client: KmsClient,
+    key_id: String,
+    verifying_key: VerifyingKey,
+}
+
+struct KmsClient {
+    http: reqwest::Client,
+    endpoint: String,
+    host: String,
+    region: String,
+    credentials: AwsCredentials,
+}
+
+impl KmsSigner {
+    /// Signer of the KMS key `key_id`, called in the region `AWS_REGION` or
+    /// `AWS_DEFAULT_REGION` with the credentials `AwsCredentials::from_env` reads. The
+    /// endpoint can be overridden with `AWS_ENDPOINT_URL_KMS` or `AWS_ENDPOINT_URL`.
+    pub async fn from_env(key_id: &str) -> Result<Self, Error> {
+        let region = env::var("AWS_REGION")
+            .or_else(|_| env::var("AWS_DEFAULT_REGION"))
+            .map_err(|_| Error::InvalidArgument("AWS_REGION is not set".into()))?;
+        let endpoint = env::var("AWS_ENDPOINT_URL_KMS")
+            .or_else(|_| env::var("AWS_ENDPOINT_URL"))
+            .ok();
+        KmsSigner::connect(
+            key_id,
+            &region,
+            endpoint.as_deref(),
+            AwsCredentials::from_env()?,
+        )
+        .await
+    }
+
+    /// Signer of the KMS key `key_id` in `region`, at the regional endpoint unless
+    /// `endpoint` is given. The key's public key is fetched here, which also checks access.
+    pub async fn connect(
+        key_id: &str,
+        region: &str,
+        endpoint: Option<&str>,
+        credentials: AwsCredentials,
+    ) -> Result<Self, Error> {
+        let endpoint = endpoint
+            .map(str::to_owned)
+            .unwrap_or_else(|| format!("https://kms.{region}.amazonaws.com/"));
+        let url = reqwest::Url::parse(&endpoint)
+            .map_err(|_| Error::InvalidArgument(format!("invalid KMS endpoint {endpoint}")))?;
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{host}:{port}"),
+            (Some(host), None) => host.to_owned(),
+            (None, _) => {
+                return Err(Error::InvalidArgument(format!(
+                    "invalid KMS endpoint {endpoint}"
+                )))
+            }
+        };
+        let client = KmsClient {
+            http: reqwest::Client::new(),
+            endpoint,
+            host,
+            region: region.into(),
+            credentials,
+        };
+        let response: GetPublicKeyResponse = client
+            .call("GetPublicKey", serde_json::json!({ "KeyId": key_id }))
+            .await?;
+        if response.key_spec != "ECC_NIST_EDWARDS25519" {
+            return Err(Error::Kms(format!(
+                "key {key_id} is {}, not an Ed25519 key",
+                response.key_spec
+            )));
+        }
+        let verifying_key = ed25519_public_key(&decode(&response.public_key)?)
+            .and_then(|key| VerifyingKey::from_bytes(&key).ok())
+            .ok_or_else(|| Error::Kms(format!("invalid public key of {key_id}")))?;
+        Ok(KmsSigner {
+            client,
+            key_id: key_id.into(),
+            verifying_key,
+        })
+    }
+
+    pub fn key_id(&self) -> &str {
+        &self.key_id
+    }
+}
+
+impl KmsClient {
+    /// Response of the KMS action `action` to `body`
+    async fn call<T: DeserializeOwned>(
+        &self,
+        action: &str,
+        body: serde_json::Value,
+    ) -> Result<T, Error> {
+        let body = serde_json::to_vec(&body).expect("JSON values serialize");
+        let amz_date = amz_date(unix_now());
+        let target = format!("TrentService.{action}");
+        let mut request = self
+            .http
+            .post(&self.endpoint)
+            .header("content-type", CONTENT_TYPE)
+            .header("x-amz-date", &amz_date)
+            .header("x-amz-target", &target)
+            .header(
+                "authorization",
+                authorization(
+                    &self.credentials,
+                    &self.region,
+                    &self.host,
+                    &amz_date,
+                    &target,
+                    &body,
+                ),
+            );
+        if let Some(token) = &self.credentials.session_token {
+            request = request.header("x-amz-security-token", token);
+        }
+        let response = request
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| Error::Kms(e.to_string()))?;
+        let status = response.status();
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| Error::Kms(e.to_string()))?;
+        if !status.is_success() {
+            let error: KmsError = serde_json::from_slice(&body).unwrap_or_default();
+            // Error types may come qualified as `namespace#Type`
+            let kind = error.kind.rsplit('#').next().unwrap_or_default();
+            return Err(Error::Kms(format!(
+                "{action} failed with {status} {kind}: {}",
+                error.message.unwrap_or_default()
+            )));
+        }
+        serde_json::from_slice(&body)
+            .map_err(|e| Error::Kms(format!("invalid {action} response: {e}")))
+    }
+}
+
+#[async_trait]
+impl Signer for KmsSigner {
+    fn public_key(&self) -> [u8; 32] {
+        self.verifying_key.to_bytes()
+    }
+
+    async fn sign_hash(&self, hash: &[u8; 32]) -> Result<DecoratedSignature, Error> {
+        let response: SignResponse = self
+            .client
+            .call(
+                "Sign",
+                serde_json::json!({
+                    "KeyId": self.key_id,
+                    "Message": STANDARD.encode(hash),
+                    "MessageType": "RAW",
+                    "SigningAlgorithm": "ED25519_SHA_512",
+                }),
+            )
+            .await?;
+        let signature: [u8; 64] = decode(&response.signature)?
+            .try_into()
+            .map_err(|_| Error::Kms("signature is not 64 bytes".into()))?;
+        self.verifying_key
+            .verify_strict(hash, &Signature::from_bytes(&signature))
+            .map_err(|_| Error::Kms(format!("{} returned an invalid signature", self.key_id)))?;
+        Ok(signer::decorated(&self.public_key(), signature))
+    }
+}
+
+impl fmt::Debug for KmsSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KmsSigner")
+            .field("key_id", &self.key_id)
+            .field("address", &self.address())
+            .finish()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct GetPublicKeyResponse {
+    public_key: String,
+    key_spec: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SignResponse {
+    signature: String,
+}
+
+#[derive(Default, Deserialize)]
+struct KmsError {
+    #[serde(rename = "__type", default)]
+    kind: String,
+    #[serde(alias = "Message")]
+    message: Option<String>,
+}
+
+fn decode(base64: &str) -> Result<Vec<u8>, Error> {
+    STANDARD
+        .decode(base64)
+        .map_err(|_| Error::Kms("invalid base64 in response".into()))
+}
+
+/// The key of a DER-encoded Ed25519 `SubjectPublicKeyInfo`
+pub(crate) fn ed25519_public_key(der: &[u8]) -> Option<[u8; 32]> {
+    der.strip_prefix(&ED25519_SPKI_PREFIX)?.try_into().ok()
+}
+
+/// `X-Amz-Date` of unix time `unix`, as `YYYYMMDDTHHMMSSZ`
+pub(crate) fn amz_date(unix: u64) -> String {
+    // Civil date of a day count, after Howard Hinnant's `civil_from_days`
+    let days = (unix / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    let secs = unix % 86_400;
+    format!(
+        "{year:04}{month:02}{day:02}T{:02}{:02}{:02}Z",
+        secs / 3_600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// SigV4 `Authorization` header of a KMS `POST /` to `host` calling `target` with `body`
+pub(crate) fn authorization(
+    credentials: &AwsCredentials,
+    region: &str,
+    host: &str,
+    amz_date: &str,
+    target: &str,
+    body: &[u8],
+) -> String {
+    let mut headers = vec![
+        ("content-type", CONTENT_TYPE),
+        ("host", host),
+        ("x-amz-date", amz_date),
+    ];
+    if let Some(token) = &credentials.session_token {
+        headers.push(("x-amz-security-token", token));
+    }
+    headers.push(("x-amz-target", target));
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{name}:{}\n", value.trim()))
+        .collect();
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_request = format!(
+        "POST\n/\n\n{canonical_headers}\n{signed_headers}\n{}",
+        hex::encode(Sha256::digest(body))
+    );
+    let date = &amz_date[..8];
+    let scope = format!("{date}/{region}/kms/aws4_request");
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+        hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+    let key = signing_key(&credentials.secret_access_key, date, region, "kms");
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={}",
+        credentials.access_key_id,
+        hex::encode(hmac(&key, string_to_sign.as_bytes()))
+    )
+}
+
+/// SigV4 signing key of `secret` for `date`, `region` and `service`
+pub(crate) fn signing_key(secret: &str, date: &str, region: &str, service: &str) -> [u8; 32] {
+    let key = hmac(format!("AWS4{secret}").as_bytes(), date.as_bytes());
+    let key = hmac(&key, region.as_bytes());
+    let key = hmac(&key, service.as_bytes());
+    hmac(&key, b"aws4_request")
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes any key length");
+    mac.update(data);
+    mac.finalize().into_bytes().into()
+}
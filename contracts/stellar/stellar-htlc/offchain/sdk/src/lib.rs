@@ -0,0 +1,35 @@
+//! Rust SDK for the Stellar HTLC contract.
+//!
+//! `HtlcClient` creates, withdraws and refunds HTLCs by building, simulating, signing and
+//! submitting Soroban transactions over JSON-RPC, and reads them back with `get_htlc`.
+//! Contract failures surface as `Error::Contract` with the contract's error code. The
+//! `events` module decodes the contract's lifecycle events from `getEvents`, and `secret`
+//! generates secrets and derives hashlocks and partial-fill Merkle roots from them.
+//! `fusion` holds the Fusion+ order schemas resolvers and order books exchange.
+//! Transactions are signed by any `Signer`: an in-memory `Keypair`, a password-encrypted
+//! `Keystore` file once unlocked, or a key kept in AWS KMS.
+
+pub mod client;
+pub mod error;
+pub mod events;
+pub mod fusion;
+pub mod keypair;
+pub mod keystore;
+pub mod kms;
+pub mod rpc;
+mod scval;
+pub mod secret;
+pub mod signer;
+pub mod testutils;
+pub mod transaction;
+pub mod types;
+
+pub use client::{HtlcClient, Receipt};
+pub use error::{ContractError, Error};
+pub use keypair::Keypair;
+pub use keystore::Keystore;
+pub use secret::{MerkleTree, Secret};
+pub use signer::{Signer, SignerConfig};
+pub use types::{CreateHtlc, Hash32, HashAlgo, Htlc, HtlcStatus};
+
+mod test;
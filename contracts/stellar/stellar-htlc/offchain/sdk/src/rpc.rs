@@ -0,0 +1,242 @@
+//! Minimal Soroban JSON-RPC client covering the methods the SDK and its users need
+
+use crate::Error;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// Events returned per `getEvents` page
+pub const PAGE_LIMIT: u32 = 200;
+
+pub struct RpcClient {
+    http: reqwest::Client,
+    url: String,
+}
+
+#[derive(Serialize)]
+struct Request<'a, P> {
+    jsonrpc: &'static str,
+    id: u64,
+    method: &'a str,
+    params: P,
+}
+
+#[derive(Deserialize)]
+struct Response<R> {
+    result: Option<R>,
+    error: Option<ResponseError>,
+}
+
+#[derive(Deserialize)]
+struct ResponseError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LatestLedger {
+    pub sequence: u32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Network {
+    pub passphrase: String,
+}
+
+/// A ledger entry as returned by `getLedgerEntries`, with base64 XDR `LedgerEntryData`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LedgerEntry {
+    pub key: String,
+    pub xdr: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetLedgerEntriesResponse {
+    #[serde(default)]
+    pub entries: Vec<LedgerEntry>,
+    pub latest_ledger: u32,
+}
+
+/// Outcome of `simulateTransaction`; `error` is set instead of the footprint when the
+/// invocation fails
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulateTransactionResponse {
+    pub error: Option<String>,
+    /// Base64 XDR `SorobanTransactionData`
+    pub transaction_data: Option<String>,
+    pub min_resource_fee: Option<String>,
+    #[serde(default)]
+    pub results: Vec<SimulationResult>,
+    pub latest_ledger: u32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulationResult {
+    /// Base64 XDR `SorobanAuthorizationEntry` values the invocation needs
+    #[serde(default)]
+    pub auth: Vec<String>,
+    pub xdr: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SendTransactionResponse {
+    /// `PENDING`, `DUPLICATE`, `TRY_AGAIN_LATER` or `ERROR`
+    pub status: String,
+    pub hash: String,
+    /// Base64 XDR `TransactionResult` when rejected
+    pub error_result_xdr: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetTransactionResponse {
+    /// `SUCCESS`, `FAILED` or `NOT_FOUND`
+    pub status: String,
+    pub ledger: Option<u32>,
+    pub latest_ledger: u32,
+    /// Base64 XDR `TransactionMeta` of an included transaction
+    pub result_meta_xdr: Option<String>,
+    /// Base64 XDR `ScVal` the invocation returned, reported by newer RPC versions
+    pub return_value: Option<String>,
+}
+
+/// Parameters of `getEvents`. With a cursor, `start_ledger` must be left out.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetEventsRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_ledger: Option<u32>,
+    pub filters: Vec<EventFilter>,
+    pub pagination: Pagination,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventFilter {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub contract_ids: Vec<String>,
+    /// Alternatives of base64 XDR topic segments, `*` matching any one segment
+    pub topics: Vec<Vec<String>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Pagination {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    pub limit: u32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetEventsResponse {
+    pub events: Vec<EventInfo>,
+    /// Where the next page starts; past the last event returned, or the end of the
+    /// scanned range when there were none
+    pub cursor: String,
+    pub latest_ledger: u32,
+}
+
+/// A contract event as returned by `getEvents`, with base64 XDR topics and value
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventInfo {
+    pub id: String,
+    pub ledger: u32,
+    pub ledger_closed_at: String,
+    pub contract_id: String,
+    pub tx_hash: String,
+    pub topic: Vec<String>,
+    pub value: String,
+}
+
+impl RpcClient {
+    pub fn new(url: impl Into<String>) -> Self {
+        RpcClient {
+            http: reqwest::Client::new(),
+            url: url.into(),
+        }
+    }
+
+    pub async fn get_latest_ledger(&self) -> Result<LatestLedger, Error> {
+        self.call("getLatestLedger", serde_json::json!({})).await
+    }
+
+    pub async fn get_events(&self, request: &GetEventsRequest) -> Result<GetEventsResponse, Error> {
+        self.call("getEvents", request).await
+    }
+
+    pub async fn get_network(&self) -> Result<Network, Error> {
+        self.call("getNetwork", serde_json::json!({})).await
+    }
+
+    /// Entries for base64 XDR `LedgerKey`s; keys without an entry are left out
+    pub async fn get_ledger_entries(
+        &self,
+        keys: &[String],
+    ) -> Result<GetLedgerEntriesResponse, Error> {
+        self.call("getLedgerEntries", serde_json::json!({ "keys": keys }))
+            .await
+    }
+
+    pub async fn simulate_transaction(
+        &self,
+        envelope: &str,
+    ) -> Result<SimulateTransactionResponse, Error> {
+        self.call(
+            "simulateTransaction",
+            serde_json::json!({ "transaction": envelope }),
+        )
+        .await
+    }
+
+    pub async fn send_transaction(&self, envelope: &str) -> Result<SendTransactionResponse, Error> {
+        self.call(
+            "sendTransaction",
+            serde_json::json!({ "transaction": envelope }),
+        )
+        .await
+    }
+
+    pub async fn get_transaction(&self, hash: &str) -> Result<GetTransactionResponse, Error> {
+        self.call("getTransaction", serde_json::json!({ "hash": hash }))
+            .await
+    }
+
+    async fn call<P: Serialize, R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: P,
+    ) -> Result<R, Error> {
+        let response: Response<R> = self
+            .http
+            .post(&self.url)
+            .json(&Request {
+                jsonrpc: "2.0",
+                id: 1,
+                method,
+                params,
+            })
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        match (response.result, response.error) {
+            (Some(result), None) => Ok(result),
+            (_, Some(error)) => Err(Error::Rpc {
+                code: error.code,
+                message: error.message,
+            }),
+            (None, None) => Err(Error::Rpc {
+                code: 0,
+                message: format!("{method} returned neither a result nor an error"),
+            }),
+        }
+    }
+}
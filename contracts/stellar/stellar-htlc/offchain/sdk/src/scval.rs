@@ -0,0 +1,146 @@
+//! Conversions between `ScVal`s and the contract's types
+
+use crate::{
+    types::{CrossChainMetadata, DecodeError, Hash32, HashAlgo},
+    Error,
+};
+use stellar_xdr::{Limits, ReadXdr, ScAddress, ScBytes, ScMap, ScMapEntry, ScSymbol, ScVal, ScVec};
+
+pub(crate) fn from_base64(xdr: &str) -> Result<ScVal, DecodeError> {
+    ScVal::from_xdr_base64(xdr, Limits::none()).map_err(|e| DecodeError::Xdr(e.to_string()))
+}
+
+pub(crate) fn field<'a>(map: &'a ScMap, name: &'static str) -> Result<&'a ScVal, DecodeError> {
+    map.iter()
+        .find(|entry| matches!(&entry.key, ScVal::Symbol(key) if key.to_string() == name))
+        .map(|entry| &entry.val)
+        .ok_or(DecodeError::MissingField(name))
+}
+
+pub(crate) fn map_of<'a>(value: &'a ScVal, name: &'static str) -> Result<&'a ScMap, DecodeError> {
+    match value {
+        ScVal::Map(Some(map)) => Ok(map),
+        _ => Err(DecodeError::UnexpectedValue(name)),
+    }
+}
+
+pub(crate) fn vec_of<const N: usize>(
+    value: &ScVal,
+    name: &'static str,
+) -> Result<[ScVal; N], DecodeError> {
+    match value {
+        ScVal::Vec(Some(items)) => items
+            .to_vec()
+            .try_into()
+            .map_err(|_| DecodeError::UnexpectedValue(name)),
+        _ => Err(DecodeError::UnexpectedValue(name)),
+    }
+}
+
+pub(crate) fn address(value: &ScVal, name: &'static str) -> Result<String, DecodeError> {
+    match value {
+        ScVal::Address(address) => Ok(address.to_string()),
+        _ => Err(DecodeError::UnexpectedValue(name)),
+    }
+}
+
+pub(crate) fn bytes(value: &ScVal, name: &'static str) -> Result<Vec<u8>, DecodeError> {
+    match value {
+        ScVal::Bytes(bytes) => Ok(bytes.to_vec()),
+        _ => Err(DecodeError::UnexpectedValue(name)),
+    }
+}
+
+pub(crate) fn hash(value: &ScVal, name: &'static str) -> Result<Hash32, DecodeError> {
+    bytes(value, name)?
+        .try_into()
+        .map(Hash32)
+        .map_err(|_| DecodeError::UnexpectedValue(name))
+}
+
+pub(crate) fn hashes(value: &ScVal, name: &'static str) -> Result<Vec<Hash32>, DecodeError> {
+    match value {
+        ScVal::Vec(Some(items)) => items.iter().map(|item| hash(item, name)).collect(),
+        _ => Err(DecodeError::UnexpectedValue(name)),
+    }
+}
+
+pub(crate) fn hash_algo(value: &ScVal) -> Result<HashAlgo, DecodeError> {
+    let [variant] = vec_of::<1>(value, "hash_algo")?;
+    match &variant {
+        ScVal::Symbol(symbol) if symbol.to_string() == "Sha256" => Ok(HashAlgo::Sha256),
+        ScVal::Symbol(symbol) if symbol.to_string() == "Keccak256" => Ok(HashAlgo::Keccak256),
+        _ => Err(DecodeError::UnexpectedValue("hash_algo")),
+    }
+}
+
+pub(crate) fn u32_of(value: &ScVal, name: &'static str) -> Result<u32, DecodeError> {
+    u32::try_from(value.clone()).map_err(|_| DecodeError::UnexpectedValue(name))
+}
+
+pub(crate) fn u64_of(value: &ScVal, name: &'static str) -> Result<u64, DecodeError> {
+    u64::try_from(value.clone()).map_err(|_| DecodeError::UnexpectedValue(name))
+}
+
+pub(crate) fn i128_of(value: &ScVal, name: &'static str) -> Result<i128, DecodeError> {
+    i128::try_from(value.clone()).map_err(|_| DecodeError::UnexpectedValue(name))
+}
+
+/// Name of a unit enum variant, which the SDK encodes as a one-symbol vector
+pub(crate) fn unit_variant(value: &ScVal, name: &'static str) -> Result<String, DecodeError> {
+    match vec_of::<1>(value, name)? {
+        [ScVal::Symbol(symbol)] => Ok(symbol.to_string()),
+        _ => Err(DecodeError::UnexpectedValue(name)),
+    }
+}
+
+pub(crate) fn metadata(value: &ScVal) -> Result<CrossChainMetadata, DecodeError> {
+    let metadata = map_of(value, "metadata")?;
+    Ok(CrossChainMetadata {
+        evm_chain_id: u64_of(field(metadata, "evm_chain_id")?, "evm_chain_id")?,
+        evm_order_hash: hash(field(metadata, "evm_order_hash")?, "evm_order_hash")?,
+        maker: hex::encode(bytes(field(metadata, "maker")?, "maker")?),
+    })
+}
+
+pub(crate) fn symbol_val(name: &str) -> ScVal {
+    ScVal::Symbol(ScSymbol(name.try_into().expect("names are short")))
+}
+
+pub(crate) fn bytes_val(bytes: &[u8]) -> ScVal {
+    ScVal::Bytes(ScBytes(
+        bytes.to_vec().try_into().expect("bounded by the caller"),
+    ))
+}
+
+pub(crate) fn hash_val(hash: Hash32) -> ScVal {
+    bytes_val(&hash.0)
+}
+
+pub(crate) fn address_val(strkey: &str) -> Result<ScVal, Error> {
+    strkey
+        .parse::<ScAddress>()
+        .map(ScVal::Address)
+        .map_err(|_| Error::InvalidArgument(format!("invalid address {strkey}")))
+}
+
+pub(crate) fn vec_val(items: Vec<ScVal>) -> ScVal {
+    ScVal::Vec(Some(ScVec(
+        items.try_into().expect("bounded by the caller"),
+    )))
+}
+
+/// A contracttype struct; fields are sorted by name as the SDK encodes them
+pub(crate) fn struct_val(mut fields: Vec<(&str, ScVal)>) -> ScVal {
+    fields.sort_by(|a, b| a.0.cmp(b.0));
+    let entries: Vec<ScMapEntry> = fields
+        .into_iter()
+        .map(|(key, val)| ScMapEntry {
+            key: symbol_val(key),
+            val,
+        })
+        .collect();
+    ScVal::Map(Some(ScMap(
+        entries.try_into().expect("bounded by the caller"),
+    )))
+}
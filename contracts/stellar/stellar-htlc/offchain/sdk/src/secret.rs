@@ -0,0 +1,171 @@
+//! Swap secrets, their hashlocks, and the Merkle trees committing to partial-fill secrets
+//!
+//! Hashing mirrors the contract: a hashlock is `H(secret)`, and a partial-fill HTLC with
+//! `parts` parts commits to `parts + 1` secrets through a Merkle root over the leaves
+//! `H(index as u64 big-endian || H(secret))`. Inner nodes hash their children as a sorted
+//! pair, and the last node of an odd level is carried up unchanged.
+
+use crate::{
+    types::{Hash32, HashAlgo},
+    Error,
+};
+use sha2::{Digest, Sha256};
+use sha3::Keccak256;
+use std::fmt;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+/// `H(data)` with the HTLC hash algorithm `algo`
+pub fn hash(algo: HashAlgo, data: &[u8]) -> Hash32 {
+    match algo {
+        HashAlgo::Sha256 => Hash32(Sha256::digest(data).into()),
+        HashAlgo::Keccak256 => Hash32(Keccak256::digest(data).into()),
+    }
+}
+
+/// A 32-byte swap secret. It is zeroed on drop and never printed by `Debug`.
+#[derive(Clone, PartialEq, Eq, Zeroize, ZeroizeOnDrop)]
+pub struct Secret([u8; 32]);
+
+impl Secret {
+    /// A fresh secret from the operating system's CSPRNG
+    pub fn generate() -> Result<Self, Error> {
+        let mut secret = Secret([0; 32]);
+        getrandom::fill(&mut secret.0).map_err(|e| Error::Random(e.to_string()))?;
+        Ok(secret)
+    }
+
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Secret(bytes)
+    }
+
+    /// Secret of 64 hex digits, with or without `0x`
+    pub fn from_hex(hex: &str) -> Result<Self, Error> {
+        let mut secret = Secret([0; 32]);
+        hex::decode_to_slice(hex.trim().trim_start_matches("0x"), &mut secret.0)
+            .map_err(|_| Error::InvalidArgument("a secret is 32 hex-encoded bytes".into()))?;
+        Ok(secret)
+    }
+
+    pub fn expose(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Hex form, zeroed on drop like the secret itself
+    pub fn to_hex(&self) -> Zeroizing<String> {
+        Zeroizing::new(hex::encode(self.0))
+    }
+
+    /// The secret as the preimage `withdraw` reveals. Unlike `Secret`, the copy is not
+    /// zeroed on drop.
+    pub fn preimage(&self) -> Hash32 {
+        Hash32(self.0)
+    }
+
+    pub fn hashlock(&self, algo: HashAlgo) -> Hash32 {
+        hash(algo, &self.0)
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret([REDACTED])")
+    }
+}
+
+/// Merkle tree over the secrets of a partial-fill HTLC, secret `i` opening part `i`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerkleTree {
+    algo: HashAlgo,
+    /// Leaves first, root last
+    levels: Vec<Vec<Hash32>>,
+}
+
+impl MerkleTree {
+    /// Tree over `secrets`, of which there are `parts + 1`
+    pub fn new(algo: HashAlgo, secrets: &[Secret]) -> Result<Self, Error> {
+        if secrets.len() < 2 {
+            return Err(Error::InvalidArgument(
+                "a partial fill needs at least two secrets".into(),
+            ));
+        }
+        let leaves = secrets
+            .iter()
+            .enumerate()
+            .map(|(index, secret)| MerkleTree::leaf(algo, index as u32, &secret.hashlock(algo)))
+            .collect();
+        let mut levels: Vec<Vec<Hash32>> = vec![leaves];
+        while let Some(level) = levels.last().filter(|level| level.len() > 1) {
+            let next = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => hash_pair(algo, left, right),
+                    [single] => *single,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+        Ok(MerkleTree { algo, levels })
+    }
+
+    /// Leaf committing to the secret hash of part `index`
+    pub fn leaf(algo: HashAlgo, index: u32, secret_hash: &Hash32) -> Hash32 {
+        let mut packed = [0; 40];
+        packed[..8].copy_from_slice(&(index as u64).to_be_bytes());
+        packed[8..].copy_from_slice(&secret_hash.0);
+        hash(algo, &packed)
+    }
+
+    /// The hashlock a partial-fill HTLC over these secrets is created with
+    pub fn root(&self) -> Hash32 {
+        self.levels[self.levels.len() - 1][0]
+    }
+
+    /// The HTLC's `parts`, one less than the number of secrets
+    pub fn parts(&self) -> u32 {
+        self.levels[0].len() as u32 - 1
+    }
+
+    /// Proof `withdraw_partial` takes with the secret of part `index`
+    pub fn proof(&self, index: u32) -> Option<Vec<Hash32>> {
+        let mut position = index as usize;
+        if position >= self.levels[0].len() {
+            return None;
+        }
+        let mut proof = Vec::new();
+        for level in &self.levels[..self.levels.len() - 1] {
+            if let Some(sibling) = level.get(position ^ 1) {
+                proof.push(*sibling);
+            }
+            position /= 2;
+        }
+        Some(proof)
+    }
+
+    /// Checks a proof the way the contract does
+    pub fn verify(
+        algo: HashAlgo,
+        root: &Hash32,
+        index: u32,
+        secret: &Secret,
+        proof: &[Hash32],
+    ) -> bool {
+        let leaf = MerkleTree::leaf(algo, index, &secret.hashlock(algo));
+        let computed = proof
+            .iter()
+            .fold(leaf, |computed, node| hash_pair(algo, &computed, node));
+        computed == *root
+    }
+
+    pub fn algo(&self) -> HashAlgo {
+        self.algo
+    }
+}
+
+fn hash_pair(algo: HashAlgo, a: &Hash32, b: &Hash32) -> Hash32 {
+    let (first, second) = if a <= b { (a, b) } else { (b, a) };
+    let mut packed = [0; 64];
+    packed[..32].copy_from_slice(&first.0);
+    packed[32..].copy_from_slice(&second.0);
+    hash(algo, &packed)
+}
@@ -0,0 +1,125 @@
+//! Signers of Stellar transactions, wherever their key is kept
+//!
+//! `Signer` abstracts over a key: a `Keypair` holds it in memory, a `Keystore` file holds
+//! it encrypted under a password until unlocked into a `Keypair`, and a `KmsSigner`
+//! leaves it in AWS KMS and asks KMS for every signature. Services pick one from their
+//! environment with `SignerConfig`, so production keys need not sit in config files.
+
+use crate::{
+    keypair::Keypair,
+    keystore::{self, Keystore},
+    kms::KmsSigner,
+    Error,
+};
+use async_trait::async_trait;
+use std::{env, path::PathBuf};
+use stellar_xdr::{
+    AccountId, DecoratedSignature, PublicKey, ScAddress, Signature, SignatureHint, Uint256,
+};
+use zeroize::Zeroizing;
+
+/// Environment variable holding an `S...` secret seed
+pub const SECRET_KEY_VAR: &str = "STELLAR_SECRET_KEY";
+
+/// Environment variable naming a keystore file
+pub const KEYSTORE_VAR: &str = "STELLAR_KEYSTORE";
+
+/// Environment variable holding the ID or ARN of an AWS KMS key
+pub const KMS_KEY_ID_VAR: &str = "STELLAR_KMS_KEY_ID";
+
+/// The key of a Stellar account, able to sign transaction hashes
+#[async_trait]
+pub trait Signer: Send + Sync {
+    /// Ed25519 public key of the account
+    fn public_key(&self) -> [u8; 32];
+
+    /// Signature of a transaction hash, tagged with the key's hint
+    async fn sign_hash(&self, hash: &[u8; 32]) -> Result<DecoratedSignature, Error>;
+
+    fn account_id(&self) -> AccountId {
+        AccountId(PublicKey::PublicKeyTypeEd25519(Uint256(self.public_key())))
+    }
+
+    /// `G...` strkey of the account
+    fn address(&self) -> String {
+        ScAddress::Account(self.account_id()).to_string()
+    }
+}
+
+#[async_trait]
+impl Signer for Keypair {
+    fn public_key(&self) -> [u8; 32] {
+        Keypair::public_key(self)
+    }
+
+    async fn sign_hash(&self, hash: &[u8; 32]) -> Result<DecoratedSignature, Error> {
+        Ok(Keypair::sign_hash(self, hash))
+    }
+}
+
+/// `signature` by the key `public_key`, tagged with its last four bytes as the hint
+pub(crate) fn decorated(public_key: &[u8; 32], signature: [u8; 64]) -> DecoratedSignature {
+    DecoratedSignature {
+        hint: SignatureHint(public_key[28..].try_into().expect("four bytes")),
+        signature: Signature(signature.to_vec().try_into().expect("64 bytes")),
+    }
+}
+
+/// Where a service's signing key is kept. Not `Debug`, as it may hold a seed or password.
+#[derive(Clone)]
+pub enum SignerConfig {
+    /// An `S...` secret seed, best kept to development
+    Secret(Zeroizing<String>),
+    /// A keystore file and its password
+    Keystore {
+        path: PathBuf,
+        password: Zeroizing<String>,
+    },
+    /// The ID, alias or ARN of an Ed25519 key in AWS KMS
+    Kms { key_id: String },
+}
+
+impl SignerConfig {
+    /// The signer `STELLAR_KMS_KEY_ID`, `STELLAR_KEYSTORE` or `STELLAR_SECRET_KEY` sets up,
+    /// if any; setting more than one of them is an error. A keystore's password is read
+    /// as `keystore::password_from_env` describes.
+    pub fn from_env() -> Result<Option<Self>, Error> {
+        let set = [KMS_KEY_ID_VAR, KEYSTORE_VAR, SECRET_KEY_VAR]
+            .into_iter()
+            .filter(|name| env::var_os(name).is_some())
+            .collect::<Vec<_>>();
+        if set.len() > 1 {
+            return Err(Error::InvalidArgument(format!(
+                "only one of {} may be set",
+                set.join(", ")
+            )));
+        }
+        Ok(match set.first().copied() {
+            Some(KMS_KEY_ID_VAR) => Some(SignerConfig::Kms {
+                key_id: env::var(KMS_KEY_ID_VAR).unwrap_or_default(),
+            }),
+            Some(KEYSTORE_VAR) => Some(SignerConfig::Keystore {
+                path: env::var_os(KEYSTORE_VAR).unwrap_or_default().into(),
+                password: keystore::password_from_env(KEYSTORE_VAR)?,
+            }),
+            Some(_) => Some(SignerConfig::Secret(Zeroizing::new(
+                env::var(SECRET_KEY_VAR).map_err(|_| {
+                    Error::InvalidArgument(format!("{SECRET_KEY_VAR} is not valid UTF-8"))
+                })?,
+            ))),
+            None => None,
+        })
+    }
+
+    /// The signer, once the seed is parsed, the keystore unlocked or the KMS key's public
+    /// key fetched
+    pub async fn load(&self) -> Result<Box<dyn Signer>, Error> {
+        Ok(match self {
+            SignerConfig::Secret(secret) => Box::new(Keypair::from_secret(secret)?),
+            SignerConfig::Keystore { path, password } => {
+                Box::new(Keystore::load(path)?.unlock(password)?)
+            }
+            SignerConfig::Kms { key_id } => Box::new(KmsSigner::from_env(key_id).await?),
+        })
+    }
+}
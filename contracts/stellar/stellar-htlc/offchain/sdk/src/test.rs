@@ -0,0 +1,661 @@
+#![cfg(test)]
+
+use crate::{
+    events::{self, ContractEvent, HtlcEvent},
+    fusion::{ChainId, CrossChainOrder, Page},
+    keystore, kms,
+    rpc::{EventInfo, SimulateTransactionResponse},
+    testutils::*,
+    transaction::{self, Invocation, BASE_FEE},
+    types::{CrossChainMetadata, DecodeError},
+    ContractError, CreateHtlc, Error, Hash32, HashAlgo, Htlc, HtlcStatus, Keypair, Keystore,
+    MerkleTree, Secret, Signer,
+};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use stellar_xdr::{
+    AccountId, FeeBumpTransactionInnerTx, Limits, OperationBody, PublicKey, ReadXdr, ScAddress,
+    ScVal, SorobanAuthorizationEntry, SorobanTransactionData, TransactionEnvelope, TransactionExt,
+    Uint256, WriteXdr,
+};
+
+fn decoded(info: &EventInfo) -> ContractEvent {
+    events::decode(info).unwrap().unwrap()
+}
+
+fn strkey(value: ScVal) -> String {
+    let ScVal::Address(address) = value else {
+        unreachable!();
+    };
+    address.to_string()
+}
+
+//------------------------------------------------------------------
+//  Event decoding
+//------------------------------------------------------------------
+#[test]
+fn decode_htlc_new() {
+    let event = decoded(&event_info("1", "HTLCNew", htlc_new_payload()));
+    assert_eq!(event.contract_id, CONTRACT);
+    assert_eq!(event.sequence, 1);
+    let HtlcEvent::New(htlc) = event.event else {
+        panic!("expected HTLCNew");
+    };
+    assert_eq!(htlc.hashlock, HASHLOCK);
+    assert_eq!(htlc.hash_algo, HashAlgo::Keccak256);
+    assert_eq!(htlc.amount, 1_000_000_000);
+    assert_eq!(
+        htlc.sender,
+        ScAddress::Account(AccountId(PublicKey::PublicKeyTypeEd25519(Uint256([1; 32]))))
+            .to_string()
+    );
+    assert!(htlc.token_address.starts_with('C'));
+    assert_eq!(htlc.metadata.evm_chain_id, 1);
+    assert_eq!(htlc.metadata.maker, "ab".repeat(20));
+    assert_eq!(htlc.memo, b"order-1");
+}
+
+#[test]
+fn decode_withdraw_and_refund() {
+    let withdraw = decoded(&event_info(
+        "2",
+        "HTLCWithdraw",
+        lifecycle(bytes(&PREIMAGE.0)),
+    ));
+    assert_eq!(withdraw.event, HtlcEvent::Withdraw { preimage: PREIMAGE });
+
+    let refund = decoded(&event_info(
+        "3",
+        "HTLCRefund",
+        lifecycle(bytes(&CONTRACT.0)),
+    ));
+    assert_eq!(refund.event, HtlcEvent::Refund);
+}
+
+#[test]
+fn decode_skips_other_events() {
+    let info = event_info("1", "HTLCFrozen", lifecycle(ScVal::Void));
+    assert_eq!(events::decode(&info), Ok(None));
+}
+
+#[test]
+fn decode_rejects_unknown_layouts() {
+    let info = event_info_versioned("1", "HTLCNew", htlc_new_payload(), 4);
+    assert_eq!(events::decode(&info), Err(DecodeError::SchemaVersion(4)));
+
+    let info = event_info("1", "HTLCNew", map(vec![("sender", account(1))]));
+    assert_eq!(
+        events::decode(&info),
+        Err(DecodeError::MissingField("metadata"))
+    );
+
+    let info = event_info("1", "HTLCWithdraw", lifecycle(bytes(&[1; 31])));
+    assert_eq!(
+        events::decode(&info),
+        Err(DecodeError::UnexpectedValue("preimage"))
+    );
+}
+
+//------------------------------------------------------------------
+//  Contract values
+//------------------------------------------------------------------
+#[test]
+fn decode_get_htlc() {
+    let htlc = Htlc::from_scval(&htlc_data_payload("Active", [0; 32])).unwrap();
+    assert_eq!(htlc.contract_id, CONTRACT);
+    assert_eq!(htlc.status, HtlcStatus::Active);
+    assert_eq!(htlc.hash_algo, HashAlgo::Keccak256);
+    assert_eq!(htlc.receiver, strkey(account(2)));
+    assert_eq!(htlc.preimage, None);
+    assert_eq!(htlc.metadata.maker, "ab".repeat(20));
+
+    let withdrawn = Htlc::from_scval(&htlc_data_payload("Withdrawn", PREIMAGE.0)).unwrap();
+    assert_eq!(withdrawn.status, HtlcStatus::Withdrawn);
+    assert_eq!(withdrawn.preimage, Some(PREIMAGE));
+    assert_eq!(withdrawn.completed_at, 1_700_000_100);
+
+    assert_eq!(
+        Htlc::from_scval(&htlc_data_payload("Frozen", [0; 32])),
+        Err(DecodeError::UnexpectedValue("status"))
+    );
+}
+
+#[test]
+fn create_params_encode_every_field() {
+    let sender = strkey(account(1));
+    let mut params = CreateHtlc::new(
+        strkey(account(2)),
+        1_000_000_000,
+        strkey(contract(3)),
+        HASHLOCK,
+        1_700_003_600,
+    );
+    params.hash_algo = HashAlgo::Keccak256;
+    params.metadata = CrossChainMetadata {
+        evm_chain_id: 1,
+        evm_order_hash: Hash32([6; 32]),
+        maker: format!("0x{}", "ab".repeat(20)),
+    };
+    let ScVal::Map(Some(fields)) = params.to_scval(&sender).unwrap() else {
+        panic!("expected a struct");
+    };
+
+    // Every `CreateParams` field, in the order the contract's encoding sorts them
+    let keys: Vec<String> = fields
+        .iter()
+        .map(|entry| format!("{:?}", entry.key))
+        .collect();
+    let mut sorted = keys.clone();
+    sorted.sort();
+    assert_eq!(keys, sorted);
+    assert_eq!(fields.len(), 33);
+    let field = |name: &str| {
+        fields
+            .iter()
+            .find(|entry| entry.key == symbol(name))
+            .map(|entry| entry.val.clone())
+            .unwrap()
+    };
+    assert_eq!(field("hash_algo"), vec(vec![symbol("Keccak256")]));
+    assert_eq!(field("taker"), account(2));
+    assert_eq!(field("safety_deposit_token"), contract(3));
+    assert_eq!(field("refund_to"), account(1));
+    assert_eq!(
+        field("metadata"),
+        map(vec![
+            ("evm_chain_id", 1u64.into()),
+            ("evm_order_hash", bytes(&[6; 32])),
+            ("maker", bytes(&[0xab; 20])),
+        ])
+    );
+
+    params.metadata.maker = "abcd".into();
+    assert!(matches!(
+        params.to_scval(&sender),
+        Err(Error::InvalidArgument(_))
+    ));
+    assert!(matches!(
+        params.to_scval("not-an-address"),
+        Err(Error::InvalidArgument(_))
+    ));
+}
+
+#[test]
+fn contract_errors_map_from_simulation() {
+    let error = Error::from_simulation(
+        "HostError: Error(Contract, #11)\n\nEvent log (newest first):\n...".into(),
+    );
+    assert!(matches!(
+        error,
+        Error::Contract(ContractError::InvalidPreimage)
+    ));
+    assert_eq!(error.to_string(), "contract error #11 InvalidPreimage");
+    assert_eq!(
+        ContractError::from_code(50),
+        Some(ContractError::InvalidThreshold)
+    );
+    assert_eq!(
+        ContractError::from_code(51),
+        Some(ContractError::MemoTooLong)
+    );
+    assert!(matches!(
+        Error::from_simulation("HostError: Error(Contract, #99)".into()),
+        Error::UnknownContractError(99)
+    ));
+    assert!(matches!(
+        Error::from_simulation("HostError: Error(Budget, ExceededLimit)".into()),
+        Error::Simulation(_)
+    ));
+}
+
+//------------------------------------------------------------------
+//  Transactions
+//------------------------------------------------------------------
+#[test]
+fn keypair_from_secret_seed() {
+    let secret =
+        stellar_strkey::Unredacted(stellar_strkey::ed25519::PrivateKey([1; 32])).to_string();
+    let keypair = Keypair::from_secret(secret.as_str()).unwrap();
+    assert_eq!(
+        keypair.public_key(),
+        Keypair::from_seed(&[1; 32]).public_key()
+    );
+    assert!(keypair.address().starts_with('G'));
+    assert_eq!(
+        keypair.address(),
+        ScAddress::Account(AccountId(PublicKey::PublicKeyTypeEd25519(Uint256(
+            keypair.public_key()
+        ))))
+        .to_string()
+    );
+    // Public keys are rejected, and the secret never shows in debug output
+    assert!(Keypair::from_secret(&keypair.address()).is_err());
+    assert!(!format!("{keypair:?}").contains(secret.as_str()));
+}
+
+fn simulation(error: Option<&str>) -> SimulateTransactionResponse {
+    let base64 = |xdr: String| serde_json::Value::String(xdr);
+    serde_json::from_value(serde_json::json!({
+        "error": error,
+        "transactionData": base64(
+            SorobanTransactionData::default()
+                .to_xdr_base64(Limits::none())
+                .unwrap()
+        ),
+        "minResourceFee": "51234",
+        "results": [{
+            "auth": [SorobanAuthorizationEntry::default().to_xdr_base64(Limits::none()).unwrap()],
+            "xdr": ScVal::Void.to_xdr_base64(Limits::none()).unwrap(),
+        }],
+        "latestLedger": 120,
+    }))
+    .unwrap()
+}
+
+#[tokio::test]
+async fn transactions_are_assembled_and_signed() {
+    let keypair = Keypair::from_seed(&[1; 32]);
+    let network_id = transaction::network_id("Test SDF Network ; September 2015");
+    let tx = transaction::build(
+        keypair.public_key(),
+        43,
+        &contract_address(8),
+        &Invocation::withdraw(CONTRACT, PREIMAGE),
+        1_700_000_300,
+    )
+    .unwrap();
+    assert_eq!(tx.fee, BASE_FEE);
+
+    let assembled = transaction::assemble(tx, &simulation(None)).unwrap();
+    assert_eq!(assembled.fee, BASE_FEE + 51_234);
+    assert!(matches!(assembled.ext, TransactionExt::V1(_)));
+    let OperationBody::InvokeHostFunction(op) = &assembled.operations[0].body else {
+        panic!("expected an invocation");
+    };
+    assert_eq!(op.auth.len(), 1);
+
+    let signed = transaction::sign(assembled, &keypair, network_id)
+        .await
+        .unwrap();
+    assert_eq!(signed.valid_until, 1_700_000_300);
+    let TransactionEnvelope::Tx(envelope) =
+        TransactionEnvelope::from_xdr_base64(&signed.envelope, Limits::none()).unwrap()
+    else {
+        panic!("expected a v1 envelope");
+    };
+    let hash = envelope.hash(network_id).unwrap();
+    assert_eq!(signed.hash, hex::encode(hash));
+    assert_eq!(envelope.tx.seq_num.0, 43);
+
+    // The signature verifies over the network-bound hash with the key's hint
+    let decorated = &envelope.signatures[0];
+    assert_eq!(decorated.hint.0, keypair.public_key()[28..]);
+    let signature = Signature::from_slice(&decorated.signature.0).unwrap();
+    VerifyingKey::from_bytes(&keypair.public_key())
+        .unwrap()
+        .verify(&hash, &signature)
+        .unwrap();
+}
+
+#[tokio::test]
+async fn fee_bumps_wrap_the_inner_transaction() {
+    let keypair = Keypair::from_seed(&[1; 32]);
+    let network_id = transaction::network_id("Test SDF Network ; September 2015");
+    let tx = transaction::build(
+        keypair.public_key(),
+        43,
+        &contract_address(8),
+        &Invocation::withdraw(CONTRACT, PREIMAGE),
+        1_700_000_300,
+    )
+    .unwrap();
+    let mut simulation = simulation(None);
+    simulation.transaction_data = Some(
+        SorobanTransactionData {
+            resource_fee: 51_234,
+            ..Default::default()
+        }
+        .to_xdr_base64(Limits::none())
+        .unwrap(),
+    );
+    let assembled = transaction::assemble(tx, &simulation).unwrap();
+    let signed = transaction::sign(assembled, &keypair, network_id)
+        .await
+        .unwrap();
+
+    let bumped = transaction::fee_bump(&signed.envelope, &keypair, 1_000, network_id)
+        .await
+        .unwrap();
+    assert_ne!(bumped.hash, signed.hash);
+    assert_eq!(bumped.valid_until, signed.valid_until);
+    let TransactionEnvelope::TxFeeBump(envelope) =
+        TransactionEnvelope::from_xdr_base64(&bumped.envelope, Limits::none()).unwrap()
+    else {
+        panic!("expected a fee bump envelope");
+    };
+    // The resource fee plus the inclusion fee of the invocation and of the bump itself
+    assert_eq!(envelope.tx.fee, 51_234 + 2 * 1_000);
+    let hash = envelope.hash(network_id).unwrap();
+    assert_eq!(bumped.hash, hex::encode(hash));
+    let FeeBumpTransactionInnerTx::Tx(inner) = &envelope.tx.inner_tx;
+    assert_eq!(inner.hash(network_id).unwrap(), hash_bytes(&signed.hash));
+
+    // Bumping again replaces the outer fee bump, keeping the same inner transaction
+    let rebumped = transaction::fee_bump(&bumped.envelope, &keypair, 10_000, network_id)
+        .await
+        .unwrap();
+    let TransactionEnvelope::TxFeeBump(envelope) =
+        TransactionEnvelope::from_xdr_base64(&rebumped.envelope, Limits::none()).unwrap()
+    else {
+        panic!("expected a fee bump envelope");
+    };
+    assert_eq!(envelope.tx.fee, 51_234 + 2 * 10_000);
+    let FeeBumpTransactionInnerTx::Tx(inner) = &envelope.tx.inner_tx;
+    assert_eq!(inner.hash(network_id).unwrap(), hash_bytes(&signed.hash));
+}
+
+fn hash_bytes(hex_hash: &str) -> [u8; 32] {
+    hex::decode(hex_hash).unwrap().try_into().unwrap()
+}
+
+#[test]
+fn failed_simulations_are_not_signed() {
+    let keypair = Keypair::from_seed(&[1; 32]);
+    let tx = transaction::build(
+        keypair.public_key(),
+        43,
+        &contract_address(8),
+        &Invocation::refund(CONTRACT),
+        1_700_000_300,
+    )
+    .unwrap();
+    assert!(matches!(
+        transaction::assemble(tx, &simulation(Some("HostError: Error(Contract, #10)"))),
+        Err(Error::Contract(ContractError::TimelockNotExpired))
+    ));
+    assert_eq!(
+        Error::from_simulation("HostError: Error(Contract, #7)".into()).contract_error(),
+        Some(ContractError::AlreadyWithdrawn)
+    );
+    assert_eq!(
+        Error::Simulation("out of budget".into()).contract_error(),
+        None
+    );
+
+    let invocation = Invocation::public_withdraw(&keypair.address(), CONTRACT, PREIMAGE).unwrap();
+    assert_eq!(invocation.function, "public_withdraw");
+    assert_eq!(invocation.args.len(), 3);
+}
+
+//------------------------------------------------------------------
+//  Signers
+//------------------------------------------------------------------
+#[tokio::test]
+async fn keystores_unlock_with_their_password() {
+    let keypair = Keypair::from_seed(&[1; 32]);
+    let keystore = Keystore::encrypt_seed(&[1; 32], "hunter2", 1_000).unwrap();
+    assert_eq!(keystore.address, Some(keypair.address()));
+    assert!(!keystore.ciphertext.contains(&hex::encode([1; 32])));
+
+    // The unlocked key signs like the seed's keypair, through the trait
+    let unlocked: Box<dyn Signer> = Box::new(keystore.unlock("hunter2").unwrap());
+    assert_eq!(unlocked.address(), keypair.address());
+    assert_eq!(
+        unlocked.sign_hash(&[7; 32]).await.unwrap(),
+        keypair.sign_hash(&[7; 32])
+    );
+
+    assert!(matches!(
+        keystore.unlock("hunter3"),
+        Err(Error::Keystore(_))
+    ));
+    let mut tampered = keystore.clone();
+    tampered.ciphertext.replace_range(..2, "00");
+    assert!(matches!(
+        tampered.decrypt("hunter2"),
+        Err(Error::Keystore(_))
+    ));
+    let mut relabeled = keystore.clone();
+    relabeled.address = Some(Keypair::from_seed(&[2; 32]).address());
+    assert!(matches!(
+        relabeled.unlock("hunter2"),
+        Err(Error::Keystore(_))
+    ));
+    assert!(Keystore::encrypt(&[1; 32], "", 1_000).is_err());
+
+    // Other keys are kept without an address
+    let evm_key = Keystore::encrypt(&[9; 32], "hunter2", 1_000).unwrap();
+    assert_eq!(evm_key.address, None);
+    assert_eq!(*evm_key.decrypt("hunter2").unwrap(), [9; 32]);
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("relayer.json");
+    keystore.save(&path).unwrap();
+    assert_eq!(Keystore::load(&path).unwrap(), keystore);
+    assert!(matches!(keystore.save(&path), Err(Error::Keystore(_))));
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    let password = dir.path().join("password");
+    std::fs::write(&password, "hunter2\n").unwrap();
+    assert_eq!(
+        keystore::read_password(&password).unwrap().as_str(),
+        "hunter2"
+    );
+}
+
+#[test]
+fn kms_requests_are_signed_with_sigv4() {
+    // Signing key example of the AWS Signature Version 4 documentation
+    assert_eq!(
+        hex::encode(kms::signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "20120215",
+            "us-east-1",
+            "iam"
+        )),
+        "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+    );
+    assert_eq!(kms::amz_date(1_440_938_160), "20150830T123600Z");
+    assert_eq!(kms::amz_date(951_868_799), "20000229T235959Z");
+
+    let mut credentials = kms::AwsCredentials {
+        access_key_id: "AKIDEXAMPLE".into(),
+        secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY"
+            .to_string()
+            .into(),
+        session_token: None,
+    };
+    let authorization = |credentials: &kms::AwsCredentials| {
+        kms::authorization(
+            credentials,
+            "us-east-1",
+            "kms.us-east-1.amazonaws.com",
+            "20150830T123600Z",
+            "TrentService.Sign",
+            br#"{"KeyId":"alias/relayer"}"#,
+        )
+    };
+    assert_eq!(
+        authorization(&credentials),
+        "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/kms/aws4_request, \
+         SignedHeaders=content-type;host;x-amz-date;x-amz-target, \
+         Signature=e3b51933d08ce82883acb96eaa6ad64f25cc8d8ab837403dec50322076589296"
+    );
+    // Temporary credentials sign their session token too
+    credentials.session_token = Some("session".into());
+    assert_eq!(
+        authorization(&credentials),
+        "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/kms/aws4_request, \
+         SignedHeaders=content-type;host;x-amz-date;x-amz-security-token;x-amz-target, \
+         Signature=85c25f910d746c00da5b63d467daa0758bc276325b26937499f1d8f975c1dc65"
+    );
+    assert!(!format!("{credentials:?}").contains("EXAMPLEKEY"));
+
+    // KMS returns public keys as DER `SubjectPublicKeyInfo`
+    let public_key = Keypair::from_seed(&[1; 32]).public_key();
+    let der = [
+        &[
+            0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00,
+        ][..],
+        &public_key,
+    ]
+    .concat();
+    assert_eq!(kms::ed25519_public_key(&der), Some(public_key));
+    assert_eq!(kms::ed25519_public_key(&der[..40]), None);
+    assert_eq!(kms::ed25519_public_key(&public_key), None);
+}
+
+//------------------------------------------------------------------
+//  Secrets
+//------------------------------------------------------------------
+#[test]
+fn secrets_hash_to_their_hashlock() {
+    let secret = Secret::from_bytes([0; 32]);
+    assert_eq!(
+        secret.hashlock(HashAlgo::Sha256).to_string(),
+        "66687aadf862bd776c8fc18b8e9f8e20089714856ee233b3902a591d0d5f2925"
+    );
+    assert_eq!(
+        secret.hashlock(HashAlgo::Keccak256).to_string(),
+        "290decd9548b62a8d60345a988386fc84ba6bc95484008f6362f93160ef3e563"
+    );
+
+    let generated = Secret::generate().unwrap();
+    assert_ne!(generated, Secret::generate().unwrap());
+    let parsed = Secret::from_hex(&format!("0x{}", generated.to_hex().as_str())).unwrap();
+    assert_eq!(parsed, generated);
+    assert_eq!(parsed.preimage().0, *generated.expose());
+    assert_eq!(format!("{generated:?}"), "Secret([REDACTED])");
+    assert!(matches!(
+        Secret::from_hex("abcd"),
+        Err(Error::InvalidArgument(_))
+    ));
+}
+
+/// The contract tests' partial-fill secrets, `[100 + i; 32]` for each of 4 parts
+fn part_secrets() -> Vec<Secret> {
+    (0..=4).map(|i| Secret::from_bytes([100 + i; 32])).collect()
+}
+
+#[test]
+fn merkle_roots_match_the_contract() {
+    // Roots the contract tests build over the same secrets
+    for (algo, root) in [
+        (
+            HashAlgo::Sha256,
+            "e643eb4b5e5f8984175d02759204aba301b3c223f7ecf6a965b257590bd1a918",
+        ),
+        (
+            HashAlgo::Keccak256,
+            "8b9ad29bd690e16d2e718e69b92a4ebf6d24c85268aac4fd257188683eefa277",
+        ),
+    ] {
+        let secrets = part_secrets();
+        let tree = MerkleTree::new(algo, &secrets).unwrap();
+        assert_eq!(tree.root().to_string(), root);
+        assert_eq!(tree.parts(), 4);
+        for (index, secret) in secrets.iter().enumerate() {
+            let proof = tree.proof(index as u32).unwrap();
+            assert!(MerkleTree::verify(
+                algo,
+                &tree.root(),
+                index as u32,
+                secret,
+                &proof
+            ));
+            // A proof only opens its own part
+            assert!(!MerkleTree::verify(
+                algo,
+                &tree.root(),
+                (index as u32 + 1) % 5,
+                secret,
+                &proof
+            ));
+        }
+        // The odd last leaf is carried up, so its proof is shorter
+        assert_eq!(tree.proof(2).unwrap().len(), 3);
+        assert_eq!(tree.proof(4).unwrap().len(), 1);
+        assert_eq!(tree.proof(5), None);
+    }
+    assert!(MerkleTree::new(HashAlgo::Sha256, &part_secrets()[..1]).is_err());
+}
+
+//------------------------------------------------------------------
+//  Fusion+ orders
+//------------------------------------------------------------------
+#[test]
+fn fusion_orders_parse_api_json() {
+    let page: Page<CrossChainOrder> = serde_json::from_value(serde_json::json!({
+        "meta": {"totalItems": 1, "itemsPerPage": 100, "totalPages": 1, "currentPage": 1},
+        "items": [{
+            "orderHash": format!("0x{}", "11".repeat(32)),
+            "quoteId": "quote",
+            "srcChainId": 137,
+            "dstChainId": "stellar",
+            "order": {
+                "salt": "42",
+                "maker": "0x00000000000000000000000000000000000000aa",
+                "receiver": "0x0000000000000000000000000000000000000000",
+                "makerAsset": "0x00000000000000000000000000000000000000bb",
+                "takerAsset": "0x00000000000000000000000000000000000000cc",
+                "makingAmount": "340282366920938463463374607431768211455",
+                "takingAmount": "1",
+                "makerTraits": "0"
+            },
+            "signature": "0x",
+            "extension": "0x",
+            "deadline": 2000,
+            "auction": {
+                "startTime": 1000,
+                "duration": 180,
+                "initialRateBump": 50000,
+                "points": [{"delay": 60, "coefficient": 20000}],
+                "gasCost": {"gasBumpEstimate": 10, "gasPriceEstimate": 1000}
+            },
+            "hashlock": "22".repeat(32),
+            "timeLocks": {
+                "srcWithdrawal": 12,
+                "srcPublicWithdrawal": 120,
+                "srcCancellation": 600,
+                "srcPublicCancellation": 720,
+                "dstWithdrawal": 10,
+                "dstPublicWithdrawal": 100,
+                "dstCancellation": 300
+            },
+            "srcSafetyDeposit": "0",
+            "dstSafetyDeposit": "0",
+            "dstToken": "CDLZFC3SYJYDZT7K67VZ75HPJVIEUVNIXF47ZG2FB2RMQQVU2HHGCYSC",
+            "dstReceiver": "GCATS5YOVB6ROX2WUNKGNQ2MP3GMXDMKSG2O4N5CLX3A6W4PZGZZI55U",
+            "remainingMakerAmount": "1"
+        }]
+    }))
+    .unwrap();
+    let order = &page.items[0];
+    assert_eq!(order.src_chain_id, ChainId::Evm(137));
+    assert_eq!(order.dst_chain_id, ChainId::Stellar);
+    assert_eq!(order.order.making_amount, u128::MAX);
+    assert_eq!(order.secret_hashes, None);
+    let details = fusion_auction::AuctionDetails::from(&order.auction);
+    assert_eq!(details.points[0].rate_bump, 20000);
+    assert_eq!(details.gas_cost.gas_price_estimate, 1000);
+
+    // Amounts go back out as strings, chains as numbers or "stellar"
+    let json = serde_json::to_value(order).unwrap();
+    assert_eq!(json["srcChainId"], 137);
+    assert_eq!(json["dstChainId"], "stellar");
+    assert_eq!(json["order"]["makingAmount"], u128::MAX.to_string());
+    assert_eq!(
+        serde_json::from_value::<CrossChainOrder>(json).unwrap(),
+        *order
+    );
+    assert!(serde_json::from_str::<ChainId>("\"solana\"").is_err());
+    // Query strings carry chain IDs as strings
+    assert_eq!(
+        serde_json::from_str::<ChainId>("\"137\"").unwrap(),
+        ChainId::Evm(137)
+    );
+}
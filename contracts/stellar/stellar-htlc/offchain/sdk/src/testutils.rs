@@ -0,0 +1,166 @@
+#![cfg(any(test, feature = "testutils"))]
+//! Fixtures shared by this crate's tests and by crates testing against contract events:
+//! `ScVal` builders shaped like the contract's encoding and RPC events wrapping them.
+
+use crate::{events, rpc::EventInfo, types::Hash32};
+use stellar_xdr::{
+    AccountId, ContractId, Hash, Limits, PublicKey, ScAddress, ScBytes, ScMap, ScMapEntry,
+    ScSymbol, ScVal, ScVec, Uint256, WriteXdr,
+};
+
+/// Contract ID of the HTLC the fixtures describe
+pub const CONTRACT: Hash32 = Hash32([7; 32]);
+pub const HASHLOCK: Hash32 = Hash32([9; 32]);
+pub const PREIMAGE: Hash32 = Hash32([42; 32]);
+
+pub fn symbol(name: &str) -> ScVal {
+    ScVal::Symbol(ScSymbol(name.try_into().unwrap()))
+}
+
+pub fn bytes(value: &[u8]) -> ScVal {
+    ScVal::Bytes(ScBytes(value.try_into().unwrap()))
+}
+
+pub fn account(byte: u8) -> ScVal {
+    ScVal::Address(ScAddress::Account(AccountId(
+        PublicKey::PublicKeyTypeEd25519(Uint256([byte; 32])),
+    )))
+}
+
+pub fn contract(byte: u8) -> ScVal {
+    ScVal::Address(contract_address(byte))
+}
+
+pub fn contract_address(byte: u8) -> ScAddress {
+    ScAddress::Contract(ContractId(Hash([byte; 32])))
+}
+
+pub fn vec(items: Vec<ScVal>) -> ScVal {
+    ScVal::Vec(Some(ScVec(items.try_into().unwrap())))
+}
+
+/// A contracttype struct; fields are sorted by name as the SDK encodes them
+pub fn map(mut fields: Vec<(&str, ScVal)>) -> ScVal {
+    fields.sort_by(|a, b| a.0.cmp(b.0));
+    let entries: Vec<ScMapEntry> = fields
+        .into_iter()
+        .map(|(key, val)| ScMapEntry {
+            key: symbol(key),
+            val,
+        })
+        .collect();
+    ScVal::Map(Some(ScMap(entries.try_into().unwrap())))
+}
+
+pub fn htlc_new_payload() -> ScVal {
+    map(vec![
+        ("sender", account(1)),
+        ("receiver", account(2)),
+        ("amount", 1_000_000_000i128.into()),
+        ("token_address", contract(3)),
+        ("hashlock", bytes(&HASHLOCK.0)),
+        ("hash_algo", vec(vec![symbol("Keccak256")])),
+        ("timelock", 1_700_003_600u64.into()),
+        ("timestamp", 1_700_000_000u64.into()),
+        ("created_ledger", 100u32.into()),
+        ("safety_deposit", 100_000_000i128.into()),
+        ("safety_deposit_token", contract(3)),
+        ("parts", 0u32.into()),
+        ("public_withdrawal", 0u64.into()),
+        ("public_cancellation", 0u64.into()),
+        ("order_hash", bytes(&[5; 32])),
+        ("resolvers_only", false.into()),
+        (
+            "metadata",
+            map(vec![
+                ("evm_chain_id", 1u64.into()),
+                ("evm_order_hash", bytes(&[6; 32])),
+                ("maker", bytes(&[0xab; 20])),
+            ]),
+        ),
+        ("taker", account(2)),
+        ("memo", bytes(b"order-1")),
+    ])
+}
+
+/// An RPC event of the HTLC contract, with data wrapped as the contract publishes it
+pub fn event_info(id: &str, name: &str, payload: ScVal) -> EventInfo {
+    event_info_versioned(id, name, payload, events::EVENT_SCHEMA_VERSION)
+}
+
+pub fn event_info_versioned(id: &str, name: &str, payload: ScVal, version: u32) -> EventInfo {
+    let base64 = |value: ScVal| value.to_xdr_base64(Limits::none()).unwrap();
+    EventInfo {
+        id: id.into(),
+        ledger: 100,
+        ledger_closed_at: "2024-01-01T00:00:00Z".into(),
+        contract_id: contract_address(8).to_string(),
+        tx_hash: "ab".repeat(32),
+        topic: vec![base64(symbol(name)), base64(bytes(&CONTRACT.0))],
+        value: base64(vec(vec![version.into(), 1u64.into(), payload])),
+    }
+}
+
+pub fn lifecycle(data: ScVal) -> ScVal {
+    vec(vec![data, bytes(b"order-1"), 100u32.into()])
+}
+
+/// `HTLCData` of the HTLC `htlc_new_payload` creates, as `get_htlc` returns it
+pub fn htlc_data_payload(status: &str, preimage: [u8; 32]) -> ScVal {
+    let completed = status != "Active";
+    map(vec![
+        ("contract_id", bytes(&CONTRACT.0)),
+        ("sender", account(1)),
+        ("receiver", account(2)),
+        ("amount", 1_000_000_000i128.into()),
+        ("token_address", contract(3)),
+        ("hashlock", bytes(&HASHLOCK.0)),
+        ("hash_algo", vec(vec![symbol("Keccak256")])),
+        ("timelock", 1_700_003_600u64.into()),
+        ("timestamp", 1_700_000_000u64.into()),
+        ("created_ledger", 100u32.into()),
+        ("safety_deposit", 100_000_000i128.into()),
+        ("safety_deposit_token", contract(3)),
+        ("status", vec(vec![symbol(status)])),
+        ("parts", 0u32.into()),
+        ("filled_amount", 0i128.into()),
+        ("next_fill_index", 0u32.into()),
+        ("public_withdrawal", 0u64.into()),
+        ("public_cancellation", 0u64.into()),
+        ("order_hash", bytes(&[5; 32])),
+        ("resolvers_only", false.into()),
+        (
+            "completed_at",
+            if completed { 1_700_000_100u64 } else { 0 }.into(),
+        ),
+        ("preimage", bytes(&preimage)),
+        (
+            "metadata",
+            map(vec![
+                ("evm_chain_id", 1u64.into()),
+                ("evm_order_hash", bytes(&[6; 32])),
+                ("maker", bytes(&[0xab; 20])),
+            ]),
+        ),
+        ("basket", vec(vec![])),
+        ("safety_deposit_payer", account(1)),
+        ("salt", bytes(&[0; 32])),
+        ("taker", account(2)),
+        ("hashlocks", vec(vec![])),
+        ("threshold", 0u32.into()),
+        ("callback", account(1)),
+        ("callback_data", bytes(&[])),
+        ("extension", bytes(&[])),
+        ("rescue_delay", 0u64.into()),
+        ("frozen_until", 0u64.into()),
+        ("refund_to", account(1)),
+        ("commit_reveal", false.into()),
+        ("memo", bytes(b"order-1")),
+        ("referral", account(1)),
+        ("referral_bps", 0u32.into()),
+        (
+            "vesting",
+            map(vec![("tranches", 0u32.into()), ("interval", 0u64.into())]),
+        ),
+    ])
+}
@@ -0,0 +1,304 @@
+//! Assembly and signing of contract invocations as Soroban transactions
+//!
+//! A transaction is built around one `InvokeHostFunction` operation, simulated to learn
+//! its footprint, resource fee and authorization entries, then assembled and signed. The
+//! signer is the transaction source, so its own authorizations need no extra signature.
+
+use crate::{
+    rpc::SimulateTransactionResponse,
+    scval::{address_val, hash_val},
+    signer::Signer,
+    types::{CreateHtlc, Hash32},
+    Error,
+};
+use sha2::{Digest, Sha256};
+use std::time::{SystemTime, UNIX_EPOCH};
+use stellar_xdr::{
+    DecoratedSignature, FeeBumpTransaction, FeeBumpTransactionEnvelope, FeeBumpTransactionExt,
+    FeeBumpTransactionInnerTx, HostFunction, InvokeContractArgs, InvokeHostFunctionOp, Limits,
+    Memo, MuxedAccount, Operation, OperationBody, Preconditions, ReadXdr, ScAddress, ScSymbol,
+    ScVal, SequenceNumber, SorobanAuthorizationEntry, SorobanTransactionData, TimeBounds,
+    TimePoint, Transaction, TransactionEnvelope, TransactionExt, TransactionV1Envelope, Uint256,
+    VecM, WriteXdr,
+};
+
+/// Inclusion fee offered on top of the simulated resource fee, in stroops
+pub const BASE_FEE: u32 = 100;
+
+/// Seconds a signed transaction stays valid; past it the network drops it for good
+pub const TX_VALIDITY_SECS: u64 = 300;
+
+/// Factor a fee bump must raise the fee rate of the pending transaction it replaces by
+pub const FEE_BUMP_MULTIPLIER: i64 = 10;
+
+/// A call of a contract function
+#[derive(Clone, Debug, PartialEq)]
+pub struct Invocation {
+    pub function: String,
+    pub args: Vec<ScVal>,
+}
+
+impl Invocation {
+    pub fn new(function: impl Into<String>, args: Vec<ScVal>) -> Self {
+        Invocation {
+            function: function.into(),
+            args,
+        }
+    }
+
+    /// `create_htlc_with_params`, funded by `sender`
+    pub fn create_htlc(sender: &str, params: &CreateHtlc) -> Result<Self, Error> {
+        Ok(Invocation::new(
+            "create_htlc_with_params",
+            vec![params.to_scval(sender)?],
+        ))
+    }
+
+    pub fn withdraw(contract_id: Hash32, preimage: Hash32) -> Self {
+        Invocation::new("withdraw", vec![hash_val(contract_id), hash_val(preimage)])
+    }
+
+    /// `public_withdraw`, paying the safety deposit to `caller`
+    pub fn public_withdraw(
+        caller: &str,
+        contract_id: Hash32,
+        preimage: Hash32,
+    ) -> Result<Self, Error> {
+        Ok(Invocation::new(
+            "public_withdraw",
+            vec![
+                address_val(caller)?,
+                hash_val(contract_id),
+                hash_val(preimage),
+            ],
+        ))
+    }
+
+    pub fn refund(contract_id: Hash32) -> Self {
+        Invocation::new("refund", vec![hash_val(contract_id)])
+    }
+
+    pub fn get_htlc(contract_id: Hash32) -> Self {
+        Invocation::new("get_htlc", vec![hash_val(contract_id)])
+    }
+
+    /// `get_htlcs_by_sender`, a page of the contract IDs `sender` created
+    pub fn htlcs_by_sender(sender: &str, offset: u32, limit: u32) -> Result<Self, Error> {
+        Ok(Invocation::new(
+            "get_htlcs_by_sender",
+            vec![address_val(sender)?, offset.into(), limit.into()],
+        ))
+    }
+
+    /// `get_htlcs_by_receiver`, a page of the contract IDs `receiver` may claim
+    pub fn htlcs_by_receiver(
+        receiver: &str,
+        offset: u32,
+        limit: u32,
+        active_only: bool,
+    ) -> Result<Self, Error> {
+        Ok(Invocation::new(
+            "get_htlcs_by_receiver",
+            vec![
+                address_val(receiver)?,
+                offset.into(),
+                limit.into(),
+                active_only.into(),
+            ],
+        ))
+    }
+}
+
+/// A signed transaction, kept verbatim so that it can be rebroadcast as is
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignedTransaction {
+    /// Hex transaction hash
+    pub hash: String,
+    /// Base64 XDR `TransactionEnvelope`
+    pub envelope: String,
+    /// Upper time bound, after which the transaction can no longer be included
+    pub valid_until: u64,
+}
+
+/// Network ID a passphrase stands for, as signed over in transaction hashes
+pub fn network_id(passphrase: &str) -> [u8; 32] {
+    Sha256::digest(passphrase.as_bytes()).into()
+}
+
+/// Unsigned transaction invoking `contract` from `source`, before simulation
+pub fn build(
+    source: [u8; 32],
+    sequence: i64,
+    contract: &ScAddress,
+    invocation: &Invocation,
+    valid_until: u64,
+) -> Result<Transaction, Error> {
+    let host_function = HostFunction::InvokeContract(InvokeContractArgs {
+        contract_address: contract.clone(),
+        function_name: ScSymbol(invocation.function.as_str().try_into()?),
+        args: invocation.args.clone().try_into()?,
+    });
+    Ok(Transaction {
+        source_account: MuxedAccount::Ed25519(Uint256(source)),
+        fee: BASE_FEE,
+        seq_num: SequenceNumber(sequence),
+        cond: Preconditions::Time(TimeBounds {
+            min_time: TimePoint(0),
+            max_time: TimePoint(valid_until),
+        }),
+        memo: Memo::None,
+        operations: operations(host_function, VecM::default())?,
+        ext: TransactionExt::V0,
+    })
+}
+
+fn operations(
+    host_function: HostFunction,
+    auth: VecM<SorobanAuthorizationEntry>,
+) -> Result<VecM<Operation, 100>, Error> {
+    Ok(vec![Operation {
+        source_account: None,
+        body: OperationBody::InvokeHostFunction(InvokeHostFunctionOp {
+            host_function,
+            auth,
+        }),
+    }]
+    .try_into()?)
+}
+
+/// Applies a simulation to `tx`: footprint and resources, resource fee and the
+/// authorization entries of the invocation
+pub fn assemble(
+    mut tx: Transaction,
+    simulation: &SimulateTransactionResponse,
+) -> Result<Transaction, Error> {
+    if let Some(error) = &simulation.error {
+        return Err(Error::from_simulation(error.clone()));
+    }
+    let (Some(data), Some(resource_fee)) =
+        (&simulation.transaction_data, &simulation.min_resource_fee)
+    else {
+        return Err(Error::Simulation(
+            "no transaction data in simulation".into(),
+        ));
+    };
+    let resource_fee: u32 = resource_fee
+        .parse()
+        .map_err(|_| Error::Simulation(format!("invalid resource fee {resource_fee}")))?;
+    let auth = simulation
+        .results
+        .first()
+        .map(|result| {
+            result
+                .auth
+                .iter()
+                .map(|entry| SorobanAuthorizationEntry::from_xdr_base64(entry, Limits::none()))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()?
+        .unwrap_or_default();
+    let OperationBody::InvokeHostFunction(op) = &tx.operations[0].body else {
+        return Err(Error::InvalidArgument("not a contract invocation".into()));
+    };
+
+    tx.operations = operations(op.host_function.clone(), auth.try_into()?)?;
+    tx.fee = BASE_FEE + resource_fee;
+    tx.ext = TransactionExt::V1(SorobanTransactionData::from_xdr_base64(
+        data,
+        Limits::none(),
+    )?);
+    Ok(tx)
+}
+
+/// Base64 XDR envelope of `tx` with `signatures`
+pub fn envelope(tx: Transaction, signatures: Vec<DecoratedSignature>) -> Result<String, Error> {
+    Ok(TransactionEnvelope::Tx(TransactionV1Envelope {
+        tx,
+        signatures: signatures.try_into()?,
+    })
+    .to_xdr_base64(Limits::none())?)
+}
+
+/// Signs `tx` for the network of `network_id`
+pub async fn sign(
+    tx: Transaction,
+    signer: &dyn Signer,
+    network_id: [u8; 32],
+) -> Result<SignedTransaction, Error> {
+    let valid_until = match &tx.cond {
+        Preconditions::Time(bounds) => bounds.max_time.0,
+        _ => u64::MAX,
+    };
+    let hash = TransactionEnvelope::Tx(TransactionV1Envelope {
+        tx: tx.clone(),
+        signatures: VecM::default(),
+    })
+    .hash(network_id)?;
+    Ok(SignedTransaction {
+        hash: hex::encode(hash),
+        envelope: envelope(tx, vec![signer.sign_hash(&hash).await?])?,
+        valid_until,
+    })
+}
+
+/// Wraps the signed transaction `envelope` in a fee bump paid by `fee_source`, offering
+/// `inclusion_fee` stroops per operation on top of the inner transaction's resource fee.
+/// The inner transaction of a fee bump is wrapped again rather than the fee bump itself.
+/// Every wrapping shares the inner transaction's sequence number, so at most one of them is
+/// applied.
+pub async fn fee_bump(
+    envelope: &str,
+    fee_source: &dyn Signer,
+    inclusion_fee: i64,
+    network_id: [u8; 32],
+) -> Result<SignedTransaction, Error> {
+    let inner = match TransactionEnvelope::from_xdr_base64(envelope, Limits::none())? {
+        TransactionEnvelope::Tx(inner) => inner,
+        TransactionEnvelope::TxFeeBump(bump) => match bump.tx.inner_tx {
+            FeeBumpTransactionInnerTx::Tx(inner) => inner,
+        },
+        TransactionEnvelope::TxV0(_) => {
+            return Err(Error::InvalidArgument(
+                "v0 transactions cannot be fee-bumped".into(),
+            ))
+        }
+    };
+    let resource_fee = match &inner.tx.ext {
+        TransactionExt::V1(data) => data.resource_fee,
+        TransactionExt::V0 => 0,
+    };
+    let valid_until = match &inner.tx.cond {
+        Preconditions::Time(bounds) => bounds.max_time.0,
+        _ => u64::MAX,
+    };
+    // The fee bump counts as one more operation
+    let operations = inner.tx.operations.len() as i64 + 1;
+    let tx = FeeBumpTransaction {
+        fee_source: MuxedAccount::Ed25519(Uint256(fee_source.public_key())),
+        fee: resource_fee + inclusion_fee * operations,
+        inner_tx: FeeBumpTransactionInnerTx::Tx(inner),
+        ext: FeeBumpTransactionExt::V0,
+    };
+    let hash = TransactionEnvelope::TxFeeBump(FeeBumpTransactionEnvelope {
+        tx: tx.clone(),
+        signatures: VecM::default(),
+    })
+    .hash(network_id)?;
+    let envelope = TransactionEnvelope::TxFeeBump(FeeBumpTransactionEnvelope {
+        tx,
+        signatures: vec![fee_source.sign_hash(&hash).await?].try_into()?,
+    })
+    .to_xdr_base64(Limits::none())?;
+    Ok(SignedTransaction {
+        hash: hex::encode(hash),
+        envelope,
+        valid_until,
+    })
+}
+
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("clock after 1970")
+        .as_secs()
+}
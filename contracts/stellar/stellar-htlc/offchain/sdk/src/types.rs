@@ -0,0 +1,285 @@
+//! Values exchanged with the HTLC contract, in their Rust form
+
+use crate::{scval, Error};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use stellar_xdr::ScVal;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum DecodeError {
+    #[error("invalid XDR: {0}")]
+    Xdr(String),
+    #[error("unsupported event schema version {0}")]
+    SchemaVersion(u32),
+    #[error("missing field `{0}`")]
+    MissingField(&'static str),
+    #[error("unexpected value for `{0}`")]
+    UnexpectedValue(&'static str),
+}
+
+/// A 32-byte hash, hashlock or contract ID, serialized as hex
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Hash32(pub [u8; 32]);
+
+impl fmt::Display for Hash32 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex::encode(self.0))
+    }
+}
+
+impl std::str::FromStr for Hash32 {
+    type Err = hex::FromHexError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut bytes = [0; 32];
+        hex::decode_to_slice(s.trim_start_matches("0x"), &mut bytes)?;
+        Ok(Hash32(bytes))
+    }
+}
+
+impl Serialize for Hash32 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Hash32 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Hash function an HTLC validates its secret with
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HashAlgo {
+    Sha256,
+    Keccak256,
+}
+
+/// EVM side of a swap as recorded at creation; `evm_chain_id` 0 means unset
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrossChainMetadata {
+    pub evm_chain_id: u64,
+    pub evm_order_hash: Hash32,
+    /// Maker's EVM address, hex encoded
+    pub maker: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HtlcStatus {
+    Active,
+    Withdrawn,
+    Refunded,
+}
+
+/// An HTLC as returned by `get_htlc`, limited to the fields needed to operate a swap
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Htlc {
+    pub contract_id: Hash32,
+    pub sender: String,
+    pub receiver: String,
+    pub amount: i128,
+    pub token_address: String,
+    pub hashlock: Hash32,
+    pub hash_algo: HashAlgo,
+    pub timelock: u64,
+    pub timestamp: u64,
+    pub created_ledger: u32,
+    pub safety_deposit: i128,
+    pub safety_deposit_token: String,
+    pub status: HtlcStatus,
+    pub parts: u32,
+    pub filled_amount: i128,
+    pub public_withdrawal: u64,
+    pub public_cancellation: u64,
+    pub order_hash: Hash32,
+    /// Timestamp of the withdrawal or refund, 0 while active
+    pub completed_at: u64,
+    /// Revealed preimage, the latest one for partial fills
+    pub preimage: Option<Hash32>,
+    pub taker: String,
+    pub metadata: CrossChainMetadata,
+    pub memo: Vec<u8>,
+}
+
+impl Htlc {
+    /// Decodes the `HTLCData` struct the contract returns
+    pub fn from_scval(value: &ScVal) -> Result<Self, DecodeError> {
+        use scval::*;
+        let map = map_of(value, "htlc")?;
+        let preimage = hash(field(map, "preimage")?, "preimage")?;
+        Ok(Htlc {
+            contract_id: hash(field(map, "contract_id")?, "contract_id")?,
+            sender: address(field(map, "sender")?, "sender")?,
+            receiver: address(field(map, "receiver")?, "receiver")?,
+            amount: i128_of(field(map, "amount")?, "amount")?,
+            token_address: address(field(map, "token_address")?, "token_address")?,
+            hashlock: hash(field(map, "hashlock")?, "hashlock")?,
+            hash_algo: hash_algo(field(map, "hash_algo")?)?,
+            timelock: u64_of(field(map, "timelock")?, "timelock")?,
+            timestamp: u64_of(field(map, "timestamp")?, "timestamp")?,
+            created_ledger: u32_of(field(map, "created_ledger")?, "created_ledger")?,
+            safety_deposit: i128_of(field(map, "safety_deposit")?, "safety_deposit")?,
+            safety_deposit_token: address(
+                field(map, "safety_deposit_token")?,
+                "safety_deposit_token",
+            )?,
+            status: match unit_variant(field(map, "status")?, "status")?.as_str() {
+                "Active" => HtlcStatus::Active,
+                "Withdrawn" => HtlcStatus::Withdrawn,
+                "Refunded" => HtlcStatus::Refunded,
+                _ => return Err(DecodeError::UnexpectedValue("status")),
+            },
+            parts: u32_of(field(map, "parts")?, "parts")?,
+            filled_amount: i128_of(field(map, "filled_amount")?, "filled_amount")?,
+            public_withdrawal: u64_of(field(map, "public_withdrawal")?, "public_withdrawal")?,
+            public_cancellation: u64_of(field(map, "public_cancellation")?, "public_cancellation")?,
+            order_hash: hash(field(map, "order_hash")?, "order_hash")?,
+            completed_at: u64_of(field(map, "completed_at")?, "completed_at")?,
+            preimage: (preimage != Hash32::default()).then_some(preimage),
+            taker: address(field(map, "taker")?, "taker")?,
+            metadata: metadata(field(map, "metadata")?)?,
+            memo: bytes(field(map, "memo")?, "memo")?,
+        })
+    }
+}
+
+/// Parameters of a new HTLC funded by the signer. Fields left out default as in the
+/// contract's `create_htlc`: no basket, referral, callback, vesting or rescue delay, and
+/// the sender pays the safety deposit and receives refunds.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CreateHtlc {
+    /// Strkey of the receiver
+    pub receiver: String,
+    pub amount: i128,
+    /// Strkey of the token contract
+    pub token_address: String,
+    /// Hashlock, or the Merkle root of the parts' secrets when `parts > 0`
+    pub hashlock: Hash32,
+    pub hash_algo: HashAlgo,
+    pub timelock: u64,
+    pub safety_deposit: i128,
+    /// Token of the safety deposit; `token_address` if `None`
+    pub safety_deposit_token: Option<String>,
+    pub parts: u32,
+    /// 0 disables public withdrawal
+    pub public_withdrawal: u64,
+    /// 0 disables public cancellation
+    pub public_cancellation: u64,
+    /// Used as the contract ID when set; all zeroes derives it instead
+    pub order_hash: Hash32,
+    /// Only address allowed to withdraw during the exclusive window; the receiver if `None`
+    pub taker: Option<String>,
+    pub metadata: CrossChainMetadata,
+    pub memo: Vec<u8>,
+    /// Accept a hashlock already guarding another active HTLC
+    pub allow_reuse: bool,
+}
+
+impl CreateHtlc {
+    /// A single-secret SHA-256 HTLC without a safety deposit
+    pub fn new(
+        receiver: impl Into<String>,
+        amount: i128,
+        token_address: impl Into<String>,
+        hashlock: Hash32,
+        timelock: u64,
+    ) -> Self {
+        CreateHtlc {
+            receiver: receiver.into(),
+            amount,
+            token_address: token_address.into(),
+            hashlock,
+            hash_algo: HashAlgo::Sha256,
+            timelock,
+            safety_deposit: 0,
+            safety_deposit_token: None,
+            parts: 0,
+            public_withdrawal: 0,
+            public_cancellation: 0,
+            order_hash: Hash32::default(),
+            taker: None,
+            metadata: CrossChainMetadata::default(),
+            memo: Vec::new(),
+            allow_reuse: false,
+        }
+    }
+
+    /// Encodes the `CreateParams` struct of `create_htlc_with_params` for `sender`
+    pub fn to_scval(&self, sender: &str) -> Result<ScVal, Error> {
+        use scval::{address_val, bytes_val, struct_val, symbol_val, vec_val};
+        let sender = address_val(sender)?;
+        let receiver = address_val(&self.receiver)?;
+        let token = address_val(&self.token_address)?;
+        let maker = match self.metadata.maker.as_str() {
+            "" => vec![0; 20],
+            maker => hex::decode(maker.trim_start_matches("0x"))
+                .ok()
+                .filter(|maker| maker.len() == 20)
+                .ok_or_else(|| Error::InvalidArgument(format!("invalid EVM maker {maker}")))?,
+        };
+        let hash_algo = match self.hash_algo {
+            HashAlgo::Sha256 => "Sha256",
+            HashAlgo::Keccak256 => "Keccak256",
+        };
+        Ok(struct_val(vec![
+            ("sender", sender.clone()),
+            ("receiver", receiver.clone()),
+            ("amount", self.amount.into()),
+            ("token_address", token.clone()),
+            ("hashlock", bytes_val(&self.hashlock.0)),
+            ("hash_algo", vec_val(vec![symbol_val(hash_algo)])),
+            ("timelock", self.timelock.into()),
+            ("safety_deposit", self.safety_deposit.into()),
+            (
+                "safety_deposit_token",
+                match &self.safety_deposit_token {
+                    Some(deposit_token) => address_val(deposit_token)?,
+                    None => token,
+                },
+            ),
+            ("parts", self.parts.into()),
+            ("public_withdrawal", self.public_withdrawal.into()),
+            ("public_cancellation", self.public_cancellation.into()),
+            ("order_hash", bytes_val(&self.order_hash.0)),
+            ("resolvers_only", false.into()),
+            (
+                "metadata",
+                struct_val(vec![
+                    ("evm_chain_id", self.metadata.evm_chain_id.into()),
+                    ("evm_order_hash", bytes_val(&self.metadata.evm_order_hash.0)),
+                    ("maker", bytes_val(&maker)),
+                ]),
+            ),
+            ("basket", vec_val(vec![])),
+            ("safety_deposit_payer", sender.clone()),
+            ("salt", bytes_val(&[0; 32])),
+            ("allow_reuse", self.allow_reuse.into()),
+            (
+                "taker",
+                match &self.taker {
+                    Some(taker) => address_val(taker)?,
+                    None => receiver,
+                },
+            ),
+            ("hashlocks", vec_val(vec![])),
+            ("threshold", 0u32.into()),
+            ("callback", sender.clone()),
+            ("callback_data", bytes_val(&[])),
+            ("extension", bytes_val(&[])),
+            ("rescue_delay", 0u64.into()),
+            ("refund_to", sender.clone()),
+            ("commit_reveal", false.into()),
+            ("memo", bytes_val(&self.memo)),
+            ("referral", sender),
+            ("referral_bps", 0u32.into()),
+            ("use_deposit_policy", false.into()),
+            (
+                "vesting",
+                struct_val(vec![("tranches", 0u32.into()), ("interval", 0u64.into())]),
+            ),
+        ]))
+    }
+}